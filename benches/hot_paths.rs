@@ -0,0 +1,169 @@
+//! Benchmarks for color conversion, resizing, WebM muxing and small slideshow encodes
+//!
+//! Run with `cargo bench`; pass a filter such as `cargo bench -- color` to run one group.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use image::{Rgba, RgbaImage};
+use minmpeg::encoder::color::{rgba_to_yuv420, Matrix, PlaneMut};
+use minmpeg::encoder::Packet;
+use minmpeg::image_loader::LoadedImage;
+use minmpeg::muxer::{create_muxer_with_output, MuxerConfig};
+use minmpeg::{
+    slideshow_to_memory, Codec, ColorSpace, Container, EncodeOptions, PixelRange, SlideEntry,
+    SlideSource,
+};
+use std::hint::black_box;
+use std::io::Cursor;
+use tempfile::TempDir;
+
+/// Gradient RGBA frame, so conversions and encoders see varied pixels
+fn gradient(width: u32, height: u32) -> Vec<u8> {
+    RgbaImage::from_fn(width, height, |x, y| {
+        Rgba([(x % 256) as u8, (y % 256) as u8, ((x + y) % 256) as u8, 255])
+    })
+    .into_raw()
+}
+
+fn color_conversion(c: &mut Criterion) {
+    let mut group = c.benchmark_group("color/rgba_to_yuv420");
+
+    for (width, height) in [(1280, 720), (1920, 1080), (3840, 2160)] {
+        let rgba = gradient(width, height);
+        let (w, h) = (width as usize, height as usize);
+        let (uv_w, uv_h) = (w.div_ceil(2), h.div_ceil(2));
+        let mut y = vec![0u8; w * h];
+        let mut u = vec![0u8; uv_w * uv_h];
+        let mut v = vec![0u8; uv_w * uv_h];
+
+        group.throughput(Throughput::Elements(w as u64 * h as u64));
+        group.bench_function(
+            BenchmarkId::from_parameter(format!("{}x{}", width, height)),
+            |b| {
+                b.iter(|| {
+                    rgba_to_yuv420(
+                        black_box(&rgba),
+                        w,
+                        h,
+                        Matrix::new(ColorSpace::Bt601, false),
+                        PlaneMut {
+                            data: &mut y,
+                            stride: w,
+                        },
+                        PlaneMut {
+                            data: &mut u,
+                            stride: uv_w,
+                        },
+                        PlaneMut {
+                            data: &mut v,
+                            stride: uv_w,
+                        },
+                    )
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn resize(c: &mut Criterion) {
+    let image = LoadedImage::from_rgba(1920, 1080, gradient(1920, 1080)).unwrap();
+    let mut group = c.benchmark_group("resize");
+
+    group.bench_function("1920x1080_to_1280x720", |b| {
+        b.iter(|| black_box(&image).resize(1280, 720).unwrap())
+    });
+    group.bench_function("1920x1080_fit_1080x1080", |b| {
+        b.iter(|| {
+            black_box(&image)
+                .resize_fit(1080, 1080, [0, 0, 0, 255])
+                .unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+fn webm_muxing(c: &mut Criterion) {
+    // 10 seconds at 30 fps with a keyframe every second
+    let packets: Vec<Packet> = (0..300)
+        .map(|i| Packet {
+            data: vec![(i % 256) as u8; 8 * 1024],
+            pts: i,
+            dts: i,
+            is_keyframe: i % 30 == 0,
+        })
+        .collect();
+    let config = MuxerConfig {
+        width: 1280,
+        height: 720,
+        fps: 30,
+        codec: Codec::Av1,
+        codec_config: None,
+        pps: None,
+        color_space: ColorSpace::Bt601,
+        pixel_range: PixelRange::Auto,
+    };
+
+    let mut group = c.benchmark_group("mux");
+    group.throughput(Throughput::Bytes(
+        packets.iter().map(|p| p.data.len() as u64).sum(),
+    ));
+    group.bench_function("webm_300_packets", |b| {
+        b.iter_batched(
+            || Box::new(Cursor::new(Vec::with_capacity(4 << 20))),
+            |output| {
+                let mut muxer =
+                    create_muxer_with_output(Container::WebM, output, config.clone()).unwrap();
+                for packet in &packets {
+                    muxer.write_packet(packet).unwrap();
+                }
+                muxer.finalize().unwrap();
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+fn slideshow_encode(c: &mut Criterion) {
+    let temp_dir = TempDir::new().unwrap();
+    let entries: Vec<SlideEntry> = (0..2)
+        .map(|i| {
+            let path = temp_dir.path().join(format!("slide_{}.png", i));
+            let mut image = RgbaImage::from_raw(160, 120, gradient(160, 120)).unwrap();
+            image.put_pixel(i, i, Rgba([255, 0, 0, 255]));
+            image.save(&path).unwrap();
+            SlideEntry {
+                source: SlideSource::Path(path.to_string_lossy().to_string()),
+                duration_ms: 200,
+                transition: None,
+                letterbox_color: None,
+                caption: None,
+                rotation: None,
+            }
+        })
+        .collect();
+    let options = EncodeOptions {
+        container: Container::WebM,
+        codec: Codec::Av1,
+        ..Default::default()
+    };
+
+    let mut group = c.benchmark_group("slideshow");
+    group.sample_size(10);
+    group.bench_function("av1_webm_160x120_2_slides", |b| {
+        b.iter(|| slideshow_to_memory(black_box(&entries), &options).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    color_conversion,
+    resize,
+    webm_muxing,
+    slideshow_encode
+);
+criterion_main!(benches);
@@ -0,0 +1,5 @@
+fn main() {
+    // Node.js addons resolve N-API symbols from the host process
+    #[cfg(feature = "napi")]
+    napi_build::setup();
+}
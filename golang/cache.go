@@ -0,0 +1,37 @@
+package minmpeg
+
+/*
+#include "../include/minmpeg.h"
+*/
+import "C"
+import "runtime"
+
+// ImageCache keeps decoded and resized slide images for reuse across slides and operations
+type ImageCache struct {
+	ptr *C.ImageCache
+}
+
+// NewImageCache creates an empty image cache
+func NewImageCache() *ImageCache {
+	c := &ImageCache{ptr: C.minmpeg_image_cache_new()}
+	runtime.SetFinalizer(c, (*ImageCache).Free)
+	return c
+}
+
+// Free drops the cached images and releases the cache. Running operations keep their own reference.
+func (c *ImageCache) Free() {
+	if c == nil || c.ptr == nil {
+		return
+	}
+	C.minmpeg_image_cache_free(c.ptr)
+	c.ptr = nil
+	runtime.SetFinalizer(c, nil)
+}
+
+// cPtr returns the C handle, or nil for a nil cache
+func (c *ImageCache) cPtr() *C.ImageCache {
+	if c == nil {
+		return nil
+	}
+	return c.ptr
+}
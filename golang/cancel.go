@@ -0,0 +1,45 @@
+package minmpeg
+
+/*
+#include "../include/minmpeg.h"
+*/
+import "C"
+import "runtime"
+
+// CancelToken cancels running Slideshow/Juxtapose calls from another goroutine
+type CancelToken struct {
+	ptr *C.CancelToken
+}
+
+// NewCancelToken creates a new cancellation token
+func NewCancelToken() *CancelToken {
+	t := &CancelToken{ptr: C.minmpeg_cancel_token_new()}
+	runtime.SetFinalizer(t, (*CancelToken).Free)
+	return t
+}
+
+// Cancel requests cancellation of every operation using this token
+func (t *CancelToken) Cancel() {
+	if t == nil || t.ptr == nil {
+		return
+	}
+	C.minmpeg_cancel_token_cancel(t.ptr)
+}
+
+// Free releases the token. It must not be called while an operation using the token is running.
+func (t *CancelToken) Free() {
+	if t == nil || t.ptr == nil {
+		return
+	}
+	C.minmpeg_cancel_token_free(t.ptr)
+	t.ptr = nil
+	runtime.SetFinalizer(t, nil)
+}
+
+// cPtr returns the C handle, or nil for a nil token
+func (t *CancelToken) cPtr() *C.CancelToken {
+	if t == nil {
+		return nil
+	}
+	return t.ptr
+}
@@ -0,0 +1,85 @@
+package minmpeg
+
+/*
+#include "../include/minmpeg.h"
+#include <stdlib.h>
+
+extern int32_t goFrameCallback(uint64_t frame_index, uint8_t* rgba, size_t rgba_len, void* user_data);
+*/
+import "C"
+import (
+	"runtime/cgo"
+	"unsafe"
+)
+
+// FrameFormat describes a video rendered frame by frame
+type FrameFormat struct {
+	// Width and Height in pixels (must be even)
+	Width  uint32
+	Height uint32
+	FPS    uint32
+	// FrameCount is the number of frames to encode
+	FrameCount uint64
+}
+
+// RenderFunc draws frame frameIndex into rgba (Width*Height*4 bytes, zeroed).
+// Returning an error stops encoding; the error is returned by EncodeFrames.
+type RenderFunc func(frameIndex uint64, rgba []byte) error
+
+// frameRenderer holds a RenderFunc and the error that stopped it
+type frameRenderer struct {
+	render RenderFunc
+	err    error
+}
+
+//export goFrameCallback
+func goFrameCallback(frameIndex C.uint64_t, rgba *C.uint8_t, rgbaLen C.size_t, userData unsafe.Pointer) C.int32_t {
+	h := *(*cgo.Handle)(userData)
+	r := h.Value().(*frameRenderer)
+
+	frame := unsafe.Slice((*byte)(unsafe.Pointer(rgba)), int(rgbaLen))
+	if r.err = r.render(uint64(frameIndex), frame); r.err != nil {
+		return 1
+	}
+	return 0
+}
+
+// EncodeFrames encodes frames drawn by render to opts.OutputPath
+func EncodeFrames(format FrameFormat, render RenderFunc, opts EncodeOptions) error {
+	return frameCall(render, opts, func(callback C.FrameCallback, userData unsafe.Pointer, cOptions *C.EncodeOptions) C.Result {
+		return C.minmpeg_encode_frames(C.uint32_t(format.Width), C.uint32_t(format.Height), C.uint32_t(format.FPS),
+			C.uint64_t(format.FrameCount), callback, userData, cOptions)
+	})
+}
+
+// EncodeFramesToBytes encodes frames drawn by render and returns the encoded video.
+// opts.OutputPath is ignored.
+func EncodeFramesToBytes(format FrameFormat, render RenderFunc, opts EncodeOptions) ([]byte, error) {
+	var buffer C.Buffer
+	err := frameCall(render, opts, func(callback C.FrameCallback, userData unsafe.Pointer, cOptions *C.EncodeOptions) C.Result {
+		return C.minmpeg_encode_frames_to_buffer(C.uint32_t(format.Width), C.uint32_t(format.Height), C.uint32_t(format.FPS),
+			C.uint64_t(format.FrameCount), callback, userData, cOptions, &buffer)
+	})
+
+	return bufferToBytes(&buffer, err)
+}
+
+// frameCall registers render so it can be passed through C as user data and invokes fn.
+// An error returned by render takes precedence over the resulting ErrCancelled.
+func frameCall(render RenderFunc, opts EncodeOptions, fn func(C.FrameCallback, unsafe.Pointer, *C.EncodeOptions) C.Result) error {
+	r := &frameRenderer{render: render}
+	ptr := C.malloc(C.size_t(unsafe.Sizeof(cgo.Handle(0))))
+	*(*cgo.Handle)(ptr) = cgo.NewHandle(r)
+	defer func() {
+		(*(*cgo.Handle)(ptr)).Delete()
+		C.free(ptr)
+	}()
+
+	err := encodeOptionsCall(opts, func(cOptions *C.EncodeOptions) C.Result {
+		return fn(C.FrameCallback(C.goFrameCallback), ptr, cOptions)
+	})
+	if r.err != nil {
+		return r.err
+	}
+	return err
+}
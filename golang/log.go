@@ -0,0 +1,64 @@
+package minmpeg
+
+/*
+#include "../include/minmpeg.h"
+#include <stdlib.h>
+
+extern void goLogCallback(LogLevel level, char* message, void* user_data);
+*/
+import "C"
+import (
+	"runtime/cgo"
+	"sync"
+	"unsafe"
+)
+
+// LogLevel is the severity of a log message, from most to least severe
+type LogLevel int
+
+const (
+	LogError LogLevel = C.MINMPEG_LOG_ERROR
+	LogWarn  LogLevel = C.MINMPEG_LOG_WARN
+	LogInfo  LogLevel = C.MINMPEG_LOG_INFO
+	LogDebug LogLevel = C.MINMPEG_LOG_DEBUG
+	LogTrace LogLevel = C.MINMPEG_LOG_TRACE
+)
+
+// LogFunc receives internal diagnostic messages
+type LogFunc func(level LogLevel, message string)
+
+var (
+	logMu     sync.Mutex
+	logHandle unsafe.Pointer // C memory holding the cgo.Handle of the current LogFunc
+)
+
+//export goLogCallback
+func goLogCallback(level C.LogLevel, message *C.char, userData unsafe.Pointer) {
+	h := *(*cgo.Handle)(userData)
+	if fn, ok := h.Value().(LogFunc); ok {
+		fn(LogLevel(level), C.GoString(message))
+	}
+}
+
+// SetLogCallback routes internal diagnostics at level or more severe to fn.
+// Pass a nil fn to disable logging. fn may be called from any goroutine running an operation.
+func SetLogCallback(level LogLevel, fn LogFunc) {
+	logMu.Lock()
+	defer logMu.Unlock()
+
+	var newHandle unsafe.Pointer
+	if fn != nil {
+		newHandle = C.malloc(C.size_t(unsafe.Sizeof(cgo.Handle(0))))
+		*(*cgo.Handle)(newHandle) = cgo.NewHandle(fn)
+		C.minmpeg_set_log_callback(C.LogCallback(C.goLogCallback), newHandle, C.LogLevel(level))
+	} else {
+		C.minmpeg_set_log_callback(nil, nil, C.LogLevel(level))
+	}
+
+	// The library no longer references the previous handle
+	if logHandle != nil {
+		(*(*cgo.Handle)(logHandle)).Delete()
+		C.free(logHandle)
+	}
+	logHandle = newHandle
+}
@@ -18,8 +18,15 @@ import (
 type Container int
 
 const (
-	ContainerMP4  Container = C.CONTAINER_MP4
-	ContainerWebM Container = C.CONTAINER_WEBM
+	ContainerMP4    Container = C.CONTAINER_MP4
+	ContainerWebM   Container = C.CONTAINER_WEBM
+	ContainerWebP   Container = C.CONTAINER_WEBP
+	ContainerAPNG   Container = C.CONTAINER_APNG
+	ContainerMKV    Container = C.CONTAINER_MKV
+	ContainerHLS    Container = C.CONTAINER_HLS
+	ContainerIVF    Container = C.CONTAINER_IVF
+	ContainerAnnexB Container = C.CONTAINER_ANNEX_B
+	ContainerY4M    Container = C.CONTAINER_Y4M
 )
 
 // Codec represents video codecs
@@ -28,34 +35,185 @@ type Codec int
 const (
 	CodecAV1  Codec = C.CODEC_AV1
 	CodecH264 Codec = C.CODEC_H264
+	CodecVP9  Codec = C.CODEC_VP9
 )
 
+// Priority is the scheduling priority of ffmpeg processes and encoding threads
+type Priority int
+
+const (
+	PriorityNormal Priority = C.PRIORITY_NORMAL
+	PriorityLow    Priority = C.PRIORITY_LOW
+	PriorityIdle   Priority = C.PRIORITY_IDLE
+)
+
+// Av1Backend is the encoder implementation used for AV1
+type Av1Backend int
+
+const (
+	Av1BackendRav1e  Av1Backend = C.AV1_BACKEND_RAV1E
+	Av1BackendSvtAv1 Av1Backend = C.AV1_BACKEND_SVT_AV1
+)
+
+// RateControl is how the encoder holds the target bitrate or quality
+type RateControl int
+
+const (
+	RateControlAuto     RateControl = C.RATE_CONTROL_AUTO
+	RateControlQuality  RateControl = C.RATE_CONTROL_QUALITY
+	RateControlAverage  RateControl = C.RATE_CONTROL_AVERAGE
+	RateControlConstant RateControl = C.RATE_CONTROL_CONSTANT
+)
+
+// ColorSpace is the color space frames are converted to YUV in and signalled as
+type ColorSpace int
+
+const (
+	ColorSpaceBt601 ColorSpace = C.COLOR_SPACE_BT601
+	ColorSpaceBt709 ColorSpace = C.COLOR_SPACE_BT709
+)
+
+// PixelRange is the range of the YUV values frames are converted to and signalled as
+type PixelRange int
+
+const (
+	PixelRangeAuto    PixelRange = C.PIXEL_RANGE_AUTO
+	PixelRangeLimited PixelRange = C.PIXEL_RANGE_LIMITED
+	PixelRangeFull    PixelRange = C.PIXEL_RANGE_FULL
+)
+
+// ScaleMode is how slides of another aspect ratio are scaled to the video dimensions
+type ScaleMode int
+
+const (
+	ScaleStretch ScaleMode = C.SCALE_MODE_STRETCH
+	ScaleFit     ScaleMode = C.SCALE_MODE_FIT
+	ScaleFill    ScaleMode = C.SCALE_MODE_FILL
+)
+
+// WatermarkPosition is the corner or center of the frame a watermark is placed at
+type WatermarkPosition int
+
+const (
+	WatermarkTopLeft     WatermarkPosition = C.WATERMARK_TOP_LEFT
+	WatermarkTopRight    WatermarkPosition = C.WATERMARK_TOP_RIGHT
+	WatermarkBottomLeft  WatermarkPosition = C.WATERMARK_BOTTOM_LEFT
+	WatermarkBottomRight WatermarkPosition = C.WATERMARK_BOTTOM_RIGHT
+	WatermarkCenter      WatermarkPosition = C.WATERMARK_CENTER
+)
+
+// Watermark is an image drawn over every frame of slideshows and juxtapositions
+type Watermark struct {
+	// Path to the image file
+	Path     string
+	Position WatermarkPosition
+	// Opacity from 0 (invisible) to 1 (as the image is)
+	Opacity float32
+	// Margin is the distance in pixels from the edges of the frame
+	Margin uint32
+}
+
 // Color represents an RGB color
 type Color struct {
 	R, G, B uint8
 }
 
+// ErrCancelled is returned when an operation is stopped through a CancelToken
+var ErrCancelled = errors.New("operation cancelled")
+
 // SlideEntry represents a single slide in a slideshow
 type SlideEntry struct {
 	Path       string
 	DurationMs uint32
 }
 
+// SlideData represents a slide supplied from memory.
+// Data holds an encoded image (JPEG, PNG, WebP, GIF), or raw RGBA pixels when Width and Height are set.
+type SlideData struct {
+	Data       []byte
+	Width      uint32
+	Height     uint32
+	DurationMs uint32
+}
+
+// Error codes carried by Error.Code. Values are stable across releases.
+const (
+	CodeInvalidInput           = C.MINMPEG_ERR_INVALID_INPUT
+	CodeCodecUnavailable       = C.MINMPEG_ERR_CODEC_UNAVAILABLE
+	CodeContainerCodecMismatch = C.MINMPEG_ERR_CONTAINER_CODEC_MISMATCH
+	CodeIOError                = C.MINMPEG_ERR_IO_ERROR
+	CodeEncodeError            = C.MINMPEG_ERR_ENCODE_ERROR
+	CodeDecodeError            = C.MINMPEG_ERR_DECODE_ERROR
+	CodeInternal               = C.MINMPEG_ERR_INTERNAL
+	CodeMuxError               = C.MINMPEG_ERR_MUX_ERROR
+	CodeFfmpegError            = C.MINMPEG_ERR_FFMPEG_ERROR
+	CodePlatformError          = C.MINMPEG_ERR_PLATFORM_ERROR
+	CodeUnsupported            = C.MINMPEG_ERR_UNSUPPORTED
+)
+
+// Error is returned when a minmpeg operation fails
+type Error struct {
+	// Code is the C error code (one of the Code* constants)
+	Code int
+	// Message is the error message
+	Message string
+	// Path is the input file that caused the error, if known
+	Path string
+	// Frame is the output frame index where the error occurred, or -1
+	Frame int64
+	// Chain holds the messages of the error and its causes, outermost first
+	Chain []string
+	// Retryable reports whether retrying the operation may succeed (e.g. a busy hardware encoder)
+	Retryable bool
+}
+
+func (e *Error) Error() string {
+	return e.Message
+}
+
 // resultToError converts a C Result to a Go error
 func resultToError(result C.Result) error {
 	if result.code == C.MINMPEG_OK {
 		return nil
 	}
 
-	var msg string
+	if result.code == C.MINMPEG_ERR_CANCELLED {
+		C.minmpeg_free_result(&result)
+		return ErrCancelled
+	}
+
+	err := &Error{Code: int(result.code), Message: "Unknown error", Frame: -1}
 	if result.message != nil {
-		msg = C.GoString(result.message)
+		err.Message = C.GoString(result.message)
+
+		var details C.ErrorDetails
+		if C.minmpeg_result_details(&result, &details) != 0 {
+			if details.path != nil {
+				err.Path = C.GoString(details.path)
+			}
+			err.Frame = int64(details.frame)
+			chain := unsafe.Slice(details.chain, details.chain_len)
+			for _, msg := range chain {
+				err.Chain = append(err.Chain, C.GoString(msg))
+			}
+		}
+		err.Retryable = C.minmpeg_result_retryable(&result) != 0
+
 		C.minmpeg_free_result(&result)
-	} else {
-		msg = "Unknown error"
 	}
 
-	return errors.New(msg)
+	return err
+}
+
+// Init starts platform runtimes (COM and Media Foundation on Windows) ahead of time.
+// It is optional; the runtimes stay running until Shutdown.
+func Init() error {
+	return resultToError(C.minmpeg_init())
+}
+
+// Shutdown releases platform runtimes kept by Init
+func Shutdown() {
+	C.minmpeg_shutdown()
 }
 
 // Available checks if a codec is available on this system
@@ -70,8 +228,225 @@ func Available(codec Codec, ffmpegPath string) error {
 	return resultToError(result)
 }
 
+// EncodeOptions holds the encoding settings for SlideshowEx and JuxtaposeEx
+type EncodeOptions struct {
+	OutputPath string
+	Container  Container
+	Codec      Codec
+	Quality    uint8
+	FfmpegPath string
+	// Progress is called after each encoded frame (optional)
+	Progress ProgressFunc
+	// Cancel stops the operation with ErrCancelled when cancelled (optional)
+	Cancel *CancelToken
+	// Warning is called for each non-fatal warning (optional)
+	Warning WarningFunc
+	// Stats receives statistics when the operation succeeds (optional)
+	Stats *EncodeStats
+	// Strict checks all inputs before encoding starts and fails with every problem found
+	Strict bool
+	// MaxDurationMs is the longest allowed output in strict mode (0 for no limit)
+	MaxDurationMs uint64
+	// MemoryLimit is the most bytes for images, buffered frames and packets (0 for no limit)
+	MemoryLimit uint64
+	// EncoderPool reuses warm encoders across operations (optional)
+	EncoderPool *EncoderPool
+	// ImageCache reuses decoded and resized slide images across operations (optional)
+	ImageCache *ImageCache
+	// Deterministic produces byte-identical output for identical inputs on any machine
+	Deterministic bool
+	// Quality verifies slideshows written to a file with ffmpeg and receives the comparison with their slides (optional)
+	Quality *QualityReport
+	// SpillDir keeps slide images in temporary files in this directory instead of memory (optional)
+	SpillDir string
+	// Priority lowers the priority of ffmpeg processes and encoding threads (default PriorityNormal)
+	Priority Priority
+	// Av1Backend selects the AV1 encoder; SVT-AV1 needs a library built with the svt-av1 feature (default Av1BackendRav1e)
+	Av1Backend Av1Backend
+	// ScaleMode scales slides of another aspect ratio by stretching, letterboxing or cropping them (default ScaleStretch)
+	ScaleMode ScaleMode
+	// LetterboxColor colors the bars around slides fitted with ScaleFit (default black)
+	LetterboxColor *Color
+	// Watermark draws an image over every frame (optional)
+	Watermark *Watermark
+	// FadeInMs fades from FadeColor at the start of videos (0 for none)
+	FadeInMs uint32
+	// FadeOutMs fades to FadeColor at the end of videos (0 for none)
+	FadeOutMs uint32
+	// FadeColor is the color videos fade from and to (default black)
+	FadeColor *Color
+	// BitrateKbps encodes at this bitrate instead of by Quality (0 for Quality)
+	BitrateKbps uint32
+	// RateControl encodes at constant quality, or at an average or constant BitrateKbps (default RateControlAuto)
+	RateControl RateControl
+	// KeyframeIntervalMs places keyframes this many milliseconds apart, such as the HLS segment duration (0 for the encoder default)
+	KeyframeIntervalMs uint32
+	// ColorSpace is BT.601 or BT.709, the usual one for HD video (default ColorSpaceBt601)
+	ColorSpace ColorSpace
+	// PixelRange is full for Y4M and limited for encoded video by default; PixelRangeFull keeps screenshots from looking washed out
+	PixelRange PixelRange
+	// Threads limits the threads encoders and ffmpeg use, such as on a shared server (0 for the runtime setting)
+	Threads uint32
+}
+
+// DefaultEncodeOptions returns options with the library defaults (WebM, AV1, quality 50)
+func DefaultEncodeOptions(outputPath string) EncodeOptions {
+	var cOptions C.EncodeOptions
+	C.minmpeg_encode_options_init(&cOptions)
+
+	return EncodeOptions{
+		OutputPath: outputPath,
+		Container:  Container(cOptions.container),
+		Codec:      Codec(cOptions.codec),
+		Quality:    uint8(cOptions.quality),
+	}
+}
+
+// encodeOptionsCall converts options to their C form and invokes fn with them.
+// C strings and the callback handle stay valid for the duration of fn.
+func encodeOptionsCall(opts EncodeOptions, fn func(*C.EncodeOptions) C.Result) error {
+	var cOptions C.EncodeOptions
+	C.minmpeg_encode_options_init(&cOptions)
+
+	cOutputPath := C.CString(opts.OutputPath)
+	defer C.free(unsafe.Pointer(cOutputPath))
+
+	cOptions.output_path = cOutputPath
+	cOptions.container = C.Container(opts.Container)
+	cOptions.codec = C.Codec(opts.Codec)
+	cOptions.quality = C.uint8_t(opts.Quality)
+
+	if opts.FfmpegPath != "" {
+		cFfmpegPath := C.CString(opts.FfmpegPath)
+		defer C.free(unsafe.Pointer(cFfmpegPath))
+		cOptions.ffmpeg_path = cFfmpegPath
+	}
+
+	h := newCallbackHandle(opts.Progress, opts.Warning)
+	if h != nil {
+		defer h.Delete()
+	}
+	setCallbacks(&cOptions, h)
+	cOptions.cancel = opts.Cancel.cPtr()
+	if opts.Strict {
+		cOptions.strict = 1
+		cOptions.max_duration_ms = C.uint64_t(opts.MaxDurationMs)
+	}
+	cOptions.memory_limit = C.uint64_t(opts.MemoryLimit)
+	cOptions.encoder_pool = opts.EncoderPool.cPtr()
+	cOptions.image_cache = opts.ImageCache.cPtr()
+	if opts.Deterministic {
+		cOptions.deterministic = 1
+	}
+	if opts.SpillDir != "" {
+		cSpillDir := C.CString(opts.SpillDir)
+		defer C.free(unsafe.Pointer(cSpillDir))
+		cOptions.spill_dir = cSpillDir
+	}
+	cOptions.priority = C.Priority(opts.Priority)
+	cOptions.av1_backend = C.Av1Backend(opts.Av1Backend)
+	cOptions.scale_mode = C.ScaleMode(opts.ScaleMode)
+	if opts.LetterboxColor != nil {
+		cOptions.letterbox_color = *cColor(opts.LetterboxColor)
+	}
+	if opts.Watermark != nil {
+		cWatermarkPath := C.CString(opts.Watermark.Path)
+		defer C.free(unsafe.Pointer(cWatermarkPath))
+		cOptions.watermark_path = cWatermarkPath
+		cOptions.watermark_position = C.WatermarkPosition(opts.Watermark.Position)
+		cOptions.watermark_opacity = C.float(opts.Watermark.Opacity)
+		cOptions.watermark_margin = C.uint32_t(opts.Watermark.Margin)
+	}
+	cOptions.fade_in_ms = C.uint32_t(opts.FadeInMs)
+	cOptions.fade_out_ms = C.uint32_t(opts.FadeOutMs)
+	if opts.FadeColor != nil {
+		cOptions.fade_color = *cColor(opts.FadeColor)
+	}
+	cOptions.bitrate_kbps = C.uint32_t(opts.BitrateKbps)
+	cOptions.rate_control = C.RateControl(opts.RateControl)
+	cOptions.keyframe_interval_ms = C.uint32_t(opts.KeyframeIntervalMs)
+	cOptions.color_space = C.ColorSpace(opts.ColorSpace)
+	cOptions.pixel_range = C.PixelRange(opts.PixelRange)
+	cOptions.threads = C.uint32_t(opts.Threads)
+
+	// Allocated in C memory: cOptions is passed to C and may not point to Go memory
+	if opts.Stats != nil {
+		cOptions.stats = (*C.EncodeStats)(C.calloc(1, C.size_t(unsafe.Sizeof(C.EncodeStats{}))))
+		defer C.free(unsafe.Pointer(cOptions.stats))
+	}
+	if opts.Quality != nil {
+		cOptions.quality_report = (*C.QualityReport)(C.calloc(1, C.size_t(unsafe.Sizeof(C.QualityReport{}))))
+		defer C.free(unsafe.Pointer(cOptions.quality_report))
+	}
+
+	result := fn(&cOptions)
+	if opts.Stats != nil && result.code == C.MINMPEG_OK {
+		opts.Stats.fromC(cOptions.stats)
+	}
+	if opts.Quality != nil && result.code == C.MINMPEG_OK {
+		opts.Quality.fromC(cOptions.quality_report)
+	}
+	return resultToError(result)
+}
+
+// Capability describes a codec/container combination available on this machine
+type Capability struct {
+	Container Container
+	Codec     Codec
+	// Backend is the encoder implementation name (e.g. "rav1e", "VideoToolbox")
+	Backend  string
+	Hardware bool
+}
+
+// Capabilities lists the codec/container combinations available on this machine
+func Capabilities(ffmpegPath string) ([]Capability, error) {
+	var cPath *C.char
+	if ffmpegPath != "" {
+		cPath = C.CString(ffmpegPath)
+		defer C.free(unsafe.Pointer(cPath))
+	}
+
+	var caps C.Capabilities
+	if err := resultToError(C.minmpeg_capabilities(cPath, &caps)); err != nil {
+		return nil, err
+	}
+	defer C.minmpeg_capabilities_free(&caps)
+
+	items := unsafe.Slice(caps.items, int(caps.len))
+	result := make([]Capability, len(items))
+	for i, item := range items {
+		result[i] = Capability{
+			Container: Container(item.container),
+			Codec:     Codec(item.codec),
+			Backend:   C.GoString(item.backend_name),
+			Hardware:  item.hardware != 0,
+		}
+	}
+
+	return result, nil
+}
+
 // Slideshow creates a video from a sequence of images
 func Slideshow(entries []SlideEntry, outputPath string, container Container, codec Codec, quality uint8, ffmpegPath string) error {
+	return SlideshowWithProgress(entries, outputPath, container, codec, quality, ffmpegPath, nil, nil)
+}
+
+// SlideshowWithProgress creates a video from a sequence of images, calling progress after each encoded frame.
+// The operation stops with ErrCancelled when cancel is cancelled. Both progress and cancel may be nil.
+func SlideshowWithProgress(entries []SlideEntry, outputPath string, container Container, codec Codec, quality uint8, ffmpegPath string, progress ProgressFunc, cancel *CancelToken) error {
+	return SlideshowEx(entries, EncodeOptions{
+		OutputPath: outputPath,
+		Container:  container,
+		Codec:      codec,
+		Quality:    quality,
+		FfmpegPath: ffmpegPath,
+		Progress:   progress,
+		Cancel:     cancel,
+	})
+}
+
+// SlideshowEx creates a video from a sequence of images using an options struct
+func SlideshowEx(entries []SlideEntry, opts EncodeOptions) error {
 	if len(entries) == 0 {
 		return errors.New("no slides provided")
 	}
@@ -90,48 +465,285 @@ func Slideshow(entries []SlideEntry, outputPath string, container Container, cod
 		}
 	}
 
-	cOutputPath := C.CString(outputPath)
-	defer C.free(unsafe.Pointer(cOutputPath))
+	return encodeOptionsCall(opts, func(cOptions *C.EncodeOptions) C.Result {
+		return C.minmpeg_slideshow_ex(&cEntries[0], C.size_t(len(entries)), cOptions)
+	})
+}
 
-	var cFfmpegPath *C.char
-	if ffmpegPath != "" {
-		cFfmpegPath = C.CString(ffmpegPath)
-		defer C.free(unsafe.Pointer(cFfmpegPath))
+// SlideshowToBytes creates a video from a sequence of images and returns the encoded bytes.
+// opts.OutputPath is ignored.
+func SlideshowToBytes(entries []SlideEntry, opts EncodeOptions) ([]byte, error) {
+	if len(entries) == 0 {
+		return nil, errors.New("no slides provided")
 	}
 
-	result := C.minmpeg_slideshow(
-		&cEntries[0],
-		C.size_t(len(entries)),
-		cOutputPath,
-		C.Container(container),
-		C.Codec(codec),
-		C.uint8_t(quality),
-		cFfmpegPath,
-	)
+	// Convert entries
+	cEntries := make([]C.SlideEntry, len(entries))
+	cPaths := make([]*C.char, len(entries))
 
-	return resultToError(result)
+	for i, entry := range entries {
+		cPaths[i] = C.CString(entry.Path)
+		defer C.free(unsafe.Pointer(cPaths[i]))
+
+		cEntries[i] = C.SlideEntry{
+			path:        cPaths[i],
+			duration_ms: C.uint32_t(entry.DurationMs),
+		}
+	}
+
+	var buffer C.Buffer
+	err := encodeOptionsCall(opts, func(cOptions *C.EncodeOptions) C.Result {
+		return C.minmpeg_slideshow_to_buffer(&cEntries[0], C.size_t(len(entries)), cOptions, &buffer)
+	})
+
+	return bufferToBytes(&buffer, err)
+}
+
+// SlideshowFromData creates a video from slides held in memory
+func SlideshowFromData(slides []SlideData, opts EncodeOptions) error {
+	return slideDataCall(slides, opts, func(entries *C.SlideData, count C.size_t, cOptions *C.EncodeOptions) C.Result {
+		return C.minmpeg_slideshow_from_data(entries, count, cOptions)
+	})
+}
+
+// SlideshowFromDataToBytes creates a video from slides held in memory and returns the encoded bytes.
+// opts.OutputPath is ignored.
+func SlideshowFromDataToBytes(slides []SlideData, opts EncodeOptions) ([]byte, error) {
+	var buffer C.Buffer
+	err := slideDataCall(slides, opts, func(entries *C.SlideData, count C.size_t, cOptions *C.EncodeOptions) C.Result {
+		return C.minmpeg_slideshow_from_data_to_buffer(entries, count, cOptions, &buffer)
+	})
+
+	return bufferToBytes(&buffer, err)
+}
+
+// slideDataCall copies slide data into C memory and invokes fn with the converted entries
+func slideDataCall(slides []SlideData, opts EncodeOptions, fn func(*C.SlideData, C.size_t, *C.EncodeOptions) C.Result) error {
+	if len(slides) == 0 {
+		return errors.New("no slides provided")
+	}
+
+	cEntries := make([]C.SlideData, len(slides))
+
+	for i, slide := range slides {
+		if len(slide.Data) == 0 {
+			return errors.New("slide data is empty")
+		}
+
+		// Image bytes must live in C memory while referenced from C structs
+		cData := C.CBytes(slide.Data)
+		defer C.free(cData)
+
+		kind := C.SLIDE_DATA_ENCODED
+		if slide.Width != 0 && slide.Height != 0 {
+			kind = C.SLIDE_DATA_RGBA
+		}
+
+		cEntries[i] = C.SlideData{
+			kind:        C.SlideDataKind(kind),
+			data:        (*C.uint8_t)(cData),
+			data_len:    C.size_t(len(slide.Data)),
+			width:       C.uint32_t(slide.Width),
+			height:      C.uint32_t(slide.Height),
+			duration_ms: C.uint32_t(slide.DurationMs),
+		}
+	}
+
+	return encodeOptionsCall(opts, func(cOptions *C.EncodeOptions) C.Result {
+		return fn(&cEntries[0], C.size_t(len(slides)), cOptions)
+	})
+}
+
+// bufferToBytes copies a C buffer into Go memory and frees it
+func bufferToBytes(buffer *C.Buffer, err error) ([]byte, error) {
+	defer C.minmpeg_buffer_free(buffer)
+
+	if err != nil {
+		return nil, err
+	}
+
+	return C.GoBytes(unsafe.Pointer(buffer.data), C.int(buffer.len)), nil
 }
 
 // Juxtapose combines two videos side by side
 func Juxtapose(leftPath, rightPath, outputPath string, container Container, codec Codec, quality uint8, background *Color, ffmpegPath string) error {
+	return JuxtaposeWithProgress(leftPath, rightPath, outputPath, container, codec, quality, background, ffmpegPath, nil, nil)
+}
+
+// JuxtaposeWithProgress combines two videos side by side, calling progress after each encoded frame.
+// The operation stops with ErrCancelled when cancel is cancelled. Both progress and cancel may be nil.
+func JuxtaposeWithProgress(leftPath, rightPath, outputPath string, container Container, codec Codec, quality uint8, background *Color, ffmpegPath string, progress ProgressFunc, cancel *CancelToken) error {
+	return JuxtaposeEx(leftPath, rightPath, background, EncodeOptions{
+		OutputPath: outputPath,
+		Container:  container,
+		Codec:      codec,
+		Quality:    quality,
+		FfmpegPath: ffmpegPath,
+		Progress:   progress,
+		Cancel:     cancel,
+	})
+}
+
+// JuxtaposeEx combines two videos side by side using an options struct.
+// background may be nil (white).
+func JuxtaposeEx(leftPath, rightPath string, background *Color, opts EncodeOptions) error {
 	cLeftPath := C.CString(leftPath)
 	defer C.free(unsafe.Pointer(cLeftPath))
 
 	cRightPath := C.CString(rightPath)
 	defer C.free(unsafe.Pointer(cRightPath))
 
+	cBackground := cColor(background)
+
+	return encodeOptionsCall(opts, func(cOptions *C.EncodeOptions) C.Result {
+		return C.minmpeg_juxtapose_ex(cLeftPath, cRightPath, cBackground, cOptions)
+	})
+}
+
+// JuxtaposeToBytes combines two videos side by side and returns the encoded bytes.
+// opts.OutputPath is ignored.
+func JuxtaposeToBytes(leftPath, rightPath string, background *Color, opts EncodeOptions) ([]byte, error) {
+	cLeftPath := C.CString(leftPath)
+	defer C.free(unsafe.Pointer(cLeftPath))
+
+	cRightPath := C.CString(rightPath)
+	defer C.free(unsafe.Pointer(cRightPath))
+
+	cBackground := cColor(background)
+
+	var buffer C.Buffer
+	err := encodeOptionsCall(opts, func(cOptions *C.EncodeOptions) C.Result {
+		return C.minmpeg_juxtapose_to_buffer(cLeftPath, cRightPath, cBackground, cOptions, &buffer)
+	})
+
+	return bufferToBytes(&buffer, err)
+}
+
+// JuxtaposeN combines any number of videos into a grid with the given number
+// of columns (0 places all videos in a single row)
+func JuxtaposeN(paths []string, columns uint32, background *Color, opts EncodeOptions) error {
+	cPaths, free := cStrings(paths)
+	defer free()
+
+	layout := C.Layout{columns: C.uint32_t(columns)}
+	cBackground := cColor(background)
+
+	return encodeOptionsCall(opts, func(cOptions *C.EncodeOptions) C.Result {
+		return C.minmpeg_juxtapose_n(cPaths, C.size_t(len(paths)), &layout, cBackground, cOptions)
+	})
+}
+
+// JuxtaposeNToBytes combines any number of videos into a grid and returns the
+// encoded bytes. opts.OutputPath is ignored.
+func JuxtaposeNToBytes(paths []string, columns uint32, background *Color, opts EncodeOptions) ([]byte, error) {
+	cPaths, free := cStrings(paths)
+	defer free()
+
+	layout := C.Layout{columns: C.uint32_t(columns)}
+	cBackground := cColor(background)
+
+	var buffer C.Buffer
+	err := encodeOptionsCall(opts, func(cOptions *C.EncodeOptions) C.Result {
+		return C.minmpeg_juxtapose_n_to_buffer(cPaths, C.size_t(len(paths)), &layout, cBackground, cOptions, &buffer)
+	})
+
+	return bufferToBytes(&buffer, err)
+}
+
+// Transcode re-encodes a video with the container, codec and quality in opts
+func Transcode(inputPath string, opts EncodeOptions) error {
+	cInputPath := C.CString(inputPath)
+	defer C.free(unsafe.Pointer(cInputPath))
+
+	return encodeOptionsCall(opts, func(cOptions *C.EncodeOptions) C.Result {
+		return C.minmpeg_transcode(cInputPath, cOptions)
+	})
+}
+
+// Trim re-encodes the part of a video between startMs and endMs (0 for the end)
+func Trim(inputPath string, startMs, endMs uint64, opts EncodeOptions) error {
+	cInputPath := C.CString(inputPath)
+	defer C.free(unsafe.Pointer(cInputPath))
+
+	return encodeOptionsCall(opts, func(cOptions *C.EncodeOptions) C.Result {
+		return C.minmpeg_trim(cInputPath, C.uint64_t(startMs), C.uint64_t(endMs), cOptions)
+	})
+}
+
+// Concat joins videos one after another
+func Concat(paths []string, opts EncodeOptions) error {
+	cPaths, free := cStrings(paths)
+	defer free()
+
+	return encodeOptionsCall(opts, func(cOptions *C.EncodeOptions) C.Result {
+		return C.minmpeg_concat(cPaths, C.size_t(len(paths)), cOptions)
+	})
+}
+
+// Thumbnail saves the frame shown at timeMs as an image; the format follows
+// the extension of outputPath
+func Thumbnail(inputPath string, timeMs uint64, outputPath string, ffmpegPath string) error {
+	cInputPath := C.CString(inputPath)
+	defer C.free(unsafe.Pointer(cInputPath))
+
 	cOutputPath := C.CString(outputPath)
 	defer C.free(unsafe.Pointer(cOutputPath))
 
-	var cBackground *C.Color
-	if background != nil {
-		bg := C.Color{
-			r: C.uint8_t(background.R),
-			g: C.uint8_t(background.G),
-			b: C.uint8_t(background.B),
+	var cFfmpegPath *C.char
+	if ffmpegPath != "" {
+		cFfmpegPath = C.CString(ffmpegPath)
+		defer C.free(unsafe.Pointer(cFfmpegPath))
+	}
+
+	result := C.minmpeg_thumbnail(cInputPath, C.uint64_t(timeMs), cOutputPath, cFfmpegPath)
+	return resultToError(result)
+}
+
+// cStrings converts strings to a C array; call free when done
+func cStrings(strs []string) (**C.char, func()) {
+	if len(strs) == 0 {
+		return nil, func() {}
+	}
+
+	cStrs := make([]*C.char, len(strs))
+	for i, s := range strs {
+		cStrs[i] = C.CString(s)
+	}
+
+	return &cStrs[0], func() {
+		for _, cStr := range cStrs {
+			C.free(unsafe.Pointer(cStr))
 		}
-		cBackground = &bg
 	}
+}
+
+// cColor converts an optional color for C
+func cColor(color *Color) *C.Color {
+	if color == nil {
+		return nil
+	}
+	return &C.Color{
+		r: C.uint8_t(color.R),
+		g: C.uint8_t(color.G),
+		b: C.uint8_t(color.B),
+	}
+}
+
+// VideoInfo describes a video file
+type VideoInfo struct {
+	Width      uint32
+	Height     uint32
+	FPS        float64
+	DurationMs uint64
+	FrameCount uint64
+	Codec      string
+	Container  string
+}
+
+// Probe reads the dimensions, frame rate, duration, codec and container of a video file
+func Probe(path string, ffmpegPath string) (*VideoInfo, error) {
+	cPath := C.CString(path)
+	defer C.free(unsafe.Pointer(cPath))
 
 	var cFfmpegPath *C.char
 	if ffmpegPath != "" {
@@ -139,18 +751,22 @@ func Juxtapose(leftPath, rightPath, outputPath string, container Container, code
 		defer C.free(unsafe.Pointer(cFfmpegPath))
 	}
 
-	result := C.minmpeg_juxtapose(
-		cLeftPath,
-		cRightPath,
-		cOutputPath,
-		C.Container(container),
-		C.Codec(codec),
-		C.uint8_t(quality),
-		cBackground,
-		cFfmpegPath,
-	)
+	var info C.VideoInfo
+	info.struct_size = C.uint32_t(unsafe.Sizeof(info))
+	result := C.minmpeg_probe(cPath, cFfmpegPath, &info)
+	if err := resultToError(result); err != nil {
+		return nil, err
+	}
 
-	return resultToError(result)
+	return &VideoInfo{
+		Width:      uint32(info.width),
+		Height:     uint32(info.height),
+		FPS:        float64(info.fps),
+		DurationMs: uint64(info.duration_ms),
+		FrameCount: uint64(info.frame_count),
+		Codec:      C.GoString(&info.codec[0]),
+		Container:  C.GoString(&info.container[0]),
+	}, nil
 }
 
 // Version returns the library version string
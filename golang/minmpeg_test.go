@@ -1,6 +1,7 @@
 package minmpeg
 
 import (
+	"errors"
 	"fmt"
 	"image"
 	"image/color"
@@ -53,6 +54,20 @@ func TestAvailable(t *testing.T) {
 	}
 }
 
+func TestCapabilities(t *testing.T) {
+	caps, err := Capabilities("")
+	if err != nil {
+		t.Fatalf("Capabilities failed: %v", err)
+	}
+
+	for _, c := range caps {
+		if c.Codec == CodecAV1 && c.Container == ContainerWebM {
+			return
+		}
+	}
+	t.Errorf("WebM/AV1 should be listed: %+v", caps)
+}
+
 func TestSlideshowCreatesValidVideo(t *testing.T) {
 	// Create temp directory
 	tmpDir, err := os.MkdirTemp("", "minmpeg-test-*")
@@ -106,6 +121,278 @@ func TestSlideshowCreatesValidVideo(t *testing.T) {
 	t.Logf("Created valid WebM file: %s (%d bytes)", outputPath, info.Size())
 }
 
+func TestSlideshowWithProgress(t *testing.T) {
+	tmpDir, err := os.MkdirTemp("", "minmpeg-test-*")
+	if err != nil {
+		t.Fatalf("Failed to create temp dir: %v", err)
+	}
+	defer os.RemoveAll(tmpDir)
+
+	imgPath := filepath.Join(tmpDir, "slide.png")
+	if err := createTestImage(imgPath, 160, 120, color.RGBA{255, 0, 0, 255}); err != nil {
+		t.Fatalf("Failed to create test image: %v", err)
+	}
+
+	entries := []SlideEntry{{Path: imgPath, DurationMs: 200}}
+
+	var calls, lastDone, lastTotal uint64
+	progress := func(framesDone, framesTotal uint64) {
+		calls++
+		lastDone = framesDone
+		lastTotal = framesTotal
+	}
+
+	outputPath := filepath.Join(tmpDir, "output.webm")
+	err = SlideshowWithProgress(entries, outputPath, ContainerWebM, CodecAV1, 50, "", progress, nil)
+	if err != nil {
+		t.Fatalf("SlideshowWithProgress failed: %v", err)
+	}
+
+	if calls == 0 {
+		t.Fatal("Progress callback was never called")
+	}
+	if lastDone != lastTotal {
+		t.Errorf("Final progress should be complete: %d/%d", lastDone, lastTotal)
+	}
+}
+
+func TestSlideshowEx(t *testing.T) {
+	tmpDir, err := os.MkdirTemp("", "minmpeg-test-*")
+	if err != nil {
+		t.Fatalf("Failed to create temp dir: %v", err)
+	}
+	defer os.RemoveAll(tmpDir)
+
+	imgPath := filepath.Join(tmpDir, "slide.png")
+	if err := createTestImage(imgPath, 160, 120, color.RGBA{0, 255, 0, 255}); err != nil {
+		t.Fatalf("Failed to create test image: %v", err)
+	}
+
+	entries := []SlideEntry{{Path: imgPath, DurationMs: 200}}
+
+	outputPath := filepath.Join(tmpDir, "output.webm")
+	opts := DefaultEncodeOptions(outputPath)
+	if opts.Container != ContainerWebM || opts.Codec != CodecAV1 || opts.Quality != 50 {
+		t.Fatalf("Unexpected default options: %+v", opts)
+	}
+
+	if err := SlideshowEx(entries, opts); err != nil {
+		t.Fatalf("SlideshowEx failed: %v", err)
+	}
+
+	if !verifyWebMHeader(outputPath) {
+		t.Fatal("Output file is not a valid WebM")
+	}
+}
+
+func TestSlideshowToBytes(t *testing.T) {
+	tmpDir, err := os.MkdirTemp("", "minmpeg-test-*")
+	if err != nil {
+		t.Fatalf("Failed to create temp dir: %v", err)
+	}
+	defer os.RemoveAll(tmpDir)
+
+	imgPath := filepath.Join(tmpDir, "slide.png")
+	if err := createTestImage(imgPath, 160, 120, color.RGBA{255, 255, 0, 255}); err != nil {
+		t.Fatalf("Failed to create test image: %v", err)
+	}
+
+	entries := []SlideEntry{{Path: imgPath, DurationMs: 200}}
+
+	data, err := SlideshowToBytes(entries, DefaultEncodeOptions(""))
+	if err != nil {
+		t.Fatalf("SlideshowToBytes failed: %v", err)
+	}
+
+	// WebM starts with EBML header: 0x1A 0x45 0xDF 0xA3
+	if len(data) < 4 || data[0] != 0x1A || data[1] != 0x45 || data[2] != 0xDF || data[3] != 0xA3 {
+		t.Fatal("Output is not a valid WebM")
+	}
+}
+
+func TestSlideshowFromDataToBytes(t *testing.T) {
+	// Raw RGBA pixels for a 16x16 gray image
+	pixels := make([]byte, 16*16*4)
+	for i := range pixels {
+		pixels[i] = 128
+	}
+
+	slides := []SlideData{{Data: pixels, Width: 16, Height: 16, DurationMs: 100}}
+
+	data, err := SlideshowFromDataToBytes(slides, DefaultEncodeOptions(""))
+	if err != nil {
+		t.Fatalf("SlideshowFromDataToBytes failed: %v", err)
+	}
+
+	// WebM starts with EBML header: 0x1A 0x45 0xDF 0xA3
+	if len(data) < 4 || data[0] != 0x1A || data[1] != 0x45 || data[2] != 0xDF || data[3] != 0xA3 {
+		t.Fatal("Output is not a valid WebM")
+	}
+}
+
+func TestSlideshowWarning(t *testing.T) {
+	// 17x16 is rounded down to 16x16
+	pixels := make([]byte, 17*16*4)
+	slides := []SlideData{{Data: pixels, Width: 17, Height: 16, DurationMs: 100}}
+
+	var warnings []Warning
+	opts := DefaultEncodeOptions("")
+	opts.Warning = func(warning Warning) {
+		warnings = append(warnings, warning)
+	}
+
+	if _, err := SlideshowFromDataToBytes(slides, opts); err != nil {
+		t.Fatalf("SlideshowFromDataToBytes failed: %v", err)
+	}
+	if len(warnings) != 1 || warnings[0].Code != WarnDimensionsRounded {
+		t.Errorf("Expected one dimensions warning, got %v", warnings)
+	}
+}
+
+func TestSlideshowEncoderPool(t *testing.T) {
+	pixels := make([]byte, 16*16*4)
+	slides := []SlideData{{Data: pixels, Width: 16, Height: 16, DurationMs: 100}}
+
+	pool := NewEncoderPool(0)
+	defer pool.Free()
+
+	opts := DefaultEncodeOptions("")
+	opts.EncoderPool = pool
+
+	// The second call reuses the encoder returned by the first
+	for i := 0; i < 2; i++ {
+		if _, err := SlideshowFromDataToBytes(slides, opts); err != nil {
+			t.Fatalf("SlideshowFromDataToBytes failed: %v", err)
+		}
+	}
+}
+
+func TestSlideshowImageCache(t *testing.T) {
+	tmpDir, err := os.MkdirTemp("", "minmpeg-test-*")
+	if err != nil {
+		t.Fatalf("Failed to create temp dir: %v", err)
+	}
+	defer os.RemoveAll(tmpDir)
+
+	imgPath := filepath.Join(tmpDir, "logo.png")
+	if err := createTestImage(imgPath, 160, 120, color.RGBA{0, 0, 255, 255}); err != nil {
+		t.Fatalf("Failed to create test image: %v", err)
+	}
+
+	cache := NewImageCache()
+	defer cache.Free()
+
+	opts := DefaultEncodeOptions("")
+	opts.ImageCache = cache
+
+	// The logo is decoded once and reused by every slide of both calls
+	entries := []SlideEntry{{Path: imgPath, DurationMs: 100}, {Path: imgPath, DurationMs: 100}}
+	for i := 0; i < 2; i++ {
+		if _, err := SlideshowToBytes(entries, opts); err != nil {
+			t.Fatalf("SlideshowToBytes failed: %v", err)
+		}
+	}
+}
+
+func TestEncodeFramesToBytes(t *testing.T) {
+	format := FrameFormat{Width: 16, Height: 16, FPS: 10, FrameCount: 3}
+	rendered := 0
+	render := func(frameIndex uint64, rgba []byte) error {
+		for i := range rgba {
+			rgba[i] = byte(frameIndex * 80)
+		}
+		rendered++
+		return nil
+	}
+
+	var stats EncodeStats
+	opts := DefaultEncodeOptions("")
+	opts.Stats = &stats
+
+	data, err := EncodeFramesToBytes(format, render, opts)
+	if err != nil {
+		t.Fatalf("EncodeFramesToBytes failed: %v", err)
+	}
+	if rendered != 3 {
+		t.Errorf("Rendered %d frames, want 3", rendered)
+	}
+	if stats.Frames != 3 || stats.OutputBytes != uint64(len(data)) || stats.Backend != "rav1e" {
+		t.Errorf("Unexpected stats %+v", stats)
+	}
+	if len(data) < 4 || data[0] != 0x1A || data[1] != 0x45 || data[2] != 0xDF || data[3] != 0xA3 {
+		t.Fatal("Output is not a valid WebM")
+	}
+
+	stop := errors.New("stop")
+	_, err = EncodeFramesToBytes(format, func(uint64, []byte) error { return stop }, DefaultEncodeOptions(""))
+	if err != stop {
+		t.Errorf("Expected render error, got %v", err)
+	}
+}
+
+func TestSlideshowCancelled(t *testing.T) {
+	tmpDir, err := os.MkdirTemp("", "minmpeg-test-*")
+	if err != nil {
+		t.Fatalf("Failed to create temp dir: %v", err)
+	}
+	defer os.RemoveAll(tmpDir)
+
+	imgPath := filepath.Join(tmpDir, "slide.png")
+	if err := createTestImage(imgPath, 160, 120, color.RGBA{0, 0, 255, 255}); err != nil {
+		t.Fatalf("Failed to create test image: %v", err)
+	}
+
+	entries := []SlideEntry{{Path: imgPath, DurationMs: 1000}}
+
+	cancel := NewCancelToken()
+	defer cancel.Free()
+
+	// Cancel as soon as the first frame is encoded
+	progress := func(framesDone, framesTotal uint64) {
+		cancel.Cancel()
+	}
+
+	outputPath := filepath.Join(tmpDir, "output.webm")
+	err = SlideshowWithProgress(entries, outputPath, ContainerWebM, CodecAV1, 50, "", progress, cancel)
+	if !errors.Is(err, ErrCancelled) {
+		t.Fatalf("Expected ErrCancelled, got: %v", err)
+	}
+}
+
+func TestProbeNonexistentFile(t *testing.T) {
+	if _, err := Probe(filepath.Join(os.TempDir(), "minmpeg-missing.webm"), "/nonexistent/ffmpeg"); err == nil {
+		t.Fatal("Probe should fail for an invalid ffmpeg path")
+	}
+}
+
+func TestSetLogCallback(t *testing.T) {
+	tmpDir, err := os.MkdirTemp("", "minmpeg-test-*")
+	if err != nil {
+		t.Fatalf("Failed to create temp dir: %v", err)
+	}
+	defer os.RemoveAll(tmpDir)
+
+	imgPath := filepath.Join(tmpDir, "slide.png")
+	if err := createTestImage(imgPath, 160, 120, color.RGBA{0, 0, 0, 255}); err != nil {
+		t.Fatalf("Failed to create test image: %v", err)
+	}
+
+	var messages []string
+	SetLogCallback(LogInfo, func(level LogLevel, message string) {
+		messages = append(messages, message)
+	})
+	defer SetLogCallback(LogError, nil)
+
+	entries := []SlideEntry{{Path: imgPath, DurationMs: 100}}
+	if err := Slideshow(entries, filepath.Join(tmpDir, "output.webm"), ContainerWebM, CodecAV1, 50, ""); err != nil {
+		t.Fatalf("Slideshow failed: %v", err)
+	}
+
+	if len(messages) == 0 {
+		t.Fatal("No log messages received")
+	}
+}
+
 func TestVersion(t *testing.T) {
 	version := Version()
 	if version == "" {
@@ -0,0 +1,38 @@
+package minmpeg
+
+/*
+#include "../include/minmpeg.h"
+*/
+import "C"
+import "runtime"
+
+// EncoderPool keeps warm encoders for reuse across many short operations
+type EncoderPool struct {
+	ptr *C.EncoderPool
+}
+
+// NewEncoderPool creates a pool keeping up to maxIdle idle encoders per codec and
+// configuration (0 for the default of 4)
+func NewEncoderPool(maxIdle uint32) *EncoderPool {
+	p := &EncoderPool{ptr: C.minmpeg_encoder_pool_new(C.uint32_t(maxIdle))}
+	runtime.SetFinalizer(p, (*EncoderPool).Free)
+	return p
+}
+
+// Free closes the idle encoders and releases the pool. Running operations keep their own reference.
+func (p *EncoderPool) Free() {
+	if p == nil || p.ptr == nil {
+		return
+	}
+	C.minmpeg_encoder_pool_free(p.ptr)
+	p.ptr = nil
+	runtime.SetFinalizer(p, nil)
+}
+
+// cPtr returns the C handle, or nil for a nil pool
+func (p *EncoderPool) cPtr() *C.EncoderPool {
+	if p == nil {
+		return nil
+	}
+	return p.ptr
+}
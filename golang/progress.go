@@ -0,0 +1,77 @@
+package minmpeg
+
+/*
+#include "../include/minmpeg.h"
+#include <stdlib.h>
+
+extern void goProgressCallback(uint64_t frames_done, uint64_t frames_total, void* user_data);
+extern void goWarningCallback(WarningCode code, char* message, void* user_data);
+*/
+import "C"
+import (
+	"runtime/cgo"
+	"unsafe"
+)
+
+// ProgressFunc receives the number of frames encoded so far and the total number of frames
+type ProgressFunc func(framesDone, framesTotal uint64)
+
+// encodeCallbacks holds the Go functions reachable through EncodeOptions.user_data
+type encodeCallbacks struct {
+	progress ProgressFunc
+	warning  WarningFunc
+}
+
+//export goProgressCallback
+func goProgressCallback(framesDone, framesTotal C.uint64_t, userData unsafe.Pointer) {
+	h := *(*cgo.Handle)(userData)
+	if callbacks, ok := h.Value().(*encodeCallbacks); ok && callbacks.progress != nil {
+		callbacks.progress(uint64(framesDone), uint64(framesTotal))
+	}
+}
+
+//export goWarningCallback
+func goWarningCallback(code C.WarningCode, message *C.char, userData unsafe.Pointer) {
+	h := *(*cgo.Handle)(userData)
+	if callbacks, ok := h.Value().(*encodeCallbacks); ok && callbacks.warning != nil {
+		callbacks.warning(Warning{Code: WarningCode(code), Message: C.GoString(message)})
+	}
+}
+
+// callbackHandle keeps a cgo.Handle in C memory so it can be stored inside C structs
+type callbackHandle struct {
+	ptr       unsafe.Pointer
+	callbacks *encodeCallbacks
+}
+
+// newCallbackHandle registers progress and warning functions so they can be passed through C
+// as user data. The returned handle must be released with Delete. Returns nil if both are nil.
+func newCallbackHandle(progress ProgressFunc, warning WarningFunc) *callbackHandle {
+	if progress == nil && warning == nil {
+		return nil
+	}
+	callbacks := &encodeCallbacks{progress: progress, warning: warning}
+	ptr := C.malloc(C.size_t(unsafe.Sizeof(cgo.Handle(0))))
+	*(*cgo.Handle)(ptr) = cgo.NewHandle(callbacks)
+	return &callbackHandle{ptr: ptr, callbacks: callbacks}
+}
+
+// Delete releases the handle and its C memory
+func (h *callbackHandle) Delete() {
+	(*(*cgo.Handle)(h.ptr)).Delete()
+	C.free(h.ptr)
+}
+
+// setCallbacks stores the C callbacks and user data for a handle created by newCallbackHandle
+func setCallbacks(cOptions *C.EncodeOptions, h *callbackHandle) {
+	if h == nil {
+		return
+	}
+	if h.callbacks.progress != nil {
+		cOptions.progress = C.ProgressCallback(C.goProgressCallback)
+	}
+	if h.callbacks.warning != nil {
+		cOptions.warning = C.WarningCallback(C.goWarningCallback)
+	}
+	cOptions.user_data = h.ptr
+}
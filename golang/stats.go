@@ -0,0 +1,84 @@
+package minmpeg
+
+/*
+#include "../include/minmpeg.h"
+*/
+import "C"
+import "time"
+
+// EncodeStats describes a finished encoding operation
+type EncodeStats struct {
+	Frames      uint64
+	Keyframes   uint64
+	OutputBytes uint64
+	// Duration of the output video
+	Duration time.Duration
+	// Bitrate is the average bitrate in bits per second
+	Bitrate uint64
+	// Backend is the encoder implementation name (e.g. "rav1e", "VideoToolbox")
+	Backend string
+	// Time spent per stage and in total
+	PrepareTime time.Duration
+	EncodeTime  time.Duration
+	MuxTime     time.Duration
+	TotalTime   time.Duration
+}
+
+// fromC copies statistics filled in by the library
+func (s *EncodeStats) fromC(c *C.EncodeStats) {
+	us := func(v C.uint64_t) time.Duration { return time.Duration(v) * time.Microsecond }
+
+	*s = EncodeStats{
+		Frames:      uint64(c.frames),
+		Keyframes:   uint64(c.keyframes),
+		OutputBytes: uint64(c.output_bytes),
+		Duration:    time.Duration(c.duration_ms) * time.Millisecond,
+		Bitrate:     uint64(c.bitrate),
+		Backend:     backendName(c.backend),
+		PrepareTime: us(c.prepare_us),
+		EncodeTime:  us(c.encode_us),
+		MuxTime:     us(c.mux_us),
+		TotalTime:   us(c.total_us),
+	}
+}
+
+// backendName returns the name used by Capabilities for an encoder backend
+func backendName(backend C.EncoderBackend) string {
+	switch backend {
+	case C.BACKEND_RAV1E:
+		return "rav1e"
+	case C.BACKEND_VIDEOTOOLBOX:
+		return "VideoToolbox"
+	case C.BACKEND_MEDIA_FOUNDATION:
+		return "Media Foundation"
+	case C.BACKEND_FFMPEG:
+		return "ffmpeg"
+	}
+	return ""
+}
+
+// QualityReport compares a verified output with its source frames
+type QualityReport struct {
+	// Frames is the number of frames compared, 0 when the output was not verified
+	Frames uint64
+	// PSNR of the RGB channels in dB, 100 for identical frames
+	MeanPSNR float64
+	MinPSNR  float64
+	// SSIM of the luma, 1 for identical frames
+	MeanSSIM float64
+	MinSSIM  float64
+	// Checksum is an FNV-1a hash of all decoded frames
+	Checksum uint64
+}
+
+// fromC copies a quality report filled in by the library
+func (q *QualityReport) fromC(c *C.QualityReport) {
+	*q = QualityReport{
+		Frames:   uint64(c.frames),
+		MeanPSNR: float64(c.mean_psnr),
+		MinPSNR:  float64(c.min_psnr),
+		MeanSSIM: float64(c.mean_ssim),
+		MinSSIM:  float64(c.min_ssim),
+		Checksum: uint64(c.checksum),
+	}
+}
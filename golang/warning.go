@@ -0,0 +1,26 @@
+package minmpeg
+
+/*
+#include "../include/minmpeg.h"
+*/
+import "C"
+
+// WarningCode identifies the kind of a non-fatal warning
+type WarningCode int
+
+const (
+	WarnDimensionsRounded  WarningCode = C.MINMPEG_WARN_DIMENSIONS_ROUNDED
+	WarnImageResized       WarningCode = C.MINMPEG_WARN_IMAGE_RESIZED
+	WarnDurationRounded    WarningCode = C.MINMPEG_WARN_DURATION_ROUNDED
+	WarnFrameRateConverted WarningCode = C.MINMPEG_WARN_FRAME_RATE_CONVERTED
+	WarnAudioIgnored       WarningCode = C.MINMPEG_WARN_AUDIO_IGNORED
+)
+
+// Warning is an issue that did not stop an operation but changed its output
+type Warning struct {
+	Code    WarningCode
+	Message string
+}
+
+// WarningFunc receives each warning raised while an operation runs
+type WarningFunc func(warning Warning)
@@ -22,6 +22,13 @@ extern "C" {
 typedef enum {
     CONTAINER_MP4 = 0,
     CONTAINER_WEBM = 1,
+    CONTAINER_WEBP = 2,            /* Lossless animated WebP, slideshows only */
+    CONTAINER_APNG = 3,            /* Lossless animated PNG, slideshows only */
+    CONTAINER_MKV = 4,             /* Matroska: AV1, H.264 or VP9 */
+    CONTAINER_HLS = 5,             /* HLS playlist with MPEG-TS segments: H.264, files only */
+    CONTAINER_IVF = 6,             /* IVF raw frames: AV1 or VP9 */
+    CONTAINER_ANNEX_B = 7,         /* Raw H.264 Annex B elementary stream */
+    CONTAINER_Y4M = 8,             /* Uncompressed YUV4MPEG2 frames, codec ignored */
 } Container;
 
 /**
@@ -30,10 +37,92 @@ typedef enum {
 typedef enum {
     CODEC_AV1 = 0,
     CODEC_H264 = 1,
+    CODEC_VP9 = 2,
 } Codec;
 
+/**
+ * Encoder implementations
+ */
+typedef enum {
+    BACKEND_RAV1E = 0,             /* rav1e software AV1 encoder */
+    BACKEND_VIDEOTOOLBOX = 1,      /* Apple VideoToolbox (macOS and iOS) */
+    BACKEND_MEDIA_FOUNDATION = 2,  /* Windows Media Foundation */
+    BACKEND_FFMPEG = 3,            /* External ffmpeg process (libx264, libvpx-vp9) */
+    BACKEND_SVT_AV1 = 4,           /* SVT-AV1 in an external ffmpeg process (libsvtav1) */
+    BACKEND_IMAGE = 5,             /* Built-in lossless encoder for animated images */
+    BACKEND_RAW = 6,               /* No encoding, uncompressed frames for Y4M output */
+    BACKEND_NVENC = 7,             /* NVIDIA NVENC in an external ffmpeg process (h264_nvenc) */
+    BACKEND_VAAPI = 8,             /* VAAPI on Intel and AMD GPUs in an external ffmpeg process (h264_vaapi) */
+} EncoderBackend;
+
+/**
+ * Scheduling priority of ffmpeg processes and encoding threads
+ */
+typedef enum {
+    PRIORITY_NORMAL = 0,           /* Priority of the calling process */
+    PRIORITY_LOW = 1,              /* Below normal (nice 10) */
+    PRIORITY_IDLE = 2,             /* Only when the machine is otherwise idle (nice 19) */
+} Priority;
+
+/**
+ * AV1 encoder implementation
+ */
+typedef enum {
+    AV1_BACKEND_RAV1E = 0,         /* rav1e, built into the library */
+    AV1_BACKEND_SVT_AV1 = 1,       /* SVT-AV1 through ffmpeg (needs the svt-av1 feature) */
+} Av1Backend;
+
+/**
+ * How the encoder holds the target bitrate or quality
+ */
+typedef enum {
+    RATE_CONTROL_AUTO = 0,         /* Average with a target bitrate, quality without one */
+    RATE_CONTROL_QUALITY = 1,      /* Constant quality, ignoring the target bitrate */
+    RATE_CONTROL_AVERAGE = 2,      /* Target bitrate on average (needs bitrate_kbps) */
+    RATE_CONTROL_CONSTANT = 3,     /* Target bitrate held steady (needs bitrate_kbps) */
+} RateControl;
+
+/**
+ * Color space frames are converted to YUV in and signalled as
+ */
+typedef enum {
+    COLOR_SPACE_BT601 = 0,         /* BT.601, as for SD video */
+    COLOR_SPACE_BT709 = 1,         /* BT.709, as for HD video */
+} ColorSpace;
+
+/**
+ * Range of the YUV values frames are converted to and signalled as
+ */
+typedef enum {
+    PIXEL_RANGE_AUTO = 0,          /* Full for Y4M, limited for encoded video */
+    PIXEL_RANGE_LIMITED = 1,       /* Luma from 16 to 235, what players expect */
+    PIXEL_RANGE_FULL = 2,          /* Every value from 0 to 255, for screenshots */
+} PixelRange;
+
+/**
+ * How slides of another aspect ratio are scaled to the video dimensions
+ */
+typedef enum {
+    SCALE_MODE_STRETCH = 0,        /* Scale each dimension on its own, distorting the image */
+    SCALE_MODE_FIT = 1,            /* Fit inside, centered with black bars */
+    SCALE_MODE_FILL = 2,           /* Cover, cropping the center */
+} ScaleMode;
+
+/**
+ * Corner or center of the frame a watermark is placed at
+ */
+typedef enum {
+    WATERMARK_TOP_LEFT = 0,
+    WATERMARK_TOP_RIGHT = 1,
+    WATERMARK_BOTTOM_LEFT = 2,
+    WATERMARK_BOTTOM_RIGHT = 3,
+    WATERMARK_CENTER = 4,
+} WatermarkPosition;
+
 /**
  * Error codes
+ *
+ * Values are stable: codes are only ever appended, never renumbered or reused.
  */
 typedef enum {
     MINMPEG_OK = 0,
@@ -43,8 +132,36 @@ typedef enum {
     MINMPEG_ERR_IO_ERROR = 4,
     MINMPEG_ERR_ENCODE_ERROR = 5,
     MINMPEG_ERR_DECODE_ERROR = 6,
+    MINMPEG_ERR_CANCELLED = 7,
+    MINMPEG_ERR_INTERNAL = 8,      /* Internal error (e.g. a caught panic) */
+    MINMPEG_ERR_MUX_ERROR = 9,     /* Writing the container failed */
+    MINMPEG_ERR_FFMPEG_ERROR = 10, /* ffmpeg/ffprobe missing or failed */
+    MINMPEG_ERR_PLATFORM_ERROR = 11, /* Platform encoder (VideoToolbox, Media Foundation) failed */
+    MINMPEG_ERR_UNSUPPORTED = 12,  /* Not supported by this build or platform */
 } ErrorCode;
 
+/**
+ * Log message severity, from most to least severe
+ */
+typedef enum {
+    MINMPEG_LOG_ERROR = 0,
+    MINMPEG_LOG_WARN = 1,
+    MINMPEG_LOG_INFO = 2,
+    MINMPEG_LOG_DEBUG = 3,
+    MINMPEG_LOG_TRACE = 4,
+} LogLevel;
+
+/**
+ * Kinds of non-fatal warnings passed to WarningCallback
+ */
+typedef enum {
+    MINMPEG_WARN_DIMENSIONS_ROUNDED = 0,     /* Odd output dimensions rounded down to even */
+    MINMPEG_WARN_IMAGE_RESIZED = 1,          /* Slide resized to the output dimensions */
+    MINMPEG_WARN_DURATION_ROUNDED = 2,       /* Slide shorter than one frame shown for one frame */
+    MINMPEG_WARN_FRAME_RATE_CONVERTED = 3,   /* Input video converted to the output frame rate */
+    MINMPEG_WARN_AUDIO_IGNORED = 4,          /* Input video's audio track dropped */
+} WarningCode;
+
 /**
  * Result structure returned by minmpeg functions
  */
@@ -53,6 +170,16 @@ typedef struct {
     char* message;  /* Error message (must be freed with minmpeg_free_result) */
 } Result;
 
+/**
+ * Structured error details returned by minmpeg_result_details
+ */
+typedef struct {
+    const char* path;          /* Input file that caused the error, or NULL */
+    int64_t frame;             /* Output frame index where the error occurred, or -1 */
+    const char* const* chain;  /* Messages of the error and its causes, outermost first */
+    size_t chain_len;          /* Number of messages in chain */
+} ErrorDetails;
+
 /**
  * Slide entry for slideshow creation
  */
@@ -61,6 +188,36 @@ typedef struct {
     uint32_t duration_ms;  /* Duration to display this image in milliseconds */
 } SlideEntry;
 
+/**
+ * Slide entry with a UTF-16 path, for minmpeg_slideshow_w
+ */
+typedef struct {
+    const uint16_t* path;  /* Path to the image file, null-terminated UTF-16 */
+    uint32_t duration_ms;  /* Duration to display this image in milliseconds */
+} SlideEntryW;
+
+/**
+ * Kind of image data carried by a SlideData entry
+ */
+typedef enum {
+    SLIDE_DATA_PATH = 0,     /* path points to an image file */
+    SLIDE_DATA_ENCODED = 1,  /* data/data_len hold an encoded image (JPEG, PNG, WebP, GIF) */
+    SLIDE_DATA_RGBA = 2,     /* data/data_len hold raw RGBA pixels of width x height */
+} SlideDataKind;
+
+/**
+ * Slide entry supplied as a path, encoded bytes, or raw RGBA pixels
+ */
+typedef struct {
+    SlideDataKind kind;
+    const char* path;      /* Image file path (SLIDE_DATA_PATH) */
+    const uint8_t* data;   /* Image bytes (SLIDE_DATA_ENCODED, SLIDE_DATA_RGBA) */
+    size_t data_len;       /* Length of data in bytes */
+    uint32_t width;        /* Pixel width (SLIDE_DATA_RGBA) */
+    uint32_t height;       /* Pixel height (SLIDE_DATA_RGBA) */
+    uint32_t duration_ms;  /* Duration to display this image in milliseconds */
+} SlideData;
+
 /**
  * RGB color
  */
@@ -70,6 +227,286 @@ typedef struct {
     uint8_t b;
 } Color;
 
+/**
+ * Grid layout for minmpeg_juxtapose_n
+ *
+ * Videos fill the grid left to right, then top to bottom.
+ */
+typedef struct {
+    uint32_t columns;  /* Number of columns (0 for a single row) */
+} Layout;
+
+/**
+ * Video file information returned by minmpeg_probe
+ *
+ * Set struct_size to sizeof(VideoInfo) before calling minmpeg_probe. New
+ * fields are only ever appended, and only the fields within struct_size are
+ * written.
+ */
+typedef struct {
+    uint32_t struct_size;  /* sizeof(VideoInfo) */
+    uint32_t width;        /* Frame width in pixels */
+    uint32_t height;       /* Frame height in pixels */
+    double fps;            /* Frame rate */
+    uint64_t duration_ms;  /* Duration in milliseconds */
+    uint64_t frame_count;  /* Number of frames (estimated if not stored) */
+    char codec[32];        /* Null-terminated codec name (e.g. "av1", "h264") */
+    char container[32];    /* Null-terminated container format names as reported by
+                              ffprobe (e.g. "matroska,webm"), or for MP4 and its
+                              relatives the one named by its brand (e.g. "mp4") */
+} VideoInfo;
+
+/**
+ * Opaque cancellation token
+ *
+ * Created with minmpeg_cancel_token_new and freed with minmpeg_cancel_token_free.
+ */
+typedef struct CancelToken CancelToken;
+
+/**
+ * Opaque pool of reusable encoders
+ *
+ * Created with minmpeg_encoder_pool_new and freed with minmpeg_encoder_pool_free.
+ */
+typedef struct EncoderPool EncoderPool;
+
+/**
+ * Opaque cache of decoded and resized slide images
+ *
+ * Created with minmpeg_image_cache_new and freed with minmpeg_image_cache_free.
+ */
+typedef struct ImageCache ImageCache;
+
+/**
+ * Opaque asynchronous job
+ *
+ * Created with a minmpeg_submit_* function and freed with minmpeg_job_free.
+ */
+typedef struct Job Job;
+
+/**
+ * Progress callback
+ *
+ * Called synchronously on the calling thread after each encoded frame.
+ *
+ * @param frames_done   Number of frames encoded so far
+ * @param frames_total  Total number of frames to encode
+ * @param user_data     User data pointer passed to the *_with_progress function
+ */
+typedef void (*ProgressCallback)(uint64_t frames_done, uint64_t frames_total, void* user_data);
+
+/**
+ * Warning callback
+ *
+ * Called on the thread running the operation for issues that do not stop it.
+ *
+ * @param code          Kind of warning
+ * @param message       Null-terminated description, valid only during the call
+ * @param user_data     User data pointer from EncodeOptions
+ */
+typedef void (*WarningCallback)(WarningCode code, const char* message, void* user_data);
+
+/**
+ * Byte buffer owned by the library
+ *
+ * Returned by the *_to_buffer functions; free with minmpeg_buffer_free.
+ */
+typedef struct {
+    uint8_t* data;
+    size_t len;
+} Buffer;
+
+/**
+ * Codec/container combination available on this machine
+ */
+typedef struct {
+    Codec codec;
+    Container container;
+    EncoderBackend backend;
+    const char* backend_name;  /* Static backend name (e.g. "rav1e") */
+    uint8_t hardware;          /* Non-zero if the backend uses hardware acceleration */
+} Capability;
+
+/**
+ * Array of capabilities returned by minmpeg_capabilities
+ */
+typedef struct {
+    Capability* items;
+    size_t len;
+} Capabilities;
+
+/**
+ * Statistics of a finished operation
+ *
+ * Filled on success when EncodeOptions.stats is set. Times are in microseconds.
+ */
+typedef struct {
+    uint64_t frames;               /* Frames encoded */
+    uint64_t keyframes;            /* Keyframes in the output */
+    uint64_t output_bytes;         /* Size of the output in bytes */
+    uint64_t duration_ms;          /* Duration of the output video */
+    uint64_t bitrate;              /* Average bitrate in bits per second */
+    uint64_t prepare_us;           /* Loading images or opening input videos */
+    uint64_t encode_us;            /* Producing and encoding frames */
+    uint64_t mux_us;               /* Writing the container */
+    uint64_t total_us;             /* Whole operation */
+    EncoderBackend backend;        /* Encoder implementation used */
+} EncodeStats;
+
+/**
+ * Comparison of an output with its source frames
+ *
+ * Filled on success when EncodeOptions.quality_report is set. Slideshows
+ * written to a file are decoded with ffmpeg and compared with their slides;
+ * frames is 0 when the output was not verified.
+ */
+typedef struct {
+    uint64_t frames;               /* Frames compared */
+    double mean_psnr;              /* Mean PSNR of the RGB channels in dB (100 for identical frames) */
+    double min_psnr;               /* PSNR of the worst frame in dB */
+    double mean_ssim;              /* Mean SSIM of the luma (1 for identical frames) */
+    double min_ssim;               /* SSIM of the worst frame */
+    uint64_t checksum;             /* FNV-1a hash of all decoded frames */
+} QualityReport;
+
+/**
+ * Encoding options for the *_ex functions
+ *
+ * Always initialize with minmpeg_encode_options_init before setting fields.
+ * New fields are only appended; struct_size tells the library which fields
+ * the caller knows about.
+ */
+typedef struct {
+    uint32_t struct_size;          /* sizeof(EncodeOptions), set by minmpeg_encode_options_init */
+    const char* output_path;       /* Path to the output video file */
+    Container container;           /* Container format (default WebM) */
+    Codec codec;                   /* Video codec (default AV1) */
+    uint8_t quality;               /* Quality 0-100 (default 50) */
+    const char* ffmpeg_path;       /* Optional path to ffmpeg, NULL for PATH */
+    ProgressCallback progress;     /* Progress callback (NULL for none) */
+    void* user_data;               /* User data passed to the progress and warning callbacks */
+    const CancelToken* cancel;     /* Cancellation token (NULL for none) */
+    WarningCallback warning;       /* Warning callback (NULL for none) */
+    EncodeStats* stats;            /* Receives statistics on success (NULL for none, not filled by jobs) */
+    int32_t strict;                /* Non-zero to check all inputs before encoding starts */
+    uint64_t max_duration_ms;      /* Longest allowed output in strict mode (0 for no limit) */
+    uint64_t memory_limit;         /* Most bytes for images, buffered frames and packets (0 for no limit) */
+    const EncoderPool* encoder_pool; /* Reuse encoders from this pool (NULL to create one per call) */
+    const ImageCache* image_cache; /* Reuse slide images from this cache (NULL to load them per call) */
+    int32_t deterministic;         /* Non-zero for byte-identical output from identical inputs */
+    QualityReport* quality_report; /* Verifies the output and receives the result (NULL to skip) */
+    const char* spill_dir;         /* Keep slide images in temporary files here (NULL for memory) */
+    Priority priority;             /* Priority of ffmpeg processes and encoding threads (default normal) */
+    Av1Backend av1_backend;        /* Encoder for AV1 (default rav1e) */
+    ScaleMode scale_mode;          /* Scaling of slides to the video dimensions (default stretch) */
+    Color letterbox_color;         /* Bars around slides fitted with SCALE_MODE_FIT (default black) */
+    const char* watermark_path;    /* Image drawn over every frame (NULL for none) */
+    WatermarkPosition watermark_position; /* Where the watermark is drawn (default bottom right) */
+    float watermark_opacity;       /* Opacity of the watermark from 0 to 1 (default 1) */
+    uint32_t watermark_margin;     /* Pixels between the watermark and the frame edges (default 16) */
+    uint32_t fade_in_ms;           /* Fade from fade_color at the start of videos (0 for none) */
+    uint32_t fade_out_ms;          /* Fade to fade_color at the end of videos (0 for none) */
+    Color fade_color;              /* Color videos fade from and to (default black) */
+    uint32_t bitrate_kbps;         /* Target bitrate in kbps instead of quality (0 for quality) */
+    RateControl rate_control;      /* How the bitrate or quality is held (default auto) */
+    uint32_t keyframe_interval_ms; /* Milliseconds between keyframes (0 for the encoder default) */
+    ColorSpace color_space;        /* Color space of the video (default BT.601) */
+    PixelRange pixel_range;        /* Range of the YUV values (default auto) */
+    uint32_t threads;              /* Threads encoders and ffmpeg may use (0 for the runtime setting) */
+} EncodeOptions;
+
+/**
+ * Initialize encoding options with defaults
+ *
+ * @param options   Options to initialize
+ */
+void minmpeg_encode_options_init(EncodeOptions* options);
+
+/**
+ * Log callback
+ *
+ * May be called from any thread running a minmpeg operation.
+ *
+ * @param level         Message severity
+ * @param message       Null-terminated message, valid only during the call
+ * @param user_data     User data pointer passed to minmpeg_set_log_callback
+ */
+typedef void (*LogCallback)(LogLevel level, const char* message, void* user_data);
+
+/**
+ * Register a process-wide log callback
+ *
+ * Routes internal diagnostics (encoder selection, ffmpeg invocations,
+ * warnings) to the host application.
+ *
+ * @param callback      Log callback (NULL to disable logging)
+ * @param user_data     User data passed to the callback
+ * @param level         Most verbose level to deliver
+ */
+void minmpeg_set_log_callback(LogCallback callback, void* user_data, LogLevel level);
+
+/**
+ * Create a new cancellation token
+ *
+ * @return  Token handle (must be freed with minmpeg_cancel_token_free)
+ */
+CancelToken* minmpeg_cancel_token_new(void);
+
+/**
+ * Request cancellation of every operation using the token
+ *
+ * May be called from any thread. Running operations stop at the next frame
+ * and return MINMPEG_ERR_CANCELLED.
+ *
+ * @param token     Token to cancel (NULL is ignored)
+ */
+void minmpeg_cancel_token_cancel(const CancelToken* token);
+
+/**
+ * Free a cancellation token
+ *
+ * @param token     Token to free (NULL is ignored)
+ */
+void minmpeg_cancel_token_free(CancelToken* token);
+
+/**
+ * Create a pool of reusable encoders
+ *
+ * Operations given the pool in EncodeOptions take a warm encoder for their
+ * codec, dimensions, frame rate and quality instead of starting a new
+ * session, and return it when done. May be shared by concurrent operations.
+ *
+ * @param max_idle  Most idle encoders kept per configuration (0 for 4)
+ * @return  Pool handle (must be freed with minmpeg_encoder_pool_free)
+ */
+EncoderPool* minmpeg_encoder_pool_new(uint32_t max_idle);
+
+/**
+ * Free an encoder pool, closing its idle encoders
+ *
+ * @param pool      Pool to free (NULL is ignored)
+ */
+void minmpeg_encoder_pool_free(EncoderPool* pool);
+
+/**
+ * Create a cache of decoded and resized slide images
+ *
+ * Slideshows given the cache in EncodeOptions decode and resize an image
+ * file once, keyed by path, modification time and size, so a logo shown by
+ * many slides or videos is loaded once. May be shared by concurrent
+ * operations. Images stay cached until the cache is freed.
+ *
+ * @return  Cache handle (must be freed with minmpeg_image_cache_free)
+ */
+ImageCache* minmpeg_image_cache_new(void);
+
+/**
+ * Free an image cache, dropping its images
+ *
+ * @param cache     Cache to free (NULL is ignored)
+ */
+void minmpeg_image_cache_free(ImageCache* cache);
+
 /**
  * Check if a codec is available on this system
  *
@@ -80,6 +517,22 @@ typedef struct {
  */
 Result minmpeg_available(Codec codec, const char* ffmpeg_path);
 
+/**
+ * List the codec/container/backend combinations available on this machine
+ *
+ * @param ffmpeg_path   Optional path to ffmpeg (for H.264 on Linux), NULL for PATH
+ * @param out           Output array (free with minmpeg_capabilities_free)
+ * @return              Result with code MINMPEG_OK on success
+ */
+Result minmpeg_capabilities(const char* ffmpeg_path, Capabilities* out);
+
+/**
+ * Free an array returned by minmpeg_capabilities
+ *
+ * @param caps      Pointer to the array to free
+ */
+void minmpeg_capabilities_free(Capabilities* caps);
+
 /**
  * Create a slideshow video from a sequence of images
  *
@@ -105,6 +558,118 @@ Result minmpeg_slideshow(
     const char* ffmpeg_path
 );
 
+/**
+ * Create a slideshow video, reporting progress
+ *
+ * Same as minmpeg_slideshow, with an additional progress callback
+ * and cancellation token.
+ *
+ * @param progress      Progress callback (NULL for none)
+ * @param user_data     User data passed to the progress callback
+ * @param cancel        Cancellation token (NULL for none)
+ */
+Result minmpeg_slideshow_with_progress(
+    const SlideEntry* entries,
+    size_t entry_count,
+    const char* output_path,
+    Container container,
+    Codec codec,
+    uint8_t quality,
+    const char* ffmpeg_path,
+    ProgressCallback progress,
+    void* user_data,
+    const CancelToken* cancel
+);
+
+/**
+ * Create a slideshow video using an options struct
+ *
+ * @param entries       Array of slide entries
+ * @param entry_count   Number of entries in the array
+ * @param options       Encoding options (initialized with minmpeg_encode_options_init)
+ * @return              Result with code MINMPEG_OK on success
+ */
+Result minmpeg_slideshow_ex(
+    const SlideEntry* entries,
+    size_t entry_count,
+    const EncodeOptions* options
+);
+
+/**
+ * Create a slideshow video in memory
+ *
+ * Same as minmpeg_slideshow_ex, but the encoded video is returned in `out`
+ * instead of being written to a file. options->output_path is ignored.
+ *
+ * @param out           Output buffer (free with minmpeg_buffer_free)
+ */
+Result minmpeg_slideshow_to_buffer(
+    const SlideEntry* entries,
+    size_t entry_count,
+    const EncodeOptions* options,
+    Buffer* out
+);
+
+/**
+ * One slideshow of a batch
+ */
+typedef struct {
+    const SlideEntry* entries;     /* Array of slide entries */
+    size_t entry_count;            /* Number of entries in the array */
+    const EncodeOptions* options;  /* Encoding options (initialized with minmpeg_encode_options_init) */
+} SlideshowJob;
+
+/**
+ * Create many slideshow videos, running up to `concurrency` at once
+ *
+ * Jobs given the same options->image_cache decode an image shown by several
+ * of them once. A failed job does not stop the others. Callbacks in each
+ * job's options are called on worker threads.
+ *
+ * @param jobs          Array of jobs
+ * @param job_count     Number of jobs in the array
+ * @param concurrency   Most jobs running at once (0 for 1)
+ * @param results       Receives the result of each job (free each with minmpeg_free_result)
+ * @return              Result with code MINMPEG_OK unless the arguments are invalid
+ */
+Result minmpeg_slideshow_batch(
+    const SlideshowJob* jobs,
+    size_t job_count,
+    uint32_t concurrency,
+    Result* results
+);
+
+/**
+ * Create a slideshow video from slides supplied in memory
+ *
+ * Same as minmpeg_slideshow_ex, but each slide may be a path, encoded image
+ * bytes, or raw RGBA pixels. Image data is copied during the call.
+ *
+ * @param entries       Array of slide data entries
+ * @param entry_count   Number of entries in the array
+ * @param options       Encoding options (initialized with minmpeg_encode_options_init)
+ * @return              Result with code MINMPEG_OK on success
+ */
+Result minmpeg_slideshow_from_data(
+    const SlideData* entries,
+    size_t entry_count,
+    const EncodeOptions* options
+);
+
+/**
+ * Create a slideshow video in memory from slides supplied in memory
+ *
+ * Combines minmpeg_slideshow_from_data and minmpeg_slideshow_to_buffer.
+ *
+ * @param out           Output buffer (free with minmpeg_buffer_free)
+ */
+Result minmpeg_slideshow_from_data_to_buffer(
+    const SlideData* entries,
+    size_t entry_count,
+    const EncodeOptions* options,
+    Buffer* out
+);
+
 /**
  * Combine two videos side by side
  *
@@ -137,6 +702,318 @@ Result minmpeg_juxtapose(
     const char* ffmpeg_path
 );
 
+/**
+ * Combine two videos side by side, reporting progress
+ *
+ * Same as minmpeg_juxtapose, with an additional progress callback
+ * and cancellation token.
+ *
+ * @param progress      Progress callback (NULL for none)
+ * @param user_data     User data passed to the progress callback
+ * @param cancel        Cancellation token (NULL for none)
+ */
+Result minmpeg_juxtapose_with_progress(
+    const char* left_path,
+    const char* right_path,
+    const char* output_path,
+    Container container,
+    Codec codec,
+    uint8_t quality,
+    const Color* background,
+    const char* ffmpeg_path,
+    ProgressCallback progress,
+    void* user_data,
+    const CancelToken* cancel
+);
+
+/**
+ * Combine two videos side by side using an options struct
+ *
+ * @param left_path     Path to the left video file
+ * @param right_path    Path to the right video file
+ * @param background    Background color for padding (NULL for white)
+ * @param options       Encoding options (initialized with minmpeg_encode_options_init)
+ * @return              Result with code MINMPEG_OK on success
+ */
+Result minmpeg_juxtapose_ex(
+    const char* left_path,
+    const char* right_path,
+    const Color* background,
+    const EncodeOptions* options
+);
+
+/**
+ * Combine two videos side by side in memory
+ *
+ * Same as minmpeg_juxtapose_ex, but the encoded video is returned in `out`
+ * instead of being written to a file. options->output_path is ignored.
+ *
+ * @param out           Output buffer (free with minmpeg_buffer_free)
+ */
+Result minmpeg_juxtapose_to_buffer(
+    const char* left_path,
+    const char* right_path,
+    const Color* background,
+    const EncodeOptions* options,
+    Buffer* out
+);
+
+/**
+ * Combine any number of videos into a grid
+ *
+ * Each column is as wide as its widest video and each row as tall as its
+ * tallest. Videos are aligned to the top left of their cell, with the
+ * background color filling the rest. Shorter videos hold their last frame.
+ *
+ * @param paths         Array of video file paths
+ * @param path_count    Number of paths
+ * @param layout        Grid layout (NULL for a single row)
+ * @param background    Background color for padding (NULL for white)
+ * @param options       Encoding options (initialized with minmpeg_encode_options_init)
+ * @return              Result with code MINMPEG_OK on success
+ */
+Result minmpeg_juxtapose_n(
+    const char* const* paths,
+    size_t path_count,
+    const Layout* layout,
+    const Color* background,
+    const EncodeOptions* options
+);
+
+/**
+ * Combine any number of videos into a grid in memory
+ *
+ * Same as minmpeg_juxtapose_n, but the encoded video is returned in `out`
+ * instead of being written to a file. options->output_path is ignored.
+ *
+ * @param out           Output buffer (free with minmpeg_buffer_free)
+ */
+Result minmpeg_juxtapose_n_to_buffer(
+    const char* const* paths,
+    size_t path_count,
+    const Layout* layout,
+    const Color* background,
+    const EncodeOptions* options,
+    Buffer* out
+);
+
+/**
+ * Re-encode a video
+ *
+ * The output has the input's dimensions (rounded down to even) at 30 fps,
+ * with the container, codec and quality in options.
+ *
+ * @param input_path    Path to the input video file
+ * @param options       Encoding options (initialized with minmpeg_encode_options_init)
+ * @return              Result with code MINMPEG_OK on success
+ */
+Result minmpeg_transcode(const char* input_path, const EncodeOptions* options);
+
+/**
+ * Re-encode the part of a video between start_ms and end_ms
+ *
+ * @param input_path    Path to the input video file
+ * @param start_ms      Start time in milliseconds
+ * @param end_ms        End time in milliseconds (0 for the end of the video)
+ * @param options       Encoding options (initialized with minmpeg_encode_options_init)
+ * @return              Result with code MINMPEG_OK on success
+ */
+Result minmpeg_trim(
+    const char* input_path,
+    uint64_t start_ms,
+    uint64_t end_ms,
+    const EncodeOptions* options
+);
+
+/**
+ * Join videos one after another
+ *
+ * The output has the first video's dimensions; other videos are scaled to
+ * fit and letterboxed in black.
+ *
+ * @param paths         Array of video file paths
+ * @param path_count    Number of paths
+ * @param options       Encoding options (initialized with minmpeg_encode_options_init)
+ * @return              Result with code MINMPEG_OK on success
+ */
+Result minmpeg_concat(
+    const char* const* paths,
+    size_t path_count,
+    const EncodeOptions* options
+);
+
+/**
+ * Save the frame shown at time_ms as an image
+ *
+ * The image format is chosen from the extension of output_path (e.g. .png, .jpg).
+ * Times past the end of the video give the last frame.
+ *
+ * @param input_path    Path to the input video file
+ * @param time_ms       Time of the frame in milliseconds
+ * @param output_path   Path to the output image file
+ * @param ffmpeg_path   Optional path to ffmpeg, NULL for PATH
+ * @return              Result with code MINMPEG_OK on success
+ */
+Result minmpeg_thumbnail(
+    const char* input_path,
+    uint64_t time_ms,
+    const char* output_path,
+    const char* ffmpeg_path
+);
+
+/**
+ * Frame callback for minmpeg_encode_frames
+ *
+ * @param frame_index   Index of the frame to draw, starting at 0
+ * @param rgba          Zeroed RGBA buffer to fill (width * height * 4 bytes)
+ * @param rgba_len      Size of rgba in bytes
+ * @param user_data     User data pointer passed to minmpeg_encode_frames
+ * @return              0 to continue, non-zero to stop with MINMPEG_ERR_CANCELLED
+ */
+typedef int32_t (*FrameCallback)(uint64_t frame_index, uint8_t* rgba, size_t rgba_len, void* user_data);
+
+/**
+ * Encode frames drawn by a callback
+ *
+ * The callback is called on the calling thread for each frame in order.
+ *
+ * @param width         Frame width in pixels (must be even)
+ * @param height        Frame height in pixels (must be even)
+ * @param fps           Frames per second
+ * @param frame_count   Number of frames to encode
+ * @param render        Callback drawing each frame
+ * @param user_data     User data passed to the callback
+ * @param options       Encoding options (initialized with minmpeg_encode_options_init)
+ * @return              Result with code MINMPEG_OK on success
+ */
+Result minmpeg_encode_frames(
+    uint32_t width,
+    uint32_t height,
+    uint32_t fps,
+    uint64_t frame_count,
+    FrameCallback render,
+    void* user_data,
+    const EncodeOptions* options
+);
+
+/**
+ * Encode frames drawn by a callback in memory
+ *
+ * Same as minmpeg_encode_frames, but the encoded video is returned in out.
+ * options->output_path is ignored and may be NULL.
+ *
+ * @param out           Output buffer (free with minmpeg_buffer_free)
+ */
+Result minmpeg_encode_frames_to_buffer(
+    uint32_t width,
+    uint32_t height,
+    uint32_t fps,
+    uint64_t frame_count,
+    FrameCallback render,
+    void* user_data,
+    const EncodeOptions* options,
+    Buffer* out
+);
+
+/**
+ * Start a slideshow on a background thread
+ *
+ * Takes the same arguments as minmpeg_slideshow_ex. All inputs are copied
+ * before returning; input errors are reported by minmpeg_job_result.
+ * The progress callback, if any, is called on the job's thread.
+ *
+ * @return              Job handle (free with minmpeg_job_free)
+ */
+Job* minmpeg_submit_slideshow(
+    const SlideEntry* entries,
+    size_t entry_count,
+    const EncodeOptions* options
+);
+
+/**
+ * Start a side-by-side juxtaposition on a background thread
+ *
+ * Takes the same arguments as minmpeg_juxtapose_ex. All inputs are copied
+ * before returning; input errors are reported by minmpeg_job_result.
+ * The progress callback, if any, is called on the job's thread.
+ *
+ * @return              Job handle (free with minmpeg_job_free)
+ */
+Job* minmpeg_submit_juxtapose(
+    const char* left_path,
+    const char* right_path,
+    const Color* background,
+    const EncodeOptions* options
+);
+
+/**
+ * Check whether a job has finished
+ *
+ * @param job           Job handle
+ * @return              1 if the job has finished, 0 if it is still running
+ */
+int32_t minmpeg_job_poll(const Job* job);
+
+/**
+ * Block until a job has finished
+ *
+ * @param job           Job handle
+ */
+void minmpeg_job_wait(const Job* job);
+
+/**
+ * Get the result of a finished job
+ *
+ * @param job           Job handle
+ * @return              Result of the job (MINMPEG_ERR_INVALID_INPUT if still running)
+ */
+Result minmpeg_job_result(const Job* job);
+
+/**
+ * Request cancellation of a running job
+ *
+ * The job finishes with MINMPEG_ERR_CANCELLED at the next frame.
+ *
+ * @param job           Job handle
+ */
+void minmpeg_job_cancel(const Job* job);
+
+/**
+ * Free a job handle, cancelling the job if it is still running
+ *
+ * @param job           Job handle
+ */
+void minmpeg_job_free(Job* job);
+
+/**
+ * Probe a video file for its dimensions, frame rate, duration, codec and container
+ *
+ * Uses ffprobe located next to ffmpeg.
+ *
+ * @param path          Path to the video file
+ * @param ffmpeg_path   Optional path to ffmpeg, NULL for PATH
+ * @param info          Output video information, with struct_size set
+ * @return              Result with code MINMPEG_OK on success
+ */
+Result minmpeg_probe(const char* path, const char* ffmpeg_path, VideoInfo* info);
+
+/**
+ * Start platform runtimes ahead of time (COM and Media Foundation on Windows)
+ *
+ * Optional. Encoders start and stop the runtimes on demand; after minmpeg_init
+ * they stay running until minmpeg_shutdown. No-op on other platforms.
+ *
+ * @return              Result with code MINMPEG_OK on success
+ */
+Result minmpeg_init(void);
+
+/**
+ * Release platform runtimes kept by minmpeg_init
+ *
+ * Encoders still running keep the runtimes alive until they finish.
+ */
+void minmpeg_shutdown(void);
+
 /**
  * Free resources associated with a Result
  *
@@ -146,6 +1023,91 @@ Result minmpeg_juxtapose(
  */
 void minmpeg_free_result(Result* result);
 
+/**
+ * Get structured details of an error result
+ *
+ * The strings in `out` stay valid until minmpeg_free_result is called on `result`.
+ *
+ * @param result    Result returned by a minmpeg function
+ * @param out       Output details (cleared if none are available)
+ * @return          1 if details are available, 0 otherwise
+ */
+int32_t minmpeg_result_details(const Result* result, ErrorDetails* out);
+
+/**
+ * Check if the operation that returned an error result may succeed when retried
+ *
+ * Transient failures include helper processes that could not be started for lack of
+ * resources and hardware encoders in use by other processes.
+ *
+ * @param result    Result returned by a minmpeg function
+ * @return          1 if retrying may succeed, 0 otherwise
+ */
+int32_t minmpeg_result_retryable(const Result* result);
+
+/**
+ * Free a buffer returned by a *_to_buffer function
+ *
+ * @param buffer    Pointer to the buffer to free
+ */
+void minmpeg_buffer_free(Buffer* buffer);
+
+/**
+ * Convert a null-terminated UTF-16 string (e.g. a Windows wide path) to UTF-8
+ *
+ * All path parameters take UTF-8. Hosts holding wide paths convert them with
+ * this function instead of the ANSI code page. Paths longer than MAX_PATH
+ * are supported for files the library opens itself; videos read through
+ * ffmpeg or ffprobe (juxtapose, probe, transcode) are limited by the ffmpeg
+ * build.
+ *
+ * @param wide          UTF-16 string
+ * @return              UTF-8 string (free with minmpeg_string_free), or NULL if invalid
+ *                      (such paths can be passed to minmpeg_slideshow_w instead)
+ */
+char* minmpeg_utf16_to_utf8(const uint16_t* wide);
+
+/**
+ * Free a string returned by minmpeg_utf16_to_utf8
+ *
+ * @param s             String to free
+ */
+void minmpeg_string_free(char* s);
+
+/**
+ * Create a slideshow video, with UTF-16 paths
+ *
+ * Same as minmpeg_slideshow_ex, but paths are taken as Windows wide strings,
+ * including those with unpaired surrogates that minmpeg_utf16_to_utf8 cannot
+ * convert. options->output_path is ignored. An output path that is not valid
+ * Unicode cannot be used for HLS, and its output is not verified.
+ *
+ * @param output_path   Output file path, null-terminated UTF-16
+ */
+Result minmpeg_slideshow_w(
+    const SlideEntryW* entries,
+    size_t entry_count,
+    const uint16_t* output_path,
+    const EncodeOptions* options
+);
+
+/**
+ * Combine two videos side by side, with UTF-16 paths
+ *
+ * Same as minmpeg_juxtapose_ex, but paths are taken as Windows wide strings.
+ * options->output_path is ignored. An output path that is not valid Unicode
+ * cannot be used for HLS.
+ *
+ * @param output_path   Output file path, null-terminated UTF-16
+ */
+Result minmpeg_juxtapose_w(
+    const uint16_t* left_path,
+    const uint16_t* right_path,
+    const Color* background,
+    const uint16_t* output_path,
+    const EncodeOptions* options
+);
+
 /**
  * Get the library version string
  *
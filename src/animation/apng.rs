@@ -0,0 +1,112 @@
+//! Lossless animated PNG writer
+
+use super::AnimationWriter;
+use crate::muxer::MemoryOutput;
+use crate::{Error, Result};
+use png::{BitDepth, ColorType, Encoder, Writer};
+use std::io::Write;
+
+/// Denominators tried for frame delays, finest first
+const DELAY_DENOMINATORS: [u16; 4] = [1000, 100, 10, 1];
+
+/// Animated PNG whose frames each cover the whole image, with transparency
+pub(crate) struct ApngWriter {
+    writer: Writer<MemoryOutput>,
+    /// Bytes written by `writer`
+    output: MemoryOutput,
+}
+
+impl ApngWriter {
+    /// Start an animation of exactly `frames` frames
+    pub fn new(width: u32, height: u32, frames: u32) -> Result<Self> {
+        let output = MemoryOutput::new();
+        let mut encoder = Encoder::new(output.clone(), width, height);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+        // Loop forever
+        encoder.set_animated(frames, 0).map_err(png_error)?;
+        let writer = encoder.write_header().map_err(png_error)?;
+        Ok(Self { writer, output })
+    }
+}
+
+impl AnimationWriter for ApngWriter {
+    fn add_frame(&mut self, data: &[u8], duration_ms: u32) -> Result<()> {
+        let (numerator, denominator) = frame_delay(duration_ms)?;
+        self.writer
+            .set_frame_delay(numerator, denominator)
+            .map_err(png_error)?;
+        self.writer.write_image_data(data).map_err(png_error)
+    }
+
+    fn finish(self: Box<Self>, output: &mut dyn Write) -> Result<()> {
+        self.writer.finish().map_err(png_error)?;
+        output.write_all(&self.output.take())?;
+        Ok(())
+    }
+}
+
+/// Delay of a frame as a fraction of a second, exact to the millisecond where it fits
+fn frame_delay(duration_ms: u32) -> Result<(u16, u16)> {
+    DELAY_DENOMINATORS
+        .iter()
+        .find_map(|&denominator| {
+            let scaled = duration_ms as u64 * denominator as u64;
+            // Rounded to the nearest unit of the denominator
+            let numerator = (scaled + 500) / 1000;
+            u16::try_from(numerator)
+                .ok()
+                .map(|numerator| (numerator, denominator))
+        })
+        .ok_or_else(|| {
+            Error::InvalidInput(format!(
+                "APNG frames last at most {} s, not {} ms",
+                u16::MAX,
+                duration_ms
+            ))
+        })
+}
+
+fn png_error(e: png::EncodingError) -> Error {
+    Error::Encode(format!("Failed to encode APNG frame: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use png::Decoder;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_frame_delay() {
+        assert_eq!(frame_delay(250).unwrap(), (250, 1000));
+        assert_eq!(frame_delay(65535).unwrap(), (65535, 1000));
+        assert_eq!(frame_delay(70_004).unwrap(), (7000, 100));
+        assert_eq!(frame_delay(3_600_000).unwrap(), (36000, 10));
+        assert!(frame_delay(u32::MAX).is_err());
+    }
+
+    #[test]
+    fn test_apng_round_trip() {
+        let mut writer = Box::new(ApngWriter::new(3, 2, 2).unwrap());
+        let clear = [0, 0, 255, 0].repeat(6);
+        let red = [255, 0, 0, 128].repeat(6);
+        writer.add_frame(&clear, 400).unwrap();
+        writer.add_frame(&red, 1000).unwrap();
+
+        let mut output = Vec::new();
+        writer.finish(&mut output).unwrap();
+
+        let mut reader = Decoder::new(Cursor::new(output)).read_info().unwrap();
+        let animation = reader.info().animation_control.unwrap();
+        assert_eq!((animation.num_frames, animation.num_plays), (2, 0));
+
+        let mut frame = vec![0u8; reader.output_buffer_size().unwrap()];
+        for (expected, delay) in [(&clear, 400), (&red, 1000)] {
+            reader.next_frame(&mut frame).unwrap();
+            let control = reader.info().frame_control.unwrap();
+            assert_eq!((control.delay_num, control.delay_den), (delay, 1000));
+            assert_eq!(&frame, expected);
+        }
+    }
+}
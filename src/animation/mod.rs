@@ -0,0 +1,66 @@
+//! Animated image output, showing each slide as one frame
+
+mod apng;
+mod webp;
+
+use crate::logging::trace_span;
+use crate::muxer::MemoryOutput;
+use crate::{Container, EncodeOptions, Error, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// Encoder of an animated image, holding the encoded frames until it is written
+pub(crate) trait AnimationWriter {
+    /// Encode a frame of RGBA `data` shown for `duration_ms`
+    fn add_frame(&mut self, data: &[u8], duration_ms: u32) -> Result<()>;
+
+    /// Write the animation to `output`
+    fn finish(self: Box<Self>, output: &mut dyn Write) -> Result<()>;
+}
+
+/// Create a writer for an animated image container, to be given exactly `frames` frames
+pub(crate) fn create_writer(
+    container: Container,
+    width: u32,
+    height: u32,
+    frames: u32,
+) -> Result<Box<dyn AnimationWriter>> {
+    match container {
+        Container::WebP => Ok(Box::new(webp::WebpWriter::new(width, height)?)),
+        Container::Apng => Ok(Box::new(apng::ApngWriter::new(width, height, frames)?)),
+        Container::Mp4
+        | Container::WebM
+        | Container::Mkv
+        | Container::Hls
+        | Container::Ivf
+        | Container::AnnexB
+        | Container::Y4m => Err(Error::Internal(format!(
+            "{:?} is not an animated image container",
+            container
+        ))),
+    }
+}
+
+/// Write an animation to `memory` if set or to `options.output_path` otherwise
+///
+/// Returns the size of the output in bytes.
+pub(crate) fn write_animation(
+    options: &EncodeOptions,
+    memory: Option<&MemoryOutput>,
+    writer: Box<dyn AnimationWriter>,
+) -> Result<u64> {
+    let _span = trace_span!("mux");
+
+    match memory {
+        Some(output) => {
+            writer.finish(&mut output.clone())?;
+            Ok(output.len() as u64)
+        }
+        None => {
+            let mut file = BufWriter::new(File::create(&options.output_path)?);
+            writer.finish(&mut file)?;
+            file.flush()?;
+            Ok(std::fs::metadata(&options.output_path)?.len())
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Lossless animated WebP writer
+
+use super::AnimationWriter;
+use crate::{Error, Result};
+use image_webp::{ColorType, WebPEncoder};
+use std::io::Write;
+
+/// Largest width and height of a lossless WebP image
+const MAX_DIMENSION: u32 = 16384;
+
+/// Longest frame duration, stored in 24 bits
+const MAX_DURATION_MS: u32 = 0xff_ffff;
+
+/// Size of the RIFF header before the first chunk of a WebP file
+const RIFF_HEADER: usize = 12;
+
+/// VP8X flags: alpha channel and animation
+const FLAG_ALPHA: u8 = 0x10;
+const FLAG_ANIMATION: u8 = 0x02;
+
+/// ANMF flags: replace the canvas instead of blending over it, and keep the frame shown
+const ANMF_NO_BLEND: u8 = 0x02;
+
+/// Animated WebP whose frames are each a full lossless (VP8L) image
+pub(crate) struct WebpWriter {
+    width: u32,
+    height: u32,
+    /// ANMF chunks of the frames added so far
+    frames: Vec<u8>,
+    /// Whether any frame has a pixel that is not opaque
+    has_alpha: bool,
+}
+
+impl WebpWriter {
+    pub fn new(width: u32, height: u32) -> Result<Self> {
+        if width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return Err(Error::InvalidInput(format!(
+                "WebP images are at most {}x{}, not {}x{}",
+                MAX_DIMENSION, MAX_DIMENSION, width, height
+            )));
+        }
+        Ok(Self {
+            width,
+            height,
+            frames: Vec::new(),
+            has_alpha: false,
+        })
+    }
+}
+
+impl AnimationWriter for WebpWriter {
+    fn add_frame(&mut self, data: &[u8], duration_ms: u32) -> Result<()> {
+        if duration_ms > MAX_DURATION_MS {
+            return Err(Error::InvalidInput(format!(
+                "WebP frames last at most {} ms, not {} ms",
+                MAX_DURATION_MS, duration_ms
+            )));
+        }
+
+        let mut image = Vec::new();
+        WebPEncoder::new(&mut image)
+            .encode(data, self.width, self.height, ColorType::Rgba8)
+            .map_err(|e| Error::Encode(format!("Failed to encode WebP frame: {}", e)))?;
+        // An image without metadata is a RIFF header and a single VP8L chunk
+        let bitstream = image
+            .get(RIFF_HEADER..)
+            .filter(|chunk| chunk.starts_with(b"VP8L"))
+            .ok_or_else(|| Error::Internal("Unexpected WebP encoder output".to_string()))?;
+        self.has_alpha |= data.chunks_exact(4).any(|pixel| pixel[3] != 255);
+
+        let mut payload = Vec::with_capacity(16 + bitstream.len());
+        // Frame offset (halved), size minus one and duration
+        payload.extend_from_slice(&u24(0));
+        payload.extend_from_slice(&u24(0));
+        payload.extend_from_slice(&u24(self.width - 1));
+        payload.extend_from_slice(&u24(self.height - 1));
+        payload.extend_from_slice(&u24(duration_ms));
+        payload.push(ANMF_NO_BLEND);
+        payload.extend_from_slice(bitstream);
+        write_chunk(&mut self.frames, b"ANMF", &payload)?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>, output: &mut dyn Write) -> Result<()> {
+        let mut vp8x = [0u8; 10];
+        vp8x[0] = FLAG_ANIMATION | if self.has_alpha { FLAG_ALPHA } else { 0 };
+        vp8x[4..7].copy_from_slice(&u24(self.width - 1));
+        vp8x[7..10].copy_from_slice(&u24(self.height - 1));
+
+        // Transparent background (BGRA), looping forever
+        let anim = [0u8; 6];
+
+        let riff_size = u32::try_from(4 + 8 + vp8x.len() + 8 + anim.len() + self.frames.len())
+            .map_err(|_| Error::Mux("Animated WebP is larger than 4 GiB".to_string()))?;
+        output.write_all(b"RIFF")?;
+        output.write_all(&riff_size.to_le_bytes())?;
+        output.write_all(b"WEBP")?;
+        write_chunk(&mut *output, b"VP8X", &vp8x)?;
+        write_chunk(&mut *output, b"ANIM", &anim)?;
+        output.write_all(&self.frames)?;
+        Ok(())
+    }
+}
+
+/// Little-endian 24-bit value
+fn u24(value: u32) -> [u8; 3] {
+    let [a, b, c, _] = value.to_le_bytes();
+    [a, b, c]
+}
+
+/// Write a RIFF chunk, padded to an even size
+fn write_chunk(output: &mut dyn Write, name: &[u8; 4], data: &[u8]) -> Result<()> {
+    let size = u32::try_from(data.len())
+        .map_err(|_| Error::Mux("WebP frame is larger than 4 GiB".to_string()))?;
+    output.write_all(name)?;
+    output.write_all(&size.to_le_bytes())?;
+    output.write_all(data)?;
+    if data.len() % 2 == 1 {
+        output.write_all(&[0])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image_webp::WebPDecoder;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_animated_webp_round_trip() {
+        let mut writer = Box::new(WebpWriter::new(4, 2).unwrap());
+        let red = [255, 0, 0, 255].repeat(8);
+        let clear = [0, 0, 255, 0].repeat(8);
+        writer.add_frame(&red, 500).unwrap();
+        writer.add_frame(&clear, 1500).unwrap();
+
+        let mut output = Vec::new();
+        writer.finish(&mut output).unwrap();
+
+        let mut decoder = WebPDecoder::new(Cursor::new(output)).unwrap();
+        assert!(decoder.is_animated());
+        assert!(decoder.has_alpha());
+        assert_eq!(decoder.dimensions(), (4, 2));
+        assert_eq!(decoder.num_frames(), 2);
+
+        let mut frame = vec![0u8; 4 * 2 * 4];
+        assert_eq!(decoder.read_frame(&mut frame).unwrap(), 500);
+        assert_eq!(frame, red);
+        assert_eq!(decoder.read_frame(&mut frame).unwrap(), 1500);
+        assert!(frame.chunks_exact(4).all(|pixel| pixel[3] == 0));
+    }
+
+    #[test]
+    fn test_animated_webp_limits() {
+        assert!(WebpWriter::new(MAX_DIMENSION + 1, 2).is_err());
+        let mut writer = WebpWriter::new(2, 2).unwrap();
+        assert!(matches!(
+            writer.add_frame(&[0; 16], MAX_DURATION_MS + 1),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+}
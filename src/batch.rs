@@ -0,0 +1,69 @@
+//! Many slideshows run over a bounded set of worker threads
+
+use crate::logging::{log_info, trace_span};
+use crate::priority::lower_current_thread;
+use crate::slideshow::slideshow_to;
+use crate::stats::EncodeStats;
+use crate::{EncodeOptions, Result, SlideEntry};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// Slideshow created by [`slideshow_batch`]
+#[derive(Debug, Clone)]
+pub struct SlideshowJob {
+    /// Slides of the video
+    pub entries: Vec<SlideEntry>,
+    /// Output path and encoding options of the video
+    pub options: EncodeOptions,
+}
+
+/// Create many slideshows, running up to `concurrency` of them at once
+///
+/// Jobs start in order on worker threads, and each job's callbacks are called on the
+/// thread running it. Jobs given the same [`EncodeOptions::image_cache`] decode and
+/// resize an image shown by several of them once. Returns the result of every job in the
+/// order of `jobs`; a failed job does not stop the others.
+pub fn slideshow_batch(jobs: &[SlideshowJob], concurrency: usize) -> Vec<Result<EncodeStats>> {
+    let _span = trace_span!("slideshow_batch", jobs = jobs.len());
+    let workers = concurrency.clamp(1, jobs.len().max(1));
+    log_info!("Batch: {} slideshows on {} threads", jobs.len(), workers);
+
+    let next = AtomicUsize::new(0);
+
+    let mut results: Vec<Option<Result<EncodeStats>>> = jobs.iter().map(|_| None).collect();
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(job) = jobs.get(index) else {
+                            break;
+                        };
+                        // Priority cannot be raised again, so a worker keeps the lowest one
+                        // asked for by its jobs so far
+                        lower_current_thread(job.options.priority);
+                        let result = slideshow_to(&job.entries, &job.options, None);
+                        done.push((index, result));
+                    }
+                    done
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            let done = handle
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            for (index, result) in done {
+                results[index] = Some(result);
+            }
+        }
+    });
+
+    results
+        .into_iter()
+        .map(|result| result.expect("every job is taken by a worker"))
+        .collect()
+}
@@ -0,0 +1,96 @@
+//! Memory accounting against `EncodeOptions::memory_limit`
+
+use crate::encoder::Packet;
+use crate::{EncodeOptions, Error, Result};
+
+/// Memory used by one operation's large buffers: slide images, buffered frames and
+/// encoded packets
+pub(crate) struct MemoryBudget {
+    limit: Option<u64>,
+    used: u64,
+}
+
+impl MemoryBudget {
+    /// Start accounting against `options.memory_limit`
+    pub(crate) fn new(options: &EncodeOptions) -> Self {
+        Self {
+            limit: options.memory_limit,
+            used: 0,
+        }
+    }
+
+    /// Account for `bytes` held by `what`, failing if the limit would be exceeded
+    pub(crate) fn reserve(&mut self, bytes: u64, what: &str) -> Result<()> {
+        let used = self.used.saturating_add(bytes);
+        match self.limit {
+            Some(limit) if used > limit => Err(Error::MemoryLimit {
+                what: what.to_string(),
+                needed: used,
+                limit,
+            }),
+            _ => {
+                self.used = used;
+                Ok(())
+            }
+        }
+    }
+
+    /// Account for encoded packets that are kept until muxing
+    pub(crate) fn reserve_packets(&mut self, packets: &[Packet]) -> Result<()> {
+        let bytes = packets.iter().map(|p| p.data.len() as u64).sum();
+        self.reserve(bytes, "encoded packets")
+    }
+
+    /// Number of frames of `frame_size` bytes, up to `max`, that fit in the remaining budget
+    ///
+    /// The frames are accounted for as used.
+    pub(crate) fn reserve_frames(&mut self, frame_size: usize, max: usize) -> usize {
+        let count = match self.limit {
+            Some(limit) => {
+                let remaining = limit.saturating_sub(self.used);
+                (remaining / (frame_size as u64).max(1)).min(max as u64) as usize
+            }
+            None => max,
+        };
+        self.used = self
+            .used
+            .saturating_add((count as u64).saturating_mul(frame_size as u64));
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_budget() {
+        let options = EncodeOptions {
+            memory_limit: Some(1000),
+            ..Default::default()
+        };
+        let mut budget = MemoryBudget::new(&options);
+
+        budget.reserve(600, "slide images").unwrap();
+        assert_eq!(budget.reserve_frames(100, 4), 4);
+        assert_eq!(budget.reserve_frames(100, 4), 0);
+
+        let err = budget.reserve(1, "encoded packets").unwrap_err();
+        assert!(matches!(
+            err,
+            Error::MemoryLimit {
+                needed: 1001,
+                limit: 1000,
+                ..
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Memory limit of 1000 bytes exceeded by encoded packets (1001 bytes needed)"
+        );
+
+        let mut unlimited = MemoryBudget::new(&EncodeOptions::default());
+        assert_eq!(unlimited.reserve_frames(usize::MAX, 4), 4);
+        unlimited.reserve(u64::MAX, "slide images").unwrap();
+    }
+}
@@ -0,0 +1,71 @@
+//! Query of the codec/container combinations usable on this machine
+
+use crate::encoder::{backend_for, EncoderBackend};
+use crate::{available, muxer, Codec, Container};
+
+/// A codec/container combination that can be produced on this machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capability {
+    /// Video codec
+    pub codec: Codec,
+    /// Container format
+    pub container: Container,
+    /// Encoder implementation used for the codec
+    pub backend: EncoderBackend,
+    /// Whether the backend uses hardware acceleration
+    pub hardware: bool,
+}
+
+/// List the codec/container combinations available on this machine
+///
+/// Codecs whose encoder is unavailable (e.g. no ffmpeg with libx264 on Linux) are omitted.
+pub fn capabilities(ffmpeg_path: Option<&str>) -> Vec<Capability> {
+    let mut result = Vec::new();
+
+    for codec in [Codec::Av1, Codec::H264, Codec::Vp9] {
+        let backend = match backend_for(codec) {
+            Some(backend) => backend,
+            None => continue,
+        };
+
+        if available(codec, ffmpeg_path).is_err() {
+            continue;
+        }
+
+        for container in [
+            Container::Mp4,
+            Container::WebM,
+            Container::Mkv,
+            Container::Hls,
+            Container::Ivf,
+            Container::AnnexB,
+        ] {
+            if container.supports_codec(codec) && muxer::check_codec(container, codec).is_ok() {
+                result.push(Capability {
+                    codec,
+                    container,
+                    backend,
+                    hardware: backend.is_hardware(),
+                });
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(all(test, feature = "av1"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities_include_webm_av1() {
+        let caps = capabilities(None);
+        assert!(caps.contains(&Capability {
+            codec: Codec::Av1,
+            container: Container::WebM,
+            backend: EncoderBackend::Rav1e,
+            hardware: false,
+        }));
+    }
+}
@@ -0,0 +1,287 @@
+//! Text captions drawn onto slides
+
+use crate::image_loader::LoadedImage;
+use crate::{Color, Error, Result};
+
+/// Fraction of the slide height between a caption and the top or bottom edge
+#[cfg(feature = "text")]
+const MARGIN: f32 = 0.05;
+
+/// Where a caption is placed on its slide
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
+pub enum CaptionPosition {
+    Top = 0,
+    Center = 1,
+    #[default]
+    Bottom = 2,
+}
+
+/// Text drawn on top of a slide, each line centered horizontally
+///
+/// Lines are separated by `\n` and are not wrapped. Captions are drawn onto the slide
+/// image once it is resized, so filters and transitions see them as part of the slide.
+/// Drawing them needs the `text` feature; without it, slideshows with captions fail with
+/// [`Error::Unsupported`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Caption {
+    pub text: String,
+    /// Path to a TrueType or OpenType font file
+    pub font_path: String,
+    /// Font size in pixels
+    pub size: f32,
+    pub color: Color,
+    pub position: CaptionPosition,
+    /// Color of the outline around the glyphs, `None` for no outline
+    pub outline: Option<Color>,
+    /// Width of the outline in pixels
+    pub outline_width: u32,
+}
+
+impl Caption {
+    /// White 32-pixel text at the bottom of the slide, with a 2-pixel black outline
+    pub fn new(text: impl Into<String>, font_path: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            font_path: font_path.into(),
+            size: 32.0,
+            color: Color::default(),
+            position: CaptionPosition::Bottom,
+            outline: Some(Color { r: 0, g: 0, b: 0 }),
+            outline_width: 2,
+        }
+    }
+
+    /// Draw the caption onto `image`
+    #[cfg(feature = "text")]
+    pub(crate) fn draw(&self, image: &mut LoadedImage) -> Result<()> {
+        use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+
+        let data = std::fs::read(&self.font_path).map_err(|e| {
+            Error::InvalidInput(format!("Failed to read font {}: {}", self.font_path, e))
+        })?;
+        let font = FontVec::try_from_vec(data).map_err(|_| {
+            Error::InvalidInput(format!(
+                "{} is not a TrueType or OpenType font",
+                self.font_path
+            ))
+        })?;
+        let scaled = font.as_scaled(PxScale::from(self.size));
+
+        let lines: Vec<&str> = self.text.lines().collect();
+        let line_height = scaled.height() + scaled.line_gap();
+        let text_height = line_height * lines.len() as f32 - scaled.line_gap();
+        let (width, height) = (image.width as usize, image.height as usize);
+        // The outline stays inside the margin
+        let inset = image.height as f32 * MARGIN + self.outline_width as f32;
+        let top = match self.position {
+            CaptionPosition::Top => inset,
+            CaptionPosition::Center => (image.height as f32 - text_height) / 2.0,
+            CaptionPosition::Bottom => image.height as f32 - inset - text_height,
+        };
+
+        let mut coverage = Coverage::new(width, height);
+        for (index, line) in lines.iter().enumerate() {
+            let mut glyphs = Vec::new();
+            let mut caret = 0.0;
+            let mut previous = None;
+            for c in line.chars() {
+                let id = font.glyph_id(c);
+                if let Some(previous) = previous {
+                    caret += scaled.kern(previous, id);
+                }
+                glyphs.push((id, caret));
+                caret += scaled.h_advance(id);
+                previous = Some(id);
+            }
+
+            let left = (image.width as f32 - caret) / 2.0;
+            let baseline = top + index as f32 * line_height + scaled.ascent();
+            for (id, x) in glyphs {
+                let glyph = id.with_scale_and_position(scaled.scale(), point(left + x, baseline));
+                if let Some(outlined) = font.outline_glyph(glyph) {
+                    let bounds = outlined.px_bounds();
+                    outlined.draw(|x, y, c| {
+                        coverage.add(
+                            bounds.min.x as i64 + x as i64,
+                            bounds.min.y as i64 + y as i64,
+                            c,
+                        )
+                    });
+                }
+            }
+        }
+
+        if let Some(outline) = self.outline.filter(|_| self.outline_width > 0) {
+            coverage
+                .dilate(self.outline_width)
+                .blend(&mut image.data, outline);
+        }
+        coverage.blend(&mut image.data, self.color);
+        Ok(())
+    }
+
+    /// Draw the caption onto `image`, which needs the `text` feature
+    #[cfg(not(feature = "text"))]
+    pub(crate) fn draw(&self, _image: &mut LoadedImage) -> Result<()> {
+        Err(Error::Unsupported(
+            "Captions need minmpeg built with the text feature".to_string(),
+        ))
+    }
+}
+
+/// Coverage of an image by glyphs, from 0 to 1 per pixel
+#[cfg(feature = "text")]
+struct Coverage {
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+    /// Pixels covered so far, as `(min_x, min_y, max_x, max_y)`
+    bounds: Option<(usize, usize, usize, usize)>,
+}
+
+#[cfg(feature = "text")]
+impl Coverage {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            values: vec![0.0; width * height],
+            bounds: None,
+        }
+    }
+
+    /// Add coverage `c` at `(x, y)`, ignoring pixels outside the image
+    fn add(&mut self, x: i64, y: i64, c: f32) {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height || c <= 0.0 {
+            return;
+        }
+        let (x, y) = (x as usize, y as usize);
+        let value = &mut self.values[y * self.width + x];
+        *value = (*value + c).min(1.0);
+        self.bounds = Some(match self.bounds {
+            None => (x, y, x, y),
+            Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+        });
+    }
+
+    /// Coverage grown by `radius` pixels in every direction
+    fn dilate(&self, radius: u32) -> Self {
+        let mut dilated = Self::new(self.width, self.height);
+        let Some((x0, y0, x1, y1)) = self.bounds else {
+            return dilated;
+        };
+        let r = radius as i64;
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let c = self.values[y * self.width + x];
+                if c <= 0.0 {
+                    continue;
+                }
+                for dy in -r..=r {
+                    for dx in -r..=r {
+                        if dx * dx + dy * dy > r * r {
+                            continue;
+                        }
+                        let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                        if nx < 0
+                            || ny < 0
+                            || nx as usize >= self.width
+                            || ny as usize >= self.height
+                        {
+                            continue;
+                        }
+                        let (nx, ny) = (nx as usize, ny as usize);
+                        let value = &mut dilated.values[ny * self.width + nx];
+                        if c > *value {
+                            *value = c;
+                        }
+                    }
+                }
+            }
+        }
+        dilated.bounds = Some((
+            x0.saturating_sub(radius as usize),
+            y0.saturating_sub(radius as usize),
+            (x1 + radius as usize).min(self.width - 1),
+            (y1 + radius as usize).min(self.height - 1),
+        ));
+        dilated
+    }
+
+    /// Draw `color` over RGBA `data` where covered
+    fn blend(&self, data: &mut [u8], color: Color) {
+        let Some((x0, y0, x1, y1)) = self.bounds else {
+            return;
+        };
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let a = self.values[y * self.width + x];
+                if a <= 0.0 {
+                    continue;
+                }
+                let pixel = &mut data[(y * self.width + x) * 4..][..4];
+                for (channel, value) in pixel.iter_mut().zip([color.r, color.g, color.b, 255]) {
+                    *channel = (*channel as f32 * (1.0 - a) + value as f32 * a).round() as u8;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Font shipped with most Linux distributions
+    #[cfg(feature = "text")]
+    const FONT: &str = "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf";
+
+    fn gray_image() -> LoadedImage {
+        LoadedImage::from_rgba(160, 120, [128, 128, 128, 255].repeat(160 * 120)).unwrap()
+    }
+
+    #[cfg(feature = "text")]
+    #[test]
+    fn test_draw_caption() {
+        if !std::path::Path::new(FONT).exists() {
+            println!("Skipping caption test: {} not found", FONT);
+            return;
+        }
+
+        let mut image = gray_image();
+        let caption = Caption {
+            outline: None,
+            ..Caption::new("Hello", FONT)
+        };
+        caption.draw(&mut image).unwrap();
+
+        let rows_with_text: Vec<usize> = (0..120)
+            .filter(|&y| {
+                image.data[y * 160 * 4..(y + 1) * 160 * 4]
+                    .chunks_exact(4)
+                    .any(|pixel| pixel[0] > 200)
+            })
+            .collect();
+        // White text in the lower part, above the margin
+        assert!(!rows_with_text.is_empty());
+        assert!(rows_with_text[0] > 60);
+        assert!(*rows_with_text.last().unwrap() < 114);
+
+        // The outline darkens pixels around the glyphs
+        let mut outlined = gray_image();
+        Caption::new("Hello", FONT).draw(&mut outlined).unwrap();
+        assert!(outlined.data.chunks_exact(4).any(|pixel| pixel[0] < 50));
+
+        let missing = Caption::new("Hello", "/nonexistent/font.ttf").draw(&mut gray_image());
+        assert!(matches!(missing, Err(Error::InvalidInput(_))));
+    }
+
+    #[cfg(not(feature = "text"))]
+    #[test]
+    fn test_caption_needs_text_feature() {
+        let result = Caption::new("Hello", "font.ttf").draw(&mut gray_image());
+        assert!(matches!(result, Err(Error::Unsupported(_))));
+    }
+}
@@ -0,0 +1,287 @@
+//! H.264 samples read from MP4 files, for the platform decoders
+
+use crate::probe::{iso_container, VideoInfo};
+use crate::{Error, Result};
+use mp4::{MediaType, Mp4Reader, Mp4Track, TrackType};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// H.264 access unit, its NAL units prefixed by their length
+// Read by the platform decoders, which other platforms lack
+#[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+pub(crate) struct Sample {
+    pub data: Vec<u8>,
+    /// Presentation time in microseconds from the first frame
+    pub pts_us: i64,
+    pub is_sync: bool,
+}
+
+/// H.264 track of an MP4 file
+// Read by the platform decoders, which other platforms lack
+#[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+pub(crate) struct Mp4Source {
+    reader: Mp4Reader<BufReader<File>>,
+    track_id: u32,
+    pub width: u32,
+    pub height: u32,
+    pub sps: Vec<u8>,
+    pub pps: Vec<u8>,
+    /// Size of the length prefix of NAL units in samples
+    pub nal_length_size: usize,
+    pub has_audio: bool,
+    /// Container named by the major brand of the file
+    container: &'static str,
+    duration_ms: u64,
+    /// Presentation times of the samples in decoding order
+    pts_us: Vec<i64>,
+    /// Samples that decode on their own, in decoding order
+    sync: Vec<bool>,
+    /// Index of the next sample to read
+    next: usize,
+}
+
+impl Mp4Source {
+    /// Open the H.264 track of the MP4 file at `path`, or return `None` if the file is
+    /// not a plain (unfragmented) MP4 with such a track
+    pub fn open(path: &Path) -> Result<Option<Self>> {
+        let mut file = File::open(path)?;
+        let mut header = [0u8; 8];
+        if file.read_exact(&mut header).is_err() || &header[4..8] != b"ftyp" {
+            return Ok(None);
+        }
+        let size = file.metadata()?.len();
+        let file = File::open(path)?;
+        let Ok(reader) = Mp4Reader::read_header(BufReader::new(file), size) else {
+            return Ok(None);
+        };
+        if reader.is_fragmented() {
+            return Ok(None);
+        }
+
+        let container = iso_container(&String::from_utf8_lossy(&reader.ftyp.major_brand.value));
+        let has_audio = reader
+            .tracks()
+            .values()
+            .any(|track| matches!(track.track_type(), Ok(TrackType::Audio)));
+        let Some(track) = reader
+            .tracks()
+            .values()
+            .find(|track| matches!(track.media_type(), Ok(MediaType::H264)))
+        else {
+            return Ok(None);
+        };
+        let (Ok(sps), Ok(pps)) = (
+            track.sequence_parameter_set(),
+            track.picture_parameter_set(),
+        ) else {
+            return Ok(None);
+        };
+        let nal_length_size = match &track.trak.mdia.minf.stbl.stsd.avc1 {
+            Some(avc1) => avc1.avcc.length_size_minus_one as usize % 4 + 1,
+            None => return Ok(None),
+        };
+        if track.sample_count() == 0 || track.timescale() == 0 {
+            return Ok(None);
+        }
+
+        let (sps, pps) = (sps.to_vec(), pps.to_vec());
+        let (width, height) = (track.width() as u32, track.height() as u32);
+        let track_id = track.track_id();
+        let duration_ms = track.duration().as_millis() as u64;
+        let pts_us = presentation_times(track);
+        let sync = sync_samples(track);
+
+        Ok(Some(Self {
+            reader,
+            track_id,
+            width,
+            height,
+            sps,
+            pps,
+            nal_length_size,
+            has_audio,
+            container,
+            duration_ms,
+            pts_us,
+            sync,
+            next: 0,
+        }))
+    }
+
+    /// Dimensions, frame rate and duration of the track
+    pub fn info(&self) -> VideoInfo {
+        let frame_count = self.pts_us.len() as u64;
+        VideoInfo {
+            width: self.width,
+            height: self.height,
+            fps: frame_count as f64 * 1000.0 / self.duration_ms.max(1) as f64,
+            duration_ms: self.duration_ms,
+            frame_count,
+            codec: "h264".to_string(),
+            container: self.container.to_string(),
+        }
+    }
+
+    /// Continue reading from the last sync sample presented at or before `pts_us`, the
+    /// first one that can be decoded to show that time
+    pub fn seek(&mut self, pts_us: i64) {
+        self.next = (0..self.pts_us.len())
+            .rev()
+            .find(|&i| self.sync[i] && self.pts_us[i] <= pts_us)
+            .unwrap_or(0);
+    }
+
+    /// Presentation times of the samples left to read, in presentation order
+    pub fn remaining_pts(&self) -> Vec<i64> {
+        let mut pts = self.pts_us[self.next..].to_vec();
+        pts.sort_unstable();
+        pts
+    }
+
+    /// Read the next sample in decoding order, or `None` at the end of the track
+    pub fn read(&mut self) -> Result<Option<Sample>> {
+        if self.next >= self.pts_us.len() {
+            return Ok(None);
+        }
+        let index = self.next;
+        let sample = self
+            .reader
+            .read_sample(self.track_id, index as u32 + 1)
+            .map_err(|e| Error::Decode(format!("Failed to read MP4 sample: {}", e)))?;
+        self.next += 1;
+        Ok(sample.map(|sample| Sample {
+            data: sample.bytes.to_vec(),
+            pts_us: self.pts_us[index],
+            is_sync: self.sync[index],
+        }))
+    }
+}
+
+/// Presentation times of the samples of `track` in microseconds, in decoding order,
+/// counted from the earliest one
+fn presentation_times(track: &Mp4Track) -> Vec<i64> {
+    let stbl = &track.trak.mdia.minf.stbl;
+    let mut offsets = stbl
+        .ctts
+        .iter()
+        .flat_map(|ctts| &ctts.entries)
+        .flat_map(|entry| {
+            std::iter::repeat(entry.sample_offset as i64).take(entry.sample_count as usize)
+        });
+    let mut dts = 0i64;
+    let mut times = Vec::with_capacity(track.sample_count() as usize);
+    for entry in &stbl.stts.entries {
+        for _ in 0..entry.sample_count {
+            times.push(dts + offsets.next().unwrap_or(0));
+            dts += entry.sample_delta as i64;
+        }
+    }
+    times.truncate(track.sample_count() as usize);
+
+    let timescale = track.timescale() as i128;
+    let start = times.iter().copied().min().unwrap_or(0);
+    times
+        .into_iter()
+        .map(|t| (((t - start) as i128 * 1_000_000 + timescale / 2) / timescale) as i64)
+        .collect()
+}
+
+/// Whether each sample of `track` is a sync sample, all of them without a sync table
+fn sync_samples(track: &Mp4Track) -> Vec<bool> {
+    let count = track.sample_count() as usize;
+    match &track.trak.mdia.minf.stbl.stss {
+        Some(stss) => {
+            let mut sync = vec![false; count];
+            for &number in &stss.entries {
+                if let Some(flag) = sync.get_mut((number as usize).wrapping_sub(1)) {
+                    *flag = true;
+                }
+            }
+            sync
+        }
+        None => vec![true; count],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::Packet;
+    use crate::muxer::mp4::Mp4Muxer;
+    use crate::muxer::{Muxer, MuxerConfig};
+    use crate::{Codec, ColorSpace, PixelRange};
+    use tempfile::TempDir;
+
+    /// Write an MP4 of `count` one-NAL samples at 30 fps, a keyframe every 10
+    fn write_mp4(path: &Path, count: u8) {
+        let config = MuxerConfig {
+            width: 64,
+            height: 48,
+            fps: 30,
+            codec: Codec::H264,
+            codec_config: Some(vec![0x67, 0x42, 0xc0, 0x1e]),
+            pps: Some(vec![0x68, 0xce, 0x3c, 0x80]),
+            color_space: ColorSpace::Bt601,
+            pixel_range: PixelRange::Auto,
+        };
+        let mut muxer: Box<dyn Muxer> = Box::new(Mp4Muxer::new(path, config).unwrap());
+        for i in 0..count {
+            muxer
+                .write_packet(&Packet {
+                    data: vec![if i % 10 == 0 { 0x65 } else { 0x41 }, i],
+                    pts: i as i64,
+                    dts: i as i64,
+                    is_keyframe: i % 10 == 0,
+                })
+                .unwrap();
+        }
+        muxer.finalize().unwrap();
+    }
+
+    #[test]
+    fn test_mp4_source() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("video.mp4");
+        write_mp4(&path, 25);
+
+        let mut source = Mp4Source::open(&path).unwrap().expect("H.264 track");
+        assert_eq!((source.width, source.height), (64, 48));
+        assert_eq!(source.sps, [0x67, 0x42, 0xc0, 0x1e]);
+        assert_eq!(source.nal_length_size, 4);
+        assert!(!source.has_audio);
+        assert_eq!(source.info().container, "mp4");
+        let info = source.info();
+        assert_eq!(info.frame_count, 25);
+        assert!((info.fps - 30.0).abs() < 0.1, "{}", info.fps);
+
+        let first = source.read().unwrap().unwrap();
+        assert_eq!(first.data, [0, 0, 0, 2, 0x65, 0]);
+        assert!(first.is_sync);
+        assert_eq!(first.pts_us, 0);
+        let second = source.read().unwrap().unwrap();
+        assert!(!second.is_sync);
+        assert_eq!(second.pts_us, 33_333);
+
+        // Seeking lands on the keyframe before the time
+        source.seek(600_000);
+        assert_eq!(source.remaining_pts().len(), 15);
+        let sample = source.read().unwrap().unwrap();
+        assert!(sample.is_sync);
+        assert_eq!(sample.data[5], 10);
+        source.seek(800_000);
+        while source.read().unwrap().is_some() {}
+        assert!(source.remaining_pts().is_empty());
+    }
+
+    #[test]
+    fn test_mp4_source_other_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("image.png");
+        std::fs::write(&path, b"\x89PNG\r\n\x1a\n").unwrap();
+        assert!(Mp4Source::open(&path).unwrap().is_none());
+        std::fs::write(&path, b"").unwrap();
+        assert!(Mp4Source::open(&path).unwrap().is_none());
+        assert!(Mp4Source::open(&temp_dir.path().join("missing.mp4")).is_err());
+    }
+}
@@ -0,0 +1,464 @@
+//! macOS H.264 decoder using VideoToolbox
+
+use super::super::demux::{Mp4Source, Sample};
+use super::super::native::{Picture, PictureDecoder};
+use crate::{Error, Result};
+use std::ffi::c_void;
+use std::ptr;
+use std::slice;
+use std::sync::Mutex;
+
+// VideoToolbox FFI bindings
+#[link(name = "VideoToolbox", kind = "framework")]
+extern "C" {
+    fn VTDecompressionSessionCreate(
+        allocator: *const c_void,
+        video_format_description: *mut c_void,
+        video_decoder_specification: *const c_void,
+        destination_image_buffer_attributes: *const c_void,
+        output_callback: *const VTDecompressionOutputCallbackRecord,
+        decompression_session_out: *mut *mut c_void,
+    ) -> i32;
+
+    fn VTDecompressionSessionDecodeFrame(
+        session: *mut c_void,
+        sample_buffer: *mut c_void,
+        decode_flags: u32,
+        source_frame_ref_con: *mut c_void,
+        info_flags_out: *mut u32,
+    ) -> i32;
+
+    fn VTDecompressionSessionFinishDelayedFrames(session: *mut c_void) -> i32;
+
+    fn VTDecompressionSessionWaitForAsynchronousFrames(session: *mut c_void) -> i32;
+
+    fn VTDecompressionSessionInvalidate(session: *mut c_void);
+}
+
+#[link(name = "CoreMedia", kind = "framework")]
+extern "C" {
+    fn CMTimeMake(value: i64, timescale: i32) -> CMTime;
+
+    fn CMVideoFormatDescriptionCreateFromH264ParameterSets(
+        allocator: *const c_void,
+        parameter_set_count: usize,
+        parameter_set_pointers: *const *const u8,
+        parameter_set_sizes: *const usize,
+        nal_unit_header_length: i32,
+        format_description_out: *mut *mut c_void,
+    ) -> i32;
+
+    fn CMBlockBufferCreateWithMemoryBlock(
+        structure_allocator: *const c_void,
+        memory_block: *mut c_void,
+        block_length: usize,
+        block_allocator: *const c_void,
+        custom_block_source: *const c_void,
+        offset_to_data: usize,
+        data_length: usize,
+        flags: u32,
+        block_buffer_out: *mut *mut c_void,
+    ) -> i32;
+
+    fn CMBlockBufferReplaceDataBytes(
+        source_bytes: *const c_void,
+        destination_buffer: *mut c_void,
+        offset_into_destination: usize,
+        data_length: usize,
+    ) -> i32;
+
+    fn CMSampleBufferCreateReady(
+        allocator: *const c_void,
+        data_buffer: *mut c_void,
+        format_description: *mut c_void,
+        num_samples: isize,
+        num_sample_timing_entries: isize,
+        sample_timing_array: *const CMSampleTimingInfo,
+        num_sample_size_entries: isize,
+        sample_size_array: *const usize,
+        sample_buffer_out: *mut *mut c_void,
+    ) -> i32;
+}
+
+#[link(name = "CoreVideo", kind = "framework")]
+extern "C" {
+    fn CVPixelBufferLockBaseAddress(pixel_buffer: *mut c_void, lock_flags: u64) -> i32;
+    fn CVPixelBufferUnlockBaseAddress(pixel_buffer: *mut c_void, unlock_flags: u64) -> i32;
+    fn CVPixelBufferGetBaseAddress(pixel_buffer: *mut c_void) -> *mut u8;
+    fn CVPixelBufferGetBytesPerRow(pixel_buffer: *mut c_void) -> usize;
+    fn CVPixelBufferGetWidth(pixel_buffer: *mut c_void) -> usize;
+    fn CVPixelBufferGetHeight(pixel_buffer: *mut c_void) -> usize;
+
+    static kCVPixelBufferPixelFormatTypeKey: *const c_void;
+}
+
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    fn CFRelease(cf: *mut c_void);
+    fn CFNumberCreate(
+        allocator: *const c_void,
+        the_type: i32,
+        value_ptr: *const c_void,
+    ) -> *mut c_void;
+    fn CFDictionaryCreate(
+        allocator: *const c_void,
+        keys: *const *const c_void,
+        values: *const *const c_void,
+        num_values: isize,
+        key_call_backs: *const CFDictionaryCallBacks,
+        value_call_backs: *const CFDictionaryCallBacks,
+    ) -> *mut c_void;
+
+    static kCFTypeDictionaryKeyCallBacks: CFDictionaryCallBacks;
+    static kCFTypeDictionaryValueCallBacks: CFDictionaryCallBacks;
+}
+
+/// Opaque CoreFoundation dictionary callbacks, only passed by address
+#[repr(C)]
+struct CFDictionaryCallBacks {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct CMTime {
+    value: i64,
+    timescale: i32,
+    flags: u32,
+    epoch: i64,
+}
+
+#[repr(C)]
+struct CMSampleTimingInfo {
+    duration: CMTime,
+    presentation_time_stamp: CMTime,
+    decode_time_stamp: CMTime,
+}
+
+#[repr(C)]
+struct VTDecompressionOutputCallbackRecord {
+    decompression_output_callback:
+        extern "C" fn(*mut c_void, *mut c_void, i32, u32, *mut c_void, CMTime, CMTime),
+    decompression_output_ref_con: *mut c_void,
+}
+
+const K_CM_TIME_INVALID: CMTime = CMTime {
+    value: 0,
+    timescale: 0,
+    flags: 0,
+    epoch: 0,
+};
+const K_CV_PIXEL_FORMAT_TYPE_32_BGRA: i64 = 0x42475241; // 'BGRA'
+const K_CF_NUMBER_INT64_TYPE: i32 = 4;
+const K_CM_BLOCK_BUFFER_ASSURE_MEMORY_NOW_FLAG: u32 = 1;
+const K_CV_PIXEL_BUFFER_LOCK_READ_ONLY: u64 = 1;
+/// Microsecond timescale sample times are passed in
+const TIMESCALE: i32 = 1_000_000;
+
+/// Pictures delivered through the output callback
+struct CallbackData {
+    /// Size of the track, which pictures are copied at
+    width: usize,
+    height: usize,
+    pictures: Vec<Picture>,
+    /// Status of the first frame that failed to decode
+    error: Option<i32>,
+}
+
+/// VideoToolbox H.264 decoder
+pub struct VideoToolboxDecoder {
+    session: *mut c_void,
+    format_description: *mut c_void,
+    // Boxed so its address, passed to the callback, stays put
+    callback_data: Box<Mutex<CallbackData>>,
+}
+
+unsafe impl Send for VideoToolboxDecoder {}
+
+impl VideoToolboxDecoder {
+    pub fn new(source: &Mp4Source) -> Result<Self> {
+        let callback_data = Box::new(Mutex::new(CallbackData {
+            width: source.width as usize,
+            height: source.height as usize,
+            pictures: Vec::new(),
+            error: None,
+        }));
+
+        let mut format_description: *mut c_void = ptr::null_mut();
+        let parameter_sets = [source.sps.as_ptr(), source.pps.as_ptr()];
+        let parameter_set_sizes = [source.sps.len(), source.pps.len()];
+        let status = unsafe {
+            CMVideoFormatDescriptionCreateFromH264ParameterSets(
+                ptr::null(),
+                2,
+                parameter_sets.as_ptr(),
+                parameter_set_sizes.as_ptr(),
+                source.nal_length_size as i32,
+                &mut format_description,
+            )
+        };
+        if status != 0 {
+            return Err(Error::Decode(format!(
+                "Failed to read the H.264 parameter sets: {}",
+                status
+            )));
+        }
+
+        let callback = VTDecompressionOutputCallbackRecord {
+            decompression_output_callback,
+            decompression_output_ref_con: &*callback_data as *const Mutex<CallbackData>
+                as *mut c_void,
+        };
+        let mut session: *mut c_void = ptr::null_mut();
+
+        // Create decompression session, which outputs BGRA pixel buffers
+        let status = unsafe {
+            let attributes = create_bgra_attributes();
+            let status = VTDecompressionSessionCreate(
+                ptr::null(),
+                format_description,
+                ptr::null(),
+                attributes,
+                &callback,
+                &mut session,
+            );
+            if !attributes.is_null() {
+                CFRelease(attributes);
+            }
+            status
+        };
+
+        if status != 0 {
+            unsafe { CFRelease(format_description) };
+            return Err(Error::CodecUnavailable(format!(
+                "Failed to create VideoToolbox decompression session: {}",
+                status
+            )));
+        }
+
+        Ok(Self {
+            session,
+            format_description,
+            callback_data,
+        })
+    }
+
+    /// Wrap the AVCC data of `sample` into a sample buffer (released by the caller)
+    fn create_sample_buffer(&self, sample: &Sample) -> Result<*mut c_void> {
+        let mut block_buffer: *mut c_void = ptr::null_mut();
+        let mut sample_buffer: *mut c_void = ptr::null_mut();
+        let len = sample.data.len();
+
+        unsafe {
+            let status = CMBlockBufferCreateWithMemoryBlock(
+                ptr::null(),
+                ptr::null_mut(),
+                len,
+                ptr::null(),
+                ptr::null(),
+                0,
+                len,
+                K_CM_BLOCK_BUFFER_ASSURE_MEMORY_NOW_FLAG,
+                &mut block_buffer,
+            );
+            if status != 0 {
+                return Err(Error::Decode(format!(
+                    "Failed to create block buffer: {}",
+                    status
+                )));
+            }
+
+            let status = CMBlockBufferReplaceDataBytes(
+                sample.data.as_ptr() as *const c_void,
+                block_buffer,
+                0,
+                len,
+            );
+            if status != 0 {
+                CFRelease(block_buffer);
+                return Err(Error::Decode(format!(
+                    "Failed to copy sample data: {}",
+                    status
+                )));
+            }
+
+            let timing = CMSampleTimingInfo {
+                duration: K_CM_TIME_INVALID,
+                presentation_time_stamp: CMTimeMake(sample.pts_us, TIMESCALE),
+                decode_time_stamp: K_CM_TIME_INVALID,
+            };
+            let status = CMSampleBufferCreateReady(
+                ptr::null(),
+                block_buffer,
+                self.format_description,
+                1,
+                1,
+                &timing,
+                1,
+                &len,
+                &mut sample_buffer,
+            );
+            // The sample buffer keeps its own reference
+            CFRelease(block_buffer);
+            if status != 0 {
+                return Err(Error::Decode(format!(
+                    "Failed to create sample buffer: {}",
+                    status
+                )));
+            }
+        }
+
+        Ok(sample_buffer)
+    }
+
+    /// Take the pictures delivered by the output callback so far
+    fn take_pictures(&self) -> Result<Vec<Picture>> {
+        let mut data = self
+            .callback_data
+            .lock()
+            .map_err(|_| Error::Decode("VideoToolbox callback state poisoned".to_string()))?;
+        if let Some(status) = data.error.take() {
+            return Err(Error::Decode(format!("Failed to decode frame: {}", status)));
+        }
+        Ok(std::mem::take(&mut data.pictures))
+    }
+}
+
+extern "C" fn decompression_output_callback(
+    decompression_output_ref_con: *mut c_void,
+    _source_frame_ref_con: *mut c_void,
+    status: i32,
+    _info_flags: u32,
+    image_buffer: *mut c_void,
+    presentation_time_stamp: CMTime,
+    _presentation_duration: CMTime,
+) {
+    // Don't take ownership - just borrow
+    let callback_data = unsafe { &*(decompression_output_ref_con as *const Mutex<CallbackData>) };
+    let mut data = match callback_data.lock() {
+        Ok(d) => d,
+        Err(_) => return,
+    };
+
+    if status != 0 {
+        data.error.get_or_insert(status);
+        return;
+    }
+    // Frames dropped by the decoder come without an image
+    if image_buffer.is_null() {
+        return;
+    }
+
+    let (width, height) = (data.width, data.height);
+    let mut rgba = vec![0u8; width * height * 4];
+    unsafe {
+        let lock_status =
+            CVPixelBufferLockBaseAddress(image_buffer, K_CV_PIXEL_BUFFER_LOCK_READ_ONLY);
+        if lock_status != 0 {
+            data.error.get_or_insert(lock_status);
+            return;
+        }
+        let base_address = CVPixelBufferGetBaseAddress(image_buffer);
+        let bytes_per_row = CVPixelBufferGetBytesPerRow(image_buffer);
+        let copy_width = CVPixelBufferGetWidth(image_buffer).min(width);
+        let copy_height = CVPixelBufferGetHeight(image_buffer).min(height);
+        let pixels = slice::from_raw_parts(base_address, bytes_per_row * copy_height);
+
+        // Convert BGRA to RGBA
+        for (dst, src) in rgba
+            .chunks_exact_mut(width * 4)
+            .zip(pixels.chunks_exact(bytes_per_row))
+        {
+            for (dst, src) in dst[..copy_width * 4]
+                .chunks_exact_mut(4)
+                .zip(src[..copy_width * 4].chunks_exact(4))
+            {
+                dst[0] = src[2]; // R
+                dst[1] = src[1]; // G
+                dst[2] = src[0]; // B
+                dst[3] = 255;
+            }
+        }
+
+        CVPixelBufferUnlockBaseAddress(image_buffer, K_CV_PIXEL_BUFFER_LOCK_READ_ONLY);
+    }
+
+    let time = presentation_time_stamp;
+    let pts_us = if time.timescale > 0 {
+        (time.value as i128 * 1_000_000 / time.timescale as i128) as i64
+    } else {
+        0
+    };
+    data.pictures.push(Picture { pts_us, data: rgba });
+}
+
+/// Destination attributes asking for BGRA pixel buffers (released by the caller)
+fn create_bgra_attributes() -> *mut c_void {
+    unsafe {
+        let format = CFNumberCreate(
+            ptr::null(),
+            K_CF_NUMBER_INT64_TYPE,
+            &K_CV_PIXEL_FORMAT_TYPE_32_BGRA as *const _ as *const c_void,
+        );
+        if format.is_null() {
+            return ptr::null_mut();
+        }
+        let keys = [kCVPixelBufferPixelFormatTypeKey];
+        let values = [format as *const c_void];
+        let attributes = CFDictionaryCreate(
+            ptr::null(),
+            keys.as_ptr(),
+            values.as_ptr(),
+            1,
+            &kCFTypeDictionaryKeyCallBacks,
+            &kCFTypeDictionaryValueCallBacks,
+        );
+        CFRelease(format);
+        attributes
+    }
+}
+
+impl PictureDecoder for VideoToolboxDecoder {
+    fn decode(&mut self, sample: &Sample) -> Result<Vec<Picture>> {
+        let sample_buffer = self.create_sample_buffer(sample)?;
+
+        // Without the asynchronous flag, frames are output before this returns
+        let status = unsafe {
+            let status = VTDecompressionSessionDecodeFrame(
+                self.session,
+                sample_buffer,
+                0,
+                ptr::null_mut(),
+                ptr::null_mut(),
+            );
+            CFRelease(sample_buffer);
+            status
+        };
+
+        if status != 0 {
+            return Err(Error::Decode(format!("Failed to decode frame: {}", status)));
+        }
+
+        self.take_pictures()
+    }
+
+    fn flush(&mut self) -> Result<Vec<Picture>> {
+        unsafe {
+            VTDecompressionSessionFinishDelayedFrames(self.session);
+            VTDecompressionSessionWaitForAsynchronousFrames(self.session);
+        }
+
+        self.take_pictures()
+    }
+}
+
+impl Drop for VideoToolboxDecoder {
+    fn drop(&mut self) {
+        // The session stops calling back before `callback_data` is dropped
+        unsafe {
+            VTDecompressionSessionInvalidate(self.session);
+            CFRelease(self.session);
+            CFRelease(self.format_description);
+        }
+    }
+}
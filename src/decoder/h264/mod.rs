@@ -0,0 +1,35 @@
+//! H.264 decoders of the platforms, counterparts of the platform encoders
+
+use super::demux::Mp4Source;
+use super::native::PictureDecoder;
+use crate::Result;
+
+#[cfg(target_os = "macos")]
+mod macos;
+
+#[cfg(target_os = "windows")]
+mod windows;
+
+/// Whether the platform decodes H.264 itself, so MP4/H.264 videos need no ffmpeg
+pub(crate) const AVAILABLE: bool = cfg!(any(target_os = "macos", target_os = "windows"));
+
+/// Create the platform decoder for the H.264 track of `source`
+#[allow(unused_variables)]
+pub(crate) fn create(source: &Mp4Source) -> Result<Box<dyn PictureDecoder>> {
+    #[cfg(target_os = "macos")]
+    {
+        Ok(Box::new(macos::VideoToolboxDecoder::new(source)?))
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        Ok(Box::new(windows::MediaFoundationDecoder::new(source)?))
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        Err(crate::Error::CodecUnavailable(
+            "H.264 decoding needs ffmpeg on this platform".to_string(),
+        ))
+    }
+}
@@ -0,0 +1,400 @@
+//! Windows H.264 decoder using Media Foundation
+
+use super::super::demux::{Mp4Source, Sample};
+use super::super::native::{Picture, PictureDecoder};
+use crate::encoder::h264::media_foundation::MfSession;
+use crate::{Error, Result};
+use rayon::prelude::*;
+use std::mem::ManuallyDrop;
+use std::ptr;
+use windows::Win32::Media::MediaFoundation::*;
+use windows::Win32::System::Com::*;
+
+const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+/// Media Foundation H.264 decoder
+pub struct MediaFoundationDecoder {
+    transform: IMFTransform,
+    /// SPS and PPS in Annex B format, sent again with every keyframe
+    parameter_sets: Vec<u8>,
+    nal_length_size: usize,
+    /// Size of the track, which pictures are cropped to
+    width: usize,
+    height: usize,
+    /// Rows of the luma plane of output frames, padded to whole macroblocks
+    frame_height: usize,
+    stride: usize,
+    /// Whether the transform allocates output samples itself
+    provides_samples: bool,
+    // Declared last so the MF objects above are released before the session
+    _session: MfSession,
+}
+
+unsafe impl Send for MediaFoundationDecoder {}
+
+impl MediaFoundationDecoder {
+    pub fn new(source: &Mp4Source) -> Result<Self> {
+        let session = MfSession::acquire()?;
+
+        let mut parameter_sets = Vec::new();
+        for nal in [&source.sps, &source.pps] {
+            parameter_sets.extend_from_slice(&START_CODE);
+            parameter_sets.extend_from_slice(nal);
+        }
+
+        unsafe {
+            // Find and create H.264 decoder
+            let transform = find_h264_decoder()?;
+
+            // Create input media type (H.264)
+            let input_type: IMFMediaType = MFCreateMediaType()
+                .map_err(|e| Error::Decode(format!("Failed to create input type: {}", e)))?;
+
+            input_type
+                .SetGUID(&MF_MT_MAJOR_TYPE, &MFMediaType_Video)
+                .map_err(|e| Error::Decode(format!("Failed to set major type: {}", e)))?;
+
+            input_type
+                .SetGUID(&MF_MT_SUBTYPE, &MFVideoFormat_H264)
+                .map_err(|e| Error::Decode(format!("Failed to set subtype: {}", e)))?;
+
+            input_type
+                .SetUINT64(
+                    &MF_MT_FRAME_SIZE,
+                    ((source.width as u64) << 32) | (source.height as u64),
+                )
+                .map_err(|e| Error::Decode(format!("Failed to set frame size: {}", e)))?;
+
+            transform
+                .SetInputType(0, &input_type, 0)
+                .map_err(|e| Error::Decode(format!("Failed to set input type: {}", e)))?;
+
+            let mut decoder = Self {
+                transform,
+                parameter_sets,
+                nal_length_size: source.nal_length_size,
+                width: source.width as usize,
+                height: source.height as usize,
+                frame_height: source.height as usize,
+                stride: source.width as usize,
+                provides_samples: false,
+                _session: session,
+            };
+            decoder.set_output_type()?;
+
+            decoder
+                .transform
+                .ProcessMessage(MFT_MESSAGE_NOTIFY_BEGIN_STREAMING, 0)
+                .ok();
+            decoder
+                .transform
+                .ProcessMessage(MFT_MESSAGE_NOTIFY_START_OF_STREAM, 0)
+                .ok();
+
+            Ok(decoder)
+        }
+    }
+
+    /// Pick NV12 output, again whenever the decoder reports a format change
+    unsafe fn set_output_type(&mut self) -> Result<()> {
+        let mut index = 0;
+        while let Ok(output_type) = self.transform.GetOutputAvailableType(0, index) {
+            index += 1;
+            if output_type.GetGUID(&MF_MT_SUBTYPE).ok() != Some(MFVideoFormat_NV12) {
+                continue;
+            }
+
+            self.transform
+                .SetOutputType(0, &output_type, 0)
+                .map_err(|e| Error::Decode(format!("Failed to set output type: {}", e)))?;
+
+            if let Ok(size) = output_type.GetUINT64(&MF_MT_FRAME_SIZE) {
+                self.stride = (size >> 32) as usize;
+                self.frame_height = (size & 0xffff_ffff) as usize;
+            }
+            if let Ok(stride) = output_type.GetUINT32(&MF_MT_DEFAULT_STRIDE) {
+                self.stride = (stride as i32).unsigned_abs() as usize;
+            }
+            let stream_info = self
+                .transform
+                .GetOutputStreamInfo(0)
+                .map_err(|e| Error::Decode(format!("Failed to get stream info: {}", e)))?;
+            self.provides_samples =
+                stream_info.dwFlags & MFT_OUTPUT_STREAM_PROVIDES_SAMPLES.0 as u32 != 0;
+            return Ok(());
+        }
+
+        Err(Error::CodecUnavailable(
+            "H.264 decoder has no NV12 output".to_string(),
+        ))
+    }
+
+    /// Annex B data of `sample`, with the parameter sets before keyframes
+    fn annex_b(&self, sample: &Sample) -> Vec<u8> {
+        let mut data = Vec::with_capacity(sample.data.len() + self.parameter_sets.len());
+        if sample.is_sync {
+            data.extend_from_slice(&self.parameter_sets);
+        }
+
+        let mut offset = 0;
+        while offset + self.nal_length_size <= sample.data.len() {
+            let nal_length = sample.data[offset..offset + self.nal_length_size]
+                .iter()
+                .fold(0usize, |length, &byte| (length << 8) | byte as usize);
+            offset += self.nal_length_size;
+
+            if offset + nal_length > sample.data.len() {
+                break;
+            }
+
+            data.extend_from_slice(&START_CODE);
+            data.extend_from_slice(&sample.data[offset..offset + nal_length]);
+            offset += nal_length;
+        }
+
+        data
+    }
+
+    unsafe fn get_output_pictures(&mut self) -> Result<Vec<Picture>> {
+        let mut pictures = Vec::new();
+
+        loop {
+            let output_sample = if self.provides_samples {
+                None
+            } else {
+                let stream_info = self
+                    .transform
+                    .GetOutputStreamInfo(0)
+                    .map_err(|e| Error::Decode(format!("Failed to get stream info: {}", e)))?;
+                Some(create_sample(stream_info.cbSize, None)?)
+            };
+
+            let mut output_info = [MFT_OUTPUT_DATA_BUFFER {
+                pSample: ManuallyDrop::new(output_sample),
+                ..Default::default()
+            }];
+            let mut status = 0u32;
+
+            let result = self
+                .transform
+                .ProcessOutput(0, &mut output_info, &mut status);
+            let sample = ManuallyDrop::take(&mut output_info[0].pSample);
+            ManuallyDrop::drop(&mut output_info[0].pEvents);
+
+            match result {
+                Ok(()) => {
+                    if let Some(sample) = sample {
+                        pictures.push(self.picture(&sample)?);
+                    }
+                }
+                Err(e) if e.code() == MF_E_TRANSFORM_NEED_MORE_INPUT => break,
+                Err(e) if e.code() == MF_E_TRANSFORM_STREAM_CHANGE => self.set_output_type()?,
+                Err(e) => return Err(Error::Decode(format!("Failed to get decoded frame: {}", e))),
+            }
+        }
+
+        Ok(pictures)
+    }
+
+    /// Convert a decoded NV12 sample to an RGBA picture
+    unsafe fn picture(&self, sample: &IMFSample) -> Result<Picture> {
+        let time = sample
+            .GetSampleTime()
+            .map_err(|e| Error::Decode(format!("Failed to get time: {}", e)))?;
+        let buffer = sample
+            .ConvertToContiguousBuffer()
+            .map_err(|e| Error::Decode(format!("Failed to get buffer: {}", e)))?;
+
+        let mut data_ptr: *mut u8 = ptr::null_mut();
+        let mut length = 0u32;
+        buffer
+            .Lock(&mut data_ptr, None, Some(&mut length))
+            .map_err(|e| Error::Decode(format!("Failed to lock buffer: {}", e)))?;
+        let data = std::slice::from_raw_parts(data_ptr, length as usize);
+        let rgba = nv12_to_rgba(
+            data,
+            self.stride,
+            self.frame_height,
+            self.width,
+            self.height,
+        );
+        buffer.Unlock().ok();
+
+        Ok(Picture {
+            // Sample times are in 100 ns units
+            pts_us: time / 10,
+            data: rgba?,
+        })
+    }
+}
+
+impl PictureDecoder for MediaFoundationDecoder {
+    fn decode(&mut self, sample: &Sample) -> Result<Vec<Picture>> {
+        let data = self.annex_b(sample);
+
+        unsafe {
+            let input = create_sample(data.len() as u32, Some(&data))?;
+            input
+                .SetSampleTime(sample.pts_us * 10)
+                .map_err(|e| Error::Decode(format!("Failed to set time: {}", e)))?;
+
+            let mut pictures = Vec::new();
+            loop {
+                match self.transform.ProcessInput(0, &input, 0) {
+                    Ok(()) => break,
+                    // Frames must be taken out before more go in
+                    Err(e) if e.code() == MF_E_NOTACCEPTING => {
+                        let taken = self.get_output_pictures()?;
+                        if taken.is_empty() {
+                            return Err(Error::Decode(format!("Decoder accepts no input: {}", e)));
+                        }
+                        pictures.extend(taken);
+                    }
+                    Err(e) => return Err(Error::Decode(format!("Failed to process input: {}", e))),
+                }
+            }
+
+            pictures.extend(self.get_output_pictures()?);
+            Ok(pictures)
+        }
+    }
+
+    fn flush(&mut self) -> Result<Vec<Picture>> {
+        unsafe {
+            self.transform
+                .ProcessMessage(MFT_MESSAGE_NOTIFY_END_OF_STREAM, 0)
+                .ok();
+
+            self.transform
+                .ProcessMessage(MFT_MESSAGE_COMMAND_DRAIN, 0)
+                .map_err(|e| Error::Decode(format!("Failed to drain decoder: {}", e)))?;
+
+            self.get_output_pictures()
+        }
+    }
+}
+
+/// Create a sample with a buffer of `size` bytes, holding `data` if given
+unsafe fn create_sample(size: u32, data: Option<&[u8]>) -> Result<IMFSample> {
+    let sample: IMFSample =
+        MFCreateSample().map_err(|e| Error::Decode(format!("Failed to create sample: {}", e)))?;
+
+    let buffer: IMFMediaBuffer = MFCreateMemoryBuffer(size)
+        .map_err(|e| Error::Decode(format!("Failed to create buffer: {}", e)))?;
+
+    if let Some(data) = data {
+        let mut buffer_ptr: *mut u8 = ptr::null_mut();
+        buffer
+            .Lock(&mut buffer_ptr, None, None)
+            .map_err(|e| Error::Decode(format!("Failed to lock buffer: {}", e)))?;
+
+        ptr::copy_nonoverlapping(data.as_ptr(), buffer_ptr, data.len());
+
+        buffer
+            .Unlock()
+            .map_err(|e| Error::Decode(format!("Failed to unlock buffer: {}", e)))?;
+
+        buffer
+            .SetCurrentLength(data.len() as u32)
+            .map_err(|e| Error::Decode(format!("Failed to set length: {}", e)))?;
+    }
+
+    sample
+        .AddBuffer(&buffer)
+        .map_err(|e| Error::Decode(format!("Failed to add buffer: {}", e)))?;
+
+    Ok(sample)
+}
+
+/// Convert the top left `width` x `height` of an NV12 frame to RGBA, one row per task
+///
+/// The luma plane has `rows` rows of `stride` bytes, followed by the interleaved chroma
+/// plane. Colors are BT.601 in limited range, as the decoder outputs them.
+fn nv12_to_rgba(
+    data: &[u8],
+    stride: usize,
+    rows: usize,
+    width: usize,
+    height: usize,
+) -> Result<Vec<u8>> {
+    let uv_offset = stride * rows;
+    if width > stride || height > rows || data.len() < uv_offset + stride * height.div_ceil(2) {
+        return Err(Error::Decode(format!(
+            "Decoded frame of {} bytes is too small for {}x{}",
+            data.len(),
+            width,
+            height
+        )));
+    }
+
+    let mut rgba = vec![0u8; width * height * 4];
+    rgba.par_chunks_mut(width * 4)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let luma = &data[y * stride..];
+            let chroma = &data[uv_offset + (y / 2) * stride..];
+            for (x, out) in row.chunks_exact_mut(4).enumerate() {
+                let c = (luma[x] as i32 - 16) * 298;
+                let d = chroma[x & !1] as i32 - 128;
+                let e = chroma[x | 1] as i32 - 128;
+
+                out[0] = ((c + 409 * e + 128) >> 8).clamp(0, 255) as u8;
+                out[1] = ((c - 100 * d - 208 * e + 128) >> 8).clamp(0, 255) as u8;
+                out[2] = ((c + 516 * d + 128) >> 8).clamp(0, 255) as u8;
+                out[3] = 255;
+            }
+        });
+
+    Ok(rgba)
+}
+
+fn find_h264_decoder() -> Result<IMFTransform> {
+    unsafe {
+        let mut count = 0u32;
+        let mut activates: *mut Option<IMFActivate> = ptr::null_mut();
+
+        let input_type = MFT_REGISTER_TYPE_INFO {
+            guidMajorType: MFMediaType_Video,
+            guidSubtype: MFVideoFormat_H264,
+        };
+
+        let output_type = MFT_REGISTER_TYPE_INFO {
+            guidMajorType: MFMediaType_Video,
+            guidSubtype: MFVideoFormat_NV12,
+        };
+
+        MFTEnumEx(
+            MFT_CATEGORY_VIDEO_DECODER,
+            MFT_ENUM_FLAG_SYNCMFT | MFT_ENUM_FLAG_SORTANDFILTER,
+            Some(&input_type),
+            Some(&output_type),
+            &mut activates,
+            &mut count,
+        )
+        .map_err(|e| Error::CodecUnavailable(format!("Failed to enumerate decoders: {}", e)))?;
+
+        if count == 0 || activates.is_null() {
+            return Err(Error::CodecUnavailable(
+                "No H.264 decoder found".to_string(),
+            ));
+        }
+
+        // Create transform from the first activate object
+        let transform = match &*activates {
+            Some(activate) => activate
+                .ActivateObject()
+                .map_err(|e| Error::CodecUnavailable(format!("Failed to activate decoder: {}", e))),
+            None => Err(Error::CodecUnavailable(
+                "Invalid activate object".to_string(),
+            )),
+        };
+
+        // Release the activate objects and free their array
+        for i in 0..count as usize {
+            ptr::drop_in_place(activates.add(i));
+        }
+        CoTaskMemFree(Some(activates as *const _));
+
+        transform
+    }
+}
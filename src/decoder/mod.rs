@@ -0,0 +1,380 @@
+//! Video decoding through an ffmpeg process, or the platform H.264 decoder for MP4
+//! videos on macOS and Windows
+
+mod demux;
+mod h264;
+mod native;
+
+use crate::ffmpeg::{find_ffmpeg, StderrTail};
+use crate::image_loader::rgba_len;
+use crate::logging::log_debug;
+use crate::priority::lower_command;
+use crate::probe::{has_audio, probe};
+use crate::{EncodeOptions, Error, Priority, Result, Stage, Warning};
+use demux::Mp4Source;
+use native::NativeStream;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Frame rate videos are decoded at, and default rate of produced videos
+pub(crate) const DEFAULT_FPS: u32 = 30;
+
+/// Video frame from decoded video, borrowed from the decoder until the next read
+pub(crate) struct DecodedFrame<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub data: &'a [u8], // RGBA
+}
+
+/// Video decoder using ffmpeg or the platform H.264 decoder
+pub(crate) struct VideoDecoder {
+    pub width: u32,
+    pub height: u32,
+    pub duration_ms: u64,
+    fps: f64,
+    frame_count: u64,
+    current_frame: u64,
+    process: Option<std::process::Child>,
+    /// Stderr of the running process until it has been checked for failure
+    stderr: Option<StderrTail>,
+    /// Last complete frame (empty before the first)
+    frame: Vec<u8>,
+    /// Buffer the next frame is read into, swapped with `frame` once complete
+    next_frame: Vec<u8>,
+    /// Priority the ffmpeg process is started at
+    priority: Priority,
+    /// Threads ffmpeg decodes with, 0 for its default
+    threads: usize,
+    /// Whether frames are scaled to `width` x `height`, set by `scale_to`
+    scaled: bool,
+    /// Start and duration decoding was last started with, to start over from
+    range: (u64, Option<u64>),
+    /// Whether the video has audio, known without ffprobe for MP4 videos the platform
+    /// decodes
+    has_audio: Option<bool>,
+    /// Platform decoder of an MP4 video, running instead of ffmpeg
+    native: Option<Box<NativeStream>>,
+}
+
+impl VideoDecoder {
+    pub fn new<P: AsRef<Path>>(path: P, ffmpeg_path: Option<&str>) -> Result<Self> {
+        let path = path.as_ref();
+        // MP4/H.264 videos the platform decodes need no ffprobe either
+        let source = if h264::AVAILABLE {
+            Mp4Source::open(path)?
+        } else {
+            None
+        };
+        let (info, has_audio) = match &source {
+            Some(source) => (source.info(), Some(source.has_audio)),
+            // Get video info using ffprobe
+            None => (probe(path, ffmpeg_path)?, None),
+        };
+
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            duration_ms: info.duration_ms,
+            fps: info.fps,
+            frame_count: info.frame_count,
+            current_frame: 0,
+            process: None,
+            stderr: None,
+            frame: Vec::new(),
+            next_frame: Vec::new(),
+            priority: Priority::Normal,
+            threads: 0,
+            scaled: false,
+            range: (0, None),
+            has_audio,
+            native: None,
+        })
+    }
+
+    /// Open a video to be re-encoded, warning about how it will be converted
+    pub fn open<P: AsRef<Path>>(path: P, options: &EncodeOptions) -> Result<Self> {
+        let path = path.as_ref();
+        let ffmpeg_path = options.ffmpeg_path.as_deref();
+        let mut decoder =
+            Self::new(path, ffmpeg_path).map_err(|e| e.with_path(path).during(Stage::Decoding))?;
+        decoder.priority = options.priority;
+        decoder.threads = options.threads();
+
+        if (decoder.fps - DEFAULT_FPS as f64).abs() > 0.01 {
+            options.warn(Warning::FrameRateConverted {
+                path: path.to_path_buf(),
+                from: decoder.fps,
+                to: DEFAULT_FPS,
+            });
+        }
+
+        // Checking for audio takes another ffprobe run, so only do it when someone listens
+        if options.warning.is_some()
+            && decoder
+                .has_audio
+                .map_or_else(|| has_audio(path, ffmpeg_path), Ok)
+                .unwrap_or(false)
+        {
+            options.warn(Warning::AudioIgnored {
+                path: path.to_path_buf(),
+            });
+        }
+
+        Ok(decoder)
+    }
+
+    /// Scale frames to `width` x `height`, before decoding starts
+    pub fn scale_to(&mut self, width: u32, height: u32) {
+        if (width, height) != (self.width, self.height) {
+            self.width = width;
+            self.height = height;
+            self.scaled = true;
+        }
+    }
+
+    /// Only decode the part of the video between `start_ms` and `end_ms` (`None` for
+    /// the end), before decoding starts
+    pub fn trim(&mut self, start_ms: u64, end_ms: Option<u64>) -> Result<()> {
+        let end_ms = end_ms.unwrap_or(self.duration_ms).min(self.duration_ms);
+        if start_ms >= end_ms {
+            return Err(Error::InvalidInput(format!(
+                "Empty range {}-{} ms in a {} ms video",
+                start_ms, end_ms, self.duration_ms
+            )));
+        }
+        let duration_ms = end_ms - start_ms;
+        self.range = (start_ms, Some(duration_ms));
+        self.duration_ms = duration_ms;
+        self.frame_count = (duration_ms as f64 * self.fps / 1000.0).ceil() as u64;
+        Ok(())
+    }
+
+    /// Start decoding the video, or the part of it set by `trim`
+    pub fn start_decode<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        ffmpeg_path: Option<&str>,
+    ) -> Result<()> {
+        let (start_ms, duration_ms) = self.range;
+        self.start_decode_range(path, ffmpeg_path, start_ms, duration_ms)
+    }
+
+    /// Start decoding `duration_ms` (or the rest of the video) from `start_ms`
+    pub fn start_decode_range<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        ffmpeg_path: Option<&str>,
+        start_ms: u64,
+        duration_ms: Option<u64>,
+    ) -> Result<()> {
+        self.stop();
+        self.range = (start_ms, duration_ms);
+
+        // Only MP4 videos opened for the platform decoder know about audio up front
+        if self.has_audio.is_some() {
+            let output = (self.width, self.height);
+            match NativeStream::start(path.as_ref(), output, start_ms, duration_ms) {
+                Ok(stream) => {
+                    log_debug!(
+                        "Decoding {} with the platform decoder",
+                        path.as_ref().display()
+                    );
+                    self.native = Some(Box::new(stream));
+                    return Ok(());
+                }
+                // Such as a profile the platform cannot decode
+                Err(e) => log_debug!("Platform decoder failed, using ffmpeg: {}", e),
+            }
+        }
+
+        let ffmpeg = find_ffmpeg(ffmpeg_path)?;
+        log_debug!("Decoding {} with {}", path.as_ref().display(), ffmpeg);
+
+        let mut command = Command::new(&ffmpeg);
+        if self.threads > 0 {
+            command.args(["-threads", &self.threads.to_string()]);
+        }
+        if start_ms > 0 {
+            command.args(["-ss", &ms_to_secs(start_ms)]);
+        }
+        command.arg("-i").arg(path.as_ref());
+        if let Some(duration_ms) = duration_ms {
+            command.args(["-t", &ms_to_secs(duration_ms)]);
+        }
+        command.args(["-vf", &self.video_filter()]);
+
+        lower_command(&mut command, self.priority);
+        let mut process = command
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba", "pipe:1"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| Error::spawn(&ffmpeg, e))?;
+
+        self.stderr = Some(StderrTail::capture(&mut process));
+        self.process = Some(process);
+        Ok(())
+    }
+
+    /// Filters resampling the video onto the output timebase by the timestamps of its
+    /// frames, counted from the first one, so inputs of slightly different frame rates
+    /// such as 29.97 and 30 fps stay in sync
+    fn video_filter(&self) -> String {
+        let mut filters = vec!["setpts=PTS-STARTPTS".to_string()];
+        if self.scaled {
+            filters.push(format!("scale={}:{}", self.width, self.height));
+        }
+        filters.push(format!("fps={}:round=near", DEFAULT_FPS));
+        filters.join(",")
+    }
+
+    /// Read the next frame, repeating the last frame once the video has ended
+    ///
+    /// Frames are read into two buffers owned by the decoder, so reading allocates nothing
+    /// after the first frames.
+    pub fn read_frame(&mut self) -> Result<Option<DecodedFrame<'_>>> {
+        self.read_next()?;
+        // At the end of the video this repeats the last frame, if there was one
+        Ok(self.last_frame())
+    }
+
+    /// Read the next frame, decoding the video again from its start once it has ended
+    pub fn read_frame_looping<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        ffmpeg_path: Option<&str>,
+    ) -> Result<Option<DecodedFrame<'_>>> {
+        let started = self.process.is_some() || self.native.is_some();
+        if !self.read_next()? && self.current_frame > 0 && started {
+            let (start_ms, duration_ms) = self.range;
+            self.start_decode_range(path, ffmpeg_path, start_ms, duration_ms)?;
+            self.read_next()?;
+        }
+        Ok(self.last_frame())
+    }
+
+    /// Read the next frame into `frame`, returning false once the video has ended
+    fn read_next(&mut self) -> Result<bool> {
+        if let Some(native) = self.native.as_mut() {
+            if !native.read(&mut self.next_frame)? {
+                return Ok(false);
+            }
+            self.current_frame += 1;
+            std::mem::swap(&mut self.frame, &mut self.next_frame);
+            return Ok(true);
+        }
+
+        let process = match self.process.as_mut() {
+            Some(p) => p,
+            None => return Ok(false),
+        };
+
+        let stdout = match process.stdout.as_mut() {
+            Some(s) => s,
+            None => return Ok(false),
+        };
+
+        let frame_size = rgba_len(self.width, self.height)?;
+        self.next_frame.resize(frame_size, 0);
+
+        match stdout.read_exact(&mut self.next_frame) {
+            Ok(_) => {
+                self.current_frame += 1;
+                std::mem::swap(&mut self.frame, &mut self.next_frame);
+                Ok(true)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                // Output also ends when ffmpeg fails, so check how it exited
+                if let Some(mut stderr) = self.stderr.take() {
+                    stderr.wait(process)?;
+                }
+                Ok(false)
+            }
+            Err(e) => Err(Error::Decode(format!("Failed to read frame: {}", e))),
+        }
+    }
+
+    /// Stop the ffmpeg process or platform decoder, if running
+    fn stop(&mut self) {
+        self.native = None;
+        if let Some(mut process) = self.process.take() {
+            let _ = process.kill();
+            let _ = process.wait();
+        }
+        self.stderr = None;
+    }
+
+    /// Last frame read, if any
+    pub fn last_frame(&self) -> Option<DecodedFrame<'_>> {
+        if self.frame.is_empty() {
+            return None;
+        }
+        Some(DecodedFrame {
+            width: self.width,
+            height: self.height,
+            data: &self.frame,
+        })
+    }
+
+    /// Number of frames read from the video, not counting repeats past its end
+    pub fn frames_read(&self) -> u64 {
+        self.current_frame
+    }
+
+    /// Number of frames the whole video spans at `DEFAULT_FPS`
+    pub fn duration_frames(&self) -> u64 {
+        ((self.frame_count as f64 * DEFAULT_FPS as f64) / self.fps).ceil() as u64
+    }
+}
+
+impl Drop for VideoDecoder {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Format milliseconds as seconds for ffmpeg arguments
+fn ms_to_secs(ms: u64) -> String {
+    format!("{}.{:03}", ms / 1000, ms % 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoder(width: u32, height: u32) -> VideoDecoder {
+        VideoDecoder {
+            width,
+            height,
+            duration_ms: 1000,
+            fps: 29.97,
+            frame_count: 30,
+            current_frame: 0,
+            process: None,
+            stderr: None,
+            frame: Vec::new(),
+            next_frame: Vec::new(),
+            priority: Priority::Normal,
+            threads: 0,
+            scaled: false,
+            range: (0, None),
+            has_audio: None,
+            native: None,
+        }
+    }
+
+    #[test]
+    fn test_video_filter() {
+        let mut decoder = decoder(1920, 1080);
+        assert_eq!(
+            decoder.video_filter(),
+            "setpts=PTS-STARTPTS,fps=30:round=near"
+        );
+        decoder.scale_to(1280, 720);
+        assert_eq!(
+            decoder.video_filter(),
+            "setpts=PTS-STARTPTS,scale=1280:720,fps=30:round=near"
+        );
+    }
+}
@@ -0,0 +1,326 @@
+//! MP4/H.264 videos decoded by the platform, without ffmpeg
+//!
+//! Frames come out at `DEFAULT_FPS` the way ffmpeg's `fps=30:round=near` filter puts
+//! them: each frame fills the output slot nearest its timestamp, counted from the first
+//! frame, the last one of a slot wins and empty slots repeat the frame before.
+
+use super::demux::{Mp4Source, Sample};
+use super::{h264, DEFAULT_FPS};
+use crate::image_loader::LoadedImage;
+use crate::Result;
+use std::collections::{BTreeMap, VecDeque};
+use std::path::Path;
+
+/// Most frames a decoder can hold back before outputting an earlier one
+const MAX_REORDER: usize = 16;
+
+/// Decoded frame in RGBA at the size of the track
+pub(crate) struct Picture {
+    /// Presentation time in microseconds
+    pub pts_us: i64,
+    pub data: Vec<u8>,
+}
+
+/// Platform H.264 decoder
+///
+/// Pictures may come out in decoding order; the stream puts them back in presentation
+/// order by their timestamps.
+pub(crate) trait PictureDecoder: Send {
+    /// Decode one sample, returning the pictures it completed
+    fn decode(&mut self, sample: &Sample) -> Result<Vec<Picture>>;
+
+    /// Return the pictures the decoder still holds at the end of the samples
+    fn flush(&mut self) -> Result<Vec<Picture>>;
+}
+
+/// Frames of a part of an MP4 video, resampled to `DEFAULT_FPS`
+pub(crate) struct NativeStream {
+    source: Mp4Source,
+    decoder: Box<dyn PictureDecoder>,
+    /// Size pictures are decoded at
+    size: (u32, u32),
+    /// Size frames are scaled to
+    output: (u32, u32),
+    start_us: i64,
+    end_us: Option<i64>,
+    /// Presentation times of the pictures still to come out of the decoder, in order
+    expected: VecDeque<i64>,
+    /// Pictures decoded before some that are presented earlier
+    pending: BTreeMap<i64, Vec<u8>>,
+    /// Pictures in presentation order
+    ready: VecDeque<Picture>,
+    /// All samples have been decoded
+    finished: bool,
+    /// Time of the first frame in range, which the others are counted from
+    first_pts: Option<i64>,
+    /// Frame of the last slot, scaled to `output`
+    current: Option<Vec<u8>>,
+    /// Next picture and the slot it fills
+    lookahead: Option<(u64, Vec<u8>)>,
+    /// Next output slot
+    slot: u64,
+    /// Number of output frames
+    frame_count: u64,
+}
+
+impl NativeStream {
+    /// Start decoding `duration_ms` (or the rest of the video) from `start_ms` with the
+    /// platform decoder, scaling frames to `output`
+    pub fn start(
+        path: &Path,
+        output: (u32, u32),
+        start_ms: u64,
+        duration_ms: Option<u64>,
+    ) -> Result<Self> {
+        let source = Mp4Source::open(path)?.ok_or_else(|| {
+            crate::Error::Decode("The video has no H.264 track to decode".to_string())
+        })?;
+        let decoder = h264::create(&source)?;
+        Ok(Self::new(source, decoder, output, start_ms, duration_ms))
+    }
+
+    fn new(
+        mut source: Mp4Source,
+        decoder: Box<dyn PictureDecoder>,
+        output: (u32, u32),
+        start_ms: u64,
+        duration_ms: Option<u64>,
+    ) -> Self {
+        let duration_ms =
+            duration_ms.unwrap_or_else(|| source.info().duration_ms.saturating_sub(start_ms));
+        let start_us = start_ms as i64 * 1000;
+        source.seek(start_us);
+        Self {
+            size: (source.width, source.height),
+            expected: source.remaining_pts().into(),
+            source,
+            decoder,
+            output,
+            start_us,
+            end_us: duration_ms.checked_mul(1000).map(|d| start_us + d as i64),
+            pending: BTreeMap::new(),
+            ready: VecDeque::new(),
+            finished: false,
+            first_pts: None,
+            current: None,
+            lookahead: None,
+            slot: 0,
+            frame_count: (duration_ms * DEFAULT_FPS as u64).div_ceil(1000),
+        }
+    }
+
+    /// Write the frame of the next slot into `frame`, returning false after the last
+    pub fn read(&mut self, frame: &mut Vec<u8>) -> Result<bool> {
+        if self.slot >= self.frame_count {
+            return Ok(false);
+        }
+        loop {
+            if self.lookahead.is_none() {
+                self.lookahead = self.next_picture()?.map(|picture| {
+                    // Timestamps are rounded to microseconds, so halfway ones may be a
+                    // little early: nudge them to round up like ffmpeg's
+                    let slot = ((picture.pts_us + 1) * DEFAULT_FPS as i64 + 500_000) / 1_000_000;
+                    (slot.max(0) as u64, picture.data)
+                });
+            }
+            match self.lookahead.take() {
+                Some((slot, data)) if slot <= self.slot || self.current.is_none() => {
+                    self.current = Some(self.scale(data)?);
+                }
+                lookahead => {
+                    self.lookahead = lookahead;
+                    break;
+                }
+            }
+        }
+
+        let Some(current) = &self.current else {
+            return Ok(false);
+        };
+        frame.clear();
+        frame.extend_from_slice(current);
+        self.slot += 1;
+        Ok(true)
+    }
+
+    fn scale(&self, data: Vec<u8>) -> Result<Vec<u8>> {
+        if self.output == self.size {
+            return Ok(data);
+        }
+        let (width, height) = self.size;
+        let image = LoadedImage::from_rgba(width, height, data)?;
+        Ok(image.resize(self.output.0, self.output.1)?.data)
+    }
+
+    /// Next picture in range in presentation order, timed from the first one
+    fn next_picture(&mut self) -> Result<Option<Picture>> {
+        loop {
+            if let Some(mut picture) = self.ready.pop_front() {
+                // Pictures before the start only decode the ones after it
+                if picture.pts_us < self.start_us
+                    || self.end_us.is_some_and(|end| picture.pts_us >= end)
+                {
+                    continue;
+                }
+                picture.pts_us -= *self.first_pts.get_or_insert(picture.pts_us);
+                return Ok(Some(picture));
+            }
+            if self.finished {
+                return Ok(None);
+            }
+            self.feed()?;
+        }
+    }
+
+    /// Decode the next sample, or flush the decoder once no more are needed
+    fn feed(&mut self) -> Result<()> {
+        let sample = self.source.read()?;
+        let past_end =
+            |sample: &Sample| sample.is_sync && self.end_us.is_some_and(|end| sample.pts_us >= end);
+        match sample {
+            Some(sample) if !past_end(&sample) => {
+                let pictures = self.decoder.decode(&sample)?;
+                self.reorder(pictures, false);
+            }
+            _ => {
+                self.finished = true;
+                let pictures = self.decoder.flush()?;
+                self.reorder(pictures, true);
+            }
+        }
+        Ok(())
+    }
+
+    /// Move decoded pictures to `ready` once those presented before them are, or all of
+    /// them at the end
+    fn reorder(&mut self, pictures: Vec<Picture>, end: bool) {
+        for picture in pictures {
+            self.pending.insert(picture.pts_us, picture.data);
+        }
+        loop {
+            let next = match self.expected.front() {
+                Some(pts) if self.pending.contains_key(pts) => *pts,
+                // A picture the decoder dropped, or left over at the end
+                _ if end || self.pending.len() > MAX_REORDER => match self.pending.keys().next() {
+                    Some(&pts) => pts,
+                    None => break,
+                },
+                _ => break,
+            };
+            while self.expected.front().is_some_and(|&pts| pts <= next) {
+                self.expected.pop_front();
+            }
+            if let Some(data) = self.pending.remove(&next) {
+                self.ready.push_back(Picture { pts_us: next, data });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::Packet;
+    use crate::muxer::mp4::Mp4Muxer;
+    use crate::muxer::{Muxer, MuxerConfig};
+    use crate::{Codec, ColorSpace, PixelRange};
+    use tempfile::TempDir;
+
+    /// Decoder of the test samples, which carry their frame number: pictures are filled
+    /// with it and come out one sample late, like a decoder with a frame of delay
+    struct FakeDecoder {
+        held: Option<Picture>,
+    }
+
+    impl PictureDecoder for FakeDecoder {
+        fn decode(&mut self, sample: &Sample) -> Result<Vec<Picture>> {
+            let picture = Picture {
+                pts_us: sample.pts_us,
+                data: vec![sample.data[5]; 4 * 4 * 4],
+            };
+            Ok(self.held.replace(picture).into_iter().collect())
+        }
+
+        fn flush(&mut self) -> Result<Vec<Picture>> {
+            Ok(self.held.take().into_iter().collect())
+        }
+    }
+
+    /// MP4 of `count` 4x4 frames at `fps`, a keyframe every 10
+    fn stream(temp_dir: &TempDir, fps: u32, count: u8, range: (u64, Option<u64>)) -> NativeStream {
+        let path = temp_dir.path().join(format!("video{}.mp4", fps));
+        let config = MuxerConfig {
+            width: 4,
+            height: 4,
+            fps,
+            codec: Codec::H264,
+            codec_config: Some(vec![0x67, 0x42, 0xc0, 0x1e]),
+            pps: Some(vec![0x68, 0xce, 0x3c, 0x80]),
+            color_space: ColorSpace::Bt601,
+            pixel_range: PixelRange::Auto,
+        };
+        let mut muxer: Box<dyn Muxer> = Box::new(Mp4Muxer::new(&path, config).unwrap());
+        for i in 0..count {
+            muxer
+                .write_packet(&Packet {
+                    data: vec![if i % 10 == 0 { 0x65 } else { 0x41 }, i],
+                    pts: i as i64,
+                    dts: i as i64,
+                    is_keyframe: i % 10 == 0,
+                })
+                .unwrap();
+        }
+        muxer.finalize().unwrap();
+
+        let source = Mp4Source::open(&path).unwrap().unwrap();
+        let decoder = Box::new(FakeDecoder { held: None });
+        NativeStream::new(source, decoder, (4, 4), range.0, range.1)
+    }
+
+    fn frames(mut stream: NativeStream) -> Vec<u8> {
+        let mut frame = Vec::new();
+        let mut numbers = Vec::new();
+        while stream.read(&mut frame).unwrap() {
+            numbers.push(frame[0]);
+        }
+        numbers
+    }
+
+    #[test]
+    fn test_native_stream_resamples() {
+        let temp_dir = TempDir::new().unwrap();
+        assert_eq!(
+            frames(stream(&temp_dir, 30, 6, (0, None))),
+            [0, 1, 2, 3, 4, 5]
+        );
+        // 15 fps frames are shown twice, 60 fps frames every other one
+        assert_eq!(
+            frames(stream(&temp_dir, 15, 3, (0, None))),
+            [0, 0, 1, 1, 2, 2]
+        );
+        assert_eq!(
+            frames(stream(&temp_dir, 60, 12, (0, None))),
+            [0, 2, 4, 6, 8, 10]
+        );
+    }
+
+    #[test]
+    fn test_native_stream_range() {
+        let temp_dir = TempDir::new().unwrap();
+        // Decoding starts at the keyframe of frame 10, and frames before 15 are dropped
+        assert_eq!(
+            frames(stream(&temp_dir, 30, 30, (500, Some(200)))),
+            [15, 16, 17, 18, 19, 20]
+        );
+    }
+
+    #[test]
+    fn test_native_stream_scales() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut stream = stream(&temp_dir, 30, 2, (0, None));
+        stream.output = (2, 2);
+        let mut frame = Vec::new();
+        assert!(stream.read(&mut frame).unwrap());
+        assert_eq!(frame.len(), 2 * 2 * 4);
+    }
+}
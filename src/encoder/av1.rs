@@ -1,15 +1,21 @@
 //! AV1 encoder using rav1e
 
-use super::{Encoder, EncoderConfig, Frame, Packet};
+use super::color::{rgba_to_yuv420, Matrix, PlaneMut};
+use super::{ColorSpace, Encoder, EncoderBackend, EncoderConfig, Frame, Packet};
+use crate::priority::thread_pool;
 use crate::{Error, Result};
 use rav1e::prelude::*;
+use rayon::ThreadPool;
+use std::sync::Arc;
 
 /// AV1 encoder using rav1e
 pub struct Av1Encoder {
     context: Context<u8>,
-    #[allow(dead_code)]
+    rav1e_config: Config,
     config: EncoderConfig,
     frame_count: u64,
+    /// Threads encoding runs on, `None` for the calling thread and rayon's global pool
+    pool: Option<Arc<ThreadPool>>,
 }
 
 impl Av1Encoder {
@@ -19,6 +25,22 @@ impl Av1Encoder {
         // Higher quality = lower quantizer
         let quantizer = ((100 - config.quality.min(100)) as usize * 255) / 100;
         let min_quantizer = (quantizer.saturating_sub(10)) as u8;
+        // A target bitrate lets rate control pick quantizers, starting from the quality's;
+        // the shortest reservoir holds it close to constant
+        let (bitrate, min_quantizer, reservoir_frame_delay) = match config.bitrate() {
+            Some((kbps, constant)) => (
+                kbps.saturating_mul(1000).min(i32::MAX as u32) as i32,
+                0,
+                constant.then_some(12),
+            ),
+            None => (0, min_quantizer, None),
+        };
+
+        // A set interval places keyframes exactly that far apart, not at scene changes
+        let (min_key_frame_interval, max_key_frame_interval) = match config.keyframe_interval {
+            Some(interval) => (interval as u64, interval as u64),
+            None => (0, 240),
+        };
 
         let enc_config = rav1e::config::EncoderConfig {
             width: config.width as usize,
@@ -29,21 +51,25 @@ impl Av1Encoder {
             bit_depth: 8,
             chroma_sampling: ChromaSampling::Cs420,
             chroma_sample_position: ChromaSamplePosition::Unknown,
-            pixel_range: PixelRange::Limited,
-            color_description: None,
+            pixel_range: if config.pixel_range.is_full(false) {
+                PixelRange::Full
+            } else {
+                PixelRange::Limited
+            },
+            color_description: Some(color_description(config.color_space)),
             mastering_display: None,
             content_light: None,
             enable_timing_info: false,
             still_picture: false,
             error_resilient: false,
             switch_frame_interval: 0,
-            min_key_frame_interval: 0,
-            max_key_frame_interval: 240,
-            reservoir_frame_delay: None,
+            min_key_frame_interval,
+            max_key_frame_interval,
+            reservoir_frame_delay,
             low_latency: false,
             quantizer,
             min_quantizer,
-            bitrate: 0,
+            bitrate,
             tune: Tune::Psychovisual,
             tile_cols: 0,
             tile_rows: 0,
@@ -51,6 +77,13 @@ impl Av1Encoder {
             ..Default::default()
         };
 
+        // Without a pool, rav1e and the color conversion use rayon's global pool
+        let threads = if config.deterministic {
+            1
+        } else {
+            config.threads
+        };
+        let pool = thread_pool(config.priority, threads);
         let rav1e_config = Config::new()
             .with_encoder_config(enc_config)
             .with_threads(0);
@@ -61,8 +94,10 @@ impl Av1Encoder {
 
         Ok(Self {
             context,
+            rav1e_config,
             config,
             frame_count: 0,
+            pool,
         })
     }
 
@@ -70,62 +105,19 @@ impl Av1Encoder {
     fn rgba_to_yuv420(&self, frame: &Frame) -> rav1e::Frame<u8> {
         let mut yuv_frame = self.context.new_frame();
 
-        let width = frame.width as usize;
-        let height = frame.height as usize;
-
-        // Y plane
-        for y in 0..height {
-            for x in 0..width {
-                let idx = (y * width + x) * 4;
-                let r = frame.data[idx] as f32;
-                let g = frame.data[idx + 1] as f32;
-                let b = frame.data[idx + 2] as f32;
-
-                // BT.601 conversion
-                let y_val = (0.299 * r + 0.587 * g + 0.114 * b).clamp(0.0, 255.0) as u8;
-                yuv_frame.planes[0].data_origin_mut()[y * width + x] = y_val;
-            }
-        }
-
-        // U and V planes (subsampled 2x2)
-        let uv_width = width.div_ceil(2);
-        let uv_height = height.div_ceil(2);
-
-        for y in 0..uv_height {
-            for x in 0..uv_width {
-                let src_x = x * 2;
-                let src_y = y * 2;
-
-                // Average 2x2 block
-                let mut r_sum = 0u32;
-                let mut g_sum = 0u32;
-                let mut b_sum = 0u32;
-                let mut count = 0u32;
-
-                for dy in 0..2 {
-                    for dx in 0..2 {
-                        let sx = (src_x + dx).min(width - 1);
-                        let sy = (src_y + dy).min(height - 1);
-                        let idx = (sy * width + sx) * 4;
-                        r_sum += frame.data[idx] as u32;
-                        g_sum += frame.data[idx + 1] as u32;
-                        b_sum += frame.data[idx + 2] as u32;
-                        count += 1;
-                    }
-                }
-
-                let r = (r_sum / count) as f32;
-                let g = (g_sum / count) as f32;
-                let b = (b_sum / count) as f32;
-
-                // BT.601 conversion
-                let u = ((-0.169 * r - 0.331 * g + 0.500 * b) + 128.0).clamp(0.0, 255.0) as u8;
-                let v = ((0.500 * r - 0.419 * g - 0.081 * b) + 128.0).clamp(0.0, 255.0) as u8;
-
-                yuv_frame.planes[1].data_origin_mut()[y * uv_width + x] = u;
-                yuv_frame.planes[2].data_origin_mut()[y * uv_width + x] = v;
-            }
-        }
+        let [y_plane, u_plane, v_plane] = &mut yuv_frame.planes;
+        rgba_to_yuv420(
+            frame.data,
+            frame.width as usize,
+            frame.height as usize,
+            Matrix::new(
+                self.config.color_space,
+                self.config.pixel_range.is_full(false),
+            ),
+            plane_mut(y_plane),
+            plane_mut(u_plane),
+            plane_mut(v_plane),
+        );
 
         yuv_frame
     }
@@ -154,10 +146,17 @@ impl Av1Encoder {
 
         Ok(packets)
     }
-}
 
-impl Encoder for Av1Encoder {
-    fn encode(&mut self, frame: &Frame) -> Result<Vec<Packet>> {
+    /// Run `f` on the encoder's pool, if it has one
+    fn on_pool<R: Send>(&mut self, f: impl FnOnce(&mut Self) -> R + Send) -> R {
+        match self.pool.clone() {
+            Some(pool) => pool.install(|| f(self)),
+            None => f(self),
+        }
+    }
+
+    fn encode_frame(&mut self, frame: &Frame) -> Result<Vec<Packet>> {
+        frame.check(&self.config)?;
         let yuv_frame = self.rgba_to_yuv420(frame);
 
         self.context
@@ -168,7 +167,7 @@ impl Encoder for Av1Encoder {
         self.receive_packets()
     }
 
-    fn flush(&mut self) -> Result<Vec<Packet>> {
+    fn flush_packets(&mut self) -> Result<Vec<Packet>> {
         self.context.flush();
 
         let mut packets = Vec::new();
@@ -193,3 +192,103 @@ impl Encoder for Av1Encoder {
         Ok(packets)
     }
 }
+
+impl Encoder for Av1Encoder {
+    fn encode(&mut self, frame: &Frame) -> Result<Vec<Packet>> {
+        self.on_pool(|encoder| encoder.encode_frame(frame))
+    }
+
+    fn flush(&mut self) -> Result<Vec<Packet>> {
+        self.on_pool(Self::flush_packets)
+    }
+
+    fn backend(&self) -> EncoderBackend {
+        EncoderBackend::Rav1e
+    }
+
+    fn reset(&mut self) -> bool {
+        // A flushed context cannot take more frames, a new one starts the next stream
+        match self.rav1e_config.new_context() {
+            Ok(context) => {
+                self.context = context;
+                self.frame_count = 0;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+/// Visible area of a rav1e plane, from its origin
+fn plane_mut(plane: &mut Plane<u8>) -> PlaneMut<'_> {
+    let stride = plane.cfg.stride;
+    PlaneMut {
+        data: plane.data_origin_mut(),
+        stride,
+    }
+}
+
+/// Color description of the sequence header for `color_space`
+fn color_description(color_space: ColorSpace) -> ColorDescription {
+    match color_space {
+        ColorSpace::Bt601 => ColorDescription {
+            color_primaries: ColorPrimaries::BT601,
+            transfer_characteristics: TransferCharacteristics::BT601,
+            matrix_coefficients: MatrixCoefficients::BT601,
+        },
+        ColorSpace::Bt709 => ColorDescription {
+            color_primaries: ColorPrimaries::BT709,
+            transfer_characteristics: TransferCharacteristics::BT709,
+            matrix_coefficients: MatrixCoefficients::BT709,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Av1Backend, PixelRange, Priority, RateControl};
+
+    #[test]
+    fn test_rgba_to_yuv420_rows() {
+        let config = EncoderConfig {
+            width: 64,
+            height: 32,
+            fps: 30,
+            quality: 50,
+            bitrate_kbps: None,
+            rate_control: RateControl::Auto,
+            keyframe_interval: None,
+            color_space: ColorSpace::Bt601,
+            // Gray levels come out as they are in full range
+            pixel_range: PixelRange::Full,
+            deterministic: false,
+            priority: Priority::Normal,
+            threads: 0,
+            av1_backend: Av1Backend::Rav1e,
+        };
+        let encoder = Av1Encoder::new(config).unwrap();
+
+        // Each row has its own gray level
+        let data: Vec<u8> = (0..32u8)
+            .flat_map(|y| std::iter::repeat_n(y * 8, 64 * 4))
+            .collect();
+        let frame = Frame {
+            width: 64,
+            height: 32,
+            data: &data,
+            pts_ms: 0,
+        };
+
+        let yuv = encoder.rgba_to_yuv420(&frame);
+        let y_plane = &yuv.planes[0];
+        for y in 0..32 {
+            let row = &y_plane.data_origin()[y * y_plane.cfg.stride..][..64];
+            assert!(
+                row.iter().all(|&v| v.abs_diff(y as u8 * 8) <= 1),
+                "row {}",
+                y
+            );
+        }
+    }
+}
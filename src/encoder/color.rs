@@ -0,0 +1,228 @@
+//! RGBA to YUV color conversion (BT.601 or BT.709, full or limited range)
+
+use super::ColorSpace;
+use rayon::prelude::*;
+
+/// Rows of the matrix converting RGB to Y, U and V, scaled to the range
+#[derive(Debug, Clone, Copy)]
+pub struct Matrix {
+    y: [f32; 3],
+    u: [f32; 3],
+    v: [f32; 3],
+    /// Luma of black and chroma of gray
+    offsets: (f32, f32),
+}
+
+impl Matrix {
+    /// Matrix of `color_space`, to values from 0 to 255 if `full_range` or else to luma
+    /// from 16 to 235 and chroma from 16 to 240
+    pub fn new(color_space: ColorSpace, full_range: bool) -> Self {
+        let (y, u, v) = match color_space {
+            ColorSpace::Bt601 => (
+                [0.299, 0.587, 0.114],
+                [-0.169, -0.331, 0.500],
+                [0.500, -0.419, -0.081],
+            ),
+            ColorSpace::Bt709 => (
+                [0.2126, 0.7152, 0.0722],
+                [-0.1146, -0.3854, 0.500],
+                [0.500, -0.4542, -0.0458],
+            ),
+        };
+        if full_range {
+            return Self {
+                y,
+                u,
+                v,
+                offsets: (0.0, 128.0),
+            };
+        }
+        let scale = |row: [f32; 3], range: f32| row.map(|c| c * range / 255.0);
+        Self {
+            y: scale(y, 219.0),
+            u: scale(u, 224.0),
+            v: scale(v, 224.0),
+            // Half a step more rounds the scaled values, which may fall just short
+            offsets: (16.5, 128.5),
+        }
+    }
+
+    /// Luma of an RGB pixel
+    pub fn luma(&self, r: u8, g: u8, b: u8) -> u8 {
+        (dot(self.y, r, g, b) + self.offsets.0).clamp(0.0, 255.0) as u8
+    }
+
+    /// Chroma (U, V) of an RGB pixel
+    pub fn chroma(&self, r: u8, g: u8, b: u8) -> (u8, u8) {
+        let u = (dot(self.u, r, g, b) + self.offsets.1).clamp(0.0, 255.0) as u8;
+        let v = (dot(self.v, r, g, b) + self.offsets.1).clamp(0.0, 255.0) as u8;
+        (u, v)
+    }
+}
+
+fn dot(row: [f32; 3], r: u8, g: u8, b: u8) -> f32 {
+    row[0] * r as f32 + row[1] * g as f32 + row[2] * b as f32
+}
+
+/// Destination image plane with its row stride in bytes
+pub struct PlaneMut<'a> {
+    pub data: &'a mut [u8],
+    pub stride: usize,
+}
+
+/// Convert RGBA pixels to planar YUV 4:2:0, one row per task
+///
+/// Chroma is averaged over 2x2 blocks; with odd dimensions the last column or row is
+/// repeated. Planes must hold `height` (or `height / 2` rounded up) rows of their stride.
+pub fn rgba_to_yuv420(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    matrix: Matrix,
+    y_plane: PlaneMut,
+    u_plane: PlaneMut,
+    v_plane: PlaneMut,
+) {
+    convert_luma(rgba, width, height, matrix, y_plane);
+
+    // U and V planes (subsampled 2x2)
+    u_plane
+        .data
+        .par_chunks_mut(u_plane.stride)
+        .zip(v_plane.data.par_chunks_mut(v_plane.stride))
+        .take(height.div_ceil(2))
+        .enumerate()
+        .for_each(|(y, (u_row, v_row))| {
+            for x in 0..width.div_ceil(2) {
+                (u_row[x], v_row[x]) = block_chroma(rgba, width, height, matrix, x, y);
+            }
+        });
+}
+
+/// Convert RGBA pixels to NV12 (a luma plane, then interleaved U and V), one row per
+/// task, like [`rgba_to_yuv420`]
+pub fn rgba_to_nv12(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    matrix: Matrix,
+    y_plane: PlaneMut,
+    uv_plane: PlaneMut,
+) {
+    convert_luma(rgba, width, height, matrix, y_plane);
+
+    uv_plane
+        .data
+        .par_chunks_mut(uv_plane.stride)
+        .take(height.div_ceil(2))
+        .enumerate()
+        .for_each(|(y, row)| {
+            for x in 0..width.div_ceil(2) {
+                (row[x * 2], row[x * 2 + 1]) = block_chroma(rgba, width, height, matrix, x, y);
+            }
+        });
+}
+
+fn convert_luma(rgba: &[u8], width: usize, height: usize, matrix: Matrix, plane: PlaneMut) {
+    plane
+        .data
+        .par_chunks_mut(plane.stride)
+        .take(height)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, out) in row[..width].iter_mut().enumerate() {
+                let idx = (y * width + x) * 4;
+                *out = matrix.luma(rgba[idx], rgba[idx + 1], rgba[idx + 2]);
+            }
+        });
+}
+
+/// Chroma of the 2x2 block at (`x`, `y`) in chroma samples, averaged over its pixels
+fn block_chroma(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    matrix: Matrix,
+    x: usize,
+    y: usize,
+) -> (u8, u8) {
+    let mut r_sum = 0u32;
+    let mut g_sum = 0u32;
+    let mut b_sum = 0u32;
+
+    for dy in 0..2 {
+        for dx in 0..2 {
+            let sx = (x * 2 + dx).min(width - 1);
+            let sy = (y * 2 + dy).min(height - 1);
+            let idx = (sy * width + sx) * 4;
+            r_sum += rgba[idx] as u32;
+            g_sum += rgba[idx + 1] as u32;
+            b_sum += rgba[idx + 2] as u32;
+        }
+    }
+
+    matrix.chroma((r_sum / 4) as u8, (g_sum / 4) as u8, (b_sum / 4) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix() {
+        for color_space in [ColorSpace::Bt601, ColorSpace::Bt709] {
+            let matrix = Matrix::new(color_space, true);
+            assert_eq!(matrix.luma(255, 255, 255), 255);
+            assert_eq!(matrix.chroma(255, 255, 255), (128, 128));
+        }
+        // Green weighs more in BT.709 luma, red and blue less
+        assert_eq!(Matrix::new(ColorSpace::Bt601, true).luma(0, 255, 0), 149);
+        assert_eq!(Matrix::new(ColorSpace::Bt709, true).luma(0, 255, 0), 182);
+        assert_eq!(Matrix::new(ColorSpace::Bt709, true).luma(255, 0, 0), 54);
+    }
+
+    #[test]
+    fn test_matrix_limited_range() {
+        for color_space in [ColorSpace::Bt601, ColorSpace::Bt709] {
+            let matrix = Matrix::new(color_space, false);
+            assert_eq!(matrix.luma(0, 0, 0), 16);
+            assert_eq!(matrix.luma(255, 255, 255), 235);
+            assert_eq!(matrix.chroma(128, 128, 128), (128, 128));
+            assert_eq!(matrix.chroma(0, 0, 255).0, 240);
+            assert_eq!(matrix.chroma(255, 0, 0).1, 240);
+        }
+    }
+
+    #[test]
+    fn test_rgba_to_nv12() {
+        // 3x2 pixels of red, green and blue over gray
+        let rgba = [
+            [255, 0, 0, 255],
+            [0, 255, 0, 255],
+            [0, 0, 255, 255],
+            [128, 128, 128, 255],
+            [128, 128, 128, 255],
+            [128, 128, 128, 255],
+        ]
+        .concat();
+        let matrix = Matrix::new(ColorSpace::Bt601, true);
+        let (mut y, mut uv) = ([0u8; 6], [0u8; 4]);
+        rgba_to_nv12(
+            &rgba,
+            3,
+            2,
+            matrix,
+            PlaneMut {
+                data: &mut y,
+                stride: 3,
+            },
+            PlaneMut {
+                data: &mut uv,
+                stride: 4,
+            },
+        );
+        assert_eq!(y, [76, 149, 29, 128, 128, 128]);
+        let (u, v) = matrix.chroma(64, 64, 191);
+        assert_eq!(uv[2..], [u, v]);
+    }
+}
@@ -0,0 +1,426 @@
+//! Raw frames streamed through an ffmpeg encoder process
+
+use super::{ColorSpace, EncoderConfig};
+use crate::ffmpeg::StderrTail;
+use crate::{Error, Result};
+use std::io::{self, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::thread;
+
+/// Frames handed to the writer thread and not yet written to ffmpeg
+const MAX_FRAMES_IN_FLIGHT: usize = 2;
+
+/// Events from the writer and reader threads not yet handled by the encoder
+const EVENT_QUEUE: usize = 16;
+
+/// Bytes read from ffmpeg stdout at a time
+const READ_CHUNK: usize = 65536;
+
+/// Size of the IVF file header
+const IVF_HEADER: usize = 32;
+
+/// Size of the header before each IVF frame: frame size and timestamp
+const IVF_FRAME_HEADER: usize = 12;
+
+/// Progress reported by the threads feeding and draining ffmpeg
+enum Event {
+    /// A frame was written to stdin; its buffer is returned for reuse
+    Written(Vec<u8>),
+    WriteFailed(io::Error),
+    /// Encoded bytes read from stdout
+    Output(Vec<u8>),
+    ReadFailed(io::Error),
+}
+
+/// ffmpeg process reading raw frames from stdin and writing encoded output to stdout
+///
+/// Frames are written to ffmpeg's stdin and its output read from stdout on two threads,
+/// which report to the encoder through one bounded queue. The encoder waits on that queue
+/// whenever too many frames are in flight, so output keeps being drained while ffmpeg
+/// consumes large frames, and ffmpeg is held back while the caller is not encoding.
+pub(crate) struct FfmpegPipe {
+    process: Child,
+    stderr: StderrTail,
+    /// Sends frames to the writer thread; dropped to close stdin
+    frames: Option<Sender<Vec<u8>>>,
+    events: Receiver<Event>,
+    in_flight: usize,
+    /// Frame buffers returned by the writer thread
+    spare: Vec<Vec<u8>>,
+}
+
+impl FfmpegPipe {
+    /// Start `command`, running `ffmpeg`, with its standard streams piped
+    pub fn spawn(mut command: Command, ffmpeg: &str) -> Result<Self> {
+        let mut process = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| Error::spawn(ffmpeg, e))?;
+        let stderr = StderrTail::capture(&mut process);
+
+        let (Some(stdin), Some(stdout)) = (process.stdin.take(), process.stdout.take()) else {
+            return Err(Error::ffmpeg("FFmpeg pipes not available"));
+        };
+        let (frames, frame_receiver) = mpsc::channel();
+        let (event_sender, events) = mpsc::sync_channel(EVENT_QUEUE);
+        let writer_events = event_sender.clone();
+        thread::spawn(move || write_frames(stdin, frame_receiver, writer_events));
+        thread::spawn(move || read_output(stdout, event_sender));
+
+        Ok(Self {
+            process,
+            stderr,
+            frames: Some(frames),
+            events,
+            in_flight: 0,
+            spare: Vec::new(),
+        })
+    }
+
+    /// Queue `data` to be written to ffmpeg, appending output read so far to `output`
+    pub fn send(&mut self, data: &[u8], output: &mut Vec<u8>) -> Result<()> {
+        // Wait for the writer, collecting output meanwhile so ffmpeg never stalls
+        while self.in_flight >= MAX_FRAMES_IN_FLIGHT {
+            self.wait_event(output)?;
+        }
+        while let Ok(event) = self.events.try_recv() {
+            self.handle(event, output)?;
+        }
+
+        let mut buffer = self.spare.pop().unwrap_or_default();
+        buffer.clear();
+        buffer.extend_from_slice(data);
+        let sent = self
+            .frames
+            .as_ref()
+            .is_some_and(|frames| frames.send(buffer).is_ok());
+        if !sent {
+            return Err(self
+                .stderr
+                .error(&mut self.process, "FFmpeg stopped accepting frames"));
+        }
+        self.in_flight += 1;
+        Ok(())
+    }
+
+    /// Close stdin and append the remaining output to `output` once ffmpeg exits
+    pub fn finish(&mut self, output: &mut Vec<u8>) -> Result<()> {
+        // The writer closes stdin once its frames are written, signalling end of input
+        drop(self.frames.take());
+
+        // Both threads stop, closing the queue, once ffmpeg has written all output
+        while let Ok(event) = self.events.recv() {
+            self.handle(event, output)?;
+        }
+
+        self.stderr.wait(&mut self.process)
+    }
+
+    /// Apply an event from the writer or reader thread
+    fn handle(&mut self, event: Event, output: &mut Vec<u8>) -> Result<()> {
+        match event {
+            Event::Written(buffer) => {
+                self.in_flight -= 1;
+                self.spare.push(buffer);
+            }
+            Event::WriteFailed(e) => {
+                // Writing fails once ffmpeg has exited
+                return Err(self
+                    .stderr
+                    .error(&mut self.process, format!("Failed to write frame: {}", e)));
+            }
+            Event::Output(data) => output.extend_from_slice(&data),
+            Event::ReadFailed(e) => {
+                return Err(Error::ffmpeg(format!("Failed to read output: {}", e)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Block until the writer or reader thread reports, failing if both have stopped
+    fn wait_event(&mut self, output: &mut Vec<u8>) -> Result<()> {
+        match self.events.recv() {
+            Ok(event) => self.handle(event, output),
+            Err(_) => Err(self
+                .stderr
+                .error(&mut self.process, "FFmpeg stopped unexpectedly")),
+        }
+    }
+}
+
+impl Drop for FfmpegPipe {
+    fn drop(&mut self) {
+        // Kill the process if it's still running
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+/// Write frames to ffmpeg until the encoder stops sending them, then close stdin
+fn write_frames(mut stdin: ChildStdin, frames: Receiver<Vec<u8>>, events: SyncSender<Event>) {
+    for frame in frames {
+        let event = match stdin.write_all(&frame) {
+            Ok(()) => Event::Written(frame),
+            Err(e) => Event::WriteFailed(e),
+        };
+        let failed = matches!(event, Event::WriteFailed(_));
+        // A closed queue means the encoder was dropped
+        if events.send(event).is_err() || failed {
+            break;
+        }
+    }
+}
+
+/// Read ffmpeg output until it closes stdout
+fn read_output(mut stdout: ChildStdout, events: SyncSender<Event>) {
+    loop {
+        let mut chunk = vec![0u8; READ_CHUNK];
+        let event = match stdout.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => {
+                chunk.truncate(n);
+                Event::Output(chunk)
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => Event::ReadFailed(e),
+        };
+        let failed = matches!(event, Event::ReadFailed(_));
+        if events.send(event).is_err() || failed {
+            break;
+        }
+    }
+}
+
+/// IVF output of ffmpeg, read in chunks and split into frames
+#[derive(Default)]
+pub(crate) struct IvfFrames {
+    /// Output not yet split into frames
+    buffer: Vec<u8>,
+    /// Whether the IVF file header has been skipped
+    header_skipped: bool,
+}
+
+impl IvfFrames {
+    /// Buffer ffmpeg output is appended to
+    pub fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    /// Split the complete frames off the output read so far
+    pub fn take(&mut self) -> Vec<Vec<u8>> {
+        if !self.header_skipped {
+            if self.buffer.len() < IVF_HEADER {
+                return Vec::new();
+            }
+            self.buffer.drain(..IVF_HEADER);
+            self.header_skipped = true;
+        }
+
+        let (frames, consumed) = split_ivf_frames(&self.buffer);
+        self.buffer.drain(..consumed);
+        frames
+    }
+}
+
+/// Split `data`, IVF output after the file header, into frames
+///
+/// Returns the complete frames and the number of bytes they take; a trailing partial
+/// frame is left for later.
+fn split_ivf_frames(data: &[u8]) -> (Vec<Vec<u8>>, usize) {
+    let mut frames = Vec::new();
+    let mut pos = 0;
+    while data.len() - pos >= IVF_FRAME_HEADER {
+        let size =
+            u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize;
+        let start = pos + IVF_FRAME_HEADER;
+        if data.len() - start < size {
+            break;
+        }
+        frames.push(data[start..start + size].to_vec());
+        pos = start + size;
+    }
+    (frames, pos)
+}
+
+/// Check that ffmpeg lists `encoder` among its encoders
+pub(crate) fn check_encoder(ffmpeg: &str, encoder: &str) -> Result<()> {
+    let output = Command::new(ffmpeg)
+        .args(["-encoders"])
+        .output()
+        .map_err(|e| Error::ffmpeg(format!("Failed to run ffmpeg: {}", e)))?;
+
+    let encoders = String::from_utf8_lossy(&output.stdout);
+    if encoders.contains(encoder) {
+        Ok(())
+    } else {
+        Err(Error::CodecUnavailable(format!(
+            "FFmpeg does not have {} support",
+            encoder
+        )))
+    }
+}
+
+/// ffmpeg arguments encoding at the target bitrate of `config`, or else at `crf`
+///
+/// A constant bitrate is also the minimum and maximum rate, over a one second buffer.
+pub(crate) fn rate_args(config: &EncoderConfig, crf: u32) -> Vec<String> {
+    match config.bitrate() {
+        Some((kbps, constant)) => {
+            let rate = format!("{}k", kbps);
+            let mut args = vec!["-b:v".to_string(), rate.clone()];
+            if constant {
+                for option in ["-minrate", "-maxrate", "-bufsize"] {
+                    args.extend([option.to_string(), rate.clone()]);
+                }
+            }
+            args
+        }
+        None => vec!["-crf".to_string(), crf.to_string()],
+    }
+}
+
+/// ffmpeg arguments placing keyframes every `default` frames, or exactly at the interval
+/// of `config`, without extra ones at scene changes
+pub(crate) fn keyframe_args(config: &EncoderConfig, default: u32) -> Vec<String> {
+    match config.keyframe_interval {
+        Some(interval) => [
+            "-g",
+            &interval.to_string(),
+            "-keyint_min",
+            &interval.to_string(),
+            "-sc_threshold",
+            "0",
+        ]
+        .map(str::to_string)
+        .to_vec(),
+        None => vec!["-g".to_string(), default.to_string()],
+    }
+}
+
+/// ffmpeg arguments converting RGBA frames in the color space and range of `config` and
+/// tagging the stream with them
+///
+/// swscale converts RGB in BT.601 to limited range unless told otherwise.
+pub(crate) fn color_args(config: &EncoderConfig) -> Vec<String> {
+    let full_range = config.pixel_range.is_full(false);
+    let (mut scale, tag) = match config.color_space {
+        ColorSpace::Bt601 => (Vec::new(), "smpte170m"),
+        ColorSpace::Bt709 => (vec!["out_color_matrix=bt709"], "bt709"),
+    };
+    if full_range {
+        scale.push("out_range=pc");
+    }
+
+    let mut args = Vec::new();
+    if !scale.is_empty() {
+        args.extend(["-vf".to_string(), format!("scale={}", scale.join(":"))]);
+    }
+    for option in ["-colorspace", "-color_primaries", "-color_trc"] {
+        args.extend([option.to_string(), tag.to_string()]);
+    }
+    let range = if full_range { "pc" } else { "tv" };
+    args.extend(["-color_range".to_string(), range.to_string()]);
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Av1Backend, PixelRange, Priority, RateControl};
+
+    #[test]
+    fn test_encoder_args() {
+        let mut config = EncoderConfig {
+            width: 64,
+            height: 48,
+            fps: 30,
+            quality: 50,
+            bitrate_kbps: None,
+            rate_control: RateControl::Auto,
+            keyframe_interval: None,
+            color_space: ColorSpace::Bt601,
+            pixel_range: PixelRange::Auto,
+            deterministic: false,
+            priority: Priority::Normal,
+            threads: 0,
+            av1_backend: Av1Backend::Rav1e,
+        };
+        assert_eq!(rate_args(&config, 31), ["-crf", "31"]);
+        config.bitrate_kbps = Some(1500);
+        assert_eq!(rate_args(&config, 31), ["-b:v", "1500k"]);
+        config.rate_control = RateControl::Constant;
+        assert_eq!(
+            rate_args(&config, 31),
+            ["-b:v", "1500k", "-minrate", "1500k", "-maxrate", "1500k", "-bufsize", "1500k"]
+        );
+        config.rate_control = RateControl::Quality;
+        assert_eq!(rate_args(&config, 31), ["-crf", "31"]);
+
+        assert_eq!(keyframe_args(&config, 240), ["-g", "240"]);
+        config.keyframe_interval = Some(60);
+        assert_eq!(
+            keyframe_args(&config, 240),
+            ["-g", "60", "-keyint_min", "60", "-sc_threshold", "0"]
+        );
+
+        assert_eq!(
+            color_args(&config),
+            [
+                "-colorspace",
+                "smpte170m",
+                "-color_primaries",
+                "smpte170m",
+                "-color_trc",
+                "smpte170m",
+                "-color_range",
+                "tv"
+            ]
+        );
+        config.color_space = ColorSpace::Bt709;
+        assert_eq!(
+            color_args(&config)[..2],
+            ["-vf", "scale=out_color_matrix=bt709"]
+        );
+        assert_eq!(color_args(&config)[2..4], ["-colorspace", "bt709"]);
+        config.pixel_range = PixelRange::Full;
+        let args = color_args(&config);
+        assert_eq!(args[1], "scale=out_color_matrix=bt709:out_range=pc");
+        assert_eq!(args[args.len() - 2..], ["-color_range", "pc"]);
+    }
+
+    #[test]
+    fn test_split_ivf_frames() {
+        let mut stream = Vec::new();
+        for (i, len) in [3usize, 0, 5].into_iter().enumerate() {
+            stream.extend_from_slice(&(len as u32).to_le_bytes());
+            stream.extend_from_slice(&(i as u64).to_le_bytes());
+            stream.extend(std::iter::repeat(i as u8 + 1).take(len));
+        }
+
+        let (frames, consumed) = split_ivf_frames(&stream);
+        assert_eq!(frames, vec![vec![1; 3], vec![], vec![3; 5]]);
+        assert_eq!(consumed, stream.len());
+
+        // A partial frame is left for later
+        let (frames, consumed) = split_ivf_frames(&stream[..stream.len() - 1]);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(consumed, 2 * IVF_FRAME_HEADER + 3);
+    }
+
+    #[test]
+    fn test_ivf_frames_skip_file_header() {
+        let mut ivf = IvfFrames::default();
+        ivf.buffer().extend_from_slice(&[0; IVF_HEADER - 1]);
+        assert!(ivf.take().is_empty());
+
+        ivf.buffer().push(0);
+        ivf.buffer().extend_from_slice(&2u32.to_le_bytes());
+        ivf.buffer().extend_from_slice(&0u64.to_le_bytes());
+        ivf.buffer().extend_from_slice(&[7, 8]);
+        assert_eq!(ivf.take(), vec![vec![7, 8]]);
+        assert!(ivf.buffer().is_empty());
+    }
+}
@@ -1,18 +1,26 @@
 //! Linux H.264 encoder using ffmpeg external process
 
-use super::super::{Encoder, EncoderConfig, Frame, Packet};
+use super::super::ffmpeg_pipe::{check_encoder, color_args, keyframe_args, rate_args, FfmpegPipe};
+use super::super::{Encoder, EncoderBackend, EncoderConfig, Frame, Packet};
+use crate::logging::log_debug;
+use crate::priority::lower_command;
 use crate::{Error, Result};
-use std::io::Write;
-use std::process::{Child, Command, Stdio};
+use std::mem;
+use std::process::{Command, Stdio};
 
 /// FFmpeg-based H.264 encoder for Linux
+///
+/// Frames stream through an ffmpeg process (see [`FfmpegPipe`]) and its output is split
+/// into NAL units as it arrives.
 pub struct FfmpegEncoder {
-    process: Child,
-    #[allow(dead_code)]
+    pipe: FfmpegPipe,
     config: EncoderConfig,
-    frame_count: u64,
-    #[allow(dead_code)]
+    /// Pictures whose first slice has been split off, numbering the next one
+    pictures: u64,
+    /// Output not yet split into packets, starting at a start code
     output_buffer: Vec<u8>,
+    /// Encoder ffmpeg runs, reported in statistics
+    backend: EncoderBackend,
 }
 
 impl FfmpegEncoder {
@@ -22,146 +30,112 @@ impl FfmpegEncoder {
         // Map quality (0-100) to CRF (51-0)
         let crf = ((100 - config.quality.min(100)) as u32 * 51) / 100;
 
-        let process = Command::new(&ffmpeg)
-            .args([
-                "-f",
-                "rawvideo",
-                "-pix_fmt",
-                "rgba",
-                "-s",
-                &format!("{}x{}", config.width, config.height),
-                "-r",
-                &config.fps.to_string(),
-                "-i",
-                "pipe:0",
-                "-c:v",
-                "libx264",
-                "-preset",
-                "medium",
-                "-crf",
-                &crf.to_string(),
-                "-pix_fmt",
-                "yuv420p",
-                "-f",
-                "h264",
-                "pipe:1",
-            ])
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .spawn()
-            .map_err(|e| Error::Ffmpeg(format!("Failed to start ffmpeg: {}", e)))?;
+        log_debug!(
+            "Starting {} for {}x{} H.264 encoding ({})",
+            ffmpeg,
+            config.width,
+            config.height,
+            rate_args(&config, crf).join(" ")
+        );
+
+        let mut command = input_command(&ffmpeg, &config);
+        command.args([
+            "-c:v", "libx264", "-preset", "medium", "-pix_fmt", "yuv420p",
+        ]);
+        command.args(rate_args(&config, crf));
+        command.args(color_args(&config));
+        if let Some(interval) = config.keyframe_interval {
+            command.args(keyframe_args(&config, interval));
+        }
+        if config.deterministic {
+            // x264 output depends on its thread count, and its SEI carries the build options
+            command.args(["-threads", "1", "-bsf:v", "filter_units=remove_types=6"]);
+        } else if config.threads > 0 {
+            command.args(["-threads", &config.threads.to_string()]);
+        }
+        Self::spawn(command, &ffmpeg, config, EncoderBackend::Ffmpeg)
+    }
+
+    /// Start `command`, running `ffmpeg` with its input set by [`input_command`] and an
+    /// H.264 encoder, writing the stream to stdout
+    pub(super) fn spawn(
+        mut command: Command,
+        ffmpeg: &str,
+        config: EncoderConfig,
+        backend: EncoderBackend,
+    ) -> Result<Self> {
+        lower_command(&mut command, config.priority);
+        // No B-frames, so pictures come out in presentation order and their number is
+        // their timestamp, as with VideoToolbox
+        command.args(["-bf", "0", "-f", "h264", "pipe:1"]);
 
         Ok(Self {
-            process,
+            pipe: FfmpegPipe::spawn(command, ffmpeg)?,
             config,
-            frame_count: 0,
+            pictures: 0,
             output_buffer: Vec::new(),
+            backend,
         })
     }
+}
 
-    fn read_available_output(&mut self) -> Result<Vec<u8>> {
-        use std::io::Read;
-
-        let stdout = self
-            .process
-            .stdout
-            .as_mut()
-            .ok_or_else(|| Error::Ffmpeg("FFmpeg stdout not available".to_string()))?;
-
-        let mut buffer = vec![0u8; 65536];
-        let mut result = Vec::new();
-
-        // Non-blocking read - this is a simplified approach
-        // In production, you might want to use async I/O
-        loop {
-            match stdout.read(&mut buffer) {
-                Ok(0) => break,
-                Ok(n) => result.extend_from_slice(&buffer[..n]),
-                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
-                Err(_) => break,
-            }
-        }
-
-        Ok(result)
-    }
+/// ffmpeg command reading RGBA frames of `config` from stdin
+pub(super) fn input_command(ffmpeg: &str, config: &EncoderConfig) -> Command {
+    let mut command = Command::new(ffmpeg);
+    command.args([
+        "-f",
+        "rawvideo",
+        "-pix_fmt",
+        "rgba",
+        "-s",
+        &format!("{}x{}", config.width, config.height),
+        "-r",
+        &config.fps.to_string(),
+        "-i",
+        "pipe:0",
+    ]);
+    command
 }
 
 impl Encoder for FfmpegEncoder {
     fn encode(&mut self, frame: &Frame) -> Result<Vec<Packet>> {
-        let stdin = self
-            .process
-            .stdin
-            .as_mut()
-            .ok_or_else(|| Error::Ffmpeg("FFmpeg stdin not available".to_string()))?;
+        frame.check(&self.config)?;
 
-        // Write raw RGBA frame data
-        stdin
-            .write_all(&frame.data)
-            .map_err(|e| Error::Ffmpeg(format!("Failed to write frame: {}", e)))?;
+        self.pipe.send(frame.data, &mut self.output_buffer)?;
 
-        self.frame_count += 1;
-
-        // Try to read any available output
-        let output = self.read_available_output()?;
-
-        if output.is_empty() {
-            return Ok(Vec::new());
-        }
-
-        // Parse H.264 NAL units from output
-        let packets = parse_h264_packets(&output, self.frame_count - 1);
-        Ok(packets)
+        // The last NAL unit may still be incomplete
+        let output = take_complete_nal_units(&mut self.output_buffer);
+        Ok(parse_h264_packets(&output, &mut self.pictures))
     }
 
     fn flush(&mut self) -> Result<Vec<Packet>> {
-        // Close stdin to signal end of input
-        drop(self.process.stdin.take());
-
-        // Wait for process to finish and read remaining output
-        use std::io::Read;
-
-        let mut output = Vec::new();
-        if let Some(ref mut stdout) = self.process.stdout {
-            stdout
-                .read_to_end(&mut output)
-                .map_err(|e| Error::Ffmpeg(format!("Failed to read output: {}", e)))?;
-        }
-
-        // Wait for process to exit
-        self.process
-            .wait()
-            .map_err(|e| Error::Ffmpeg(format!("FFmpeg process error: {}", e)))?;
+        self.pipe.finish(&mut self.output_buffer)?;
 
         // Parse remaining packets
-        let packets = parse_h264_packets(&output, self.frame_count);
+        let output = mem::take(&mut self.output_buffer);
+        let packets = parse_h264_packets(&output, &mut self.pictures);
         Ok(packets)
     }
-}
 
-impl Drop for FfmpegEncoder {
-    fn drop(&mut self) {
-        // Kill the process if it's still running
-        let _ = self.process.kill();
-        let _ = self.process.wait();
+    fn backend(&self) -> EncoderBackend {
+        self.backend
     }
 }
 
 /// Parse H.264 NAL units from raw H.264 stream
-fn parse_h264_packets(data: &[u8], base_pts: u64) -> Vec<Packet> {
+///
+/// Each NAL unit is a packet with the number of its picture as timestamp, counted in
+/// `pictures` across calls. Units before a picture's first slice belong to that picture.
+fn parse_h264_packets(data: &[u8], pictures: &mut u64) -> Vec<Packet> {
     let mut packets = Vec::new();
     let mut start = 0;
-    let mut pts = base_pts as i64;
 
     // Simple NAL unit parsing (looking for start codes)
     while start < data.len() {
         // Find start code (0x00 0x00 0x01 or 0x00 0x00 0x00 0x01)
-        let nal_start = find_start_code(data, start);
-        if nal_start.is_none() {
+        let Some((nal_start, start_code_len)) = find_start_code(data, start) else {
             break;
-        }
-
-        let (nal_start, start_code_len) = nal_start.unwrap();
+        };
 
         // Find next start code or end of data
         let nal_end = find_start_code(data, nal_start + start_code_len)
@@ -174,14 +148,22 @@ fn parse_h264_packets(data: &[u8], base_pts: u64) -> Vec<Packet> {
             let nal_type = nal_data[0] & 0x1F;
             let is_keyframe = nal_type == 5; // IDR slice
 
+            // A slice with first_mb_in_slice 0, coded as a single 1 bit, starts a picture
+            let pts = match nal_data.get(1) {
+                Some(&header) if (1..=5).contains(&nal_type) && header & 0x80 != 0 => {
+                    *pictures += 1;
+                    *pictures - 1
+                }
+                _ if (1..=5).contains(&nal_type) => pictures.saturating_sub(1),
+                _ => *pictures,
+            } as i64;
+
             packets.push(Packet {
                 data: nal_data,
                 pts,
                 dts: pts,
                 is_keyframe,
             });
-
-            pts += 1;
         }
 
         start = nal_end;
@@ -190,6 +172,25 @@ fn parse_h264_packets(data: &[u8], base_pts: u64) -> Vec<Packet> {
     packets
 }
 
+/// Split off the NAL units of `buffer` followed by another start code
+///
+/// The rest of `buffer`, which may still grow, is kept.
+fn take_complete_nal_units(buffer: &mut Vec<u8>) -> Vec<u8> {
+    let mut last = None;
+    let mut pos = 0;
+    while let Some((start, len)) = find_start_code(buffer, pos) {
+        last = Some(start);
+        pos = start + len;
+    }
+    match last {
+        Some(start) if start > 0 => {
+            let rest = buffer.split_off(start);
+            mem::replace(buffer, rest)
+        }
+        _ => Vec::new(),
+    }
+}
+
 /// Find H.264 start code in data
 fn find_start_code(data: &[u8], start: usize) -> Option<(usize, usize)> {
     if start + 3 > data.len() {
@@ -211,12 +212,12 @@ fn find_start_code(data: &[u8], start: usize) -> Option<(usize, usize)> {
 }
 
 /// Find ffmpeg executable
-fn find_ffmpeg(custom_path: Option<&str>) -> Result<String> {
+pub(super) fn find_ffmpeg(custom_path: Option<&str>) -> Result<String> {
     if let Some(path) = custom_path {
         if std::path::Path::new(path).exists() {
             return Ok(path.to_string());
         }
-        return Err(Error::Ffmpeg(format!("FFmpeg not found at: {}", path)));
+        return Err(Error::ffmpeg(format!("FFmpeg not found at: {}", path)));
     }
 
     // Try to find ffmpeg in PATH
@@ -242,19 +243,104 @@ fn find_ffmpeg(custom_path: Option<&str>) -> Result<String> {
 /// Check if ffmpeg with H.264 support is available
 pub fn check_available(ffmpeg_path: Option<&str>) -> Result<()> {
     let ffmpeg = find_ffmpeg(ffmpeg_path)?;
+    check_encoder(&ffmpeg, "libx264")
+}
 
-    // Check if ffmpeg has libx264 support
-    let output = Command::new(&ffmpeg)
-        .args(["-encoders"])
-        .output()
-        .map_err(|e| Error::Ffmpeg(format!("Failed to run ffmpeg: {}", e)))?;
-
-    let encoders = String::from_utf8_lossy(&output.stdout);
-    if encoders.contains("libx264") {
-        Ok(())
-    } else {
-        Err(Error::CodecUnavailable(
-            "FFmpeg does not have libx264 support".to_string(),
-        ))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Av1Backend, ColorSpace, PixelRange, Priority, RateControl};
+
+    #[test]
+    fn test_take_complete_nal_units_across_chunks() {
+        let stream = [
+            0, 0, 0, 1, 0x67, 1, 2, 0, 0, 1, 0x68, 3, 0, 0, 0, 1, 0x65, 4, 5, 6, 0, 0, 1, 0x41, 7,
+        ];
+        let whole: Vec<Vec<u8>> = parse_h264_packets(&stream, &mut 0)
+            .into_iter()
+            .map(|p| p.data)
+            .collect();
+
+        for chunk_size in 1..stream.len() {
+            let mut buffer = Vec::new();
+            let mut nal_units = Vec::new();
+            for chunk in stream.chunks(chunk_size) {
+                buffer.extend_from_slice(chunk);
+                let complete = take_complete_nal_units(&mut buffer);
+                nal_units.extend(
+                    parse_h264_packets(&complete, &mut 0)
+                        .into_iter()
+                        .map(|p| p.data),
+                );
+            }
+            nal_units.extend(
+                parse_h264_packets(&buffer, &mut 0)
+                    .into_iter()
+                    .map(|p| p.data),
+            );
+            assert_eq!(nal_units, whole, "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn test_packets_numbered_by_picture() {
+        // SPS, PPS, SEI and IDR slice, then a picture of two slices across two calls
+        let mut pictures = 0;
+        let first = [
+            0, 0, 0, 1, 0x67, 1, 0, 0, 1, 0x68, 2, 0, 0, 1, 0x06, 3, 0, 0, 1, 0x65, 0x88, 0, 0, 1,
+            0x41, 0x9a,
+        ];
+        let second = [0, 0, 1, 0x41, 0x20, 0, 0, 1, 0x41, 0x9b];
+        let pts: Vec<i64> = parse_h264_packets(&first, &mut pictures)
+            .into_iter()
+            .chain(parse_h264_packets(&second, &mut pictures))
+            .map(|p| p.pts)
+            .collect();
+        assert_eq!(pts, [0, 0, 0, 0, 1, 1, 2]);
+        assert_eq!(pictures, 3);
+    }
+
+    #[test]
+    fn test_large_frames_do_not_block_on_full_pipes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Stands in for ffmpeg, writing back as much as it reads
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("ffmpeg");
+        std::fs::write(&script, "#!/bin/sh\nexec cat\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config = EncoderConfig {
+            width: 1280,
+            height: 720,
+            fps: 30,
+            quality: 50,
+            bitrate_kbps: None,
+            rate_control: RateControl::Auto,
+            keyframe_interval: None,
+            color_space: ColorSpace::Bt601,
+            pixel_range: PixelRange::Auto,
+            deterministic: false,
+            priority: Priority::Normal,
+            threads: 0,
+            av1_backend: Av1Backend::Rav1e,
+        };
+        let mut encoder = FfmpegEncoder::new(config, Some(script.to_str().unwrap())).unwrap();
+
+        // One H.264 NAL unit per frame, far larger than a pipe buffer
+        let mut data = vec![0xFFu8; 1280 * 720 * 4];
+        data[..5].copy_from_slice(&[0, 0, 0, 1, 0x65]);
+        let mut nal_units = 0;
+        for i in 0..10 {
+            let frame = Frame {
+                width: 1280,
+                height: 720,
+                data: &data,
+                pts_ms: i * 33,
+            };
+            nal_units += encoder.encode(&frame).unwrap().len();
+        }
+        nal_units += encoder.flush().unwrap().len();
+        assert_eq!(nal_units, 10);
     }
 }
@@ -1,9 +1,14 @@
-//! macOS H.264 encoder using VideoToolbox
+//! macOS and iOS H.264 encoder using VideoToolbox
+//!
+//! VideoToolbox, Core Media, Core Video and Core Foundation are the same frameworks on both.
 
-use super::super::{Encoder, EncoderConfig, Frame, Packet};
+use super::super::color::{rgba_to_nv12, Matrix, PlaneMut};
+use super::super::{ColorSpace, Encoder, EncoderBackend, EncoderConfig, Frame, Packet};
 use crate::{Error, Result};
+use rayon::prelude::*;
 use std::ffi::c_void;
 use std::ptr;
+use std::slice;
 use std::sync::{Arc, Mutex};
 
 // VideoToolbox FFI bindings
@@ -91,6 +96,8 @@ extern "C" {
     fn CVPixelBufferUnlockBaseAddress(pixel_buffer: *mut c_void, unlock_flags: u64) -> i32;
     fn CVPixelBufferGetBaseAddress(pixel_buffer: *mut c_void) -> *mut u8;
     fn CVPixelBufferGetBytesPerRow(pixel_buffer: *mut c_void) -> usize;
+    fn CVPixelBufferGetBaseAddressOfPlane(pixel_buffer: *mut c_void, plane: usize) -> *mut u8;
+    fn CVPixelBufferGetBytesPerRowOfPlane(pixel_buffer: *mut c_void, plane: usize) -> usize;
     fn CVPixelBufferRelease(pixel_buffer: *mut c_void);
 }
 
@@ -108,12 +115,24 @@ extern "C" {
     static kVTCompressionPropertyKey_AllowFrameReordering: *const c_void;
     static kVTCompressionPropertyKey_MaxKeyFrameInterval: *const c_void;
     static kVTCompressionPropertyKey_AverageBitRate: *const c_void;
+    static kVTCompressionPropertyKey_DataRateLimits: *const c_void;
+    static kVTCompressionPropertyKey_ColorPrimaries: *const c_void;
+    static kVTCompressionPropertyKey_TransferFunction: *const c_void;
+    static kVTCompressionPropertyKey_YCbCrMatrix: *const c_void;
+
+    static kCVImageBufferColorPrimaries_ITU_R_709_2: *const c_void;
+    static kCVImageBufferColorPrimaries_SMPTE_C: *const c_void;
+    static kCVImageBufferTransferFunction_ITU_R_709_2: *const c_void;
+    static kCVImageBufferYCbCrMatrix_ITU_R_709_2: *const c_void;
+    static kCVImageBufferYCbCrMatrix_ITU_R_601_4: *const c_void;
 
     #[allow(dead_code)]
     static kVTProfileLevel_H264_Baseline_AutoLevel: *const c_void;
     static kVTProfileLevel_H264_Main_AutoLevel: *const c_void;
 
     static kCMSampleAttachmentKey_NotSync: *const c_void;
+
+    static kVTEncodeFrameOptionKey_ForceKeyFrame: *const c_void;
 }
 
 #[repr(C)]
@@ -127,7 +146,9 @@ struct CMTime {
 
 const K_CM_TIME_FLAGS_VALID: u32 = 1;
 const K_CV_PIXEL_FORMAT_TYPE_32_BGRA: u32 = 0x42475241; // 'BGRA'
+const K_CV_PIXEL_FORMAT_TYPE_420_FULL_RANGE: u32 = 0x34323066; // '420f'
 const K_CMV_VIDEO_CODEC_TYPE_H264: u32 = 0x61766331; // 'avc1'
+const K_VT_VIDEO_ENCODER_NOT_AVAILABLE_NOW_ERR: i32 = -12915;
 
 /// Encoded packet data passed through callback
 struct CallbackData {
@@ -143,6 +164,8 @@ pub struct VideoToolboxEncoder {
     config: EncoderConfig,
     callback_data: Arc<Mutex<CallbackData>>,
     frame_count: u64,
+    /// Start the next frame with a keyframe, set when a new stream begins on the session
+    force_keyframe: bool,
 }
 
 unsafe impl Send for VideoToolboxEncoder {}
@@ -181,6 +204,11 @@ impl VideoToolboxEncoder {
             unsafe {
                 let _ = Arc::from_raw(callback_data_ptr as *const Mutex<CallbackData>);
             }
+            if status == K_VT_VIDEO_ENCODER_NOT_AVAILABLE_NOW_ERR {
+                return Err(Error::Busy(
+                    "VideoToolbox encoder is not available now".to_string(),
+                ));
+            }
             return Err(Error::Encode(format!(
                 "Failed to create VideoToolbox session: {}",
                 status
@@ -203,8 +231,8 @@ impl VideoToolboxEncoder {
                 kCFBooleanFalse,
             );
 
-            // Set keyframe interval
-            let keyframe_interval = config.fps; // Keyframe every second
+            // Set keyframe interval, every second by default
+            let keyframe_interval = config.keyframe_interval.unwrap_or(config.fps);
             let cf_number = create_cf_number(keyframe_interval as i64);
             if !cf_number.is_null() {
                 VTSessionSetProperty(
@@ -215,7 +243,7 @@ impl VideoToolboxEncoder {
                 CFRelease(cf_number);
             }
 
-            // Set bitrate based on quality
+            // Set the target bitrate, or one based on quality
             let bitrate = calculate_bitrate(&config);
             let cf_bitrate = create_cf_number(bitrate as i64);
             if !cf_bitrate.is_null() {
@@ -227,6 +255,35 @@ impl VideoToolboxEncoder {
                 CFRelease(cf_bitrate);
             }
 
+            // Hold a constant bitrate to it over every second
+            if let Some((kbps, true)) = config.bitrate() {
+                let limits = create_data_rate_limits(kbps as i64 * 125, 1);
+                if !limits.is_null() {
+                    VTSessionSetProperty(session, kVTCompressionPropertyKey_DataRateLimits, limits);
+                    CFRelease(limits);
+                }
+            }
+
+            // Convert frames in the color space and signal it in the SPS
+            // (BT.601 shares the transfer function of BT.709)
+            let (primaries, matrix) = match config.color_space {
+                ColorSpace::Bt601 => (
+                    kCVImageBufferColorPrimaries_SMPTE_C,
+                    kCVImageBufferYCbCrMatrix_ITU_R_601_4,
+                ),
+                ColorSpace::Bt709 => (
+                    kCVImageBufferColorPrimaries_ITU_R_709_2,
+                    kCVImageBufferYCbCrMatrix_ITU_R_709_2,
+                ),
+            };
+            VTSessionSetProperty(session, kVTCompressionPropertyKey_ColorPrimaries, primaries);
+            VTSessionSetProperty(
+                session,
+                kVTCompressionPropertyKey_TransferFunction,
+                kCVImageBufferTransferFunction_ITU_R_709_2,
+            );
+            VTSessionSetProperty(session, kVTCompressionPropertyKey_YCbCrMatrix, matrix);
+
             // Enable real-time encoding
             VTSessionSetProperty(session, kVTCompressionPropertyKey_RealTime, kCFBooleanTrue);
         }
@@ -236,18 +293,28 @@ impl VideoToolboxEncoder {
             config,
             callback_data,
             frame_count: 0,
+            force_keyframe: false,
         })
     }
 
     fn create_pixel_buffer(&self, frame: &Frame) -> Result<*mut c_void> {
         let mut pixel_buffer: *mut c_void = ptr::null_mut();
 
+        // VideoToolbox converts BGRA to limited range, so full range frames are converted
+        // here to NV12
+        let full_range = self.config.pixel_range.is_full(false);
+        let pixel_format = if full_range {
+            K_CV_PIXEL_FORMAT_TYPE_420_FULL_RANGE
+        } else {
+            K_CV_PIXEL_FORMAT_TYPE_32_BGRA
+        };
+
         let status = unsafe {
             CVPixelBufferCreate(
                 ptr::null(),
                 frame.width as usize,
                 frame.height as usize,
-                K_CV_PIXEL_FORMAT_TYPE_32_BGRA,
+                pixel_format,
                 ptr::null(),
                 &mut pixel_buffer,
             )
@@ -263,21 +330,57 @@ impl VideoToolboxEncoder {
         // Lock and copy data
         unsafe {
             CVPixelBufferLockBaseAddress(pixel_buffer, 0);
+            let width = frame.width as usize;
+            let height = frame.height as usize;
+
+            if full_range {
+                let y_stride = CVPixelBufferGetBytesPerRowOfPlane(pixel_buffer, 0);
+                let uv_stride = CVPixelBufferGetBytesPerRowOfPlane(pixel_buffer, 1);
+                let y_plane = slice::from_raw_parts_mut(
+                    CVPixelBufferGetBaseAddressOfPlane(pixel_buffer, 0),
+                    y_stride * height,
+                );
+                let uv_plane = slice::from_raw_parts_mut(
+                    CVPixelBufferGetBaseAddressOfPlane(pixel_buffer, 1),
+                    uv_stride * height.div_ceil(2),
+                );
+                rgba_to_nv12(
+                    frame.data,
+                    width,
+                    height,
+                    Matrix::new(self.config.color_space, true),
+                    PlaneMut {
+                        data: y_plane,
+                        stride: y_stride,
+                    },
+                    PlaneMut {
+                        data: uv_plane,
+                        stride: uv_stride,
+                    },
+                );
+                CVPixelBufferUnlockBaseAddress(pixel_buffer, 0);
+                return Ok(pixel_buffer);
+            }
+
             let base_address = CVPixelBufferGetBaseAddress(pixel_buffer);
             let bytes_per_row = CVPixelBufferGetBytesPerRow(pixel_buffer);
-
-            // Convert RGBA to BGRA and copy
-            for y in 0..frame.height as usize {
-                for x in 0..frame.width as usize {
-                    let src_idx = (y * frame.width as usize + x) * 4;
-                    let dst_idx = y * bytes_per_row + x * 4;
-
-                    *base_address.add(dst_idx) = frame.data[src_idx + 2]; // B
-                    *base_address.add(dst_idx + 1) = frame.data[src_idx + 1]; // G
-                    *base_address.add(dst_idx + 2) = frame.data[src_idx]; // R
-                    *base_address.add(dst_idx + 3) = frame.data[src_idx + 3]; // A
-                }
-            }
+            let pixels = slice::from_raw_parts_mut(base_address, bytes_per_row * height);
+
+            // Convert RGBA to BGRA and copy, one row per task
+            pixels
+                .par_chunks_mut(bytes_per_row)
+                .zip(frame.data.par_chunks(width * 4))
+                .for_each(|(dst, src)| {
+                    for (dst, src) in dst[..width * 4]
+                        .chunks_exact_mut(4)
+                        .zip(src.chunks_exact(4))
+                    {
+                        dst[0] = src[2]; // B
+                        dst[1] = src[1]; // G
+                        dst[2] = src[0]; // R
+                        dst[3] = src[3]; // A
+                    }
+                });
 
             CVPixelBufferUnlockBaseAddress(pixel_buffer, 0);
         }
@@ -296,6 +399,15 @@ impl VideoToolboxEncoder {
         let data = self.callback_data.lock().ok()?;
         data.pps.clone()
     }
+
+    /// Take the packets delivered by the output callback so far
+    fn take_packets(&self) -> Result<Vec<Packet>> {
+        let mut data = self
+            .callback_data
+            .lock()
+            .map_err(|_| Error::Encode("VideoToolbox callback state poisoned".to_string()))?;
+        Ok(std::mem::take(&mut data.packets))
+    }
 }
 
 extern "C" fn compression_output_callback(
@@ -476,6 +588,53 @@ extern "C" {
     ) -> *mut c_void;
     fn CFRelease(cf: *mut c_void);
     fn CFArrayGetValueAtIndex(array: *const c_void, index: isize) -> *const c_void;
+    fn CFDictionaryCreate(
+        allocator: *const c_void,
+        keys: *const *const c_void,
+        values: *const *const c_void,
+        num_values: isize,
+        key_call_backs: *const CFDictionaryCallBacks,
+        value_call_backs: *const CFDictionaryCallBacks,
+    ) -> *mut c_void;
+
+    fn CFArrayCreate(
+        allocator: *const c_void,
+        values: *const *const c_void,
+        num_values: isize,
+        call_backs: *const CFArrayCallBacks,
+    ) -> *mut c_void;
+
+    static kCFTypeDictionaryKeyCallBacks: CFDictionaryCallBacks;
+    static kCFTypeDictionaryValueCallBacks: CFDictionaryCallBacks;
+    static kCFTypeArrayCallBacks: CFArrayCallBacks;
+}
+
+/// Opaque CoreFoundation dictionary callbacks, only passed by address
+#[repr(C)]
+struct CFDictionaryCallBacks {
+    _private: [u8; 0],
+}
+
+/// Opaque CoreFoundation array callbacks, only passed by address
+#[repr(C)]
+struct CFArrayCallBacks {
+    _private: [u8; 0],
+}
+
+/// Frame properties requesting a keyframe (released by the caller)
+fn create_force_keyframe_properties() -> *mut c_void {
+    unsafe {
+        let keys = [kVTEncodeFrameOptionKey_ForceKeyFrame];
+        let values = [kCFBooleanTrue];
+        CFDictionaryCreate(
+            ptr::null(),
+            keys.as_ptr(),
+            values.as_ptr(),
+            1,
+            &kCFTypeDictionaryKeyCallBacks,
+            &kCFTypeDictionaryValueCallBacks,
+        )
+    }
 }
 
 const K_CF_NUMBER_INT64_TYPE: i32 = 4;
@@ -490,7 +649,30 @@ fn create_cf_number(value: i64) -> *mut c_void {
     }
 }
 
+/// Data rate limit of `bytes` per `seconds` (released by the caller)
+fn create_data_rate_limits(bytes: i64, seconds: i64) -> *mut c_void {
+    let bytes = create_cf_number(bytes);
+    let seconds = create_cf_number(seconds);
+    unsafe {
+        let limits = if bytes.is_null() || seconds.is_null() {
+            ptr::null_mut()
+        } else {
+            let values = [bytes as *const c_void, seconds as *const c_void];
+            CFArrayCreate(ptr::null(), values.as_ptr(), 2, &kCFTypeArrayCallBacks)
+        };
+        for number in [bytes, seconds] {
+            if !number.is_null() {
+                CFRelease(number);
+            }
+        }
+        limits
+    }
+}
+
 fn calculate_bitrate(config: &EncoderConfig) -> u32 {
+    if let Some((kbps, _)) = config.bitrate() {
+        return kbps.saturating_mul(1000);
+    }
     // Base bitrate calculation based on resolution and quality
     let pixels = config.width * config.height;
     let base_bitrate = match pixels {
@@ -508,18 +690,25 @@ fn calculate_bitrate(config: &EncoderConfig) -> u32 {
 
 impl Encoder for VideoToolboxEncoder {
     fn encode(&mut self, frame: &Frame) -> Result<Vec<Packet>> {
+        frame.check(&self.config)?;
         let pixel_buffer = self.create_pixel_buffer(frame)?;
 
         let pts = unsafe { CMTimeMake(self.frame_count as i64, self.config.fps as i32) };
         let duration = unsafe { CMTimeMake(1, self.config.fps as i32) };
 
+        let frame_properties = if self.force_keyframe {
+            create_force_keyframe_properties()
+        } else {
+            ptr::null_mut()
+        };
+
         let status = unsafe {
             VTCompressionSessionEncodeFrame(
                 self.session,
                 pixel_buffer,
                 pts,
                 duration,
-                ptr::null(),
+                frame_properties,
                 ptr::null_mut(),
                 ptr::null_mut(),
             )
@@ -527,18 +716,25 @@ impl Encoder for VideoToolboxEncoder {
 
         unsafe {
             CVPixelBufferRelease(pixel_buffer);
+            if !frame_properties.is_null() {
+                CFRelease(frame_properties);
+            }
         }
 
+        if status == K_VT_VIDEO_ENCODER_NOT_AVAILABLE_NOW_ERR {
+            return Err(Error::Busy(
+                "VideoToolbox encoder is not available now".to_string(),
+            ));
+        }
         if status != 0 {
             return Err(Error::Encode(format!("Failed to encode frame: {}", status)));
         }
 
         self.frame_count += 1;
+        self.force_keyframe = false;
 
         // Get encoded packets
-        let mut data = self.callback_data.lock().unwrap();
-        let result = std::mem::take(&mut data.packets);
-        Ok(result)
+        self.take_packets()
     }
 
     fn flush(&mut self) -> Result<Vec<Packet>> {
@@ -553,8 +749,7 @@ impl Encoder for VideoToolboxEncoder {
             VTCompressionSessionCompleteFrames(self.session, complete_time);
         }
 
-        let mut data = self.callback_data.lock().unwrap();
-        Ok(std::mem::take(&mut data.packets))
+        self.take_packets()
     }
 
     fn codec_config(&self) -> Option<Vec<u8>> {
@@ -564,6 +759,26 @@ impl Encoder for VideoToolboxEncoder {
     fn pps(&self) -> Option<Vec<u8>> {
         self.get_pps()
     }
+
+    fn backend(&self) -> EncoderBackend {
+        EncoderBackend::VideoToolbox
+    }
+
+    fn reset(&mut self) -> bool {
+        // Finish the previous stream; session timestamps keep increasing across streams
+        if self.flush().is_err() {
+            return false;
+        }
+        match self.callback_data.lock() {
+            Ok(mut data) => {
+                data.packets.clear();
+                data.frame_count = 0;
+            }
+            Err(_) => return false,
+        }
+        self.force_keyframe = true;
+        true
+    }
 }
 
 impl Drop for VideoToolboxEncoder {
@@ -579,6 +794,7 @@ impl Drop for VideoToolboxEncoder {
 
 /// Check if VideoToolbox is available
 pub fn check_available() -> Result<()> {
-    // VideoToolbox is always available on macOS 10.8+
+    // VideoToolbox compression sessions exist since macOS 10.8 and iOS 8, older than any
+    // deployment target Rust supports
     Ok(())
 }
@@ -0,0 +1,110 @@
+//! Reference-counted COM and Media Foundation lifetime management
+//!
+//! COM is initialized once per thread and uninitialized when the thread exits.
+//! `MFStartup` runs when the first [`MfSession`] is acquired and `MFShutdown` when the
+//! last one is dropped, so encoders created from many host threads share one runtime.
+
+use crate::{Error, Result};
+use std::cell::RefCell;
+use std::sync::{Mutex, MutexGuard};
+use windows::Win32::Foundation::RPC_E_CHANGED_MODE;
+use windows::Win32::Media::MediaFoundation::{MFShutdown, MFStartup, MFSTARTUP_FULL, MF_VERSION};
+use windows::Win32::System::Com::{CoInitializeEx, CoUninitialize, COINIT_MULTITHREADED};
+
+/// Number of live sessions
+static SESSIONS: Mutex<usize> = Mutex::new(0);
+
+/// Session held between `init` and `shutdown`
+static EXPLICIT: Mutex<Option<MfSession>> = Mutex::new(None);
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// COM initialization of the current thread
+struct ComApartment {
+    /// Whether we initialized COM and must uninitialize it
+    owned: bool,
+}
+
+impl Drop for ComApartment {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe { CoUninitialize() };
+        }
+    }
+}
+
+thread_local! {
+    static COM: RefCell<Option<ComApartment>> = const { RefCell::new(None) };
+}
+
+/// Initialize COM on the current thread if not done yet
+fn ensure_com() -> Result<()> {
+    COM.with(|com| {
+        let mut com = com.borrow_mut();
+        if com.is_some() {
+            return Ok(());
+        }
+
+        let hr = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) };
+        // The host already initialized this thread as single-threaded; MF works there too
+        let owned = if hr == RPC_E_CHANGED_MODE {
+            false
+        } else {
+            hr.ok()
+                .map_err(|e| Error::Platform(format!("Failed to initialize COM: {}", e)))?;
+            true
+        };
+
+        *com = Some(ComApartment { owned });
+        Ok(())
+    })
+}
+
+/// Reference to the process-wide Media Foundation runtime
+pub(crate) struct MfSession(());
+
+impl MfSession {
+    /// Start Media Foundation if this is the first live session
+    pub(crate) fn acquire() -> Result<Self> {
+        ensure_com()?;
+
+        let mut sessions = lock(&SESSIONS);
+        if *sessions == 0 {
+            unsafe { MFStartup(MF_VERSION, MFSTARTUP_FULL) }
+                .map_err(|e| Error::Platform(format!("Failed to start MF: {}", e)))?;
+        }
+        *sessions += 1;
+
+        Ok(Self(()))
+    }
+}
+
+impl Drop for MfSession {
+    fn drop(&mut self) {
+        let mut sessions = lock(&SESSIONS);
+        *sessions -= 1;
+        if *sessions == 0 {
+            let _ = unsafe { MFShutdown() };
+        }
+    }
+}
+
+/// Keep Media Foundation running until `shutdown`
+pub(crate) fn init() -> Result<()> {
+    let mut explicit = lock(&EXPLICIT);
+    if explicit.is_none() {
+        *explicit = Some(MfSession::acquire()?);
+    }
+    Ok(())
+}
+
+/// Release the session held by `init`
+pub(crate) fn shutdown() {
+    let session = lock(&EXPLICIT).take();
+    drop(session);
+}
@@ -1,21 +1,63 @@
 //! H.264 encoder with platform-specific implementations
 
-use super::{Encoder, EncoderConfig};
-use crate::Result;
+use super::{Encoder, EncoderBackend, EncoderConfig};
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "windows",
+    target_os = "linux"
+))]
+use crate::logging::log_info;
+use crate::{runtime, Result};
 
-#[cfg(target_os = "macos")]
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 mod macos;
 
+#[cfg(target_os = "windows")]
+pub(crate) mod media_foundation;
+
 #[cfg(target_os = "windows")]
 mod windows;
 
 #[cfg(target_os = "linux")]
 mod linux;
 
+#[cfg(all(feature = "nvenc", target_os = "linux"))]
+mod nvenc;
+
+/// Whether H.264 is encoded with NVENC: the machine has an NVIDIA GPU and the runtime
+/// configuration allows hardware encoders
+#[cfg(all(feature = "nvenc", target_os = "linux"))]
+fn use_nvenc() -> bool {
+    runtime::config().hardware && nvenc::gpu_present()
+}
+
+#[cfg(all(feature = "vaapi", target_os = "linux"))]
+mod vaapi;
+
+/// Whether H.264 may be encoded with VAAPI: the machine has a GPU render node and the
+/// runtime configuration allows hardware encoders
+#[cfg(all(feature = "vaapi", target_os = "linux"))]
+fn use_vaapi() -> bool {
+    runtime::config().hardware && vaapi::device_present()
+}
+
+/// Fail if the platform encoder uses hardware and the runtime configuration disables it
+fn check_hardware_allowed() -> Result<()> {
+    if backend().is_some_and(|b| b.is_hardware()) && !runtime::config().hardware {
+        return Err(crate::Error::CodecUnavailable(
+            "Hardware H.264 encoding is disabled by the runtime configuration".to_string(),
+        ));
+    }
+    Ok(())
+}
+
 /// Check if H.264 encoding is available
 #[allow(unused_variables)]
 pub fn check_available(ffmpeg_path: Option<&str>) -> Result<()> {
-    #[cfg(target_os = "macos")]
+    check_hardware_allowed()?;
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
     {
         macos::check_available()
     }
@@ -27,10 +69,23 @@ pub fn check_available(ffmpeg_path: Option<&str>) -> Result<()> {
 
     #[cfg(target_os = "linux")]
     {
+        #[cfg(feature = "nvenc")]
+        if use_nvenc() && nvenc::check_available(ffmpeg_path).is_ok() {
+            return Ok(());
+        }
+        #[cfg(feature = "vaapi")]
+        if use_vaapi() && vaapi::check_available(ffmpeg_path).is_ok() {
+            return Ok(());
+        }
         linux::check_available(ffmpeg_path)
     }
 
-    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+    #[cfg(not(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "windows",
+        target_os = "linux"
+    )))]
     {
         Err(crate::Error::CodecUnavailable(
             "H.264 not supported on this platform".to_string(),
@@ -38,27 +93,80 @@ pub fn check_available(ffmpeg_path: Option<&str>) -> Result<()> {
     }
 }
 
+/// Start the platform runtime used by the encoder and keep it running until `shutdown`
+pub fn init() -> Result<()> {
+    #[cfg(target_os = "windows")]
+    {
+        media_foundation::init()
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        Ok(())
+    }
+}
+
+/// Release the runtime kept by `init`
+pub fn shutdown() {
+    #[cfg(target_os = "windows")]
+    media_foundation::shutdown();
+}
+
+/// H.264 encoder backend for the current platform
+///
+/// NVENC is reported when an NVIDIA GPU is present, even if ffmpeg turns out not to have
+/// it and libx264 is used instead. VAAPI is reported once ffmpeg has encoded with it.
+pub fn backend() -> Option<EncoderBackend> {
+    #[cfg(all(feature = "nvenc", target_os = "linux"))]
+    if use_nvenc() {
+        return Some(EncoderBackend::Nvenc);
+    }
+    #[cfg(all(feature = "vaapi", target_os = "linux"))]
+    if use_vaapi() && vaapi::check_available(None).is_ok() {
+        return Some(EncoderBackend::Vaapi);
+    }
+
+    if cfg!(any(target_os = "macos", target_os = "ios")) {
+        Some(EncoderBackend::VideoToolbox)
+    } else if cfg!(target_os = "windows") {
+        Some(EncoderBackend::MediaFoundation)
+    } else if cfg!(target_os = "linux") {
+        Some(EncoderBackend::Ffmpeg)
+    } else {
+        None
+    }
+}
+
 /// Create an H.264 encoder for the current platform
 pub fn create_encoder(config: EncoderConfig) -> Result<Box<dyn Encoder>> {
-    #[cfg(target_os = "macos")]
+    check_hardware_allowed()?;
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
     {
+        log_info!("Using H.264 encoder (VideoToolbox)");
         Ok(Box::new(macos::VideoToolboxEncoder::new(config)?))
     }
 
     #[cfg(target_os = "windows")]
     {
+        log_info!("Using H.264 encoder (Media Foundation)");
         Ok(Box::new(windows::MediaFoundationEncoder::new(config)?))
     }
 
     #[cfg(target_os = "linux")]
     {
-        Ok(Box::new(linux::FfmpegEncoder::new(config, None)?))
+        create_ffmpeg_encoder(config, None)
     }
 
-    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+    #[cfg(not(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "windows",
+        target_os = "linux"
+    )))]
     {
         let _ = config;
-        Err(Error::CodecUnavailable(
+        Err(crate::Error::CodecUnavailable(
             "H.264 not supported on this platform".to_string(),
         ))
     }
@@ -72,7 +180,7 @@ pub fn create_encoder_with_ffmpeg(
 ) -> Result<Box<dyn Encoder>> {
     #[cfg(target_os = "linux")]
     {
-        Ok(Box::new(linux::FfmpegEncoder::new(config, ffmpeg_path)?))
+        create_ffmpeg_encoder(config, ffmpeg_path)
     }
 
     #[cfg(not(target_os = "linux"))]
@@ -81,3 +189,28 @@ pub fn create_encoder_with_ffmpeg(
         create_encoder(config)
     }
 }
+
+/// Create an ffmpeg H.264 encoder, with NVENC or else VAAPI when the machine has them and
+/// libx264 otherwise
+///
+/// Deterministic output needs libx264, as hardware encoder output depends on the GPU and
+/// driver.
+#[cfg(target_os = "linux")]
+fn create_ffmpeg_encoder(
+    config: EncoderConfig,
+    ffmpeg_path: Option<&str>,
+) -> Result<Box<dyn Encoder>> {
+    #[cfg(feature = "nvenc")]
+    if !config.deterministic && use_nvenc() && nvenc::check_available(ffmpeg_path).is_ok() {
+        log_info!("Using H.264 encoder (ffmpeg h264_nvenc)");
+        return Ok(Box::new(nvenc::create_encoder(config, ffmpeg_path)?));
+    }
+    #[cfg(feature = "vaapi")]
+    if !config.deterministic && use_vaapi() && vaapi::check_available(ffmpeg_path).is_ok() {
+        log_info!("Using H.264 encoder (ffmpeg h264_vaapi)");
+        return Ok(Box::new(vaapi::create_encoder(config, ffmpeg_path)?));
+    }
+
+    log_info!("Using H.264 encoder (ffmpeg libx264)");
+    Ok(Box::new(linux::FfmpegEncoder::new(config, ffmpeg_path)?))
+}
@@ -0,0 +1,122 @@
+//! NVIDIA NVENC H.264 encoder using an ffmpeg process with h264_nvenc
+
+use super::super::ffmpeg_pipe::{check_encoder, color_args, keyframe_args, rate_args};
+use super::super::{EncoderBackend, EncoderConfig};
+use super::linux::{find_ffmpeg, input_command, FfmpegEncoder};
+use crate::logging::log_debug;
+use crate::Result;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// NVENC preset, from p1 (fastest) to p7 (best quality); p4 is ffmpeg's default
+const PRESET: &str = "p4";
+
+/// Files the NVIDIA driver creates once it has found a GPU, the second also in containers
+/// given access to one
+const DRIVER_FILES: [&str; 2] = ["/proc/driver/nvidia/version", "/dev/nvidia0"];
+
+/// Whether the machine has an NVIDIA GPU with its driver loaded, checked once
+pub fn gpu_present() -> bool {
+    static PRESENT: OnceLock<bool> = OnceLock::new();
+    *PRESENT.get_or_init(|| DRIVER_FILES.iter().any(|path| Path::new(path).exists()))
+}
+
+/// Start ffmpeg encoding H.264 with h264_nvenc
+///
+/// Frames are converted to YUV by ffmpeg as for libx264, then encoded on the GPU.
+pub fn create_encoder(config: EncoderConfig, ffmpeg_path: Option<&str>) -> Result<FfmpegEncoder> {
+    let ffmpeg = find_ffmpeg(ffmpeg_path)?;
+
+    // Map quality (0-100) to a constant quality level (51-0), as for x264's CRF
+    let cq = ((100 - config.quality.min(100)) as u32 * 51) / 100;
+
+    log_debug!(
+        "Starting {} for {}x{} NVENC H.264 encoding ({})",
+        ffmpeg,
+        config.width,
+        config.height,
+        nvenc_rate_args(&config, cq).join(" ")
+    );
+
+    let mut command = input_command(&ffmpeg, &config);
+    command.args([
+        "-c:v",
+        "h264_nvenc",
+        "-preset",
+        PRESET,
+        "-pix_fmt",
+        "yuv420p",
+    ]);
+    command.args(nvenc_rate_args(&config, cq));
+    command.args(color_args(&config));
+    if let Some(interval) = config.keyframe_interval {
+        command.args(keyframe_args(&config, interval));
+    }
+    FfmpegEncoder::spawn(command, &ffmpeg, config, EncoderBackend::Nvenc)
+}
+
+/// ffmpeg arguments for NVENC's rate control at the target bitrate of `config`, or else
+/// at the constant quality level `cq`
+///
+/// NVENC has no CRF: constant quality is its variable bitrate mode without a target.
+fn nvenc_rate_args(config: &EncoderConfig, cq: u32) -> Vec<String> {
+    let mut args = vec!["-rc".to_string()];
+    match config.bitrate() {
+        Some((_, constant)) => {
+            args.push(if constant { "cbr" } else { "vbr" }.to_string());
+            args.extend(rate_args(config, cq));
+        }
+        None => args.extend(["vbr", "-cq", &cq.to_string(), "-b:v", "0"].map(str::to_string)),
+    }
+    args
+}
+
+/// Check if ffmpeg with NVENC H.264 support is available
+pub fn check_available(ffmpeg_path: Option<&str>) -> Result<()> {
+    let ffmpeg = find_ffmpeg(ffmpeg_path)?;
+    check_encoder(&ffmpeg, "h264_nvenc")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Av1Backend, ColorSpace, PixelRange, Priority, RateControl};
+
+    #[test]
+    fn test_nvenc_rate_args() {
+        let mut config = EncoderConfig {
+            width: 64,
+            height: 48,
+            fps: 30,
+            quality: 50,
+            bitrate_kbps: None,
+            rate_control: RateControl::Auto,
+            keyframe_interval: None,
+            color_space: ColorSpace::Bt601,
+            pixel_range: PixelRange::Auto,
+            deterministic: false,
+            priority: Priority::Normal,
+            threads: 0,
+            av1_backend: Av1Backend::Rav1e,
+        };
+        assert_eq!(
+            nvenc_rate_args(&config, 25),
+            ["-rc", "vbr", "-cq", "25", "-b:v", "0"]
+        );
+
+        config.bitrate_kbps = Some(2000);
+        assert_eq!(
+            nvenc_rate_args(&config, 25),
+            ["-rc", "vbr", "-b:v", "2000k"]
+        );
+
+        config.rate_control = RateControl::Constant;
+        assert_eq!(
+            nvenc_rate_args(&config, 25),
+            [
+                "-rc", "cbr", "-b:v", "2000k", "-minrate", "2000k", "-maxrate", "2000k",
+                "-bufsize", "2000k"
+            ]
+        );
+    }
+}
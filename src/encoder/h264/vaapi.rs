@@ -0,0 +1,179 @@
+//! VAAPI H.264 encoder for Intel and AMD GPUs using an ffmpeg process with h264_vaapi
+
+use super::super::ffmpeg_pipe::{color_args, keyframe_args, rate_args};
+use super::super::{EncoderBackend, EncoderConfig};
+use super::linux::{find_ffmpeg, input_command, FfmpegEncoder};
+use crate::logging::{log_debug, log_info};
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, OnceLock};
+
+/// DRM render node of the first GPU, which VAAPI encodes on
+const RENDER_NODE: &str = "/dev/dri/renderD128";
+
+/// Whether the machine has a GPU render node
+pub fn device_present() -> bool {
+    Path::new(RENDER_NODE).exists()
+}
+
+/// Start ffmpeg encoding H.264 with h264_vaapi
+///
+/// ffmpeg converts frames to NV12 on the CPU and uploads them to the GPU for encoding.
+pub fn create_encoder(config: EncoderConfig, ffmpeg_path: Option<&str>) -> Result<FfmpegEncoder> {
+    let ffmpeg = find_ffmpeg(ffmpeg_path)?;
+
+    // Map quality (0-100) to a constant quantizer (51-0), as for x264's CRF
+    let qp = ((100 - config.quality.min(100)) as u32 * 51) / 100;
+
+    log_debug!(
+        "Starting {} for {}x{} VAAPI H.264 encoding ({})",
+        ffmpeg,
+        config.width,
+        config.height,
+        vaapi_rate_args(&config, qp).join(" ")
+    );
+
+    let mut command = input_command(&ffmpeg, &config);
+    command.args(["-vaapi_device", RENDER_NODE, "-c:v", "h264_vaapi"]);
+    command.args(upload_args(color_args(&config)));
+    command.args(vaapi_rate_args(&config, qp));
+    if let Some(interval) = config.keyframe_interval {
+        command.args(keyframe_args(&config, interval));
+    }
+    FfmpegEncoder::spawn(command, &ffmpeg, config, EncoderBackend::Vaapi)
+}
+
+/// Color arguments with the frames converted to NV12 and uploaded to the GPU after any
+/// color conversion
+fn upload_args(color_args: Vec<String>) -> Vec<String> {
+    let mut filters = Vec::new();
+    let mut tags = Vec::new();
+    let mut color_args = color_args.into_iter();
+    while let Some(arg) = color_args.next() {
+        if arg == "-vf" {
+            filters.extend(color_args.next());
+        } else {
+            tags.push(arg);
+        }
+    }
+    filters.extend(["format=nv12", "hwupload"].map(str::to_string));
+
+    let mut args = vec!["-vf".to_string(), filters.join(",")];
+    args.extend(tags);
+    args
+}
+
+/// ffmpeg arguments for VAAPI's rate control at the target bitrate of `config`, or else
+/// at the constant quantizer `qp`
+fn vaapi_rate_args(config: &EncoderConfig, qp: u32) -> Vec<String> {
+    let mut args = vec!["-rc_mode".to_string()];
+    match config.bitrate() {
+        Some((_, constant)) => {
+            args.push(if constant { "CBR" } else { "VBR" }.to_string());
+            args.extend(rate_args(config, qp));
+        }
+        None => args.extend(["CQP", "-qp", &qp.to_string()].map(str::to_string)),
+    }
+    args
+}
+
+/// Check that ffmpeg can encode H.264 with VAAPI on this machine
+///
+/// Having h264_vaapi and a render node is not enough, as the GPU or its driver may not
+/// encode H.264, so one frame is encoded. The result is kept for each ffmpeg.
+pub fn check_available(ffmpeg_path: Option<&str>) -> Result<()> {
+    static PROBED: OnceLock<Mutex<HashMap<String, bool>>> = OnceLock::new();
+
+    let ffmpeg = find_ffmpeg(ffmpeg_path)?;
+    let mut probed = PROBED
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let available = *probed
+        .entry(ffmpeg.clone())
+        .or_insert_with(|| probe(&ffmpeg));
+    if available {
+        Ok(())
+    } else {
+        Err(Error::CodecUnavailable(
+            "FFmpeg cannot encode H.264 with VAAPI".to_string(),
+        ))
+    }
+}
+
+/// Encode one black frame with h264_vaapi, discarding the output
+fn probe(ffmpeg: &str) -> bool {
+    let available = Command::new(ffmpeg)
+        .args(["-v", "error", "-vaapi_device", RENDER_NODE])
+        .args(["-f", "lavfi", "-i", "color=black:size=64x64"])
+        .args(["-vf", "format=nv12,hwupload", "-c:v", "h264_vaapi"])
+        .args(["-frames:v", "1", "-f", "null", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !available {
+        log_info!("VAAPI H.264 encoding unavailable with {}", ffmpeg);
+    }
+    available
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Av1Backend, ColorSpace, PixelRange, Priority, RateControl};
+
+    fn config() -> EncoderConfig {
+        EncoderConfig {
+            width: 64,
+            height: 48,
+            fps: 30,
+            quality: 50,
+            bitrate_kbps: None,
+            rate_control: RateControl::Auto,
+            keyframe_interval: None,
+            color_space: ColorSpace::Bt601,
+            pixel_range: PixelRange::Auto,
+            deterministic: false,
+            priority: Priority::Normal,
+            threads: 0,
+            av1_backend: Av1Backend::Rav1e,
+        }
+    }
+
+    #[test]
+    fn test_vaapi_rate_args() {
+        let mut config = config();
+        assert_eq!(
+            vaapi_rate_args(&config, 25),
+            ["-rc_mode", "CQP", "-qp", "25"]
+        );
+
+        config.bitrate_kbps = Some(2000);
+        config.rate_control = RateControl::Constant;
+        assert_eq!(
+            vaapi_rate_args(&config, 25)[..4],
+            ["-rc_mode", "CBR", "-b:v", "2000k"]
+        );
+    }
+
+    #[test]
+    fn test_upload_args() {
+        let mut config = config();
+        assert_eq!(
+            upload_args(color_args(&config))[..2],
+            ["-vf", "format=nv12,hwupload"]
+        );
+
+        config.color_space = ColorSpace::Bt709;
+        let args = upload_args(color_args(&config));
+        assert_eq!(
+            args[..2],
+            ["-vf", "scale=out_color_matrix=bt709,format=nv12,hwupload"]
+        );
+        assert_eq!(args.iter().filter(|arg| *arg == "-vf").count(), 1);
+    }
+}
@@ -1,6 +1,8 @@
 //! Windows H.264 encoder using Media Foundation
 
-use super::super::{Encoder, EncoderConfig, Frame, Packet};
+use super::super::color::{rgba_to_nv12, Matrix, PlaneMut};
+use super::super::{ColorSpace, Encoder, EncoderBackend, EncoderConfig, Frame, Packet};
+use super::media_foundation::MfSession;
 use crate::{Error, Result};
 use std::ptr;
 use windows::Win32::Media::MediaFoundation::*;
@@ -16,22 +18,17 @@ pub struct MediaFoundationEncoder {
     initialized: bool,
     sps: Option<Vec<u8>>,
     pps: Option<Vec<u8>>,
+    // Declared last so the MF objects above are released before the session
+    _session: MfSession,
 }
 
 unsafe impl Send for MediaFoundationEncoder {}
 
 impl MediaFoundationEncoder {
     pub fn new(config: EncoderConfig) -> Result<Self> {
-        unsafe {
-            // Initialize COM
-            CoInitializeEx(None, COINIT_MULTITHREADED)
-                .ok()
-                .map_err(|e| Error::Platform(format!("Failed to initialize COM: {}", e)))?;
-
-            // Initialize Media Foundation
-            MFStartup(MF_VERSION, MFSTARTUP_FULL)
-                .map_err(|e| Error::Platform(format!("Failed to start MF: {}", e)))?;
+        let session = MfSession::acquire()?;
 
+        unsafe {
             // Find and create H.264 encoder
             let transform = find_h264_encoder()?;
 
@@ -58,6 +55,8 @@ impl MediaFoundationEncoder {
                 .SetUINT64(&MF_MT_FRAME_RATE, ((config.fps as u64) << 32) | 1u64)
                 .map_err(|e| Error::Encode(format!("Failed to set frame rate: {}", e)))?;
 
+            set_colorimetry(&input_type, &config)?;
+
             // Create output media type (H.264)
             let output_type: IMFMediaType = MFCreateMediaType()
                 .map_err(|e| Error::Encode(format!("Failed to create output type: {}", e)))?;
@@ -81,12 +80,33 @@ impl MediaFoundationEncoder {
                 .SetUINT64(&MF_MT_FRAME_RATE, ((config.fps as u64) << 32) | 1u64)
                 .map_err(|e| Error::Encode(format!("Failed to set frame rate: {}", e)))?;
 
-            // Calculate bitrate from quality (rough estimate)
+            // Target bitrate, or one estimated from quality
             let bitrate = calculate_bitrate(&config);
             output_type
                 .SetUINT32(&MF_MT_AVG_BITRATE, bitrate)
                 .map_err(|e| Error::Encode(format!("Failed to set bitrate: {}", e)))?;
 
+            if let Some(interval) = config.keyframe_interval {
+                output_type
+                    .SetUINT32(&CODECAPI_AVEncMPVGOPSize, interval)
+                    .map_err(|e| Error::Encode(format!("Failed to set GOP size: {}", e)))?;
+            }
+
+            // The encoder holds its bitrate constant unless told to average it
+            let rate_control_mode = match config.bitrate() {
+                Some((_, false)) => eAVEncCommonRateControlMode_UnconstrainedVBR,
+                _ => eAVEncCommonRateControlMode_CBR,
+            };
+            output_type
+                .SetUINT32(
+                    &CODECAPI_AVEncCommonRateControlMode,
+                    rate_control_mode.0 as u32,
+                )
+                .map_err(|e| Error::Encode(format!("Failed to set rate control: {}", e)))?;
+
+            // Signal the color space and range in the SPS
+            set_colorimetry(&output_type, &config)?;
+
             // Set interlace mode (progressive scan)
             output_type
                 .SetUINT32(&MF_MT_INTERLACE_MODE, MFVideoInterlace_Progressive.0 as u32)
@@ -111,6 +131,7 @@ impl MediaFoundationEncoder {
                 initialized: true,
                 sps: None,
                 pps: None,
+                _session: session,
             };
 
             // Try to extract SPS/PPS from output media type attributes
@@ -120,61 +141,33 @@ impl MediaFoundationEncoder {
         }
     }
 
-    fn rgba_to_nv12(&self, frame: &Frame) -> Vec<u8> {
+    /// Convert RGBA frame to NV12
+    fn to_nv12(&self, frame: &Frame) -> Vec<u8> {
         let width = frame.width as usize;
         let height = frame.height as usize;
         let y_size = width * height;
-        let uv_size = (width / 2) * (height / 2) * 2;
-        let mut nv12 = vec![0u8; y_size + uv_size];
-
-        // Y plane
-        for y in 0..height {
-            for x in 0..width {
-                let idx = (y * width + x) * 4;
-                let r = frame.data[idx] as f32;
-                let g = frame.data[idx + 1] as f32;
-                let b = frame.data[idx + 2] as f32;
-
-                // BT.601 conversion
-                let y_val = (0.299 * r + 0.587 * g + 0.114 * b).clamp(0.0, 255.0) as u8;
-                nv12[y * width + x] = y_val;
-            }
-        }
-
-        // UV plane (interleaved)
-        let uv_offset = y_size;
-        let uv_width = width / 2;
-
-        for y in 0..(height / 2) {
-            for x in 0..(width / 2) {
-                let src_x = x * 2;
-                let src_y = y * 2;
-
-                // Average 2x2 block
-                let mut r_sum = 0u32;
-                let mut g_sum = 0u32;
-                let mut b_sum = 0u32;
-
-                for dy in 0..2 {
-                    for dx in 0..2 {
-                        let idx = ((src_y + dy) * width + (src_x + dx)) * 4;
-                        r_sum += frame.data[idx] as u32;
-                        g_sum += frame.data[idx + 1] as u32;
-                        b_sum += frame.data[idx + 2] as u32;
-                    }
-                }
-
-                let r = (r_sum / 4) as f32;
-                let g = (g_sum / 4) as f32;
-                let b = (b_sum / 4) as f32;
-
-                let u = ((-0.169 * r - 0.331 * g + 0.500 * b) + 128.0).clamp(0.0, 255.0) as u8;
-                let v = ((0.500 * r - 0.419 * g - 0.081 * b) + 128.0).clamp(0.0, 255.0) as u8;
-
-                nv12[uv_offset + y * uv_width * 2 + x * 2] = u;
-                nv12[uv_offset + y * uv_width * 2 + x * 2 + 1] = v;
-            }
-        }
+        let uv_stride = width.div_ceil(2) * 2;
+        let mut nv12 = vec![0u8; y_size + uv_stride * height.div_ceil(2)];
+        let matrix = Matrix::new(
+            self.config.color_space,
+            self.config.pixel_range.is_full(false),
+        );
+
+        let (y_plane, uv_plane) = nv12.split_at_mut(y_size);
+        rgba_to_nv12(
+            frame.data,
+            width,
+            height,
+            matrix,
+            PlaneMut {
+                data: y_plane,
+                stride: width,
+            },
+            PlaneMut {
+                data: uv_plane,
+                stride: uv_stride,
+            },
+        );
 
         nv12
     }
@@ -182,7 +175,8 @@ impl MediaFoundationEncoder {
 
 impl Encoder for MediaFoundationEncoder {
     fn encode(&mut self, frame: &Frame) -> Result<Vec<Packet>> {
-        let nv12_data = self.rgba_to_nv12(frame);
+        frame.check(&self.config)?;
+        let nv12_data = self.to_nv12(frame);
 
         unsafe {
             // Create input sample
@@ -256,6 +250,28 @@ impl Encoder for MediaFoundationEncoder {
     fn pps(&self) -> Option<Vec<u8>> {
         self.pps.clone()
     }
+
+    fn backend(&self) -> EncoderBackend {
+        EncoderBackend::MediaFoundation
+    }
+
+    fn reset(&mut self) -> bool {
+        unsafe {
+            // Drop anything still queued in the transform, which restarts its GOP
+            if self
+                .transform
+                .ProcessMessage(MFT_MESSAGE_COMMAND_FLUSH, 0)
+                .is_err()
+            {
+                return false;
+            }
+            self.transform
+                .ProcessMessage(MFT_MESSAGE_NOTIFY_START_OF_STREAM, 0)
+                .ok();
+        }
+        self.frame_count = 0;
+        true
+    }
 }
 
 impl MediaFoundationEncoder {
@@ -314,7 +330,8 @@ impl MediaFoundationEncoder {
                             self.extract_sps_pps(&data);
                         }
 
-                        // If still no SPS/PPS, try to get from media type (may be available after first encode)
+                        // If still no SPS/PPS, try to get from media type (may be available
+                        // after first encode)
                         if self.sps.is_none() || self.pps.is_none() {
                             self.extract_sps_pps_from_media_type();
                         }
@@ -342,7 +359,8 @@ impl MediaFoundationEncoder {
     /// This is used when the encoder doesn't provide SPS/PPS through standard interfaces
     fn generate_fallback_sps_pps(&mut self) {
         // Generate minimal SPS
-        // Format: NAL header + profile_idc + constraint flags + level_idc + seq_parameter_set_id + ...
+        // Format: NAL header + profile_idc + constraint flags + level_idc +
+        // seq_parameter_set_id + ...
         let width = self.config.width;
         let height = self.config.height;
 
@@ -602,10 +620,8 @@ impl MediaFoundationEncoder {
     }
 }
 
-// Note: We intentionally don't implement Drop to call MFShutdown/CoUninitialize.
-// MFStartup/MFShutdown are process-wide, and calling MFShutdown while another
-// encoder is still active (in parallel tests) causes crashes.
-// COM/MF will be cleaned up when the process exits.
+// Note: MFShutdown runs when the last MfSession is dropped, so encoders active
+// on other threads keep the runtime alive. COM is uninitialized at thread exit.
 
 fn find_h264_encoder() -> Result<IMFTransform> {
     unsafe {
@@ -645,9 +661,14 @@ fn find_h264_encoder() -> Result<IMFTransform> {
             .ok_or_else(|| Error::CodecUnavailable("Invalid activate object".to_string()))?;
 
         // Create transform from activate
-        let transform: IMFTransform = activate
-            .ActivateObject()
-            .map_err(|e| Error::CodecUnavailable(format!("Failed to activate encoder: {}", e)))?;
+        let transform: IMFTransform = activate.ActivateObject().map_err(|e| {
+            // Hardware encoders have a limited number of sessions
+            if e.code() == MF_E_HW_MFT_FAILED_START_STREAMING {
+                Error::Busy(format!("Hardware encoder is in use: {}", e))
+            } else {
+                Error::CodecUnavailable(format!("Failed to activate encoder: {}", e))
+            }
+        })?;
 
         // Free the activate array
         for i in 0..count as usize {
@@ -659,7 +680,29 @@ fn find_h264_encoder() -> Result<IMFTransform> {
     }
 }
 
+/// Set the YUV matrix, primaries, transfer function and range of `config` on a media type
+unsafe fn set_colorimetry(media_type: &IMFMediaType, config: &EncoderConfig) -> Result<()> {
+    let (matrix, primaries) = match config.color_space {
+        ColorSpace::Bt601 => (MFVideoTransferMatrix_BT601, MFVideoPrimaries_SMPTE170M),
+        ColorSpace::Bt709 => (MFVideoTransferMatrix_BT709, MFVideoPrimaries_BT709),
+    };
+    let range = if config.pixel_range.is_full(false) {
+        MFNominalRange_0_255
+    } else {
+        MFNominalRange_16_235
+    };
+    media_type
+        .SetUINT32(&MF_MT_YUV_MATRIX, matrix.0 as u32)
+        .and_then(|_| media_type.SetUINT32(&MF_MT_VIDEO_PRIMARIES, primaries.0 as u32))
+        .and_then(|_| media_type.SetUINT32(&MF_MT_TRANSFER_FUNCTION, MFVideoTransFunc_709.0 as u32))
+        .and_then(|_| media_type.SetUINT32(&MF_MT_VIDEO_NOMINAL_RANGE, range.0 as u32))
+        .map_err(|e| Error::Encode(format!("Failed to set color space: {}", e)))
+}
+
 fn calculate_bitrate(config: &EncoderConfig) -> u32 {
+    if let Some((kbps, _)) = config.bitrate() {
+        return kbps.saturating_mul(1000);
+    }
     // Rough bitrate calculation based on resolution, fps, and quality
     let pixels = config.width * config.height;
     let base_bitrate = (pixels * config.fps) / 100;
@@ -685,19 +728,11 @@ fn encode_exp_golomb(bits: &mut Vec<bool>, value: u32) {
 
 /// Check if Media Foundation H.264 encoder is available
 pub fn check_available() -> Result<()> {
-    unsafe {
-        CoInitializeEx(None, COINIT_MULTITHREADED)
-            .ok()
-            .map_err(|e| Error::Platform(format!("Failed to initialize COM: {}", e)))?;
-
-        MFStartup(MF_VERSION, MFSTARTUP_FULL)
-            .map_err(|e| Error::Platform(format!("Failed to start MF: {}", e)))?;
-
-        // Just check if we can find an encoder
-        // Don't call MFShutdown/CoUninitialize - it affects other encoders in parallel tests
-        match find_h264_encoder() {
-            Ok(_transform) => Ok(()),
-            Err(e) => Err(e),
-        }
+    let _session = MfSession::acquire()?;
+
+    // Just check if we can find an encoder
+    match find_h264_encoder() {
+        Ok(_transform) => Ok(()),
+        Err(e) => Err(e),
     }
 }
@@ -2,24 +2,65 @@
 
 #[cfg(feature = "av1")]
 pub mod av1;
+pub mod color;
+#[cfg(not(target_arch = "wasm32"))]
+mod ffmpeg_pipe;
+mod pool;
+pub mod raw;
 
 pub mod h264;
+#[cfg(all(feature = "svt-av1", not(target_arch = "wasm32")))]
+pub mod svt_av1;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod vp9;
 
-use crate::{Codec, Result};
+use crate::image_loader::rgba_len;
+#[cfg(feature = "av1")]
+use crate::logging::log_info;
+use crate::logging::trace_span;
+use crate::{Codec, Error, Priority, Result};
+
+pub use pool::EncoderPool;
+pub(crate) use pool::PooledEncoder;
 
 /// Raw video frame in RGBA format
-#[derive(Debug, Clone)]
-pub struct Frame {
+///
+/// Borrows its pixels, so repeated frames such as a slide shown for many frames can share
+/// one buffer.
+#[derive(Debug, Clone, Copy)]
+pub struct Frame<'a> {
     /// Frame width in pixels
     pub width: u32,
     /// Frame height in pixels
     pub height: u32,
     /// RGBA pixel data (width * height * 4 bytes)
-    pub data: Vec<u8>,
+    pub data: &'a [u8],
     /// Presentation timestamp in milliseconds
     pub pts_ms: u64,
 }
 
+impl Frame<'_> {
+    /// Check that the frame has the encoder's dimensions and a full RGBA buffer
+    pub fn check(&self, config: &EncoderConfig) -> Result<()> {
+        if (self.width, self.height) != (config.width, config.height) {
+            return Err(Error::Internal(format!(
+                "Frame is {}x{}, encoder expects {}x{}",
+                self.width, self.height, config.width, config.height
+            )));
+        }
+
+        let expected = rgba_len(self.width, self.height)?;
+        if self.data.len() != expected {
+            return Err(Error::Internal(format!(
+                "Frame is {} bytes, expected {}",
+                self.data.len(),
+                expected
+            )));
+        }
+        Ok(())
+    }
+}
+
 /// Encoded video packet
 #[derive(Debug, Clone)]
 pub struct Packet {
@@ -41,6 +82,9 @@ pub trait Encoder: Send {
     /// Flush remaining packets
     fn flush(&mut self) -> Result<Vec<Packet>>;
 
+    /// Implementation doing the encoding
+    fn backend(&self) -> EncoderBackend;
+
     /// Get the codec-specific configuration data (SPS for H.264)
     fn codec_config(&self) -> Option<Vec<u8>> {
         None
@@ -50,10 +94,163 @@ pub trait Encoder: Send {
     fn pps(&self) -> Option<Vec<u8>> {
         None
     }
+
+    /// Start a new stream with the same configuration, discarding frames not yet flushed
+    ///
+    /// The next frame is encoded as a keyframe with timestamp 0. Returns false if the
+    /// encoder cannot be reused.
+    fn reset(&mut self) -> bool {
+        false
+    }
+}
+
+/// Encoder implementation backing a codec
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
+pub enum EncoderBackend {
+    /// rav1e software AV1 encoder
+    Rav1e = 0,
+    /// Apple VideoToolbox (macOS and iOS)
+    VideoToolbox = 1,
+    /// Windows Media Foundation
+    MediaFoundation = 2,
+    /// External ffmpeg process (libx264 for H.264, libvpx-vp9 for VP9)
+    Ffmpeg = 3,
+    /// SVT-AV1 in an external ffmpeg process (libsvtav1)
+    SvtAv1 = 4,
+    /// Built-in lossless image encoder for animated images
+    Image = 5,
+    /// No encoding: uncompressed YUV frames for Y4M output
+    Raw = 6,
+    /// NVIDIA NVENC in an external ffmpeg process (h264_nvenc)
+    Nvenc = 7,
+    /// VAAPI on Intel and AMD GPUs in an external ffmpeg process (h264_vaapi)
+    Vaapi = 8,
+}
+
+impl EncoderBackend {
+    /// Human-readable backend name
+    pub fn name(&self) -> &'static str {
+        match self {
+            EncoderBackend::Rav1e => "rav1e",
+            EncoderBackend::VideoToolbox => "VideoToolbox",
+            EncoderBackend::MediaFoundation => "Media Foundation",
+            EncoderBackend::Ffmpeg => "ffmpeg",
+            EncoderBackend::SvtAv1 => "SVT-AV1",
+            EncoderBackend::Image => "image",
+            EncoderBackend::Raw => "raw",
+            EncoderBackend::Nvenc => "NVENC",
+            EncoderBackend::Vaapi => "VAAPI",
+        }
+    }
+
+    /// Whether the backend uses hardware acceleration when the machine provides it
+    pub fn is_hardware(&self) -> bool {
+        matches!(
+            self,
+            EncoderBackend::VideoToolbox
+                | EncoderBackend::MediaFoundation
+                | EncoderBackend::Nvenc
+                | EncoderBackend::Vaapi
+        )
+    }
+}
+
+/// AV1 encoder implementation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
+pub enum Av1Backend {
+    /// rav1e, built into the library
+    #[default]
+    Rav1e = 0,
+    /// SVT-AV1 through ffmpeg with libsvtav1, much faster at similar quality (needs the
+    /// `svt-av1` feature)
+    SvtAv1 = 1,
+}
+
+/// How an encoder spends bits over the video
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
+pub enum RateControl {
+    /// `Average` with a target bitrate, `Quality` without one
+    #[default]
+    Auto = 0,
+    /// Constant quality set by `quality`, ignoring the target bitrate
+    Quality = 1,
+    /// Target bitrate on average, with more bits for complex frames
+    Average = 2,
+    /// Target bitrate held steady, for streaming over fixed bandwidth
+    Constant = 3,
+}
+
+/// Color space frames are converted to YUV in and tagged with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
+pub enum ColorSpace {
+    /// BT.601, for standard definition
+    #[default]
+    Bt601 = 0,
+    /// BT.709, what players expect of HD video
+    Bt709 = 1,
+}
+
+impl ColorSpace {
+    /// Color primaries, transfer characteristics and matrix coefficients code points
+    /// (ISO/IEC 23091-2) that containers signal the color space with
+    pub(crate) fn code_points(self) -> (u8, u8, u8) {
+        match self {
+            ColorSpace::Bt601 => (6, 6, 6),
+            ColorSpace::Bt709 => (1, 1, 1),
+        }
+    }
+}
+
+/// Range of the YUV values frames are converted to and tagged with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
+pub enum PixelRange {
+    /// `Full` for uncompressed Y4M, `Limited` for encoded video
+    #[default]
+    Auto = 0,
+    /// Luma from 16 to 235 and chroma from 16 to 240, what players expect of video
+    Limited = 1,
+    /// Every value from 0 to 255, for screenshots and UI captures
+    Full = 2,
+}
+
+impl PixelRange {
+    /// Whether the range is full, `auto_full` giving the answer for `Auto`
+    pub(crate) fn is_full(self, auto_full: bool) -> bool {
+        match self {
+            PixelRange::Auto => auto_full,
+            PixelRange::Limited => false,
+            PixelRange::Full => true,
+        }
+    }
+}
+
+/// Backend used to encode a codec on this platform, if any
+pub fn backend_for(codec: Codec) -> Option<EncoderBackend> {
+    match codec {
+        #[cfg(feature = "av1")]
+        Codec::Av1 => Some(EncoderBackend::Rav1e),
+        #[cfg(not(feature = "av1"))]
+        Codec::Av1 => None,
+        Codec::H264 => h264::backend(),
+        #[cfg(not(target_arch = "wasm32"))]
+        Codec::Vp9 => Some(EncoderBackend::Ffmpeg),
+        #[cfg(target_arch = "wasm32")]
+        Codec::Vp9 => None,
+    }
 }
 
 /// Encoder configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct EncoderConfig {
     /// Frame width
     pub width: u32,
@@ -63,17 +260,75 @@ pub struct EncoderConfig {
     pub fps: u32,
     /// Quality (0-100)
     pub quality: u8,
+    /// Target bitrate in kilobits per second, which takes over from `quality`
+    pub bitrate_kbps: Option<u32>,
+    /// How the target bitrate or quality is held
+    pub rate_control: RateControl,
+    /// Frames between keyframes, or `None` for the encoder's default
+    pub keyframe_interval: Option<u32>,
+    /// Color space to convert frames in and signal
+    pub color_space: ColorSpace,
+    /// Range of the YUV values
+    pub pixel_range: PixelRange,
+    /// Produce the same bytes for the same frames on every machine
+    pub deterministic: bool,
+    /// Priority of the encoder's threads and processes
+    pub priority: Priority,
+    /// Threads the encoder may use, 0 for one per core
+    pub threads: usize,
+    /// Implementation encoding AV1
+    pub av1_backend: Av1Backend,
+}
+
+impl EncoderConfig {
+    /// Bitrate in kilobits per second to encode at and whether to hold it constant, or
+    /// `None` to encode by `quality`
+    pub(crate) fn bitrate(&self) -> Option<(u32, bool)> {
+        match (self.rate_control, self.bitrate_kbps) {
+            (RateControl::Quality, _) | (_, None) => None,
+            (rate_control, Some(kbps)) => Some((kbps, rate_control == RateControl::Constant)),
+        }
+    }
 }
 
 /// Create an encoder for the specified codec
 pub fn create_encoder(codec: Codec, config: EncoderConfig) -> Result<Box<dyn Encoder>> {
+    let _span = trace_span!("create_encoder", ?codec, config.width, config.height);
+
+    if config.bitrate_kbps.is_none()
+        && matches!(
+            config.rate_control,
+            RateControl::Average | RateControl::Constant
+        )
+    {
+        return Err(Error::InvalidInput(format!(
+            "{:?} rate control needs a target bitrate",
+            config.rate_control
+        )));
+    }
+
     match codec {
+        #[cfg(all(feature = "svt-av1", not(target_arch = "wasm32")))]
+        Codec::Av1 if config.av1_backend == Av1Backend::SvtAv1 => svt_av1::create_encoder(config),
+        #[cfg(not(all(feature = "svt-av1", not(target_arch = "wasm32"))))]
+        Codec::Av1 if config.av1_backend == Av1Backend::SvtAv1 => Err(Error::CodecUnavailable(
+            "SVT-AV1 support not compiled in".to_string(),
+        )),
         #[cfg(feature = "av1")]
-        Codec::Av1 => Ok(Box::new(av1::Av1Encoder::new(config)?)),
+        Codec::Av1 => {
+            log_info!("Using AV1 encoder (rav1e)");
+            Ok(Box::new(av1::Av1Encoder::new(config)?))
+        }
         #[cfg(not(feature = "av1"))]
         Codec::Av1 => Err(crate::Error::CodecUnavailable(
             "AV1 support not compiled in".to_string(),
         )),
         Codec::H264 => h264::create_encoder(config),
+        #[cfg(not(target_arch = "wasm32"))]
+        Codec::Vp9 => vp9::create_encoder(config),
+        #[cfg(target_arch = "wasm32")]
+        Codec::Vp9 => Err(crate::Error::CodecUnavailable(
+            "VP9 needs ffmpeg, which cannot be run on wasm32".to_string(),
+        )),
     }
 }
@@ -0,0 +1,220 @@
+//! Reuse of encoder instances across operations
+
+use super::raw::RawEncoder;
+use super::{create_encoder, Encoder, EncoderConfig};
+use crate::logging::log_debug;
+use crate::{Codec, Container, EncodeOptions, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Idle encoders kept for each codec and configuration by [`EncoderPool::new`]
+const DEFAULT_MAX_IDLE: usize = 4;
+
+/// Encoders are interchangeable when they share a codec and configuration
+type PoolKey = (Codec, EncoderConfig);
+
+/// Warm encoder instances shared by many operations
+///
+/// Starting a Media Foundation or VideoToolbox session has a noticeable cost compared to
+/// encoding a short clip. Set a pool as [`EncodeOptions::encoder_pool`] and encoders are
+/// taken from it when an operation starts and returned once the output is written, keyed
+/// by codec, dimensions, frame rate and quality. Clones share the same encoders, and the
+/// pool may be used from several threads at once.
+///
+/// Encoders that cannot start a new stream (the ffmpeg subprocess on Linux) are created
+/// for every operation as before.
+#[derive(Clone)]
+pub struct EncoderPool(Arc<Mutex<Idle>>);
+
+struct Idle {
+    encoders: HashMap<PoolKey, Vec<Box<dyn Encoder>>>,
+    max_per_key: usize,
+}
+
+impl EncoderPool {
+    /// Create an empty pool keeping up to 4 idle encoders per configuration
+    pub fn new() -> Self {
+        Self::with_max_idle(DEFAULT_MAX_IDLE)
+    }
+
+    /// Create an empty pool keeping up to `max_idle` idle encoders per configuration
+    pub fn with_max_idle(max_idle: usize) -> Self {
+        Self(Arc::new(Mutex::new(Idle {
+            encoders: HashMap::new(),
+            max_per_key: max_idle,
+        })))
+    }
+
+    /// Number of idle encoders in the pool
+    pub fn idle(&self) -> usize {
+        self.lock().encoders.values().map(Vec::len).sum()
+    }
+
+    /// Drop every idle encoder, closing its session
+    pub fn clear(&self) {
+        // Encoders are dropped after the lock is released
+        let encoders = std::mem::take(&mut self.lock().encoders);
+        drop(encoders);
+    }
+
+    /// Take an idle encoder for `codec` and `config`, or create one
+    pub(crate) fn acquire(&self, codec: Codec, config: EncoderConfig) -> Result<PooledEncoder> {
+        let key = (codec, config);
+        let idle = self.lock().encoders.get_mut(&key).and_then(Vec::pop);
+        let encoder = match idle {
+            Some(encoder) => {
+                log_debug!("Reusing pooled {:?} encoder", codec);
+                encoder
+            }
+            None => create_encoder(codec, key.1.clone())?,
+        };
+        Ok(PooledEncoder {
+            encoder: Some(encoder),
+            home: Some((self.clone(), key)),
+        })
+    }
+
+    /// Keep `encoder`, ready for a new stream, unless the pool is full
+    fn release(&self, key: PoolKey, encoder: Box<dyn Encoder>) {
+        let mut idle = self.lock();
+        let max_per_key = idle.max_per_key;
+        let encoders = idle.encoders.entry(key).or_default();
+        if encoders.len() < max_per_key {
+            encoders.push(encoder);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Idle> {
+        // Encoders are only moved in and out under the lock, a panic cannot tear the map
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for EncoderPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for EncoderPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncoderPool")
+            .field("idle", &self.idle())
+            .finish()
+    }
+}
+
+/// Encoder that goes back to its pool, if any, when dropped
+pub(crate) struct PooledEncoder {
+    encoder: Option<Box<dyn Encoder>>,
+    home: Option<(EncoderPool, PoolKey)>,
+}
+
+impl PooledEncoder {
+    /// Open an encoder for `options.codec`, from `options.encoder_pool` if set
+    pub(crate) fn open(options: &EncodeOptions, config: EncoderConfig) -> Result<Self> {
+        // Y4M output takes the frames as they are, whatever the codec
+        if options.container == Container::Y4m {
+            return Ok(Self {
+                encoder: Some(Box::new(RawEncoder::new(config))),
+                home: None,
+            });
+        }
+
+        match options.encoder_pool {
+            Some(ref pool) => pool.acquire(options.codec, config),
+            None => Ok(Self {
+                encoder: Some(create_encoder(options.codec, config)?),
+                home: None,
+            }),
+        }
+    }
+}
+
+impl Deref for PooledEncoder {
+    type Target = dyn Encoder;
+
+    fn deref(&self) -> &Self::Target {
+        self.encoder.as_deref().expect("encoder taken on drop only")
+    }
+}
+
+impl DerefMut for PooledEncoder {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.encoder
+            .as_deref_mut()
+            .expect("encoder taken on drop only")
+    }
+}
+
+impl Drop for PooledEncoder {
+    fn drop(&mut self) {
+        if let (Some(mut encoder), Some((pool, key))) = (self.encoder.take(), self.home.take()) {
+            if encoder.reset() {
+                pool.release(key, encoder);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "av1"))]
+mod tests {
+    use super::*;
+    use crate::encoder::Frame;
+    use crate::{Av1Backend, ColorSpace, PixelRange, Priority, RateControl};
+
+    #[test]
+    fn test_encoder_pool_reuses_encoders() {
+        let pool = EncoderPool::with_max_idle(1);
+        let options = EncodeOptions {
+            encoder_pool: Some(pool.clone()),
+            ..Default::default()
+        };
+        let config = EncoderConfig {
+            width: 16,
+            height: 16,
+            fps: 30,
+            quality: 50,
+            bitrate_kbps: None,
+            rate_control: RateControl::Auto,
+            keyframe_interval: None,
+            color_space: ColorSpace::Bt601,
+            pixel_range: PixelRange::Auto,
+            deterministic: false,
+            priority: Priority::Normal,
+            threads: 0,
+            av1_backend: Av1Backend::Rav1e,
+        };
+        let data = vec![128u8; 16 * 16 * 4];
+        let frame = Frame {
+            width: 16,
+            height: 16,
+            data: &data,
+            pts_ms: 0,
+        };
+
+        for _ in 0..2 {
+            let mut encoder = PooledEncoder::open(&options, config.clone()).unwrap();
+            assert_eq!(pool.idle(), 0);
+            let mut packets = encoder.encode(&frame).unwrap();
+            packets.extend(encoder.flush().unwrap());
+            // A reused encoder starts a new stream
+            assert_eq!(packets.len(), 1);
+            assert_eq!(packets[0].pts, 0);
+            assert!(packets[0].is_keyframe);
+            drop(encoder);
+            assert_eq!(pool.idle(), 1);
+        }
+
+        // Only `max_idle` encoders are kept per configuration
+        let first = PooledEncoder::open(&options, config.clone()).unwrap();
+        let second = PooledEncoder::open(&options, config.clone()).unwrap();
+        drop((first, second));
+        assert_eq!(pool.idle(), 1);
+
+        pool.clear();
+        assert_eq!(pool.idle(), 0);
+    }
+}
@@ -0,0 +1,136 @@
+//! Uncompressed output: frames converted to YUV 4:2:0 and passed on as they are
+
+use super::color::{rgba_to_yuv420, Matrix, PlaneMut};
+use super::{Encoder, EncoderBackend, EncoderConfig, Frame, Packet};
+use crate::priority::thread_pool;
+use crate::Result;
+use rayon::ThreadPool;
+use std::sync::Arc;
+
+/// Converts each RGBA frame to planar YUV 4:2:0 (I420) in its color space, in full range
+/// unless limited range is asked for
+///
+/// Every packet holds one whole frame, so every packet is a keyframe.
+pub struct RawEncoder {
+    config: EncoderConfig,
+    frame_count: u64,
+    /// Threads converting frames, `None` for rayon's global pool
+    pool: Option<Arc<ThreadPool>>,
+}
+
+impl RawEncoder {
+    pub fn new(config: EncoderConfig) -> Self {
+        Self {
+            pool: thread_pool(config.priority, config.threads),
+            config,
+            frame_count: 0,
+        }
+    }
+}
+
+impl Encoder for RawEncoder {
+    fn encode(&mut self, frame: &Frame) -> Result<Vec<Packet>> {
+        frame.check(&self.config)?;
+
+        let (width, height) = (frame.width as usize, frame.height as usize);
+        let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+        let luma_size = width * height;
+        let chroma_size = chroma_width * chroma_height;
+
+        let mut data = vec![0u8; luma_size + 2 * chroma_size];
+        let (y, chroma) = data.split_at_mut(luma_size);
+        let (u, v) = chroma.split_at_mut(chroma_size);
+        let matrix = Matrix::new(
+            self.config.color_space,
+            self.config.pixel_range.is_full(true),
+        );
+        let mut convert = || {
+            rgba_to_yuv420(
+                frame.data,
+                width,
+                height,
+                matrix,
+                PlaneMut {
+                    data: y,
+                    stride: width,
+                },
+                PlaneMut {
+                    data: u,
+                    stride: chroma_width,
+                },
+                PlaneMut {
+                    data: v,
+                    stride: chroma_width,
+                },
+            )
+        };
+        match &self.pool {
+            Some(pool) => pool.install(convert),
+            None => convert(),
+        }
+
+        let pts = self.frame_count as i64;
+        self.frame_count += 1;
+        Ok(vec![Packet {
+            data,
+            pts,
+            dts: pts,
+            is_keyframe: true,
+        }])
+    }
+
+    fn flush(&mut self) -> Result<Vec<Packet>> {
+        Ok(Vec::new())
+    }
+
+    fn backend(&self) -> EncoderBackend {
+        EncoderBackend::Raw
+    }
+
+    fn reset(&mut self) -> bool {
+        self.frame_count = 0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Av1Backend, ColorSpace, PixelRange, Priority, RateControl};
+
+    #[test]
+    fn test_raw_encoder_odd_size() {
+        let config = EncoderConfig {
+            width: 3,
+            height: 3,
+            fps: 30,
+            quality: 50,
+            bitrate_kbps: None,
+            rate_control: RateControl::Auto,
+            keyframe_interval: None,
+            color_space: ColorSpace::Bt601,
+            pixel_range: PixelRange::Auto,
+            deterministic: false,
+            priority: Priority::Normal,
+            threads: 0,
+            av1_backend: Av1Backend::Rav1e,
+        };
+        let mut encoder = RawEncoder::new(config);
+        let white = [255u8; 3 * 3 * 4];
+        let packets = encoder
+            .encode(&Frame {
+                width: 3,
+                height: 3,
+                data: &white,
+                pts_ms: 0,
+            })
+            .unwrap();
+
+        // 3x3 luma and 2x2 chroma planes
+        assert_eq!(packets.len(), 1);
+        let data = &packets[0].data;
+        assert_eq!(data.len(), 9 + 4 + 4);
+        assert!(data[..9].iter().all(|&y| y == 255));
+        assert!(data[9..].iter().all(|&c| c == 128));
+    }
+}
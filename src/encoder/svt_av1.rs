@@ -0,0 +1,244 @@
+//! AV1 encoder using an ffmpeg process with SVT-AV1
+
+use super::ffmpeg_pipe::{check_encoder, color_args, rate_args, FfmpegPipe, IvfFrames};
+use super::{Encoder, EncoderBackend, EncoderConfig, Frame, Packet};
+use crate::ffmpeg::find_ffmpeg;
+use crate::logging::{log_debug, log_info};
+use crate::priority::lower_command;
+use crate::Result;
+use std::process::Command;
+
+/// Frames between keyframes by default, as for rav1e
+const KEYFRAME_INTERVAL: u32 = 240;
+
+/// SVT-AV1 preset: 8 is several times faster than rav1e's speed 6 at similar quality
+const PRESET: u32 = 8;
+
+/// OBU types read from the encoder output
+const OBU_TEMPORAL_DELIMITER: u8 = 2;
+const OBU_FRAME_HEADER: u8 = 3;
+const OBU_FRAME: u8 = 6;
+
+/// AV1 encoder streaming frames through ffmpeg's libsvtav1
+///
+/// ffmpeg writes IVF, where each frame is a temporal unit holding one shown frame and
+/// any hidden frames it depends on, so every input frame gives one packet, in order.
+pub struct SvtAv1Encoder {
+    pipe: FfmpegPipe,
+    config: EncoderConfig,
+    /// Packets returned so far, one per frame
+    packets: u64,
+    output: IvfFrames,
+}
+
+impl SvtAv1Encoder {
+    pub fn new(config: EncoderConfig) -> Result<Self> {
+        let ffmpeg = find_ffmpeg(None)?;
+
+        // Map quality (0-100) to CRF (63-0), the range of rav1e's quantizer
+        let crf = ((100 - config.quality.min(100)) as u32 * 63) / 100;
+
+        log_debug!(
+            "Starting {} for {}x{} SVT-AV1 encoding ({})",
+            ffmpeg,
+            config.width,
+            config.height,
+            rate_args(&config, crf).join(" ")
+        );
+
+        let mut command = Command::new(&ffmpeg);
+        command.args([
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgba",
+            "-s",
+            &format!("{}x{}", config.width, config.height),
+            "-r",
+            &config.fps.to_string(),
+            "-i",
+            "pipe:0",
+            "-c:v",
+            "libsvtav1",
+            "-preset",
+            &PRESET.to_string(),
+            "-g",
+            &config
+                .keyframe_interval
+                .unwrap_or(KEYFRAME_INTERVAL)
+                .to_string(),
+            "-pix_fmt",
+            "yuv420p",
+        ]);
+        command.args(rate_args(&config, crf));
+        command.args(color_args(&config));
+        if config.deterministic {
+            // One logical processor gives the same output on every machine
+            command.args(["-svtav1-params", "lp=1"]);
+        } else if config.threads > 0 {
+            // SVT-AV1 sizes its thread pool by logical processors, not ffmpeg's -threads
+            command.args(["-svtav1-params", &format!("lp={}", config.threads)]);
+        }
+        lower_command(&mut command, config.priority);
+        command.args(["-f", "ivf", "pipe:1"]);
+
+        Ok(Self {
+            pipe: FfmpegPipe::spawn(command, &ffmpeg)?,
+            config,
+            packets: 0,
+            output: IvfFrames::default(),
+        })
+    }
+
+    /// Split the complete temporal units off the output read so far
+    fn take_packets(&mut self) -> Vec<Packet> {
+        self.output
+            .take()
+            .into_iter()
+            .map(|unit| {
+                let pts = self.packets as i64;
+                self.packets += 1;
+                let (data, is_keyframe) = parse_temporal_unit(&unit);
+                Packet {
+                    data,
+                    pts,
+                    dts: pts,
+                    is_keyframe,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Encoder for SvtAv1Encoder {
+    fn encode(&mut self, frame: &Frame) -> Result<Vec<Packet>> {
+        frame.check(&self.config)?;
+
+        self.pipe.send(frame.data, self.output.buffer())?;
+        Ok(self.take_packets())
+    }
+
+    fn flush(&mut self) -> Result<Vec<Packet>> {
+        self.pipe.finish(self.output.buffer())?;
+        Ok(self.take_packets())
+    }
+
+    fn backend(&self) -> EncoderBackend {
+        EncoderBackend::SvtAv1
+    }
+}
+
+/// Drop the temporal delimiter from a temporal unit and check it for a keyframe
+///
+/// Matroska stores temporal units without their delimiter. The unit is a keyframe when
+/// its first frame header is that of a key frame.
+fn parse_temporal_unit(unit: &[u8]) -> (Vec<u8>, bool) {
+    let mut data = Vec::with_capacity(unit.len());
+    let mut is_keyframe = None;
+    let mut pos = 0;
+    while pos < unit.len() {
+        let Some((obu_type, payload, end)) = parse_obu(unit, pos) else {
+            // Keep anything that cannot be parsed as it is
+            data.extend_from_slice(&unit[pos..]);
+            break;
+        };
+        if obu_type != OBU_TEMPORAL_DELIMITER {
+            data.extend_from_slice(&unit[pos..end]);
+        }
+        if is_keyframe.is_none() && matches!(obu_type, OBU_FRAME_HEADER | OBU_FRAME) {
+            is_keyframe = Some(is_key_frame_header(&unit[payload..end]));
+        }
+        pos = end;
+    }
+    (data, is_keyframe.unwrap_or(false))
+}
+
+/// Read the OBU at `pos`: its type, and where its payload starts and it ends
+fn parse_obu(data: &[u8], pos: usize) -> Option<(u8, usize, usize)> {
+    let header = *data.get(pos)?;
+    let obu_type = (header >> 3) & 0x0f;
+    let has_extension = header & 0x04 != 0;
+    let has_size = header & 0x02 != 0;
+
+    let mut payload = pos + 1 + has_extension as usize;
+    let size = if has_size {
+        let (size, len) = read_leb128(data.get(payload..)?)?;
+        payload += len;
+        usize::try_from(size).ok()?
+    } else {
+        // Without a size the OBU runs to the end of the unit
+        data.len().checked_sub(payload)?
+    };
+    let end = payload.checked_add(size)?;
+    (end <= data.len()).then_some((obu_type, payload, end))
+}
+
+/// Decode a LEB128 value, returning it and the number of bytes it takes
+fn read_leb128(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, &byte) in data.iter().take(8).enumerate() {
+        value |= ((byte & 0x7f) as u64) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Check whether an uncompressed frame header starts a key frame
+fn is_key_frame_header(header: &[u8]) -> bool {
+    let Some(&byte) = header.first() else {
+        return false;
+    };
+    // show_existing_frame (1 bit), then frame_type (2 bits), KEY_FRAME being 0
+    let show_existing_frame = byte >> 7;
+    let frame_type = (byte >> 5) & 0x03;
+    show_existing_frame == 0 && frame_type == 0
+}
+
+/// Create an SVT-AV1 encoder
+pub fn create_encoder(config: EncoderConfig) -> Result<Box<dyn Encoder>> {
+    log_info!("Using AV1 encoder (ffmpeg libsvtav1)");
+    Ok(Box::new(SvtAv1Encoder::new(config)?))
+}
+
+/// Check if ffmpeg with SVT-AV1 support is available
+pub fn check_available(ffmpeg_path: Option<&str>) -> Result<()> {
+    let ffmpeg = find_ffmpeg(ffmpeg_path)?;
+    check_encoder(&ffmpeg, "libsvtav1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_temporal_unit() {
+        // Temporal delimiter, sequence header, then a key frame
+        let unit = [
+            0x12, 0x00, // temporal delimiter with size 0
+            0x0a, 0x02, 0xaa, 0xbb, // sequence header
+            0x32, 0x02, 0x10, 0xcc, // frame, show_frame set, KEY_FRAME
+        ];
+        let (data, is_keyframe) = parse_temporal_unit(&unit);
+        assert_eq!(data, unit[2..]);
+        assert!(is_keyframe);
+
+        // Inter frame
+        let (data, is_keyframe) = parse_temporal_unit(&[0x12, 0x00, 0x32, 0x01, 0x30]);
+        assert_eq!(data, [0x32, 0x01, 0x30]);
+        assert!(!is_keyframe);
+
+        // A truncated OBU is kept as it is
+        let (data, is_keyframe) = parse_temporal_unit(&[0x32, 0x05, 0x10]);
+        assert_eq!(data, [0x32, 0x05, 0x10]);
+        assert!(!is_keyframe);
+    }
+
+    #[test]
+    fn test_read_leb128() {
+        assert_eq!(read_leb128(&[0x05]), Some((5, 1)));
+        assert_eq!(read_leb128(&[0x80, 0x01]), Some((128, 2)));
+        assert_eq!(read_leb128(&[0x80]), None);
+    }
+}
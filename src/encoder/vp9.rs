@@ -0,0 +1,178 @@
+//! VP9 encoder using an ffmpeg process with libvpx
+
+use super::ffmpeg_pipe::{
+    check_encoder, color_args, keyframe_args, rate_args, FfmpegPipe, IvfFrames,
+};
+use super::{Encoder, EncoderBackend, EncoderConfig, Frame, Packet};
+use crate::ffmpeg::find_ffmpeg;
+use crate::logging::{log_debug, log_info};
+use crate::priority::lower_command;
+use crate::Result;
+use std::process::Command;
+
+/// Frames between keyframes by default, as for AV1
+const KEYFRAME_INTERVAL: u32 = 240;
+
+/// VP9 encoder streaming frames through ffmpeg's libvpx-vp9
+///
+/// ffmpeg writes IVF, which wraps each frame with its size. Alternate reference frames
+/// and lookahead are off, so every input frame gives one shown frame, in order.
+pub struct Vp9Encoder {
+    pipe: FfmpegPipe,
+    config: EncoderConfig,
+    /// Packets returned so far, one per frame
+    packets: u64,
+    output: IvfFrames,
+}
+
+impl Vp9Encoder {
+    pub fn new(config: EncoderConfig) -> Result<Self> {
+        let ffmpeg = find_ffmpeg(None)?;
+
+        // Map quality (0-100) to CRF (63-0)
+        let crf = ((100 - config.quality.min(100)) as u32 * 63) / 100;
+
+        log_debug!(
+            "Starting {} for {}x{} VP9 encoding ({})",
+            ffmpeg,
+            config.width,
+            config.height,
+            rate_args(&config, crf).join(" ")
+        );
+
+        let mut command = Command::new(&ffmpeg);
+        command.args([
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgba",
+            "-s",
+            &format!("{}x{}", config.width, config.height),
+            "-r",
+            &config.fps.to_string(),
+            "-i",
+            "pipe:0",
+            "-c:v",
+            "libvpx-vp9",
+            "-deadline",
+            "good",
+            "-cpu-used",
+            "4",
+            "-auto-alt-ref",
+            "0",
+            "-lag-in-frames",
+            "0",
+            "-pix_fmt",
+            "yuv420p",
+        ]);
+        command.args(rate_args(&config, crf));
+        command.args(keyframe_args(&config, KEYFRAME_INTERVAL));
+        command.args(color_args(&config));
+        if config.bitrate().is_none() {
+            // Constant quality: libvpx caps the bitrate of CRF otherwise
+            command.args(["-b:v", "0"]);
+        }
+        if config.deterministic {
+            command.args(["-threads", "1", "-row-mt", "0"]);
+        } else {
+            command.args(["-row-mt", "1"]);
+            if config.threads > 0 {
+                command.args(["-threads", &config.threads.to_string()]);
+            }
+        }
+        lower_command(&mut command, config.priority);
+        command.args(["-f", "ivf", "pipe:1"]);
+
+        Ok(Self {
+            pipe: FfmpegPipe::spawn(command, &ffmpeg)?,
+            config,
+            packets: 0,
+            output: IvfFrames::default(),
+        })
+    }
+
+    /// Split the complete IVF frames off the output read so far
+    fn take_packets(&mut self) -> Vec<Packet> {
+        self.output
+            .take()
+            .into_iter()
+            .map(|data| {
+                let pts = self.packets as i64;
+                self.packets += 1;
+                Packet {
+                    is_keyframe: is_keyframe(&data),
+                    data,
+                    pts,
+                    dts: pts,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Encoder for Vp9Encoder {
+    fn encode(&mut self, frame: &Frame) -> Result<Vec<Packet>> {
+        frame.check(&self.config)?;
+
+        self.pipe.send(frame.data, self.output.buffer())?;
+        Ok(self.take_packets())
+    }
+
+    fn flush(&mut self) -> Result<Vec<Packet>> {
+        self.pipe.finish(self.output.buffer())?;
+        Ok(self.take_packets())
+    }
+
+    fn backend(&self) -> EncoderBackend {
+        EncoderBackend::Ffmpeg
+    }
+}
+
+/// Check the uncompressed header of a VP9 frame for a keyframe
+fn is_keyframe(frame: &[u8]) -> bool {
+    let Some(&byte) = frame.first() else {
+        return false;
+    };
+    // frame_marker (2 bits), profile_low_bit, profile_high_bit
+    if byte >> 6 != 2 {
+        return false;
+    }
+    let profile = ((byte >> 5) & 1) | (((byte >> 4) & 1) << 1);
+    // Profile 3 has a reserved bit before show_existing_frame
+    let shift = if profile == 3 { 2 } else { 3 };
+    let show_existing_frame = (byte >> shift) & 1;
+    let frame_type = (byte >> (shift - 1)) & 1;
+    show_existing_frame == 0 && frame_type == 0
+}
+
+/// Create a VP9 encoder
+pub fn create_encoder(config: EncoderConfig) -> Result<Box<dyn Encoder>> {
+    log_info!("Using VP9 encoder (ffmpeg libvpx-vp9)");
+    Ok(Box::new(Vp9Encoder::new(config)?))
+}
+
+/// Check if ffmpeg with VP9 support is available
+pub fn check_available(ffmpeg_path: Option<&str>) -> Result<()> {
+    let ffmpeg = find_ffmpeg(ffmpeg_path)?;
+    check_encoder(&ffmpeg, "libvpx-vp9")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_keyframe() {
+        // Profile 0 keyframe and inter frame
+        assert!(is_keyframe(&[0b1000_0010, 0x49]));
+        assert!(!is_keyframe(&[0b1000_0110, 0x49]));
+        // A shown existing frame is never a keyframe
+        assert!(!is_keyframe(&[0b1000_1000]));
+        // Profile 1 keyframe
+        assert!(is_keyframe(&[0b1010_0000]));
+        // Profile 3 has a reserved bit first
+        assert!(is_keyframe(&[0b1011_0000]));
+        assert!(!is_keyframe(&[0b1011_0010]));
+        assert!(!is_keyframe(&[]));
+    }
+}
@@ -1,6 +1,7 @@
 //! Error types for minmpeg
 
-use crate::{Codec, Container};
+use crate::{Codec, Container, Violation};
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 /// Result type alias for minmpeg operations
@@ -41,17 +42,329 @@ pub enum Error {
     #[error("Muxing error: {0}")]
     Mux(String),
 
-    /// FFmpeg process error
-    #[error("FFmpeg error: {0}")]
-    Ffmpeg(String),
+    /// FFmpeg process error, with the last lines ffmpeg wrote to stderr
+    #[error("FFmpeg error: {message}{}", stderr_suffix(.stderr))]
+    Ffmpeg {
+        message: String,
+        /// Last lines of ffmpeg's stderr, oldest first (empty if not captured)
+        stderr: Vec<String>,
+    },
 
     /// Platform-specific error
     #[error("Platform error: {0}")]
     Platform(String),
+
+    /// A helper process (ffmpeg, ffprobe) could not be started
+    #[error("Failed to start {program}: {source}")]
+    Spawn {
+        program: String,
+        source: std::io::Error,
+    },
+
+    /// Encoder temporarily unavailable, such as a hardware encoder in use by other processes
+    #[error("Encoder busy: {0}")]
+    Busy(String),
+
+    /// Operation is not supported by this build or platform
+    #[error("Unsupported: {0}")]
+    Unsupported(String),
+
+    /// The operation would use more memory than `EncodeOptions::memory_limit`
+    #[error("Memory limit of {limit} bytes exceeded by {what} ({needed} bytes needed)")]
+    MemoryLimit {
+        /// What the memory was needed for, e.g. "slide images"
+        what: String,
+        needed: u64,
+        limit: u64,
+    },
+
+    /// Strict validation found problems with the inputs (see [`crate::StrictLimits`])
+    #[error("Validation failed: {}", join_violations(.0))]
+    Validation(Vec<Violation>),
+
+    /// Operation was cancelled
+    #[error("Operation cancelled")]
+    Cancelled,
+
+    /// Internal error (bug), such as a caught panic
+    #[error("Internal error: {0}")]
+    Internal(String),
+
+    /// Error annotated with where it occurred, e.g. "while encoding frame 412 of slide 3: ..."
+    #[error("{}{error}", context_prefix(.stage, .path, .frame, .slide))]
+    Context {
+        error: Box<Error>,
+        /// Stage of the operation that failed
+        stage: Option<Stage>,
+        /// Input file being processed
+        path: Option<PathBuf>,
+        /// Output frame being produced
+        frame: Option<u64>,
+        /// Index of the slide being processed
+        slide: Option<usize>,
+    },
+}
+
+/// Stage of an operation in which an error occurred
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Stage {
+    /// Loading a slide image
+    Loading,
+    /// Opening or decoding an input video
+    Decoding,
+    /// Producing a frame (resizing, compositing or a render callback)
+    Rendering,
+    /// Encoding a frame
+    Encoding,
+}
+
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Stage::Loading => "loading",
+            Stage::Decoding => "decoding",
+            Stage::Rendering => "rendering",
+            Stage::Encoding => "encoding",
+        })
+    }
+}
+
+/// Location attached to an error; fields already set on the error take precedence
+#[derive(Default)]
+struct Location {
+    stage: Option<Stage>,
+    path: Option<PathBuf>,
+    frame: Option<u64>,
+    slide: Option<usize>,
+}
+
+impl Error {
+    /// FFmpeg error without captured stderr
+    pub(crate) fn ffmpeg(message: impl Into<String>) -> Self {
+        Error::Ffmpeg {
+            message: message.into(),
+            stderr: Vec::new(),
+        }
+    }
+
+    /// Failure to start a helper process
+    pub(crate) fn spawn(program: &str, source: std::io::Error) -> Self {
+        Error::Spawn {
+            program: program.to_string(),
+            source,
+        }
+    }
+
+    /// Attach the input file being processed
+    pub(crate) fn with_path<P: AsRef<Path>>(self, path: P) -> Self {
+        self.with_location(Location {
+            path: Some(path.as_ref().to_path_buf()),
+            ..Default::default()
+        })
+    }
+
+    /// Attach the index of the output frame being produced
+    pub(crate) fn with_frame(self, frame: u64) -> Self {
+        self.with_location(Location {
+            frame: Some(frame),
+            ..Default::default()
+        })
+    }
+
+    /// Attach the index of the slide being processed
+    pub(crate) fn with_slide(self, slide: usize) -> Self {
+        self.with_location(Location {
+            slide: Some(slide),
+            ..Default::default()
+        })
+    }
+
+    /// Attach the stage of the operation that failed
+    pub(crate) fn during(self, stage: Stage) -> Self {
+        self.with_location(Location {
+            stage: Some(stage),
+            ..Default::default()
+        })
+    }
+
+    fn with_location(self, new: Location) -> Self {
+        match self {
+            // Cancellation is not a failure at a location
+            Error::Cancelled => Error::Cancelled,
+            Error::Context {
+                error,
+                stage,
+                path,
+                frame,
+                slide,
+            } => Error::Context {
+                error,
+                stage: stage.or(new.stage),
+                path: path.or(new.path),
+                frame: frame.or(new.frame),
+                slide: slide.or(new.slide),
+            },
+            error => Error::Context {
+                error: Box::new(error),
+                stage: new.stage,
+                path: new.path,
+                frame: new.frame,
+                slide: new.slide,
+            },
+        }
+    }
+
+    /// The error without location context
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::Context { error, .. } => error.inner(),
+            error => error,
+        }
+    }
+
+    /// Check if the operation may succeed when retried unchanged
+    ///
+    /// True for transient conditions: helper processes that could not be started for lack of
+    /// resources, busy hardware encoders and interrupted or timed out I/O.
+    pub fn is_retryable(&self) -> bool {
+        match self.inner() {
+            Error::Busy(_) => true,
+            Error::Spawn { source, .. } | Error::Io(source) => is_transient(source),
+            _ => false,
+        }
+    }
+
+    /// Input file that caused the error, if known
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::Context { path, .. } => path.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Index of the output frame where the error occurred, if known
+    pub fn frame(&self) -> Option<u64> {
+        match self {
+            Error::Context { frame, .. } => *frame,
+            _ => None,
+        }
+    }
+
+    /// Index of the slide being processed when the error occurred, if known
+    pub fn slide(&self) -> Option<usize> {
+        match self {
+            Error::Context { slide, .. } => *slide,
+            _ => None,
+        }
+    }
+
+    /// Stage of the operation in which the error occurred, if known
+    pub fn stage(&self) -> Option<Stage> {
+        match self {
+            Error::Context { stage, .. } => *stage,
+            _ => None,
+        }
+    }
+}
+
+/// Describe where an error occurred, e.g. "while encoding frame 412 of slide 3: "
+fn context_prefix(
+    stage: &Option<Stage>,
+    path: &Option<PathBuf>,
+    frame: &Option<u64>,
+    slide: &Option<usize>,
+) -> String {
+    let mut parts = Vec::new();
+    if let Some(stage) = stage {
+        parts.push(format!("while {}", stage));
+    }
+    if let Some(frame) = frame {
+        parts.push(format!("frame {}", frame));
+    }
+    if let Some(slide) = slide {
+        let of = if frame.is_some() { "of " } else { "" };
+        parts.push(format!("{}slide {}", of, slide));
+    }
+    if let Some(path) = path {
+        if frame.is_some() || slide.is_some() {
+            parts.push(format!("({})", path.display()));
+        } else {
+            parts.push(path.display().to_string());
+        }
+    }
+
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!("{}: ", parts.join(" "))
+    }
+}
+
+/// Serialized as its code, message, retryability and location
+#[cfg(feature = "serde")]
+impl serde::Serialize for Error {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Error", 7)?;
+        state.serialize_field("code", &ErrorCode::from(self))?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("retryable", &self.is_retryable())?;
+        state.serialize_field("stage", &self.stage())?;
+        state.serialize_field("path", &self.path())?;
+        state.serialize_field("frame", &self.frame())?;
+        state.serialize_field("slide", &self.slide())?;
+        state.end()
+    }
+}
+
+/// Check if an I/O error is caused by a temporary lack of resources
+fn is_transient(error: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+
+    if matches!(
+        error.kind(),
+        ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::TimedOut
+    ) {
+        return true;
+    }
+
+    // ENOMEM, EBUSY, ENFILE, EMFILE (same values on Linux and macOS); EAGAIN is WouldBlock
+    #[cfg(unix)]
+    const TRANSIENT_OS_ERRORS: &[i32] = &[12, 16, 23, 24];
+    // ERROR_TOO_MANY_OPEN_FILES, ERROR_NOT_ENOUGH_MEMORY, ERROR_OUTOFMEMORY, ERROR_BUSY
+    #[cfg(windows)]
+    const TRANSIENT_OS_ERRORS: &[i32] = &[4, 8, 14, 170];
+    #[cfg(not(any(unix, windows)))]
+    const TRANSIENT_OS_ERRORS: &[i32] = &[];
+
+    error
+        .raw_os_error()
+        .is_some_and(|code| TRANSIENT_OS_ERRORS.contains(&code))
+}
+
+/// Indented stderr lines appended to the ffmpeg error message
+fn stderr_suffix(stderr: &[String]) -> String {
+    stderr.iter().map(|line| format!("\n  {}", line)).collect()
+}
+
+fn join_violations(violations: &[Violation]) -> String {
+    violations
+        .iter()
+        .map(Violation::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
 }
 
 /// Error code for FFI
+///
+/// Values are stable: codes are only ever appended, never renumbered or reused.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(C)]
 pub enum ErrorCode {
     /// Success
@@ -68,6 +381,18 @@ pub enum ErrorCode {
     EncodeError = 5,
     /// Decoding error
     DecodeError = 6,
+    /// Operation cancelled
+    Cancelled = 7,
+    /// Internal error (panic or bug)
+    Internal = 8,
+    /// Muxing error
+    MuxError = 9,
+    /// FFmpeg process error
+    FfmpegError = 10,
+    /// Platform encoder error
+    PlatformError = 11,
+    /// Operation not supported by this build or platform
+    Unsupported = 12,
 }
 
 impl From<&Error> for ErrorCode {
@@ -80,9 +405,101 @@ impl From<&Error> for ErrorCode {
             Error::Image(_) => ErrorCode::EncodeError,
             Error::Encode(_) => ErrorCode::EncodeError,
             Error::Decode(_) => ErrorCode::DecodeError,
-            Error::Mux(_) => ErrorCode::EncodeError,
-            Error::Ffmpeg(_) => ErrorCode::EncodeError,
-            Error::Platform(_) => ErrorCode::EncodeError,
+            Error::Mux(_) => ErrorCode::MuxError,
+            Error::Ffmpeg { .. } => ErrorCode::FfmpegError,
+            Error::Platform(_) => ErrorCode::PlatformError,
+            Error::Spawn { .. } => ErrorCode::FfmpegError,
+            Error::Busy(_) => ErrorCode::PlatformError,
+            Error::Unsupported(_) => ErrorCode::Unsupported,
+            Error::MemoryLimit { .. } => ErrorCode::InvalidInput,
+            Error::Validation(_) => ErrorCode::InvalidInput,
+            Error::Cancelled => ErrorCode::Cancelled,
+            Error::Internal(_) => ErrorCode::Internal,
+            Error::Context { error, .. } => ErrorCode::from(error.as_ref()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes() {
+        let cases = [
+            (Error::Mux("m".to_string()), ErrorCode::MuxError),
+            (Error::ffmpeg("f"), ErrorCode::FfmpegError),
+            (Error::Platform("p".to_string()), ErrorCode::PlatformError),
+            (Error::Unsupported("u".to_string()), ErrorCode::Unsupported),
+            (
+                Error::Encode("e".to_string()).with_frame(3),
+                ErrorCode::EncodeError,
+            ),
+        ];
+        for (error, code) in cases {
+            assert_eq!(ErrorCode::from(&error), code, "{:?}", error);
+        }
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let busy = Error::Busy("in use".to_string()).with_path("a.mp4");
+        assert!(busy.is_retryable());
+
+        let interrupted = Error::Io(std::io::Error::from(std::io::ErrorKind::Interrupted));
+        assert!(interrupted.is_retryable());
+
+        let not_found = Error::Spawn {
+            program: "ffmpeg".to_string(),
+            source: std::io::Error::from(std::io::ErrorKind::NotFound),
+        };
+        assert!(!not_found.is_retryable());
+        assert!(!Error::InvalidInput("bad".to_string()).is_retryable());
+    }
+
+    #[test]
+    fn test_context_message() {
+        let error = Error::Encode("failed".to_string())
+            .with_frame(412)
+            .with_slide(3)
+            .during(Stage::Encoding)
+            // Outer context does not override what is already known
+            .with_frame(0);
+        assert_eq!(
+            error.to_string(),
+            "while encoding frame 412 of slide 3: Encoding error: failed"
+        );
+        assert_eq!(error.slide(), Some(3));
+        assert_eq!(error.stage(), Some(Stage::Encoding));
+
+        let error = Error::Decode("bad".to_string())
+            .with_path("/path/img.png")
+            .during(Stage::Loading);
+        assert_eq!(
+            error.to_string(),
+            "while loading /path/img.png: Decoding error: bad"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize_error() {
+        let error = Error::Decode("truncated".to_string())
+            .with_path("a.mp4")
+            .with_frame(7)
+            .during(Stage::Decoding);
+
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "code": "DecodeError",
+                "message": "while decoding frame 7 (a.mp4): Decoding error: truncated",
+                "retryable": false,
+                "stage": "Decoding",
+                "path": "a.mp4",
+                "frame": 7,
+                "slide": null,
+            })
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Fades from and to a color at the start and end of a video
+
+use crate::filter::{FilterFrame, FrameFilter};
+use crate::{Color, EncodeOptions, Result};
+use std::borrow::Cow;
+
+/// Options with the fades of `options`, if any, appended to its filters
+///
+/// `last_pts_ms` is the presentation time of the last frame, which is drawn entirely in
+/// the fade color like the first one.
+pub(crate) fn with_fade(options: &EncodeOptions, last_pts_ms: u64) -> Cow<'_, EncodeOptions> {
+    if options.fade_in_ms == 0 && options.fade_out_ms == 0 {
+        return Cow::Borrowed(options);
+    }
+    let fade = Fade {
+        in_ms: options.fade_in_ms,
+        out_ms: options.fade_out_ms,
+        color: options.fade_color,
+        last_pts_ms,
+    };
+    let mut options = options.clone();
+    options.filters = options.filters.with(fade);
+    Cow::Owned(options)
+}
+
+/// Fade drawn as the last filter of an operation
+struct Fade {
+    in_ms: u32,
+    out_ms: u32,
+    color: Color,
+    last_pts_ms: u64,
+}
+
+impl Fade {
+    /// How much of the frame at `pts_ms` shows through the fade color, from 0 to 1
+    fn visibility(&self, pts_ms: u64) -> f32 {
+        let mut visibility: f32 = 1.0;
+        if self.in_ms > 0 {
+            visibility = visibility.min(pts_ms as f32 / self.in_ms as f32);
+        }
+        if self.out_ms > 0 {
+            let remaining = self.last_pts_ms.saturating_sub(pts_ms);
+            visibility = visibility.min(remaining as f32 / self.out_ms as f32);
+        }
+        visibility
+    }
+}
+
+impl FrameFilter for Fade {
+    fn apply(&self, frame: &mut FilterFrame<'_>) -> Result<()> {
+        let visibility = self.visibility(frame.pts_ms);
+        if visibility >= 1.0 {
+            return Ok(());
+        }
+        let color = [self.color.r, self.color.g, self.color.b].map(|c| c as f32);
+        for pixel in frame.data.chunks_exact_mut(4) {
+            for (channel, color) in pixel.iter_mut().zip(color) {
+                *channel =
+                    (*channel as f32 * visibility + color * (1.0 - visibility)).round() as u8;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fade_in_and_out() {
+        let options = EncodeOptions {
+            fade_in_ms: 100,
+            fade_out_ms: 200,
+            fade_color: Color { r: 0, g: 0, b: 255 },
+            ..Default::default()
+        };
+        let faded = with_fade(&options, 1000);
+        let pixel = |pts_ms| {
+            let mut data = [200, 200, 0, 255];
+            faded
+                .filters
+                .apply_to((1, 1), 0, pts_ms, &mut data)
+                .unwrap();
+            data
+        };
+        assert_eq!(pixel(0), [0, 0, 255, 255]);
+        assert_eq!(pixel(50), [100, 100, 128, 255]);
+        assert_eq!(pixel(500), [200, 200, 0, 255]);
+        assert_eq!(pixel(900), [100, 100, 128, 255]);
+        assert_eq!(pixel(1000), [0, 0, 255, 255]);
+
+        assert!(matches!(
+            with_fade(&EncodeOptions::default(), 1000),
+            Cow::Borrowed(_)
+        ));
+    }
+}
@@ -0,0 +1,78 @@
+//! Batches of slideshows over FFI
+
+use super::{
+    catch_panic, encode_options_from_ffi, ffi_stats_out, slide_entries, stats_result,
+    FfiEncodeOptions, FfiResult, FfiSlideEntry,
+};
+use crate::error::ErrorCode;
+use crate::{slideshow_batch, SlideshowJob};
+use libc::size_t;
+use std::ptr;
+use std::slice;
+
+/// One slideshow of a batch
+#[repr(C)]
+pub struct FfiSlideshowJob {
+    pub entries: *const FfiSlideEntry,
+    pub entry_count: size_t,
+    pub options: *const FfiEncodeOptions,
+}
+
+/// Create many slideshows, running up to `concurrency` of them at once
+///
+/// Writes the outcome of each job to `results`, which must each be freed with
+/// `minmpeg_free_result`. Jobs with invalid inputs fail without stopping the others.
+/// Returns an error only if the batch itself is invalid, leaving `results` untouched.
+///
+/// # Safety
+/// - `jobs` must point to `job_count` jobs, each following the rules of
+///   `minmpeg_slideshow_ex`
+/// - `results` must point to `job_count` writable results
+/// - Callbacks in the jobs' options are called on worker threads
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_slideshow_batch(
+    jobs: *const FfiSlideshowJob,
+    job_count: size_t,
+    concurrency: u32,
+    results: *mut FfiResult,
+) -> FfiResult {
+    catch_panic(|| {
+        if job_count == 0 {
+            return FfiResult::ok();
+        }
+        if jobs.is_null() || results.is_null() {
+            return FfiResult::error(ErrorCode::InvalidInput, "Jobs or results are null");
+        }
+
+        let ffi_jobs = slice::from_raw_parts(jobs, job_count);
+        let results = slice::from_raw_parts_mut(results, job_count);
+
+        // Jobs with invalid inputs are reported without running
+        let mut valid = Vec::new();
+        let mut outcomes: Vec<Option<FfiResult>> = Vec::with_capacity(job_count);
+        for job in ffi_jobs {
+            let converted = encode_options_from_ffi(job.options, true).and_then(|options| {
+                let entries = slide_entries(job.entries, job.entry_count)?;
+                Ok(SlideshowJob { entries, options })
+            });
+            match converted {
+                Ok(job) => {
+                    valid.push(job);
+                    outcomes.push(None);
+                }
+                Err(result) => outcomes.push(Some(result)),
+            }
+        }
+
+        let mut batch = slideshow_batch(&valid, concurrency as usize).into_iter();
+        for ((job, outcome), result) in ffi_jobs.iter().zip(outcomes).zip(results) {
+            let outcome = outcome.unwrap_or_else(|| {
+                let stats = batch.next().expect("one result per valid job");
+                stats_result(stats, ffi_stats_out(job.options))
+            });
+            ptr::write(result, outcome);
+        }
+
+        FfiResult::ok()
+    })
+}
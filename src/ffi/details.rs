@@ -0,0 +1,273 @@
+//! Structured error details for FFI results
+//!
+//! Details are recorded when an error result is created and looked up through the
+//! result's message pointer, so `FfiResult` keeps its layout. `minmpeg_free_result`
+//! releases them together with the message.
+
+use super::{catch_panic_or, FfiResult};
+use crate::error::ErrorCode;
+use crate::Error;
+use libc::{c_char, size_t};
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::ptr;
+use std::sync::{Mutex, MutexGuard};
+
+/// Error information captured from an `Error`
+#[derive(Debug, Clone)]
+pub(crate) struct ErrorInfo {
+    code: ErrorCode,
+    message: String,
+    path: Option<String>,
+    frame: Option<u64>,
+    /// Messages of the error and its causes, outermost first
+    chain: Vec<String>,
+    retryable: bool,
+}
+
+impl ErrorInfo {
+    pub(crate) fn new(e: &Error) -> Self {
+        let mut chain = vec![e.to_string()];
+        let mut source = std::error::Error::source(e.inner());
+        while let Some(cause) = source {
+            chain.push(cause.to_string());
+            source = cause.source();
+        }
+
+        Self {
+            code: ErrorCode::from(e),
+            message: e.to_string(),
+            path: e.path().map(|path| path.to_string_lossy().into_owned()),
+            frame: e.frame(),
+            chain,
+            retryable: e.is_retryable(),
+        }
+    }
+
+    /// Take over an error result, releasing its message and details
+    pub(crate) unsafe fn from_result(result: FfiResult) -> Self {
+        if result.message.is_null() {
+            return Self::plain(result.code, String::new());
+        }
+
+        let details = lock().remove(&(result.message as usize));
+        let message = CString::from_raw(result.message)
+            .to_string_lossy()
+            .into_owned();
+
+        match details {
+            Some(details) => Self {
+                code: result.code,
+                message,
+                path: details.path.map(|path| path.to_string_lossy().into_owned()),
+                frame: u64::try_from(details.frame).ok(),
+                chain: details
+                    .chain
+                    .iter()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .collect(),
+                retryable: details.retryable,
+            },
+            None => Self::plain(result.code, message),
+        }
+    }
+
+    fn plain(code: ErrorCode, message: String) -> Self {
+        Self {
+            code,
+            chain: vec![message.clone()],
+            message,
+            path: None,
+            frame: None,
+            retryable: false,
+        }
+    }
+}
+
+/// Details owned by the library until the result is freed
+struct StoredDetails {
+    path: Option<CString>,
+    frame: i64,
+    chain: Vec<CString>,
+    /// Pointers into `chain`, handed out to C
+    chain_ptrs: Vec<*const c_char>,
+    retryable: bool,
+}
+
+// The raw pointers only point into the CStrings owned by the same value
+unsafe impl Send for StoredDetails {}
+
+/// Details of live error results, keyed by message pointer
+static DETAILS: Mutex<BTreeMap<usize, StoredDetails>> = Mutex::new(BTreeMap::new());
+
+fn lock() -> MutexGuard<'static, BTreeMap<usize, StoredDetails>> {
+    match DETAILS.lock() {
+        Ok(details) => details,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+fn to_c_string(s: &str) -> CString {
+    CString::new(s.replace('\0', " ")).unwrap_or_default()
+}
+
+impl FfiResult {
+    /// Create an error result for `e`, recording its details
+    pub(super) fn from_error(e: &Error) -> Self {
+        Self::from_info(&ErrorInfo::new(e))
+    }
+
+    /// Create an error result from captured error information
+    pub(super) fn from_info(info: &ErrorInfo) -> Self {
+        let result = Self::error(info.code, &info.message);
+
+        let chain: Vec<CString> = info.chain.iter().map(|s| to_c_string(s)).collect();
+        let details = StoredDetails {
+            path: info.path.as_deref().map(to_c_string),
+            frame: info.frame.map_or(-1, |frame| frame as i64),
+            chain_ptrs: chain.iter().map(|s| s.as_ptr()).collect(),
+            chain,
+            retryable: info.retryable,
+        };
+        lock().insert(result.message as usize, details);
+
+        result
+    }
+}
+
+/// Release the details recorded for a result message
+pub(super) fn release(message: *const c_char) {
+    lock().remove(&(message as usize));
+}
+
+/// FFI error details structure
+#[repr(C)]
+pub struct FfiErrorDetails {
+    /// Input file that caused the error, or null
+    pub path: *const c_char,
+    /// Index of the output frame where the error occurred, or -1
+    pub frame: i64,
+    /// Messages of the error and its causes, outermost first
+    pub chain: *const *const c_char,
+    pub chain_len: size_t,
+}
+
+/// Get structured details of an error result
+///
+/// Returns 1 and fills `out` if details are available, or 0 and clears `out` otherwise.
+/// The strings in `out` stay valid until `minmpeg_free_result` is called on `result`.
+///
+/// # Safety
+/// - `result` must point to a result returned by a minmpeg function, or be null
+/// - `out` must point to a writable `FfiErrorDetails`
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_result_details(
+    result: *const FfiResult,
+    out: *mut FfiErrorDetails,
+) -> i32 {
+    catch_panic_or(0, || {
+        if out.is_null() {
+            return 0;
+        }
+
+        ptr::write(
+            out,
+            FfiErrorDetails {
+                path: ptr::null(),
+                frame: -1,
+                chain: ptr::null(),
+                chain_len: 0,
+            },
+        );
+
+        if result.is_null() || (*result).message.is_null() {
+            return 0;
+        }
+
+        let details = lock();
+        match details.get(&((*result).message as usize)) {
+            Some(details) => {
+                ptr::write(
+                    out,
+                    FfiErrorDetails {
+                        path: details.path.as_ref().map_or(ptr::null(), |p| p.as_ptr()),
+                        frame: details.frame,
+                        chain: details.chain_ptrs.as_ptr(),
+                        chain_len: details.chain_ptrs.len(),
+                    },
+                );
+                1
+            }
+            None => 0,
+        }
+    })
+}
+
+/// Check if the operation that returned an error result may succeed when retried
+///
+/// Returns 1 for transient failures (helper processes that could not be started for lack
+/// of resources, busy hardware encoders), 0 otherwise or if `result` is not an error.
+///
+/// # Safety
+/// `result` must point to a result returned by a minmpeg function, or be null
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_result_retryable(result: *const FfiResult) -> i32 {
+    catch_panic_or(0, || {
+        if result.is_null() || (*result).message.is_null() {
+            return 0;
+        }
+
+        let details = lock();
+        match details.get(&((*result).message as usize)) {
+            Some(details) if details.retryable => 1,
+            _ => 0,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::minmpeg_free_result;
+    use super::*;
+    use std::ffi::CStr;
+
+    #[test]
+    fn test_result_details() {
+        let e = Error::Io(std::io::Error::other("disk full"))
+            .with_path("slide.png")
+            .with_frame(3);
+        let mut result = FfiResult::from_error(&e);
+        assert_eq!(result.code, ErrorCode::IoError);
+
+        let mut details = std::mem::MaybeUninit::<FfiErrorDetails>::uninit();
+        let details = unsafe {
+            assert_eq!(minmpeg_result_details(&result, details.as_mut_ptr()), 1);
+            details.assume_init()
+        };
+
+        assert_eq!(unsafe { CStr::from_ptr(details.path) }, c"slide.png");
+        assert_eq!(details.frame, 3);
+        let chain = unsafe { std::slice::from_raw_parts(details.chain, details.chain_len) };
+        let chain: Vec<_> = chain
+            .iter()
+            .map(|&s| unsafe { CStr::from_ptr(s) }.to_str().unwrap())
+            .collect();
+        assert_eq!(
+            chain,
+            vec!["frame 3 (slide.png): I/O error: disk full", "disk full"]
+        );
+        assert_eq!(unsafe { minmpeg_result_retryable(&result) }, 0);
+
+        let message = result.message;
+        unsafe { minmpeg_free_result(&mut result) };
+        assert!(!lock().contains_key(&(message as usize)));
+    }
+
+    #[test]
+    fn test_result_retryable() {
+        let mut result = FfiResult::from_error(&Error::Busy("in use".to_string()));
+        assert_eq!(unsafe { minmpeg_result_retryable(&result) }, 1);
+        unsafe { minmpeg_free_result(&mut result) };
+        assert_eq!(unsafe { minmpeg_result_retryable(std::ptr::null()) }, 0);
+    }
+}
@@ -0,0 +1,139 @@
+//! FFI entry points for encoding frames rendered by the host
+
+use super::{
+    buffer_result, catch_panic, encode_options_from_ffi, ffi_stats_out, stats_result, FfiBuffer,
+    FfiEncodeOptions, FfiResult,
+};
+use crate::error::{Error, ErrorCode};
+use crate::frames::{encode_frames_to, render_with};
+use crate::muxer::MemoryOutput;
+use crate::{encode_frames, FrameFormat, Result};
+use libc::{c_void, size_t};
+use std::ptr;
+
+/// FFI frame callback, invoked with (frame_index, rgba, rgba_len, user_data)
+///
+/// The callback fills `rgba` (`width * height * 4` bytes, zeroed) with the frame and
+/// returns 0, or returns non-zero to stop encoding.
+pub type FfiFrameCallback = Option<FrameFn>;
+
+type FrameFn = unsafe extern "C" fn(
+    frame_index: u64,
+    rgba: *mut u8,
+    rgba_len: size_t,
+    user_data: *mut c_void,
+) -> i32;
+
+/// Build a render closure calling the host's frame callback
+///
+/// A non-zero return stops encoding with `Error::Cancelled`.
+fn ffi_render(
+    callback: FrameFn,
+    user_data: *mut c_void,
+) -> impl FnMut(u64, &mut [u8]) -> Result<()> {
+    move |frame_idx, rgba| {
+        let status = unsafe { callback(frame_idx, rgba.as_mut_ptr(), rgba.len(), user_data) };
+        if status == 0 {
+            Ok(())
+        } else {
+            Err(Error::Cancelled)
+        }
+    }
+}
+
+/// Check the frame callback is set
+fn frame_callback(callback: FfiFrameCallback) -> std::result::Result<FrameFn, FfiResult> {
+    callback.ok_or_else(|| FfiResult::error(ErrorCode::InvalidInput, "Frame callback is null"))
+}
+
+/// Encode `frame_count` frames drawn by `render` at `fps`
+///
+/// `render` is called on the calling thread for each frame in order.
+///
+/// # Safety
+/// - `render` must be safe to call with `user_data` for the duration of the call
+/// - `options` must point to an `FfiEncodeOptions` initialized with `minmpeg_encode_options_init`
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_encode_frames(
+    width: u32,
+    height: u32,
+    fps: u32,
+    frame_count: u64,
+    render: FfiFrameCallback,
+    user_data: *mut c_void,
+    options: *const FfiEncodeOptions,
+) -> FfiResult {
+    catch_panic(|| {
+        let stats = ffi_stats_out(options);
+        let options = match encode_options_from_ffi(options, true) {
+            Ok(options) => options,
+            Err(result) => return result,
+        };
+
+        let render = match frame_callback(render) {
+            Ok(render) => render,
+            Err(result) => return result,
+        };
+
+        let format = FrameFormat {
+            width,
+            height,
+            fps,
+            frame_count,
+        };
+
+        let result = encode_frames(format, &options, ffi_render(render, user_data));
+        stats_result(result, stats)
+    })
+}
+
+/// Encode frames drawn by `render` in memory
+///
+/// On success, `out` receives the encoded video, which must be freed with `minmpeg_buffer_free`.
+/// `options.output_path` is ignored and may be null.
+///
+/// # Safety
+/// - Same requirements as `minmpeg_encode_frames`
+/// - `out` must point to a writable `FfiBuffer`
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn minmpeg_encode_frames_to_buffer(
+    width: u32,
+    height: u32,
+    fps: u32,
+    frame_count: u64,
+    render: FfiFrameCallback,
+    user_data: *mut c_void,
+    options: *const FfiEncodeOptions,
+    out: *mut FfiBuffer,
+) -> FfiResult {
+    catch_panic(|| {
+        if out.is_null() {
+            return FfiResult::error(ErrorCode::InvalidInput, "Output buffer pointer is null");
+        }
+        ptr::write(out, FfiBuffer::empty());
+
+        let stats = ffi_stats_out(options);
+        let options = match encode_options_from_ffi(options, false) {
+            Ok(options) => options,
+            Err(result) => return result,
+        };
+
+        let render = match frame_callback(render) {
+            Ok(render) => render,
+            Err(result) => return result,
+        };
+
+        let format = FrameFormat {
+            width,
+            height,
+            fps,
+            frame_count,
+        };
+
+        let output = MemoryOutput::new();
+        let render = render_with(ffi_render(render, user_data));
+        let result = encode_frames_to(format, &options, Some(&output), render);
+        buffer_result(result, &output, out, stats)
+    })
+}
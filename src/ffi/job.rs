@@ -0,0 +1,318 @@
+//! Asynchronous jobs over FFI
+//!
+//! A job runs an operation on an internal thread so hosts with single-threaded runtimes
+//! can poll for completion instead of blocking inside a C call. Jobs do not fill
+//! `options.stats` or `options.quality_report`.
+
+use super::details::ErrorInfo;
+use super::{
+    catch_panic, catch_panic_or, encode_options_from_ffi, juxtapose_inputs, panic_message,
+    slide_entries, FfiColor, FfiEncodeOptions, FfiResult, FfiSlideEntry,
+};
+use crate::error::ErrorCode;
+use crate::priority::lower_current_thread;
+use crate::{juxtapose, slideshow, CancelToken, EncodeOptions, EncodeStats, Error, Result};
+use libc::{c_char, size_t};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+/// Outcome of a finished job: `None` on success, or the error
+type Outcome = Option<ErrorInfo>;
+
+/// Completion state shared between a job handle and its worker thread
+#[derive(Default)]
+struct JobState {
+    outcome: Mutex<Option<Outcome>>,
+    done: Condvar,
+}
+
+impl JobState {
+    fn finish(&self, outcome: Outcome) {
+        let mut slot = match self.outcome.lock() {
+            Ok(slot) => slot,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *slot = Some(outcome);
+        self.done.notify_all();
+    }
+
+    fn is_done(&self) -> bool {
+        match self.outcome.lock() {
+            Ok(slot) => slot.is_some(),
+            Err(poisoned) => poisoned.into_inner().is_some(),
+        }
+    }
+
+    fn wait(&self) {
+        let mut slot = match self.outcome.lock() {
+            Ok(slot) => slot,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        while slot.is_none() {
+            slot = match self.done.wait(slot) {
+                Ok(slot) => slot,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+        }
+    }
+
+    fn result(&self) -> FfiResult {
+        let slot = match self.outcome.lock() {
+            Ok(slot) => slot,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        match *slot {
+            None => FfiResult::error(ErrorCode::InvalidInput, "Job is still running"),
+            Some(None) => FfiResult::ok(),
+            Some(Some(ref info)) => FfiResult::from_info(info),
+        }
+    }
+}
+
+/// Opaque asynchronous job handle
+pub struct FfiJob {
+    state: Arc<JobState>,
+    cancel: CancelToken,
+}
+
+impl FfiJob {
+    /// Run `task` on a new thread, cancelling through the token stored in `options`
+    fn spawn<F>(mut options: EncodeOptions, task: F) -> Self
+    where
+        F: FnOnce(&EncodeOptions) -> Result<EncodeStats> + Send + 'static,
+    {
+        let cancel = options.cancel.get_or_insert_with(CancelToken::new).clone();
+        let state = Arc::new(JobState::default());
+        let worker_state = Arc::clone(&state);
+
+        let spawned = thread::Builder::new()
+            .name("minmpeg-job".to_string())
+            .spawn(move || {
+                lower_current_thread(options.priority);
+                let result = panic::catch_unwind(AssertUnwindSafe(|| task(&options)))
+                    .unwrap_or_else(|payload| {
+                        Err(Error::Internal(format!(
+                            "panic: {}",
+                            panic_message(payload.as_ref())
+                        )))
+                    });
+                worker_state.finish(result.err().as_ref().map(ErrorInfo::new));
+            });
+
+        if let Err(e) = spawned {
+            state.finish(Some(ErrorInfo::new(&Error::Io(e))));
+        }
+
+        Self { state, cancel }
+    }
+
+    /// Create a job that has already failed with `result`
+    fn failed(result: FfiResult) -> Self {
+        let state = Arc::new(JobState::default());
+        state.finish(Some(unsafe { ErrorInfo::from_result(result) }));
+
+        Self {
+            state,
+            cancel: CancelToken::new(),
+        }
+    }
+
+    fn into_raw(self) -> *mut FfiJob {
+        Box::into_raw(Box::new(self))
+    }
+}
+
+/// Start a slideshow on a background thread
+///
+/// All inputs are copied before returning. Input errors are reported through
+/// `minmpeg_job_result` like any other failure.
+///
+/// # Safety
+/// - Same requirements as `minmpeg_slideshow_ex`
+/// - The progress callback in `options`, if any, is called on the job's thread
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_submit_slideshow(
+    entries: *const FfiSlideEntry,
+    entry_count: size_t,
+    options: *const FfiEncodeOptions,
+) -> *mut FfiJob {
+    catch_panic_or(ptr::null_mut(), || {
+        let options = match encode_options_from_ffi(options, true) {
+            Ok(options) => options,
+            Err(result) => return FfiJob::failed(result).into_raw(),
+        };
+
+        let entries = match slide_entries(entries, entry_count) {
+            Ok(entries) => entries,
+            Err(result) => return FfiJob::failed(result).into_raw(),
+        };
+
+        FfiJob::spawn(options, move |options| slideshow(&entries, options)).into_raw()
+    })
+}
+
+/// Start a side-by-side juxtaposition on a background thread
+///
+/// All inputs are copied before returning. Input errors are reported through
+/// `minmpeg_job_result` like any other failure.
+///
+/// # Safety
+/// - Same requirements as `minmpeg_juxtapose_ex`
+/// - The progress callback in `options`, if any, is called on the job's thread
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_submit_juxtapose(
+    left_path: *const c_char,
+    right_path: *const c_char,
+    background: *const FfiColor,
+    options: *const FfiEncodeOptions,
+) -> *mut FfiJob {
+    catch_panic_or(ptr::null_mut(), || {
+        let options = match encode_options_from_ffi(options, true) {
+            Ok(options) => options,
+            Err(result) => return FfiJob::failed(result).into_raw(),
+        };
+
+        let (left_path, right_path, bg_color) =
+            match juxtapose_inputs(left_path, right_path, background) {
+                Ok((left, right, bg)) => (left.to_string(), right.to_string(), bg),
+                Err(result) => return FfiJob::failed(result).into_raw(),
+            };
+
+        FfiJob::spawn(options, move |options| {
+            juxtapose(&left_path, &right_path, options, bg_color)
+        })
+        .into_raw()
+    })
+}
+
+/// Check whether a job has finished
+///
+/// Returns 1 if the job has finished (successfully or not), 0 if it is still running.
+///
+/// # Safety
+/// - `job` must be a job returned by a `minmpeg_submit_*` function, or null
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_job_poll(job: *const FfiJob) -> i32 {
+    catch_panic_or(1, || {
+        if job.is_null() {
+            return 1;
+        }
+
+        (*job).state.is_done() as i32
+    })
+}
+
+/// Block until a job has finished
+///
+/// # Safety
+/// - `job` must be a job returned by a `minmpeg_submit_*` function, or null
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_job_wait(job: *const FfiJob) {
+    catch_panic_or((), || {
+        if job.is_null() {
+            return;
+        }
+
+        (*job).state.wait();
+    })
+}
+
+/// Get the result of a finished job
+///
+/// Returns `MINMPEG_ERR_INVALID_INPUT` if the job is still running. The returned
+/// result must be freed with `minmpeg_free_result`.
+///
+/// # Safety
+/// - `job` must be a job returned by a `minmpeg_submit_*` function
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_job_result(job: *const FfiJob) -> FfiResult {
+    catch_panic(|| {
+        if job.is_null() {
+            return FfiResult::error(ErrorCode::InvalidInput, "Job is null");
+        }
+
+        (*job).state.result()
+    })
+}
+
+/// Request cancellation of a running job
+///
+/// The job finishes with `MINMPEG_ERR_CANCELLED` at the next frame. If a cancellation
+/// token was passed in the options, it is cancelled as well.
+///
+/// # Safety
+/// - `job` must be a job returned by a `minmpeg_submit_*` function, or null
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_job_cancel(job: *const FfiJob) {
+    catch_panic_or((), || {
+        if job.is_null() {
+            return;
+        }
+
+        (*job).cancel.cancel();
+    })
+}
+
+/// Free a job handle
+///
+/// A job that is still running is cancelled and finishes in the background.
+///
+/// # Safety
+/// - `job` must be a job returned by a `minmpeg_submit_*` function, or null
+/// - `job` must not be used after this call
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_job_free(job: *mut FfiJob) {
+    catch_panic_or((), || {
+        if job.is_null() {
+            return;
+        }
+
+        let job = Box::from_raw(job);
+        if !job.state.is_done() {
+            job.cancel.cancel();
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::minmpeg_free_result;
+    use super::*;
+
+    #[test]
+    fn test_failed_job_reports_input_error() {
+        let job = unsafe { minmpeg_submit_slideshow(ptr::null(), 0, ptr::null()) };
+        assert!(!job.is_null());
+
+        unsafe {
+            assert_eq!(minmpeg_job_poll(job), 1);
+            minmpeg_job_wait(job);
+
+            let mut result = minmpeg_job_result(job);
+            assert_eq!(result.code, ErrorCode::InvalidInput);
+            minmpeg_free_result(&mut result);
+            minmpeg_job_free(job);
+        }
+    }
+
+    #[test]
+    fn test_spawned_job_completes() {
+        let job = FfiJob::spawn(EncodeOptions::default(), |options| {
+            options.check_cancelled()?;
+            Err(Error::Encode("expected".to_string()))
+        })
+        .into_raw();
+
+        unsafe {
+            minmpeg_job_wait(job);
+            assert_eq!(minmpeg_job_poll(job), 1);
+
+            let mut result = minmpeg_job_result(job);
+            assert_eq!(result.code, ErrorCode::EncodeError);
+            minmpeg_free_result(&mut result);
+            minmpeg_job_free(job);
+        }
+    }
+}
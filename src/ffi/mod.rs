@@ -0,0 +1,2100 @@
+//! FFI (Foreign Function Interface) for C/Go interoperability
+
+pub mod batch;
+pub mod details;
+pub mod frames;
+pub mod job;
+pub mod ops;
+pub mod wide;
+
+use crate::error::{Error, ErrorCode};
+use crate::juxtapose::juxtapose_to;
+use crate::muxer::MemoryOutput;
+use crate::report::WarningCode;
+use crate::slideshow::slideshow_to;
+use crate::{
+    available, capabilities, juxtapose, juxtapose_n, probe, set_log_callback, slideshow,
+    Av1Backend, CancelToken, Codec, Color, ColorSpace, Container, EncodeOptions, EncodeStats,
+    EncoderBackend, EncoderPool, ImageCache, Layout, LogCallback, LogLevel, Phase, PixelRange,
+    Priority, ProgressCallback, QualityReport, RateControl, ScaleMode, SlideEntry, SlideSource,
+    StrictLimits, WarningCallback, Watermark, WatermarkPosition,
+};
+use libc::{c_char, c_void, size_t};
+use std::any::Any;
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+/// FFI result structure
+#[repr(C)]
+pub struct FfiResult {
+    pub code: ErrorCode,
+    pub message: *mut c_char,
+}
+
+impl FfiResult {
+    fn ok() -> Self {
+        Self {
+            code: ErrorCode::Ok,
+            message: ptr::null_mut(),
+        }
+    }
+
+    fn error(code: ErrorCode, message: &str) -> Self {
+        let c_message =
+            CString::new(message).unwrap_or_else(|_| CString::new("Unknown error").unwrap());
+        Self {
+            code,
+            message: c_message.into_raw(),
+        }
+    }
+}
+
+/// Run an FFI entry point body, converting a panic into an `Internal` error result
+///
+/// Unwinding across the C ABI would abort the host process.
+fn catch_panic(f: impl FnOnce() -> FfiResult) -> FfiResult {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let e = Error::Internal(format!("panic: {}", panic_message(payload.as_ref())));
+        FfiResult::from_error(&e)
+    })
+}
+
+/// Run an FFI entry point body, returning `fallback` if it panics
+fn catch_panic_or<T>(fallback: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(fallback)
+}
+
+/// Extract the message from a panic payload
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s
+    } else {
+        "unknown panic"
+    }
+}
+
+/// FFI slide entry structure
+#[repr(C)]
+pub struct FfiSlideEntry {
+    pub path: *const c_char,
+    pub duration_ms: u32,
+}
+
+/// Kind of image data carried by an `FfiSlideData`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub enum FfiSlideDataKind {
+    /// `path` points to an image file
+    Path = 0,
+    /// `data`/`data_len` hold an encoded image (JPEG, PNG, WebP, GIF)
+    Encoded = 1,
+    /// `data`/`data_len` hold raw RGBA pixels of `width` x `height`
+    Rgba = 2,
+}
+
+/// FFI slide entry supplied as a path, encoded bytes, or raw RGBA pixels
+#[repr(C)]
+pub struct FfiSlideData {
+    pub kind: FfiSlideDataKind,
+    pub path: *const c_char,
+    pub data: *const u8,
+    pub data_len: size_t,
+    pub width: u32,
+    pub height: u32,
+    pub duration_ms: u32,
+}
+
+/// FFI color structure
+#[repr(C)]
+pub struct FfiColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+/// FFI grid layout for `minmpeg_juxtapose_n`
+#[repr(C)]
+pub struct FfiLayout {
+    /// Number of columns (0 places all videos in a single row)
+    pub columns: u32,
+}
+
+/// FFI video information structure
+///
+/// `struct_size` must be set to `sizeof(VideoInfo)` by the caller. New fields are only ever
+/// appended, and only the fields within `struct_size` are written, so callers built against
+/// older headers keep working.
+#[repr(C)]
+pub struct FfiVideoInfo {
+    pub struct_size: u32,
+    pub width: u32,
+    pub height: u32,
+    pub fps: f64,
+    pub duration_ms: u64,
+    pub frame_count: u64,
+    /// Null-terminated codec name (e.g. "av1", "h264"), truncated to fit
+    pub codec: [c_char; 32],
+    /// Null-terminated container format names (e.g. "matroska,webm"), truncated to fit
+    pub container: [c_char; 32],
+}
+
+/// Size of the first version of `FfiVideoInfo`
+const FFI_VIDEO_INFO_V1_SIZE: usize =
+    mem::offset_of!(FfiVideoInfo, container) + mem::size_of::<[c_char; 32]>();
+
+/// Sizes of the versions of `FfiVideoInfo`, each ending at its last field
+const FFI_VIDEO_INFO_VERSIONS: [usize; 1] = [FFI_VIDEO_INFO_V1_SIZE];
+
+/// FFI byte buffer owned by the library
+///
+/// Must be freed with `minmpeg_buffer_free`.
+#[repr(C)]
+pub struct FfiBuffer {
+    pub data: *mut u8,
+    pub len: size_t,
+}
+
+impl FfiBuffer {
+    fn empty() -> Self {
+        Self {
+            data: ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        Self { data, len }
+    }
+}
+
+/// FFI capability entry
+#[repr(C)]
+pub struct FfiCapability {
+    pub codec: Codec,
+    pub container: Container,
+    pub backend: EncoderBackend,
+    /// Static null-terminated backend name
+    pub backend_name: *const c_char,
+    /// Non-zero if the backend uses hardware acceleration
+    pub hardware: u8,
+}
+
+/// FFI array of capabilities owned by the library
+///
+/// Must be freed with `minmpeg_capabilities_free`.
+#[repr(C)]
+pub struct FfiCapabilities {
+    pub items: *mut FfiCapability,
+    pub len: size_t,
+}
+
+/// Null-terminated backend name with static lifetime
+fn backend_name(backend: EncoderBackend) -> *const c_char {
+    let name = match backend {
+        EncoderBackend::Rav1e => c"rav1e",
+        EncoderBackend::VideoToolbox => c"VideoToolbox",
+        EncoderBackend::MediaFoundation => c"Media Foundation",
+        EncoderBackend::Ffmpeg => c"ffmpeg",
+        EncoderBackend::SvtAv1 => c"SVT-AV1",
+        EncoderBackend::Image => c"image",
+        EncoderBackend::Raw => c"raw",
+        EncoderBackend::Nvenc => c"NVENC",
+        EncoderBackend::Vaapi => c"VAAPI",
+    };
+    name.as_ptr()
+}
+
+/// Copy a string into a fixed-size C buffer, truncating and null-terminating it
+fn copy_to_c_buf(s: &str, buf: &mut [c_char]) {
+    let len = s.len().min(buf.len().saturating_sub(1));
+    for (dst, &src) in buf.iter_mut().zip(&s.as_bytes()[..len]) {
+        *dst = src as c_char;
+    }
+    if let Some(end) = buf.get_mut(len) {
+        *end = 0;
+    }
+}
+
+/// FFI progress callback, invoked with (frames_done, frames_total, user_data)
+pub type FfiProgressCallback =
+    Option<unsafe extern "C" fn(frames_done: u64, frames_total: u64, user_data: *mut c_void)>;
+
+/// User data pointer handed back to the host's progress callback
+struct FfiUserData(*mut c_void);
+
+// The pointer is only passed back to the host, never dereferenced on the Rust side
+unsafe impl Send for FfiUserData {}
+unsafe impl Sync for FfiUserData {}
+
+impl FfiUserData {
+    fn as_ptr(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+/// Wrap an FFI progress callback into a `ProgressCallback`
+fn ffi_progress_callback(
+    callback: FfiProgressCallback,
+    user_data: *mut c_void,
+) -> Option<ProgressCallback> {
+    let callback = callback?;
+    let user_data = FfiUserData(user_data);
+
+    Some(ProgressCallback::new(move |progress| {
+        // The C callback only counts frames
+        if progress.phase == Phase::Encoding {
+            unsafe {
+                callback(
+                    progress.frames_done,
+                    progress.frames_total,
+                    user_data.as_ptr(),
+                )
+            }
+        }
+    }))
+}
+
+/// FFI warning callback, invoked with (code, message, user_data)
+///
+/// `message` is only valid for the duration of the call.
+pub type FfiWarningCallback =
+    Option<unsafe extern "C" fn(code: WarningCode, message: *const c_char, user_data: *mut c_void)>;
+
+/// Wrap an FFI warning callback into a `WarningCallback`
+fn ffi_warning_callback(
+    callback: FfiWarningCallback,
+    user_data: *mut c_void,
+) -> Option<WarningCallback> {
+    let callback = callback?;
+    let user_data = FfiUserData(user_data);
+
+    Some(WarningCallback::new(move |warning| {
+        let message = CString::new(warning.to_string()).unwrap_or_default();
+        unsafe {
+            callback(
+                WarningCode::from(warning),
+                message.as_ptr(),
+                user_data.as_ptr(),
+            )
+        }
+    }))
+}
+
+/// FFI log callback, invoked with (level, message, user_data)
+///
+/// `message` is only valid for the duration of the call.
+pub type FfiLogCallback =
+    Option<unsafe extern "C" fn(level: LogLevel, message: *const c_char, user_data: *mut c_void)>;
+
+/// Register a process-wide log callback
+///
+/// Messages at `level` or more severe are delivered. Pass a null callback to disable logging.
+///
+/// # Safety
+/// - `callback` may be called from any thread running a minmpeg operation
+/// - `user_data` is passed through to `callback` untouched and must stay valid until replaced
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_set_log_callback(
+    callback: FfiLogCallback,
+    user_data: *mut c_void,
+    level: LogLevel,
+) {
+    catch_panic_or((), || {
+        let callback = callback.map(|callback| {
+            let user_data = FfiUserData(user_data);
+            LogCallback::new(move |level, message| {
+                let message = CString::new(message.replace('\0', ""))
+                    .unwrap_or_else(|_| CString::new("Invalid log message").unwrap());
+                unsafe { callback(level, message.as_ptr(), user_data.as_ptr()) }
+            })
+        });
+
+        set_log_callback(callback, level);
+    })
+}
+
+/// Opaque cancellation token handle
+pub struct FfiCancelToken(CancelToken);
+
+/// Clone the token behind an FFI handle
+unsafe fn ffi_cancel_token(token: *const FfiCancelToken) -> Option<CancelToken> {
+    if token.is_null() {
+        None
+    } else {
+        Some((*token).0.clone())
+    }
+}
+
+/// Create a new cancellation token
+///
+/// The token must be freed with `minmpeg_cancel_token_free`.
+#[no_mangle]
+pub extern "C" fn minmpeg_cancel_token_new() -> *mut FfiCancelToken {
+    catch_panic_or(ptr::null_mut(), || {
+        Box::into_raw(Box::new(FfiCancelToken(CancelToken::new())))
+    })
+}
+
+/// Request cancellation of every operation using this token
+///
+/// # Safety
+/// - `token` must be a token returned by `minmpeg_cancel_token_new`, or null
+/// - May be called from any thread
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_cancel_token_cancel(token: *const FfiCancelToken) {
+    catch_panic_or((), || {
+        if token.is_null() {
+            return;
+        }
+
+        (*token).0.cancel();
+    })
+}
+
+/// Free a cancellation token
+///
+/// Operations still running with this token keep their own reference and are unaffected.
+///
+/// # Safety
+/// - `token` must be a token returned by `minmpeg_cancel_token_new`, or null
+/// - `token` must not be used after this call
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_cancel_token_free(token: *mut FfiCancelToken) {
+    catch_panic_or((), || {
+        if token.is_null() {
+            return;
+        }
+
+        drop(Box::from_raw(token));
+    })
+}
+
+/// Opaque encoder pool handle
+pub struct FfiEncoderPool(EncoderPool);
+
+/// Clone the pool behind an FFI handle
+unsafe fn ffi_encoder_pool(pool: *const FfiEncoderPool) -> Option<EncoderPool> {
+    if pool.is_null() {
+        None
+    } else {
+        Some((*pool).0.clone())
+    }
+}
+
+/// Create a pool of reusable encoders
+///
+/// `max_idle` is the most idle encoders kept per codec and configuration (0 for the
+/// default of 4). The pool must be freed with `minmpeg_encoder_pool_free`.
+#[no_mangle]
+pub extern "C" fn minmpeg_encoder_pool_new(max_idle: u32) -> *mut FfiEncoderPool {
+    catch_panic_or(ptr::null_mut(), || {
+        let pool = if max_idle == 0 {
+            EncoderPool::new()
+        } else {
+            EncoderPool::with_max_idle(max_idle as usize)
+        };
+        Box::into_raw(Box::new(FfiEncoderPool(pool)))
+    })
+}
+
+/// Free an encoder pool, closing its idle encoders
+///
+/// Operations still running with this pool keep their own reference and are unaffected.
+///
+/// # Safety
+/// - `pool` must be a pool returned by `minmpeg_encoder_pool_new`, or null
+/// - `pool` must not be used after this call
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_encoder_pool_free(pool: *mut FfiEncoderPool) {
+    catch_panic_or((), || {
+        if pool.is_null() {
+            return;
+        }
+
+        let pool = Box::from_raw(pool);
+        pool.0.clear();
+    })
+}
+
+/// Opaque image cache handle
+pub struct FfiImageCache(ImageCache);
+
+/// Clone the cache behind an FFI handle
+unsafe fn ffi_image_cache(cache: *const FfiImageCache) -> Option<ImageCache> {
+    if cache.is_null() {
+        None
+    } else {
+        Some((*cache).0.clone())
+    }
+}
+
+/// Create a cache of decoded and resized slide images
+///
+/// The cache must be freed with `minmpeg_image_cache_free`.
+#[no_mangle]
+pub extern "C" fn minmpeg_image_cache_new() -> *mut FfiImageCache {
+    catch_panic_or(ptr::null_mut(), || {
+        Box::into_raw(Box::new(FfiImageCache(ImageCache::new())))
+    })
+}
+
+/// Free an image cache, dropping its images
+///
+/// Operations still running with this cache keep their own reference and are unaffected.
+///
+/// # Safety
+/// - `cache` must be a cache returned by `minmpeg_image_cache_new`, or null
+/// - `cache` must not be used after this call
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_image_cache_free(cache: *mut FfiImageCache) {
+    catch_panic_or((), || {
+        if cache.is_null() {
+            return;
+        }
+
+        let cache = Box::from_raw(cache);
+        cache.0.clear();
+    })
+}
+
+/// FFI encode options structure
+///
+/// `struct_size` must be set to `sizeof(EncodeOptions)` by the caller (done by
+/// `minmpeg_encode_options_init`). New fields are only ever appended, and fields beyond
+/// `struct_size` are treated as unset, so callers built against older headers keep working.
+#[repr(C)]
+pub struct FfiEncodeOptions {
+    pub struct_size: u32,
+    pub output_path: *const c_char,
+    pub container: Container,
+    pub codec: Codec,
+    pub quality: u8,
+    pub ffmpeg_path: *const c_char,
+    pub progress: FfiProgressCallback,
+    pub user_data: *mut c_void,
+    pub cancel: *const FfiCancelToken,
+    pub warning: FfiWarningCallback,
+    pub stats: *mut FfiEncodeStats,
+    pub strict: i32,
+    pub max_duration_ms: u64,
+    pub memory_limit: u64,
+    pub encoder_pool: *const FfiEncoderPool,
+    pub image_cache: *const FfiImageCache,
+    pub deterministic: i32,
+    pub quality_report: *mut FfiQualityReport,
+    pub spill_dir: *const c_char,
+    pub priority: Priority,
+    pub av1_backend: Av1Backend,
+    pub scale_mode: ScaleMode,
+    pub letterbox_color: Color,
+    pub watermark_path: *const c_char,
+    pub watermark_position: WatermarkPosition,
+    pub watermark_opacity: f32,
+    pub watermark_margin: u32,
+    pub fade_in_ms: u32,
+    pub fade_out_ms: u32,
+    pub fade_color: Color,
+    pub bitrate_kbps: u32,
+    pub rate_control: RateControl,
+    pub keyframe_interval_ms: u32,
+    pub color_space: ColorSpace,
+    pub pixel_range: PixelRange,
+    pub threads: u32,
+}
+
+/// Size of the first version of `FfiEncodeOptions`
+const FFI_ENCODE_OPTIONS_V1_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, cancel) + mem::size_of::<*const FfiCancelToken>();
+
+/// Size of `FfiEncodeOptions` with the warning callback
+const FFI_ENCODE_OPTIONS_V2_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, warning) + mem::size_of::<FfiWarningCallback>();
+
+/// Size of `FfiEncodeOptions` with the statistics output
+const FFI_ENCODE_OPTIONS_V3_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, stats) + mem::size_of::<*mut FfiEncodeStats>();
+
+/// Size of `FfiEncodeOptions` with strict validation
+const FFI_ENCODE_OPTIONS_V4_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, max_duration_ms) + mem::size_of::<u64>();
+
+/// Size of `FfiEncodeOptions` with the memory limit
+const FFI_ENCODE_OPTIONS_V5_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, memory_limit) + mem::size_of::<u64>();
+
+/// Size of `FfiEncodeOptions` with the encoder pool
+const FFI_ENCODE_OPTIONS_V6_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, encoder_pool) + mem::size_of::<*const FfiEncoderPool>();
+
+/// Size of `FfiEncodeOptions` with the image cache
+const FFI_ENCODE_OPTIONS_V7_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, image_cache) + mem::size_of::<*const FfiImageCache>();
+
+/// Size of `FfiEncodeOptions` with deterministic output
+const FFI_ENCODE_OPTIONS_V8_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, deterministic) + mem::size_of::<i32>();
+
+/// Size of `FfiEncodeOptions` with output verification
+const FFI_ENCODE_OPTIONS_V9_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, quality_report) + mem::size_of::<*mut FfiQualityReport>();
+
+/// Size of `FfiEncodeOptions` with the spill directory
+const FFI_ENCODE_OPTIONS_V10_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, spill_dir) + mem::size_of::<*const c_char>();
+
+/// Size of `FfiEncodeOptions` with the process priority
+const FFI_ENCODE_OPTIONS_V11_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, priority) + mem::size_of::<Priority>();
+
+/// Size of `FfiEncodeOptions` with the AV1 encoder
+const FFI_ENCODE_OPTIONS_V12_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, av1_backend) + mem::size_of::<Av1Backend>();
+
+/// Size of `FfiEncodeOptions` with the scale mode
+const FFI_ENCODE_OPTIONS_V13_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, scale_mode) + mem::size_of::<ScaleMode>();
+
+/// Size of `FfiEncodeOptions` with the letterbox color
+const FFI_ENCODE_OPTIONS_V14_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, letterbox_color) + mem::size_of::<Color>();
+
+/// Size of `FfiEncodeOptions` with the watermark
+const FFI_ENCODE_OPTIONS_V15_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, watermark_margin) + mem::size_of::<u32>();
+
+/// Size of `FfiEncodeOptions` with the fades
+const FFI_ENCODE_OPTIONS_V16_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, fade_color) + mem::size_of::<Color>();
+
+/// Size of `FfiEncodeOptions` with the bitrate
+const FFI_ENCODE_OPTIONS_V17_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, bitrate_kbps) + mem::size_of::<u32>();
+
+/// Size of `FfiEncodeOptions` with the rate control
+const FFI_ENCODE_OPTIONS_V18_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, rate_control) + mem::size_of::<RateControl>();
+
+/// Size of `FfiEncodeOptions` with the keyframe interval
+const FFI_ENCODE_OPTIONS_V19_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, keyframe_interval_ms) + mem::size_of::<u32>();
+
+/// Size of `FfiEncodeOptions` with the color space
+const FFI_ENCODE_OPTIONS_V20_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, color_space) + mem::size_of::<ColorSpace>();
+
+/// Size of `FfiEncodeOptions` with the pixel range
+const FFI_ENCODE_OPTIONS_V21_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, pixel_range) + mem::size_of::<PixelRange>();
+
+/// Size of `FfiEncodeOptions` with the thread count
+const FFI_ENCODE_OPTIONS_V22_SIZE: usize =
+    mem::offset_of!(FfiEncodeOptions, threads) + mem::size_of::<u32>();
+
+/// Sizes of the versions of `FfiEncodeOptions`, each ending at its last field
+///
+/// `sizeof` of an older version may include trailing padding where a newer version has a
+/// field, so only the fields of the largest version within `struct_size` are read.
+const FFI_ENCODE_OPTIONS_VERSIONS: [usize; 22] = [
+    FFI_ENCODE_OPTIONS_V1_SIZE,
+    FFI_ENCODE_OPTIONS_V2_SIZE,
+    FFI_ENCODE_OPTIONS_V3_SIZE,
+    FFI_ENCODE_OPTIONS_V4_SIZE,
+    FFI_ENCODE_OPTIONS_V5_SIZE,
+    FFI_ENCODE_OPTIONS_V6_SIZE,
+    FFI_ENCODE_OPTIONS_V7_SIZE,
+    FFI_ENCODE_OPTIONS_V8_SIZE,
+    FFI_ENCODE_OPTIONS_V9_SIZE,
+    FFI_ENCODE_OPTIONS_V10_SIZE,
+    FFI_ENCODE_OPTIONS_V11_SIZE,
+    FFI_ENCODE_OPTIONS_V12_SIZE,
+    FFI_ENCODE_OPTIONS_V13_SIZE,
+    FFI_ENCODE_OPTIONS_V14_SIZE,
+    FFI_ENCODE_OPTIONS_V15_SIZE,
+    FFI_ENCODE_OPTIONS_V16_SIZE,
+    FFI_ENCODE_OPTIONS_V17_SIZE,
+    FFI_ENCODE_OPTIONS_V18_SIZE,
+    FFI_ENCODE_OPTIONS_V19_SIZE,
+    FFI_ENCODE_OPTIONS_V20_SIZE,
+    FFI_ENCODE_OPTIONS_V21_SIZE,
+    FFI_ENCODE_OPTIONS_V22_SIZE,
+];
+
+/// FFI statistics of a finished operation, with times in microseconds
+#[repr(C)]
+pub struct FfiEncodeStats {
+    pub frames: u64,
+    pub keyframes: u64,
+    pub output_bytes: u64,
+    pub duration_ms: u64,
+    pub bitrate: u64,
+    pub prepare_us: u64,
+    pub encode_us: u64,
+    pub mux_us: u64,
+    pub total_us: u64,
+    pub backend: EncoderBackend,
+}
+
+impl From<&EncodeStats> for FfiEncodeStats {
+    fn from(stats: &EncodeStats) -> Self {
+        Self {
+            frames: stats.frames,
+            keyframes: stats.keyframes,
+            output_bytes: stats.output_bytes,
+            duration_ms: stats.duration_ms,
+            bitrate: stats.bitrate,
+            prepare_us: stats.prepare_time.as_micros() as u64,
+            encode_us: stats.encode_time.as_micros() as u64,
+            mux_us: stats.mux_time.as_micros() as u64,
+            total_us: stats.total_time.as_micros() as u64,
+            backend: stats.backend,
+        }
+    }
+}
+
+/// FFI comparison of a verified output with its source frames
+#[repr(C)]
+pub struct FfiQualityReport {
+    pub frames: u64,
+    pub mean_psnr: f64,
+    pub min_psnr: f64,
+    pub mean_ssim: f64,
+    pub min_ssim: f64,
+    pub checksum: u64,
+}
+
+impl From<Option<&QualityReport>> for FfiQualityReport {
+    fn from(report: Option<&QualityReport>) -> Self {
+        match report {
+            Some(report) => Self {
+                frames: report.frames.len() as u64,
+                mean_psnr: report.mean_psnr,
+                min_psnr: report.min_psnr,
+                mean_ssim: report.mean_ssim,
+                min_ssim: report.min_ssim,
+                checksum: report.checksum,
+            },
+            // Zero frames tells the caller the output was not verified
+            None => Self {
+                frames: 0,
+                mean_psnr: 0.0,
+                min_psnr: 0.0,
+                mean_ssim: 0.0,
+                min_ssim: 0.0,
+                checksum: 0,
+            },
+        }
+    }
+}
+
+/// Outputs requested in `options` for a finished operation, each possibly null
+#[derive(Clone, Copy)]
+pub(crate) struct StatsOut {
+    stats: *mut FfiEncodeStats,
+    quality: *mut FfiQualityReport,
+}
+
+impl StatsOut {
+    /// No outputs, for functions without options
+    const NONE: Self = Self {
+        stats: ptr::null_mut(),
+        quality: ptr::null_mut(),
+    };
+}
+
+/// Statistics and quality outputs requested in `options`
+pub(crate) unsafe fn ffi_stats_out(options: *const FfiEncodeOptions) -> StatsOut {
+    if options.is_null() {
+        return StatsOut::NONE;
+    }
+    match read_encode_options(options) {
+        Some(options) => StatsOut {
+            stats: options.stats,
+            quality: options.quality_report,
+        },
+        None => StatsOut::NONE,
+    }
+}
+
+/// Convert the result of an in-memory operation, moving the output into `out` on success
+pub(crate) unsafe fn buffer_result(
+    result: crate::Result<EncodeStats>,
+    output: &MemoryOutput,
+    out: *mut FfiBuffer,
+    stats: StatsOut,
+) -> FfiResult {
+    if result.is_ok() {
+        ptr::write(out, FfiBuffer::from_vec(output.take()));
+    }
+    stats_result(result, stats)
+}
+
+/// Convert an operation result, writing its statistics and quality to the non-null outputs
+pub(crate) unsafe fn stats_result(result: crate::Result<EncodeStats>, out: StatsOut) -> FfiResult {
+    match result {
+        Ok(encode_stats) => {
+            if !out.stats.is_null() {
+                ptr::write(out.stats, FfiEncodeStats::from(&encode_stats));
+            }
+            if !out.quality.is_null() {
+                ptr::write(
+                    out.quality,
+                    FfiQualityReport::from(encode_stats.quality.as_ref()),
+                );
+            }
+            FfiResult::ok()
+        }
+        Err(e) => FfiResult::from_error(&e),
+    }
+}
+
+/// Initialize encode options with defaults and the current struct size
+///
+/// # Safety
+/// - `options` must point to a writable `FfiEncodeOptions`, or be null
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_encode_options_init(options: *mut FfiEncodeOptions) {
+    catch_panic_or((), || {
+        if options.is_null() {
+            return;
+        }
+
+        ptr::write(options, default_encode_options());
+    })
+}
+
+/// Encode options of the current version with every field at its default
+fn default_encode_options() -> FfiEncodeOptions {
+    let defaults = EncodeOptions::default();
+    let watermark = Watermark::new("");
+    FfiEncodeOptions {
+        struct_size: mem::size_of::<FfiEncodeOptions>() as u32,
+        output_path: ptr::null(),
+        container: defaults.container,
+        codec: defaults.codec,
+        quality: defaults.quality,
+        ffmpeg_path: ptr::null(),
+        progress: None,
+        user_data: ptr::null_mut(),
+        cancel: ptr::null(),
+        warning: None,
+        stats: ptr::null_mut(),
+        strict: 0,
+        max_duration_ms: 0,
+        memory_limit: 0,
+        encoder_pool: ptr::null(),
+        image_cache: ptr::null(),
+        deterministic: 0,
+        quality_report: ptr::null_mut(),
+        spill_dir: ptr::null(),
+        priority: defaults.priority,
+        av1_backend: defaults.av1_backend,
+        scale_mode: defaults.scale_mode,
+        letterbox_color: defaults.letterbox_color,
+        watermark_path: ptr::null(),
+        watermark_position: watermark.position,
+        watermark_opacity: watermark.opacity,
+        watermark_margin: watermark.margin,
+        fade_in_ms: defaults.fade_in_ms,
+        fade_out_ms: defaults.fade_out_ms,
+        fade_color: defaults.fade_color,
+        bitrate_kbps: defaults.bitrate_kbps.unwrap_or(0),
+        rate_control: defaults.rate_control,
+        keyframe_interval_ms: defaults.keyframe_interval_ms.unwrap_or(0),
+        color_space: defaults.color_space,
+        pixel_range: defaults.pixel_range,
+        threads: defaults.threads.unwrap_or(0) as u32,
+    }
+}
+
+/// Copy of the caller's encode options, with the fields of versions newer than its
+/// `struct_size` at their defaults, or `None` if it is older than the first version
+///
+/// Only the caller's version of the struct may be read, as that is all it allocated.
+unsafe fn read_encode_options(options: *const FfiEncodeOptions) -> Option<FfiEncodeOptions> {
+    let struct_size = (*options).struct_size as usize;
+    let len = FFI_ENCODE_OPTIONS_VERSIONS
+        .into_iter()
+        .rev()
+        .find(|&size| size <= struct_size)?;
+
+    let mut copy = default_encode_options();
+    ptr::copy_nonoverlapping(
+        options.cast::<u8>(),
+        ptr::addr_of_mut!(copy).cast::<u8>(),
+        len,
+    );
+    Some(copy)
+}
+
+/// Convert FFI encode options into `EncodeOptions`
+///
+/// When `require_output_path` is false, a null `output_path` is accepted (in-memory output).
+unsafe fn encode_options_from_ffi(
+    options: *const FfiEncodeOptions,
+    require_output_path: bool,
+) -> Result<EncodeOptions, FfiResult> {
+    if options.is_null() {
+        return Err(FfiResult::error(
+            ErrorCode::InvalidInput,
+            "Encode options are null",
+        ));
+    }
+
+    let Some(options) = read_encode_options(options) else {
+        return Err(FfiResult::error(
+            ErrorCode::InvalidInput,
+            "Unsupported encode options size",
+        ));
+    };
+
+    let output_path = if options.output_path.is_null() && !require_output_path {
+        c"".as_ptr()
+    } else {
+        options.output_path
+    };
+
+    let mut encode_options = encode_options(
+        output_path,
+        options.container,
+        options.codec,
+        options.quality,
+        options.ffmpeg_path,
+        ffi_progress_callback(options.progress, options.user_data),
+        ffi_cancel_token(options.cancel),
+    )?;
+
+    encode_options.warning = ffi_warning_callback(options.warning, options.user_data);
+    if options.strict != 0 {
+        encode_options.strict = Some(StrictLimits {
+            max_duration_ms: (options.max_duration_ms > 0).then_some(options.max_duration_ms),
+        });
+    }
+    encode_options.memory_limit = (options.memory_limit > 0).then_some(options.memory_limit);
+    encode_options.encoder_pool = ffi_encoder_pool(options.encoder_pool);
+    encode_options.image_cache = ffi_image_cache(options.image_cache);
+    encode_options.deterministic = options.deterministic != 0;
+    encode_options.verify = !options.quality_report.is_null();
+
+    if !options.spill_dir.is_null() {
+        match CStr::from_ptr(options.spill_dir).to_str() {
+            Ok(dir) => encode_options.spill_dir = Some(dir.to_string()),
+            Err(_) => {
+                return Err(FfiResult::error(
+                    ErrorCode::InvalidInput,
+                    "Invalid spill directory",
+                ))
+            }
+        }
+    }
+
+    encode_options.priority = options.priority;
+    encode_options.av1_backend = options.av1_backend;
+    encode_options.scale_mode = options.scale_mode;
+    encode_options.letterbox_color = options.letterbox_color;
+
+    if !options.watermark_path.is_null() {
+        match CStr::from_ptr(options.watermark_path).to_str() {
+            Ok(path) => {
+                encode_options.watermark = Some(Watermark {
+                    path: path.to_string(),
+                    position: options.watermark_position,
+                    opacity: options.watermark_opacity,
+                    margin: options.watermark_margin,
+                })
+            }
+            Err(_) => {
+                return Err(FfiResult::error(
+                    ErrorCode::InvalidInput,
+                    "Invalid watermark path",
+                ))
+            }
+        }
+    }
+
+    encode_options.fade_in_ms = options.fade_in_ms;
+    encode_options.fade_out_ms = options.fade_out_ms;
+    encode_options.fade_color = options.fade_color;
+    encode_options.bitrate_kbps = (options.bitrate_kbps > 0).then_some(options.bitrate_kbps);
+    encode_options.rate_control = options.rate_control;
+    encode_options.keyframe_interval_ms =
+        (options.keyframe_interval_ms > 0).then_some(options.keyframe_interval_ms);
+    encode_options.color_space = options.color_space;
+    encode_options.pixel_range = options.pixel_range;
+    encode_options.threads = (options.threads > 0).then_some(options.threads as usize);
+
+    Ok(encode_options)
+}
+
+/// Build `EncodeOptions` from FFI arguments
+unsafe fn encode_options(
+    output_path: *const c_char,
+    container: Container,
+    codec: Codec,
+    quality: u8,
+    ffmpeg_path: *const c_char,
+    progress: Option<ProgressCallback>,
+    cancel: Option<CancelToken>,
+) -> Result<EncodeOptions, FfiResult> {
+    if output_path.is_null() {
+        return Err(FfiResult::error(
+            ErrorCode::InvalidInput,
+            "Output path is null",
+        ));
+    }
+
+    // Convert output path
+    let output_path = match CStr::from_ptr(output_path).to_str() {
+        Ok(s) => s.to_string(),
+        Err(_) => {
+            return Err(FfiResult::error(
+                ErrorCode::InvalidInput,
+                "Invalid output path",
+            ))
+        }
+    };
+
+    // Convert ffmpeg path
+    let ffmpeg_path = if ffmpeg_path.is_null() {
+        None
+    } else {
+        match CStr::from_ptr(ffmpeg_path).to_str() {
+            Ok(s) => Some(s.to_string()),
+            Err(_) => {
+                return Err(FfiResult::error(
+                    ErrorCode::InvalidInput,
+                    "Invalid ffmpeg path",
+                ))
+            }
+        }
+    };
+
+    Ok(EncodeOptions {
+        output_path,
+        container,
+        codec,
+        quality,
+        ffmpeg_path,
+        progress,
+        cancel,
+        ..EncodeOptions::default()
+    })
+}
+
+/// Start platform runtimes ahead of time (COM and Media Foundation on Windows)
+///
+/// Optional. Runtimes stay running until `minmpeg_shutdown`; otherwise they are started
+/// and stopped on demand by each encoder.
+#[no_mangle]
+pub extern "C" fn minmpeg_init() -> FfiResult {
+    catch_panic(|| match crate::init() {
+        Ok(()) => FfiResult::ok(),
+        Err(e) => FfiResult::from_error(&e),
+    })
+}
+
+/// Release platform runtimes kept by `minmpeg_init`
+#[no_mangle]
+pub extern "C" fn minmpeg_shutdown() {
+    catch_panic_or((), crate::shutdown)
+}
+
+/// Check if a codec is available
+///
+/// # Safety
+/// - `ffmpeg_path` must be a valid null-terminated string or null
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_available(codec: Codec, ffmpeg_path: *const c_char) -> FfiResult {
+    catch_panic(|| {
+        let ffmpeg_path = if ffmpeg_path.is_null() {
+            None
+        } else {
+            match CStr::from_ptr(ffmpeg_path).to_str() {
+                Ok(s) => Some(s),
+                Err(_) => return FfiResult::error(ErrorCode::InvalidInput, "Invalid ffmpeg path"),
+            }
+        };
+
+        match available(codec, ffmpeg_path) {
+            Ok(_) => FfiResult::ok(),
+            Err(e) => FfiResult::from_error(&e),
+        }
+    })
+}
+
+/// List the codec/container/backend combinations available on this machine
+///
+/// On success, `out` receives an array that must be freed with `minmpeg_capabilities_free`.
+///
+/// # Safety
+/// - `ffmpeg_path` must be a valid null-terminated string or null
+/// - `out` must point to a writable `FfiCapabilities`
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_capabilities(
+    ffmpeg_path: *const c_char,
+    out: *mut FfiCapabilities,
+) -> FfiResult {
+    catch_panic(|| {
+        if out.is_null() {
+            return FfiResult::error(ErrorCode::InvalidInput, "Output pointer is null");
+        }
+
+        let ffmpeg_path = if ffmpeg_path.is_null() {
+            None
+        } else {
+            match CStr::from_ptr(ffmpeg_path).to_str() {
+                Ok(s) => Some(s),
+                Err(_) => return FfiResult::error(ErrorCode::InvalidInput, "Invalid ffmpeg path"),
+            }
+        };
+
+        let items: Box<[FfiCapability]> = capabilities(ffmpeg_path)
+            .into_iter()
+            .map(|cap| FfiCapability {
+                codec: cap.codec,
+                container: cap.container,
+                backend: cap.backend,
+                backend_name: backend_name(cap.backend),
+                hardware: cap.hardware as u8,
+            })
+            .collect();
+
+        let len = items.len();
+        ptr::write(
+            out,
+            FfiCapabilities {
+                items: Box::into_raw(items) as *mut FfiCapability,
+                len,
+            },
+        );
+        FfiResult::ok()
+    })
+}
+
+/// Free a capability array returned by `minmpeg_capabilities`
+///
+/// # Safety
+/// - `caps` must point to an `FfiCapabilities` filled by `minmpeg_capabilities`, or be null
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_capabilities_free(caps: *mut FfiCapabilities) {
+    catch_panic_or((), || {
+        if caps.is_null() {
+            return;
+        }
+
+        let caps = &mut *caps;
+        if !caps.items.is_null() {
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+                caps.items, caps.len,
+            )));
+            caps.items = ptr::null_mut();
+            caps.len = 0;
+        }
+    })
+}
+
+/// Create a slideshow video from images
+///
+/// # Safety
+/// - `entries` must point to a valid array of `FfiSlideEntry` with `entry_count` elements
+/// - `output_path` must be a valid null-terminated string
+/// - `ffmpeg_path` must be a valid null-terminated string or null
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_slideshow(
+    entries: *const FfiSlideEntry,
+    entry_count: size_t,
+    output_path: *const c_char,
+    container: Container,
+    codec: Codec,
+    quality: u8,
+    ffmpeg_path: *const c_char,
+) -> FfiResult {
+    catch_panic(|| {
+        match encode_options(
+            output_path,
+            container,
+            codec,
+            quality,
+            ffmpeg_path,
+            None,
+            None,
+        ) {
+            Ok(options) => slideshow_impl(entries, entry_count, &options, StatsOut::NONE),
+            Err(result) => result,
+        }
+    })
+}
+
+/// Create a slideshow video from images, reporting progress
+///
+/// # Safety
+/// - Same requirements as `minmpeg_slideshow`
+/// - `progress` can be null; if set, it is called synchronously on the calling thread
+/// - `user_data` is passed through to `progress` untouched
+/// - `cancel` can be null; if set, it must be a token from `minmpeg_cancel_token_new`
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_slideshow_with_progress(
+    entries: *const FfiSlideEntry,
+    entry_count: size_t,
+    output_path: *const c_char,
+    container: Container,
+    codec: Codec,
+    quality: u8,
+    ffmpeg_path: *const c_char,
+    progress: FfiProgressCallback,
+    user_data: *mut c_void,
+    cancel: *const FfiCancelToken,
+) -> FfiResult {
+    catch_panic(|| {
+        match encode_options(
+            output_path,
+            container,
+            codec,
+            quality,
+            ffmpeg_path,
+            ffi_progress_callback(progress, user_data),
+            ffi_cancel_token(cancel),
+        ) {
+            Ok(options) => slideshow_impl(entries, entry_count, &options, StatsOut::NONE),
+            Err(result) => result,
+        }
+    })
+}
+
+/// Create a slideshow video from images using an options struct
+///
+/// # Safety
+/// - `entries` must point to a valid array of `FfiSlideEntry` with `entry_count` elements
+/// - `options` must point to an `FfiEncodeOptions` initialized with `minmpeg_encode_options_init`
+/// - Pointers inside `options` follow the same rules as `minmpeg_slideshow_with_progress`
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_slideshow_ex(
+    entries: *const FfiSlideEntry,
+    entry_count: size_t,
+    options: *const FfiEncodeOptions,
+) -> FfiResult {
+    catch_panic(|| match encode_options_from_ffi(options, true) {
+        Ok(encode_options) => slideshow_impl(
+            entries,
+            entry_count,
+            &encode_options,
+            ffi_stats_out(options),
+        ),
+        Err(result) => result,
+    })
+}
+
+unsafe fn slideshow_impl(
+    entries: *const FfiSlideEntry,
+    entry_count: size_t,
+    options: &EncodeOptions,
+    stats: StatsOut,
+) -> FfiResult {
+    let slide_entries = match slide_entries(entries, entry_count) {
+        Ok(entries) => entries,
+        Err(result) => return result,
+    };
+
+    // Run slideshow
+    stats_result(slideshow(&slide_entries, options), stats)
+}
+
+/// Convert FFI slide entries
+unsafe fn slide_entries(
+    entries: *const FfiSlideEntry,
+    entry_count: size_t,
+) -> Result<Vec<SlideEntry>, FfiResult> {
+    // Validate inputs
+    if entries.is_null() || entry_count == 0 {
+        return Err(FfiResult::error(
+            ErrorCode::InvalidInput,
+            "No slides provided",
+        ));
+    }
+
+    let ffi_entries = slice::from_raw_parts(entries, entry_count);
+    let mut slide_entries: Vec<SlideEntry> = Vec::with_capacity(entry_count);
+
+    for entry in ffi_entries {
+        if entry.path.is_null() {
+            return Err(FfiResult::error(
+                ErrorCode::InvalidInput,
+                "Slide path is null",
+            ));
+        }
+
+        let source = match CStr::from_ptr(entry.path).to_str() {
+            Ok(s) => SlideSource::Path(s.to_string()),
+            Err(_) => {
+                return Err(FfiResult::error(
+                    ErrorCode::InvalidInput,
+                    "Invalid slide path",
+                ))
+            }
+        };
+
+        slide_entries.push(SlideEntry {
+            source,
+            duration_ms: entry.duration_ms,
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
+        });
+    }
+
+    Ok(slide_entries)
+}
+
+/// Convert FFI slide data entries, copying their image data
+unsafe fn slide_data_entries(
+    entries: *const FfiSlideData,
+    entry_count: size_t,
+) -> Result<Vec<SlideEntry>, FfiResult> {
+    if entries.is_null() || entry_count == 0 {
+        return Err(FfiResult::error(
+            ErrorCode::InvalidInput,
+            "No slides provided",
+        ));
+    }
+
+    let ffi_entries = slice::from_raw_parts(entries, entry_count);
+    let mut slide_entries = Vec::with_capacity(entry_count);
+
+    for entry in ffi_entries {
+        let source = match entry.kind {
+            FfiSlideDataKind::Path => {
+                if entry.path.is_null() {
+                    return Err(FfiResult::error(
+                        ErrorCode::InvalidInput,
+                        "Slide path is null",
+                    ));
+                }
+
+                match CStr::from_ptr(entry.path).to_str() {
+                    Ok(path) => SlideSource::Path(path.to_string()),
+                    Err(_) => {
+                        return Err(FfiResult::error(
+                            ErrorCode::InvalidInput,
+                            "Invalid slide path",
+                        ))
+                    }
+                }
+            }
+            FfiSlideDataKind::Encoded | FfiSlideDataKind::Rgba => {
+                if entry.data.is_null() || entry.data_len == 0 {
+                    return Err(FfiResult::error(
+                        ErrorCode::InvalidInput,
+                        "Slide data is empty",
+                    ));
+                }
+
+                let data = slice::from_raw_parts(entry.data, entry.data_len).to_vec();
+                if entry.kind == FfiSlideDataKind::Encoded {
+                    SlideSource::Bytes(data)
+                } else {
+                    SlideSource::Rgba {
+                        width: entry.width,
+                        height: entry.height,
+                        data,
+                    }
+                }
+            }
+        };
+
+        slide_entries.push(SlideEntry {
+            source,
+            duration_ms: entry.duration_ms,
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
+        });
+    }
+
+    Ok(slide_entries)
+}
+
+/// Create a slideshow video from slides supplied as paths, encoded bytes, or raw RGBA
+///
+/// Image data is copied during the call; the caller keeps ownership of its buffers.
+///
+/// # Safety
+/// - `entries` must point to a valid array of `FfiSlideData` with `entry_count` elements
+/// - Each entry's `path` or `data`/`data_len` must be valid for its `kind`
+/// - `options` follows the same rules as in `minmpeg_slideshow_ex`
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_slideshow_from_data(
+    entries: *const FfiSlideData,
+    entry_count: size_t,
+    options: *const FfiEncodeOptions,
+) -> FfiResult {
+    catch_panic(|| {
+        let stats = ffi_stats_out(options);
+        let options = match encode_options_from_ffi(options, true) {
+            Ok(options) => options,
+            Err(result) => return result,
+        };
+
+        let slide_entries = match slide_data_entries(entries, entry_count) {
+            Ok(entries) => entries,
+            Err(result) => return result,
+        };
+
+        stats_result(slideshow_to(&slide_entries, &options, None), stats)
+    })
+}
+
+/// Create a slideshow video in memory from slides supplied as paths, encoded bytes, or raw RGBA
+///
+/// # Safety
+/// - Same requirements as `minmpeg_slideshow_from_data`
+/// - `out` must point to a writable `FfiBuffer`
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_slideshow_from_data_to_buffer(
+    entries: *const FfiSlideData,
+    entry_count: size_t,
+    options: *const FfiEncodeOptions,
+    out: *mut FfiBuffer,
+) -> FfiResult {
+    catch_panic(|| {
+        if out.is_null() {
+            return FfiResult::error(ErrorCode::InvalidInput, "Output buffer pointer is null");
+        }
+        ptr::write(out, FfiBuffer::empty());
+
+        let stats = ffi_stats_out(options);
+        let options = match encode_options_from_ffi(options, false) {
+            Ok(options) => options,
+            Err(result) => return result,
+        };
+
+        let slide_entries = match slide_data_entries(entries, entry_count) {
+            Ok(entries) => entries,
+            Err(result) => return result,
+        };
+
+        let output = MemoryOutput::new();
+        let result = slideshow_to(&slide_entries, &options, Some(&output));
+        buffer_result(result, &output, out, stats)
+    })
+}
+
+/// Create a slideshow video in memory
+///
+/// On success, `out` receives the encoded video, which must be freed with `minmpeg_buffer_free`.
+/// `options.output_path` is ignored and may be null.
+///
+/// # Safety
+/// - Same requirements as `minmpeg_slideshow_ex`
+/// - `out` must point to a writable `FfiBuffer`
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_slideshow_to_buffer(
+    entries: *const FfiSlideEntry,
+    entry_count: size_t,
+    options: *const FfiEncodeOptions,
+    out: *mut FfiBuffer,
+) -> FfiResult {
+    catch_panic(|| {
+        if out.is_null() {
+            return FfiResult::error(ErrorCode::InvalidInput, "Output buffer pointer is null");
+        }
+        ptr::write(out, FfiBuffer::empty());
+
+        let stats = ffi_stats_out(options);
+        let options = match encode_options_from_ffi(options, false) {
+            Ok(options) => options,
+            Err(result) => return result,
+        };
+
+        let slide_entries = match slide_entries(entries, entry_count) {
+            Ok(entries) => entries,
+            Err(result) => return result,
+        };
+
+        let output = MemoryOutput::new();
+        let result = slideshow_to(&slide_entries, &options, Some(&output));
+        buffer_result(result, &output, out, stats)
+    })
+}
+
+/// Combine two videos side by side
+///
+/// # Safety
+/// - `left_path`, `right_path`, and `output_path` must be valid null-terminated strings
+/// - `background` can be null (defaults to white)
+/// - `ffmpeg_path` can be null
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_juxtapose(
+    left_path: *const c_char,
+    right_path: *const c_char,
+    output_path: *const c_char,
+    container: Container,
+    codec: Codec,
+    quality: u8,
+    background: *const FfiColor,
+    ffmpeg_path: *const c_char,
+) -> FfiResult {
+    catch_panic(|| {
+        match encode_options(
+            output_path,
+            container,
+            codec,
+            quality,
+            ffmpeg_path,
+            None,
+            None,
+        ) {
+            Ok(options) => {
+                juxtapose_impl(left_path, right_path, background, &options, StatsOut::NONE)
+            }
+            Err(result) => result,
+        }
+    })
+}
+
+/// Combine two videos side by side, reporting progress
+///
+/// # Safety
+/// - Same requirements as `minmpeg_juxtapose`
+/// - `progress` can be null; if set, it is called synchronously on the calling thread
+/// - `user_data` is passed through to `progress` untouched
+/// - `cancel` can be null; if set, it must be a token from `minmpeg_cancel_token_new`
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_juxtapose_with_progress(
+    left_path: *const c_char,
+    right_path: *const c_char,
+    output_path: *const c_char,
+    container: Container,
+    codec: Codec,
+    quality: u8,
+    background: *const FfiColor,
+    ffmpeg_path: *const c_char,
+    progress: FfiProgressCallback,
+    user_data: *mut c_void,
+    cancel: *const FfiCancelToken,
+) -> FfiResult {
+    catch_panic(|| {
+        match encode_options(
+            output_path,
+            container,
+            codec,
+            quality,
+            ffmpeg_path,
+            ffi_progress_callback(progress, user_data),
+            ffi_cancel_token(cancel),
+        ) {
+            Ok(options) => {
+                juxtapose_impl(left_path, right_path, background, &options, StatsOut::NONE)
+            }
+            Err(result) => result,
+        }
+    })
+}
+
+/// Combine two videos side by side using an options struct
+///
+/// # Safety
+/// - `left_path` and `right_path` must be valid null-terminated strings
+/// - `background` can be null (defaults to white)
+/// - `options` must point to an `FfiEncodeOptions` initialized with `minmpeg_encode_options_init`
+/// - Pointers inside `options` follow the same rules as `minmpeg_juxtapose_with_progress`
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_juxtapose_ex(
+    left_path: *const c_char,
+    right_path: *const c_char,
+    background: *const FfiColor,
+    options: *const FfiEncodeOptions,
+) -> FfiResult {
+    catch_panic(|| match encode_options_from_ffi(options, true) {
+        Ok(encode_options) => juxtapose_impl(
+            left_path,
+            right_path,
+            background,
+            &encode_options,
+            ffi_stats_out(options),
+        ),
+        Err(result) => result,
+    })
+}
+
+unsafe fn juxtapose_impl(
+    left_path: *const c_char,
+    right_path: *const c_char,
+    background: *const FfiColor,
+    options: &EncodeOptions,
+    stats: StatsOut,
+) -> FfiResult {
+    let (left_path, right_path, bg_color) =
+        match juxtapose_inputs(left_path, right_path, background) {
+            Ok(inputs) => inputs,
+            Err(result) => return result,
+        };
+
+    // Run juxtapose
+    stats_result(juxtapose(left_path, right_path, options, bg_color), stats)
+}
+
+/// Convert FFI juxtapose input paths and background color
+unsafe fn juxtapose_inputs<'a>(
+    left_path: *const c_char,
+    right_path: *const c_char,
+    background: *const FfiColor,
+) -> Result<(&'a str, &'a str, Option<Color>), FfiResult> {
+    // Validate inputs
+    if left_path.is_null() {
+        return Err(FfiResult::error(
+            ErrorCode::InvalidInput,
+            "Left video path is null",
+        ));
+    }
+
+    if right_path.is_null() {
+        return Err(FfiResult::error(
+            ErrorCode::InvalidInput,
+            "Right video path is null",
+        ));
+    }
+
+    // Convert paths
+    let left_path = match CStr::from_ptr(left_path).to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            return Err(FfiResult::error(
+                ErrorCode::InvalidInput,
+                "Invalid left video path",
+            ))
+        }
+    };
+
+    let right_path = match CStr::from_ptr(right_path).to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            return Err(FfiResult::error(
+                ErrorCode::InvalidInput,
+                "Invalid right video path",
+            ))
+        }
+    };
+
+    Ok((left_path, right_path, ffi_color(background)))
+}
+
+/// Convert an optional FFI background color
+unsafe fn ffi_color(background: *const FfiColor) -> Option<Color> {
+    if background.is_null() {
+        None
+    } else {
+        let bg = &*background;
+        Some(Color {
+            r: bg.r,
+            g: bg.g,
+            b: bg.b,
+        })
+    }
+}
+
+/// Combine two videos side by side in memory
+///
+/// On success, `out` receives the encoded video, which must be freed with `minmpeg_buffer_free`.
+/// `options.output_path` is ignored and may be null.
+///
+/// # Safety
+/// - Same requirements as `minmpeg_juxtapose_ex`
+/// - `out` must point to a writable `FfiBuffer`
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_juxtapose_to_buffer(
+    left_path: *const c_char,
+    right_path: *const c_char,
+    background: *const FfiColor,
+    options: *const FfiEncodeOptions,
+    out: *mut FfiBuffer,
+) -> FfiResult {
+    catch_panic(|| {
+        if out.is_null() {
+            return FfiResult::error(ErrorCode::InvalidInput, "Output buffer pointer is null");
+        }
+        ptr::write(out, FfiBuffer::empty());
+
+        let stats = ffi_stats_out(options);
+        let options = match encode_options_from_ffi(options, false) {
+            Ok(options) => options,
+            Err(result) => return result,
+        };
+
+        let (left_path, right_path, bg_color) =
+            match juxtapose_inputs(left_path, right_path, background) {
+                Ok(inputs) => inputs,
+                Err(result) => return result,
+            };
+
+        let output = MemoryOutput::new();
+        let result = juxtapose_to(
+            &[left_path, right_path],
+            Layout::default(),
+            &options,
+            bg_color,
+            Some(&output),
+        );
+        buffer_result(result, &output, out, stats)
+    })
+}
+
+/// Combine any number of videos into a grid
+///
+/// `layout` may be null to place all videos in a single row.
+///
+/// # Safety
+/// - `paths` must point to `path_count` valid null-terminated strings
+/// - `layout` and `background` can be null
+/// - `options` must point to an `FfiEncodeOptions` initialized with `minmpeg_encode_options_init`
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_juxtapose_n(
+    paths: *const *const c_char,
+    path_count: size_t,
+    layout: *const FfiLayout,
+    background: *const FfiColor,
+    options: *const FfiEncodeOptions,
+) -> FfiResult {
+    catch_panic(|| {
+        let stats = ffi_stats_out(options);
+        let options = match encode_options_from_ffi(options, true) {
+            Ok(options) => options,
+            Err(result) => return result,
+        };
+
+        let paths = match video_paths(paths, path_count) {
+            Ok(paths) => paths,
+            Err(result) => return result,
+        };
+
+        let result = juxtapose_n(&paths, ffi_layout(layout), &options, ffi_color(background));
+        stats_result(result, stats)
+    })
+}
+
+/// Combine any number of videos into a grid in memory
+///
+/// On success, `out` receives the encoded video, which must be freed with `minmpeg_buffer_free`.
+/// `options.output_path` is ignored and may be null.
+///
+/// # Safety
+/// - Same requirements as `minmpeg_juxtapose_n`
+/// - `out` must point to a writable `FfiBuffer`
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_juxtapose_n_to_buffer(
+    paths: *const *const c_char,
+    path_count: size_t,
+    layout: *const FfiLayout,
+    background: *const FfiColor,
+    options: *const FfiEncodeOptions,
+    out: *mut FfiBuffer,
+) -> FfiResult {
+    catch_panic(|| {
+        if out.is_null() {
+            return FfiResult::error(ErrorCode::InvalidInput, "Output buffer pointer is null");
+        }
+        ptr::write(out, FfiBuffer::empty());
+
+        let stats = ffi_stats_out(options);
+        let options = match encode_options_from_ffi(options, false) {
+            Ok(options) => options,
+            Err(result) => return result,
+        };
+
+        let paths = match video_paths(paths, path_count) {
+            Ok(paths) => paths,
+            Err(result) => return result,
+        };
+
+        let output = MemoryOutput::new();
+        let result = juxtapose_to(
+            &paths,
+            ffi_layout(layout),
+            &options,
+            ffi_color(background),
+            Some(&output),
+        );
+        buffer_result(result, &output, out, stats)
+    })
+}
+
+/// Convert an array of FFI video paths
+unsafe fn video_paths<'a>(
+    paths: *const *const c_char,
+    path_count: size_t,
+) -> Result<Vec<&'a str>, FfiResult> {
+    if paths.is_null() || path_count == 0 {
+        return Err(FfiResult::error(
+            ErrorCode::InvalidInput,
+            "No input videos provided",
+        ));
+    }
+
+    slice::from_raw_parts(paths, path_count)
+        .iter()
+        .map(|&path| {
+            if path.is_null() {
+                return Err(FfiResult::error(
+                    ErrorCode::InvalidInput,
+                    "Video path is null",
+                ));
+            }
+            CStr::from_ptr(path)
+                .to_str()
+                .map_err(|_| FfiResult::error(ErrorCode::InvalidInput, "Invalid video path"))
+        })
+        .collect()
+}
+
+/// Convert an optional FFI layout
+unsafe fn ffi_layout(layout: *const FfiLayout) -> Layout {
+    if layout.is_null() {
+        Layout::default()
+    } else {
+        Layout {
+            columns: (*layout).columns,
+            ..Default::default()
+        }
+    }
+}
+
+/// Probe a video file for its dimensions, frame rate, duration, codec and container
+///
+/// # Safety
+/// - `path` must be a valid null-terminated string
+/// - `ffmpeg_path` must be a valid null-terminated string or null
+/// - `info` must point to a writable `FfiVideoInfo` of `struct_size` bytes
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_probe(
+    path: *const c_char,
+    ffmpeg_path: *const c_char,
+    info: *mut FfiVideoInfo,
+) -> FfiResult {
+    catch_panic(|| {
+        if path.is_null() {
+            return FfiResult::error(ErrorCode::InvalidInput, "Video path is null");
+        }
+
+        if info.is_null() {
+            return FfiResult::error(ErrorCode::InvalidInput, "Video info pointer is null");
+        }
+
+        // Only the caller's version of the struct may be written
+        let struct_size = (*info).struct_size;
+        let Some(len) = FFI_VIDEO_INFO_VERSIONS
+            .into_iter()
+            .rev()
+            .find(|&size| size <= struct_size as usize)
+        else {
+            return FfiResult::error(ErrorCode::InvalidInput, "Unsupported video info size");
+        };
+
+        let path = match CStr::from_ptr(path).to_str() {
+            Ok(s) => s,
+            Err(_) => return FfiResult::error(ErrorCode::InvalidInput, "Invalid video path"),
+        };
+
+        let ffmpeg_path = if ffmpeg_path.is_null() {
+            None
+        } else {
+            match CStr::from_ptr(ffmpeg_path).to_str() {
+                Ok(s) => Some(s),
+                Err(_) => return FfiResult::error(ErrorCode::InvalidInput, "Invalid ffmpeg path"),
+            }
+        };
+
+        match probe(path, ffmpeg_path) {
+            Ok(video_info) => {
+                let mut out = FfiVideoInfo {
+                    struct_size,
+                    width: video_info.width,
+                    height: video_info.height,
+                    fps: video_info.fps,
+                    duration_ms: video_info.duration_ms,
+                    frame_count: video_info.frame_count,
+                    codec: [0; 32],
+                    container: [0; 32],
+                };
+                copy_to_c_buf(&video_info.codec, &mut out.codec);
+                copy_to_c_buf(&video_info.container, &mut out.container);
+                ptr::copy_nonoverlapping(ptr::addr_of!(out).cast::<u8>(), info.cast::<u8>(), len);
+                FfiResult::ok()
+            }
+            Err(e) => FfiResult::from_error(&e),
+        }
+    })
+}
+
+/// Free a result's message string
+///
+/// # Safety
+/// - `result` must point to a valid `FfiResult` that was returned by a minmpeg function
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_free_result(result: *mut FfiResult) {
+    catch_panic_or((), || {
+        if result.is_null() {
+            return;
+        }
+
+        let result = &mut *result;
+        if !result.message.is_null() {
+            details::release(result.message);
+            // Reclaim the CString and let it drop
+            let _ = CString::from_raw(result.message);
+            result.message = ptr::null_mut();
+        }
+    })
+}
+
+/// Free a buffer returned by a `*_to_buffer` function
+///
+/// # Safety
+/// - `buffer` must point to an `FfiBuffer` filled by a minmpeg function, or be null
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_buffer_free(buffer: *mut FfiBuffer) {
+    catch_panic_or((), || {
+        if buffer.is_null() {
+            return;
+        }
+
+        let buffer = &mut *buffer;
+        if !buffer.data.is_null() {
+            // Reclaim the boxed slice and let it drop
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+                buffer.data,
+                buffer.len,
+            )));
+            buffer.data = ptr::null_mut();
+            buffer.len = 0;
+        }
+    })
+}
+
+/// Convert a null-terminated UTF-16 string (such as a Windows wide path) to UTF-8
+///
+/// The result can be passed to any path parameter and must be freed with
+/// `minmpeg_string_free`. Returns null if `wide` is null or not valid UTF-16; such paths
+/// can be passed to `minmpeg_slideshow_w` and `minmpeg_juxtapose_w` instead.
+/// Paths longer than `MAX_PATH` are handled by the library when opening files, but
+/// are passed as they are to ffmpeg and ffprobe.
+///
+/// # Safety
+/// - `wide` must be a valid null-terminated UTF-16 string or null
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_utf16_to_utf8(wide: *const u16) -> *mut c_char {
+    catch_panic_or(ptr::null_mut(), || {
+        if wide.is_null() {
+            return ptr::null_mut();
+        }
+
+        match String::from_utf16(wide::wide_units(wide)) {
+            // Cannot contain a nul: the input stops at the first one
+            Ok(s) => CString::new(s).map_or(ptr::null_mut(), CString::into_raw),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+/// Free a string returned by `minmpeg_utf16_to_utf8`
+///
+/// # Safety
+/// - `s` must be a string returned by `minmpeg_utf16_to_utf8`, or null
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_string_free(s: *mut c_char) {
+    catch_panic_or((), || {
+        if !s.is_null() {
+            drop(CString::from_raw(s));
+        }
+    })
+}
+
+/// Get version string
+#[no_mangle]
+pub extern "C" fn minmpeg_version() -> *const c_char {
+    catch_panic_or(ptr::null(), || {
+        static VERSION: &[u8] = concat!(env!("CARGO_PKG_VERSION"), "\0").as_bytes();
+        VERSION.as_ptr() as *const c_char
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_options_init() {
+        let mut options = mem::MaybeUninit::<FfiEncodeOptions>::uninit();
+        let options = unsafe {
+            minmpeg_encode_options_init(options.as_mut_ptr());
+            options.assume_init()
+        };
+
+        assert_eq!(
+            options.struct_size as usize,
+            mem::size_of::<FfiEncodeOptions>()
+        );
+        assert_eq!(options.container, Container::WebM);
+        assert_eq!(options.codec, Codec::Av1);
+        assert_eq!(options.quality, 50);
+        assert!(options.output_path.is_null());
+    }
+
+    #[test]
+    fn test_catch_panic() {
+        let mut result = catch_panic(|| panic!("boom"));
+        assert_eq!(result.code, ErrorCode::Internal);
+
+        let message = unsafe { CStr::from_ptr(result.message) };
+        assert_eq!(message.to_str().unwrap(), "Internal error: panic: boom");
+        unsafe { minmpeg_free_result(&mut result) };
+    }
+
+    #[test]
+    #[cfg(feature = "av1")]
+    fn test_capabilities() {
+        let mut caps = FfiCapabilities {
+            items: ptr::null_mut(),
+            len: 0,
+        };
+        let result = unsafe { minmpeg_capabilities(ptr::null(), &mut caps) };
+        assert_eq!(result.code, ErrorCode::Ok);
+
+        let items = unsafe { slice::from_raw_parts(caps.items, caps.len) };
+        let webm_av1 = items
+            .iter()
+            .find(|cap| cap.codec == Codec::Av1 && cap.container == Container::WebM)
+            .expect("WebM/AV1 should be available");
+        let name = unsafe { CStr::from_ptr(webm_av1.backend_name) };
+        assert_eq!(name.to_str().unwrap(), "rav1e");
+
+        unsafe { minmpeg_capabilities_free(&mut caps) };
+        assert!(caps.items.is_null());
+    }
+
+    #[test]
+    fn test_utf16_to_utf8() {
+        let wide: Vec<u16> = "C:\\動画\\slide.png\0".encode_utf16().collect();
+        let s = unsafe { minmpeg_utf16_to_utf8(wide.as_ptr()) };
+        assert!(!s.is_null());
+        assert_eq!(
+            unsafe { CStr::from_ptr(s) }.to_str().unwrap(),
+            "C:\\動画\\slide.png"
+        );
+        unsafe { minmpeg_string_free(s) };
+
+        // Unpaired surrogate
+        let invalid = [0xD800u16, 0];
+        assert!(unsafe { minmpeg_utf16_to_utf8(invalid.as_ptr()) }.is_null());
+    }
+
+    #[test]
+    fn test_buffer_free() {
+        let mut buffer = FfiBuffer::from_vec(vec![1, 2, 3]);
+        assert_eq!(buffer.len, 3);
+
+        unsafe { minmpeg_buffer_free(&mut buffer) };
+        assert!(buffer.data.is_null());
+        assert_eq!(buffer.len, 0);
+    }
+
+    #[test]
+    fn test_slide_data_entries() {
+        let pixels = vec![128u8; 2 * 2 * 4];
+        let entry = FfiSlideData {
+            kind: FfiSlideDataKind::Rgba,
+            path: ptr::null(),
+            data: pixels.as_ptr(),
+            data_len: pixels.len(),
+            width: 2,
+            height: 2,
+            duration_ms: 33,
+        };
+
+        let entries = unsafe { slide_data_entries(&entry, 1) }.ok().unwrap();
+        assert_eq!(
+            entries[0].source,
+            SlideSource::Rgba {
+                width: 2,
+                height: 2,
+                data: pixels.clone(),
+            }
+        );
+        assert_eq!(entries[0].duration_ms, 33);
+    }
+
+    #[test]
+    #[cfg(feature = "av1")]
+    fn test_slideshow_from_rgba_data_to_buffer() {
+        let pixels = vec![128u8; 16 * 16 * 4];
+        let entry = FfiSlideData {
+            kind: FfiSlideDataKind::Rgba,
+            path: ptr::null(),
+            data: pixels.as_ptr(),
+            data_len: pixels.len(),
+            width: 16,
+            height: 16,
+            duration_ms: 33,
+        };
+
+        let mut options = mem::MaybeUninit::<FfiEncodeOptions>::uninit();
+        let options = unsafe {
+            minmpeg_encode_options_init(options.as_mut_ptr());
+            options.assume_init()
+        };
+
+        let mut buffer = FfiBuffer::empty();
+        let result =
+            unsafe { minmpeg_slideshow_from_data_to_buffer(&entry, 1, &options, &mut buffer) };
+        assert_eq!(result.code, ErrorCode::Ok);
+
+        // The buffer is freed before checking it, so a failure does not leak it
+        let magic = unsafe { slice::from_raw_parts(buffer.data, buffer.len.min(4)) }.to_vec();
+        unsafe { minmpeg_buffer_free(&mut buffer) };
+        assert_eq!(magic, [0x1A, 0x45, 0xDF, 0xA3]);
+    }
+
+    #[test]
+    fn test_encode_options_read_within_struct_size() {
+        let mut options = mem::MaybeUninit::<FfiEncodeOptions>::uninit();
+        let mut options = unsafe {
+            minmpeg_encode_options_init(options.as_mut_ptr());
+            options.assume_init()
+        };
+        options.struct_size = FFI_ENCODE_OPTIONS_V1_SIZE as u32;
+        options.quality = 80;
+
+        // An allocation of the first version only, as from a caller built against it
+        let mut first = vec![0u64; FFI_ENCODE_OPTIONS_V1_SIZE.div_ceil(8)];
+        let copy = unsafe {
+            ptr::copy_nonoverlapping(
+                ptr::addr_of!(options).cast::<u8>(),
+                first.as_mut_ptr().cast::<u8>(),
+                FFI_ENCODE_OPTIONS_V1_SIZE,
+            );
+            read_encode_options(first.as_ptr().cast::<FfiEncodeOptions>()).unwrap()
+        };
+        assert_eq!(copy.struct_size as usize, FFI_ENCODE_OPTIONS_V1_SIZE);
+        assert_eq!(copy.quality, 80);
+    }
+
+    #[test]
+    fn test_encode_options_rejects_small_struct() {
+        let mut options = mem::MaybeUninit::<FfiEncodeOptions>::uninit();
+        let mut options = unsafe {
+            minmpeg_encode_options_init(options.as_mut_ptr());
+            options.assume_init()
+        };
+        options.struct_size = 4;
+
+        let mut result = unsafe { minmpeg_slideshow_ex(ptr::null(), 0, &options) };
+        assert_eq!(result.code, ErrorCode::InvalidInput);
+        unsafe { minmpeg_free_result(&mut result) };
+    }
+}
@@ -0,0 +1,149 @@
+//! FFI entry points for re-encoding operations and still frame extraction
+
+use super::{
+    catch_panic, encode_options_from_ffi, ffi_stats_out, stats_result, video_paths,
+    FfiEncodeOptions, FfiResult,
+};
+use crate::error::ErrorCode;
+use crate::{concat, thumbnail_to_file, transcode, trim, Result};
+use libc::{c_char, size_t};
+use std::ffi::CStr;
+
+/// Convert a required FFI path argument
+unsafe fn path_arg<'a>(path: *const c_char, name: &str) -> std::result::Result<&'a str, FfiResult> {
+    if path.is_null() {
+        return Err(FfiResult::error(
+            ErrorCode::InvalidInput,
+            &format!("Missing {}", name),
+        ));
+    }
+
+    CStr::from_ptr(path)
+        .to_str()
+        .map_err(|_| FfiResult::error(ErrorCode::InvalidInput, &format!("Invalid {}", name)))
+}
+
+fn to_ffi_result(result: Result<()>) -> FfiResult {
+    match result {
+        Ok(()) => FfiResult::ok(),
+        Err(e) => FfiResult::from_error(&e),
+    }
+}
+
+/// Re-encode a video with the container, codec and quality in `options`
+///
+/// # Safety
+/// - `input_path` must be a valid null-terminated string
+/// - `options` must point to an `FfiEncodeOptions` initialized with `minmpeg_encode_options_init`
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_transcode(
+    input_path: *const c_char,
+    options: *const FfiEncodeOptions,
+) -> FfiResult {
+    catch_panic(|| {
+        let stats = ffi_stats_out(options);
+        let options = match encode_options_from_ffi(options, true) {
+            Ok(options) => options,
+            Err(result) => return result,
+        };
+
+        match path_arg(input_path, "input path") {
+            Ok(input_path) => stats_result(transcode(input_path, &options), stats),
+            Err(result) => result,
+        }
+    })
+}
+
+/// Re-encode the part of a video between `start_ms` and `end_ms`
+///
+/// `end_ms` of 0 keeps the rest of the video.
+///
+/// # Safety
+/// - Same requirements as `minmpeg_transcode`
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_trim(
+    input_path: *const c_char,
+    start_ms: u64,
+    end_ms: u64,
+    options: *const FfiEncodeOptions,
+) -> FfiResult {
+    catch_panic(|| {
+        let stats = ffi_stats_out(options);
+        let options = match encode_options_from_ffi(options, true) {
+            Ok(options) => options,
+            Err(result) => return result,
+        };
+
+        let end_ms = (end_ms > 0).then_some(end_ms);
+        match path_arg(input_path, "input path") {
+            Ok(input_path) => stats_result(trim(input_path, start_ms, end_ms, &options), stats),
+            Err(result) => result,
+        }
+    })
+}
+
+/// Join videos one after another
+///
+/// # Safety
+/// - `paths` must point to `path_count` valid null-terminated strings
+/// - `options` must point to an `FfiEncodeOptions` initialized with `minmpeg_encode_options_init`
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_concat(
+    paths: *const *const c_char,
+    path_count: size_t,
+    options: *const FfiEncodeOptions,
+) -> FfiResult {
+    catch_panic(|| {
+        let stats = ffi_stats_out(options);
+        let options = match encode_options_from_ffi(options, true) {
+            Ok(options) => options,
+            Err(result) => return result,
+        };
+
+        match video_paths(paths, path_count) {
+            Ok(paths) => stats_result(concat(&paths, &options), stats),
+            Err(result) => result,
+        }
+    })
+}
+
+/// Save the frame shown at `time_ms` as an image, in the format given by the extension
+///
+/// # Safety
+/// - `input_path` and `output_path` must be valid null-terminated strings
+/// - `ffmpeg_path` must be a valid null-terminated string or null
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_thumbnail(
+    input_path: *const c_char,
+    time_ms: u64,
+    output_path: *const c_char,
+    ffmpeg_path: *const c_char,
+) -> FfiResult {
+    catch_panic(|| {
+        let input_path = match path_arg(input_path, "input path") {
+            Ok(path) => path,
+            Err(result) => return result,
+        };
+
+        let output_path = match path_arg(output_path, "output path") {
+            Ok(path) => path,
+            Err(result) => return result,
+        };
+
+        let ffmpeg_path = if ffmpeg_path.is_null() {
+            None
+        } else {
+            match path_arg(ffmpeg_path, "ffmpeg path") {
+                Ok(path) => Some(path),
+                Err(result) => return result,
+            }
+        };
+
+        to_ffi_result(thumbnail_to_file(
+            input_path,
+            time_ms,
+            output_path,
+            ffmpeg_path,
+        ))
+    })
+}
@@ -0,0 +1,233 @@
+//! FFI entry points taking UTF-16 paths, for Windows callers
+//!
+//! Windows paths may hold unpaired surrogates, which UTF-8 cannot represent, so these
+//! take paths as the wide strings Win32 hands out.
+
+use super::{
+    catch_panic, encode_options_from_ffi, ffi_color, ffi_stats_out, stats_result, FfiColor,
+    FfiEncodeOptions, FfiResult,
+};
+use crate::error::ErrorCode;
+use crate::juxtapose::juxtapose_to;
+use crate::muxer::MemoryOutput;
+use crate::slideshow::slideshow_to;
+use crate::{EncodeOptions, EncodeStats, Error, Layout, Result, SlideEntry, SlideSource};
+use libc::size_t;
+use std::fs;
+use std::path::PathBuf;
+use std::slice;
+
+/// FFI slide entry with a UTF-16 path
+#[repr(C)]
+pub struct FfiSlideEntryW {
+    pub path: *const u16,
+    pub duration_ms: u32,
+}
+
+/// Code units of a null-terminated UTF-16 string, without the terminator
+pub(crate) unsafe fn wide_units<'a>(wide: *const u16) -> &'a [u16] {
+    let mut len = 0;
+    while *wide.add(len) != 0 {
+        len += 1;
+    }
+    slice::from_raw_parts(wide, len)
+}
+
+/// Convert a required UTF-16 path argument
+///
+/// Windows paths are taken as they are, unpaired surrogates included. Elsewhere paths
+/// are strings, so they must be valid UTF-16.
+unsafe fn wide_path(wide: *const u16, name: &str) -> std::result::Result<PathBuf, FfiResult> {
+    if wide.is_null() {
+        return Err(FfiResult::error(
+            ErrorCode::InvalidInput,
+            &format!("Missing {}", name),
+        ));
+    }
+
+    let units = wide_units(wide);
+    #[cfg(windows)]
+    let path = {
+        use std::os::windows::ffi::OsStringExt;
+        Some(PathBuf::from(std::ffi::OsString::from_wide(units)))
+    };
+    #[cfg(not(windows))]
+    let path = String::from_utf16(units).ok().map(PathBuf::from);
+    path.ok_or_else(|| FfiResult::error(ErrorCode::InvalidInput, &format!("Invalid {}", name)))
+}
+
+/// Source of a slide at `path`
+///
+/// [`SlideSource::Path`] holds a string, so a file whose path is not valid Unicode is
+/// read here instead.
+fn slide_source(path: PathBuf) -> Result<SlideSource> {
+    match path.to_str() {
+        Some(path) => Ok(SlideSource::Path(path.to_string())),
+        None => fs::read(&path)
+            .map(SlideSource::Bytes)
+            .map_err(|e| Error::Io(e).with_path(&path)),
+    }
+}
+
+/// Run `operation`, writing its output to `output_path`
+///
+/// A path that is not valid Unicode cannot be [`EncodeOptions::output_path`], so the
+/// output is encoded in memory and written to the path afterwards.
+fn write_output(
+    output_path: PathBuf,
+    mut options: EncodeOptions,
+    operation: impl FnOnce(&EncodeOptions, Option<&MemoryOutput>) -> Result<EncodeStats>,
+) -> Result<EncodeStats> {
+    match output_path.into_os_string().into_string() {
+        Ok(path) => {
+            options.output_path = path;
+            operation(&options, None)
+        }
+        Err(path) => {
+            let output = MemoryOutput::new();
+            let stats = operation(&options, Some(&output))?;
+            fs::write(&path, output.take()).map_err(|e| Error::Io(e).with_path(&path))?;
+            Ok(stats)
+        }
+    }
+}
+
+/// Create a slideshow video from images, with UTF-16 paths
+///
+/// `output_path` takes the place of `options->output_path`, which is ignored. An output
+/// path that is not valid Unicode cannot be used for HLS, and its output is not verified.
+///
+/// # Safety
+/// - `entries` must point to a valid array of `FfiSlideEntryW` with `entry_count` elements
+/// - Paths must be valid null-terminated UTF-16 strings
+/// - `options` must point to an `FfiEncodeOptions` initialized with `minmpeg_encode_options_init`
+/// - Pointers inside `options` follow the same rules as `minmpeg_slideshow_with_progress`
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_slideshow_w(
+    entries: *const FfiSlideEntryW,
+    entry_count: size_t,
+    output_path: *const u16,
+    options: *const FfiEncodeOptions,
+) -> FfiResult {
+    catch_panic(|| {
+        let stats = ffi_stats_out(options);
+        let options = match encode_options_from_ffi(options, false) {
+            Ok(options) => options,
+            Err(result) => return result,
+        };
+        let output_path = match wide_path(output_path, "output path") {
+            Ok(path) => path,
+            Err(result) => return result,
+        };
+
+        if entries.is_null() || entry_count == 0 {
+            return FfiResult::error(ErrorCode::InvalidInput, "No slides provided");
+        }
+        let mut slides = Vec::with_capacity(entry_count);
+        for entry in slice::from_raw_parts(entries, entry_count) {
+            let source = match wide_path(entry.path, "slide path").map(slide_source) {
+                Ok(Ok(source)) => source,
+                Ok(Err(e)) => return FfiResult::from_error(&e),
+                Err(result) => return result,
+            };
+            slides.push(SlideEntry {
+                source,
+                duration_ms: entry.duration_ms,
+                transition: None,
+                letterbox_color: None,
+                caption: None,
+                rotation: None,
+            });
+        }
+
+        let result = write_output(output_path, options, |options, memory| {
+            slideshow_to(&slides, options, memory)
+        });
+        stats_result(result, stats)
+    })
+}
+
+/// Combine two videos side by side, with UTF-16 paths
+///
+/// `output_path` takes the place of `options->output_path`, which is ignored. An output
+/// path that is not valid Unicode cannot be used for HLS.
+///
+/// # Safety
+/// - Paths must be valid null-terminated UTF-16 strings
+/// - `background` can be null (defaults to white)
+/// - `options` must point to an `FfiEncodeOptions` initialized with `minmpeg_encode_options_init`
+/// - Pointers inside `options` follow the same rules as `minmpeg_juxtapose_with_progress`
+#[no_mangle]
+pub unsafe extern "C" fn minmpeg_juxtapose_w(
+    left_path: *const u16,
+    right_path: *const u16,
+    background: *const FfiColor,
+    output_path: *const u16,
+    options: *const FfiEncodeOptions,
+) -> FfiResult {
+    catch_panic(|| {
+        let stats = ffi_stats_out(options);
+        let options = match encode_options_from_ffi(options, false) {
+            Ok(options) => options,
+            Err(result) => return result,
+        };
+        let paths = [
+            wide_path(left_path, "left video path"),
+            wide_path(right_path, "right video path"),
+            wide_path(output_path, "output path"),
+        ];
+        let [left_path, right_path, output_path] = match paths {
+            [Ok(left), Ok(right), Ok(output)] => [left, right, output],
+            [Err(result), ..] | [_, Err(result), _] | [.., Err(result)] => return result,
+        };
+
+        let background = ffi_color(background);
+        let result = write_output(output_path, options, |options, memory| {
+            juxtapose_to(
+                &[left_path, right_path],
+                Layout::default(),
+                options,
+                background,
+                memory,
+            )
+        });
+        stats_result(result, stats)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain([0]).collect()
+    }
+
+    #[test]
+    fn test_wide_path() {
+        let path = wide("C:\\動画\\slide.png");
+        let path = unsafe { wide_path(path.as_ptr(), "slide path") }
+            .ok()
+            .unwrap();
+        assert_eq!(path, PathBuf::from("C:\\動画\\slide.png"));
+
+        let mut result = unsafe { wide_path(std::ptr::null(), "slide path") }.unwrap_err();
+        assert_eq!(result.code, ErrorCode::InvalidInput);
+        unsafe { super::super::minmpeg_free_result(&mut result) };
+    }
+
+    #[test]
+    fn test_slideshow_w_rejects_missing_slides() {
+        let mut options = std::mem::MaybeUninit::<FfiEncodeOptions>::uninit();
+        let options = unsafe {
+            super::super::minmpeg_encode_options_init(options.as_mut_ptr());
+            options.assume_init()
+        };
+        let output = wide("out.webm");
+
+        let mut result =
+            unsafe { minmpeg_slideshow_w(std::ptr::null(), 0, output.as_ptr(), &options) };
+        assert_eq!(result.code, ErrorCode::InvalidInput);
+        unsafe { super::super::minmpeg_free_result(&mut result) };
+    }
+}
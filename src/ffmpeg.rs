@@ -0,0 +1,158 @@
+//! Helpers for locating the ffmpeg/ffprobe executables
+
+use crate::logging::{log_debug, log_trace};
+use crate::runtime;
+use crate::{Error, Result};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// Number of trailing stderr lines kept for error messages
+const STDERR_LINES: usize = 20;
+
+/// Find ffmpeg executable
+pub(crate) fn find_ffmpeg(custom_path: Option<&str>) -> Result<String> {
+    // Browsers cannot start processes
+    if cfg!(target_arch = "wasm32") {
+        return Err(Error::Unsupported(
+            "ffmpeg cannot be run on wasm32".to_string(),
+        ));
+    }
+
+    if let Some(path) = custom_path {
+        if Path::new(path).exists() {
+            return Ok(path.to_string());
+        }
+        return Err(Error::ffmpeg(format!("FFmpeg not found at: {}", path)));
+    }
+
+    for path in &runtime::config().ffmpeg_paths {
+        if Command::new(path)
+            .arg("-version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok()
+        {
+            log_debug!("Found ffmpeg at {}", path);
+            return Ok(path.clone());
+        }
+    }
+
+    Err(Error::ffmpeg("FFmpeg not found in PATH"))
+}
+
+/// Derive the ffprobe path from an ffmpeg path
+///
+/// ffprobe is expected to live next to ffmpeg (e.g. `/usr/bin/ffmpeg` -> `/usr/bin/ffprobe`).
+pub(crate) fn ffprobe_path(ffmpeg: &str) -> String {
+    match ffmpeg.strip_suffix("ffmpeg") {
+        Some(prefix) => format!("{}ffprobe", prefix),
+        None => match ffmpeg.strip_suffix("ffmpeg.exe") {
+            Some(prefix) => format!("{}ffprobe.exe", prefix),
+            None => "ffprobe".to_string(),
+        },
+    }
+}
+
+/// Last lines written to stderr by an ffmpeg process
+///
+/// Lines are read on a background thread so ffmpeg never blocks on a full pipe, and each
+/// line is sent to the log callback at `LogLevel::Trace`.
+pub(crate) struct StderrTail {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl StderrTail {
+    /// Start reading the piped stderr of `child`
+    pub fn capture(child: &mut Child) -> Self {
+        let lines = Arc::new(Mutex::new(VecDeque::new()));
+        let reader = child.stderr.take().map(|stderr| {
+            let lines = Arc::clone(&lines);
+            std::thread::spawn(move || {
+                for line in BufReader::new(stderr).lines() {
+                    let Ok(line) = line else { break };
+                    log_trace!("ffmpeg: {}", line);
+                    let mut lines = lines.lock().unwrap_or_else(|e| e.into_inner());
+                    if lines.len() == STDERR_LINES {
+                        lines.pop_front();
+                    }
+                    lines.push_back(line);
+                }
+            })
+        });
+        Self { lines, reader }
+    }
+
+    /// Error for a failed process, carrying the captured lines
+    ///
+    /// Waits for the process to exit so its final messages are included.
+    pub fn error(&mut self, child: &mut Child, message: impl Into<String>) -> Error {
+        let _ = child.wait();
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+        let lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        Error::Ffmpeg {
+            message: message.into(),
+            stderr: lines.iter().cloned().collect(),
+        }
+    }
+
+    /// Wait for the process to exit, failing if it did not exit successfully
+    pub fn wait(&mut self, child: &mut Child) -> Result<()> {
+        let status = child
+            .wait()
+            .map_err(|e| Error::ffmpeg(format!("FFmpeg process error: {}", e)))?;
+        if !status.success() {
+            return Err(self.error(child, format!("ffmpeg failed ({})", status)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffprobe_path() {
+        assert_eq!(ffprobe_path("ffmpeg"), "ffprobe");
+        assert_eq!(
+            ffprobe_path("/opt/ffmpeg/bin/ffmpeg"),
+            "/opt/ffmpeg/bin/ffprobe"
+        );
+        assert_eq!(
+            ffprobe_path("C:\\ffmpeg\\ffmpeg.exe"),
+            "C:\\ffmpeg\\ffprobe.exe"
+        );
+        assert_eq!(ffprobe_path("/usr/bin/avconv"), "ffprobe");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stderr_tail_error() {
+        let mut child = Command::new("sh")
+            .args([
+                "-c",
+                "for i in $(seq 1 30); do echo line $i >&2; done; exit 3",
+            ])
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stderr = StderrTail::capture(&mut child);
+
+        match stderr.wait(&mut child) {
+            Err(Error::Ffmpeg { stderr, .. }) => {
+                assert_eq!(stderr.len(), STDERR_LINES);
+                assert_eq!(stderr.first().unwrap(), "line 11");
+                assert_eq!(stderr.last().unwrap(), "line 30");
+            }
+            other => panic!("expected ffmpeg error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Filters applied to every frame before it is encoded
+
+use crate::Result;
+use std::fmt;
+use std::sync::Arc;
+
+/// RGBA frame passed through a filter chain, modified in place
+pub struct FilterFrame<'a> {
+    /// Frame width in pixels
+    pub width: u32,
+    /// Frame height in pixels
+    pub height: u32,
+    /// Index of the frame in the output video
+    pub index: u64,
+    /// Presentation time of the frame in milliseconds
+    pub pts_ms: u64,
+    /// RGBA pixels, `width * height * 4` bytes
+    pub data: &'a mut [u8],
+}
+
+/// Effect applied to each frame, such as a watermark, a timestamp or a color lookup
+///
+/// Filters receive the frame produced by the operation and replace its pixels; the size
+/// of a frame cannot change. They may be called from several threads at once, for frames
+/// of different segments, and in any order across segments. Closures taking a
+/// [`FilterFrame`] are filters too.
+pub trait FrameFilter: Send + Sync {
+    /// Filter `frame` in place
+    fn apply(&self, frame: &mut FilterFrame<'_>) -> Result<()>;
+}
+
+impl<F> FrameFilter for F
+where
+    F: Fn(&mut FilterFrame<'_>) -> Result<()> + Send + Sync,
+{
+    fn apply(&self, frame: &mut FilterFrame<'_>) -> Result<()> {
+        self(frame)
+    }
+}
+
+/// Filters applied one after another to every frame of slideshows, juxtapositions,
+/// transcodes and rendered frames
+///
+/// Set a chain as [`EncodeOptions::filters`]; clones share the same filters.
+///
+/// [`EncodeOptions::filters`]: crate::EncodeOptions::filters
+#[derive(Clone, Default)]
+pub struct FilterChain(Vec<Arc<dyn FrameFilter>>);
+
+impl FilterChain {
+    /// Create an empty chain, leaving frames as they are
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `filter`, applied after the filters already in the chain
+    pub fn with<F: FrameFilter + 'static>(mut self, filter: F) -> Self {
+        self.0.push(Arc::new(filter));
+        self
+    }
+
+    /// Number of filters in the chain
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check if the chain has no filters
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Apply every filter to `frame` in order, stopping at the first error
+    pub fn apply(&self, frame: &mut FilterFrame<'_>) -> Result<()> {
+        self.0.iter().try_for_each(|filter| filter.apply(frame))
+    }
+
+    /// Apply the chain to the RGBA `data` of output frame `index`
+    pub(crate) fn apply_to(
+        &self,
+        (width, height): (u32, u32),
+        index: u64,
+        pts_ms: u64,
+        data: &mut [u8],
+    ) -> Result<()> {
+        self.apply(&mut FilterFrame {
+            width,
+            height,
+            index,
+            pts_ms,
+            data,
+        })
+    }
+}
+
+impl fmt::Debug for FilterChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FilterChain")
+            .field("filters", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn test_filter_chain_applies_in_order() {
+        let chain = FilterChain::new()
+            .with(|frame: &mut FilterFrame<'_>| {
+                frame.data.fill(10);
+                Ok(())
+            })
+            .with(|frame: &mut FilterFrame<'_>| {
+                let index = frame.index as u8;
+                frame.data.iter_mut().for_each(|v| *v += index);
+                Ok(())
+            });
+        assert_eq!(chain.len(), 2);
+
+        let mut data = vec![0u8; 8];
+        let mut frame = FilterFrame {
+            width: 2,
+            height: 1,
+            index: 3,
+            pts_ms: 100,
+            data: &mut data,
+        };
+        chain.apply(&mut frame).unwrap();
+        assert_eq!(data, vec![13; 8]);
+
+        let failing = chain.with(|_: &mut FilterFrame<'_>| Err(Error::Cancelled));
+        let mut frame = FilterFrame {
+            width: 2,
+            height: 1,
+            index: 0,
+            pts_ms: 0,
+            data: &mut data,
+        };
+        assert!(matches!(failing.apply(&mut frame), Err(Error::Cancelled)));
+    }
+}
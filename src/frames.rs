@@ -0,0 +1,241 @@
+//! Encoding of frames rendered by the caller
+
+use crate::budget::MemoryBudget;
+use crate::encoder::{EncoderConfig, Frame, Packet, PooledEncoder};
+use crate::fade::with_fade;
+use crate::image_loader::rgba_len;
+use crate::logging::{log_info, trace_span};
+use crate::muxer::{check_output, write_packets, MemoryOutput, MuxerConfig};
+use crate::stats::{EncodeStats, StatsRecorder};
+use crate::validate::StrictCheck;
+use crate::{EncodeOptions, Error, Result, Stage};
+
+/// Dimensions, frame rate and length of a video rendered frame by frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameFormat {
+    /// Frame width in pixels (must be even)
+    pub width: u32,
+    /// Frame height in pixels (must be even)
+    pub height: u32,
+    /// Frames per second
+    pub fps: u32,
+    /// Number of frames to encode
+    pub frame_count: u64,
+}
+
+impl FrameFormat {
+    /// Size of one RGBA frame in bytes
+    pub fn frame_size(&self) -> usize {
+        (self.width as usize)
+            .saturating_mul(self.height as usize)
+            .saturating_mul(4)
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.width == 0 || self.height == 0 || self.width % 2 != 0 || self.height % 2 != 0 {
+            return Err(Error::InvalidInput(format!(
+                "Frame dimensions {}x{} must be even and non-zero",
+                self.width, self.height
+            )));
+        }
+        rgba_len(self.width, self.height)?;
+        if self.fps == 0 {
+            return Err(Error::InvalidInput(
+                "Frame rate must be non-zero".to_string(),
+            ));
+        }
+        if self.frame_count == 0 {
+            return Err(Error::InvalidInput("No frames to encode".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Encode frames drawn by `render` to `options.output_path`
+///
+/// `render` is called with each frame index in order and a zeroed RGBA buffer of
+/// `format.frame_size()` bytes to fill.
+pub fn encode_frames<F>(
+    format: FrameFormat,
+    options: &EncodeOptions,
+    render: F,
+) -> Result<EncodeStats>
+where
+    F: FnMut(u64, &mut [u8]) -> Result<()>,
+{
+    encode_frames_to(format, options, None, render_with(render))
+}
+
+/// Encode frames drawn by `render` in memory and return the encoded bytes
+///
+/// `options.output_path` is ignored.
+pub fn encode_frames_to_memory<F>(
+    format: FrameFormat,
+    options: &EncodeOptions,
+    render: F,
+) -> Result<Vec<u8>>
+where
+    F: FnMut(u64, &mut [u8]) -> Result<()>,
+{
+    let output = MemoryOutput::new();
+    encode_frames_to(format, options, Some(&output), render_with(render))?;
+    Ok(output.take())
+}
+
+/// Adapt a render callback so it is handed a zeroed buffer for every frame
+pub(crate) fn render_with<F>(mut render: F) -> impl FnMut(u64, &mut [u8]) -> Result<()>
+where
+    F: FnMut(u64, &mut [u8]) -> Result<()>,
+{
+    move |frame_idx, data| {
+        data.fill(0);
+        render(frame_idx, data)
+    }
+}
+
+/// Encode RGBA frames drawn by `next_frame`, writing to `memory` if set or to
+/// `options.output_path` otherwise
+///
+/// `next_frame` fills one buffer of `format.frame_size()` bytes that is reused for every
+/// frame and still holds the previous frame, as filtered by `options.filters`, when called.
+pub(crate) fn encode_frames_to(
+    format: FrameFormat,
+    options: &EncodeOptions,
+    memory: Option<&MemoryOutput>,
+    mut next_frame: impl FnMut(u64, &mut [u8]) -> Result<()>,
+) -> Result<EncodeStats> {
+    let _span = trace_span!("encode_frames", frames = format.frame_count);
+    let mut recorder = StatsRecorder::start();
+
+    options.validate_video()?;
+    format.validate()?;
+    check_output(options.container, memory)?;
+
+    if let Some(mut check) = StrictCheck::new(options) {
+        check.total_duration(format.frame_count.saturating_mul(1000) / format.fps as u64);
+        check.finish()?;
+    }
+
+    log_info!(
+        "Encoding {} frames at {}x{}, {} fps",
+        format.frame_count,
+        format.width,
+        format.height,
+        format.fps
+    );
+
+    let last_pts_ms = format.frame_count.saturating_sub(1) * 1000 / format.fps as u64;
+    let faded = with_fade(options, last_pts_ms);
+    let options: &EncodeOptions = &faded;
+
+    let encoder_config = EncoderConfig {
+        width: format.width,
+        height: format.height,
+        fps: format.fps,
+        quality: options.quality,
+        bitrate_kbps: options.bitrate_kbps.filter(|&kbps| kbps > 0),
+        rate_control: options.rate_control,
+        keyframe_interval: options.keyframe_interval(format.fps),
+        color_space: options.color_space,
+        pixel_range: options.pixel_range,
+        deterministic: options.deterministic,
+        priority: options.priority,
+        threads: options.threads(),
+        av1_backend: options.av1_backend,
+    };
+
+    let mut encoder = PooledEncoder::open(options, encoder_config)?;
+    recorder.prepared();
+
+    // Collect all packets first (to get SPS/PPS for H.264 muxer)
+    let mut all_packets: Vec<Packet> = Vec::new();
+    let mut budget = MemoryBudget::new(options);
+    let mut data = vec![0u8; format.frame_size()];
+
+    {
+        let _span = trace_span!("encode");
+
+        for frame_idx in 0..format.frame_count {
+            options.check_cancelled()?;
+
+            let pts_ms = frame_idx * 1000 / format.fps as u64;
+            next_frame(frame_idx, &mut data)
+                .and_then(|()| {
+                    let size = (format.width, format.height);
+                    options.filters.apply_to(size, frame_idx, pts_ms, &mut data)
+                })
+                .map_err(|e| e.with_frame(frame_idx).during(Stage::Rendering))?;
+
+            let frame = Frame {
+                width: format.width,
+                height: format.height,
+                data: &data,
+                pts_ms,
+            };
+
+            let packets = encoder
+                .encode(&frame)
+                .map_err(|e| e.with_frame(frame_idx).during(Stage::Encoding))?;
+            budget.reserve_packets(&packets)?;
+            all_packets.extend(packets);
+
+            options.report_progress(frame_idx + 1, format.frame_count);
+        }
+
+        // Flush encoder
+        let flush_packets = encoder.flush()?;
+        budget.reserve_packets(&flush_packets)?;
+        all_packets.extend(flush_packets);
+    }
+
+    recorder.encoded(format.frame_count, &all_packets);
+
+    let muxer_config = MuxerConfig {
+        width: format.width,
+        height: format.height,
+        fps: format.fps,
+        codec: options.codec,
+        codec_config: encoder.codec_config(),
+        pps: encoder.pps(),
+        color_space: options.color_space,
+        pixel_range: options.pixel_range,
+    };
+
+    let output_bytes = write_packets(options, memory, muxer_config, all_packets)?;
+    Ok(recorder.finish(output_bytes, format.fps, encoder.backend()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_format_validate() {
+        let format = FrameFormat {
+            width: 64,
+            height: 48,
+            fps: 30,
+            frame_count: 10,
+        };
+        assert!(format.validate().is_ok());
+        assert_eq!(format.frame_size(), 64 * 48 * 4);
+
+        for invalid in [
+            FrameFormat {
+                width: 63,
+                ..format
+            },
+            FrameFormat {
+                height: 0,
+                ..format
+            },
+            FrameFormat { fps: 0, ..format },
+            FrameFormat {
+                frame_count: 0,
+                ..format
+            },
+        ] {
+            assert!(matches!(invalid.validate(), Err(Error::InvalidInput(_))));
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! HEIC/HEIF images, decoded with ImageIO on macOS
+
+use crate::image_loader::LoadedImage;
+use crate::{Error, Result};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Major brands of the `ftyp` box of HEIF images, such as iPhone photos
+const BRANDS: [&[u8; 4]; 8] = [
+    b"heic", b"heix", b"hevc", b"hevx", b"heim", b"heis", b"mif1", b"msf1",
+];
+
+/// Check if `bytes` start like a HEIF image
+pub(crate) fn is_heif(bytes: &[u8]) -> bool {
+    bytes.len() >= 12 && &bytes[4..8] == b"ftyp" && BRANDS.iter().any(|b| &bytes[8..12] == *b)
+}
+
+/// Check if the file at `path` is a HEIF image, reading only its first bytes
+pub(crate) fn is_heif_file(path: &Path) -> Result<bool> {
+    let mut header = [0u8; 12];
+    let mut file = File::open(path)?;
+    let mut read = 0;
+    while read < header.len() {
+        match file.read(&mut header[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(is_heif(&header[..read]))
+}
+
+/// Decode a HEIF image, which needs ImageIO on macOS
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn decode(_bytes: &[u8]) -> Result<LoadedImage> {
+    Err(Error::Unsupported(
+        "HEIC/HEIF images can only be decoded on macOS".to_string(),
+    ))
+}
+
+/// Decode a HEIF image with ImageIO, drawn into an sRGB bitmap
+#[cfg(target_os = "macos")]
+pub(crate) fn decode(bytes: &[u8]) -> Result<LoadedImage> {
+    use crate::image_loader::rgba_len;
+    use std::ffi::c_void;
+    use std::ptr;
+
+    #[repr(C)]
+    struct CGRect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFDataCreate(allocator: *const c_void, bytes: *const u8, length: isize) -> *mut c_void;
+        fn CFRelease(cf: *mut c_void);
+    }
+
+    #[link(name = "ImageIO", kind = "framework")]
+    extern "C" {
+        fn CGImageSourceCreateWithData(data: *const c_void, options: *const c_void) -> *mut c_void;
+        fn CGImageSourceCreateImageAtIndex(
+            source: *const c_void,
+            index: usize,
+            options: *const c_void,
+        ) -> *mut c_void;
+    }
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        static kCGColorSpaceSRGB: *const c_void;
+
+        fn CGImageGetWidth(image: *const c_void) -> usize;
+        fn CGImageGetHeight(image: *const c_void) -> usize;
+        fn CGImageRelease(image: *const c_void);
+        fn CGColorSpaceCreateWithName(name: *const c_void) -> *mut c_void;
+        fn CGColorSpaceRelease(space: *const c_void);
+        fn CGBitmapContextCreate(
+            data: *mut c_void,
+            width: usize,
+            height: usize,
+            bits_per_component: usize,
+            bytes_per_row: usize,
+            space: *const c_void,
+            bitmap_info: u32,
+        ) -> *mut c_void;
+        fn CGContextDrawImage(context: *mut c_void, rect: CGRect, image: *const c_void);
+        fn CGContextRelease(context: *const c_void);
+    }
+
+    /// RGBA byte order with premultiplied alpha, the only RGBA layout bitmaps support
+    const K_CG_IMAGE_ALPHA_PREMULTIPLIED_LAST: u32 = 1;
+    const K_CG_BITMAP_BYTE_ORDER_32_BIG: u32 = 4 << 12;
+
+    let length = isize::try_from(bytes.len())
+        .map_err(|_| Error::InvalidInput("HEIF image is too large".to_string()))?;
+
+    // SAFETY: every object created here is released before returning, and the bitmap
+    // context draws into `data`, which outlives it
+    unsafe {
+        let cf_data = CFDataCreate(ptr::null(), bytes.as_ptr(), length);
+        if cf_data.is_null() {
+            return Err(Error::Decode("Failed to copy HEIF image data".to_string()));
+        }
+        let source = CGImageSourceCreateWithData(cf_data, ptr::null());
+        CFRelease(cf_data);
+        if source.is_null() {
+            return Err(Error::Decode("Failed to read HEIF image".to_string()));
+        }
+        let image = CGImageSourceCreateImageAtIndex(source, 0, ptr::null());
+        CFRelease(source);
+        if image.is_null() {
+            return Err(Error::Decode("Failed to decode HEIF image".to_string()));
+        }
+
+        let (width, height) = (CGImageGetWidth(image), CGImageGetHeight(image));
+        let size = u32::try_from(width)
+            .ok()
+            .zip(u32::try_from(height).ok())
+            .map(|(width, height)| (width, height, rgba_len(width, height)));
+        let (width32, height32, len) = match size {
+            Some((w, h, Ok(len))) if w > 0 && h > 0 => (w, h, len),
+            _ => {
+                CGImageRelease(image);
+                return Err(Error::InvalidInput(format!(
+                    "Invalid HEIF image dimensions: {}x{}",
+                    width, height
+                )));
+            }
+        };
+
+        let mut data = vec![0u8; len];
+        let space = CGColorSpaceCreateWithName(kCGColorSpaceSRGB);
+        let context = CGBitmapContextCreate(
+            data.as_mut_ptr() as *mut c_void,
+            width,
+            height,
+            8,
+            width * 4,
+            space,
+            K_CG_IMAGE_ALPHA_PREMULTIPLIED_LAST | K_CG_BITMAP_BYTE_ORDER_32_BIG,
+        );
+        CGColorSpaceRelease(space);
+        if context.is_null() {
+            CGImageRelease(image);
+            return Err(Error::Decode(
+                "Failed to create a bitmap for the HEIF image".to_string(),
+            ));
+        }
+        let rect = CGRect {
+            x: 0.0,
+            y: 0.0,
+            width: width as f64,
+            height: height as f64,
+        };
+        CGContextDrawImage(context, rect, image);
+        CGContextRelease(context);
+        CGImageRelease(image);
+
+        unpremultiply(&mut data);
+        LoadedImage::from_rgba(width32, height32, data)
+    }
+}
+
+/// Divide the color of RGBA `data` by its alpha
+#[cfg(target_os = "macos")]
+fn unpremultiply(data: &mut [u8]) {
+    for pixel in data.chunks_exact_mut(4) {
+        let a = pixel[3] as u32;
+        if a == 0 || a == 255 {
+            continue;
+        }
+        for channel in &mut pixel[..3] {
+            *channel = ((*channel as u32 * 255 + a / 2) / a).min(255) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Start of the `ftyp` box of an iPhone photo
+    const HEIC_HEADER: &[u8] = b"\0\0\0\x18ftypheic\0\0\0\0mif1heic";
+
+    #[test]
+    fn test_is_heif() {
+        assert!(is_heif(HEIC_HEADER));
+        assert!(!is_heif(b"\0\0\0\x1cftypavif\0\0\0\0"));
+        assert!(!is_heif(b"\x89PNG\r\n\x1a\n"));
+        assert!(!is_heif(b""));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn test_heif_needs_macos() {
+        assert!(matches!(decode(HEIC_HEADER), Err(Error::Unsupported(_))));
+        assert!(matches!(
+            LoadedImage::from_bytes(HEIC_HEADER),
+            Err(Error::Unsupported(_))
+        ));
+    }
+}
@@ -0,0 +1,183 @@
+//! Decoded and resized images shared between slides and jobs
+
+use crate::image_loader::{LoadedImage, ScaleMode};
+use crate::{Color, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+/// Image file as it was when loaded: its path and modification time
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct ImageSource {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+/// Cached image: a file as decoded (`size` of `None`) or scaled to `size`, with the
+/// scale mode and letterbox color
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    source: ImageSource,
+    size: Option<(u32, u32, ScaleMode, Color)>,
+}
+
+/// Slide images kept across slides and operations
+///
+/// When the same logo or background appears in many slides or videos, it is decoded and
+/// resized once. Images are keyed by path, modification time and size, so a file changed
+/// on disk is loaded again. Set a cache as [`EncodeOptions::image_cache`]; clones share
+/// the same images, which stay cached until [`clear`](Self::clear) or the last clone is
+/// dropped.
+///
+/// [`EncodeOptions::image_cache`]: crate::EncodeOptions::image_cache
+#[derive(Clone, Default)]
+pub struct ImageCache(Arc<Mutex<HashMap<CacheKey, Arc<LoadedImage>>>>);
+
+impl ImageCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of cached images, counting each size of a file
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Check if no images are cached
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Drop every cached image
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Load the image at `path`, decoding it only if not cached
+    ///
+    /// Images are decoded without holding the lock, so two threads loading a new file at
+    /// the same time may both decode it.
+    pub(crate) fn load(&self, path: &str) -> Result<(Arc<LoadedImage>, ImageSource)> {
+        let source = ImageSource {
+            path: PathBuf::from(path),
+            // Without a modification time the file is assumed unchanged
+            modified: fs::metadata(path).and_then(|m| m.modified()).ok(),
+        };
+        let key = CacheKey {
+            source: source.clone(),
+            size: None,
+        };
+
+        let image = self.get_or_insert_with(key, || LoadedImage::from_path(path))?;
+        Ok((image, source))
+    }
+
+    /// Resize `image`, loaded from `source`, unless a version scaled by `mode` is cached
+    pub(crate) fn resize(
+        &self,
+        source: &ImageSource,
+        image: &Arc<LoadedImage>,
+        (width, height): (u32, u32),
+        mode: ScaleMode,
+        letterbox: Color,
+    ) -> Result<Arc<LoadedImage>> {
+        if (image.width, image.height) == (width, height) {
+            return Ok(Arc::clone(image));
+        }
+        let key = CacheKey {
+            source: source.clone(),
+            size: Some((width, height, mode, letterbox)),
+        };
+        self.get_or_insert_with(key, || image.scale(width, height, mode, letterbox))
+    }
+
+    fn get_or_insert_with(
+        &self,
+        key: CacheKey,
+        create: impl FnOnce() -> Result<LoadedImage>,
+    ) -> Result<Arc<LoadedImage>> {
+        if let Some(image) = self.lock().get(&key) {
+            return Ok(Arc::clone(image));
+        }
+
+        let image = Arc::new(create()?);
+        Ok(Arc::clone(self.lock().entry(key).or_insert(image)))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<CacheKey, Arc<LoadedImage>>> {
+        // Entries are inserted whole, a panic cannot leave the map inconsistent
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for ImageCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImageCache")
+            .field("images", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_cache_reuses_decoded_and_resized_images() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logo.png");
+        image::RgbaImage::from_pixel(8, 6, image::Rgba([10, 20, 30, 255]))
+            .save(&path)
+            .unwrap();
+        let path = path.to_string_lossy();
+
+        let cache = ImageCache::new();
+        let (first, source) = cache.load(&path).unwrap();
+        let (second, _) = cache.load(&path).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let resized = cache
+            .resize(
+                &source,
+                &first,
+                (4, 4),
+                ScaleMode::Stretch,
+                Color::default(),
+            )
+            .unwrap();
+        assert_eq!((resized.width, resized.height), (4, 4));
+        assert!(Arc::ptr_eq(
+            &resized,
+            &cache
+                .resize(
+                    &source,
+                    &first,
+                    (4, 4),
+                    ScaleMode::Stretch,
+                    Color::default()
+                )
+                .unwrap()
+        ));
+        // The original size needs no entry of its own
+        assert!(Arc::ptr_eq(
+            &first,
+            &cache
+                .resize(&source, &first, (8, 6), ScaleMode::Fit, Color::default())
+                .unwrap()
+        ));
+        assert_eq!(cache.len(), 2);
+        // Each scale mode has its own entry
+        let filled = cache
+            .resize(&source, &first, (4, 4), ScaleMode::Fill, Color::default())
+            .unwrap();
+        assert!(!Arc::ptr_eq(&resized, &filled));
+        assert_eq!(cache.len(), 3);
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+}
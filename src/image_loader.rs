@@ -1,9 +1,56 @@
 //! Image loading utilities
 
-use crate::{Error, Result};
-use image::{DynamicImage, GenericImageView, ImageReader};
+use crate::{heif, Color, Error, Result};
+use image::{DynamicImage, GenericImageView, ImageDecoder, ImageFormat, ImageReader};
+use jpeg_decoder::PixelFormat;
+use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Read, Seek};
 use std::path::Path;
 
+/// Size in bytes of a `width` x `height` RGBA buffer
+pub(crate) fn rgba_len(width: u32, height: u32) -> Result<usize> {
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .ok_or_else(|| {
+            Error::InvalidInput(format!(
+                "Image dimensions {}x{} are too large",
+                width, height
+            ))
+        })
+}
+
+/// How slides of another aspect ratio are scaled to the video dimensions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
+pub enum ScaleMode {
+    /// Scale each dimension on its own, distorting the aspect ratio
+    #[default]
+    Stretch = 0,
+    /// Scale to fit inside, centered with bars of the letterbox color on two sides
+    Fit = 1,
+    /// Scale to cover, cropping the center
+    Fill = 2,
+}
+
+/// Clockwise rotation of an image by a quarter, half or three quarters of a turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Rotation {
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl Rotation {
+    /// Check if the rotation swaps the width and height
+    pub fn swaps_dimensions(self) -> bool {
+        self != Rotation::Rotate180
+    }
+}
+
 /// Loaded image in RGBA format
 #[derive(Debug, Clone)]
 pub struct LoadedImage {
@@ -17,12 +64,145 @@ pub struct LoadedImage {
 
 impl LoadedImage {
     /// Load an image from a file path
+    ///
+    /// HEIC/HEIF images are decoded with ImageIO on macOS and fail with
+    /// [`Error::Unsupported`] elsewhere.
     pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = path.as_ref();
+        if heif::is_heif_file(path)? {
+            return heif::decode(&std::fs::read(path)?);
+        }
+
+        Self::decode(ImageReader::open(path).map_err(Error::Io)?)
+    }
+
+    /// Load an image from a file path, decoding JPEGs at a reduced scale when they are
+    /// at least twice as large as `width` x `height`
+    ///
+    /// The image keeps its aspect ratio and is never decoded smaller than `width` x
+    /// `height` in either dimension, so it can still be scaled down to them. Other
+    /// formats load at full size as with [`LoadedImage::from_path`].
+    pub fn from_path_at_least<P: AsRef<Path>>(path: P, width: u32, height: u32) -> Result<Self> {
+        let path = path.as_ref();
+        let reader = ImageReader::open(path)
+            .and_then(|reader| reader.with_guessed_format())
+            .map_err(Error::Io)?;
+        if reader.format() == Some(ImageFormat::Jpeg) {
+            let file = BufReader::new(File::open(path)?);
+            if let Some(image) = Self::decode_jpeg_scaled(file, width, height) {
+                return Ok(image);
+            }
+        }
+        Self::from_path(path)
+    }
+
+    /// Decode a JPEG at the smallest DCT scale keeping it at least `width` x `height`,
+    /// or `None` if it cannot be decoded smaller
+    fn decode_jpeg_scaled<R: Read>(reader: R, width: u32, height: u32) -> Option<Self> {
+        let mut decoder = jpeg_decoder::Decoder::new(reader);
+        decoder.read_info().ok()?;
+        let info = decoder.info()?;
+        if !matches!(info.pixel_format, PixelFormat::L8 | PixelFormat::RGB24) {
+            return None;
+        }
+        let scaled = |len: u16, eighths: u32| (len as u32 * eighths).div_ceil(8);
+        let eighths = [1, 2, 4].into_iter().find(|&eighths| {
+            scaled(info.width, eighths) >= width && scaled(info.height, eighths) >= height
+        })?;
+        let requested = (scaled(info.width, eighths), scaled(info.height, eighths));
+        let (scaled_width, scaled_height) =
+            decoder.scale(requested.0 as u16, requested.1 as u16).ok()?;
+        if (scaled_width as u32, scaled_height as u32) != requested {
+            return None;
+        }
+
+        let pixels = decoder.decode().ok()?;
+        let data = match info.pixel_format {
+            PixelFormat::L8 => pixels.iter().flat_map(|&l| [l, l, l, 255]).collect(),
+            _ => pixels
+                .chunks_exact(3)
+                .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+                .collect(),
+        };
+        let mut image = Self::from_rgba(requested.0, requested.1, data).ok()?;
+        if let Some(icc) = decoder.icc_profile() {
+            image.convert_to_srgb(&icc);
+        }
+        Some(image)
+    }
+
+    /// Decode an image from encoded bytes (JPEG, PNG, WebP, GIF, and HEIC on macOS)
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if heif::is_heif(bytes) {
+            return heif::decode(bytes);
+        }
+        let reader = ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()
+            .map_err(Error::Io)?;
+        Self::decode(reader)
+    }
+
+    /// Decode an image, converting its colors to sRGB if it embeds an ICC profile
+    fn decode<R: BufRead + Seek>(reader: ImageReader<R>) -> Result<Self> {
+        let mut decoder = reader.into_decoder()?;
+        let icc = decoder.icc_profile()?;
+        let mut image = Self::from_dynamic_image(DynamicImage::from_decoder(decoder)?);
+        if let Some(icc) = icc {
+            image.convert_to_srgb(&icc);
+        }
+        Ok(image)
+    }
+
+    /// Convert the colors from the RGB color space of the ICC profile `icc` to sRGB
+    ///
+    /// Profiles that cannot be read or are not for RGB, such as CMYK profiles of JPEGs
+    /// already converted by the decoder, leave the colors as they are.
+    fn convert_to_srgb(&mut self, icc: &[u8]) {
+        let Ok(profile) = ColorProfile::new_from_slice(icc) else {
+            return;
+        };
+        if profile.color_space != DataColorSpace::Rgb {
+            return;
+        }
+        let Ok(transform) = profile.create_transform_8bit(
+            Layout::Rgba,
+            &ColorProfile::new_srgb(),
+            Layout::Rgba,
+            TransformOptions::default(),
+        ) else {
+            return;
+        };
+        let mut converted = vec![0; self.data.len()];
+        if transform.transform(&self.data, &mut converted).is_ok() {
+            self.data = converted;
+        }
+    }
+
+    /// Create from raw RGBA pixel data
+    pub fn from_rgba(width: u32, height: u32, data: Vec<u8>) -> Result<Self> {
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidInput(format!(
+                "Invalid image dimensions: {}x{}",
+                width, height
+            )));
+        }
 
-        let img = ImageReader::open(path).map_err(Error::Io)?.decode()?;
+        let expected = rgba_len(width, height)?;
+        if data.len() != expected {
+            return Err(Error::InvalidInput(format!(
+                "RGBA data is {} bytes, expected {} for {}x{}",
+                data.len(),
+                expected,
+                width,
+                height
+            )));
+        }
 
-        Ok(Self::from_dynamic_image(img))
+        Ok(Self {
+            width,
+            height,
+            data,
+        })
     }
 
     /// Create from a DynamicImage
@@ -38,58 +218,208 @@ impl LoadedImage {
         }
     }
 
+    /// Check the data matches the dimensions, which public fields do not guarantee
+    fn check_data(&self) -> Result<()> {
+        let expected = rgba_len(self.width, self.height)?;
+        if self.data.len() != expected {
+            return Err(Error::InvalidInput(format!(
+                "RGBA data is {} bytes, expected {} for {}x{}",
+                self.data.len(),
+                expected,
+                self.width,
+                self.height
+            )));
+        }
+        Ok(())
+    }
+
+    /// Copy the pixels into an `image` buffer, checking the data matches the dimensions
+    fn to_rgba_image(&self) -> Result<image::RgbaImage> {
+        self.check_data()?;
+        Ok(
+            image::RgbaImage::from_raw(self.width, self.height, self.data.clone())
+                .expect("data length was checked"),
+        )
+    }
+
+    /// Rotate the image clockwise by `rotation`
+    pub fn rotate(&self, rotation: Rotation) -> Result<Self> {
+        match rotation {
+            Rotation::Rotate90 => self.rotate90(),
+            Rotation::Rotate180 => self.rotate180(),
+            Rotation::Rotate270 => self.rotate270(),
+        }
+    }
+
+    /// Rotate the image a quarter turn clockwise
+    pub fn rotate90(&self) -> Result<Self> {
+        Ok(Self::from_rgba_image(image::imageops::rotate90(
+            &self.to_rgba_image()?,
+        )))
+    }
+
+    /// Rotate the image half a turn
+    pub fn rotate180(&self) -> Result<Self> {
+        Ok(Self::from_rgba_image(image::imageops::rotate180(
+            &self.to_rgba_image()?,
+        )))
+    }
+
+    /// Rotate the image a quarter turn counterclockwise
+    pub fn rotate270(&self) -> Result<Self> {
+        Ok(Self::from_rgba_image(image::imageops::rotate270(
+            &self.to_rgba_image()?,
+        )))
+    }
+
+    /// Mirror the image left to right
+    pub fn flip_horizontal(&self) -> Result<Self> {
+        Ok(Self::from_rgba_image(image::imageops::flip_horizontal(
+            &self.to_rgba_image()?,
+        )))
+    }
+
+    /// Mirror the image top to bottom
+    pub fn flip_vertical(&self) -> Result<Self> {
+        Ok(Self::from_rgba_image(image::imageops::flip_vertical(
+            &self.to_rgba_image()?,
+        )))
+    }
+
+    fn from_rgba_image(image: image::RgbaImage) -> Self {
+        Self {
+            width: image.width(),
+            height: image.height(),
+            data: image.into_raw(),
+        }
+    }
+
+    /// Copy the `width` x `height` region whose top left corner is at `x`, `y`
+    ///
+    /// Fails with [`Error::InvalidInput`] if the region is empty or extends past the image.
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Result<Self> {
+        let inside = x
+            .checked_add(width)
+            .is_some_and(|right| right <= self.width)
+            && y.checked_add(height)
+                .is_some_and(|bottom| bottom <= self.height);
+        if width == 0 || height == 0 || !inside {
+            return Err(Error::InvalidInput(format!(
+                "Crop of {}x{} at ({}, {}) is outside the {}x{} image",
+                width, height, x, y, self.width, self.height
+            )));
+        }
+        self.check_data()?;
+
+        let left = x as usize * 4;
+        let row_len = width as usize * 4;
+        let data = self
+            .data
+            .chunks_exact(self.width as usize * 4)
+            .skip(y as usize)
+            .take(height as usize)
+            .flat_map(|row| &row[left..left + row_len])
+            .copied()
+            .collect();
+        Ok(Self {
+            width,
+            height,
+            data,
+        })
+    }
+
     /// Resize the image to fit within the given dimensions
-    pub fn resize(&self, target_width: u32, target_height: u32) -> Self {
+    pub fn resize(&self, target_width: u32, target_height: u32) -> Result<Self> {
         if self.width == target_width && self.height == target_height {
-            return self.clone();
+            return Ok(self.clone());
         }
+        rgba_len(target_width, target_height)?;
 
-        let img = image::RgbaImage::from_raw(self.width, self.height, self.data.clone())
-            .expect("Invalid image data");
-
-        let dynamic = DynamicImage::ImageRgba8(img);
+        let dynamic = DynamicImage::ImageRgba8(self.to_rgba_image()?);
         let resized = dynamic.resize_exact(
             target_width,
             target_height,
             image::imageops::FilterType::Lanczos3,
         );
 
-        Self::from_dynamic_image(resized)
+        Ok(Self::from_dynamic_image(resized))
+    }
+
+    /// Resize the image to the given dimensions as `mode` scales it
+    /// Pads with the opaque `letterbox` color when fitting
+    pub fn scale(
+        &self,
+        target_width: u32,
+        target_height: u32,
+        mode: ScaleMode,
+        letterbox: Color,
+    ) -> Result<Self> {
+        match mode {
+            ScaleMode::Stretch => self.resize(target_width, target_height),
+            ScaleMode::Fit => self.resize_fit(
+                target_width,
+                target_height,
+                [letterbox.r, letterbox.g, letterbox.b, 255],
+            ),
+            ScaleMode::Fill => self.resize_fill(target_width, target_height),
+        }
+    }
+
+    /// Resize the image to cover the given dimensions while preserving aspect ratio
+    /// Crops the center of the scaled image
+    pub fn resize_fill(&self, target_width: u32, target_height: u32) -> Result<Self> {
+        if self.width == target_width && self.height == target_height {
+            return Ok(self.clone());
+        }
+        rgba_len(target_width, target_height)?;
+
+        // Calculate scaling factor to cover the target dimensions
+        let scale_x = target_width as f64 / self.width as f64;
+        let scale_y = target_height as f64 / self.height as f64;
+        let scale = scale_x.max(scale_y);
+
+        let new_width = ((self.width as f64 * scale).round() as u32).max(target_width);
+        let new_height = ((self.height as f64 * scale).round() as u32).max(target_height);
+
+        let dynamic = DynamicImage::ImageRgba8(self.to_rgba_image()?);
+        let resized =
+            dynamic.resize_exact(new_width, new_height, image::imageops::FilterType::Lanczos3);
+        Self::from_dynamic_image(resized).crop(
+            (new_width - target_width) / 2,
+            (new_height - target_height) / 2,
+            target_width,
+            target_height,
+        )
     }
 
     /// Resize the image to fit within the given dimensions while preserving aspect ratio
     /// Pads with the specified background color if needed
-    pub fn resize_fit(&self, target_width: u32, target_height: u32, bg_color: [u8; 4]) -> Self {
+    pub fn resize_fit(
+        &self,
+        target_width: u32,
+        target_height: u32,
+        bg_color: [u8; 4],
+    ) -> Result<Self> {
         if self.width == target_width && self.height == target_height {
-            return self.clone();
+            return Ok(self.clone());
         }
+        let output_len = rgba_len(target_width, target_height)?;
 
         // Calculate scaling factor to fit within target dimensions
         let scale_x = target_width as f64 / self.width as f64;
         let scale_y = target_height as f64 / self.height as f64;
         let scale = scale_x.min(scale_y);
 
-        let new_width = (self.width as f64 * scale).round() as u32;
-        let new_height = (self.height as f64 * scale).round() as u32;
+        let new_width = ((self.width as f64 * scale).round() as u32).min(target_width);
+        let new_height = ((self.height as f64 * scale).round() as u32).min(target_height);
 
         // Resize the image
-        let img = image::RgbaImage::from_raw(self.width, self.height, self.data.clone())
-            .expect("Invalid image data");
-
-        let dynamic = DynamicImage::ImageRgba8(img);
+        let dynamic = DynamicImage::ImageRgba8(self.to_rgba_image()?);
         let resized =
             dynamic.resize_exact(new_width, new_height, image::imageops::FilterType::Lanczos3);
 
-        // Create output image with background color
-        let mut output = vec![0u8; (target_width * target_height * 4) as usize];
-
-        // Fill with background color
-        for i in 0..(target_width * target_height) as usize {
-            output[i * 4] = bg_color[0];
-            output[i * 4 + 1] = bg_color[1];
-            output[i * 4 + 2] = bg_color[2];
-            output[i * 4 + 3] = bg_color[3];
-        }
+        // Create output image filled with the background color
+        let mut output = bg_color.repeat(output_len / 4);
 
         // Calculate offset to center the image
         let offset_x = (target_width - new_width) / 2;
@@ -99,7 +429,8 @@ impl LoadedImage {
         let resized_rgba = resized.to_rgba8();
         for y in 0..new_height {
             for x in 0..new_width {
-                let dst_idx = (((offset_y + y) * target_width + (offset_x + x)) * 4) as usize;
+                let dst_idx =
+                    ((offset_y + y) as usize * target_width as usize + (offset_x + x) as usize) * 4;
 
                 output[dst_idx] = resized_rgba[(x, y)][0];
                 output[dst_idx + 1] = resized_rgba[(x, y)][1];
@@ -108,11 +439,11 @@ impl LoadedImage {
             }
         }
 
-        Self {
+        Ok(Self {
             width: target_width,
             height: target_height,
             data: output,
-        }
+        })
     }
 }
 
@@ -136,7 +467,7 @@ pub fn load_and_normalize_images<P: AsRef<Path>>(paths: &[P]) -> Result<Vec<Load
     let normalized: Vec<LoadedImage> = images
         .into_iter()
         .map(|img| img.resize(target_width, target_height))
-        .collect();
+        .collect::<Result<Vec<_>>>()?;
 
     Ok(normalized)
 }
@@ -159,9 +490,57 @@ mod tests {
             ],
         };
 
-        let resized = img.resize(4, 4);
+        let resized = img.resize(4, 4).unwrap();
         assert_eq!(resized.width, 4);
         assert_eq!(resized.height, 4);
         assert_eq!(resized.data.len(), 4 * 4 * 4);
     }
+
+    #[test]
+    fn test_from_rgba_checks_length() {
+        assert!(LoadedImage::from_rgba(2, 2, vec![0; 16]).is_ok());
+        assert!(LoadedImage::from_rgba(2, 2, vec![0; 15]).is_err());
+        assert!(LoadedImage::from_rgba(0, 2, vec![]).is_err());
+    }
+
+    #[test]
+    fn test_resize_rejects_malformed_image() {
+        // Public fields allow data that does not match the dimensions
+        let img = LoadedImage {
+            width: 2,
+            height: 2,
+            data: vec![0; 15],
+        };
+        assert!(matches!(img.resize(4, 4), Err(Error::InvalidInput(_))));
+        assert!(img.resize_fit(4, 4, [0; 4]).is_err());
+        assert!(img.resize_fill(4, 4).is_err());
+    }
+
+    #[test]
+    fn test_scale_modes() {
+        // 4x2 image, red on the left half and blue on the right
+        let red_blue = [[255, 0, 0, 255].repeat(2), [0, 0, 255, 255].repeat(2)]
+            .concat()
+            .repeat(2);
+        let img = LoadedImage::from_rgba(4, 2, red_blue).unwrap();
+        let pixel = |img: &LoadedImage, x: u32, y: u32| {
+            let i = ((y * img.width + x) * 4) as usize;
+            img.data[i..i + 4].to_vec()
+        };
+
+        // Fitting into a square leaves bars of the letterbox color above and below
+        let green = Color { r: 0, g: 255, b: 0 };
+        let fit = img.scale(4, 4, ScaleMode::Fit, green).unwrap();
+        assert_eq!(pixel(&fit, 0, 0), [0, 255, 0, 255]);
+        assert_eq!(pixel(&fit, 0, 1), [255, 0, 0, 255]);
+
+        // Filling a square keeps the center, half red and half blue
+        let fill = img.scale(2, 2, ScaleMode::Fill, green).unwrap();
+        assert_eq!((fill.width, fill.height), (2, 2));
+        assert_eq!(pixel(&fill, 0, 0), [255, 0, 0, 255]);
+        assert_eq!(pixel(&fill, 1, 1), [0, 0, 255, 255]);
+
+        let stretched = img.scale(2, 2, ScaleMode::Stretch, green).unwrap();
+        assert_eq!((stretched.width, stretched.height), (2, 2));
+    }
 }
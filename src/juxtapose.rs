@@ -1,130 +1,333 @@
 //! Side-by-side video juxtaposition
 
-use crate::encoder::{create_encoder, EncoderConfig, Frame};
-use crate::muxer::{create_muxer, MuxerConfig};
-use crate::{Color, EncodeOptions, Error, Result};
-use std::io::Read;
+use crate::budget::MemoryBudget;
+use crate::decoder::{DecodedFrame, VideoDecoder, DEFAULT_FPS};
+use crate::encoder::{EncoderConfig, Frame, PooledEncoder};
+use crate::fade::with_fade;
+use crate::image_loader::{rgba_len, LoadedImage};
+use crate::logging::{log_info, trace_span};
+use crate::muxer::{check_output, write_packets, MemoryOutput, MuxerConfig};
+use crate::pipeline::{pipeline, PIPELINE_FRAMES};
+use crate::pool::FramePool;
+use crate::stats::{EncodeStats, StatsRecorder};
+use crate::validate::StrictCheck;
+use crate::watermark::with_watermark;
+use crate::{Caption, CaptionPosition, Color, EncodeOptions, Error, Result, Stage};
+use std::ops::Range;
 use std::path::Path;
-use std::process::{Command, Stdio};
 
-/// Default frame rate for output video
-const DEFAULT_FPS: u32 = 30;
+/// Grid layout for combining several videos with [`juxtapose_n`]
+///
+/// Inputs fill the grid left to right, then top to bottom. Each column is as wide as its
+/// widest video and each row as tall as its tallest; videos are aligned to the left of
+/// their cell, and vertically as `align` sets.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Layout {
+    /// Number of columns (0 places all videos in a single row)
+    pub columns: u32,
+    /// Labels of the inputs in order, such as "original" and "optimized"
+    ///
+    /// Each label is centered in a band of the background color across its column, above
+    /// the row if its position is [`CaptionPosition::Top`] and below it otherwise. Drawing
+    /// labels needs the `text` feature.
+    pub labels: Vec<Caption>,
+    /// Width in pixels of the dividers between adjacent columns and rows
+    pub gap: u32,
+    /// Color of the dividers, `None` for the background color
+    pub divider_color: Option<Color>,
+    /// Where videos shorter than their row are placed in it
+    pub align: VerticalAlign,
+    /// Height the videos are scaled to before they are laid out
+    pub height_mode: HeightMode,
+    /// What videos shorter than the longest one show once they end
+    pub end_policy: EndPolicy,
+    /// Part of each input to use in order, such as to line up recordings started at
+    /// different times; inputs past the end of the list are used whole
+    pub trims: Vec<Trim>,
+    /// Whether the videos are laid out in a grid or overlaid
+    pub composition: Composition,
+}
 
-/// Video frame from decoded video
-struct DecodedFrame {
-    width: u32,
-    height: u32,
-    data: Vec<u8>, // RGBA
+/// How the videos of [`juxtapose_n`] share the output frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Composition {
+    /// Each video in its own cell of the grid
+    #[default]
+    Grid,
+    /// Two videos overlaid, like an image comparison slider: a vertical divider sweeps
+    /// from the left edge to the right one over the output, showing the second video
+    /// left of it and the first right of it
+    ///
+    /// The divider is [`Layout::gap`] pixels wide, in [`Layout::divider_color`]. Labels
+    /// share the band across the frame, the first one on the left.
+    Wipe,
 }
 
-/// Video decoder using ffmpeg
-struct VideoDecoder {
-    width: u32,
-    height: u32,
-    fps: f64,
-    frame_count: u64,
-    current_frame: u64,
-    process: Option<std::process::Child>,
-    last_frame: Option<Vec<u8>>,
+/// Part of a video between `start_ms` and `end_ms` (`None` for the end)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Trim {
+    pub start_ms: u64,
+    pub end_ms: Option<u64>,
 }
 
-impl VideoDecoder {
-    fn new<P: AsRef<Path>>(path: P, ffmpeg_path: Option<&str>) -> Result<Self> {
-        let path = path.as_ref();
-        let ffmpeg = find_ffmpeg(ffmpeg_path)?;
+/// How videos of different lengths are combined with [`juxtapose_n`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EndPolicy {
+    /// Hold the last frame of shorter videos until the longest one ends
+    #[default]
+    FreezeLast,
+    /// Play shorter videos again from their start until the longest one ends
+    Loop,
+    /// End the output with the shortest video
+    Stop,
+}
 
-        // Get video info using ffprobe
-        let (width, height, fps, frame_count) = get_video_info(path, &ffmpeg)?;
+/// Height of videos combined with [`juxtapose_n`], which keep their aspect ratio when
+/// scaled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HeightMode {
+    /// Keep the height of each video, padding shorter ones with the background color
+    #[default]
+    Original,
+    /// Scale every video to the height of the tallest one
+    Tallest,
+    /// Scale every video to this height in pixels
+    Fixed(u32),
+}
 
-        Ok(Self {
-            width,
-            height,
-            fps,
-            frame_count,
-            current_frame: 0,
-            process: None,
-            last_frame: None,
-        })
+/// Sizes of videos of the given sizes once scaled as `mode` sets
+fn scaled_sizes(sizes: &[(u32, u32)], mode: HeightMode) -> Result<Vec<(u32, u32)>> {
+    let height = match mode {
+        HeightMode::Original => return Ok(sizes.to_vec()),
+        HeightMode::Tallest => sizes.iter().map(|&(_, height)| height).max().unwrap_or(0),
+        HeightMode::Fixed(height) => height,
+    };
+    if height == 0 {
+        return Err(Error::InvalidInput(
+            "Videos cannot be scaled to a height of 0".to_string(),
+        ));
     }
+    sizes
+        .iter()
+        .map(|&(width, original)| {
+            let scaled = (width as u64 * height as u64 + original as u64 / 2) / original as u64;
+            let width = u32::try_from(scaled.max(1)).map_err(|_| {
+                Error::InvalidInput(format!(
+                    "Video of {}x{} is too wide scaled to a height of {}",
+                    width, original, height
+                ))
+            })?;
+            Ok((width, height))
+        })
+        .collect()
+}
 
-    fn start_decode<P: AsRef<Path>>(&mut self, path: P, ffmpeg_path: Option<&str>) -> Result<()> {
-        let ffmpeg = find_ffmpeg(ffmpeg_path)?;
-
-        let process = Command::new(&ffmpeg)
-            .args([
-                "-i",
-                path.as_ref().to_str().unwrap(),
-                "-f",
-                "rawvideo",
-                "-pix_fmt",
-                "rgba",
-                "-r",
-                &DEFAULT_FPS.to_string(),
-                "pipe:1",
-            ])
-            .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .spawn()
-            .map_err(|e| Error::Ffmpeg(format!("Failed to start ffmpeg: {}", e)))?;
-
-        self.process = Some(process);
-        Ok(())
-    }
+/// Vertical alignment of a video in a taller grid cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VerticalAlign {
+    #[default]
+    Top,
+    Center,
+    Bottom,
+}
 
-    fn read_frame(&mut self) -> Result<Option<DecodedFrame>> {
-        let process = match self.process.as_mut() {
-            Some(p) => p,
-            None => return Ok(None),
+/// Position of every input in the output frame
+#[derive(Debug, PartialEq)]
+struct Grid {
+    width: u32,
+    height: u32,
+    /// Top-left corner of each input
+    offsets: Vec<(u32, u32)>,
+    /// Band of each labeled input as `(x, y, width, height)`
+    label_bands: Vec<(u32, u32, u32, u32)>,
+    /// Dividers between columns and rows as `(x, y, width, height)`
+    dividers: Vec<(u32, u32, u32, u32)>,
+    /// Whether the inputs are overlaid in one cell to be wiped between
+    wipe: bool,
+}
+
+impl Grid {
+    /// Lay out inputs of the given sizes
+    fn new(sizes: &[(u32, u32)], layout: &Layout) -> Result<Self> {
+        let wipe = layout.composition == Composition::Wipe;
+        if wipe && sizes.len() != 2 {
+            return Err(Error::InvalidInput(format!(
+                "A wipe compares 2 videos, not {}",
+                sizes.len()
+            )));
+        }
+        // Overlaid inputs all take the first cell
+        let cell = |i: usize| if wipe { 0 } else { i };
+        let count = if wipe { 1 } else { sizes.len().max(1) };
+        let columns = match layout.columns as usize {
+            0 => count,
+            columns => columns.min(count),
         };
+        let rows = count.div_ceil(columns);
+
+        let mut column_widths = vec![0u32; columns];
+        let mut row_heights = vec![0u32; rows];
+        for (i, &(width, height)) in sizes.iter().enumerate() {
+            let i = cell(i);
+            column_widths[i % columns] = column_widths[i % columns].max(width);
+            row_heights[i / columns] = row_heights[i / columns].max(height);
+        }
 
-        let stdout = match process.stdout.as_mut() {
-            Some(s) => s,
-            None => return Ok(None),
+        // Every row has the same bands, as tall as the tallest label in them
+        let labels = &layout.labels[..layout.labels.len().min(sizes.len())];
+        let band = |above: bool| {
+            labels
+                .iter()
+                .filter(|label| (label.position == CaptionPosition::Top) == above)
+                .map(band_height)
+                .max()
+                .unwrap_or(0)
         };
+        let (band_above, band_below) = (band(true), band(false));
+        let video_heights = row_heights.clone();
+        for height in &mut row_heights {
+            *height = total(&[band_above, *height, band_below])?;
+        }
 
-        let frame_size = (self.width * self.height * 4) as usize;
-        let mut buffer = vec![0u8; frame_size];
-
-        match stdout.read_exact(&mut buffer) {
-            Ok(_) => {
-                self.current_frame += 1;
-                self.last_frame = Some(buffer.clone());
-                Ok(Some(DecodedFrame {
-                    width: self.width,
-                    height: self.height,
-                    data: buffer,
-                }))
-            }
-            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                // End of video - return last frame if available
-                if let Some(ref last) = self.last_frame {
-                    Ok(Some(DecodedFrame {
-                        width: self.width,
-                        height: self.height,
-                        data: last.clone(),
-                    }))
-                } else {
-                    Ok(None)
+        let gap = layout.gap;
+        let offsets = (0..sizes.len())
+            .map(|i| {
+                let size = sizes[i];
+                let i = cell(i);
+                let x = cell_start(&column_widths, i % columns, gap)?;
+                let y = cell_start(&row_heights, i / columns, gap)?;
+                let space = video_heights[i / columns] - size.1;
+                let y = y
+                    + band_above
+                    + match layout.align {
+                        VerticalAlign::Top => 0,
+                        VerticalAlign::Center => space / 2,
+                        VerticalAlign::Bottom => space,
+                    };
+                Ok((x, y))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let label_bands = labels
+            .iter()
+            .enumerate()
+            .map(|(index, label)| {
+                let i = cell(index);
+                let mut x = cell_start(&column_widths, i % columns, gap)?;
+                let y = cell_start(&row_heights, i / columns, gap)?;
+                let mut width = column_widths[i % columns];
+                if wipe {
+                    // Overlaid inputs split the band of their cell
+                    let part = |n: usize| (width as u64 * n as u64 / labels.len() as u64) as u32;
+                    x += part(index);
+                    width = part(index + 1) - part(index);
                 }
+                Ok(if label.position == CaptionPosition::Top {
+                    (x, y, width, band_above)
+                } else {
+                    let y = y + band_above + video_heights[i / columns];
+                    (x, y, width, band_below)
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        // Ensure dimensions are even
+        let full_width = cell_start(&column_widths, columns, gap)? - gap;
+        let full_height = cell_start(&row_heights, rows, gap)? - gap;
+        let (width, height) = (full_width / 2 * 2, full_height / 2 * 2);
+        rgba_len(width, height)?;
+
+        let mut dividers = Vec::new();
+        if gap > 0 {
+            for column in 1..columns {
+                let x = cell_start(&column_widths, column, gap)? - gap;
+                dividers.push((x, 0, gap, full_height));
+            }
+            for row in 1..rows {
+                let y = cell_start(&row_heights, row, gap)? - gap;
+                dividers.push((0, y, full_width, gap));
             }
-            Err(e) => Err(Error::Decode(format!("Failed to read frame: {}", e))),
         }
-    }
 
-    fn duration_frames(&self) -> u64 {
-        ((self.frame_count as f64 * DEFAULT_FPS as f64) / self.fps).ceil() as u64
+        Ok(Self {
+            width,
+            height,
+            offsets,
+            label_bands,
+            dividers,
+            wipe,
+        })
     }
-}
 
-impl Drop for VideoDecoder {
-    fn drop(&mut self) {
-        if let Some(ref mut process) = self.process {
-            let _ = process.kill();
-            let _ = process.wait();
+    /// Frame of the background color with the dividers and labels drawn, which videos are
+    /// placed over
+    fn base_frame(&self, layout: &Layout, bg: &Color) -> Result<Vec<u8>> {
+        let divider = layout.divider_color.unwrap_or(*bg);
+        let bg = [bg.r, bg.g, bg.b, 255];
+        let mut frame = bg.repeat(rgba_len(self.width, self.height)? / 4);
+        for &(x, y, width, height) in &self.dividers {
+            let color = [divider.r, divider.g, divider.b, 255];
+            copy_clipped(
+                &color.repeat(rgba_len(width, height)? / 4),
+                (width, height),
+                (x, y),
+                &mut frame,
+                self.width,
+                self.height,
+            );
         }
+        let labels = &layout.labels;
+        for (label, &(x, y, width, height)) in labels.iter().zip(&self.label_bands) {
+            let mut band =
+                LoadedImage::from_rgba(width, height, bg.repeat(rgba_len(width, height)? / 4))?;
+            Caption {
+                position: CaptionPosition::Center,
+                ..label.clone()
+            }
+            .draw(&mut band)?;
+            copy_clipped(
+                &band.data,
+                (width, height),
+                (x, y),
+                &mut frame,
+                self.width,
+                self.height,
+            );
+        }
+        Ok(frame)
     }
 }
 
+/// Height of the band of `label`, its lines with half a line of space around them
+fn band_height(label: &Caption) -> u32 {
+    let lines = label.text.lines().count().max(1) as f32;
+    let outline = match label.outline {
+        Some(_) => label.outline_width as f32 * 2.0,
+        None => 0.0,
+    };
+    (label.size * (lines + 0.5) + outline).ceil() as u32
+}
+
+/// Start of column or row `index` after the ones before it and their dividers
+fn cell_start(lengths: &[u32], index: usize, gap: u32) -> Result<u32> {
+    let gaps = gap
+        .checked_mul(index as u32)
+        .ok_or_else(|| Error::InvalidInput("Combined video is too large".to_string()))?;
+    total(&[total(&lengths[..index])?, gaps])
+}
+
+/// Sum of column widths or row heights, failing if the output would be too large
+fn total(lengths: &[u32]) -> Result<u32> {
+    lengths
+        .iter()
+        .try_fold(0u32, |sum, &length| sum.checked_add(length))
+        .ok_or_else(|| Error::InvalidInput("Combined video is too large".to_string()))
+}
+
 /// Combine two videos side by side
 ///
 /// The output video will have:
@@ -132,40 +335,198 @@ impl Drop for VideoDecoder {
 /// - Height = max(left video height, right video height)
 /// - Duration = max(left video duration, right video duration)
 ///
-/// If heights differ, videos are aligned to the top with the background color filling the bottom.
+/// If heights differ, videos are aligned to the top with the background color filling the bottom;
+/// [`juxtapose_n`] can align them otherwise with [`Layout::align`].
 /// If durations differ, the shorter video continues showing its last frame.
 pub fn juxtapose<P: AsRef<Path>>(
     left_path: P,
     right_path: P,
     options: &EncodeOptions,
     background: Option<Color>,
-) -> Result<()> {
+) -> Result<EncodeStats> {
+    juxtapose_to(
+        &[left_path, right_path],
+        Layout::default(),
+        options,
+        background,
+        None,
+    )
+}
+
+/// Combine two videos side by side in memory and return the encoded bytes
+///
+/// `options.output_path` is ignored.
+pub fn juxtapose_to_memory<P: AsRef<Path>>(
+    left_path: P,
+    right_path: P,
+    options: &EncodeOptions,
+    background: Option<Color>,
+) -> Result<Vec<u8>> {
+    let output = MemoryOutput::new();
+    juxtapose_to(
+        &[left_path, right_path],
+        Layout::default(),
+        options,
+        background,
+        Some(&output),
+    )?;
+    Ok(output.take())
+}
+
+/// Combine any number of videos into a grid
+///
+/// Cells not covered by a video are filled with the background color. As with
+/// [`juxtapose`], the output lasts as long as the longest input and shorter inputs
+/// hold their last frame.
+pub fn juxtapose_n<P: AsRef<Path>>(
+    paths: &[P],
+    layout: Layout,
+    options: &EncodeOptions,
+    background: Option<Color>,
+) -> Result<EncodeStats> {
+    juxtapose_to(paths, layout, options, background, None)
+}
+
+/// Combine any number of videos into a grid in memory and return the encoded bytes
+///
+/// `options.output_path` is ignored.
+pub fn juxtapose_n_to_memory<P: AsRef<Path>>(
+    paths: &[P],
+    layout: Layout,
+    options: &EncodeOptions,
+    background: Option<Color>,
+) -> Result<Vec<u8>> {
+    let output = MemoryOutput::new();
+    juxtapose_to(paths, layout, options, background, Some(&output))?;
+    Ok(output.take())
+}
+
+/// Combine videos into a grid of `rows` by `columns` cells, such as outputs of several
+/// encoder settings to compare
+///
+/// This is [`juxtapose_n`] with `columns` columns on a black background, failing with
+/// [`Error::InvalidInput`] if the videos do not fit in the grid. Rows left empty are
+/// not drawn.
+pub fn mosaic<P: AsRef<Path>>(
+    paths: &[P],
+    rows: u32,
+    columns: u32,
+    options: &EncodeOptions,
+) -> Result<EncodeStats> {
+    let cells = rows as u64 * columns as u64;
+    if cells == 0 || paths.len() as u64 > cells {
+        return Err(Error::InvalidInput(format!(
+            "{} videos do not fit in a {}x{} mosaic",
+            paths.len(),
+            rows,
+            columns
+        )));
+    }
+    juxtapose_n(
+        paths,
+        Layout {
+            columns,
+            ..Default::default()
+        },
+        options,
+        None,
+    )
+}
+
+/// Combine videos, writing to `memory` if set or to `options.output_path` otherwise
+pub(crate) fn juxtapose_to<P: AsRef<Path>>(
+    paths: &[P],
+    layout: Layout,
+    options: &EncodeOptions,
+    background: Option<Color>,
+    memory: Option<&MemoryOutput>,
+) -> Result<EncodeStats> {
+    let _span = trace_span!("juxtapose", videos = paths.len());
+    let mut recorder = StatsRecorder::start();
+
     // Validate options
-    options.validate()?;
+    options.validate_video()?;
+    check_output(options.container, memory)?;
+    let watermarked = with_watermark(options)?;
+    let options: &EncodeOptions = &watermarked;
+
+    if paths.is_empty() {
+        return Err(Error::InvalidInput("No input videos provided".to_string()));
+    }
 
     let bg = background.unwrap_or_default();
+    let divider = layout.divider_color.unwrap_or(bg);
     let ffmpeg_path = options.ffmpeg_path.as_deref();
 
-    // Open both video decoders
-    let mut left_decoder = VideoDecoder::new(&left_path, ffmpeg_path)?;
-    let mut right_decoder = VideoDecoder::new(&right_path, ffmpeg_path)?;
+    // Open all video decoders
+    let mut decoders = {
+        let _span = trace_span!("open");
+        paths
+            .iter()
+            .map(|path| VideoDecoder::open(path, options))
+            .collect::<Result<Vec<_>>>()?
+    };
 
-    // Calculate output dimensions
-    let output_width = left_decoder.width + right_decoder.width;
-    let output_height = left_decoder.height.max(right_decoder.height);
+    // Trimmed inputs are seeked by ffmpeg once decoding starts
+    for ((decoder, path), trim) in decoders.iter_mut().zip(paths).zip(&layout.trims) {
+        decoder
+            .trim(trim.start_ms, trim.end_ms)
+            .map_err(|e| e.with_path(path))?;
+    }
 
-    // Ensure dimensions are even
-    let output_width = (output_width / 2) * 2;
-    let output_height = (output_height / 2) * 2;
+    if let Some(mut check) = StrictCheck::new(options) {
+        for (index, decoder) in decoders.iter().enumerate() {
+            check.dimensions(index, decoder.width, decoder.height);
+        }
+        check.total_duration(decoders.iter().map(|d| d.duration_ms).max().unwrap_or(0));
+        check.finish()?;
+    }
 
-    // Calculate total frames (longer video duration)
-    let total_frames = left_decoder
-        .duration_frames()
-        .max(right_decoder.duration_frames());
+    // Calculate output dimensions, with ffmpeg scaling the videos while decoding
+    let sizes: Vec<_> = decoders.iter().map(|d| (d.width, d.height)).collect();
+    let sizes = scaled_sizes(&sizes, layout.height_mode)?;
+    for (decoder, &(width, height)) in decoders.iter_mut().zip(&sizes) {
+        decoder.scale_to(width, height);
+    }
+    let grid = Grid::new(&sizes, &layout)?;
+    let output_width = grid.width;
+    let output_height = grid.height;
+    let base = grid
+        .base_frame(&layout, &bg)
+        .map_err(|e| e.during(Stage::Rendering))?;
+
+    // Calculate total frames (longest video duration, or shortest when stopping with it)
+    let durations = decoders.iter().map(VideoDecoder::duration_frames);
+    let total_frames = match layout.end_policy {
+        EndPolicy::FreezeLast | EndPolicy::Loop => durations.max(),
+        EndPolicy::Stop => durations.min(),
+    }
+    .unwrap_or(0);
+    let faded = with_fade(
+        options,
+        total_frames.saturating_sub(1) * 1000 / DEFAULT_FPS as u64,
+    );
+    let options: &EncodeOptions = &faded;
+
+    log_info!(
+        "Juxtaposing {} videos ({}) into {}x{}, {} frames",
+        decoders.len(),
+        sizes
+            .iter()
+            .map(|(w, h)| format!("{}x{}", w, h))
+            .collect::<Vec<_>>()
+            .join(", "),
+        output_width,
+        output_height,
+        total_frames
+    );
 
     // Start decoding
-    left_decoder.start_decode(&left_path, ffmpeg_path)?;
-    right_decoder.start_decode(&right_path, ffmpeg_path)?;
+    for (decoder, path) in decoders.iter_mut().zip(paths) {
+        decoder
+            .start_decode(path, ffmpeg_path)
+            .map_err(|e| e.with_path(path).during(Stage::Decoding))?;
+    }
 
     // Create encoder
     let encoder_config = EncoderConfig {
@@ -173,42 +534,95 @@ pub fn juxtapose<P: AsRef<Path>>(
         height: output_height,
         fps: DEFAULT_FPS,
         quality: options.quality,
+        bitrate_kbps: options.bitrate_kbps.filter(|&kbps| kbps > 0),
+        rate_control: options.rate_control,
+        keyframe_interval: options.keyframe_interval(DEFAULT_FPS),
+        color_space: options.color_space,
+        pixel_range: options.pixel_range,
+        deterministic: options.deterministic,
+        priority: options.priority,
+        threads: options.threads(),
+        av1_backend: options.av1_backend,
     };
 
-    let mut encoder = create_encoder(options.codec, encoder_config.clone())?;
+    let mut encoder = PooledEncoder::open(options, encoder_config.clone())?;
+    recorder.prepared();
 
     // Collect all packets first (to get SPS/PPS for H.264 muxer)
     let mut all_packets: Vec<crate::encoder::Packet> = Vec::new();
 
-    // Process frames
-    for frame_idx in 0..total_frames {
-        // Read frames from both videos
-        let left_frame = left_decoder.read_frame()?;
-        let right_frame = right_decoder.read_frame()?;
-
-        // Combine frames
-        let combined = combine_frames(
-            left_frame.as_ref(),
-            right_frame.as_ref(),
-            output_width,
-            output_height,
-            &bg,
-        );
-
-        let frame = Frame {
-            width: output_width,
-            height: output_height,
-            data: combined,
-            pts_ms: frame_idx * 1000 / DEFAULT_FPS as u64,
-        };
-
-        let packets = encoder.encode(&frame)?;
-        all_packets.extend(packets);
+    {
+        let _span = trace_span!("encode", frames = total_frames);
+
+        let mut budget = MemoryBudget::new(options);
+        let frame_size = rgba_len(output_width, output_height)?;
+        let buffered = budget.reserve_frames(frame_size, PIPELINE_FRAMES);
+        let pool = FramePool::new(frame_size);
+
+        // Decode and combine frames on a worker thread while encoding on this one
+        let paths: Vec<&Path> = paths.iter().map(AsRef::as_ref).collect();
+        let combined = (0..total_frames).map(|frame_idx| {
+            // Read frames from all videos
+            let frames = decoders
+                .iter_mut()
+                .zip(&paths)
+                .map(|(decoder, path)| {
+                    let frame = match layout.end_policy {
+                        EndPolicy::Loop => decoder.read_frame_looping(path, ffmpeg_path),
+                        _ => decoder.read_frame(),
+                    };
+                    frame.map_err(|e| {
+                        e.with_path(path)
+                            .during(Stage::Decoding)
+                            .with_frame(frame_idx)
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let mut data = pool.take();
+            if grid.wipe {
+                let position = wipe_position(frame_idx, total_frames, grid.width);
+                wipe_frames(&frames, &grid, &base, position, &mut data);
+                draw_divider(&mut data, &grid, position, layout.gap, &divider);
+            } else {
+                combine_frames(&frames, &grid, &base, &mut data);
+            }
+            let pts_ms = frame_idx * 1000 / DEFAULT_FPS as u64;
+            options
+                .filters
+                .apply_to((output_width, output_height), frame_idx, pts_ms, &mut data)
+                .map_err(|e| e.with_frame(frame_idx).during(Stage::Rendering))?;
+            Ok((frame_idx, data))
+        });
+
+        pipeline(buffered, options.priority, combined, |(frame_idx, data)| {
+            options.check_cancelled()?;
+
+            let frame = Frame {
+                width: output_width,
+                height: output_height,
+                data: &data,
+                pts_ms: frame_idx * 1000 / DEFAULT_FPS as u64,
+            };
+
+            let packets = encoder
+                .encode(&frame)
+                .map_err(|e| e.with_frame(frame_idx).during(Stage::Encoding))?;
+            budget.reserve_packets(&packets)?;
+            all_packets.extend(packets);
+            pool.give(data);
+
+            options.report_progress(frame_idx + 1, total_frames);
+            Ok(())
+        })?;
+
+        // Flush encoder
+        let flush_packets = encoder.flush()?;
+        budget.reserve_packets(&flush_packets)?;
+        all_packets.extend(flush_packets);
     }
 
-    // Flush encoder
-    let flush_packets = encoder.flush()?;
-    all_packets.extend(flush_packets);
+    recorder.encoded(total_frames, &all_packets);
 
     // Create muxer with SPS/PPS from encoder (available after encoding)
     let muxer_config = MuxerConfig {
@@ -218,188 +632,304 @@ pub fn juxtapose<P: AsRef<Path>>(
         codec: options.codec,
         codec_config: encoder.codec_config(),
         pps: encoder.pps(),
+        color_space: options.color_space,
+        pixel_range: options.pixel_range,
     };
 
-    let mut muxer = create_muxer(options.container, &options.output_path, muxer_config)?;
+    let output_bytes = write_packets(options, memory, muxer_config, all_packets)?;
+    Ok(recorder.finish(output_bytes, DEFAULT_FPS, encoder.backend()))
+}
 
-    // Write all packets
-    for packet in all_packets {
-        muxer.write_packet(&packet)?;
+/// Place frames at their grid positions over the `base` frame in `output`
+fn combine_frames(frames: &[Option<DecodedFrame>], grid: &Grid, base: &[u8], output: &mut [u8]) {
+    output.copy_from_slice(base);
+    for (frame, &offset) in frames.iter().zip(&grid.offsets) {
+        let Some(frame) = frame else { continue };
+        copy_clipped(
+            frame.data,
+            (frame.width, frame.height),
+            offset,
+            output,
+            grid.width,
+            grid.height,
+        );
     }
+}
 
-    // Finalize output
-    muxer.finalize()?;
-
-    Ok(())
+/// Column of the wipe divider at `frame_idx`, from the left edge at the first frame to
+/// the right one at the last
+fn wipe_position(frame_idx: u64, total_frames: u64, width: u32) -> u32 {
+    let last = total_frames.saturating_sub(1).max(1);
+    (width as u64 * frame_idx.min(last) / last) as u32
 }
 
-/// Combine two frames side by side
-fn combine_frames(
-    left: Option<&DecodedFrame>,
-    right: Option<&DecodedFrame>,
-    output_width: u32,
-    output_height: u32,
-    bg: &Color,
-) -> Vec<u8> {
-    let mut output = vec![0u8; (output_width * output_height * 4) as usize];
-
-    // Fill with background color
-    for i in 0..(output_width * output_height) as usize {
-        output[i * 4] = bg.r;
-        output[i * 4 + 1] = bg.g;
-        output[i * 4 + 2] = bg.b;
-        output[i * 4 + 3] = 255;
+/// Show the second frame left of column `position` and the first from it on, over the
+/// `base` frame in `output`
+fn wipe_frames(
+    frames: &[Option<DecodedFrame>],
+    grid: &Grid,
+    base: &[u8],
+    position: u32,
+    output: &mut [u8],
+) {
+    output.copy_from_slice(base);
+    let columns = [position..grid.width, 0..position];
+    for ((frame, &offset), columns) in frames.iter().zip(&grid.offsets).zip(columns) {
+        let Some(frame) = frame else { continue };
+        copy_columns(
+            frame.data,
+            (frame.width, frame.height),
+            offset,
+            columns,
+            output,
+            grid.width,
+            grid.height,
+        );
     }
+}
 
-    // Copy left frame (top-aligned)
-    if let Some(left) = left {
-        for y in 0..left.height.min(output_height) {
-            for x in 0..left.width {
-                let src_idx = ((y * left.width + x) * 4) as usize;
-                let dst_idx = ((y * output_width + x) * 4) as usize;
-
-                output[dst_idx] = left.data[src_idx];
-                output[dst_idx + 1] = left.data[src_idx + 1];
-                output[dst_idx + 2] = left.data[src_idx + 2];
-                output[dst_idx + 3] = left.data[src_idx + 3];
-            }
+/// Draw the wipe divider of `width` pixels centered on column `position`
+fn draw_divider(output: &mut [u8], grid: &Grid, position: u32, width: u32, color: &Color) {
+    let start = position.saturating_sub(width / 2).min(grid.width);
+    let end = start.saturating_add(width).min(grid.width);
+    let color = [color.r, color.g, color.b, 255];
+    for row in output.chunks_exact_mut(grid.width as usize * 4) {
+        for pixel in row[start as usize * 4..end as usize * 4].chunks_exact_mut(4) {
+            pixel.copy_from_slice(&color);
         }
     }
+}
 
-    // Copy right frame (top-aligned, offset by left width)
-    if let Some(right) = right {
-        let left_width = left.map(|l| l.width).unwrap_or(0);
-
-        for y in 0..right.height.min(output_height) {
-            for x in 0..right.width {
-                let src_idx = ((y * right.width + x) * 4) as usize;
-                let dst_idx = ((y * output_width + left_width + x) * 4) as usize;
+/// Copy RGBA `src` of `size` to `(x, y)` of `output`, clipped to the output, which may
+/// have been rounded down to even dimensions
+fn copy_clipped(
+    src: &[u8],
+    size: (u32, u32),
+    offset: (u32, u32),
+    output: &mut [u8],
+    output_width: u32,
+    output_height: u32,
+) {
+    let columns = 0..output_width;
+    copy_columns(
+        src,
+        size,
+        offset,
+        columns,
+        output,
+        output_width,
+        output_height,
+    );
+}
 
-                if dst_idx + 3 < output.len() {
-                    output[dst_idx] = right.data[src_idx];
-                    output[dst_idx + 1] = right.data[src_idx + 1];
-                    output[dst_idx + 2] = right.data[src_idx + 2];
-                    output[dst_idx + 3] = right.data[src_idx + 3];
-                }
-            }
-        }
+/// Copy the part of RGBA `src` of `size` at `(x, y)` of `output` that falls in `columns`
+/// of the output, clipped to the output
+fn copy_columns(
+    src: &[u8],
+    (src_width, src_height): (u32, u32),
+    (x, y): (u32, u32),
+    columns: Range<u32>,
+    output: &mut [u8],
+    output_width: u32,
+    output_height: u32,
+) {
+    let start = columns.start.max(x) as usize;
+    let end = (columns.end.min(output_width) as usize).min(x as usize + src_width as usize);
+    if start >= end {
+        return;
+    }
+    let (x, y) = (x as usize, y as usize);
+    let output_width = output_width as usize;
+    let rows = (src_height as usize).min((output_height as usize).saturating_sub(y));
+
+    for row in 0..rows {
+        let src_start = (row * src_width as usize + start - x) * 4;
+        let dst = ((y + row) * output_width + start) * 4;
+        let len = (end - start) * 4;
+        output[dst..dst + len].copy_from_slice(&src[src_start..src_start + len]);
     }
-
-    output
 }
 
-/// Find ffmpeg executable
-fn find_ffmpeg(custom_path: Option<&str>) -> Result<String> {
-    if let Some(path) = custom_path {
-        if std::path::Path::new(path).exists() {
-            return Ok(path.to_string());
-        }
-        return Err(Error::Ffmpeg(format!("FFmpeg not found at: {}", path)));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_single_row() {
+        let grid = Grid::new(&[(160, 120), (100, 80)], &Layout::default()).unwrap();
+        assert_eq!(grid.width, 260);
+        assert_eq!(grid.height, 120);
+        assert_eq!(grid.offsets, vec![(0, 0), (160, 0)]);
     }
 
-    // Try common paths
-    let paths = [
-        "ffmpeg",
-        "/usr/bin/ffmpeg",
-        "/usr/local/bin/ffmpeg",
-        "/opt/homebrew/bin/ffmpeg",
-    ];
-
-    for path in paths {
-        if Command::new(path)
-            .arg("-version")
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .status()
-            .is_ok()
-        {
-            return Ok(path.to_string());
-        }
+    #[test]
+    fn test_grid_columns() {
+        let sizes = [(100, 50), (60, 70), (80, 40)];
+        let layout = Layout {
+            columns: 2,
+            ..Default::default()
+        };
+        let grid = Grid::new(&sizes, &layout).unwrap();
+        assert_eq!(grid.width, 160);
+        assert_eq!(grid.height, 110);
+        assert_eq!(grid.offsets, vec![(0, 0), (100, 0), (0, 70)]);
     }
 
-    Err(Error::Ffmpeg("FFmpeg not found in PATH".to_string()))
-}
+    #[test]
+    fn test_grid_label_bands() {
+        let above = Caption {
+            size: 20.0,
+            outline: None,
+            position: CaptionPosition::Top,
+            ..Caption::new("original", "font.ttf")
+        };
+        let below = Caption {
+            size: 10.0,
+            outline: None,
+            ..Caption::new("optimized", "font.ttf")
+        };
+        let layout = Layout {
+            columns: 0,
+            labels: vec![above, below.clone(), below],
+            ..Default::default()
+        };
+        let grid = Grid::new(&[(160, 120), (100, 80)], &layout).unwrap();
+        // 30 pixels above and 15 below each row, the extra label ignored
+        assert_eq!((grid.width, grid.height), (260, 164));
+        assert_eq!(grid.offsets, vec![(0, 30), (160, 30)]);
+        assert_eq!(grid.label_bands, vec![(0, 0, 160, 30), (160, 150, 100, 15)]);
+    }
 
-/// Get video information using ffprobe
-fn get_video_info<P: AsRef<Path>>(path: P, ffmpeg: &str) -> Result<(u32, u32, f64, u64)> {
-    // Derive ffprobe path from ffmpeg path
-    let ffprobe = if ffmpeg.ends_with("ffmpeg") {
-        ffmpeg.replace("ffmpeg", "ffprobe")
-    } else {
-        "ffprobe".to_string()
-    };
+    #[test]
+    fn test_grid_dividers() {
+        let sizes = [(100, 50), (60, 70), (80, 40)];
+        let layout = Layout {
+            columns: 2,
+            gap: 4,
+            ..Default::default()
+        };
+        let grid = Grid::new(&sizes, &layout).unwrap();
+        assert_eq!((grid.width, grid.height), (164, 114));
+        assert_eq!(grid.offsets, vec![(0, 0), (104, 0), (0, 74)]);
+        assert_eq!(grid.dividers, vec![(100, 0, 4, 114), (0, 70, 164, 4)]);
+
+        let frame = grid
+            .base_frame(
+                &Layout {
+                    divider_color: Some(Color { r: 255, g: 0, b: 0 }),
+                    ..layout
+                },
+                &Color { r: 0, g: 0, b: 0 },
+            )
+            .unwrap();
+        let pixel = |x: usize, y: usize| &frame[(y * 164 + x) * 4..][..4];
+        assert_eq!(pixel(99, 10), [0, 0, 0, 255]);
+        assert_eq!(pixel(102, 10), [255, 0, 0, 255]);
+        assert_eq!(pixel(10, 72), [255, 0, 0, 255]);
+    }
 
-    let output = Command::new(&ffprobe)
-        .args([
-            "-v",
-            "error",
-            "-select_streams",
-            "v:0",
-            "-show_entries",
-            "stream=width,height,r_frame_rate,nb_frames",
-            "-of",
-            "csv=p=0",
-            path.as_ref().to_str().unwrap(),
-        ])
-        .output()
-        .map_err(|e| Error::Ffmpeg(format!("Failed to run ffprobe: {}", e)))?;
-
-    let info = String::from_utf8_lossy(&output.stdout);
-    let parts: Vec<&str> = info.trim().split(',').collect();
-
-    if parts.len() < 3 {
-        return Err(Error::Decode(format!(
-            "Failed to parse video info: {}",
-            info
-        )));
+    #[test]
+    fn test_grid_align() {
+        let sizes = [(160, 120), (100, 80)];
+        let offsets = |align| {
+            let layout = Layout {
+                align,
+                ..Default::default()
+            };
+            Grid::new(&sizes, &layout).unwrap().offsets
+        };
+        assert_eq!(offsets(VerticalAlign::Top), vec![(0, 0), (160, 0)]);
+        assert_eq!(offsets(VerticalAlign::Center), vec![(0, 0), (160, 20)]);
+        assert_eq!(offsets(VerticalAlign::Bottom), vec![(0, 0), (160, 40)]);
     }
 
-    let width: u32 = parts[0]
-        .parse()
-        .map_err(|_| Error::Decode("Failed to parse width".to_string()))?;
-
-    let height: u32 = parts[1]
-        .parse()
-        .map_err(|_| Error::Decode("Failed to parse height".to_string()))?;
-
-    // Parse frame rate (e.g., "30/1" or "30000/1001")
-    let fps: f64 = if parts[2].contains('/') {
-        let fps_parts: Vec<&str> = parts[2].split('/').collect();
-        let num: f64 = fps_parts[0].parse().unwrap_or(30.0);
-        let den: f64 = fps_parts[1].parse().unwrap_or(1.0);
-        num / den
-    } else {
-        parts[2].parse().unwrap_or(30.0)
-    };
+    #[test]
+    fn test_scaled_sizes() {
+        let sizes = [(1280, 720), (1920, 1080), (101, 50)];
+        assert_eq!(
+            scaled_sizes(&sizes, HeightMode::Original).unwrap(),
+            sizes.to_vec()
+        );
+        assert_eq!(
+            scaled_sizes(&sizes, HeightMode::Tallest).unwrap(),
+            vec![(1920, 1080), (1920, 1080), (2182, 1080)]
+        );
+        assert_eq!(
+            scaled_sizes(&sizes, HeightMode::Fixed(360)).unwrap(),
+            vec![(640, 360), (640, 360), (727, 360)]
+        );
+        assert!(matches!(
+            scaled_sizes(&sizes, HeightMode::Fixed(0)),
+            Err(Error::InvalidInput(_))
+        ));
+    }
 
-    let frame_count: u64 = parts.get(3).and_then(|s| s.parse().ok()).unwrap_or(0);
-
-    // If frame count is not available, estimate from duration
-    let frame_count = if frame_count == 0 {
-        // Try to get duration
-        let duration_output = Command::new(&ffprobe)
-            .args([
-                "-v",
-                "error",
-                "-show_entries",
-                "format=duration",
-                "-of",
-                "csv=p=0",
-                path.as_ref().to_str().unwrap(),
-            ])
-            .output()
-            .ok();
-
-        if let Some(output) = duration_output {
-            let duration_str = String::from_utf8_lossy(&output.stdout);
-            let duration: f64 = duration_str.trim().parse().unwrap_or(0.0);
-            (duration * fps).ceil() as u64
-        } else {
-            0
-        }
-    } else {
-        frame_count
-    };
+    #[test]
+    fn test_grid_wipe() {
+        let label = Caption {
+            size: 10.0,
+            outline: None,
+            ..Caption::new("before", "font.ttf")
+        };
+        let layout = Layout {
+            labels: vec![label.clone(), label],
+            gap: 4,
+            align: VerticalAlign::Center,
+            composition: Composition::Wipe,
+            ..Default::default()
+        };
+        let grid = Grid::new(&[(160, 120), (101, 80)], &layout).unwrap();
+        // Overlaid in one cell, the labels side by side below it
+        assert_eq!((grid.width, grid.height), (160, 134));
+        assert_eq!(grid.offsets, vec![(0, 0), (0, 20)]);
+        assert_eq!(grid.label_bands, vec![(0, 120, 80, 15), (80, 120, 80, 15)]);
+        assert!(grid.dividers.is_empty());
+
+        assert!(matches!(
+            Grid::new(&[(160, 120); 3], &layout),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_wipe_frames() {
+        let layout = Layout {
+            composition: Composition::Wipe,
+            ..Default::default()
+        };
+        let grid = Grid::new(&[(8, 2), (8, 2)], &layout).unwrap();
+        let (first, second) = (vec![1u8; 8 * 2 * 4], vec![2u8; 8 * 2 * 4]);
+        let frame = |data| {
+            Some(DecodedFrame {
+                width: 8,
+                height: 2,
+                data,
+            })
+        };
+        let frames = [frame(&first[..]), frame(&second[..])];
+        let base = vec![0u8; 8 * 2 * 4];
+        let mut output = vec![0u8; 8 * 2 * 4];
+
+        let columns = |output: &[u8]| output[..8 * 4].chunks(4).map(|p| p[0]).collect::<Vec<_>>();
+        wipe_frames(&frames, &grid, &base, 0, &mut output);
+        assert_eq!(columns(&output), [1; 8]);
+        wipe_frames(&frames, &grid, &base, 3, &mut output);
+        draw_divider(&mut output, &grid, 3, 2, &Color { r: 9, g: 9, b: 9 });
+        assert_eq!(columns(&output), [2, 2, 9, 9, 1, 1, 1, 1]);
+        assert_eq!(output[8 * 4..], output[..8 * 4]);
+
+        assert_eq!(wipe_position(0, 31, 8), 0);
+        assert_eq!(wipe_position(15, 31, 8), 4);
+        assert_eq!(wipe_position(30, 31, 8), 8);
+        assert_eq!(wipe_position(0, 1, 8), 0);
+    }
 
-    Ok((width, height, fps, frame_count))
+    #[test]
+    fn test_grid_too_large() {
+        let sizes = [(u32::MAX - 1, 2), (4, 2)];
+        assert!(matches!(
+            Grid::new(&sizes, &Layout::default()),
+            Err(Error::InvalidInput(_))
+        ));
+    }
 }
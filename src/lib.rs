@@ -3,38 +3,138 @@
 //! This library provides two main functions:
 //! - `slideshow`: Create a video from a sequence of images with durations
 //! - `juxtapose`: Combine two videos side by side
+//!
+//! `juxtapose_n` generalizes `juxtapose` to any number of videos arranged in a grid.
+//!
+//! `slideshow_to_memory` and `juxtapose_to_memory` return the encoded video as bytes
+//! instead of writing a file.
+//!
+//! `transcode`, `trim` and `concat` re-encode existing videos, and `thumbnail` extracts a
+//! still frame.
+//!
+//! `encode_frames` encodes frames rendered by the caller, for procedurally generated video.
+//! `compose` renders a `Timeline` of image, video, color and text clips on tracks.
+//!
+//! `slideshow_batch` creates many slideshows over a bounded number of threads, decoding
+//! shared images once.
+//!
+//! `verify` decodes an encoded video and compares it with its source frames (PSNR, SSIM and
+//! checksums); slideshows run it themselves when `EncodeOptions::verify` is set.
+//!
+//! `probe` reads basic metadata (dimensions, frame rate, duration, codec) from a video file.
+//!
+//! On `wasm32` the C FFI is left out; the `wasm` feature adds JavaScript bindings for
+//! AV1/WebM slideshows instead. The `napi` feature adds Node.js bindings.
 
+pub mod cancel;
+pub mod capabilities;
+pub mod caption;
 pub mod encoder;
 pub mod error;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod ffi;
+pub mod filter;
+pub mod image_cache;
 pub mod image_loader;
+pub mod logging;
 pub mod muxer;
+#[cfg(feature = "napi")]
+pub mod node;
+pub mod probe;
+pub mod progress;
+pub mod report;
+pub mod runtime;
+pub mod stats;
+pub mod transition;
+pub mod validate;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod watermark;
 
+mod animation;
+mod batch;
+mod budget;
+mod decoder;
+mod fade;
+mod ffmpeg;
+mod frames;
+mod heif;
 mod juxtapose;
+mod pipeline;
+mod pool;
+mod priority;
 mod slideshow;
+mod spill;
+mod thumbnail;
+mod timeline;
+mod transcode;
+mod verify;
 
-pub use error::{Error, Result};
-pub use juxtapose::juxtapose;
-pub use slideshow::slideshow;
+pub use batch::{slideshow_batch, SlideshowJob};
+pub use cancel::CancelToken;
+pub use capabilities::{capabilities, Capability};
+pub use caption::{Caption, CaptionPosition};
+pub use encoder::{Av1Backend, ColorSpace, EncoderBackend, EncoderPool, PixelRange, RateControl};
+pub use error::{Error, Result, Stage};
+pub use filter::{FilterChain, FilterFrame, FrameFilter};
+pub use frames::{encode_frames, encode_frames_to_memory, FrameFormat};
+pub use image_cache::ImageCache;
+pub use image_loader::{Rotation, ScaleMode};
+pub use juxtapose::{
+    juxtapose, juxtapose_n, juxtapose_n_to_memory, juxtapose_to_memory, mosaic, Composition,
+    EndPolicy, HeightMode, Layout, Trim, VerticalAlign,
+};
+pub use logging::{set_log_callback, LogCallback, LogLevel};
+pub use priority::Priority;
+pub use probe::{probe, VideoInfo};
+pub use progress::{Phase, Progress, ProgressCallback};
+pub use report::{Report, Warning, WarningCallback};
+pub use slideshow::{slideshow, slideshow_to_memory};
+pub use stats::EncodeStats;
+pub use thumbnail::{thumbnail, thumbnail_to_file};
+pub use timeline::{compose, compose_to_memory, Clip, ClipContent, Timeline, Track};
+pub use transcode::{concat, transcode, trim};
+pub use transition::{Direction, Transition, TransitionEffect};
+pub use validate::{StrictLimits, Violation};
+pub use verify::{verify, FrameQuality, QualityReport, MAX_PSNR};
+pub use watermark::{Watermark, WatermarkPosition};
 
 /// Video codec types
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(C)]
 pub enum Codec {
     /// AV1 codec (using rav1e/libaom)
     Av1 = 0,
     /// H.264 codec (platform-specific implementation)
     H264 = 1,
+    /// VP9 codec (ffmpeg with libvpx-vp9)
+    Vp9 = 2,
 }
 
 /// Container format types
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(C)]
 pub enum Container {
     /// MP4 container (supports AV1 and H.264)
     Mp4 = 0,
-    /// WebM container (supports AV1 only)
+    /// WebM container (supports AV1 and VP9)
     WebM = 1,
+    /// Lossless animated WebP image, slideshows only (the codec is ignored)
+    WebP = 2,
+    /// Lossless animated PNG image, slideshows only (the codec is ignored)
+    Apng = 3,
+    /// Matroska container (supports AV1, H.264 and VP9)
+    Mkv = 4,
+    /// HLS playlist (`output_path`) with MPEG-TS segments next to it (supports H.264)
+    Hls = 5,
+    /// IVF, raw frames with no container overhead (supports AV1 and VP9)
+    Ivf = 6,
+    /// Raw H.264 elementary stream in Annex B format, with no container (supports H.264)
+    AnnexB = 7,
+    /// Uncompressed YUV4MPEG2 frames for external encoders (the codec is ignored)
+    Y4m = 8,
 }
 
 impl Container {
@@ -42,14 +142,29 @@ impl Container {
     pub fn supports_codec(&self, codec: Codec) -> bool {
         match (self, codec) {
             (Container::Mp4, _) => true,
-            (Container::WebM, Codec::Av1) => true,
+            (Container::WebM, Codec::Av1 | Codec::Vp9) => true,
             (Container::WebM, Codec::H264) => false,
+            (Container::Mkv, _) => true,
+            (Container::Hls, Codec::H264) => true,
+            (Container::Hls, Codec::Av1 | Codec::Vp9) => false,
+            (Container::Ivf, Codec::Av1 | Codec::Vp9) => true,
+            (Container::Ivf, Codec::H264) => false,
+            (Container::AnnexB, Codec::H264) => true,
+            (Container::AnnexB, Codec::Av1 | Codec::Vp9) => false,
+            (Container::Y4m, _) => true,
+            // Animated images are written without a video codec
+            (Container::WebP | Container::Apng, _) => true,
         }
     }
+
+    /// Check if the container is an animated image, showing each slide as one frame
+    pub fn is_animation(&self) -> bool {
+        matches!(self, Container::WebP | Container::Apng)
+    }
 }
 
 /// RGB color representation
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(C)]
 pub struct Color {
     pub r: u8,
@@ -67,13 +182,37 @@ impl Default for Color {
     }
 }
 
+/// Where the image of a slide comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum SlideSource {
+    /// Path to the image file
+    Path(String),
+    /// Contents of an image file, such as a JPEG or a PNG, decoded like a file would be
+    Bytes(Vec<u8>),
+    /// Pixels already decoded, 4 bytes per pixel in RGBA order, row by row
+    Rgba {
+        width: u32,
+        height: u32,
+        data: Vec<u8>,
+    },
+}
+
 /// Slide entry for slideshow creation
 #[derive(Debug, Clone)]
 pub struct SlideEntry {
-    /// Path to the image file
-    pub path: String,
+    /// Image shown by the slide
+    pub source: SlideSource,
     /// Duration to display this image in milliseconds
     pub duration_ms: u32,
+    /// Transition from the previous slide into this one, ignored for the first slide
+    pub transition: Option<Transition>,
+    /// Color of the bars around this image when letterboxed, instead of
+    /// [`EncodeOptions::letterbox_color`]
+    pub letterbox_color: Option<Color>,
+    /// Text drawn on top of the image (see [`Caption`])
+    pub caption: Option<Caption>,
+    /// Rotation of the image once loaded, for images without orientation metadata
+    pub rotation: Option<Rotation>,
 }
 
 /// Options for video encoding
@@ -89,6 +228,110 @@ pub struct EncodeOptions {
     pub quality: u8,
     /// Path to ffmpeg executable (for H.264 on Linux)
     pub ffmpeg_path: Option<String>,
+    /// Callback invoked after each encoded frame
+    pub progress: Option<ProgressCallback>,
+    /// Token to cancel the operation
+    pub cancel: Option<CancelToken>,
+    /// Callback invoked for each non-fatal warning (see [`Report`])
+    pub warning: Option<WarningCallback>,
+    /// Check all inputs against these limits before encoding starts (see [`StrictLimits`])
+    pub strict: Option<StrictLimits>,
+    /// Most memory in bytes for slide images, buffered frames and encoded packets
+    ///
+    /// Frames are produced one at a time when buffering ahead would not fit; exceeding the
+    /// limit otherwise fails with [`Error::MemoryLimit`].
+    pub memory_limit: Option<u64>,
+    /// Reuse encoders from this pool instead of creating one per operation
+    pub encoder_pool: Option<EncoderPool>,
+    /// Reuse decoded and resized slide images from this cache (see [`ImageCache`])
+    pub image_cache: Option<ImageCache>,
+    /// Produce byte-identical output for identical inputs, regardless of the machine
+    ///
+    /// Encoders run with fixed thread counts and AV1 slideshows are split into a fixed
+    /// number of segments, at some cost in speed. H.264 is encoded with libx264 instead of
+    /// NVENC or VAAPI on Linux; VideoToolbox and Media Foundation give no such guarantee.
+    pub deterministic: bool,
+    /// Decode slideshows written to `output_path` and compare them with their slides
+    ///
+    /// The report is returned in [`EncodeStats::quality`]. Needs ffmpeg, and is skipped
+    /// for in-memory output and other operations (see [`verify`]).
+    pub verify: bool,
+    /// Keep slide images in temporary files in this directory instead of in memory
+    ///
+    /// Each slide is read back while its frames are encoded, so long slideshows of large
+    /// images need little memory. Images shared with [`EncodeOptions::image_cache`] stay
+    /// in memory.
+    pub spill_dir: Option<String>,
+    /// Run ffmpeg processes and encoding threads at this priority (see [`Priority`])
+    pub priority: Priority,
+    /// Threads encoders and ffmpeg may use (0 for one per core), instead of those of the
+    /// runtime configuration (see [`runtime::Config::threads`])
+    pub threads: Option<usize>,
+    /// Filters applied to every frame before it is encoded (see [`FilterChain`])
+    pub filters: FilterChain,
+    /// Encoder for AV1 (see [`Av1Backend`])
+    pub av1_backend: Av1Backend,
+    /// How slides of another aspect ratio than the first are scaled (see [`ScaleMode`])
+    pub scale_mode: ScaleMode,
+    /// Color of the bars around slides fitted with [`ScaleMode::Fit`] (default black)
+    pub letterbox_color: Color,
+    /// Image drawn over every frame of slideshows and juxtapositions (see [`Watermark`])
+    pub watermark: Option<Watermark>,
+    /// Duration in milliseconds of the fade from `fade_color` at the start of videos (0 for none)
+    pub fade_in_ms: u32,
+    /// Duration in milliseconds of the fade to `fade_color` at the end of videos (0 for none)
+    pub fade_out_ms: u32,
+    /// Color videos fade from and to (default black)
+    pub fade_color: Color,
+    /// Bitrate to encode at in kilobits per second, instead of by `quality`
+    pub bitrate_kbps: Option<u32>,
+    /// Whether to encode at constant quality, or at an average or constant `bitrate_kbps`
+    /// (see [`RateControl`])
+    pub rate_control: RateControl,
+    /// Milliseconds between keyframes, such as the segment duration for HLS, instead of
+    /// the encoder's default
+    pub keyframe_interval_ms: Option<u32>,
+    /// Color space to encode in and tag the video with (see [`ColorSpace`])
+    pub color_space: ColorSpace,
+    /// Range of the YUV values to encode in and tag the video with (see [`PixelRange`])
+    pub pixel_range: PixelRange,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self {
+            output_path: String::new(),
+            container: Container::WebM,
+            codec: Codec::Av1,
+            quality: 50,
+            ffmpeg_path: None,
+            progress: None,
+            cancel: None,
+            warning: None,
+            strict: None,
+            memory_limit: None,
+            encoder_pool: None,
+            image_cache: None,
+            deterministic: false,
+            verify: false,
+            spill_dir: None,
+            priority: Priority::Normal,
+            threads: None,
+            filters: FilterChain::new(),
+            av1_backend: Av1Backend::Rav1e,
+            scale_mode: ScaleMode::Stretch,
+            letterbox_color: Color { r: 0, g: 0, b: 0 },
+            watermark: None,
+            fade_in_ms: 0,
+            fade_out_ms: 0,
+            fade_color: Color { r: 0, g: 0, b: 0 },
+            bitrate_kbps: None,
+            rate_control: RateControl::Auto,
+            keyframe_interval_ms: None,
+            color_space: ColorSpace::Bt601,
+            pixel_range: PixelRange::Auto,
+        }
+    }
 }
 
 impl EncodeOptions {
@@ -102,6 +345,83 @@ impl EncodeOptions {
         }
         Ok(())
     }
+
+    /// Validate the options of an operation producing video frames at a fixed rate
+    ///
+    /// Animated images are only written by slideshows.
+    pub(crate) fn validate_video(&self) -> Result<()> {
+        self.validate()?;
+        if self.container.is_animation() {
+            return Err(Error::InvalidInput(format!(
+                "{:?} output is only supported for slideshows",
+                self.container
+            )));
+        }
+        Ok(())
+    }
+
+    /// Return `Error::Cancelled` if cancellation has been requested
+    pub(crate) fn check_cancelled(&self) -> Result<()> {
+        match self.cancel {
+            Some(ref token) if token.is_cancelled() => Err(Error::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    /// Threads encoders may use, from the runtime configuration unless set (0 for one
+    /// per core)
+    pub(crate) fn threads(&self) -> usize {
+        self.threads.unwrap_or(runtime::config().threads)
+    }
+
+    /// Frames between keyframes at `fps` for `keyframe_interval_ms`, at least one
+    pub(crate) fn keyframe_interval(&self, fps: u32) -> Option<u32> {
+        self.keyframe_interval_ms
+            .filter(|&ms| ms > 0)
+            .map(|ms| ((ms as u64 * fps as u64 + 500) / 1000).clamp(1, u32::MAX as u64) as u32)
+    }
+
+    /// Report progress to the registered callback, if any, followed by the start of
+    /// `Phase::Finishing` after the last frame
+    pub(crate) fn report_progress(&self, frames_done: u64, frames_total: u64) {
+        if let Some(ref progress) = self.progress {
+            progress.call(Progress {
+                frames_done,
+                frames_total,
+                phase: Phase::Encoding,
+            });
+            if frames_done == frames_total {
+                progress.call(Progress {
+                    frames_done,
+                    frames_total,
+                    phase: Phase::Finishing,
+                });
+            }
+        }
+    }
+
+    /// Log a warning and pass it to the registered callback, if any
+    pub(crate) fn warn(&self, warning: Warning) {
+        logging::log_warn!("{}", warning);
+        if let Some(ref callback) = self.warning {
+            callback.call(&warning);
+        }
+    }
+}
+
+/// Start platform runtimes (COM and Media Foundation on Windows) ahead of time
+///
+/// Optional: encoders start them on demand and stop them when the last encoder is
+/// dropped. After `init` they stay running until [`shutdown`], avoiding repeated startup.
+pub fn init() -> Result<()> {
+    encoder::h264::init()
+}
+
+/// Release platform runtimes kept by [`init`]
+///
+/// Encoders still alive keep their runtimes running until they are dropped.
+pub fn shutdown() {
+    encoder::h264::shutdown()
 }
 
 /// Check if a codec is available on the current system
@@ -120,5 +440,11 @@ pub fn available(codec: Codec, ffmpeg_path: Option<&str>) -> Result<()> {
             }
         }
         Codec::H264 => encoder::h264::check_available(ffmpeg_path),
+        #[cfg(not(target_arch = "wasm32"))]
+        Codec::Vp9 => encoder::vp9::check_available(ffmpeg_path),
+        #[cfg(target_arch = "wasm32")]
+        Codec::Vp9 => Err(Error::CodecUnavailable(
+            "VP9 needs ffmpeg, which cannot be run on wasm32".to_string(),
+        )),
     }
 }
@@ -0,0 +1,167 @@
+//! Diagnostic log routing
+//!
+//! Internal messages (encoder selection, ffmpeg invocations, warnings) are dropped unless a
+//! callback is registered with [`set_log_callback`]. With the `tracing` feature, messages are
+//! also emitted as `tracing` events and operations are instrumented with spans per stage.
+
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+/// Log message severity, from most to least severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(C)]
+pub enum LogLevel {
+    Error = 0,
+    Warn = 1,
+    Info = 2,
+    Debug = 3,
+    Trace = 4,
+}
+
+/// Callback receiving log messages
+///
+/// The callback may be called from any thread running a minmpeg operation.
+#[derive(Clone)]
+pub struct LogCallback(Arc<LogFn>);
+
+type LogFn = dyn Fn(LogLevel, &str) + Send + Sync;
+
+impl LogCallback {
+    /// Create a log callback from a closure
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(LogLevel, &str) + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    /// Invoke the callback
+    pub fn call(&self, level: LogLevel, message: &str) {
+        (self.0)(level, message)
+    }
+}
+
+impl fmt::Debug for LogCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LogCallback")
+    }
+}
+
+/// Registered callback and the most verbose level it receives
+static LOGGER: RwLock<Option<(LogCallback, LogLevel)>> = RwLock::new(None);
+
+/// Register a process-wide log callback receiving messages at `level` or more severe
+///
+/// Passing `None` disables logging.
+pub fn set_log_callback(callback: Option<LogCallback>, level: LogLevel) {
+    let mut logger = match LOGGER.write() {
+        Ok(logger) => logger,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    *logger = callback.map(|callback| (callback, level));
+}
+
+/// Send a message to the registered callback, formatting it only if it will be delivered
+pub(crate) fn log(level: LogLevel, message: impl FnOnce() -> String) {
+    let logger = match LOGGER.read() {
+        Ok(logger) => logger,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    if let Some((ref callback, max_level)) = *logger {
+        if level <= max_level {
+            callback.call(level, &message());
+        }
+    }
+}
+
+macro_rules! log_warn {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        tracing::warn!($($arg)*);
+        $crate::logging::log($crate::logging::LogLevel::Warn, || format!($($arg)*))
+    }};
+}
+
+macro_rules! log_info {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        tracing::info!($($arg)*);
+        $crate::logging::log($crate::logging::LogLevel::Info, || format!($($arg)*))
+    }};
+}
+
+macro_rules! log_debug {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+        $crate::logging::log($crate::logging::LogLevel::Debug, || format!($($arg)*))
+    }};
+}
+
+macro_rules! log_trace {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)*);
+        $crate::logging::log($crate::logging::LogLevel::Trace, || format!($($arg)*))
+    }};
+}
+
+/// Guard returned by `trace_span!` without the `tracing` feature
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct NoSpan;
+
+/// Enter an info-level `tracing` span until the returned guard is dropped
+///
+/// Takes the same arguments as `tracing::info_span!`; they are not evaluated without the
+/// `tracing` feature.
+macro_rules! trace_span {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!($($arg)*).entered();
+        #[cfg(not(feature = "tracing"))]
+        let span = $crate::logging::NoSpan;
+        span
+    }};
+}
+
+pub(crate) use {log_debug, log_info, log_trace, log_warn, trace_span};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_log_level_filter() {
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let messages_clone = Arc::clone(&messages);
+
+        set_log_callback(
+            Some(LogCallback::new(move |level, message| {
+                messages_clone
+                    .lock()
+                    .unwrap()
+                    .push((level, message.to_string()));
+            })),
+            LogLevel::Info,
+        );
+
+        log_warn!("warning {}", 1);
+        log_debug!("hidden");
+        set_log_callback(None, LogLevel::Trace);
+        log_warn!("after reset");
+
+        // Other tests may log concurrently, so only look at this test's messages
+        let messages: Vec<_> = messages
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, message)| {
+                ["warning 1", "hidden", "after reset"].contains(&message.as_str())
+            })
+            .cloned()
+            .collect();
+        assert_eq!(messages, vec![(LogLevel::Warn, "warning 1".to_string())]);
+    }
+}
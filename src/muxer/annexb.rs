@@ -0,0 +1,104 @@
+//! Raw H.264 elementary stream in Annex B format, with no container
+
+use super::avc::AccessUnits;
+use super::{Muxer, MuxerConfig, MuxerOutput};
+use crate::encoder::Packet;
+use crate::{Codec, Error, Result};
+
+/// Annex B writer (H.264 only)
+///
+/// Each frame is written as soon as it is complete, starting with an access unit
+/// delimiter, and keyframes carry the SPS and PPS so the stream can be cut at any of them.
+pub struct AnnexBMuxer {
+    writer: Box<dyn MuxerOutput>,
+    units: AccessUnits,
+}
+
+impl AnnexBMuxer {
+    /// Create a muxer writing to an arbitrary output
+    pub fn with_output(writer: Box<dyn MuxerOutput>, config: MuxerConfig) -> Result<Self> {
+        Self::check_codec(config.codec)?;
+
+        Ok(Self {
+            writer,
+            units: AccessUnits::new(config.codec_config.as_deref(), config.pps.as_deref()),
+        })
+    }
+
+    /// Check if codec is supported by Annex B output
+    pub fn check_codec(codec: Codec) -> Result<()> {
+        match codec {
+            Codec::H264 => Ok(()),
+            _ => Err(Error::Mux(
+                "Annex B output only supports H.264 codec".to_string(),
+            )),
+        }
+    }
+}
+
+impl Muxer for AnnexBMuxer {
+    fn write_packet(&mut self, packet: &Packet) -> Result<()> {
+        if let Some(unit) = self.units.push(packet) {
+            self.writer.write_all(&self.units.annex_b(&unit))?;
+        }
+        Ok(())
+    }
+
+    fn finalize(mut self: Box<Self>) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::muxer::MemoryOutput;
+    use crate::{ColorSpace, PixelRange};
+
+    #[test]
+    fn test_annex_b_repeats_parameter_sets() {
+        let output = MemoryOutput::new();
+        let config = MuxerConfig {
+            width: 64,
+            height: 48,
+            fps: 30,
+            codec: Codec::H264,
+            codec_config: Some(vec![0x67, 0x42]),
+            pps: Some(vec![0x68, 0xce]),
+            color_space: ColorSpace::Bt601,
+            pixel_range: PixelRange::Auto,
+        };
+        let mut muxer: Box<dyn Muxer> =
+            Box::new(AnnexBMuxer::with_output(Box::new(output.clone()), config).unwrap());
+        for (data, is_keyframe) in [(&[0x65, 0x88][..], true), (&[0x41, 0x9a], false)] {
+            muxer
+                .write_packet(&Packet {
+                    data: data.to_vec(),
+                    pts: 0,
+                    dts: 0,
+                    is_keyframe,
+                })
+                .unwrap();
+        }
+        muxer.finalize().unwrap();
+
+        let start = [0, 0, 0, 1];
+        let expected = [
+            &start[..],
+            &[0x09, 0xf0],
+            &start,
+            &[0x67, 0x42],
+            &start,
+            &[0x68, 0xce],
+            &start,
+            &[0x65, 0x88],
+            &start,
+            &[0x09, 0xf0],
+            &start,
+            &[0x41, 0x9a],
+        ]
+        .concat();
+        assert_eq!(output.take(), expected);
+    }
+}
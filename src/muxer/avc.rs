@@ -0,0 +1,217 @@
+//! H.264 access units for Matroska, with length-prefixed NAL units
+
+use crate::encoder::Packet;
+
+/// NAL unit types carried by the decoder configuration
+const NAL_SPS: u8 = 7;
+const NAL_PPS: u8 = 8;
+/// NAL unit type of an IDR slice
+const NAL_IDR: u8 = 5;
+
+/// Access unit delimiter starting every frame in Annex B output
+const AUD: [u8; 6] = [0, 0, 0, 1, 0x09, 0xf0];
+
+const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+/// Groups encoder packets into access units and records the parameter sets
+///
+/// Encoders hand over either Annex B data (start code prefixed) or one bare NAL unit per
+/// packet. Matroska needs each frame as one block of NAL units prefixed with their 4-byte
+/// length, so NAL units are held back until the slice of their frame arrives.
+#[derive(Default)]
+pub(crate) struct AccessUnits {
+    /// Length-prefixed NAL units of the frame being assembled
+    pending: Vec<u8>,
+    pending_keyframe: bool,
+    sps: Option<Vec<u8>>,
+    pps: Option<Vec<u8>>,
+}
+
+impl AccessUnits {
+    /// Start with the parameter sets reported by the encoder, if any
+    pub fn new(sps: Option<&[u8]>, pps: Option<&[u8]>) -> Self {
+        Self {
+            sps: sps.filter(|nal| !nal.is_empty()).map(<[u8]>::to_vec),
+            pps: pps.filter(|nal| !nal.is_empty()).map(<[u8]>::to_vec),
+            ..Default::default()
+        }
+    }
+
+    /// Add a packet, returning the access unit it completes, if any
+    pub fn push(&mut self, packet: &Packet) -> Option<Packet> {
+        let mut has_slice = false;
+        for nal in nal_units(&packet.data) {
+            let nal_type = nal[0] & 0x1f;
+            match nal_type {
+                NAL_SPS if self.sps.is_none() => self.sps = Some(nal.to_vec()),
+                NAL_PPS if self.pps.is_none() => self.pps = Some(nal.to_vec()),
+                _ => {}
+            }
+            has_slice |= (1..=NAL_IDR).contains(&nal_type);
+            self.pending_keyframe |= nal_type == NAL_IDR;
+            self.pending
+                .extend_from_slice(&(nal.len() as u32).to_be_bytes());
+            self.pending.extend_from_slice(nal);
+        }
+
+        if !has_slice {
+            return None;
+        }
+        let is_keyframe = std::mem::take(&mut self.pending_keyframe);
+        Some(Packet {
+            data: std::mem::take(&mut self.pending),
+            pts: packet.pts,
+            dts: packet.dts,
+            is_keyframe: is_keyframe || packet.is_keyframe,
+        })
+    }
+
+    /// SPS and PPS, once both are known
+    pub fn parameter_sets(&self) -> Option<(&[u8], &[u8])> {
+        Some((self.sps.as_deref()?, self.pps.as_deref()?))
+    }
+
+    /// Annex B data of an access unit, with the parameter sets repeated at keyframes
+    pub fn annex_b(&self, unit: &Packet) -> Vec<u8> {
+        let mut data = AUD.to_vec();
+        let has_sps = length_prefixed(&unit.data).any(|nal| nal[0] & 0x1f == NAL_SPS);
+        if unit.is_keyframe && !has_sps {
+            if let Some((sps, pps)) = self.parameter_sets() {
+                for nal in [sps, pps] {
+                    data.extend_from_slice(&START_CODE);
+                    data.extend_from_slice(nal);
+                }
+            }
+        }
+        for nal in length_prefixed(&unit.data) {
+            data.extend_from_slice(&START_CODE);
+            data.extend_from_slice(nal);
+        }
+        data
+    }
+
+    /// AVCDecoderConfigurationRecord, once both parameter sets are known
+    pub fn decoder_config(&self) -> Option<Vec<u8>> {
+        let (sps, pps) = (self.sps.as_ref()?, self.pps.as_ref()?);
+        if sps.len() < 4 {
+            return None;
+        }
+
+        let mut config = vec![
+            1,      // configurationVersion
+            sps[1], // AVCProfileIndication
+            sps[2], // profile_compatibility
+            sps[3], // AVCLevelIndication
+            0xff,   // 4-byte NAL unit lengths
+            0xe1,   // one SPS
+        ];
+        config.extend_from_slice(&(sps.len() as u16).to_be_bytes());
+        config.extend_from_slice(sps);
+        config.push(1); // one PPS
+        config.extend_from_slice(&(pps.len() as u16).to_be_bytes());
+        config.extend_from_slice(pps);
+        Some(config)
+    }
+}
+
+/// NAL units of an access unit returned by [`AccessUnits::push`]
+fn length_prefixed(mut data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    std::iter::from_fn(move || {
+        let (len, rest) = data.split_first_chunk::<4>()?;
+        let len = (u32::from_be_bytes(*len) as usize).min(rest.len());
+        let (nal, rest) = rest.split_at(len);
+        data = rest;
+        Some(nal)
+    })
+}
+
+/// NAL units of Annex B `data`, or `data` itself when it has no start code
+fn nal_units(data: &[u8]) -> Vec<&[u8]> {
+    // The emulation prevention of H.264 keeps start codes out of NAL units
+    let starts: Vec<usize> = (0..data.len().saturating_sub(2))
+        .filter(|&i| data[i..i + 3] == [0, 0, 1])
+        .collect();
+    match starts.first() {
+        Some(&first) if data[..first].iter().all(|&b| b == 0) => {}
+        _ if data.is_empty() => return Vec::new(),
+        _ => return vec![data],
+    }
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &pos)| {
+            let end = starts.get(i + 1).copied().unwrap_or(data.len());
+            let nal = &data[pos + 3..end];
+            // Drop trailing zeros, including the first byte of a 4-byte start code
+            let len = nal.iter().rposition(|&b| b != 0).map_or(0, |last| last + 1);
+            &nal[..len]
+        })
+        .filter(|nal| !nal.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(data: &[u8], is_keyframe: bool) -> Packet {
+        Packet {
+            data: data.to_vec(),
+            pts: 0,
+            dts: 0,
+            is_keyframe,
+        }
+    }
+
+    #[test]
+    fn test_nal_units() {
+        let data = [
+            0, 0, 0, 1, 0x67, 1, 2, 0, 0, 1, 0x68, 3, 0, 0, 0, 1, 0x65, 4, 0,
+        ];
+        assert_eq!(
+            nal_units(&data),
+            vec![&[0x67, 1, 2][..], &[0x68, 3][..], &[0x65, 4][..]]
+        );
+        // A bare NAL unit is taken as it is
+        assert_eq!(nal_units(&[0x41, 9]), vec![&[0x41, 9][..]]);
+    }
+
+    #[test]
+    fn test_access_units_from_bare_nal_units() {
+        let mut units = AccessUnits::new(None, None);
+        // SPS, PPS and IDR slice arrive as separate packets
+        assert!(units
+            .push(&packet(&[0x67, 0x64, 0x00, 0x1f, 0xac], false))
+            .is_none());
+        assert!(units.push(&packet(&[0x68, 0xee], false)).is_none());
+        let unit = units.push(&packet(&[0x65, 0x88], true)).unwrap();
+        assert!(unit.is_keyframe);
+        assert_eq!(
+            unit.data,
+            [
+                &[0, 0, 0, 5, 0x67, 0x64, 0x00, 0x1f, 0xac][..],
+                &[0, 0, 0, 2, 0x68, 0xee],
+                &[0, 0, 0, 2, 0x65, 0x88]
+            ]
+            .concat()
+        );
+
+        let config = units.decoder_config().unwrap();
+        assert_eq!(config[..6], [1, 0x64, 0x00, 0x1f, 0xff, 0xe1]);
+        assert_eq!(config[6..8], [0, 5]);
+
+        assert_eq!(
+            length_prefixed(&unit.data).collect::<Vec<_>>(),
+            vec![
+                &[0x67, 0x64, 0x00, 0x1f, 0xac][..],
+                &[0x68, 0xee],
+                &[0x65, 0x88]
+            ]
+        );
+
+        let unit = units.push(&packet(&[0, 0, 1, 0x41, 0x9a], false)).unwrap();
+        assert!(!unit.is_keyframe);
+        assert_eq!(unit.data, [0, 0, 0, 2, 0x41, 0x9a]);
+    }
+}
@@ -0,0 +1,393 @@
+//! HLS muxer writing MPEG-TS segments and an .m3u8 playlist
+
+use super::avc::AccessUnits;
+use super::{Muxer, MuxerConfig};
+use crate::encoder::Packet;
+use crate::{Codec, Error, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Shortest segment, ended at the first keyframe after it
+const SEGMENT_DURATION_MS: u64 = 6000;
+
+/// Size of an MPEG-TS packet and of its header
+const TS_PACKET: usize = 188;
+const TS_HEADER: usize = 4;
+
+/// Packet identifiers of the program map table and of the video stream
+const PMT_PID: u16 = 0x1000;
+const VIDEO_PID: u16 = 0x100;
+
+/// MPEG-TS stream type of H.264 video
+const STREAM_TYPE_H264: u8 = 0x1b;
+
+/// 90 kHz clock of MPEG-TS timestamps
+const CLOCK: u64 = 90_000;
+
+/// Delay of the decoding timestamps after the program clock, for the decoder buffer
+const DTS_DELAY: u64 = CLOCK / 10;
+
+/// HLS muxer (H.264 only)
+///
+/// The output path names the playlist; segments are written next to it, named after it
+/// with their index (`video.m3u8` gets `video0.ts`, `video1.ts`, ...). Segments start at
+/// keyframes and last at least [`SEGMENT_DURATION_MS`].
+pub struct HlsMuxer {
+    config: MuxerConfig,
+    playlist_path: PathBuf,
+    /// Playlist file stem, the prefix of segment names
+    stem: String,
+    units: AccessUnits,
+    /// Segment being written
+    segment: Option<Segment>,
+    /// File names and frame counts of the finished segments
+    segments: Vec<(String, u64)>,
+    /// Frames written so far
+    frames: u64,
+    /// Continuity counters of the PAT, the PMT and the video stream
+    counters: [u8; 3],
+}
+
+struct Segment {
+    writer: BufWriter<File>,
+    name: String,
+    first_frame: u64,
+}
+
+impl HlsMuxer {
+    /// Create a muxer writing the playlist to `output_path`
+    pub fn new<P: AsRef<Path>>(output_path: P, config: MuxerConfig) -> Result<Self> {
+        Self::check_codec(config.codec)?;
+
+        let playlist_path = output_path.as_ref().to_path_buf();
+        let stem = playlist_path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .filter(|stem| !stem.is_empty())
+            .ok_or_else(|| {
+                Error::InvalidInput(format!(
+                    "HLS playlist path has no file name: {}",
+                    playlist_path.display()
+                ))
+            })?
+            .to_string();
+        let units = AccessUnits::new(config.codec_config.as_deref(), config.pps.as_deref());
+
+        Ok(Self {
+            config,
+            playlist_path,
+            stem,
+            units,
+            segment: None,
+            segments: Vec::new(),
+            frames: 0,
+            counters: [0; 3],
+        })
+    }
+
+    /// Check if codec is supported by HLS
+    pub fn check_codec(codec: Codec) -> Result<()> {
+        match codec {
+            Codec::H264 => Ok(()),
+            _ => Err(Error::Mux(
+                "HLS output only supports H.264 codec".to_string(),
+            )),
+        }
+    }
+
+    /// Close the current segment and start the next one
+    fn start_segment(&mut self) -> Result<()> {
+        self.finish_segment()?;
+
+        let name = format!("{}{}.ts", self.stem, self.segments.len());
+        let file = File::create(self.playlist_path.with_file_name(&name)).map_err(Error::Io)?;
+        self.segment = Some(Segment {
+            writer: BufWriter::new(file),
+            name,
+            first_frame: self.frames,
+        });
+
+        // Every segment can be decoded on its own
+        let pat = psi_section(
+            0x00,
+            &[0x00, 0x01, 0xe0 | (PMT_PID >> 8) as u8, PMT_PID as u8],
+        );
+        let pmt = psi_section(
+            0x02,
+            &[
+                0xe0 | (VIDEO_PID >> 8) as u8, // PCR PID
+                VIDEO_PID as u8,
+                0xf0, // No program info
+                0x00,
+                STREAM_TYPE_H264,
+                0xe0 | (VIDEO_PID >> 8) as u8,
+                VIDEO_PID as u8,
+                0xf0, // No stream info
+                0x00,
+            ],
+        );
+        self.write_payload(0, 0x0000, Vec::new(), &pat)?;
+        self.write_payload(1, PMT_PID, Vec::new(), &pmt)
+    }
+
+    fn finish_segment(&mut self) -> Result<()> {
+        if let Some(mut segment) = self.segment.take() {
+            segment.writer.flush()?;
+            self.segments
+                .push((segment.name, self.frames - segment.first_frame));
+        }
+        Ok(())
+    }
+
+    /// Split `payload` into TS packets of a stream, starting a unit at the first
+    ///
+    /// `adaptation` holds the adaptation field of the first packet after its length,
+    /// empty for none.
+    fn write_payload(
+        &mut self,
+        stream: usize,
+        pid: u16,
+        mut adaptation: Vec<u8>,
+        mut payload: &[u8],
+    ) -> Result<()> {
+        let Some(segment) = self.segment.as_mut() else {
+            return Err(Error::Internal("No open HLS segment".to_string()));
+        };
+
+        let mut start = true;
+        while start || !payload.is_empty() {
+            let mut field = (!adaptation.is_empty()).then(|| std::mem::take(&mut adaptation));
+            let room = TS_PACKET - TS_HEADER - field.as_ref().map_or(0, |f| f.len() + 1);
+            let size = payload.len().min(room);
+            // Fill the rest of the last packet with adaptation field stuffing
+            let mut stuffing = room - size;
+            if stuffing > 0 {
+                let field = field.get_or_insert_with(|| {
+                    stuffing -= 1;
+                    Vec::new()
+                });
+                if field.is_empty() && stuffing > 0 {
+                    field.push(0x00);
+                    stuffing -= 1;
+                }
+                field.resize(field.len() + stuffing, 0xff);
+            }
+
+            let counter = &mut self.counters[stream];
+            let mut packet = Vec::with_capacity(TS_PACKET);
+            packet.push(0x47);
+            packet.push(if start { 0x40 } else { 0x00 } | (pid >> 8) as u8);
+            packet.push(pid as u8);
+            packet.push(if field.is_some() { 0x30 } else { 0x10 } | *counter);
+            *counter = (*counter + 1) & 0x0f;
+            if let Some(field) = field {
+                packet.push(field.len() as u8);
+                packet.extend_from_slice(&field);
+            }
+            packet.extend_from_slice(&payload[..size]);
+            segment.writer.write_all(&packet)?;
+
+            payload = &payload[size..];
+            start = false;
+        }
+        Ok(())
+    }
+
+    fn write_playlist(&self) -> Result<()> {
+        let fps = self.config.fps as f64;
+        let longest = self.segments.iter().map(|&(_, frames)| frames).max();
+        let target = longest.map_or(0, |frames| (frames as f64 / fps).ceil() as u64);
+
+        let mut playlist = format!(
+            "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:{}\n\
+             #EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-PLAYLIST-TYPE:VOD\n",
+            target
+        );
+        for (name, frames) in &self.segments {
+            playlist.push_str(&format!("#EXTINF:{:.3},\n{}\n", *frames as f64 / fps, name));
+        }
+        playlist.push_str("#EXT-X-ENDLIST\n");
+
+        std::fs::write(&self.playlist_path, playlist).map_err(Error::Io)
+    }
+}
+
+impl Muxer for HlsMuxer {
+    fn write_packet(&mut self, packet: &Packet) -> Result<()> {
+        let Some(unit) = self.units.push(packet) else {
+            return Ok(());
+        };
+
+        let min_frames = SEGMENT_DURATION_MS * self.config.fps as u64 / 1000;
+        let segment_done = match self.segment {
+            Some(ref segment) => self.frames - segment.first_frame >= min_frames,
+            None => true,
+        };
+        if segment_done && (unit.is_keyframe || self.segment.is_none()) {
+            self.start_segment()?;
+        }
+
+        let clock = self.frames * CLOCK / self.config.fps as u64;
+        let mut pes = vec![0x00, 0x00, 0x01, 0xe0, 0x00, 0x00, 0x80, 0x80, 0x05];
+        pes.extend_from_slice(&timestamp(clock + DTS_DELAY));
+        pes.extend_from_slice(&self.units.annex_b(&unit));
+
+        // Random access indicator at keyframes, and the program clock
+        let mut adaptation = vec![if unit.is_keyframe { 0x50 } else { 0x10 }];
+        adaptation.extend_from_slice(&pcr(clock));
+        self.write_payload(2, VIDEO_PID, adaptation, &pes)?;
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    fn finalize(mut self: Box<Self>) -> Result<()> {
+        self.finish_segment()?;
+        self.write_playlist()
+    }
+}
+
+/// Total size of an HLS playlist and of the segments it lists
+pub(crate) fn output_len(playlist_path: &Path) -> Result<u64> {
+    let playlist = std::fs::read_to_string(playlist_path)?;
+    let mut len = playlist.len() as u64;
+    for name in playlist.lines().filter(|line| !line.starts_with('#')) {
+        len += std::fs::metadata(playlist_path.with_file_name(name))?.len();
+    }
+    Ok(len)
+}
+
+/// PSI section of a single-program table, with the pointer field before it
+fn psi_section(table_id: u8, data: &[u8]) -> Vec<u8> {
+    // Table ID extension, version and section numbers, then the data and the CRC
+    let length = 5 + data.len() + 4;
+    let mut section = vec![
+        0x00, // Pointer field
+        table_id,
+        0xb0 | (length >> 8) as u8,
+        length as u8,
+        0x00,
+        0x01,
+        0xc1,
+        0x00,
+        0x00,
+    ];
+    section.extend_from_slice(data);
+    let crc = crc32_mpeg2(&section[1..]);
+    section.extend_from_slice(&crc.to_be_bytes());
+    section
+}
+
+/// CRC-32 of PSI sections (MPEG-2 variant)
+fn crc32_mpeg2(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in data {
+        crc ^= (byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// PES presentation timestamp, also used as decoding timestamp
+fn timestamp(ts: u64) -> [u8; 5] {
+    [
+        0x21 | ((ts >> 29) & 0x0e) as u8,
+        (ts >> 22) as u8,
+        0x01 | ((ts >> 14) & 0xfe) as u8,
+        (ts >> 7) as u8,
+        0x01 | ((ts << 1) & 0xfe) as u8,
+    ]
+}
+
+/// Program clock reference with no 27 MHz extension
+fn pcr(clock: u64) -> [u8; 6] {
+    [
+        (clock >> 25) as u8,
+        (clock >> 17) as u8,
+        (clock >> 9) as u8,
+        (clock >> 1) as u8,
+        ((clock & 1) << 7) as u8 | 0x7e,
+        0x00,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ColorSpace, PixelRange};
+
+    fn nal_packet(data: &[u8], is_keyframe: bool) -> Packet {
+        Packet {
+            data: data.to_vec(),
+            pts: 0,
+            dts: 0,
+            is_keyframe,
+        }
+    }
+
+    #[test]
+    fn test_crc32_mpeg2() {
+        assert_eq!(crc32_mpeg2(b"123456789"), 0x0376_e6e7);
+    }
+
+    #[test]
+    fn test_hls_segments_start_at_keyframes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let playlist_path = temp_dir.path().join("video.m3u8");
+        let config = MuxerConfig {
+            width: 64,
+            height: 48,
+            fps: 1,
+            codec: Codec::H264,
+            codec_config: Some(vec![0x67, 0x42, 0x00, 0x1e]),
+            pps: Some(vec![0x68, 0xce]),
+            color_space: ColorSpace::Bt601,
+            pixel_range: PixelRange::Auto,
+        };
+        let mut muxer: Box<dyn Muxer> = Box::new(HlsMuxer::new(&playlist_path, config).unwrap());
+
+        // A keyframe every 4 s splits 10 s into segments of 8 s and 2 s
+        for frame in 0..10 {
+            let packet = match frame % 4 {
+                0 => nal_packet(&[0x65, 0x88], true),
+                _ => nal_packet(&[0x41, 0x9a], false),
+            };
+            muxer.write_packet(&packet).unwrap();
+        }
+        muxer.finalize().unwrap();
+
+        let playlist = std::fs::read_to_string(&playlist_path).unwrap();
+        assert!(playlist.contains("#EXT-X-TARGETDURATION:8\n"));
+        assert!(playlist.contains("#EXTINF:8.000,\nvideo0.ts\n#EXTINF:2.000,\nvideo1.ts\n"));
+        assert!(playlist.ends_with("#EXT-X-ENDLIST\n"));
+
+        let segment = std::fs::read(temp_dir.path().join("video0.ts")).unwrap();
+        assert_eq!(segment.len() % TS_PACKET, 0);
+        assert!(segment.chunks(TS_PACKET).all(|packet| packet[0] == 0x47));
+        // PAT, PMT, then the keyframe with its parameter sets
+        let pids: Vec<u16> = segment
+            .chunks(TS_PACKET)
+            .map(|packet| u16::from_be_bytes([packet[1] & 0x1f, packet[2]]))
+            .collect();
+        assert_eq!(pids[..3], [0x0000, PMT_PID, VIDEO_PID]);
+        let keyframe = [0, 0, 0, 1, 0x09, 0xf0, 0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1e];
+        assert!(segment.windows(keyframe.len()).any(|w| w == keyframe));
+
+        let total = output_len(&playlist_path).unwrap();
+        assert_eq!(
+            total,
+            playlist.len() as u64
+                + segment.len() as u64
+                + std::fs::metadata(temp_dir.path().join("video1.ts"))
+                    .unwrap()
+                    .len()
+        );
+    }
+}
@@ -0,0 +1,131 @@
+//! IVF muxer, the minimal frame container read by dav1d, aomdec and vpxdec
+
+use super::{Muxer, MuxerConfig, MuxerOutput};
+use crate::encoder::Packet;
+use crate::{Codec, Error, Result};
+use std::io::{SeekFrom, Write};
+
+/// Size of the IVF file header
+const HEADER_SIZE: u16 = 32;
+
+/// Offset of the frame count in the file header
+const FRAME_COUNT_OFFSET: u64 = 24;
+
+/// IVF muxer (AV1 and VP9), one frame per packet
+///
+/// Timestamps count frames, with a time base of one frame.
+pub struct IvfMuxer {
+    writer: Box<dyn MuxerOutput>,
+    frame_count: u32,
+}
+
+impl IvfMuxer {
+    /// Create a muxer writing to an arbitrary output
+    pub fn with_output(mut writer: Box<dyn MuxerOutput>, config: MuxerConfig) -> Result<Self> {
+        Self::check_codec(config.codec)?;
+
+        let fourcc: &[u8; 4] = match config.codec {
+            Codec::Vp9 => b"VP90",
+            _ => b"AV01",
+        };
+        let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+        header.extend_from_slice(b"DKIF");
+        header.extend_from_slice(&0u16.to_le_bytes()); // Version
+        header.extend_from_slice(&HEADER_SIZE.to_le_bytes());
+        header.extend_from_slice(fourcc);
+        header.extend_from_slice(&(config.width as u16).to_le_bytes());
+        header.extend_from_slice(&(config.height as u16).to_le_bytes());
+        // Time base: frame rate over one
+        header.extend_from_slice(&config.fps.to_le_bytes());
+        header.extend_from_slice(&1u32.to_le_bytes());
+        // Frame count, filled in by finalize
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        writer.write_all(&header)?;
+
+        Ok(Self {
+            writer,
+            frame_count: 0,
+        })
+    }
+
+    /// Check if codec is supported by IVF
+    pub fn check_codec(codec: Codec) -> Result<()> {
+        match codec {
+            Codec::Av1 | Codec::Vp9 => Ok(()),
+            Codec::H264 => Err(Error::Mux(
+                "IVF container only supports AV1 and VP9 codecs".to_string(),
+            )),
+        }
+    }
+}
+
+impl Muxer for IvfMuxer {
+    fn write_packet(&mut self, packet: &Packet) -> Result<()> {
+        let size = u32::try_from(packet.data.len())
+            .map_err(|_| Error::Mux("IVF frame is larger than 4 GiB".to_string()))?;
+        self.writer.write_all(&size.to_le_bytes())?;
+        self.writer
+            .write_all(&(self.frame_count as u64).to_le_bytes())?;
+        self.writer.write_all(&packet.data)?;
+        self.frame_count += 1;
+        Ok(())
+    }
+
+    fn finalize(mut self: Box<Self>) -> Result<()> {
+        let end = self.writer.stream_position()?;
+        self.writer.seek(SeekFrom::Start(FRAME_COUNT_OFFSET))?;
+        self.writer.write_all(&self.frame_count.to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(end))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::muxer::MemoryOutput;
+    use crate::{ColorSpace, PixelRange};
+
+    #[test]
+    fn test_ivf_frames() {
+        let output = MemoryOutput::new();
+        let config = MuxerConfig {
+            width: 320,
+            height: 240,
+            fps: 30,
+            codec: Codec::Av1,
+            codec_config: None,
+            pps: None,
+            color_space: ColorSpace::Bt601,
+            pixel_range: PixelRange::Auto,
+        };
+        let mut muxer: Box<dyn Muxer> =
+            Box::new(IvfMuxer::with_output(Box::new(output.clone()), config).unwrap());
+        for data in [&[0x12, 0x00, 0x0a][..], &[0x12, 0x00]] {
+            muxer
+                .write_packet(&Packet {
+                    data: data.to_vec(),
+                    pts: 0,
+                    dts: 0,
+                    is_keyframe: false,
+                })
+                .unwrap();
+        }
+        muxer.finalize().unwrap();
+
+        let data = output.take();
+        assert_eq!(&data[..4], b"DKIF");
+        assert_eq!(&data[8..12], b"AV01");
+        assert_eq!(data[12..16], [0x40, 0x01, 0xf0, 0x00]);
+        assert_eq!(data[16..24], [30, 0, 0, 0, 1, 0, 0, 0]);
+        assert_eq!(data[24..28], [2, 0, 0, 0]);
+        // Frame size, timestamp and data
+        assert_eq!(data[32..36], [3, 0, 0, 0]);
+        assert_eq!(data[36..44], [0; 8]);
+        assert_eq!(data[44..47], [0x12, 0x00, 0x0a]);
+        assert_eq!(data[51..59], [1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(data.len(), 32 + 12 + 3 + 12 + 2);
+    }
+}
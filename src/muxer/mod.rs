@@ -1,11 +1,22 @@
 //! Video container muxers
 
+pub mod annexb;
+mod avc;
+pub mod hls;
+pub mod ivf;
 pub mod mp4;
+pub mod mp4_av1;
+mod obu;
 pub mod webm;
+pub mod y4m;
 
 use crate::encoder::Packet;
-use crate::{Codec, Container, Result};
+use crate::logging::trace_span;
+use crate::{Codec, ColorSpace, Container, EncodeOptions, Error, PixelRange, Result};
+use std::fs::File;
+use std::io::{self, BufWriter, Cursor, Seek, SeekFrom, Write};
 use std::path::Path;
+use std::sync::{Arc, Mutex};
 
 /// Video muxer trait
 pub trait Muxer: Send {
@@ -31,6 +42,71 @@ pub struct MuxerConfig {
     pub codec_config: Option<Vec<u8>>,
     /// Picture Parameter Set (PPS for H.264)
     pub pps: Option<Vec<u8>>,
+    /// Color space the frames were encoded in
+    pub color_space: ColorSpace,
+    /// Range of the YUV values of the frames
+    pub pixel_range: PixelRange,
+}
+
+/// Output sink a muxer writes to
+pub trait MuxerOutput: Write + Seek + Send {}
+
+impl<T: Write + Seek + Send> MuxerOutput for T {}
+
+/// In-memory muxer output
+///
+/// Clones share the same buffer, so the bytes can be taken after the muxer is finalized.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryOutput(Arc<Mutex<Cursor<Vec<u8>>>>);
+
+impl MemoryOutput {
+    /// Create an empty buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of bytes written
+    pub(crate) fn len(&self) -> usize {
+        match self.0.lock() {
+            Ok(cursor) => cursor.get_ref().len(),
+            Err(poisoned) => poisoned.into_inner().get_ref().len(),
+        }
+    }
+
+    /// Take the written bytes, leaving the buffer empty
+    pub fn take(&self) -> Vec<u8> {
+        match self.0.lock() {
+            Ok(mut cursor) => std::mem::take(cursor.get_mut()),
+            Err(poisoned) => std::mem::take(poisoned.into_inner().get_mut()),
+        }
+    }
+
+    fn with_cursor<T>(
+        &self,
+        f: impl FnOnce(&mut Cursor<Vec<u8>>) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let mut cursor = self
+            .0
+            .lock()
+            .map_err(|_| io::Error::other("Memory output lock poisoned"))?;
+        f(&mut cursor)
+    }
+}
+
+impl Write for MemoryOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.with_cursor(|cursor| cursor.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for MemoryOutput {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.with_cursor(|cursor| cursor.seek(pos))
+    }
 }
 
 /// Create a muxer for the specified container format
@@ -39,8 +115,110 @@ pub fn create_muxer<P: AsRef<Path>>(
     output_path: P,
     config: MuxerConfig,
 ) -> Result<Box<dyn Muxer>> {
+    // Check the codec before creating the file so a mismatch leaves nothing behind
+    check_codec(container, config.codec)?;
+
+    // HLS writes its playlist and segment files itself
+    if container == Container::Hls {
+        return Ok(Box::new(hls::HlsMuxer::new(output_path, config)?));
+    }
+
+    let file = File::create(output_path.as_ref()).map_err(Error::Io)?;
+    create_muxer_with_output(container, Box::new(BufWriter::new(file)), config)
+}
+
+/// Create a muxer writing to an arbitrary output
+pub fn create_muxer_with_output(
+    container: Container,
+    output: Box<dyn MuxerOutput>,
+    config: MuxerConfig,
+) -> Result<Box<dyn Muxer>> {
+    let _span = trace_span!("create_muxer", ?container, codec = ?config.codec);
+
+    match container {
+        Container::Mp4 if config.codec == Codec::Av1 => {
+            Ok(Box::new(mp4_av1::Av1Mp4Muxer::with_output(output, config)?))
+        }
+        Container::Mp4 => Ok(Box::new(mp4::Mp4Muxer::with_output(output, config)?)),
+        Container::WebM => Ok(Box::new(webm::WebmMuxer::with_output(output, config)?)),
+        Container::Mkv => Ok(Box::new(webm::WebmMuxer::matroska_with_output(
+            output, config,
+        )?)),
+        Container::Ivf => Ok(Box::new(ivf::IvfMuxer::with_output(output, config)?)),
+        Container::AnnexB => Ok(Box::new(annexb::AnnexBMuxer::with_output(output, config)?)),
+        Container::Y4m => Ok(Box::new(y4m::Y4mMuxer::with_output(output, config)?)),
+        Container::Hls => Err(memory_error()),
+        Container::WebP | Container::Apng => Err(animation_error(container)),
+    }
+}
+
+/// Check that `container` can be written to `memory`, if set
+pub(crate) fn check_output(container: Container, memory: Option<&MemoryOutput>) -> Result<()> {
+    match (container, memory) {
+        (Container::Hls, Some(_)) => Err(memory_error()),
+        _ => Ok(()),
+    }
+}
+
+/// Mux packets to `memory` if set or to `options.output_path` otherwise
+///
+/// Returns the size of the output in bytes.
+pub(crate) fn write_packets(
+    options: &EncodeOptions,
+    memory: Option<&MemoryOutput>,
+    config: MuxerConfig,
+    packets: Vec<Packet>,
+) -> Result<u64> {
+    let _span = trace_span!("mux", packets = packets.len());
+
+    let mut muxer = match memory {
+        Some(output) => {
+            create_muxer_with_output(options.container, Box::new(output.clone()), config)?
+        }
+        None => create_muxer(options.container, &options.output_path, config)?,
+    };
+
+    for packet in packets {
+        muxer.write_packet(&packet)?;
+    }
+    muxer.finalize()?;
+
+    match memory {
+        Some(output) => Ok(output.len() as u64),
+        None if options.container == Container::Hls => {
+            hls::output_len(Path::new(&options.output_path))
+        }
+        None => Ok(std::fs::metadata(&options.output_path)?.len()),
+    }
+}
+
+/// Check that the muxer for a container can write the codec
+pub(crate) fn check_codec(container: Container, codec: Codec) -> Result<()> {
     match container {
-        Container::Mp4 => Ok(Box::new(mp4::Mp4Muxer::new(output_path, config)?)),
-        Container::WebM => Ok(Box::new(webm::WebmMuxer::new(output_path, config)?)),
+        Container::Mp4 => mp4::Mp4Muxer::check_codec(codec),
+        Container::WebM => webm::WebmMuxer::check_codec(codec),
+        // Matroska holds every codec
+        Container::Mkv => Ok(()),
+        Container::Hls => hls::HlsMuxer::check_codec(codec),
+        Container::Ivf => ivf::IvfMuxer::check_codec(codec),
+        Container::AnnexB => annexb::AnnexBMuxer::check_codec(codec),
+        // Frames are written uncompressed, whatever the codec
+        Container::Y4m => Ok(()),
+        Container::WebP | Container::Apng => Err(animation_error(container)),
     }
 }
+
+/// Error for HLS output requested in memory, as it is made of several files
+fn memory_error() -> Error {
+    Error::InvalidInput(
+        "HLS output is a playlist with segment files and cannot be written to memory".to_string(),
+    )
+}
+
+/// Error for animated image containers, which hold slides rather than encoded video
+fn animation_error(container: Container) -> Error {
+    Error::Mux(format!(
+        "{:?} output is only supported for slideshows",
+        container
+    ))
+}
@@ -1,6 +1,7 @@
 //! MP4 container muxer
 
-use super::{Muxer, MuxerConfig};
+use super::avc::AccessUnits;
+use super::{Muxer, MuxerConfig, MuxerOutput};
 use crate::encoder::Packet;
 use crate::{Codec, Error, Result};
 use mp4::{Mp4Config, Mp4Writer, TrackConfig};
@@ -8,28 +9,46 @@ use std::fs::File;
 use std::io::BufWriter;
 use std::path::Path;
 
-/// MP4 muxer (H.264 only)
+/// MP4 muxer for H.264
+///
+/// Packets are grouped into access units, one sample per frame. The track timescale is
+/// the frame rate, the time base of packet timestamps. A sample lasts until the decoding
+/// timestamp of the next, so each is held back until the next frame arrives.
 pub struct Mp4Muxer {
-    writer: Mp4Writer<BufWriter<File>>,
-    #[allow(dead_code)]
+    writer: Mp4Writer<Box<dyn MuxerOutput>>,
     config: MuxerConfig,
     track_id: u32,
-    sample_count: u32,
+    /// Frame being assembled, and the parameter sets seen in the stream
+    units: AccessUnits,
+    /// Whether the track has been added, which waits for the parameter sets
+    track_added: bool,
+    /// Decoding timestamp of the first packet, the start of the track
+    first_dts: Option<i64>,
+    /// Sample waiting for the next packet to set its duration
+    pending: Option<mp4::Mp4Sample>,
+    /// Duration of the last sample written, also given to the final one
+    last_duration: u32,
 }
 
 impl Mp4Muxer {
     pub fn new<P: AsRef<Path>>(output_path: P, config: MuxerConfig) -> Result<Self> {
-        // MP4 with mp4 crate only supports H.264
-        // For AV1 in MP4, we would need a different approach
-        if config.codec == Codec::Av1 {
-            return Err(Error::Mux(
-                "MP4 container with AV1 codec requires ffmpeg. Use WebM for AV1 instead."
-                    .to_string(),
-            ));
-        }
+        Self::check_codec(config.codec)?;
 
         let file = File::create(output_path.as_ref()).map_err(Error::Io)?;
-        let writer = BufWriter::new(file);
+        Self::with_output(Box::new(BufWriter::new(file)), config)
+    }
+
+    /// Create a muxer writing to an arbitrary output
+    ///
+    /// AV1 is written by [`Av1Mp4Muxer`](super::mp4_av1::Av1Mp4Muxer) instead.
+    pub fn with_output(writer: Box<dyn MuxerOutput>, config: MuxerConfig) -> Result<Self> {
+        if config.codec != Codec::H264 {
+            Self::check_codec(config.codec)?;
+            return Err(Error::Mux(format!(
+                "MP4 muxer writes H.264, not {:?}",
+                config.codec
+            )));
+        }
 
         let mp4_config = Mp4Config {
             major_brand: str_to_brand("isom"),
@@ -43,57 +62,112 @@ impl Mp4Muxer {
             timescale: 1000, // milliseconds
         };
 
-        let mut mp4_writer = Mp4Writer::write_start(writer, &mp4_config)
+        let mp4_writer = Mp4Writer::write_start(writer, &mp4_config)
             .map_err(|e| Error::Mux(format!("Failed to create MP4 writer: {}", e)))?;
 
-        // Add video track for H.264
+        // Track ID is always 1 for single track
+        let track_id = 1;
+
+        let units = AccessUnits::new(config.codec_config.as_deref(), config.pps.as_deref());
+        Ok(Self {
+            writer: mp4_writer,
+            config,
+            track_id,
+            units,
+            track_added: false,
+            first_dts: None,
+            pending: None,
+            last_duration: 1,
+        })
+    }
+
+    /// Check that the codec can be written to MP4
+    pub(crate) fn check_codec(codec: Codec) -> Result<()> {
+        // The mp4 crate writes H.264; AV1 has its own muxer
+        match codec {
+            Codec::H264 | Codec::Av1 => Ok(()),
+            Codec::Vp9 => Err(Error::Mux(
+                "MP4 container with VP9 codec is not supported. Use WebM for VP9 instead."
+                    .to_string(),
+            )),
+        }
+    }
+
+    /// Add the video track, with the parameter sets from the encoder or else the stream
+    ///
+    /// Encoders that only put the parameter sets in the stream have them known once the
+    /// first frame is complete.
+    fn add_track(&mut self) -> Result<()> {
+        let (sps, pps) = self.units.parameter_sets().unwrap_or_default();
         let track_config = TrackConfig {
             track_type: mp4::TrackType::Video,
-            timescale: config.fps,
+            timescale: self.config.fps,
             language: String::from("und"),
             media_conf: mp4::MediaConfig::AvcConfig(mp4::AvcConfig {
-                width: config.width as u16,
-                height: config.height as u16,
-                seq_param_set: config.codec_config.clone().unwrap_or_default(),
-                pic_param_set: config.pps.clone().unwrap_or_default(),
+                width: self.config.width as u16,
+                height: self.config.height as u16,
+                seq_param_set: sps.to_vec(),
+                pic_param_set: pps.to_vec(),
             }),
         };
 
-        mp4_writer
+        self.writer
             .add_track(&track_config)
             .map_err(|e| Error::Mux(format!("Failed to add track: {}", e)))?;
+        self.track_added = true;
+        Ok(())
+    }
 
-        // Track ID is always 1 for single track
-        let track_id = 1;
-
-        Ok(Self {
-            writer: mp4_writer,
-            config,
-            track_id,
-            sample_count: 0,
-        })
+    /// Write a sample whose duration is known
+    fn write_sample(&mut self, sample: mp4::Mp4Sample) -> Result<()> {
+        self.writer
+            .write_sample(self.track_id, &sample)
+            .map_err(|e| Error::Mux(format!("Failed to write sample: {}", e)))?;
+        self.last_duration = sample.duration;
+        Ok(())
     }
 }
 
 impl Muxer for Mp4Muxer {
     fn write_packet(&mut self, packet: &Packet) -> Result<()> {
-        let sample = mp4::Mp4Sample {
-            start_time: self.sample_count as u64,
-            duration: 1,
-            rendering_offset: 0,
-            is_sync: packet.is_keyframe,
-            bytes: mp4::Bytes::copy_from_slice(&packet.data),
+        // NAL units before the slice of their frame are held back
+        let Some(unit) = self.units.push(packet) else {
+            return Ok(());
         };
+        if !self.track_added {
+            self.add_track()?;
+        }
 
-        self.writer
-            .write_sample(self.track_id, &sample)
-            .map_err(|e| Error::Mux(format!("Failed to write sample: {}", e)))?;
+        let first_dts = *self.first_dts.get_or_insert(unit.dts);
+        let sample = mp4::Mp4Sample {
+            start_time: (unit.dts - first_dts).max(0) as u64,
+            duration: 0,
+            rendering_offset: (unit.pts - unit.dts) as i32,
+            is_sync: unit.is_keyframe,
+            bytes: mp4::Bytes::from(unit.data),
+        };
 
-        self.sample_count += 1;
+        let start_time = sample.start_time;
+        if let Some(mut previous) = self.pending.replace(sample) {
+            // Timestamps that do not increase keep the previous one frame per sample
+            previous.duration = match start_time.checked_sub(previous.start_time) {
+                Some(duration @ 1..) => duration.min(u32::MAX as u64) as u32,
+                _ => 1,
+            };
+            self.write_sample(previous)?;
+        }
         Ok(())
     }
 
     fn finalize(mut self: Box<Self>) -> Result<()> {
+        if !self.track_added {
+            self.add_track()?;
+        }
+        if let Some(mut last) = self.pending.take() {
+            last.duration = self.last_duration;
+            self.write_sample(last)?;
+        }
+
         self.writer
             .write_end()
             .map_err(|e| Error::Mux(format!("Failed to finalize MP4: {}", e)))?;
@@ -113,3 +187,138 @@ fn str_to_brand(s: &str) -> mp4::FourCC {
         ],
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::muxer::MemoryOutput;
+    use crate::{ColorSpace, PixelRange};
+    use std::io::Cursor;
+
+    fn config(sps: Option<Vec<u8>>, pps: Option<Vec<u8>>) -> MuxerConfig {
+        MuxerConfig {
+            width: 64,
+            height: 48,
+            fps: 30,
+            codec: Codec::H264,
+            codec_config: sps,
+            pps,
+            color_space: ColorSpace::Bt601,
+            pixel_range: PixelRange::Auto,
+        }
+    }
+
+    #[test]
+    fn test_sample_times_from_timestamps() {
+        let config = config(
+            Some(vec![0x67, 0x42, 0xc0, 0x1e]),
+            Some(vec![0x68, 0xce, 0x3c, 0x80]),
+        );
+        let output = MemoryOutput::new();
+        let mut muxer: Box<dyn Muxer> =
+            Box::new(Mp4Muxer::with_output(Box::new(output.clone()), config).unwrap());
+        // A frame shown twice as long, then one shown after the frame decoded next
+        for (pts, dts) in [(0, 0), (2, 2), (4, 3), (3, 4)] {
+            muxer
+                .write_packet(&Packet {
+                    data: vec![if pts == 0 { 0x65 } else { 0x41 }, pts as u8],
+                    pts,
+                    dts,
+                    is_keyframe: pts == 0,
+                })
+                .unwrap();
+        }
+        muxer.finalize().unwrap();
+
+        let data = output.take();
+        let size = data.len() as u64;
+        let mut reader = mp4::Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+        let samples: Vec<(u64, u32, i32)> = (1..=4)
+            .map(|id| {
+                let sample = reader.read_sample(1, id).unwrap().unwrap();
+                (sample.start_time, sample.duration, sample.rendering_offset)
+            })
+            .collect();
+        assert_eq!(samples, [(0, 2, 0), (2, 1, 0), (3, 1, 1), (4, 1, -1)]);
+    }
+
+    #[test]
+    fn test_one_sample_per_frame_from_nal_units() {
+        // Parameter sets only in the stream, as from ffmpeg
+        let output = MemoryOutput::new();
+        let mut muxer: Box<dyn Muxer> =
+            Box::new(Mp4Muxer::with_output(Box::new(output.clone()), config(None, None)).unwrap());
+        // Bare NAL units of the first frame from one encode call, then Annex B data and a
+        // bare slice from the next two
+        let calls: [&[(&[u8], i64)]; 3] = [
+            &[
+                (&[0x67, 0x42, 0xc0, 0x1e], 0),
+                (&[0x68, 0xce, 0x3c, 0x80], 0),
+                (&[0x06, 0x05], 0),
+                (&[0x65, 0x88], 0),
+            ],
+            &[(&[0, 0, 0, 1, 0x41, 0x9a, 0, 0, 1, 0x41, 0x20], 1)],
+            &[(&[0x41, 0x9b], 2)],
+        ];
+        for packets in calls {
+            for &(data, pts) in packets {
+                muxer
+                    .write_packet(&Packet {
+                        data: data.to_vec(),
+                        pts,
+                        dts: pts,
+                        is_keyframe: data[0] == 0x65,
+                    })
+                    .unwrap();
+            }
+        }
+        muxer.finalize().unwrap();
+
+        let data = output.take();
+        let size = data.len() as u64;
+        let mut reader = mp4::Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+        let track = &reader.tracks()[&1];
+        assert_eq!(track.sample_count(), 3);
+        assert_eq!(
+            track.sequence_parameter_set().unwrap(),
+            [0x67, 0x42, 0xc0, 0x1e]
+        );
+        assert_eq!(
+            track.picture_parameter_set().unwrap(),
+            [0x68, 0xce, 0x3c, 0x80]
+        );
+
+        let samples: Vec<(u64, u32, bool, Vec<u8>)> = (1..=3)
+            .map(|id| {
+                let sample = reader.read_sample(1, id).unwrap().unwrap();
+                let bytes = sample.bytes.to_vec();
+                (sample.start_time, sample.duration, sample.is_sync, bytes)
+            })
+            .collect();
+        assert_eq!(
+            samples[0],
+            (
+                0,
+                1,
+                true,
+                [
+                    &[0, 0, 0, 4, 0x67, 0x42, 0xc0, 0x1e][..],
+                    &[0, 0, 0, 4, 0x68, 0xce, 0x3c, 0x80],
+                    &[0, 0, 0, 2, 0x06, 0x05],
+                    &[0, 0, 0, 2, 0x65, 0x88],
+                ]
+                .concat()
+            )
+        );
+        assert_eq!(
+            samples[1],
+            (
+                1,
+                1,
+                false,
+                vec![0, 0, 0, 2, 0x41, 0x9a, 0, 0, 0, 2, 0x41, 0x20]
+            )
+        );
+        assert_eq!(samples[2], (2, 1, false, vec![0, 0, 0, 2, 0x41, 0x9b]));
+    }
+}
@@ -0,0 +1,256 @@
+//! MP4 muxer for AV1, with an `av01` sample entry
+//!
+//! The mp4 crate has no AV1 sample entry, so the boxes are written here: `ftyp`, then the
+//! samples in one `mdat`, then `moov` describing them as a single chunk.
+
+use super::obu::{codec_config, obus};
+use super::{Muxer, MuxerConfig, MuxerOutput};
+use crate::encoder::Packet;
+use crate::{Codec, Error, Result};
+use std::io::{SeekFrom, Write};
+
+/// Timescale of the movie header, in units per second
+const MOVIE_TIMESCALE: u32 = 1000;
+
+/// Size of the `mdat` header, with a 64-bit size
+const MDAT_HEADER: u64 = 16;
+
+/// MP4 muxer (AV1 only)
+pub struct Av1Mp4Muxer {
+    writer: Box<dyn MuxerOutput>,
+    config: MuxerConfig,
+    /// Offset of the `mdat` box
+    mdat_start: u64,
+    /// Sizes of the samples written so far
+    sample_sizes: Vec<u32>,
+    /// 1-based numbers of the keyframe samples
+    sync_samples: Vec<u32>,
+    /// AV1CodecConfigurationRecord, from the first sequence header
+    av1_config: Option<Vec<u8>>,
+}
+
+impl Av1Mp4Muxer {
+    /// Create a muxer writing to an arbitrary output
+    pub fn with_output(mut writer: Box<dyn MuxerOutput>, config: MuxerConfig) -> Result<Self> {
+        if config.codec != Codec::Av1 {
+            return Err(Error::Internal(format!(
+                "AV1 MP4 muxer given {:?}",
+                config.codec
+            )));
+        }
+
+        let mut ftyp = Vec::new();
+        ftyp.extend_from_slice(b"isom");
+        ftyp.extend_from_slice(&512u32.to_be_bytes());
+        for brand in [b"isom", b"iso6", b"av01", b"mp41"] {
+            ftyp.extend_from_slice(brand);
+        }
+        writer.write_all(&mp4_box(b"ftyp", &ftyp))?;
+
+        let mdat_start = writer.stream_position()?;
+        // Size 1: the real size follows as 64 bits, filled in by finalize
+        writer.write_all(&1u32.to_be_bytes())?;
+        writer.write_all(b"mdat")?;
+        writer.write_all(&0u64.to_be_bytes())?;
+
+        Ok(Self {
+            writer,
+            config,
+            mdat_start,
+            sample_sizes: Vec::new(),
+            sync_samples: Vec::new(),
+            av1_config: None,
+        })
+    }
+
+    fn moov(&self, av1_config: &[u8]) -> Vec<u8> {
+        let samples = self.sample_sizes.len() as u32;
+        let fps = self.config.fps.max(1);
+        let duration_ms = samples as u64 * MOVIE_TIMESCALE as u64 / fps as u64;
+
+        let mut mvhd = Vec::new();
+        mvhd.extend_from_slice(&[0; 8]); // Creation and modification times
+        mvhd.extend_from_slice(&MOVIE_TIMESCALE.to_be_bytes());
+        mvhd.extend_from_slice(&(duration_ms as u32).to_be_bytes());
+        mvhd.extend_from_slice(&0x0001_0000u32.to_be_bytes()); // Rate 1.0
+        mvhd.extend_from_slice(&0x0100u16.to_be_bytes()); // Volume 1.0
+        mvhd.extend_from_slice(&[0; 10]);
+        mvhd.extend_from_slice(&UNITY_MATRIX);
+        mvhd.extend_from_slice(&[0; 24]); // Pre-defined
+        mvhd.extend_from_slice(&2u32.to_be_bytes()); // Next track ID
+
+        let mut tkhd = Vec::new();
+        tkhd.extend_from_slice(&[0; 8]);
+        tkhd.extend_from_slice(&1u32.to_be_bytes()); // Track ID
+        tkhd.extend_from_slice(&[0; 4]);
+        tkhd.extend_from_slice(&(duration_ms as u32).to_be_bytes());
+        tkhd.extend_from_slice(&[0; 16]); // Layer, alternate group and volume
+        tkhd.extend_from_slice(&UNITY_MATRIX);
+        tkhd.extend_from_slice(&(self.config.width << 16).to_be_bytes());
+        tkhd.extend_from_slice(&(self.config.height << 16).to_be_bytes());
+
+        let mut mdhd = Vec::new();
+        mdhd.extend_from_slice(&[0; 8]);
+        mdhd.extend_from_slice(&fps.to_be_bytes());
+        mdhd.extend_from_slice(&samples.to_be_bytes());
+        mdhd.extend_from_slice(&0x55c4u16.to_be_bytes()); // Language "und"
+        mdhd.extend_from_slice(&[0; 2]);
+
+        let mut hdlr = Vec::new();
+        hdlr.extend_from_slice(&[0; 4]);
+        hdlr.extend_from_slice(b"vide");
+        hdlr.extend_from_slice(&[0; 12]);
+        hdlr.extend_from_slice(b"VideoHandler\0");
+
+        // Data is in this file
+        let url = full_box(b"url ", 0, 1, &[]);
+        let dref = full_box(b"dref", 0, 0, &[&1u32.to_be_bytes()[..], &url].concat());
+        let minf = mp4_box(
+            b"minf",
+            &[
+                full_box(b"vmhd", 0, 1, &[0; 8]),
+                mp4_box(b"dinf", &dref),
+                self.stbl(av1_config),
+            ]
+            .concat(),
+        );
+
+        let mdia = mp4_box(
+            b"mdia",
+            &[
+                full_box(b"mdhd", 0, 0, &mdhd),
+                full_box(b"hdlr", 0, 0, &hdlr),
+                minf,
+            ]
+            .concat(),
+        );
+        let trak = mp4_box(b"trak", &[full_box(b"tkhd", 0, 3, &tkhd), mdia].concat());
+        mp4_box(b"moov", &[full_box(b"mvhd", 0, 0, &mvhd), trak].concat())
+    }
+
+    /// Sample table: every sample lasts one frame, all in one chunk after the `mdat` header
+    fn stbl(&self, av1_config: &[u8]) -> Vec<u8> {
+        let samples = self.sample_sizes.len() as u32;
+
+        let mut av01 = Vec::new();
+        av01.extend_from_slice(&[0; 6]);
+        av01.extend_from_slice(&1u16.to_be_bytes()); // Data reference index
+        av01.extend_from_slice(&[0; 16]);
+        av01.extend_from_slice(&(self.config.width as u16).to_be_bytes());
+        av01.extend_from_slice(&(self.config.height as u16).to_be_bytes());
+        av01.extend_from_slice(&0x0048_0000u32.to_be_bytes()); // 72 dpi
+        av01.extend_from_slice(&0x0048_0000u32.to_be_bytes());
+        av01.extend_from_slice(&[0; 4]);
+        av01.extend_from_slice(&1u16.to_be_bytes()); // Frames per sample
+        av01.extend_from_slice(&[0; 32]); // Compressor name
+        av01.extend_from_slice(&0x0018u16.to_be_bytes()); // Depth
+        av01.extend_from_slice(&(-1i16).to_be_bytes());
+        av01.extend_from_slice(&mp4_box(b"av1C", av1_config));
+        // Colour information: nclx code points and the full range flag
+        let (primaries, transfer, matrix) = self.config.color_space.code_points();
+        let mut colr = b"nclx".to_vec();
+        for code_point in [primaries, transfer, matrix] {
+            colr.extend_from_slice(&(code_point as u16).to_be_bytes());
+        }
+        colr.push(if self.config.pixel_range.is_full(false) {
+            0x80
+        } else {
+            0
+        });
+        av01.extend_from_slice(&mp4_box(b"colr", &colr));
+        let stsd = [&1u32.to_be_bytes()[..], &mp4_box(b"av01", &av01)].concat();
+
+        let stts = [1u32, samples, 1].map(u32::to_be_bytes).concat();
+        let stss = [&[self.sync_samples.len() as u32][..], &self.sync_samples]
+            .concat()
+            .iter()
+            .flat_map(|n| n.to_be_bytes())
+            .collect::<Vec<u8>>();
+        let stsc = [1u32, 1, samples, 1].map(u32::to_be_bytes).concat();
+        let stsz = [&[0, samples][..], &self.sample_sizes]
+            .concat()
+            .iter()
+            .flat_map(|n| n.to_be_bytes())
+            .collect::<Vec<u8>>();
+        let co64 = [
+            &1u32.to_be_bytes()[..],
+            &(self.mdat_start + MDAT_HEADER).to_be_bytes(),
+        ]
+        .concat();
+
+        mp4_box(
+            b"stbl",
+            &[
+                full_box(b"stsd", 0, 0, &stsd),
+                full_box(b"stts", 0, 0, &stts),
+                full_box(b"stss", 0, 0, &stss),
+                full_box(b"stsc", 0, 0, &stsc),
+                full_box(b"stsz", 0, 0, &stsz),
+                full_box(b"co64", 0, 0, &co64),
+            ]
+            .concat(),
+        )
+    }
+}
+
+impl Muxer for Av1Mp4Muxer {
+    fn write_packet(&mut self, packet: &Packet) -> Result<()> {
+        let mut size = 0u32;
+        for obu in obus(&packet.data) {
+            // Temporal delimiters are implied by the samples
+            if obu.is_temporal_delimiter() {
+                continue;
+            }
+            if obu.is_sequence_header() && self.av1_config.is_none() {
+                self.av1_config = codec_config(&obu);
+            }
+            self.writer.write_all(obu.data)?;
+            size += obu.data.len() as u32;
+        }
+
+        self.sample_sizes.push(size);
+        if packet.is_keyframe {
+            self.sync_samples.push(self.sample_sizes.len() as u32);
+        }
+        Ok(())
+    }
+
+    fn finalize(mut self: Box<Self>) -> Result<()> {
+        let av1_config = self
+            .av1_config
+            .take()
+            .ok_or_else(|| Error::Mux("AV1 stream has no valid sequence header".to_string()))?;
+
+        let mdat_end = self.writer.stream_position()?;
+        self.writer.seek(SeekFrom::Start(self.mdat_start + 8))?;
+        self.writer
+            .write_all(&(mdat_end - self.mdat_start).to_be_bytes())?;
+        self.writer.seek(SeekFrom::Start(mdat_end))?;
+
+        let moov = self.moov(&av1_config);
+        self.writer.write_all(&moov)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Identity transformation of the movie and track headers
+const UNITY_MATRIX: [u8; 36] = [
+    0x00, 0x01, 0x00, 0x00, 0, 0, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0x00, 0x01, 0x00, 0x00, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 0, 0, 0, 0x40, 0x00, 0x00, 0x00,
+];
+
+fn mp4_box(name: &[u8; 4], content: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(8 + content.len());
+    data.extend_from_slice(&(8 + content.len() as u32).to_be_bytes());
+    data.extend_from_slice(name);
+    data.extend_from_slice(content);
+    data
+}
+
+/// Box with a version and flags before its content
+fn full_box(name: &[u8; 4], version: u8, flags: u32, content: &[u8]) -> Vec<u8> {
+    let header = (version as u32) << 24 | flags;
+    mp4_box(name, &[&header.to_be_bytes()[..], content].concat())
+}
@@ -0,0 +1,320 @@
+//! AV1 OBU parsing for containers that describe the stream in their headers
+
+/// OBU types
+const OBU_SEQUENCE_HEADER: u8 = 1;
+const OBU_TEMPORAL_DELIMITER: u8 = 2;
+
+/// An OBU of a temporal unit, with its header
+pub(crate) struct Obu<'a> {
+    pub obu_type: u8,
+    /// The whole OBU, header included
+    pub data: &'a [u8],
+    /// Size of the OBU header and size field
+    header_len: usize,
+}
+
+impl Obu<'_> {
+    pub fn is_sequence_header(&self) -> bool {
+        self.obu_type == OBU_SEQUENCE_HEADER
+    }
+
+    pub fn is_temporal_delimiter(&self) -> bool {
+        self.obu_type == OBU_TEMPORAL_DELIMITER
+    }
+
+    fn payload(&self) -> &[u8] {
+        &self.data[self.header_len..]
+    }
+}
+
+/// OBUs of a temporal unit, stopping at the first malformed one
+pub(crate) fn obus(mut data: &[u8]) -> impl Iterator<Item = Obu<'_>> {
+    std::iter::from_fn(move || {
+        let (&header, _) = data.split_first()?;
+        let has_extension = header & 0x04 != 0;
+        let has_size = header & 0x02 != 0;
+        let mut header_len = 1 + has_extension as usize;
+
+        let size = if has_size {
+            let (size, len) = read_leb128(data.get(header_len..)?)?;
+            header_len += len;
+            usize::try_from(size).ok()?
+        } else {
+            data.len().checked_sub(header_len)?
+        };
+        let end = header_len
+            .checked_add(size)
+            .filter(|&end| end <= data.len())?;
+
+        let obu = Obu {
+            obu_type: (header >> 3) & 0x0f,
+            data: &data[..end],
+            header_len,
+        };
+        data = &data[end..];
+        Some(obu)
+    })
+}
+
+/// Read an unsigned LEB128 value, returning it and its length
+fn read_leb128(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, &byte) in data.iter().take(8).enumerate() {
+        value |= ((byte & 0x7f) as u64) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// AV1CodecConfigurationRecord (the content of `av1C` and of Matroska CodecPrivate)
+/// for a sequence header OBU, which it carries as its only configuration OBU
+pub(crate) fn codec_config(sequence_header: &Obu) -> Option<Vec<u8>> {
+    let info = SequenceInfo::parse(sequence_header.payload())?;
+
+    let mut config = vec![
+        0x81, // Marker and version 1
+        info.profile << 5 | info.level,
+        (info.tier as u8) << 7
+            | (info.high_bitdepth as u8) << 6
+            | (info.twelve_bit as u8) << 5
+            | (info.monochrome as u8) << 4
+            | (info.subsampling_x as u8) << 3
+            | (info.subsampling_y as u8) << 2
+            | info.chroma_sample_position,
+        0x00, // No initial presentation delay
+    ];
+    config.extend_from_slice(sequence_header.data);
+    Some(config)
+}
+
+/// Fields of a sequence header that `av1C` repeats
+#[derive(Debug, Default, PartialEq)]
+struct SequenceInfo {
+    profile: u8,
+    /// Level and tier of the first operating point
+    level: u8,
+    tier: bool,
+    high_bitdepth: bool,
+    twelve_bit: bool,
+    monochrome: bool,
+    subsampling_x: bool,
+    subsampling_y: bool,
+    chroma_sample_position: u8,
+}
+
+impl SequenceInfo {
+    /// Parse a sequence header OBU payload (AV1 specification 5.5)
+    fn parse(payload: &[u8]) -> Option<Self> {
+        let mut r = BitReader::new(payload);
+        let mut info = Self {
+            profile: r.bits(3)? as u8,
+            ..Self::default()
+        };
+        r.skip(1)?; // still_picture
+        let reduced_still_picture_header = r.flag()?;
+
+        if reduced_still_picture_header {
+            info.level = r.bits(5)? as u8;
+        } else {
+            info.parse_operating_points(&mut r)?;
+        }
+
+        let width_bits = r.bits(4)? + 1;
+        let height_bits = r.bits(4)? + 1;
+        r.skip(width_bits + height_bits)?;
+        if !reduced_still_picture_header && r.flag()? {
+            // delta_frame_id_length_minus_2 and additional_frame_id_length_minus_1
+            r.skip(4 + 3)?;
+        }
+        // use_128x128_superblock, enable_filter_intra and enable_intra_edge_filter
+        r.skip(3)?;
+        if !reduced_still_picture_header {
+            // enable_interintra_compound, enable_masked_compound, enable_warped_motion and
+            // enable_dual_filter
+            r.skip(4)?;
+            let enable_order_hint = r.flag()?;
+            if enable_order_hint {
+                // enable_jnt_comp and enable_ref_frame_mvs
+                r.skip(2)?;
+            }
+            // seq_choose_screen_content_tools, or seq_force_screen_content_tools
+            let screen_content_tools = r.flag()? || r.flag()?;
+            // seq_choose_integer_mv, or seq_force_integer_mv
+            if screen_content_tools && !r.flag()? {
+                r.skip(1)?;
+            }
+            if enable_order_hint {
+                r.skip(3)?; // order_hint_bits_minus_1
+            }
+        }
+        // enable_superres, enable_cdef and enable_restoration
+        r.skip(3)?;
+
+        info.parse_color_config(&mut r)?;
+        Some(info)
+    }
+
+    /// Timing and decoder model information, then the operating points
+    fn parse_operating_points(&mut self, r: &mut BitReader) -> Option<()> {
+        let mut buffer_delay_length = 0;
+        let mut decoder_model_info_present = false;
+        if r.flag()? {
+            // num_units_in_display_tick and time_scale
+            r.skip(64)?;
+            if r.flag()? {
+                r.uvlc()?; // num_ticks_per_picture_minus_1
+            }
+            decoder_model_info_present = r.flag()?;
+            if decoder_model_info_present {
+                buffer_delay_length = r.bits(5)? + 1;
+                // num_units_in_decoding_tick, buffer_removal_time_length_minus_1 and
+                // frame_presentation_time_length_minus_1
+                r.skip(32 + 5 + 5)?;
+            }
+        }
+
+        let initial_display_delay_present = r.flag()?;
+        let operating_points = r.bits(5)? + 1;
+        for i in 0..operating_points {
+            r.skip(12)?; // operating_point_idc
+            let level = r.bits(5)? as u8;
+            let tier = level > 7 && r.flag()?;
+            if i == 0 {
+                self.level = level;
+                self.tier = tier;
+            }
+            if decoder_model_info_present && r.flag()? {
+                // decoder_buffer_delay, encoder_buffer_delay and low_delay_mode_flag
+                r.skip(2 * buffer_delay_length + 1)?;
+            }
+            if initial_display_delay_present && r.flag()? {
+                r.skip(4)?;
+            }
+        }
+        Some(())
+    }
+
+    fn parse_color_config(&mut self, r: &mut BitReader) -> Option<()> {
+        self.high_bitdepth = r.flag()?;
+        if self.profile == 2 && self.high_bitdepth {
+            self.twelve_bit = r.flag()?;
+        }
+        self.monochrome = self.profile != 1 && r.flag()?;
+
+        let (mut primaries, mut transfer, mut matrix) = (2, 2, 2);
+        if r.flag()? {
+            primaries = r.bits(8)?;
+            transfer = r.bits(8)?;
+            matrix = r.bits(8)?;
+        }
+
+        if self.monochrome {
+            r.skip(1)?; // color_range
+            self.subsampling_x = true;
+            self.subsampling_y = true;
+            return Some(());
+        }
+        // sRGB is always full range 4:4:4
+        if (primaries, transfer, matrix) == (1, 13, 0) {
+            return Some(());
+        }
+        r.skip(1)?; // color_range
+        match self.profile {
+            0 => {
+                self.subsampling_x = true;
+                self.subsampling_y = true;
+            }
+            1 => {}
+            _ if self.twelve_bit => {
+                self.subsampling_x = r.flag()?;
+                self.subsampling_y = self.subsampling_x && r.flag()?;
+            }
+            _ => self.subsampling_x = true,
+        }
+        if self.subsampling_x && self.subsampling_y {
+            self.chroma_sample_position = r.bits(2)? as u8;
+        }
+        Some(())
+    }
+}
+
+/// Most significant bit first reader
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn flag(&mut self) -> Option<bool> {
+        let byte = self.data.get(self.pos / 8)?;
+        let bit = byte >> (7 - self.pos % 8) & 1;
+        self.pos += 1;
+        Some(bit == 1)
+    }
+
+    fn bits(&mut self, n: u32) -> Option<u32> {
+        (0..n).try_fold(0, |value, _| Some(value << 1 | self.flag()? as u32))
+    }
+
+    fn skip(&mut self, n: u32) -> Option<()> {
+        self.pos += n as usize;
+        (self.pos <= self.data.len() * 8).then_some(())
+    }
+
+    /// Variable length code of the AV1 specification (4.10.3)
+    fn uvlc(&mut self) -> Option<u32> {
+        let mut leading_zeros = 0;
+        while !self.flag()? {
+            leading_zeros += 1;
+        }
+        if leading_zeros >= 32 {
+            return Some(u32::MAX);
+        }
+        Some(self.bits(leading_zeros)? + ((1u32 << leading_zeros) - 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_obus() {
+        // Temporal delimiter, then an OBU without size field running to the end
+        let data = [0x12, 0x00, 0x30, 0xaa, 0xbb];
+        let obus: Vec<_> = obus(&data).collect();
+        assert_eq!(obus.len(), 2);
+        assert!(obus[0].is_temporal_delimiter());
+        assert_eq!(obus[1].obu_type, 6);
+        assert_eq!(obus[1].payload(), [0xaa, 0xbb]);
+    }
+
+    #[test]
+    fn test_codec_config() {
+        // Sequence header written by rav1e: main profile, level 31, 8-bit 4:2:0
+        let data = [
+            0x0a, 0x0b, 0x00, 0x00, 0x00, 0xf9, 0xda, 0x7f, 0xb8, 0x84, 0x2b, 0x02, 0x80,
+        ];
+        let obu = obus(&data).next().unwrap();
+        assert!(obu.is_sequence_header());
+        assert_eq!(
+            SequenceInfo::parse(obu.payload()).unwrap(),
+            SequenceInfo {
+                level: 31,
+                subsampling_x: true,
+                subsampling_y: true,
+                ..SequenceInfo::default()
+            }
+        );
+        assert_eq!(
+            codec_config(&obu).unwrap(),
+            [&[0x81, 0x1f, 0x0c, 0x00][..], &data].concat()
+        );
+    }
+}
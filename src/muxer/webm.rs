@@ -1,6 +1,7 @@
-//! WebM container muxer
+//! WebM and Matroska container muxer
 
-use super::{Muxer, MuxerConfig};
+use super::avc::AccessUnits;
+use super::{Muxer, MuxerConfig, MuxerOutput};
 use crate::encoder::Packet;
 use crate::{Codec, Error, Result};
 use std::fs::File;
@@ -8,9 +9,15 @@ use std::io::{BufWriter, Write};
 use std::path::Path;
 
 /// WebM muxer using simple EBML writing
+///
+/// Also writes Matroska, the format WebM is a subset of, which can hold H.264 as well.
 pub struct WebmMuxer {
-    writer: BufWriter<File>,
+    writer: Box<dyn MuxerOutput>,
     config: MuxerConfig,
+    /// EBML DocType, "webm" or "matroska"
+    doc_type: &'static [u8],
+    /// H.264 frames being assembled, whose parameter sets go in the track header
+    avc: Option<AccessUnits>,
     cluster_start: u64,
     timecode: u64,
     frame_duration_ms: u64,
@@ -20,21 +27,37 @@ pub struct WebmMuxer {
 
 impl WebmMuxer {
     pub fn new<P: AsRef<Path>>(output_path: P, config: MuxerConfig) -> Result<Self> {
-        // WebM only supports AV1 (and VP8/VP9, but we only implement AV1)
-        if config.codec != Codec::Av1 {
-            return Err(Error::Mux(
-                "WebM container only supports AV1 codec".to_string(),
-            ));
-        }
+        Self::check_codec(config.codec)?;
 
         let file = File::create(output_path.as_ref()).map_err(Error::Io)?;
-        let writer = BufWriter::new(file);
+        Self::with_output(Box::new(BufWriter::new(file)), config)
+    }
+
+    /// Create a muxer writing to an arbitrary output
+    pub fn with_output(writer: Box<dyn MuxerOutput>, config: MuxerConfig) -> Result<Self> {
+        Self::check_codec(config.codec)?;
+        Self::start(writer, config, b"webm")
+    }
+
+    /// Create a Matroska (.mkv) muxer writing to an arbitrary output
+    pub fn matroska_with_output(writer: Box<dyn MuxerOutput>, config: MuxerConfig) -> Result<Self> {
+        Self::start(writer, config, b"matroska")
+    }
 
+    fn start(
+        writer: Box<dyn MuxerOutput>,
+        config: MuxerConfig,
+        doc_type: &'static [u8],
+    ) -> Result<Self> {
         let frame_duration_ms = 1000 / config.fps as u64;
+        let avc = (config.codec == Codec::H264)
+            .then(|| AccessUnits::new(config.codec_config.as_deref(), config.pps.as_deref()));
 
         let mut muxer = Self {
             writer,
             config,
+            doc_type,
+            avc,
             cluster_start: 0,
             timecode: 0,
             frame_duration_ms,
@@ -42,11 +65,25 @@ impl WebmMuxer {
             header_written: false,
         };
 
-        muxer.write_header()?;
+        // H.264 parameter sets may only be known from the first frame
+        if muxer.avc.is_none() {
+            muxer.write_header()?;
+        }
 
         Ok(muxer)
     }
 
+    /// Check that the codec can be written to WebM
+    pub(crate) fn check_codec(codec: Codec) -> Result<()> {
+        // WebM also allows VP8, which has no encoder here
+        if codec == Codec::H264 {
+            return Err(Error::Mux(
+                "WebM container only supports AV1 and VP9 codecs".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
     fn write_header(&mut self) -> Result<()> {
         // EBML Header
         self.write_ebml_element(0x1A45DFA3, &self.create_ebml_header())?;
@@ -76,8 +113,8 @@ impl WebmMuxer {
         data.extend(encode_ebml_element(0x42F2, &[4]));
         // EBMLMaxSizeLength = 8
         data.extend(encode_ebml_element(0x42F3, &[8]));
-        // DocType = "webm"
-        data.extend(encode_ebml_element(0x4282, b"webm"));
+        // DocType
+        data.extend(encode_ebml_element(0x4282, self.doc_type));
         // DocTypeVersion = 4
         data.extend(encode_ebml_element(0x4287, &[4]));
         // DocTypeReadVersion = 2
@@ -118,8 +155,17 @@ impl WebmMuxer {
         data.extend(encode_ebml_element(0x73C5, &encode_uint(1)));
         // TrackType = 1 (video)
         data.extend(encode_ebml_element(0x83, &[1]));
-        // CodecID = "V_AV1"
-        data.extend(encode_ebml_element(0x86, b"V_AV1"));
+        // CodecID
+        let codec_id: &[u8] = match self.config.codec {
+            Codec::Av1 => b"V_AV1",
+            Codec::Vp9 => b"V_VP9",
+            Codec::H264 => b"V_MPEG4/ISO/AVC",
+        };
+        data.extend(encode_ebml_element(0x86, codec_id));
+        // CodecPrivate = AVCDecoderConfigurationRecord
+        if let Some(config) = self.avc.as_ref().and_then(AccessUnits::decoder_config) {
+            data.extend(encode_ebml_element(0x63A2, &config));
+        }
         // Video settings
         data.extend(encode_ebml_element(0xE0, &self.create_video_settings()));
 
@@ -139,6 +185,19 @@ impl WebmMuxer {
             0xBA,
             &encode_uint(self.config.height as u64),
         ));
+        // Colour: MatrixCoefficients, TransferCharacteristics, Primaries and Range
+        // (broadcast or full)
+        let (primaries, transfer, matrix) = self.config.color_space.code_points();
+        let range = if self.config.pixel_range.is_full(false) {
+            2
+        } else {
+            1
+        };
+        let mut colour = encode_ebml_element(0x55B1, &encode_uint(matrix as u64));
+        colour.extend(encode_ebml_element(0x55BA, &encode_uint(transfer as u64)));
+        colour.extend(encode_ebml_element(0x55BB, &encode_uint(primaries as u64)));
+        colour.extend(encode_ebml_element(0x55B9, &encode_uint(range)));
+        data.extend(encode_ebml_element(0x55B0, &colour));
 
         data
     }
@@ -207,6 +266,22 @@ impl WebmMuxer {
 
 impl Muxer for WebmMuxer {
     fn write_packet(&mut self, packet: &Packet) -> Result<()> {
+        let unit;
+        let packet = match self.avc.as_mut() {
+            Some(avc) => match avc.push(packet) {
+                Some(complete) => {
+                    unit = complete;
+                    &unit
+                }
+                // Parameter sets and other NAL units wait for the slice of their frame
+                None => return Ok(()),
+            },
+            None => packet,
+        };
+        if !self.header_written {
+            self.write_header()?;
+        }
+
         // Start a new cluster if needed (e.g., on keyframe or every few seconds)
         if !self.cluster_open || (packet.is_keyframe && self.timecode > self.cluster_start) {
             self.cluster_open = false;
@@ -220,6 +295,9 @@ impl Muxer for WebmMuxer {
     }
 
     fn finalize(mut self: Box<Self>) -> Result<()> {
+        if !self.header_written {
+            self.write_header()?;
+        }
         self.writer.flush().map_err(Error::Io)?;
         Ok(())
     }
@@ -331,3 +409,96 @@ fn encode_uint(value: u64) -> Vec<u8> {
 
     bytes
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::muxer::MemoryOutput;
+    use crate::{ColorSpace, PixelRange};
+
+    fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+        data.windows(needle.len()).position(|w| w == needle)
+    }
+
+    #[test]
+    fn test_matroska_h264_from_bare_nal_units() {
+        let output = MemoryOutput::new();
+        let config = MuxerConfig {
+            width: 64,
+            height: 48,
+            fps: 30,
+            codec: Codec::H264,
+            codec_config: None,
+            pps: None,
+            color_space: ColorSpace::Bt601,
+            pixel_range: PixelRange::Auto,
+        };
+        let mut muxer: Box<dyn Muxer> =
+            Box::new(WebmMuxer::matroska_with_output(Box::new(output.clone()), config).unwrap());
+        // Nothing is written until the parameter sets are known
+        for nal in [&[0x67, 0x42, 0x00, 0x1e][..], &[0x68, 0xce]] {
+            muxer
+                .write_packet(&Packet {
+                    data: nal.to_vec(),
+                    pts: 0,
+                    dts: 0,
+                    is_keyframe: false,
+                })
+                .unwrap();
+        }
+        assert_eq!(output.len(), 0);
+
+        muxer
+            .write_packet(&Packet {
+                data: vec![0x65, 0x88, 0x84],
+                pts: 0,
+                dts: 0,
+                is_keyframe: true,
+            })
+            .unwrap();
+        muxer.finalize().unwrap();
+
+        let data = output.take();
+        assert!(find(&data, b"matroska").is_some());
+        assert!(find(&data, b"V_MPEG4/ISO/AVC").is_some());
+        // CodecPrivate with the AVCDecoderConfigurationRecord
+        let private = find(&data, &[0x63, 0xA2]).unwrap();
+        assert_eq!(
+            data[private + 3..private + 9],
+            [1, 0x42, 0x00, 0x1e, 0xff, 0xe1]
+        );
+        // The keyframe holds every NAL unit, length-prefixed
+        let frame = [
+            &[0, 0, 0, 4, 0x67, 0x42, 0x00, 0x1e][..],
+            &[0, 0, 0, 2, 0x68, 0xce],
+            &[0, 0, 0, 3, 0x65, 0x88, 0x84],
+        ]
+        .concat();
+        assert!(find(&data, &frame).is_some());
+    }
+
+    #[test]
+    fn test_webm_colour() {
+        let output = MemoryOutput::new();
+        let config = MuxerConfig {
+            width: 64,
+            height: 48,
+            fps: 30,
+            codec: Codec::Av1,
+            codec_config: None,
+            pps: None,
+            color_space: ColorSpace::Bt709,
+            pixel_range: PixelRange::Full,
+        };
+        let muxer: Box<dyn Muxer> =
+            Box::new(WebmMuxer::with_output(Box::new(output.clone()), config).unwrap());
+        muxer.finalize().unwrap();
+
+        let data = output.take();
+        let colour = find(&data, &[0x55, 0xB0]).unwrap();
+        assert_eq!(
+            data[colour + 3..colour + 19],
+            [0x55, 0xB1, 0x81, 1, 0x55, 0xBA, 0x81, 1, 0x55, 0xBB, 0x81, 1, 0x55, 0xB9, 0x81, 2]
+        );
+    }
+}
@@ -0,0 +1,44 @@
+//! YUV4MPEG2 (.y4m) writer for uncompressed frames
+
+use super::{Muxer, MuxerConfig, MuxerOutput};
+use crate::encoder::Packet;
+use crate::Result;
+
+/// Y4M muxer, for the I420 frames of the raw encoder
+///
+/// Writes nothing but the stream header and the frames, so the output can be a pipe
+/// read by an external encoder.
+pub struct Y4mMuxer {
+    writer: Box<dyn MuxerOutput>,
+}
+
+impl Y4mMuxer {
+    /// Create a muxer writing to an arbitrary output
+    pub fn with_output(mut writer: Box<dyn MuxerOutput>, config: MuxerConfig) -> Result<Self> {
+        // Progressive, square pixels, chroma centered between its 2x2 luma samples
+        let range = if config.pixel_range.is_full(true) {
+            "FULL"
+        } else {
+            "LIMITED"
+        };
+        let header = format!(
+            "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C420jpeg XCOLORRANGE={}\n",
+            config.width, config.height, config.fps, range
+        );
+        writer.write_all(header.as_bytes())?;
+        Ok(Self { writer })
+    }
+}
+
+impl Muxer for Y4mMuxer {
+    fn write_packet(&mut self, packet: &Packet) -> Result<()> {
+        self.writer.write_all(b"FRAME\n")?;
+        self.writer.write_all(&packet.data)?;
+        Ok(())
+    }
+
+    fn finalize(mut self: Box<Self>) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
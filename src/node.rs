@@ -0,0 +1,234 @@
+//! Node.js bindings (`napi` feature)
+//!
+//! Every operation runs on the libuv thread pool and returns a `Promise`. Encoded videos
+//! resolve to a `Buffer` instead of being written to a file.
+
+use crate::probe::VideoInfo;
+use crate::{Codec, Color, Container, EncodeOptions, Result, SlideEntry, SlideSource};
+use napi::bindgen_prelude::{AsyncTask, Buffer};
+use napi::{Env, Task};
+use napi_derive::napi;
+
+/// Container format
+#[napi(js_name = "Container")]
+pub enum JsContainer {
+    Mp4 = 0,
+    WebM = 1,
+    WebP = 2,
+    Apng = 3,
+    Mkv = 4,
+    Hls = 5,
+    Ivf = 6,
+    AnnexB = 7,
+    Y4m = 8,
+}
+
+/// Video codec
+#[napi(js_name = "Codec")]
+pub enum JsCodec {
+    Av1 = 0,
+    H264 = 1,
+    Vp9 = 2,
+}
+
+/// Encoding options; omitted fields take the library defaults (WebM, AV1, quality 50)
+#[napi(object, js_name = "EncodeOptions")]
+pub struct JsEncodeOptions {
+    pub container: Option<JsContainer>,
+    pub codec: Option<JsCodec>,
+    /// Quality (0-100, where 100 is highest quality)
+    pub quality: Option<u32>,
+    /// Path to ffmpeg executable
+    pub ffmpeg_path: Option<String>,
+}
+
+/// Slide of a slideshow, given as an image file or encoded image bytes
+#[napi(object, js_name = "Slide")]
+pub struct JsSlide {
+    /// Image file path
+    pub path: Option<String>,
+    /// Encoded image (PNG, JPEG, WebP, ...), used instead of `path`
+    pub data: Option<Buffer>,
+    /// Duration to display this image in milliseconds
+    pub duration_ms: u32,
+}
+
+/// RGB background color
+#[napi(object, js_name = "Color")]
+pub struct JsColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+/// Video metadata returned by `probe`
+#[napi(object, js_name = "VideoInfo")]
+pub struct JsVideoInfo {
+    pub width: u32,
+    pub height: u32,
+    pub fps: f64,
+    pub duration_ms: i64,
+    pub frame_count: i64,
+    pub codec: String,
+    pub container: String,
+}
+
+impl From<VideoInfo> for JsVideoInfo {
+    fn from(info: VideoInfo) -> Self {
+        Self {
+            width: info.width,
+            height: info.height,
+            fps: info.fps,
+            duration_ms: info.duration_ms as i64,
+            frame_count: info.frame_count as i64,
+            codec: info.codec,
+            container: info.container,
+        }
+    }
+}
+
+fn encode_options(options: Option<JsEncodeOptions>) -> EncodeOptions {
+    let defaults = EncodeOptions::default();
+    let Some(options) = options else {
+        return defaults;
+    };
+
+    EncodeOptions {
+        container: match options.container {
+            Some(JsContainer::Mp4) => Container::Mp4,
+            Some(JsContainer::WebM) => Container::WebM,
+            Some(JsContainer::WebP) => Container::WebP,
+            Some(JsContainer::Apng) => Container::Apng,
+            Some(JsContainer::Mkv) => Container::Mkv,
+            Some(JsContainer::Hls) => Container::Hls,
+            Some(JsContainer::Ivf) => Container::Ivf,
+            Some(JsContainer::AnnexB) => Container::AnnexB,
+            Some(JsContainer::Y4m) => Container::Y4m,
+            None => defaults.container,
+        },
+        codec: match options.codec {
+            Some(JsCodec::Av1) => Codec::Av1,
+            Some(JsCodec::H264) => Codec::H264,
+            Some(JsCodec::Vp9) => Codec::Vp9,
+            None => defaults.codec,
+        },
+        quality: options
+            .quality
+            .map_or(defaults.quality, |q| q.min(100) as u8),
+        ffmpeg_path: options.ffmpeg_path,
+        ..defaults
+    }
+}
+
+fn to_napi_error(error: crate::Error) -> napi::Error {
+    napi::Error::from_reason(error.to_string())
+}
+
+/// Background operation resolving to a JavaScript value
+pub struct Operation<T: Send + 'static> {
+    run: Option<Box<dyn FnOnce() -> Result<T> + Send>>,
+}
+
+impl<T: Send + 'static> Operation<T> {
+    fn new(run: impl FnOnce() -> Result<T> + Send + 'static) -> AsyncTask<Self>
+    where
+        Self: Task,
+    {
+        AsyncTask::new(Self {
+            run: Some(Box::new(run)),
+        })
+    }
+
+    fn run(&mut self) -> napi::Result<T> {
+        let run = self
+            .run
+            .take()
+            .ok_or_else(|| napi::Error::from_reason("Operation already ran"))?;
+        run().map_err(to_napi_error)
+    }
+}
+
+impl Task for Operation<Vec<u8>> {
+    type Output = Vec<u8>;
+    type JsValue = Buffer;
+
+    fn compute(&mut self) -> napi::Result<Vec<u8>> {
+        self.run()
+    }
+
+    fn resolve(&mut self, _env: Env, output: Vec<u8>) -> napi::Result<Buffer> {
+        Ok(output.into())
+    }
+}
+
+impl Task for Operation<VideoInfo> {
+    type Output = VideoInfo;
+    type JsValue = JsVideoInfo;
+
+    fn compute(&mut self) -> napi::Result<VideoInfo> {
+        self.run()
+    }
+
+    fn resolve(&mut self, _env: Env, output: VideoInfo) -> napi::Result<JsVideoInfo> {
+        Ok(output.into())
+    }
+}
+
+/// Create a slideshow video from image files or buffers
+#[napi]
+pub fn slideshow(
+    slides: Vec<JsSlide>,
+    options: Option<JsEncodeOptions>,
+) -> napi::Result<AsyncTask<Operation<Vec<u8>>>> {
+    let entries = slides
+        .into_iter()
+        .map(|slide| {
+            let source = match (slide.data, slide.path) {
+                (Some(data), _) => SlideSource::Bytes(data.into()),
+                (None, Some(path)) => SlideSource::Path(path),
+                (None, None) => {
+                    return Err(napi::Error::from_reason("Slide has neither path nor data"))
+                }
+            };
+            Ok(SlideEntry {
+                source,
+                duration_ms: slide.duration_ms,
+                transition: None,
+                letterbox_color: None,
+                caption: None,
+                rotation: None,
+            })
+        })
+        .collect::<napi::Result<Vec<_>>>()?;
+    let options = encode_options(options);
+
+    Ok(Operation::new(move || {
+        crate::slideshow_to_memory(&entries, &options)
+    }))
+}
+
+/// Combine two videos side by side
+#[napi]
+pub fn juxtapose(
+    left_path: String,
+    right_path: String,
+    options: Option<JsEncodeOptions>,
+    background: Option<JsColor>,
+) -> AsyncTask<Operation<Vec<u8>>> {
+    let options = encode_options(options);
+    let background = background.map(|c| Color {
+        r: c.r,
+        g: c.g,
+        b: c.b,
+    });
+
+    Operation::new(move || {
+        crate::juxtapose_to_memory(&left_path, &right_path, &options, background)
+    })
+}
+
+/// Read dimensions, frame rate, duration and codec of a video file
+#[napi]
+pub fn probe(path: String, ffmpeg_path: Option<String>) -> AsyncTask<Operation<VideoInfo>> {
+    Operation::new(move || crate::probe(&path, ffmpeg_path.as_deref()))
+}
@@ -0,0 +1,110 @@
+//! Producing frames on a worker thread while they are encoded
+
+use crate::priority::lower_current_thread;
+use crate::{Priority, Result};
+use std::sync::mpsc;
+use std::thread;
+
+/// Most frames held by a pipeline besides the one being encoded
+pub(crate) const PIPELINE_FRAMES: usize = 5;
+
+/// Produce frames on a worker thread and pass each one to `consume` on this thread
+///
+/// Frames travel through a bounded channel, so decoding and compositing the next frames
+/// overlaps with encoding the current one while at most `frames` (up to
+/// [`PIPELINE_FRAMES`]) other frames are held. With `frames` of 0 everything runs on this
+/// thread, one frame at a time. The producer stops at its first error, which is
+/// returned, and as soon as `consume` fails. Callbacks in `consume` keep running on the
+/// calling thread, and the worker runs at `priority`.
+pub(crate) fn pipeline<T, I, C>(
+    frames: usize,
+    priority: Priority,
+    produce: I,
+    mut consume: C,
+) -> Result<()>
+where
+    T: Send,
+    I: Iterator<Item = Result<T>> + Send,
+    C: FnMut(T) -> Result<()>,
+{
+    if frames == 0 {
+        for frame in produce {
+            consume(frame?)?;
+        }
+        return Ok(());
+    }
+
+    thread::scope(|scope| {
+        // One more frame is held by the producer while it waits to send
+        let (sender, receiver) = mpsc::sync_channel(frames.min(PIPELINE_FRAMES) - 1);
+
+        scope.spawn(move || {
+            lower_current_thread(priority);
+            for frame in produce {
+                let failed = frame.is_err();
+                // A closed channel means the consumer has stopped
+                if sender.send(frame).is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        // Returning drops the receiver, unblocking a producer waiting to send
+        for frame in receiver {
+            consume(frame?)?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn test_pipeline_preserves_order() {
+        for frames in [0, 1, PIPELINE_FRAMES] {
+            let mut consumed = Vec::new();
+            pipeline(frames, Priority::Normal, (0..100u64).map(Ok), |frame| {
+                consumed.push(frame);
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(consumed, (0..100).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_pipeline_stops_on_errors() {
+        let frames = (0..100u64).map(|i| {
+            if i == 10 {
+                Err(Error::Decode("bad frame".to_string()))
+            } else {
+                Ok(i)
+            }
+        });
+        let mut consumed = 0;
+        let result = pipeline(PIPELINE_FRAMES, Priority::Normal, frames, |_| {
+            consumed += 1;
+            Ok(())
+        });
+        assert!(matches!(result, Err(Error::Decode(_))));
+        assert_eq!(consumed, 10);
+
+        // An endless producer stops once the consumer fails
+        let result = pipeline(
+            PIPELINE_FRAMES,
+            Priority::Normal,
+            (0u64..).map(Ok),
+            |frame| {
+                if frame == 5 {
+                    Err(Error::Cancelled)
+                } else {
+                    Ok(())
+                }
+            },
+        );
+        assert!(matches!(result, Err(Error::Cancelled)));
+    }
+}
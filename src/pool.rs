@@ -0,0 +1,62 @@
+//! Reusable frame buffers
+
+use std::sync::{Mutex, MutexGuard};
+
+/// Frame buffers of one size, handed back after use so per-frame loops stop allocating
+///
+/// Shared between the thread producing frames and the one encoding them; the pool grows
+/// to the number of frames in flight at once.
+pub(crate) struct FramePool {
+    size: usize,
+    buffers: Mutex<Vec<Vec<u8>>>,
+}
+
+impl FramePool {
+    /// Create an empty pool of `size`-byte buffers
+    pub(crate) fn new(size: usize) -> Self {
+        Self {
+            size,
+            buffers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Take a buffer of `size` bytes, allocating one if none is free
+    ///
+    /// A reused buffer keeps the contents of its previous frame.
+    pub(crate) fn take(&self) -> Vec<u8> {
+        self.lock().pop().unwrap_or_else(|| vec![0u8; self.size])
+    }
+
+    /// Return a buffer taken from this pool
+    pub(crate) fn give(&self, buffer: Vec<u8>) {
+        if buffer.len() == self.size {
+            self.lock().push(buffer);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Vec<u8>>> {
+        // Buffers are plain bytes, so a panic while locked cannot leave them inconsistent
+        self.buffers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_pool_reuses_buffers() {
+        let pool = FramePool::new(16);
+        let buffer = pool.take();
+        assert_eq!(buffer.len(), 16);
+
+        let ptr = buffer.as_ptr();
+        pool.give(buffer);
+        let reused = pool.take();
+        assert_eq!(reused.as_ptr(), ptr);
+
+        // Buffers of another size are dropped
+        pool.give(vec![0u8; 8]);
+        assert_eq!(pool.take().len(), 16);
+    }
+}
@@ -0,0 +1,215 @@
+//! Reduced scheduling priority for ffmpeg processes and encoding threads
+
+use std::process::Command;
+
+/// Scheduling priority of the processes and threads an operation starts
+///
+/// Lowering the priority keeps background video generation from starving interactive
+/// work on a shared host. It applies to ffmpeg processes, the AV1 encoder's threads and
+/// the worker threads the library spawns; the calling thread is left as it is. A priority
+/// is only ever lowered, never raised above that of the calling process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
+pub enum Priority {
+    /// Priority of the calling process
+    #[default]
+    Normal = 0,
+    /// Below normal (nice 10, utility QoS on macOS, below normal class on Windows)
+    Low = 1,
+    /// Only when the machine is otherwise idle (nice 19, background QoS, idle class)
+    Idle = 2,
+}
+
+impl Priority {
+    /// Unix niceness for this priority, `None` to leave it unchanged
+    #[cfg(unix)]
+    fn nice(self) -> Option<libc::c_int> {
+        match self {
+            Priority::Normal => None,
+            Priority::Low => Some(10),
+            Priority::Idle => Some(19),
+        }
+    }
+}
+
+/// Start the process of `command` at `priority`
+#[cfg(unix)]
+pub(crate) fn lower_command(command: &mut Command, priority: Priority) {
+    use std::os::unix::process::CommandExt;
+
+    if let Some(nice) = priority.nice() {
+        // SAFETY: setpriority is async-signal-safe and touches no memory of the parent.
+        // It fails without privileges when the process is already nicer, which is kept.
+        unsafe {
+            command.pre_exec(move || {
+                libc::setpriority(libc::PRIO_PROCESS as _, 0, nice);
+                Ok(())
+            });
+        }
+    }
+}
+
+/// Start the process of `command` at `priority`
+#[cfg(windows)]
+pub(crate) fn lower_command(command: &mut Command, priority: Priority) {
+    use std::os::windows::process::CommandExt;
+
+    const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x4000;
+    const IDLE_PRIORITY_CLASS: u32 = 0x40;
+    match priority {
+        Priority::Normal => {}
+        Priority::Low => {
+            command.creation_flags(BELOW_NORMAL_PRIORITY_CLASS);
+        }
+        Priority::Idle => {
+            command.creation_flags(IDLE_PRIORITY_CLASS);
+        }
+    }
+}
+
+/// Start the process of `command` at `priority`
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn lower_command(_command: &mut Command, _priority: Priority) {}
+
+/// Lower the priority of the current thread, a worker spawned by the library
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn lower_current_thread(priority: Priority) {
+    if let Some(nice) = priority.nice() {
+        // Linux threads have their own niceness, addressed by thread id
+        // SAFETY: plain system calls on the current thread
+        unsafe {
+            libc::setpriority(libc::PRIO_PROCESS as _, libc::gettid() as libc::id_t, nice);
+        }
+    }
+}
+
+/// Lower the priority of the current thread, a worker spawned by the library
+#[cfg(target_os = "macos")]
+pub(crate) fn lower_current_thread(priority: Priority) {
+    let class = match priority {
+        Priority::Normal => return,
+        Priority::Low => libc::qos_class_t::QOS_CLASS_UTILITY,
+        Priority::Idle => libc::qos_class_t::QOS_CLASS_BACKGROUND,
+    };
+    // SAFETY: only changes the scheduling class of the current thread
+    unsafe {
+        libc::pthread_set_qos_class_self_np(class, 0);
+    }
+}
+
+/// Lower the priority of the current thread, a worker spawned by the library
+#[cfg(windows)]
+pub(crate) fn lower_current_thread(priority: Priority) {
+    use windows::Win32::System::Threading::{
+        GetCurrentThread, SetThreadPriority, THREAD_PRIORITY_BELOW_NORMAL, THREAD_PRIORITY_IDLE,
+    };
+
+    let level = match priority {
+        Priority::Normal => return,
+        Priority::Low => THREAD_PRIORITY_BELOW_NORMAL,
+        Priority::Idle => THREAD_PRIORITY_IDLE,
+    };
+    // SAFETY: the pseudo handle of the current thread needs no closing
+    unsafe {
+        let _ = SetThreadPriority(GetCurrentThread(), level);
+    }
+}
+
+/// Lower the priority of the current thread, a worker spawned by the library
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    windows
+)))]
+pub(crate) fn lower_current_thread(_priority: Priority) {}
+
+/// Rayon pool of `threads` threads (0 for one per core) running at `priority`
+///
+/// Pools are built on first use and kept for the life of the process, one per priority
+/// and thread count. Returns `None` for [`Priority::Normal`] with a thread count of 0,
+/// where work stays on the global pool.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn thread_pool(
+    priority: Priority,
+    threads: usize,
+) -> Option<std::sync::Arc<rayon::ThreadPool>> {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex, OnceLock};
+
+    type Pools = Mutex<HashMap<(Priority, usize), Arc<rayon::ThreadPool>>>;
+    static POOLS: OnceLock<Pools> = OnceLock::new();
+
+    if priority == Priority::Normal && threads == 0 {
+        return None;
+    }
+    let mut pools = POOLS
+        .get_or_init(Pools::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some(pool) = pools.get(&(priority, threads)) {
+        return Some(Arc::clone(pool));
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|i| format!("minmpeg-worker-{}", i))
+        .start_handler(move |_| lower_current_thread(priority))
+        .build()
+        .ok()?;
+    let pool = Arc::new(pool);
+    pools.insert((priority, threads), Arc::clone(&pool));
+    Some(pool)
+}
+
+/// Rayon pool of `threads` threads running at `priority`, never built without threads
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn thread_pool(
+    _priority: Priority,
+    _threads: usize,
+) -> Option<std::sync::Arc<rayon::ThreadPool>> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thread_pool_is_shared_per_priority() {
+        assert!(thread_pool(Priority::Normal, 0).is_none());
+        assert_eq!(
+            thread_pool(Priority::Normal, 2)
+                .unwrap()
+                .current_num_threads(),
+            2
+        );
+
+        let pool = thread_pool(Priority::Low, 1).unwrap();
+        assert!(std::sync::Arc::ptr_eq(
+            &pool,
+            &thread_pool(Priority::Low, 1).unwrap()
+        ));
+        assert_eq!(pool.install(|| 1 + 1), 2);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_lowered_thread_and_process() {
+        let nice = std::thread::spawn(|| {
+            lower_current_thread(Priority::Low);
+            // SAFETY: reads the niceness of the current thread
+            unsafe { libc::getpriority(libc::PRIO_PROCESS as _, libc::gettid() as libc::id_t) }
+        })
+        .join()
+        .unwrap();
+        assert!(nice >= 10);
+
+        let mut command = Command::new("sh");
+        command.args(["-c", "nice"]);
+        lower_command(&mut command, Priority::Idle);
+        let output = command.output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "19");
+    }
+}
@@ -0,0 +1,241 @@
+//! Video file probing using ffprobe
+
+use crate::ffmpeg::{ffprobe_path, find_ffmpeg};
+use crate::logging::log_debug;
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+
+/// Information about a video file
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VideoInfo {
+    /// Frame width in pixels
+    pub width: u32,
+    /// Frame height in pixels
+    pub height: u32,
+    /// Frame rate (frames per second)
+    pub fps: f64,
+    /// Duration in milliseconds
+    pub duration_ms: u64,
+    /// Number of frames (estimated from duration if the container does not store it)
+    pub frame_count: u64,
+    /// Codec name as reported by ffprobe (e.g. "av1", "h264")
+    pub codec: String,
+    /// Container format names as reported by ffprobe (e.g. "matroska,webm"), or for MP4
+    /// and its relatives the one named by the file's brand (e.g. "mp4", "mov")
+    pub container: String,
+}
+
+/// Probe a video file for its dimensions, frame rate, duration, codec and container
+///
+/// Uses the ffprobe executable located next to ffmpeg.
+pub fn probe<P: AsRef<Path>>(path: P, ffmpeg_path: Option<&str>) -> Result<VideoInfo> {
+    let path = path.as_ref();
+    let ffmpeg = find_ffmpeg(ffmpeg_path)?;
+    let ffprobe = ffprobe_path(&ffmpeg);
+
+    log_debug!("Probing {} with {}", path.display(), ffprobe);
+
+    let output = Command::new(&ffprobe)
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            concat!(
+                "stream=codec_name,width,height,r_frame_rate,nb_frames",
+                ":format=format_name,duration:format_tags=major_brand",
+            ),
+            "-of",
+            "default=noprint_wrappers=1",
+        ])
+        .arg(path)
+        .output()
+        .map_err(|e| Error::spawn(&ffprobe, e))?;
+
+    if !output.status.success() {
+        return Err(Error::Ffmpeg {
+            message: format!("ffprobe failed for {}", path.display()),
+            stderr: String::from_utf8_lossy(&output.stderr)
+                .lines()
+                .map(str::to_string)
+                .collect(),
+        });
+    }
+
+    let info = parse_probe_output(&String::from_utf8_lossy(&output.stdout))?;
+    log_debug!(
+        "{}: {}x{} {} in {} at {:.3} fps, {} frames, {} ms",
+        path.display(),
+        info.width,
+        info.height,
+        info.codec,
+        info.container,
+        info.fps,
+        info.frame_count,
+        info.duration_ms
+    );
+
+    Ok(info)
+}
+
+/// Check whether a file has an audio stream
+pub(crate) fn has_audio<P: AsRef<Path>>(path: P, ffmpeg_path: Option<&str>) -> Result<bool> {
+    let ffprobe = ffprobe_path(&find_ffmpeg(ffmpeg_path)?);
+
+    let output = Command::new(&ffprobe)
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "a",
+            "-show_entries",
+            "stream=index",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(path.as_ref())
+        .output()
+        .map_err(|e| Error::spawn(&ffprobe, e))?;
+
+    Ok(output.status.success() && !output.stdout.trim_ascii().is_empty())
+}
+
+/// Parse `key=value` lines printed by ffprobe
+fn parse_probe_output(output: &str) -> Result<VideoInfo> {
+    let fields: HashMap<&str, &str> = output
+        .lines()
+        .filter_map(|line| line.trim().split_once('='))
+        .collect();
+
+    let width: u32 = parse_field(&fields, "width")?;
+    let height: u32 = parse_field(&fields, "height")?;
+
+    // Parse frame rate (e.g., "30/1" or "30000/1001")
+    let fps = fields
+        .get("r_frame_rate")
+        .and_then(|s| parse_frame_rate(s))
+        .unwrap_or(30.0);
+
+    let codec = fields.get("codec_name").unwrap_or(&"unknown").to_string();
+    // ffprobe gives every ISO base media file the same format names
+    let container = match (fields.get("format_name"), fields.get("TAG:major_brand")) {
+        (Some(&ISO_FORMAT_NAMES), Some(brand)) => iso_container(brand).to_string(),
+        (name, _) => name.unwrap_or(&"unknown").to_string(),
+    };
+
+    let duration_secs: Option<f64> = fields.get("duration").and_then(|s| s.parse().ok());
+    let frame_count: Option<u64> = fields
+        .get("nb_frames")
+        .and_then(|s| s.parse().ok())
+        .filter(|&n| n > 0);
+
+    // If frame count is not available, estimate from duration (and vice versa)
+    let (frame_count, duration_ms) = match (frame_count, duration_secs) {
+        (Some(frames), Some(secs)) => (frames, (secs * 1000.0).round() as u64),
+        (Some(frames), None) => (frames, (frames as f64 * 1000.0 / fps).round() as u64),
+        (None, Some(secs)) => ((secs * fps).ceil() as u64, (secs * 1000.0).round() as u64),
+        (None, None) => (0, 0),
+    };
+
+    Ok(VideoInfo {
+        width,
+        height,
+        fps,
+        duration_ms,
+        frame_count,
+        codec,
+        container,
+    })
+}
+
+/// ffprobe's format names of ISO base media files
+const ISO_FORMAT_NAMES: &str = "mov,mp4,m4a,3gp,3g2,mj2";
+
+/// Container of an ISO base media file whose ftyp box has the major brand `brand`
+pub(crate) fn iso_container(brand: &str) -> &'static str {
+    match brand.trim_end() {
+        "qt" => "mov",
+        "M4A" | "M4B" => "m4a",
+        "mjp2" | "mj2s" => "mj2",
+        brand if brand.starts_with("3g2") => "3g2",
+        brand if brand.starts_with("3g") => "3gp",
+        _ => "mp4",
+    }
+}
+
+/// Parse the value of `key` in ffprobe output
+fn parse_field<T: FromStr>(fields: &HashMap<&str, &str>, key: &str) -> Result<T> {
+    let value = fields.get(key).copied();
+    value.and_then(|s| s.parse().ok()).ok_or_else(|| {
+        Error::Decode(format!(
+            "Failed to parse {} from ffprobe: {}",
+            key,
+            value.unwrap_or("missing")
+        ))
+    })
+}
+
+/// Parse a frame rate such as "30/1", "30000/1001" or "25"
+fn parse_frame_rate(s: &str) -> Option<f64> {
+    let fps = match s.split_once('/') {
+        Some((num, den)) => {
+            let num: f64 = num.parse().ok()?;
+            let den: f64 = den.parse().ok()?;
+            num / den
+        }
+        None => s.parse().ok()?,
+    };
+
+    if fps.is_finite() && fps > 0.0 {
+        Some(fps)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_probe_output() {
+        let output = "codec_name=h264\nwidth=320\nheight=240\nr_frame_rate=30000/1001\n\
+                      nb_frames=N/A\nformat_name=mov,mp4,m4a,3gp,3g2,mj2\nduration=2.002000\n\
+                      TAG:major_brand=qt  \n";
+
+        let info = parse_probe_output(output).unwrap();
+        assert_eq!(info.width, 320);
+        assert_eq!(info.height, 240);
+        assert!((info.fps - 29.97).abs() < 0.01);
+        assert_eq!(info.duration_ms, 2002);
+        assert_eq!(info.frame_count, 60);
+        assert_eq!(info.codec, "h264");
+        assert_eq!(info.container, "mov");
+    }
+
+    #[test]
+    fn test_parse_probe_output_missing_stream() {
+        assert!(parse_probe_output("duration=1.0\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_probe_output_names_bad_field() {
+        let Error::Decode(message) = parse_probe_output("width=320\nheight=N/A\n").unwrap_err()
+        else {
+            panic!("expected a decode error");
+        };
+        assert_eq!(message, "Failed to parse height from ffprobe: N/A");
+    }
+
+    #[test]
+    fn test_parse_frame_rate() {
+        assert_eq!(parse_frame_rate("30/1"), Some(30.0));
+        assert_eq!(parse_frame_rate("25"), Some(25.0));
+        assert_eq!(parse_frame_rate("0/0"), None);
+    }
+}
@@ -0,0 +1,52 @@
+//! Progress reporting for long-running operations
+
+use std::fmt;
+use std::sync::Arc;
+
+/// Progress of a running operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Number of frames encoded so far
+    pub frames_done: u64,
+    /// Total number of frames to encode
+    pub frames_total: u64,
+    /// What the operation is busy with
+    pub phase: Phase,
+}
+
+/// Part of an operation that progress is reported for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// Rendering and encoding frames, counted by `frames_done`
+    Encoding,
+    /// Every frame is in: the encoder drains the frames it holds back, which may take
+    /// a while for AV1, and the output is written
+    Finishing,
+}
+
+/// Callback invoked as an operation makes progress
+///
+/// The callback is called synchronously on the thread running the operation.
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<dyn Fn(Progress) + Send + Sync>);
+
+impl ProgressCallback {
+    /// Create a progress callback from a closure
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(Progress) + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    /// Invoke the callback
+    pub fn call(&self, progress: Progress) {
+        (self.0)(progress)
+    }
+}
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
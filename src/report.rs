@@ -0,0 +1,180 @@
+//! Non-fatal warnings raised while an operation runs
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Issue that did not stop an operation but changed its output
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Warning {
+    /// Odd output dimensions were rounded down to even
+    DimensionsRounded { width: u32, height: u32 },
+    /// A slide was resized to the output dimensions
+    ImageResized {
+        index: usize,
+        width: u32,
+        height: u32,
+    },
+    /// A slide shorter than one frame was shown for one frame
+    DurationRounded { index: usize, duration_ms: u32 },
+    /// An input video was converted to the output frame rate
+    FrameRateConverted { path: PathBuf, from: f64, to: u32 },
+    /// An input video's audio track was dropped
+    AudioIgnored { path: PathBuf },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::DimensionsRounded { width, height } => write!(
+                f,
+                "Dimensions {}x{} are not even, rounded down to {}x{}",
+                width,
+                height,
+                width / 2 * 2,
+                height / 2 * 2
+            ),
+            Warning::ImageResized {
+                index,
+                width,
+                height,
+            } => write!(f, "Slide {} resized from {}x{}", index, width, height),
+            Warning::DurationRounded { index, duration_ms } => write!(
+                f,
+                "Slide {} duration {} ms is shorter than one frame",
+                index, duration_ms
+            ),
+            Warning::FrameRateConverted { path, from, to } => write!(
+                f,
+                "{} converted from {:.3} fps to {} fps",
+                path.display(),
+                from,
+                to
+            ),
+            Warning::AudioIgnored { path } => {
+                write!(f, "Audio track of {} ignored", path.display())
+            }
+        }
+    }
+}
+
+/// Warning kind codes for FFI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
+pub enum WarningCode {
+    DimensionsRounded = 0,
+    ImageResized = 1,
+    DurationRounded = 2,
+    FrameRateConverted = 3,
+    AudioIgnored = 4,
+}
+
+impl From<&Warning> for WarningCode {
+    fn from(warning: &Warning) -> Self {
+        match warning {
+            Warning::DimensionsRounded { .. } => WarningCode::DimensionsRounded,
+            Warning::ImageResized { .. } => WarningCode::ImageResized,
+            Warning::DurationRounded { .. } => WarningCode::DurationRounded,
+            Warning::FrameRateConverted { .. } => WarningCode::FrameRateConverted,
+            Warning::AudioIgnored { .. } => WarningCode::AudioIgnored,
+        }
+    }
+}
+
+/// Callback invoked for each warning
+///
+/// The callback is called synchronously on the thread running the operation.
+#[derive(Clone)]
+pub struct WarningCallback(Arc<dyn Fn(&Warning) + Send + Sync>);
+
+impl WarningCallback {
+    /// Create a warning callback from a closure
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&Warning) + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    /// Invoke the callback
+    pub fn call(&self, warning: &Warning) {
+        (self.0)(warning)
+    }
+}
+
+impl fmt::Debug for WarningCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WarningCallback")
+    }
+}
+
+/// Warnings collected from one or more operations
+///
+/// Clones share the same list. Pass `callback()` as `EncodeOptions::warning` and read
+/// `warnings()` once the operation returns.
+#[derive(Debug, Clone, Default)]
+pub struct Report(Arc<Mutex<Vec<Warning>>>);
+
+impl Report {
+    /// Create an empty report
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Callback appending each warning to this report
+    pub fn callback(&self) -> WarningCallback {
+        let report = self.clone();
+        WarningCallback::new(move |warning| report.lock().push(warning.clone()))
+    }
+
+    /// Warnings collected so far
+    pub fn warnings(&self) -> Vec<Warning> {
+        self.lock().clone()
+    }
+
+    /// Check if no warnings have been collected
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Warning>> {
+        // A panicking callback cannot leave the list inconsistent
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Serialized as the list of warnings collected so far
+#[cfg(feature = "serde")]
+impl serde::Serialize for Report {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.warnings())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_collects_warnings() {
+        let report = Report::new();
+        let callback = report.callback();
+        assert!(report.is_empty());
+
+        let warning = Warning::DimensionsRounded {
+            width: 101,
+            height: 50,
+        };
+        callback.call(&warning);
+
+        assert_eq!(report.warnings(), vec![warning.clone()]);
+        assert_eq!(WarningCode::from(&warning), WarningCode::DimensionsRounded);
+        assert_eq!(
+            warning.to_string(),
+            "Dimensions 101x50 are not even, rounded down to 100x50"
+        );
+    }
+}
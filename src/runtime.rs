@@ -0,0 +1,146 @@
+//! Process-wide configuration and build introspection
+
+use crate::encoder::{backend_for, EncoderBackend};
+use crate::{Codec, Error, Result};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Locations tried for ffmpeg by default, in order
+const DEFAULT_FFMPEG_PATHS: [&str; 4] = [
+    "ffmpeg",
+    "/usr/bin/ffmpeg",
+    "/usr/local/bin/ffmpeg",
+    "/opt/homebrew/bin/ffmpeg",
+];
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Settings shared by every operation of the process
+///
+/// Set once with [`configure`] before the first operation; until then, and if never set,
+/// [`Config::default`] applies. Per-operation [`EncodeOptions`] take precedence where
+/// both have a setting.
+///
+/// [`EncodeOptions`]: crate::EncodeOptions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// Threads encoding AV1, converting frames to YUV and running in each ffmpeg process,
+    /// and most segments encoded at once (0 for one per core)
+    pub threads: usize,
+    /// Keep slide images in temporary files in this directory when
+    /// [`EncodeOptions::spill_dir`](crate::EncodeOptions::spill_dir) is not set
+    pub temp_dir: Option<PathBuf>,
+    /// Locations tried in order for ffmpeg when
+    /// [`EncodeOptions::ffmpeg_path`](crate::EncodeOptions::ffmpeg_path) is not set
+    pub ffmpeg_paths: Vec<String>,
+    /// Use hardware encoders (VideoToolbox, Media Foundation, NVENC and VAAPI); without them
+    /// H.264 is unavailable on macOS, iOS and Windows, and encoded with libx264 on Linux
+    pub hardware: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            threads: 0,
+            temp_dir: None,
+            ffmpeg_paths: DEFAULT_FFMPEG_PATHS.iter().map(|p| p.to_string()).collect(),
+            hardware: true,
+        }
+    }
+}
+
+/// Set the configuration of the process
+///
+/// Fails with [`Error::InvalidInput`] once a configuration is in place, whether set by
+/// an earlier call or defaulted by an operation that has already run.
+pub fn configure(config: Config) -> Result<()> {
+    CONFIG
+        .set(config)
+        .map_err(|_| Error::InvalidInput("Runtime configuration is already in place".to_string()))
+}
+
+/// Configuration of the process, the default one if none was set
+pub fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// How the library was built
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BuildInfo {
+    /// Crate version
+    pub version: &'static str,
+    /// Operating system the library was built for
+    pub target_os: &'static str,
+    /// CPU architecture the library was built for
+    pub target_arch: &'static str,
+    /// Enabled cargo features
+    pub features: Vec<&'static str>,
+    /// Encoder backends compiled in, whether or not usable on this machine (see
+    /// [`capabilities`](crate::capabilities))
+    pub backends: Vec<EncoderBackend>,
+}
+
+/// Report the version, target, features and encoder backends of this build
+pub fn build_info() -> BuildInfo {
+    let features = [
+        ("av1", cfg!(feature = "av1")),
+        ("svt-av1", cfg!(feature = "svt-av1")),
+        ("nvenc", cfg!(feature = "nvenc")),
+        ("vaapi", cfg!(feature = "vaapi")),
+        ("wasm", cfg!(feature = "wasm")),
+        ("napi", cfg!(feature = "napi")),
+        ("tracing", cfg!(feature = "tracing")),
+        ("serde", cfg!(feature = "serde")),
+        ("text", cfg!(feature = "text")),
+    ];
+
+    let mut backends: Vec<EncoderBackend> = [Codec::Av1, Codec::H264, Codec::Vp9]
+        .into_iter()
+        .filter_map(backend_for)
+        .collect();
+    // ffmpeg may back both H.264 and VP9
+    backends.dedup();
+    if cfg!(all(feature = "svt-av1", not(target_arch = "wasm32"))) {
+        backends.push(EncoderBackend::SvtAv1);
+    }
+    if cfg!(all(feature = "nvenc", target_os = "linux"))
+        && !backends.contains(&EncoderBackend::Nvenc)
+    {
+        backends.push(EncoderBackend::Nvenc);
+    }
+    if cfg!(all(feature = "vaapi", target_os = "linux"))
+        && !backends.contains(&EncoderBackend::Vaapi)
+    {
+        backends.push(EncoderBackend::Vaapi);
+    }
+    backends.push(EncoderBackend::Image);
+    backends.push(EncoderBackend::Raw);
+
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        target_os: std::env::consts::OS,
+        target_arch: std::env::consts::ARCH,
+        features: features
+            .into_iter()
+            .filter_map(|(name, enabled)| enabled.then_some(name))
+            .collect(),
+        backends,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info() {
+        let info = build_info();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.target_os, std::env::consts::OS);
+        assert_eq!(
+            info.features.contains(&"av1"),
+            info.backends.contains(&EncoderBackend::Rav1e)
+        );
+    }
+}
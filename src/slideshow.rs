@@ -1,85 +1,435 @@
 //! Slideshow video generation
 
-use crate::encoder::{create_encoder, EncoderConfig, Frame, Packet};
-use crate::image_loader::LoadedImage;
-use crate::muxer::{create_muxer, MuxerConfig};
-use crate::{EncodeOptions, Error, Result, SlideEntry};
+use crate::animation::{create_writer, write_animation};
+use crate::budget::MemoryBudget;
+use crate::encoder::{Encoder, EncoderBackend, EncoderConfig, Frame, Packet, PooledEncoder};
+use crate::fade::with_fade;
+use crate::image_cache::ImageSource;
+use crate::image_loader::{rgba_len, LoadedImage};
+use crate::logging::{log_info, trace_span};
+use crate::muxer::{check_output, write_packets, MemoryOutput, MuxerConfig};
+use crate::priority::lower_current_thread;
+use crate::runtime;
+use crate::spill::{SpillFile, SpilledImage};
+use crate::stats::{EncodeStats, StatsRecorder};
+use crate::transition::Transition;
+use crate::validate::StrictCheck;
+use crate::verify::{verify, QualityReport};
+use crate::watermark::with_watermark;
+use crate::{
+    Caption, Codec, Color, EncodeOptions, Error, Result, SlideEntry, SlideSource, Stage, Warning,
+};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 
 /// Default frame rate for slideshow videos
 const DEFAULT_FPS: u32 = 30;
 
+/// Fewest frames worth encoding as a separate segment on its own thread
+const MIN_SEGMENT_FRAMES: u64 = 300;
+
+/// Most segments in deterministic mode, whatever the number of cores
+const DETERMINISTIC_SEGMENTS: usize = 4;
+
 /// Create a slideshow video from a sequence of images
 ///
 /// Each image is displayed for the specified duration (in milliseconds).
 /// All images are resized to match the dimensions of the first image.
-pub fn slideshow(entries: &[SlideEntry], options: &EncodeOptions) -> Result<()> {
+pub fn slideshow(entries: &[SlideEntry], options: &EncodeOptions) -> Result<EncodeStats> {
+    slideshow_to(entries, options, None)
+}
+
+/// Create a slideshow video in memory and return the encoded bytes
+///
+/// `options.output_path` is ignored.
+pub fn slideshow_to_memory(entries: &[SlideEntry], options: &EncodeOptions) -> Result<Vec<u8>> {
+    let output = MemoryOutput::new();
+    slideshow_to(entries, options, Some(&output))?;
+    Ok(output.take())
+}
+
+/// Decoded image of a slide and how long it is shown
+pub(crate) struct Slide {
+    image: SlideImage,
+    duration_ms: u32,
+    /// Transition from the previous slide
+    transition: Option<Transition>,
+    /// Color of the bars around the image when letterboxed, if not the default
+    letterbox_color: Option<Color>,
+    /// Text drawn on top of the image once it is resized
+    caption: Option<Caption>,
+    /// File the image was loaded through `EncodeOptions::image_cache`, to share its
+    /// resized versions
+    source: Option<ImageSource>,
+}
+
+impl Slide {
+    /// Move the image to `spill`, if set, unless it is shared with the image cache
+    fn spill_to(self, spill: Option<&Arc<SpillFile>>) -> Result<Self> {
+        let image = match (spill, &self.image) {
+            (Some(spill), SlideImage::Memory(image)) if self.source.is_none() => {
+                SlideImage::Spilled(spill.spill(image)?)
+            }
+            _ => self.image,
+        };
+        Ok(Self { image, ..self })
+    }
+
+    /// Check if the image stays in memory when slides are spilled
+    fn pinned(&self) -> bool {
+        self.source.is_some()
+    }
+}
+
+/// Pixels of a slide, in memory or in a spill file
+enum SlideImage {
+    Memory(Arc<LoadedImage>),
+    Spilled(SpilledImage),
+}
+
+impl SlideImage {
+    fn size(&self) -> (u32, u32) {
+        match self {
+            SlideImage::Memory(image) => (image.width, image.height),
+            SlideImage::Spilled(image) => (image.width, image.height),
+        }
+    }
+
+    /// Image in memory, read back if spilled
+    fn load(&self) -> Result<Arc<LoadedImage>> {
+        match self {
+            SlideImage::Memory(image) => Ok(Arc::clone(image)),
+            SlideImage::Spilled(image) => image.load().map(Arc::new),
+        }
+    }
+}
+
+/// Pixels of slides by index, keeping the last spilled slide read for its next frames
+struct SlidePixels<'a> {
+    slides: &'a [Slide],
+    buffer: Vec<u8>,
+    buffered: Option<usize>,
+}
+
+impl<'a> SlidePixels<'a> {
+    fn new(slides: &'a [Slide]) -> Self {
+        Self {
+            slides,
+            buffer: Vec::new(),
+            buffered: None,
+        }
+    }
+
+    fn get(&mut self, index: usize) -> Result<&[u8]> {
+        match &self.slides[index].image {
+            SlideImage::Memory(image) => Ok(&image.data),
+            SlideImage::Spilled(image) => {
+                if self.buffered != Some(index) {
+                    self.buffered = None;
+                    image.read_into(&mut self.buffer)?;
+                    self.buffered = Some(index);
+                }
+                Ok(&self.buffer)
+            }
+        }
+    }
+
+    /// Draw frame `offset` of slide `index` into `frame` if it is part of the transition
+    /// from the previous slide
+    fn transition(
+        &mut self,
+        index: usize,
+        offset: u64,
+        size: (u32, u32),
+        frame: &mut Vec<u8>,
+    ) -> Result<bool> {
+        let slide = &self.slides[index];
+        let Some(transition) = slide.transition.filter(|_| index > 0) else {
+            return Ok(false);
+        };
+        let steps = transition_frame_count(&transition, slide.duration_ms);
+        if offset >= steps {
+            return Ok(false);
+        }
+
+        frame.clear();
+        frame.extend_from_slice(self.get(index - 1)?);
+        let next = self.get(index)?;
+        transition.apply(offset, steps, next, size, frame);
+        Ok(true)
+    }
+}
+
+/// Spill file in `options.spill_dir`, or the runtime's temporary directory, if set
+fn open_spill(options: &EncodeOptions) -> Result<Option<Arc<SpillFile>>> {
+    options
+        .spill_dir
+        .as_deref()
+        .map(Path::new)
+        .or(runtime::config().temp_dir.as_deref())
+        .map(SpillFile::create)
+        .transpose()
+}
+
+/// Decode the image of a slide, through `options.image_cache` if set and the slide is a file
+///
+/// Files are decoded at a reduced scale if they will be scaled to `at_least` anyway,
+/// unless they are shared through the cache at full size.
+pub(crate) fn load_source(
+    source: &SlideSource,
+    options: &EncodeOptions,
+    at_least: Option<(u32, u32)>,
+) -> Result<(Arc<LoadedImage>, Option<ImageSource>)> {
+    match (source, &options.image_cache) {
+        (SlideSource::Path(path), Some(cache)) => cache
+            .load(path)
+            .map(|(image, source)| (image, Some(source)))
+            .map_err(|e| e.with_path(path)),
+        (SlideSource::Path(path), None) => match at_least {
+            Some((width, height)) => LoadedImage::from_path_at_least(path, width, height),
+            None => LoadedImage::from_path(path),
+        }
+        .map(|image| (Arc::new(image), None))
+        .map_err(|e| e.with_path(path)),
+        (SlideSource::Bytes(bytes), _) => {
+            LoadedImage::from_bytes(bytes).map(|image| (Arc::new(image), None))
+        }
+        (
+            SlideSource::Rgba {
+                width,
+                height,
+                data,
+            },
+            _,
+        ) => LoadedImage::from_rgba(*width, *height, data.clone())
+            .map(|image| (Arc::new(image), None)),
+    }
+}
+
+/// Create a slideshow video, writing to `memory` if set or to `options.output_path` otherwise
+pub(crate) fn slideshow_to(
+    entries: &[SlideEntry],
+    options: &EncodeOptions,
+    memory: Option<&MemoryOutput>,
+) -> Result<EncodeStats> {
+    let _span = trace_span!("slideshow", slides = entries.len());
+    let recorder = StatsRecorder::start();
+
     // Validate options
     options.validate()?;
+    check_output(options.container, memory)?;
 
     if entries.is_empty() {
         return Err(Error::InvalidInput("No slides provided".to_string()));
     }
 
     // Load and validate all images
-    let mut images: Vec<(LoadedImage, u32)> = Vec::new();
+    let mut slides = Vec::new();
+    let spill = open_spill(options)?;
+
+    {
+        let _span = trace_span!("load");
+        // Fail at the first slide over the limit rather than after loading them all
+        let mut budget = MemoryBudget::new(options);
+        // Later slides are scaled to the first one, which strict mode checks them against
+        // at their full size
+        let mut first_size = None;
+        for (index, entry) in entries.iter().enumerate() {
+            options.check_cancelled()?;
+            let at_least =
+                first_size
+                    .filter(|_| options.strict.is_none())
+                    .map(|(width, height)| match entry.rotation {
+                        Some(rotation) if rotation.swaps_dimensions() => (height, width),
+                        _ => (width, height),
+                    });
+            let (image, source) = load_source(&entry.source, options, at_least)
+                .map_err(|e| e.with_slide(index).during(Stage::Loading))?;
+            // A rotated image is the slide's own, no longer shared with the cache
+            let (image, source) = match entry.rotation {
+                Some(rotation) => image
+                    .rotate(rotation)
+                    .map(|image| (Arc::new(image), None))
+                    .map_err(|e| e.with_slide(index).during(Stage::Loading))?,
+                None => (image, source),
+            };
+            first_size.get_or_insert((image.width, image.height));
+            let slide = Slide {
+                image: SlideImage::Memory(image),
+                duration_ms: entry.duration_ms,
+                transition: entry.transition,
+                letterbox_color: entry.letterbox_color,
+                caption: entry.caption.clone(),
+                source,
+            }
+            .spill_to(spill.as_ref())
+            .map_err(|e| e.with_slide(index))?;
+            if let SlideImage::Memory(ref image) = slide.image {
+                budget.reserve(image.data.len() as u64, "slide images")?;
+            }
+            slides.push(slide);
+        }
+    }
+
+    slideshow_slides(slides, options, memory, recorder, spill)
+}
+
+/// Create a slideshow video from loaded slides, spilling resized slides to `spill` if set
+fn slideshow_slides(
+    slides: Vec<Slide>,
+    options: &EncodeOptions,
+    memory: Option<&MemoryOutput>,
+    mut recorder: StatsRecorder,
+    spill: Option<Arc<SpillFile>>,
+) -> Result<EncodeStats> {
+    // Validate options
+    options.validate()?;
+    let watermarked = with_watermark(options)?;
+    let options: &EncodeOptions = &watermarked;
 
-    for entry in entries {
-        let img = LoadedImage::from_path(&entry.path)?;
-        images.push((img, entry.duration_ms));
+    if slides.is_empty() {
+        return Err(Error::InvalidInput("No slides provided".to_string()));
+    }
+
+    if let Some(mut check) = StrictCheck::new(options) {
+        for (index, slide) in slides.iter().enumerate() {
+            let (width, height) = slide.image.size();
+            check.dimensions(index, width, height);
+            check.slide_duration(index, slide.duration_ms);
+        }
+        check.total_duration(slides.iter().map(|slide| slide.duration_ms as u64).sum());
+        check.finish()?;
     }
 
     // Get target dimensions from the first image
-    let (target_width, target_height) = (images[0].0.width, images[0].0.height);
+    let (first_width, first_height) = slides[0].image.size();
+    let (target_width, target_height) = (first_width, first_height);
+    let animation = options.container.is_animation();
 
-    // Ensure dimensions are even (required for video encoding)
-    let target_width = (target_width / 2) * 2;
-    let target_height = (target_height / 2) * 2;
+    // Ensure dimensions are even (required for video encoding, not animated images)
+    if !animation && (target_width % 2 != 0 || target_height % 2 != 0) {
+        options.warn(Warning::DimensionsRounded {
+            width: target_width,
+            height: target_height,
+        });
+    }
+    let (target_width, target_height) = if animation {
+        (target_width, target_height)
+    } else {
+        ((target_width / 2) * 2, (target_height / 2) * 2)
+    };
 
     // Resize all images to match the first one
-    let images: Vec<(LoadedImage, u32)> = images
-        .into_iter()
-        .map(|(img, duration)| (img.resize(target_width, target_height), duration))
-        .collect();
+    if target_width == 0 || target_height == 0 {
+        return Err(Error::InvalidInput(format!(
+            "Slide dimensions {}x{} are too small",
+            first_width, first_height
+        )));
+    }
+
+    // Spilled slides are read back one at a time
+    let in_memory = match spill {
+        Some(_) => slides.iter().filter(|slide| slide.pinned()).count(),
+        None => slides.len(),
+    };
+    let mut budget = MemoryBudget::new(options);
+    budget.reserve(
+        (in_memory as u64).saturating_mul(rgba_len(target_width, target_height)? as u64),
+        "slide images",
+    )?;
+
+    let slides = resize_slides(slides, target_width, target_height, options, spill.as_ref())?;
+
+    if animation {
+        return animate_slides(
+            &slides,
+            (target_width, target_height),
+            options,
+            memory,
+            recorder,
+        );
+    }
 
-    // Create encoder
+    // Encoder settings, shared by every segment
     let encoder_config = EncoderConfig {
         width: target_width,
         height: target_height,
         fps: DEFAULT_FPS,
         quality: options.quality,
+        bitrate_kbps: options.bitrate_kbps.filter(|&kbps| kbps > 0),
+        rate_control: options.rate_control,
+        keyframe_interval: options.keyframe_interval(DEFAULT_FPS),
+        color_space: options.color_space,
+        pixel_range: options.pixel_range,
+        deterministic: options.deterministic,
+        priority: options.priority,
+        threads: options.threads(),
+        av1_backend: options.av1_backend,
     };
 
-    let mut encoder = create_encoder(options.codec, encoder_config.clone())?;
-
-    // Generate all frames and collect packets
-    // We need to encode at least one frame before creating the muxer
-    // so that H.264 encoders can extract SPS/PPS
-    let mut all_packets: Vec<Packet> = Vec::new();
-    let mut total_ms: u64 = 0;
-
-    for (image, duration_ms) in &images {
-        // Calculate number of frames for this slide
-        let frame_count = (*duration_ms as u64 * DEFAULT_FPS as u64) / 1000;
-        let frame_count = frame_count.max(1); // At least one frame
-
-        for _ in 0..frame_count {
-            let frame = Frame {
-                width: image.width,
-                height: image.height,
-                data: image.data.clone(),
-                pts_ms: total_ms,
-            };
+    let total_frames: u64 = slides
+        .iter()
+        .map(|slide| slide_frame_count(slide.duration_ms))
+        .sum();
+    let faded = with_fade(
+        options,
+        total_frames.saturating_sub(1) * (1000 / DEFAULT_FPS as u64),
+    );
+    let options: &EncodeOptions = &faded;
+    // Only AV1 is split: H.264 timestamps and parameter sets are per stream, and VP9
+    // runs as one ffmpeg process
+    let segments = match options.codec {
+        Codec::Av1 => split_frames(
+            total_frames,
+            segment_count(total_frames, encoder_config.threads, options.deterministic),
+            encoder_config.keyframe_interval.unwrap_or(1),
+        ),
+        _ => split_frames(total_frames, 1, 1),
+    };
+
+    log_info!(
+        "Slideshow: {} slides at {}x{}, {} frames in {} segments",
+        slides.len(),
+        target_width,
+        target_height,
+        total_frames,
+        segments.len()
+    );
 
-            let packets = encoder.encode(&frame)?;
-            all_packets.extend(packets);
+    // The first segment's encoder provides the codec configuration for the muxer
+    let (encoder, all_packets) = {
+        let _span = trace_span!("encode", frames = total_frames);
+        let budget = Mutex::new(budget);
+        let size = (target_width, target_height);
 
-            total_ms += 1000 / DEFAULT_FPS as u64;
+        if segments.len() == 1 {
+            let mut encoder = PooledEncoder::open(options, encoder_config)?;
+            recorder.prepared();
+            let mut frames_done = 0;
+            let packets = encode_segment(
+                &mut *encoder,
+                &slides,
+                0..total_frames,
+                size,
+                options,
+                &budget,
+                || {
+                    frames_done += 1;
+                    options.report_progress(frames_done, total_frames);
+                    Ok(())
+                },
+            )?;
+            (encoder, packets)
+        } else {
+            recorder.prepared();
+            encode_segments(&segments, &slides, &encoder_config, size, options, &budget)?
         }
-    }
+    };
 
-    // Flush encoder
-    let flush_packets = encoder.flush()?;
-    all_packets.extend(flush_packets);
+    recorder.encoded(total_frames, &all_packets);
 
     // Now create muxer with SPS/PPS from encoder (available after encoding)
     let muxer_config = MuxerConfig {
@@ -89,24 +439,434 @@ pub fn slideshow(entries: &[SlideEntry], options: &EncodeOptions) -> Result<()>
         codec: options.codec,
         codec_config: encoder.codec_config(),
         pps: encoder.pps(),
+        color_space: options.color_space,
+        pixel_range: options.pixel_range,
     };
 
-    let mut muxer = create_muxer(options.container, &options.output_path, muxer_config)?;
+    let output_bytes = write_packets(options, memory, muxer_config, all_packets)?;
+    let mut stats = recorder.finish(output_bytes, DEFAULT_FPS, encoder.backend());
 
-    // Write all packets
-    for packet in all_packets {
-        muxer.write_packet(&packet)?;
+    if options.verify && memory.is_none() {
+        stats.quality = Some(verify_slides(&slides, total_frames, options)?);
     }
+    Ok(stats)
+}
 
-    // Finalize output
-    muxer.finalize()?;
+/// Write slides as an animated image, each shown for its own duration in one frame
+fn animate_slides(
+    slides: &[Slide],
+    (width, height): (u32, u32),
+    options: &EncodeOptions,
+    memory: Option<&MemoryOutput>,
+    mut recorder: StatsRecorder,
+) -> Result<EncodeStats> {
+    recorder.prepared();
+    let total = slides.len() as u64;
+    log_info!(
+        "Slideshow: {} slides at {}x{} as an animated {:?}",
+        total,
+        width,
+        height,
+        options.container
+    );
 
-    Ok(())
+    let frames = u32::try_from(slides.len())
+        .map_err(|_| Error::InvalidInput("Too many slides for an animated image".to_string()))?;
+    let mut writer = create_writer(options.container, width, height, frames)?;
+    let mut duration_ms = 0;
+    {
+        let _span = trace_span!("encode", frames = total);
+        let mut pixels = SlidePixels::new(slides);
+        let mut frame = Vec::new();
+        for (index, slide) in slides.iter().enumerate() {
+            options.check_cancelled()?;
+            frame.clear();
+            frame.extend_from_slice(pixels.get(index)?);
+            options
+                .filters
+                .apply_to((width, height), index as u64, duration_ms, &mut frame)?;
+            writer
+                .add_frame(&frame, slide.duration_ms)
+                .map_err(|e| e.with_slide(index).during(Stage::Encoding))?;
+            duration_ms += slide.duration_ms as u64;
+            options.report_progress(index as u64 + 1, total);
+        }
+    }
+    recorder.encoded_animation(total);
+
+    let output_bytes = write_animation(options, memory, writer)?;
+    Ok(recorder.finish_with_duration(output_bytes, duration_ms, EncoderBackend::Image))
+}
+
+/// Compare the video written to `options.output_path` with the slides it shows
+fn verify_slides(
+    slides: &[Slide],
+    total_frames: u64,
+    options: &EncodeOptions,
+) -> Result<QualityReport> {
+    let mut frames = slide_frames(slides);
+    let mut pixels = SlidePixels::new(slides);
+    let mut composed = Vec::new();
+
+    verify(
+        &options.output_path,
+        total_frames,
+        options.ffmpeg_path.as_deref(),
+        |frame_idx, data| {
+            let (index, offset) = frames
+                .next()
+                .ok_or_else(|| Error::Internal("Ran out of slide frames".to_string()))?;
+            let (width, height) = slides[index].image.size();
+            let slide = if pixels.transition(index, offset, (width, height), &mut composed)? {
+                &composed
+            } else {
+                pixels.get(index)?
+            };
+            if slide.len() != data.len() {
+                return Err(Error::Decode(
+                    "Output dimensions differ from the slides".to_string(),
+                ));
+            }
+            data.copy_from_slice(slide);
+            // The output shows the slides as filtered
+            let pts_ms = frame_idx * (1000 / DEFAULT_FPS as u64);
+            options
+                .filters
+                .apply_to((width, height), frame_idx, pts_ms, data)
+        },
+    )
+}
+
+/// Encoder of a segment and the packets it produced
+type EncodedSegment = (PooledEncoder, Vec<Packet>);
+
+/// Encode each of `segments` on its own thread with its own encoder and join the packets
+///
+/// Every segment starts with a keyframe, so its packets follow the previous segment's
+/// once their timestamps are offset by the segment's first frame. Progress is reported
+/// on the calling thread, and the other segments stop at the first failure.
+fn encode_segments(
+    segments: &[Range<u64>],
+    slides: &[Slide],
+    encoder_config: &EncoderConfig,
+    size: (u32, u32),
+    options: &EncodeOptions,
+    budget: &Mutex<MemoryBudget>,
+) -> Result<EncodedSegment> {
+    let total_frames = segments.last().map_or(0, |range| range.end);
+    let failed = AtomicBool::new(false);
+
+    let results: Vec<Result<EncodedSegment>> = thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+
+        let workers: Vec<_> = segments
+            .iter()
+            .map(|range| {
+                let sender = sender.clone();
+                let failed = &failed;
+                scope.spawn(move || {
+                    lower_current_thread(options.priority);
+                    let result = PooledEncoder::open(options, encoder_config.clone()).and_then(
+                        |mut encoder| {
+                            let packets = encode_segment(
+                                &mut *encoder,
+                                slides,
+                                range.clone(),
+                                size,
+                                options,
+                                budget,
+                                || {
+                                    if failed.load(Ordering::Relaxed) {
+                                        return Err(Error::Cancelled);
+                                    }
+                                    // The receiver outlives every worker
+                                    let _ = sender.send(());
+                                    Ok(())
+                                },
+                            )?;
+                            Ok((encoder, packets))
+                        },
+                    );
+                    if result.is_err() {
+                        failed.store(true, Ordering::Relaxed);
+                    }
+                    result
+                })
+            })
+            .collect();
+
+        // Ends once every worker has dropped its sender
+        drop(sender);
+        for (frames_done, ()) in (1..).zip(receiver) {
+            options.report_progress(frames_done, total_frames);
+        }
+
+        workers
+            .into_iter()
+            .map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    });
+
+    // Segments stopped by another segment's failure report it as cancellation
+    let mut encoded = Vec::with_capacity(results.len());
+    let mut cancelled = false;
+    for result in results {
+        match result {
+            Ok(segment) => encoded.push(segment),
+            Err(Error::Cancelled) => cancelled = true,
+            Err(e) => return Err(e),
+        }
+    }
+    if cancelled {
+        return Err(Error::Cancelled);
+    }
+
+    let mut all_packets = Vec::new();
+    for (range, (_, packets)) in segments.iter().zip(&mut encoded) {
+        let offset = range.start as i64;
+        all_packets.extend(packets.drain(..).map(|packet| Packet {
+            pts: packet.pts + offset,
+            dts: packet.dts + offset,
+            ..packet
+        }));
+    }
+
+    let (encoder, _) = encoded.swap_remove(0);
+    Ok((encoder, all_packets))
+}
+
+/// Encode frames `range` of the slideshow, calling `frame_done` after each frame
+///
+/// Timestamps of the returned packets count from the start of the range.
+fn encode_segment(
+    encoder: &mut dyn Encoder,
+    slides: &[Slide],
+    range: Range<u64>,
+    (width, height): (u32, u32),
+    options: &EncodeOptions,
+    budget: &Mutex<MemoryBudget>,
+    mut frame_done: impl FnMut() -> Result<()>,
+) -> Result<Vec<Packet>> {
+    let reserve = |packets: &[Packet]| {
+        // Packets are only added under the lock, a panic cannot leave the count torn
+        budget
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .reserve_packets(packets)
+    };
+
+    // Slide shown at each frame; every frame of a slide borrows the slide's buffer
+    let shown = slide_frames(slides);
+
+    let mut pixels = SlidePixels::new(slides);
+    // Transitions and filtered frames are copies, slides are borrowed as they are otherwise
+    let mut copied = Vec::new();
+    let mut all_packets = Vec::new();
+    for (frame_idx, (index, offset)) in range.clone().zip(shown.skip(range.start as usize)) {
+        options.check_cancelled()?;
+
+        let pts_ms = frame_idx * (1000 / DEFAULT_FPS as u64);
+        let mut is_copy = pixels
+            .transition(index, offset, (width, height), &mut copied)
+            .map_err(|e| e.with_slide(index).during(Stage::Rendering))?;
+        if !options.filters.is_empty() {
+            if !is_copy {
+                copied.clear();
+                copied.extend_from_slice(
+                    pixels
+                        .get(index)
+                        .map_err(|e| e.with_slide(index).during(Stage::Rendering))?,
+                );
+                is_copy = true;
+            }
+            options
+                .filters
+                .apply_to((width, height), frame_idx, pts_ms, &mut copied)
+                .map_err(|e| {
+                    e.with_frame(frame_idx)
+                        .with_slide(index)
+                        .during(Stage::Rendering)
+                })?;
+        }
+        let data = if is_copy {
+            &copied
+        } else {
+            pixels
+                .get(index)
+                .map_err(|e| e.with_slide(index).during(Stage::Rendering))?
+        };
+
+        let frame = Frame {
+            width,
+            height,
+            data,
+            pts_ms,
+        };
+
+        let packets = encoder.encode(&frame).map_err(|e| {
+            e.with_frame(frame_idx)
+                .with_slide(index)
+                .during(Stage::Encoding)
+        })?;
+        reserve(&packets)?;
+        all_packets.extend(packets);
+
+        frame_done()?;
+    }
+
+    // Flush encoder
+    let flush_packets = encoder.flush()?;
+    reserve(&flush_packets)?;
+    all_packets.extend(flush_packets);
+
+    Ok(all_packets)
+}
+
+/// Number of segments to encode `total_frames` in parallel, one per thread (0 for one
+/// per available core)
+///
+/// Segments start with keyframes, so a `deterministic` count does not depend on the
+/// machine.
+fn segment_count(total_frames: u64, threads: usize, deterministic: bool) -> usize {
+    let cores = if deterministic {
+        DETERMINISTIC_SEGMENTS
+    } else {
+        match threads {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            threads => threads,
+        }
+    };
+    cores
+        .min((total_frames / MIN_SEGMENT_FRAMES) as usize)
+        .max(1)
+}
+
+/// Split `total_frames` into at most `segments` consecutive ranges of nearly equal length,
+/// starting at multiples of `align` so their keyframes keep a set interval
+fn split_frames(total_frames: u64, segments: usize, align: u32) -> Vec<Range<u64>> {
+    let (segments, align) = (segments as u64, align.max(1) as u64);
+    let mut ranges: Vec<Range<u64>> = Vec::new();
+    for i in 1..=segments {
+        let start = ranges.last().map_or(0, |range| range.end);
+        let end = match i {
+            i if i == segments => total_frames,
+            i => total_frames * i / segments / align * align,
+        };
+        if end > start || i == segments {
+            ranges.push(start..end.max(start));
+        }
+    }
+    ranges
+}
+
+/// Resize slides to the output dimensions, warning about changed slides
+///
+/// Slides loaded through `options.image_cache` reuse resized images from the cache.
+fn resize_slides(
+    slides: Vec<Slide>,
+    width: u32,
+    height: u32,
+    options: &EncodeOptions,
+    spill: Option<&Arc<SpillFile>>,
+) -> Result<Vec<Slide>> {
+    let _span = trace_span!("resize", width, height);
+
+    slides
+        .into_iter()
+        .enumerate()
+        .map(|(index, slide)| {
+            let Slide {
+                image,
+                duration_ms,
+                transition,
+                letterbox_color,
+                caption,
+                source,
+            } = slide;
+            // The first slide only loses its odd row or column
+            let (image_width, image_height) = image.size();
+            if index > 0 && (image_width, image_height) != (width, height) {
+                options.warn(Warning::ImageResized {
+                    index,
+                    width: image_width,
+                    height: image_height,
+                });
+            }
+            // Animated images keep exact durations
+            if !options.container.is_animation() && (duration_ms as u64 * DEFAULT_FPS as u64) < 1000
+            {
+                options.warn(Warning::DurationRounded { index, duration_ms });
+            }
+            let letterbox = letterbox_color.unwrap_or(options.letterbox_color);
+            let resized = match (&options.image_cache, &source, &image) {
+                (Some(cache), Some(source), SlideImage::Memory(image)) => cache
+                    .resize(
+                        source,
+                        image,
+                        (width, height),
+                        options.scale_mode,
+                        letterbox,
+                    )
+                    .map(SlideImage::Memory),
+                _ if (image_width, image_height) == (width, height) => Ok(image),
+                _ => image
+                    .load()
+                    .and_then(|image| image.scale(width, height, options.scale_mode, letterbox))
+                    .map(|image| SlideImage::Memory(Arc::new(image))),
+            }
+            .map_err(|e| e.with_slide(index).during(Stage::Rendering))?;
+            // A captioned image is the slide's own, no longer shared with the cache
+            let (resized, source) = match &caption {
+                Some(caption) => {
+                    let mut image = resized.load().map(Arc::unwrap_or_clone)?;
+                    caption
+                        .draw(&mut image)
+                        .map_err(|e| e.with_slide(index).during(Stage::Rendering))?;
+                    (SlideImage::Memory(Arc::new(image)), None)
+                }
+                None => (resized, source),
+            };
+            Slide {
+                image: resized,
+                duration_ms,
+                transition,
+                letterbox_color,
+                caption,
+                source,
+            }
+            .spill_to(spill)
+            .map_err(|e| e.with_slide(index))
+        })
+        .collect()
+}
+
+/// Calculate number of frames for a slide (at least one frame)
+fn slide_frame_count(duration_ms: u32) -> u64 {
+    ((duration_ms as u64 * DEFAULT_FPS as u64) / 1000).max(1)
+}
+
+/// Number of frames of a slide taken by its transition, leaving the last frame alone
+fn transition_frame_count(transition: &Transition, duration_ms: u32) -> u64 {
+    let frames = transition.duration_ms as u64 * DEFAULT_FPS as u64 / 1000;
+    frames.min(slide_frame_count(duration_ms) - 1)
+}
+
+/// Slide shown at each frame and the frame's offset from the start of the slide
+fn slide_frames(slides: &[Slide]) -> impl Iterator<Item = (usize, u64)> + '_ {
+    slides.iter().enumerate().flat_map(|(index, slide)| {
+        (0..slide_frame_count(slide.duration_ms)).map(move |offset| (index, offset))
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "av1")]
+    use crate::{Av1Backend, ColorSpace, PixelRange, Priority, RateControl};
 
     #[test]
     fn test_slideshow_empty_entries() {
@@ -116,9 +876,73 @@ mod tests {
             codec: crate::Codec::Av1,
             quality: 50,
             ffmpeg_path: None,
+            ..Default::default()
         };
 
         let result = slideshow(&[], &options);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_split_frames() {
+        assert_eq!(split_frames(10, 1, 1), vec![0..10]);
+        assert_eq!(split_frames(10, 3, 1), vec![0..3, 3..6, 6..10]);
+        assert_eq!(split_frames(100, 3, 30), vec![0..30, 30..60, 60..100]);
+        assert_eq!(split_frames(100, 4, 60), vec![0..60, 60..100]);
+        assert_eq!(split_frames(0, 2, 60), vec![0..0]);
+        assert_eq!(segment_count(MIN_SEGMENT_FRAMES - 1, 0, false), 1);
+        assert_eq!(segment_count(MIN_SEGMENT_FRAMES * 100, 3, false), 3);
+        assert_eq!(
+            segment_count(MIN_SEGMENT_FRAMES * 100, 3, true),
+            DETERMINISTIC_SEGMENTS
+        );
+    }
+
+    #[cfg(feature = "av1")]
+    #[test]
+    fn test_encode_segments_joins_timestamps() {
+        let slide = |value: u8| Slide {
+            image: SlideImage::Memory(Arc::new(LoadedImage {
+                width: 16,
+                height: 16,
+                data: vec![value; 16 * 16 * 4],
+            })),
+            duration_ms: 200,
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            source: None,
+        };
+        let slides = vec![slide(0), slide(255)];
+        let config = EncoderConfig {
+            width: 16,
+            height: 16,
+            fps: DEFAULT_FPS,
+            quality: 50,
+            bitrate_kbps: None,
+            rate_control: RateControl::Auto,
+            keyframe_interval: None,
+            color_space: ColorSpace::Bt601,
+            pixel_range: PixelRange::Auto,
+            deterministic: false,
+            priority: Priority::Normal,
+            threads: 0,
+            av1_backend: Av1Backend::Rav1e,
+        };
+        let options = EncodeOptions {
+            codec: Codec::Av1,
+            ..Default::default()
+        };
+        let budget = Mutex::new(MemoryBudget::new(&options));
+
+        let segments = split_frames(12, 2, 1);
+        let (_, packets) =
+            encode_segments(&segments, &slides, &config, (16, 16), &options, &budget).unwrap();
+
+        let mut pts: Vec<i64> = packets.iter().map(|p| p.pts).collect();
+        pts.sort_unstable();
+        assert_eq!(pts, (0..12).collect::<Vec<_>>());
+        // Each segment starts with its own keyframe
+        assert!(packets.iter().any(|p| p.pts == 6 && p.is_keyframe));
+    }
 }
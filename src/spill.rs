@@ -0,0 +1,122 @@
+//! Temporary files holding intermediate images outside of memory
+
+use crate::image_loader::LoadedImage;
+use crate::logging::log_debug;
+use crate::Result;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Distinguishes spill files created by one process
+static SPILL_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Temporary file images are appended to and read back from, deleted when dropped
+pub(crate) struct SpillFile {
+    path: PathBuf,
+    /// The file and its length, locked together so appends and reads do not interleave
+    file: Mutex<(File, u64)>,
+}
+
+impl SpillFile {
+    /// Create an empty spill file in `dir`
+    pub fn create(dir: &Path) -> Result<Arc<Self>> {
+        let name = format!(
+            "minmpeg-spill-{}-{}.tmp",
+            process::id(),
+            SPILL_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = dir.join(name);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        log_debug!("Spilling images to {}", path.display());
+
+        Ok(Arc::new(Self {
+            path,
+            file: Mutex::new((file, 0)),
+        }))
+    }
+
+    /// Append the pixels of `image` to the file
+    pub fn spill(self: &Arc<Self>, image: &LoadedImage) -> Result<SpilledImage> {
+        let mut guard = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let (file, len) = &mut *guard;
+        let offset = *len;
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(&image.data)?;
+        *len += image.data.len() as u64;
+
+        Ok(SpilledImage {
+            file: Arc::clone(self),
+            offset,
+            width: image.width,
+            height: image.height,
+        })
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// RGBA image stored in a spill file
+#[derive(Clone)]
+pub(crate) struct SpilledImage {
+    file: Arc<SpillFile>,
+    offset: u64,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl SpilledImage {
+    /// Read the pixels into `buffer`, replacing its contents
+    pub fn read_into(&self, buffer: &mut Vec<u8>) -> Result<()> {
+        buffer.resize(self.width as usize * self.height as usize * 4, 0);
+        let mut guard = self.file.file.lock().unwrap_or_else(|e| e.into_inner());
+        let (file, _) = &mut *guard;
+        file.seek(SeekFrom::Start(self.offset))?;
+        file.read_exact(buffer)?;
+        Ok(())
+    }
+
+    /// Read the image back into memory
+    pub fn load(&self) -> Result<LoadedImage> {
+        let mut data = Vec::new();
+        self.read_into(&mut data)?;
+        LoadedImage::from_rgba(self.width, self.height, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spill_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let spill = SpillFile::create(dir.path()).unwrap();
+
+        let first = LoadedImage::from_rgba(2, 1, vec![1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        let second = LoadedImage::from_rgba(1, 1, vec![9, 10, 11, 12]).unwrap();
+        let spilled = [spill.spill(&first).unwrap(), spill.spill(&second).unwrap()];
+
+        let mut buffer = Vec::new();
+        spilled[1].read_into(&mut buffer).unwrap();
+        assert_eq!(buffer, second.data);
+        let loaded = spilled[0].load().unwrap();
+        assert_eq!((loaded.width, loaded.height), (2, 1));
+        assert_eq!(loaded.data, first.data);
+
+        // The file goes away with its last image
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        drop((spill, spilled));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,140 @@
+//! Statistics of encoding operations
+
+use crate::encoder::{EncoderBackend, Packet};
+use crate::QualityReport;
+use std::time::{Duration, Instant};
+
+/// Statistics of a finished encoding operation
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EncodeStats {
+    /// Number of frames encoded
+    pub frames: u64,
+    /// Number of keyframes in the output
+    pub keyframes: u64,
+    /// Size of the output in bytes
+    pub output_bytes: u64,
+    /// Duration of the output video in milliseconds
+    pub duration_ms: u64,
+    /// Average bitrate in bits per second
+    pub bitrate: u64,
+    /// Encoder implementation used
+    pub backend: EncoderBackend,
+    /// Time spent loading images or opening input videos
+    pub prepare_time: Duration,
+    /// Time spent producing and encoding frames
+    pub encode_time: Duration,
+    /// Time spent writing the container
+    pub mux_time: Duration,
+    /// Wall-clock time of the whole operation
+    pub total_time: Duration,
+    /// Comparison of the output with its source, when verified (see
+    /// [`EncodeOptions::verify`](crate::EncodeOptions::verify))
+    pub quality: Option<QualityReport>,
+}
+
+/// Stage timings and counts gathered while an operation runs
+pub(crate) struct StatsRecorder {
+    start: Instant,
+    stage_start: Instant,
+    prepare_time: Duration,
+    encode_time: Duration,
+    frames: u64,
+    keyframes: u64,
+}
+
+impl StatsRecorder {
+    /// Start timing the preparation stage
+    pub fn start() -> Self {
+        let now = Instant::now();
+        Self {
+            start: now,
+            stage_start: now,
+            prepare_time: Duration::ZERO,
+            encode_time: Duration::ZERO,
+            frames: 0,
+            keyframes: 0,
+        }
+    }
+
+    /// End the preparation stage
+    pub fn prepared(&mut self) {
+        self.prepare_time = self.stage_start.elapsed();
+        self.stage_start = Instant::now();
+    }
+
+    /// End the encoding stage, counting the frames and keyframes produced
+    pub fn encoded(&mut self, frames: u64, packets: &[Packet]) {
+        self.encode_time = self.stage_start.elapsed();
+        self.stage_start = Instant::now();
+        self.frames = frames;
+        self.keyframes = packets.iter().filter(|p| p.is_keyframe).count() as u64;
+    }
+
+    /// End the encoding stage of an animated image, whose frames all stand alone
+    pub fn encoded_animation(&mut self, frames: u64) {
+        self.encode_time = self.stage_start.elapsed();
+        self.stage_start = Instant::now();
+        self.frames = frames;
+        self.keyframes = frames;
+    }
+
+    /// End the muxing stage
+    pub fn finish(self, output_bytes: u64, fps: u32, backend: EncoderBackend) -> EncodeStats {
+        let duration_ms = self.frames * 1000 / fps.max(1) as u64;
+        self.finish_with_duration(output_bytes, duration_ms, backend)
+    }
+
+    /// End the muxing stage of an output lasting `duration_ms`
+    pub fn finish_with_duration(
+        self,
+        output_bytes: u64,
+        duration_ms: u64,
+        backend: EncoderBackend,
+    ) -> EncodeStats {
+        let bitrate = match duration_ms {
+            0 => 0,
+            ms => output_bytes * 8 * 1000 / ms,
+        };
+
+        EncodeStats {
+            frames: self.frames,
+            keyframes: self.keyframes,
+            output_bytes,
+            duration_ms,
+            bitrate,
+            backend,
+            prepare_time: self.prepare_time,
+            encode_time: self.encode_time,
+            mux_time: self.stage_start.elapsed(),
+            total_time: self.start.elapsed(),
+            quality: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_recorder() {
+        let packet = |is_keyframe| Packet {
+            data: vec![0; 10],
+            pts: 0,
+            dts: 0,
+            is_keyframe,
+        };
+
+        let mut recorder = StatsRecorder::start();
+        recorder.prepared();
+        recorder.encoded(60, &[packet(true), packet(false), packet(true)]);
+        let stats = recorder.finish(250_000, 30, EncoderBackend::Rav1e);
+
+        assert_eq!(stats.frames, 60);
+        assert_eq!(stats.keyframes, 2);
+        assert_eq!(stats.duration_ms, 2000);
+        assert_eq!(stats.bitrate, 1_000_000);
+        assert!(stats.total_time >= stats.encode_time);
+    }
+}
@@ -0,0 +1,60 @@
+//! Still image extraction from videos
+
+use crate::decoder::{VideoDecoder, DEFAULT_FPS};
+use crate::image_loader::LoadedImage;
+use crate::{Error, Result, Stage};
+use std::path::Path;
+
+/// Decode the frame shown at `time_ms`
+///
+/// Times past the end of the video return the last frame.
+pub fn thumbnail<P: AsRef<Path>>(
+    input_path: P,
+    time_ms: u64,
+    ffmpeg_path: Option<&str>,
+) -> Result<LoadedImage> {
+    let path = input_path.as_ref();
+    let mut decoder = VideoDecoder::new(path, ffmpeg_path)
+        .map_err(|e| e.with_path(path).during(Stage::Decoding))?;
+
+    // Seek at most to the start of the last frame
+    let last_frame_ms = decoder
+        .duration_ms
+        .saturating_sub(1000 / DEFAULT_FPS as u64);
+    let time_ms = time_ms.min(last_frame_ms);
+    decoder
+        .start_decode_range(path, ffmpeg_path, time_ms, None)
+        .map_err(|e| e.with_path(path).during(Stage::Decoding))?;
+
+    let frame = decoder
+        .read_frame()
+        .map_err(|e| e.with_path(path).during(Stage::Decoding))?
+        .ok_or_else(|| {
+            Error::Decode(format!("No frame at {} ms", time_ms))
+                .with_path(path)
+                .during(Stage::Decoding)
+        })?;
+
+    LoadedImage::from_rgba(frame.width, frame.height, frame.data.to_vec())
+}
+
+/// Decode the frame shown at `time_ms` and save it as an image
+///
+/// The image format is chosen from the extension of `output_path` (e.g. `.png`, `.jpg`).
+pub fn thumbnail_to_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    input_path: P,
+    time_ms: u64,
+    output_path: Q,
+    ffmpeg_path: Option<&str>,
+) -> Result<()> {
+    let image = thumbnail(input_path, time_ms, ffmpeg_path)?;
+
+    let rgba = image::RgbaImage::from_raw(image.width, image.height, image.data)
+        .ok_or_else(|| Error::Internal("Frame size mismatch".to_string()))?;
+
+    // JPEG has no alpha channel
+    image::DynamicImage::ImageRgba8(rgba)
+        .to_rgb8()
+        .save(output_path)?;
+    Ok(())
+}
@@ -0,0 +1,372 @@
+//! Videos composed from tracks of image, video, color and text clips
+
+use crate::decoder::{VideoDecoder, DEFAULT_FPS};
+use crate::frames::{encode_frames_to, FrameFormat};
+use crate::image_loader::{rgba_len, LoadedImage};
+use crate::logging::{log_info, trace_span};
+use crate::muxer::MemoryOutput;
+use crate::slideshow::load_source;
+use crate::stats::EncodeStats;
+use crate::{Caption, Color, EncodeOptions, Error, Result, SlideSource, Stage};
+
+/// Video made of tracks drawn one over another on a background color
+///
+/// Each frame shows the clips playing at its time: tracks are drawn in order, so later
+/// tracks cover earlier ones, and so are clips within a track. The video lasts until the
+/// end of its last clip, at 30 frames per second.
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    /// Frame width in pixels (must be even)
+    pub width: u32,
+    /// Frame height in pixels (must be even)
+    pub height: u32,
+    /// Color of frames where no clip is playing
+    pub background: Color,
+    pub tracks: Vec<Track>,
+}
+
+impl Timeline {
+    /// Create a timeline with no tracks on a black background
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            background: Color { r: 0, g: 0, b: 0 },
+            tracks: Vec::new(),
+        }
+    }
+
+    /// Append `track`, drawn over the tracks already in the timeline
+    pub fn with_track(mut self, track: Track) -> Self {
+        self.tracks.push(track);
+        self
+    }
+
+    /// Length of the timeline, up to the end of its last clip
+    pub fn duration_ms(&self) -> u64 {
+        self.clips().map(Clip::end_ms).max().unwrap_or(0)
+    }
+
+    fn clips(&self) -> impl Iterator<Item = &Clip> {
+        self.tracks.iter().flat_map(|track| &track.clips)
+    }
+}
+
+/// Clips of a timeline drawn at the same depth
+#[derive(Debug, Clone, Default)]
+pub struct Track {
+    pub clips: Vec<Clip>,
+}
+
+impl Track {
+    /// Create a track with no clips
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `clip`, drawn over the clips already in the track where they overlap
+    pub fn with(mut self, clip: Clip) -> Self {
+        self.clips.push(clip);
+        self
+    }
+}
+
+/// Content of a timeline shown from `start_ms` for `duration_ms`
+#[derive(Debug, Clone)]
+pub struct Clip {
+    pub content: ClipContent,
+    /// Time the clip appears at, from the start of the timeline
+    pub start_ms: u64,
+    /// Time the clip is shown for
+    pub duration_ms: u64,
+}
+
+impl Clip {
+    pub fn new(content: ClipContent, start_ms: u64, duration_ms: u64) -> Self {
+        Self {
+            content,
+            start_ms,
+            duration_ms,
+        }
+    }
+
+    fn end_ms(&self) -> u64 {
+        self.start_ms.saturating_add(self.duration_ms)
+    }
+
+    fn plays_at(&self, pts_ms: u64) -> bool {
+        (self.start_ms..self.end_ms()).contains(&pts_ms)
+    }
+}
+
+/// What a clip shows
+///
+/// Images and videos are scaled to the timeline with [`EncodeOptions::scale_mode`] and
+/// blended by their alpha channel. Text is drawn over the tracks below it.
+#[derive(Debug, Clone)]
+pub enum ClipContent {
+    /// Still image, from a file, image bytes or pixels
+    Image(SlideSource),
+    /// Video file played from its start, holding its last frame if it is shorter than
+    /// the clip
+    Video(String),
+    /// Solid color covering the frame
+    Color(Color),
+    /// Caption on a transparent background, which needs the `text` feature
+    Text(Caption),
+}
+
+/// Compose `timeline` into a video written to `options.output_path`
+pub fn compose(timeline: &Timeline, options: &EncodeOptions) -> Result<EncodeStats> {
+    compose_to(timeline, options, None)
+}
+
+/// Compose `timeline` into a video in memory and return the encoded bytes
+///
+/// `options.output_path` is ignored.
+pub fn compose_to_memory(timeline: &Timeline, options: &EncodeOptions) -> Result<Vec<u8>> {
+    let output = MemoryOutput::new();
+    compose_to(timeline, options, Some(&output))?;
+    Ok(output.take())
+}
+
+/// Compose a timeline, writing to `memory` if set or to `options.output_path` otherwise
+fn compose_to(
+    timeline: &Timeline,
+    options: &EncodeOptions,
+    memory: Option<&MemoryOutput>,
+) -> Result<EncodeStats> {
+    let _span = trace_span!("compose", tracks = timeline.tracks.len());
+
+    options.validate_video()?;
+    let format = FrameFormat {
+        width: timeline.width,
+        height: timeline.height,
+        fps: DEFAULT_FPS,
+        frame_count: (timeline.duration_ms() * DEFAULT_FPS as u64 / 1000).max(1),
+    };
+    if timeline.clips().next().is_none() {
+        return Err(Error::InvalidInput("Timeline has no clips".to_string()));
+    }
+    if let Some(index) = timeline.clips().position(|clip| clip.duration_ms == 0) {
+        return Err(Error::InvalidInput(format!(
+            "Clip {} of the timeline has no duration",
+            index
+        )));
+    }
+    format.validate()?;
+
+    log_info!(
+        "Composing {} clips on {} tracks at {}x{}, {} frames",
+        timeline.clips().count(),
+        timeline.tracks.len(),
+        timeline.width,
+        timeline.height,
+        format.frame_count
+    );
+
+    let mut layers = {
+        let _span = trace_span!("load");
+        timeline
+            .clips()
+            .map(|clip| {
+                options.check_cancelled()?;
+                Layer::prepare(clip, timeline, options)
+            })
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    let size = (timeline.width, timeline.height);
+    let background = [
+        timeline.background.r,
+        timeline.background.g,
+        timeline.background.b,
+        255,
+    ];
+    encode_frames_to(format, options, memory, |frame_idx, data| {
+        let pts_ms = frame_idx * 1000 / DEFAULT_FPS as u64;
+        for pixel in data.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&background);
+        }
+        layers
+            .iter_mut()
+            .try_for_each(|layer| layer.draw(pts_ms, size, options, data))
+    })
+}
+
+/// Clip ready to be drawn
+struct Layer<'a> {
+    clip: &'a Clip,
+    content: LayerContent<'a>,
+}
+
+enum LayerContent<'a> {
+    /// Image or text scaled to the frame, its pixels premultiplied by their alpha
+    Still(LoadedImage),
+    Fill(Color),
+    /// Video opened when the clip is prepared, decoded from the first frame of the clip
+    /// and closed after its last; the decoder is boxed as it dwarfs the other variants
+    Video {
+        path: &'a str,
+        decoder: Option<Box<VideoDecoder>>,
+        started: bool,
+    },
+}
+
+impl<'a> Layer<'a> {
+    /// Load, scale or open the content of `clip`
+    fn prepare(clip: &'a Clip, timeline: &Timeline, options: &EncodeOptions) -> Result<Self> {
+        let (width, height) = (timeline.width, timeline.height);
+        let content = match &clip.content {
+            ClipContent::Image(source) => {
+                let (image, _) = load_source(source, options, Some((width, height)))
+                    .map_err(|e| e.during(Stage::Loading))?;
+                let mut image = image
+                    .scale(width, height, options.scale_mode, options.letterbox_color)
+                    .map_err(|e| e.during(Stage::Rendering))?;
+                premultiply(&mut image.data);
+                LayerContent::Still(image)
+            }
+            ClipContent::Video(path) => LayerContent::Video {
+                path,
+                decoder: Some(Box::new(VideoDecoder::open(path, options)?)),
+                started: false,
+            },
+            ClipContent::Color(color) => LayerContent::Fill(*color),
+            ClipContent::Text(caption) => {
+                // Blending onto transparent black leaves the pixels premultiplied
+                let mut image =
+                    LoadedImage::from_rgba(width, height, vec![0; rgba_len(width, height)?])?;
+                caption
+                    .draw(&mut image)
+                    .map_err(|e| e.during(Stage::Rendering))?;
+                LayerContent::Still(image)
+            }
+        };
+        Ok(Self { clip, content })
+    }
+
+    /// Draw the clip over `frame` if it plays at `pts_ms`
+    fn draw(
+        &mut self,
+        pts_ms: u64,
+        (width, height): (u32, u32),
+        options: &EncodeOptions,
+        frame: &mut [u8],
+    ) -> Result<()> {
+        if !self.clip.plays_at(pts_ms) {
+            if pts_ms >= self.clip.end_ms() {
+                if let LayerContent::Video { decoder, .. } = &mut self.content {
+                    *decoder = None;
+                }
+            }
+            return Ok(());
+        }
+
+        match &mut self.content {
+            LayerContent::Still(image) => blend(&image.data, frame),
+            LayerContent::Fill(color) => {
+                for pixel in frame.chunks_exact_mut(4) {
+                    pixel.copy_from_slice(&[color.r, color.g, color.b, 255]);
+                }
+            }
+            LayerContent::Video {
+                path,
+                decoder,
+                started,
+            } => {
+                let path: &str = path;
+                let Some(decoder) = decoder else {
+                    return Ok(());
+                };
+                if !*started {
+                    decoder
+                        .start_decode(path, options.ffmpeg_path.as_deref())
+                        .map_err(|e| e.with_path(path).during(Stage::Decoding))?;
+                    *started = true;
+                }
+                let decoded = decoder
+                    .read_frame()
+                    .map_err(|e| e.with_path(path).during(Stage::Decoding))?;
+                let Some(decoded) = decoded else {
+                    return Ok(());
+                };
+                if (decoded.width, decoded.height) == (width, height) {
+                    frame.copy_from_slice(decoded.data);
+                } else {
+                    let scaled = LoadedImage::from_rgba(
+                        decoded.width,
+                        decoded.height,
+                        decoded.data.to_vec(),
+                    )?
+                    .scale(
+                        width,
+                        height,
+                        options.scale_mode,
+                        options.letterbox_color,
+                    )?;
+                    frame.copy_from_slice(&scaled.data);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Multiply the color of RGBA `data` by its alpha
+fn premultiply(data: &mut [u8]) {
+    for pixel in data.chunks_exact_mut(4) {
+        let a = pixel[3] as u32;
+        for channel in &mut pixel[..3] {
+            *channel = ((*channel as u32 * a + 127) / 255) as u8;
+        }
+    }
+}
+
+/// Draw premultiplied RGBA `src` over the opaque `frame` of the same size
+fn blend(src: &[u8], frame: &mut [u8]) {
+    for (dst, src) in frame.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+        let keep = 255 - src[3] as u32;
+        if keep == 255 {
+            continue;
+        }
+        for channel in 0..3 {
+            dst[channel] =
+                (src[channel] as u32 + (dst[channel] as u32 * keep + 127) / 255).min(255) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timeline_duration() {
+        let timeline = Timeline::new(160, 120)
+            .with_track(
+                Track::new()
+                    .with(Clip::new(ClipContent::Color(Color::default()), 0, 1000))
+                    .with(Clip::new(ClipContent::Color(Color::default()), 1000, 500)),
+            )
+            .with_track(Track::new().with(Clip::new(
+                ClipContent::Color(Color::default()),
+                200,
+                2000,
+            )));
+        assert_eq!(timeline.duration_ms(), 2200);
+        assert!(timeline.tracks[0].clips[1].plays_at(1000));
+        assert!(!timeline.tracks[0].clips[0].plays_at(1000));
+    }
+
+    #[test]
+    fn test_blend_premultiplied() {
+        let mut src = vec![255, 0, 0, 128, 0, 0, 0, 0, 10, 20, 30, 255];
+        premultiply(&mut src);
+        assert_eq!(src, [128, 0, 0, 128, 0, 0, 0, 0, 10, 20, 30, 255]);
+
+        let mut frame = [0, 0, 255, 255].repeat(3);
+        blend(&src, &mut frame);
+        assert_eq!(frame, [128, 0, 127, 255, 0, 0, 255, 255, 10, 20, 30, 255]);
+    }
+}
@@ -0,0 +1,196 @@
+//! Re-encoding of existing videos: transcode, trim and concat
+
+use crate::decoder::{VideoDecoder, DEFAULT_FPS};
+use crate::frames::{encode_frames_to, FrameFormat};
+use crate::image_loader::LoadedImage;
+use crate::logging::log_info;
+use crate::stats::EncodeStats;
+use crate::validate::StrictCheck;
+use crate::{EncodeOptions, Error, Result, Stage, Warning};
+use std::path::Path;
+
+/// Background for letterboxing inputs whose aspect ratio differs from the output
+const LETTERBOX_COLOR: [u8; 4] = [0, 0, 0, 255];
+
+/// Re-encode a video with the given container, codec and quality
+///
+/// The output has the input's dimensions (rounded down to even) at 30 fps.
+pub fn transcode<P: AsRef<Path>>(input_path: P, options: &EncodeOptions) -> Result<EncodeStats> {
+    trim(input_path, 0, None, options)
+}
+
+/// Re-encode the part of a video between `start_ms` and `end_ms`
+///
+/// `end_ms` of `None` keeps the rest of the video.
+pub fn trim<P: AsRef<Path>>(
+    input_path: P,
+    start_ms: u64,
+    end_ms: Option<u64>,
+    options: &EncodeOptions,
+) -> Result<EncodeStats> {
+    options.validate_video()?;
+
+    let path = input_path.as_ref();
+    let ffmpeg_path = options.ffmpeg_path.as_deref();
+    let mut decoder = VideoDecoder::open(path, options)?;
+
+    decoder.trim(start_ms, end_ms)?;
+    let end_ms = start_ms + decoder.duration_ms;
+
+    if let Some(mut check) = StrictCheck::new(options) {
+        check.dimensions(0, decoder.width, decoder.height);
+        check.total_duration(decoder.duration_ms);
+        check.finish()?;
+    }
+
+    decoder
+        .start_decode(path, ffmpeg_path)
+        .map_err(|e| e.with_path(path).during(Stage::Decoding))?;
+
+    let total_frames = ms_to_frames(decoder.duration_ms);
+    let (width, height) = even_dimensions(decoder.width, decoder.height, options);
+
+    log_info!(
+        "Transcoding {} ({}-{} ms) to {}x{}, {} frames",
+        path.display(),
+        start_ms,
+        end_ms,
+        width,
+        height,
+        total_frames
+    );
+
+    encode_video(width, height, total_frames, options, |_, buffer| {
+        next_frame(&mut decoder, width, height, buffer)
+            .map_err(|e| e.with_path(path).during(Stage::Decoding))
+    })
+}
+
+/// Join videos one after another
+///
+/// The output has the first video's dimensions (rounded down to even); other videos are
+/// scaled to fit and letterboxed in black.
+pub fn concat<P: AsRef<Path>>(input_paths: &[P], options: &EncodeOptions) -> Result<EncodeStats> {
+    options.validate_video()?;
+
+    if input_paths.is_empty() {
+        return Err(Error::InvalidInput("No input videos provided".to_string()));
+    }
+
+    let ffmpeg_path = options.ffmpeg_path.as_deref();
+    let decoders = input_paths
+        .iter()
+        .map(|path| VideoDecoder::open(path, options))
+        .collect::<Result<Vec<_>>>()?;
+
+    if let Some(mut check) = StrictCheck::new(options) {
+        for (index, decoder) in decoders.iter().enumerate() {
+            check.dimensions(index, decoder.width, decoder.height);
+        }
+        check.total_duration(decoders.iter().map(|d| d.duration_ms).sum());
+        check.finish()?;
+    }
+
+    let (width, height) = even_dimensions(decoders[0].width, decoders[0].height, options);
+    let frame_counts: Vec<u64> = decoders.iter().map(VideoDecoder::duration_frames).collect();
+    let total_frames = frame_counts.iter().sum();
+
+    log_info!(
+        "Concatenating {} videos into {}x{}, {} frames",
+        decoders.len(),
+        width,
+        height,
+        total_frames
+    );
+
+    // Decode one input at a time so only one ffmpeg process runs
+    let mut inputs = decoders.into_iter().zip(frame_counts).zip(input_paths);
+    let mut current: Option<(VideoDecoder, u64, &P)> = None;
+
+    encode_video(width, height, total_frames, options, |_, buffer| loop {
+        if let Some((ref mut decoder, ref mut remaining, path)) = current {
+            if *remaining > 0 {
+                *remaining -= 1;
+                return next_frame(decoder, width, height, buffer)
+                    .map_err(|e| e.with_path(path).during(Stage::Decoding));
+            }
+        }
+
+        let ((mut decoder, frames), path) = inputs
+            .next()
+            .ok_or_else(|| Error::Internal("Ran out of input frames".to_string()))?;
+        decoder
+            .start_decode(path, ffmpeg_path)
+            .map_err(|e| e.with_path(path).during(Stage::Decoding))?;
+        current = Some((decoder, frames, path));
+    })
+}
+
+/// Round output dimensions down to even, warning if they change
+fn even_dimensions(width: u32, height: u32, options: &EncodeOptions) -> (u32, u32) {
+    if width % 2 != 0 || height % 2 != 0 {
+        options.warn(Warning::DimensionsRounded { width, height });
+    }
+    (width / 2 * 2, height / 2 * 2)
+}
+
+/// Read the next frame of `decoder` into `buffer`, scaled to the output size
+fn next_frame(
+    decoder: &mut VideoDecoder,
+    width: u32,
+    height: u32,
+    buffer: &mut [u8],
+) -> Result<()> {
+    let frame = decoder
+        .read_frame()?
+        .ok_or_else(|| Error::Decode("Video has no frames".to_string()))?;
+
+    if frame.width == width && frame.height == height {
+        buffer.copy_from_slice(frame.data);
+        return Ok(());
+    }
+
+    let image = LoadedImage::from_rgba(frame.width, frame.height, frame.data.to_vec())?;
+    buffer.copy_from_slice(&image.resize_fit(width, height, LETTERBOX_COLOR)?.data);
+    Ok(())
+}
+
+/// Number of output frames covering `duration_ms` (at least one)
+fn ms_to_frames(duration_ms: u64) -> u64 {
+    (duration_ms * DEFAULT_FPS as u64).div_ceil(1000).max(1)
+}
+
+/// Encode `total_frames` RGBA frames produced by `next_frame` to `options.output_path`
+fn encode_video(
+    width: u32,
+    height: u32,
+    total_frames: u64,
+    options: &EncodeOptions,
+    next_frame: impl FnMut(u64, &mut [u8]) -> Result<()>,
+) -> Result<EncodeStats> {
+    let format = FrameFormat {
+        width,
+        height,
+        fps: DEFAULT_FPS,
+        frame_count: total_frames,
+    };
+    // Inputs were checked against the strict limits already; rounding the duration to
+    // whole frames must not fail them again
+    let options = EncodeOptions {
+        strict: None,
+        ..options.clone()
+    };
+    encode_frames_to(format, &options, None, next_frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ms_to_frames() {
+        assert_eq!(ms_to_frames(0), 1);
+        assert_eq!(ms_to_frames(1000), 30);
+        assert_eq!(ms_to_frames(1010), 31);
+    }
+}
@@ -0,0 +1,142 @@
+//! Transitions between consecutive slides of a slideshow
+
+/// How the next slide replaces the previous one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
+pub enum TransitionEffect {
+    /// The next slide is uncovered in place by an edge moving across the frame
+    Wipe = 0,
+    /// The next slide moves in and pushes the previous one out of the frame
+    Push = 1,
+    /// The next slide moves in over the previous one, which stays in place
+    Slide = 2,
+}
+
+/// Direction the edge or the slides of a transition move in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
+pub enum Direction {
+    Left = 0,
+    Right = 1,
+    Up = 2,
+    Down = 3,
+}
+
+/// Transition into a slide from the previous one, at the start of the slide
+///
+/// The transition takes the first `duration_ms` of the slide's own duration, so the
+/// slideshow keeps its length; it is cut short to leave at least one frame of the slide
+/// alone. Animated images show slides without transitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transition {
+    pub effect: TransitionEffect,
+    pub direction: Direction,
+    /// Duration of the transition in milliseconds
+    pub duration_ms: u32,
+}
+
+impl Transition {
+    pub fn new(effect: TransitionEffect, direction: Direction, duration_ms: u32) -> Self {
+        Self {
+            effect,
+            direction,
+            duration_ms,
+        }
+    }
+
+    /// Draw step `step` of `steps` into `frame`, which holds the previous slide
+    ///
+    /// `next` is the slide transitioned to. Both are RGBA images of `width` x `height`;
+    /// the steps exclude the previous slide alone and the next slide alone.
+    pub(crate) fn apply(
+        &self,
+        step: u64,
+        steps: u64,
+        next: &[u8],
+        (width, height): (u32, u32),
+        frame: &mut [u8],
+    ) {
+        let (width, height) = (width as usize, height as usize);
+        // Horizontal transitions move each row, vertical ones the whole image row by row
+        let (unit, extent) = match self.direction {
+            Direction::Left | Direction::Right => (4, width),
+            Direction::Up | Direction::Down => (4 * width, height),
+        };
+        let forward = matches!(self.direction, Direction::Left | Direction::Up);
+
+        // Pixels (or rows) of the next slide shown
+        let shown = (extent as u64 * (step + 1) / (steps + 1)) as usize * unit;
+        let kept = extent * unit - shown;
+        let line = extent * unit;
+        if line == 0 {
+            return;
+        }
+
+        for (frame, next) in frame.chunks_exact_mut(line).zip(next.chunks_exact(line)) {
+            match (self.effect, forward) {
+                (TransitionEffect::Wipe, true) => frame[kept..].copy_from_slice(&next[kept..]),
+                (TransitionEffect::Wipe, false) => frame[..shown].copy_from_slice(&next[..shown]),
+                (TransitionEffect::Push, true) => {
+                    frame.copy_within(shown.., 0);
+                    frame[kept..].copy_from_slice(&next[..shown]);
+                }
+                (TransitionEffect::Push, false) => {
+                    frame.copy_within(..kept, shown);
+                    frame[..shown].copy_from_slice(&next[kept..]);
+                }
+                (TransitionEffect::Slide, true) => frame[kept..].copy_from_slice(&next[..shown]),
+                (TransitionEffect::Slide, false) => frame[..shown].copy_from_slice(&next[kept..]),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 4x1 image whose pixels are `values`, one byte per channel
+    fn image(values: [u8; 4]) -> Vec<u8> {
+        values.iter().flat_map(|&v| [v; 4]).collect()
+    }
+
+    /// Step 1 of 1 shows half of the next slide
+    fn halfway(effect: TransitionEffect, direction: Direction) -> Vec<u8> {
+        let mut frame = image([1, 2, 3, 4]);
+        Transition::new(effect, direction, 500).apply(
+            0,
+            1,
+            &image([5, 6, 7, 8]),
+            (4, 1),
+            &mut frame,
+        );
+        frame
+    }
+
+    #[test]
+    fn test_horizontal_transitions() {
+        use Direction::*;
+        use TransitionEffect::*;
+
+        assert_eq!(halfway(Wipe, Left), image([1, 2, 7, 8]));
+        assert_eq!(halfway(Wipe, Right), image([5, 6, 3, 4]));
+        assert_eq!(halfway(Push, Left), image([3, 4, 5, 6]));
+        assert_eq!(halfway(Push, Right), image([7, 8, 1, 2]));
+        assert_eq!(halfway(Slide, Left), image([1, 2, 5, 6]));
+        assert_eq!(halfway(Slide, Right), image([7, 8, 3, 4]));
+    }
+
+    #[test]
+    fn test_vertical_push() {
+        // 1x3 images, one row per pixel
+        let mut frame = [[1u8; 4], [2; 4], [3; 4]].concat();
+        let next = [[4u8; 4], [5; 4], [6; 4]].concat();
+        let push = Transition::new(TransitionEffect::Push, Direction::Up, 1000);
+        // Step 0 of 2 moves a third of the height
+        push.apply(0, 2, &next, (1, 3), &mut frame);
+        assert_eq!(frame, [[2u8; 4], [3; 4], [4; 4]].concat());
+    }
+}
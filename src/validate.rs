@@ -0,0 +1,160 @@
+//! Strict validation of inputs before encoding starts
+
+use crate::{EncodeOptions, Error, Result};
+use std::fmt;
+
+/// Limits enforced when [`EncodeOptions::strict`] is set
+///
+/// Strict validation turns issues that would otherwise be fixed up with a
+/// [`Warning`](crate::Warning) into errors, and reports every problem at once before the
+/// first frame is encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StrictLimits {
+    /// Longest allowed output in milliseconds (`None` for no limit)
+    pub max_duration_ms: Option<u64>,
+}
+
+/// Problem found by strict validation
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Violation {
+    /// Quality is above 100
+    QualityOutOfRange { quality: u8 },
+    /// A slide or input video has odd dimensions
+    OddDimensions {
+        index: usize,
+        width: u32,
+        height: u32,
+    },
+    /// A slide has a duration of zero
+    ZeroDuration { index: usize },
+    /// The output is longer than [`StrictLimits::max_duration_ms`]
+    DurationTooLong { duration_ms: u64, max_ms: u64 },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::QualityOutOfRange { quality } => {
+                write!(f, "quality {} is not in 0-100", quality)
+            }
+            Violation::OddDimensions {
+                index,
+                width,
+                height,
+            } => write!(f, "input {} is {}x{}, not even", index, width, height),
+            Violation::ZeroDuration { index } => write!(f, "slide {} has no duration", index),
+            Violation::DurationTooLong {
+                duration_ms,
+                max_ms,
+            } => write!(f, "output is {} ms, longer than {} ms", duration_ms, max_ms),
+        }
+    }
+}
+
+/// Collects violations for one operation when strict validation is enabled
+pub(crate) struct StrictCheck {
+    limits: StrictLimits,
+    violations: Vec<Violation>,
+}
+
+impl StrictCheck {
+    /// Start validating if `options.strict` is set, checking the options themselves
+    pub(crate) fn new(options: &EncodeOptions) -> Option<Self> {
+        let limits = options.strict?;
+        let mut check = Self {
+            limits,
+            violations: Vec::new(),
+        };
+        if options.quality > 100 {
+            check.violations.push(Violation::QualityOutOfRange {
+                quality: options.quality,
+            });
+        }
+        Some(check)
+    }
+
+    /// Check the dimensions of input `index`
+    pub(crate) fn dimensions(&mut self, index: usize, width: u32, height: u32) {
+        if width % 2 != 0 || height % 2 != 0 {
+            self.violations.push(Violation::OddDimensions {
+                index,
+                width,
+                height,
+            });
+        }
+    }
+
+    /// Check the duration of slide `index`
+    pub(crate) fn slide_duration(&mut self, index: usize, duration_ms: u32) {
+        if duration_ms == 0 {
+            self.violations.push(Violation::ZeroDuration { index });
+        }
+    }
+
+    /// Check the duration of the whole output
+    pub(crate) fn total_duration(&mut self, duration_ms: u64) {
+        match self.limits.max_duration_ms {
+            Some(max_ms) if duration_ms > max_ms => {
+                self.violations.push(Violation::DurationTooLong {
+                    duration_ms,
+                    max_ms,
+                })
+            }
+            _ => {}
+        }
+    }
+
+    /// Fail with every violation found
+    pub(crate) fn finish(self) -> Result<()> {
+        if self.violations.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Validation(self.violations))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strict_check_lists_all_violations() {
+        let options = EncodeOptions {
+            quality: 120,
+            strict: Some(StrictLimits {
+                max_duration_ms: Some(1000),
+            }),
+            ..Default::default()
+        };
+
+        let mut check = StrictCheck::new(&options).unwrap();
+        check.dimensions(0, 64, 48);
+        check.dimensions(1, 65, 48);
+        check.slide_duration(0, 500);
+        check.slide_duration(1, 0);
+        check.total_duration(1500);
+
+        let err = check.finish().unwrap_err();
+        let Error::Validation(ref violations) = err else {
+            panic!("{:?}", err);
+        };
+        assert_eq!(violations.len(), 4);
+        assert_eq!(
+            err.to_string(),
+            "Validation failed: quality 120 is not in 0-100; input 1 is 65x48, not even; \
+             slide 1 has no duration; output is 1500 ms, longer than 1000 ms"
+        );
+    }
+
+    #[test]
+    fn test_strict_check_disabled() {
+        let options = EncodeOptions {
+            quality: 120,
+            ..Default::default()
+        };
+        assert!(StrictCheck::new(&options).is_none());
+    }
+}
@@ -0,0 +1,251 @@
+//! Comparison of encoded output with the frames it was encoded from
+
+use crate::decoder::VideoDecoder;
+use crate::image_loader::rgba_len;
+use crate::logging::{log_info, trace_span};
+use crate::{Error, Result, Stage};
+use std::path::Path;
+
+/// PSNR reported for frames identical to their source
+pub const MAX_PSNR: f64 = 100.0;
+
+/// Side of the square blocks SSIM is computed over
+const SSIM_BLOCK: usize = 8;
+
+/// Quality of one decoded frame compared to its source frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameQuality {
+    /// Peak signal-to-noise ratio over the RGB channels in dB, at most [`MAX_PSNR`]
+    pub psnr: f64,
+    /// Structural similarity of the luma, from -1 to 1 (1 for identical frames)
+    pub ssim: f64,
+    /// FNV-1a hash of the decoded RGBA frame
+    pub checksum: u64,
+}
+
+/// Quality of an encoded video compared to its source frames
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QualityReport {
+    /// Quality of each frame, in order
+    pub frames: Vec<FrameQuality>,
+    /// Mean PSNR of all frames in dB
+    pub mean_psnr: f64,
+    /// PSNR of the worst frame in dB
+    pub min_psnr: f64,
+    /// Mean SSIM of all frames
+    pub mean_ssim: f64,
+    /// SSIM of the worst frame
+    pub min_ssim: f64,
+    /// FNV-1a hash of all decoded frames, stable for byte-identical decoded output
+    pub checksum: u64,
+}
+
+impl QualityReport {
+    fn new(frames: Vec<FrameQuality>) -> Self {
+        let count = frames.len().max(1) as f64;
+        let min = |value: fn(&FrameQuality) -> f64| {
+            frames.iter().map(value).reduce(f64::min).unwrap_or(0.0)
+        };
+        let checksum = frames.iter().fold(FNV_OFFSET, |hash, frame| {
+            fnv1a(hash, &frame.checksum.to_le_bytes())
+        });
+
+        Self {
+            mean_psnr: frames.iter().map(|f| f.psnr).sum::<f64>() / count,
+            min_psnr: min(|f| f.psnr),
+            mean_ssim: frames.iter().map(|f| f.ssim).sum::<f64>() / count,
+            min_ssim: min(|f| f.ssim),
+            checksum,
+            frames,
+        }
+    }
+}
+
+/// Decode the video at `output_path` and compare its first `frame_count` frames with
+/// the frames drawn by `reference`
+///
+/// `reference` is called with each frame index in order and an RGBA buffer the size of
+/// the decoded frames to fill with the source frame. Videos are decoded with ffmpeg at
+/// 30 fps. Fails if the video has fewer frames than `frame_count`.
+pub fn verify<P, F>(
+    output_path: P,
+    frame_count: u64,
+    ffmpeg_path: Option<&str>,
+    mut reference: F,
+) -> Result<QualityReport>
+where
+    P: AsRef<Path>,
+    F: FnMut(u64, &mut [u8]) -> Result<()>,
+{
+    let path = output_path.as_ref();
+    let _span = trace_span!("verify", frames = frame_count);
+
+    let decode_error = |e: Error| e.with_path(path).during(Stage::Decoding);
+    let mut decoder = VideoDecoder::new(path, ffmpeg_path).map_err(decode_error)?;
+    decoder
+        .start_decode(path, ffmpeg_path)
+        .map_err(decode_error)?;
+
+    let mut source = vec![0u8; rgba_len(decoder.width, decoder.height)?];
+    let mut frames = Vec::with_capacity(frame_count as usize);
+
+    for frame_idx in 0..frame_count {
+        decoder.read_frame().map_err(decode_error)?;
+        // The decoder repeats the last frame once the video has ended
+        let decoded = match decoder.last_frame() {
+            Some(frame) if decoder.frames_read() > frame_idx => frame,
+            _ => {
+                return Err(decode_error(Error::Decode(format!(
+                    "Output has {} frames, expected {}",
+                    frame_idx, frame_count
+                ))))
+            }
+        };
+
+        source.fill(0);
+        reference(frame_idx, &mut source)
+            .map_err(|e| e.with_frame(frame_idx).during(Stage::Rendering))?;
+
+        frames.push(FrameQuality {
+            psnr: psnr(decoded.data, &source),
+            ssim: ssim(decoded.data, &source, decoded.width as usize),
+            checksum: fnv1a(FNV_OFFSET, decoded.data),
+        });
+    }
+
+    let report = QualityReport::new(frames);
+    log_info!(
+        "Verified {} frames: PSNR {:.2} dB (min {:.2}), SSIM {:.4} (min {:.4})",
+        frame_count,
+        report.mean_psnr,
+        report.min_psnr,
+        report.mean_ssim,
+        report.min_ssim
+    );
+    Ok(report)
+}
+
+/// PSNR of the RGB channels of two RGBA frames of the same size
+fn psnr(decoded: &[u8], source: &[u8]) -> f64 {
+    let (sum, samples) = decoded
+        .chunks_exact(4)
+        .zip(source.chunks_exact(4))
+        .flat_map(|(a, b)| a[..3].iter().zip(&b[..3]))
+        .fold((0u64, 0u64), |(sum, samples), (&a, &b)| {
+            let diff = a.abs_diff(b) as u64;
+            (sum + diff * diff, samples + 1)
+        });
+    if sum == 0 {
+        return MAX_PSNR;
+    }
+
+    let mse = sum as f64 / samples as f64;
+    (10.0 * (255.0 * 255.0 / mse).log10()).min(MAX_PSNR)
+}
+
+/// Mean SSIM of the luma of two RGBA frames of the same size, over square blocks
+fn ssim(decoded: &[u8], source: &[u8], width: usize) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let (a, b) = (luma(decoded), luma(source));
+    let height = a.len() / width.max(1);
+    let mut total = 0.0;
+    let mut blocks = 0;
+
+    for by in (0..height).step_by(SSIM_BLOCK) {
+        for bx in (0..width).step_by(SSIM_BLOCK) {
+            let pixels: Vec<usize> = (by..(by + SSIM_BLOCK).min(height))
+                .flat_map(|y| (bx..(bx + SSIM_BLOCK).min(width)).map(move |x| y * width + x))
+                .collect();
+            let n = pixels.len() as f64;
+            let mean_a = pixels.iter().map(|&i| a[i]).sum::<f64>() / n;
+            let mean_b = pixels.iter().map(|&i| b[i]).sum::<f64>() / n;
+            let (mut var_a, mut var_b, mut covar) = (0.0, 0.0, 0.0);
+            for &i in &pixels {
+                let (da, db) = (a[i] - mean_a, b[i] - mean_b);
+                var_a += da * da;
+                var_b += db * db;
+                covar += da * db;
+            }
+            let (var_a, var_b, covar) = (var_a / n, var_b / n, covar / n);
+
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covar + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            blocks += 1;
+        }
+    }
+
+    if blocks == 0 {
+        1.0
+    } else {
+        total / blocks as f64
+    }
+}
+
+/// BT.601 luma of each pixel of an RGBA frame
+fn luma(rgba: &[u8]) -> Vec<f64> {
+    rgba.chunks_exact(4)
+        .map(|p| 0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64)
+        .collect()
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Continue an FNV-1a hash over `data`
+fn fnv1a(hash: u64, data: &[u8]) -> u64 {
+    data.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_quality_metrics() {
+        let width = 16;
+        let source: Vec<u8> = (0..width * width)
+            .flat_map(|i| [(i * 7 % 256) as u8, (i * 3 % 256) as u8, 128, 255])
+            .collect();
+        assert_eq!(psnr(&source, &source), MAX_PSNR);
+        assert!((ssim(&source, &source, width) - 1.0).abs() < 1e-9);
+
+        // Alpha is ignored
+        let mut opaque = source.clone();
+        opaque.iter_mut().skip(3).step_by(4).for_each(|a| *a = 0);
+        assert_eq!(psnr(&opaque, &source), MAX_PSNR);
+
+        // A uniform error of 1 in every channel gives 48.13 dB
+        let brighter: Vec<u8> = source.iter().map(|v| v.saturating_add(1)).collect();
+        let degraded = psnr(&brighter, &source);
+        assert!(degraded < MAX_PSNR && degraded > 48.0);
+        assert!(ssim(&brighter, &source, width) < 1.0);
+
+        let flat = vec![128u8; source.len()];
+        assert!(psnr(&flat, &source) < degraded);
+        assert!(ssim(&flat, &source, width) < ssim(&brighter, &source, width));
+    }
+
+    #[test]
+    fn test_quality_report_summary() {
+        let frame = |psnr, ssim, checksum| FrameQuality {
+            psnr,
+            ssim,
+            checksum,
+        };
+        let report = QualityReport::new(vec![frame(40.0, 0.98, 1), frame(30.0, 0.90, 2)]);
+        assert_eq!(report.mean_psnr, 35.0);
+        assert_eq!(report.min_psnr, 30.0);
+        assert!((report.mean_ssim - 0.94).abs() < 1e-9);
+        assert_eq!(report.min_ssim, 0.90);
+
+        // The overall checksum follows the frames and their order
+        let swapped = QualityReport::new(vec![frame(30.0, 0.90, 2), frame(40.0, 0.98, 1)]);
+        assert_ne!(report.checksum, swapped.checksum);
+    }
+}
@@ -0,0 +1,64 @@
+//! JavaScript bindings for WebAssembly builds (`wasm` feature)
+//!
+//! Browsers have neither ffmpeg nor a platform H.264 encoder, so only AV1 in WebM is
+//! offered, encoded in memory with rav1e.
+
+use crate::{slideshow_to_memory, Codec, Container, EncodeOptions, Error, SlideEntry, SlideSource};
+use wasm_bindgen::prelude::*;
+
+/// Slideshow built up from encoded images (PNG, JPEG, WebP, ...)
+#[wasm_bindgen]
+pub struct Slideshow {
+    slides: Vec<SlideEntry>,
+    quality: u8,
+}
+
+#[wasm_bindgen]
+impl Slideshow {
+    /// Create an empty slideshow encoded at `quality` (0-100)
+    #[wasm_bindgen(constructor)]
+    pub fn new(quality: u8) -> Slideshow {
+        Slideshow {
+            slides: Vec::new(),
+            quality: quality.min(100),
+        }
+    }
+
+    /// Show the image in `bytes` for `duration_ms`
+    ///
+    /// Only the format is checked here; images are decoded by `encode`.
+    #[wasm_bindgen(js_name = addImage)]
+    pub fn add_image(&mut self, bytes: &[u8], duration_ms: u32) -> Result<(), JsError> {
+        image::guess_format(bytes).map_err(Error::from)?;
+        self.slides.push(SlideEntry {
+            source: SlideSource::Bytes(bytes.to_vec()),
+            duration_ms,
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
+        });
+        Ok(())
+    }
+
+    /// Number of images added so far
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.slides.len()
+    }
+
+    /// Encode the slideshow and return the WebM file bytes
+    ///
+    /// The added images are consumed; the slideshow is empty afterwards.
+    pub fn encode(&mut self) -> Result<Vec<u8>, JsError> {
+        let options = EncodeOptions {
+            container: Container::WebM,
+            codec: Codec::Av1,
+            quality: self.quality,
+            ..Default::default()
+        };
+
+        let slides = std::mem::take(&mut self.slides);
+        Ok(slideshow_to_memory(&slides, &options)?)
+    }
+}
@@ -0,0 +1,204 @@
+//! Image overlaid on every frame, such as a logo
+
+use crate::filter::{FilterFrame, FrameFilter};
+use crate::image_loader::LoadedImage;
+use crate::{EncodeOptions, Error, Result, Stage};
+use std::borrow::Cow;
+
+/// Corner or center of the frame a watermark is placed at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
+pub enum WatermarkPosition {
+    TopLeft = 0,
+    TopRight = 1,
+    BottomLeft = 2,
+    #[default]
+    BottomRight = 3,
+    Center = 4,
+}
+
+/// Image drawn over every frame of slideshows and juxtapositions
+///
+/// The image is drawn at its own size, blended by its alpha channel and `opacity`, and
+/// clipped to the frame. It is loaded once per operation and drawn after
+/// [`EncodeOptions::filters`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watermark {
+    /// Path to the image file
+    pub path: String,
+    pub position: WatermarkPosition,
+    /// Opacity from 0 (invisible) to 1 (as the image is)
+    pub opacity: f32,
+    /// Distance in pixels from the edges of the frame, unused when centered
+    pub margin: u32,
+}
+
+impl Watermark {
+    /// Opaque watermark in the bottom right corner, 16 pixels from the edges
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            position: WatermarkPosition::BottomRight,
+            opacity: 1.0,
+            margin: 16,
+        }
+    }
+}
+
+/// Options with the watermark of `options`, if any, loaded and appended to its filters
+pub(crate) fn with_watermark(options: &EncodeOptions) -> Result<Cow<'_, EncodeOptions>> {
+    let Some(ref watermark) = options.watermark else {
+        return Ok(Cow::Borrowed(options));
+    };
+    if !(0.0..=1.0).contains(&watermark.opacity) {
+        return Err(Error::InvalidInput(format!(
+            "Watermark opacity must be between 0 and 1, not {}",
+            watermark.opacity
+        )));
+    }
+
+    let image = LoadedImage::from_path(&watermark.path)
+        .map_err(|e| e.with_path(&watermark.path).during(Stage::Loading))?;
+    let overlay = Overlay {
+        image,
+        position: watermark.position,
+        opacity: watermark.opacity,
+        margin: watermark.margin,
+    };
+
+    let mut options = options.clone();
+    options.filters = options.filters.with(overlay);
+    Ok(Cow::Owned(options))
+}
+
+/// Loaded watermark, drawn as the last filter of an operation
+struct Overlay {
+    image: LoadedImage,
+    position: WatermarkPosition,
+    opacity: f32,
+    margin: u32,
+}
+
+impl Overlay {
+    /// Offset of the image in a `width` x `height` frame, negative where it is clipped
+    fn offset(&self, width: u32, height: u32) -> (i64, i64) {
+        let (width, height) = (width as i64, height as i64);
+        let (image_width, image_height) = (self.image.width as i64, self.image.height as i64);
+        let margin = self.margin as i64;
+        let left = margin;
+        let right = width - image_width - margin;
+        let top = margin;
+        let bottom = height - image_height - margin;
+        match self.position {
+            WatermarkPosition::TopLeft => (left, top),
+            WatermarkPosition::TopRight => (right, top),
+            WatermarkPosition::BottomLeft => (left, bottom),
+            WatermarkPosition::BottomRight => (right, bottom),
+            WatermarkPosition::Center => ((width - image_width) / 2, (height - image_height) / 2),
+        }
+    }
+}
+
+impl FrameFilter for Overlay {
+    fn apply(&self, frame: &mut FilterFrame<'_>) -> Result<()> {
+        let (x0, y0) = self.offset(frame.width, frame.height);
+        let (frame_width, frame_height) = (frame.width as i64, frame.height as i64);
+        let image_width = self.image.width as i64;
+
+        for row in 0..self.image.height as i64 {
+            let y = y0 + row;
+            if !(0..frame_height).contains(&y) {
+                continue;
+            }
+            // Columns of the image inside the frame
+            let first = (-x0).max(0);
+            let last = (frame_width - x0).min(image_width);
+            if first >= last {
+                continue;
+            }
+
+            let src = &self.image.data[((row * image_width + first) * 4) as usize..]
+                [..((last - first) * 4) as usize];
+            let dst = &mut frame.data[((y * frame_width + x0 + first) * 4) as usize..]
+                [..((last - first) * 4) as usize];
+            for (dst, src) in dst.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+                let a = src[3] as f32 / 255.0 * self.opacity;
+                for channel in 0..3 {
+                    dst[channel] =
+                        (dst[channel] as f32 * (1.0 - a) + src[channel] as f32 * a).round() as u8;
+                }
+                dst[3] = (dst[3] as f32 + (255.0 - dst[3] as f32) * a).round() as u8;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overlay(position: WatermarkPosition, opacity: f32) -> Overlay {
+        Overlay {
+            // 2x1 white image, the right pixel half transparent
+            image: LoadedImage::from_rgba(2, 1, vec![255, 255, 255, 255, 255, 255, 255, 128])
+                .unwrap(),
+            position,
+            opacity,
+            margin: 1,
+        }
+    }
+
+    /// Apply `overlay` to a black 4x3 frame and return the red channel of each pixel
+    fn reds(overlay: &Overlay) -> Vec<u8> {
+        let mut data = [0, 0, 0, 255].repeat(12);
+        overlay
+            .apply(&mut FilterFrame {
+                width: 4,
+                height: 3,
+                index: 0,
+                pts_ms: 0,
+                data: &mut data,
+            })
+            .unwrap();
+        data.chunks_exact(4).map(|pixel| pixel[0]).collect()
+    }
+
+    #[test]
+    fn test_overlay_positions_and_opacity() {
+        assert_eq!(
+            reds(&overlay(WatermarkPosition::BottomRight, 1.0)),
+            [0, 0, 0, 0, 0, 255, 128, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            reds(&overlay(WatermarkPosition::TopLeft, 0.5)),
+            [0, 0, 0, 0, 0, 128, 64, 0, 0, 0, 0, 0]
+        );
+        // Wider than the frame, clipped on both sides
+        let wide = Overlay {
+            image: LoadedImage::from_rgba(6, 1, [255; 4].repeat(6)).unwrap(),
+            ..overlay(WatermarkPosition::Center, 1.0)
+        };
+        assert_eq!(reds(&wide), [0, 0, 0, 0, 255, 255, 255, 255, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_watermark_opacity_is_checked() {
+        let options = EncodeOptions {
+            watermark: Some(Watermark {
+                opacity: 1.5,
+                ..Watermark::new("logo.png")
+            }),
+            ..Default::default()
+        };
+        assert!(matches!(
+            with_watermark(&options),
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(matches!(
+            with_watermark(&EncodeOptions::default()),
+            Ok(Cow::Borrowed(_))
+        ));
+    }
+}
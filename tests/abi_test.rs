@@ -0,0 +1,262 @@
+//! C ABI conformance tests for include/minmpeg.h
+
+use minmpeg::error::ErrorCode;
+use minmpeg::ffi::batch::FfiSlideshowJob;
+use minmpeg::ffi::details::FfiErrorDetails;
+use minmpeg::ffi::wide::FfiSlideEntryW;
+use minmpeg::ffi::{
+    FfiBuffer, FfiCapabilities, FfiCapability, FfiColor, FfiEncodeOptions, FfiEncodeStats,
+    FfiLayout, FfiQualityReport, FfiResult, FfiSlideData, FfiSlideEntry, FfiVideoInfo,
+};
+use std::collections::BTreeSet;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Collect `minmpeg_*` function names that follow `prefix` and precede `(` in `source`
+fn exported_names(source: &str, prefix: &str) -> BTreeSet<String> {
+    source
+        .match_indices(prefix)
+        .filter_map(|(i, _)| {
+            let rest = &source[i + prefix.len()..];
+            let name: String = rest
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+                .collect();
+            rest[name.len()..]
+                .trim_start()
+                .starts_with('(')
+                .then_some(name)
+        })
+        .filter(|name| name.starts_with("minmpeg_"))
+        .collect()
+}
+
+/// Every exported function must be declared in the header and vice versa
+#[test]
+fn test_header_declares_all_exports() {
+    let mut exports = BTreeSet::new();
+    for entry in fs::read_dir(manifest_dir().join("src/ffi")).unwrap() {
+        let source = fs::read_to_string(entry.unwrap().path()).unwrap();
+        exports.extend(exported_names(&source, "extern \"C\" fn "));
+    }
+
+    let header = fs::read_to_string(manifest_dir().join("include/minmpeg.h")).unwrap();
+    let declared: BTreeSet<String> = header
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('*'))
+        .flat_map(|line| exported_names(line, " "))
+        .collect();
+
+    assert!(!exports.is_empty());
+    assert_eq!(exports, declared);
+}
+
+/// Directory holding the built shared library
+fn library_dir() -> PathBuf {
+    // Test binaries live in target/<profile>/deps
+    let exe = std::env::current_exe().unwrap();
+    exe.parent().unwrap().parent().unwrap().to_path_buf()
+}
+
+/// Compile tests/c/abi_test.c against the header and shared library, then run it
+#[test]
+fn test_c_abi() {
+    // Linked with -rpath, which MSVC does not have
+    if cfg!(windows) {
+        println!("Skipping test: not supported on Windows");
+        return;
+    }
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    if Command::new(&cc).arg("--version").output().is_err() {
+        println!("Skipping test: C compiler not available");
+        return;
+    }
+
+    let lib_dir = library_dir();
+    let lib_name = if cfg!(target_os = "macos") {
+        "libminmpeg.dylib"
+    } else {
+        "libminmpeg.so"
+    };
+    if !lib_dir.join(lib_name).exists() {
+        println!("Skipping test: {} not built", lib_name);
+        return;
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let exe = temp_dir.path().join("abi_test");
+
+    let status = Command::new(&cc)
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror", "-I"])
+        .arg(manifest_dir().join("include"))
+        .arg(manifest_dir().join("tests/c/abi_test.c"))
+        .arg("-o")
+        .arg(&exe)
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lminmpeg")
+        .status()
+        .unwrap();
+    assert!(status.success(), "Failed to compile abi_test.c");
+
+    let output = Command::new(&exe)
+        .current_dir(temp_dir.path())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "abi_test.c failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Struct layouts seen by C must match the Rust definitions
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let expected = [
+        ("Result", mem::size_of::<FfiResult>()),
+        ("ErrorDetails", mem::size_of::<FfiErrorDetails>()),
+        ("SlideEntry", mem::size_of::<FfiSlideEntry>()),
+        ("SlideEntryW", mem::size_of::<FfiSlideEntryW>()),
+        ("SlideshowJob", mem::size_of::<FfiSlideshowJob>()),
+        ("SlideData", mem::size_of::<FfiSlideData>()),
+        ("Color", mem::size_of::<FfiColor>()),
+        ("Layout", mem::size_of::<FfiLayout>()),
+        ("VideoInfo", mem::size_of::<FfiVideoInfo>()),
+        (
+            "VideoInfo.container",
+            mem::offset_of!(FfiVideoInfo, container),
+        ),
+        ("Buffer", mem::size_of::<FfiBuffer>()),
+        ("Capability", mem::size_of::<FfiCapability>()),
+        ("QualityReport", mem::size_of::<FfiQualityReport>()),
+        ("Capabilities", mem::size_of::<FfiCapabilities>()),
+        ("EncodeOptions", mem::size_of::<FfiEncodeOptions>()),
+        (
+            "EncodeOptions.cancel",
+            mem::offset_of!(FfiEncodeOptions, cancel),
+        ),
+        (
+            "EncodeOptions.warning",
+            mem::offset_of!(FfiEncodeOptions, warning),
+        ),
+        (
+            "EncodeOptions.stats",
+            mem::offset_of!(FfiEncodeOptions, stats),
+        ),
+        (
+            "EncodeOptions.max_duration_ms",
+            mem::offset_of!(FfiEncodeOptions, max_duration_ms),
+        ),
+        (
+            "EncodeOptions.memory_limit",
+            mem::offset_of!(FfiEncodeOptions, memory_limit),
+        ),
+        (
+            "EncodeOptions.encoder_pool",
+            mem::offset_of!(FfiEncodeOptions, encoder_pool),
+        ),
+        (
+            "EncodeOptions.image_cache",
+            mem::offset_of!(FfiEncodeOptions, image_cache),
+        ),
+        (
+            "EncodeOptions.deterministic",
+            mem::offset_of!(FfiEncodeOptions, deterministic),
+        ),
+        (
+            "EncodeOptions.quality_report",
+            mem::offset_of!(FfiEncodeOptions, quality_report),
+        ),
+        (
+            "EncodeOptions.spill_dir",
+            mem::offset_of!(FfiEncodeOptions, spill_dir),
+        ),
+        (
+            "EncodeOptions.priority",
+            mem::offset_of!(FfiEncodeOptions, priority),
+        ),
+        (
+            "EncodeOptions.av1_backend",
+            mem::offset_of!(FfiEncodeOptions, av1_backend),
+        ),
+        (
+            "EncodeOptions.scale_mode",
+            mem::offset_of!(FfiEncodeOptions, scale_mode),
+        ),
+        (
+            "EncodeOptions.letterbox_color",
+            mem::offset_of!(FfiEncodeOptions, letterbox_color),
+        ),
+        (
+            "EncodeOptions.watermark_margin",
+            mem::offset_of!(FfiEncodeOptions, watermark_margin),
+        ),
+        (
+            "EncodeOptions.fade_color",
+            mem::offset_of!(FfiEncodeOptions, fade_color),
+        ),
+        (
+            "EncodeOptions.bitrate_kbps",
+            mem::offset_of!(FfiEncodeOptions, bitrate_kbps),
+        ),
+        (
+            "EncodeOptions.rate_control",
+            mem::offset_of!(FfiEncodeOptions, rate_control),
+        ),
+        (
+            "EncodeOptions.keyframe_interval_ms",
+            mem::offset_of!(FfiEncodeOptions, keyframe_interval_ms),
+        ),
+        (
+            "EncodeOptions.color_space",
+            mem::offset_of!(FfiEncodeOptions, color_space),
+        ),
+        (
+            "EncodeOptions.pixel_range",
+            mem::offset_of!(FfiEncodeOptions, pixel_range),
+        ),
+        (
+            "EncodeOptions.threads",
+            mem::offset_of!(FfiEncodeOptions, threads),
+        ),
+        ("EncodeStats", mem::size_of::<FfiEncodeStats>()),
+    ];
+
+    for (name, size) in expected {
+        let line = format!("{} {}", name, size);
+        assert!(
+            stdout.lines().any(|l| l == line),
+            "Layout mismatch for {}: expected {}, C reported:\n{}",
+            name,
+            size,
+            stdout
+        );
+    }
+
+    // Error code values are part of the ABI
+    let codes = [
+        ("MINMPEG_ERR_CANCELLED", ErrorCode::Cancelled),
+        ("MINMPEG_ERR_INTERNAL", ErrorCode::Internal),
+        ("MINMPEG_ERR_MUX_ERROR", ErrorCode::MuxError),
+        ("MINMPEG_ERR_FFMPEG_ERROR", ErrorCode::FfmpegError),
+        ("MINMPEG_ERR_PLATFORM_ERROR", ErrorCode::PlatformError),
+        ("MINMPEG_ERR_UNSUPPORTED", ErrorCode::Unsupported),
+    ];
+    for (name, code) in codes {
+        let line = format!("{} {}", name, code as i32);
+        assert!(
+            stdout.lines().any(|l| l == line),
+            "Code mismatch for {}: expected {}, C reported:\n{}",
+            name,
+            code as i32,
+            stdout
+        );
+    }
+}
@@ -0,0 +1,253 @@
+/*
+ * C ABI conformance test for minmpeg.h
+ *
+ * Calls every exported function through the public header. Built and run by
+ * tests/abi_test.rs, which also compares the struct sizes printed here with
+ * the Rust definitions.
+ */
+
+#include "minmpeg.h"
+
+#include <stddef.h>
+#include <stdio.h>
+#include <string.h>
+
+static int failures = 0;
+
+#define CHECK(cond)                                                 \
+    do {                                                            \
+        if (!(cond)) {                                              \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,  \
+                    __LINE__, #cond);                               \
+            failures++;                                             \
+        }                                                           \
+    } while (0)
+
+/* Check the result code and free the result */
+static void expect(Result result, ErrorCode code) {
+    if (result.code != code) {
+        fprintf(stderr, "expected code %d, got %d: %s\n", (int)code,
+                (int)result.code, result.message ? result.message : "");
+        failures++;
+    }
+    minmpeg_free_result(&result);
+}
+
+static void on_progress(uint64_t frames_done, uint64_t frames_total, void* user_data) {
+    (void)frames_total;
+    *(uint64_t*)user_data = frames_done;
+}
+
+static void on_log(LogLevel level, const char* message, void* user_data) {
+    (void)level;
+    (void)message;
+    (void)user_data;
+}
+
+static void on_warning(WarningCode code, const char* message, void* user_data) {
+    if (code == MINMPEG_WARN_DIMENSIONS_ROUNDED && message != NULL) {
+        (*(int*)user_data)++;
+    }
+}
+
+static int32_t on_frame(uint64_t frame_index, uint8_t* rgba, size_t rgba_len, void* user_data) {
+    memset(rgba, (int)(frame_index * 64), rgba_len);
+    return frame_index < *(uint64_t*)user_data ? 0 : 1;
+}
+
+static void print_layout(void) {
+    printf("Result %zu\n", sizeof(Result));
+    printf("ErrorDetails %zu\n", sizeof(ErrorDetails));
+    printf("SlideEntry %zu\n", sizeof(SlideEntry));
+    printf("SlideEntryW %zu\n", sizeof(SlideEntryW));
+    printf("SlideshowJob %zu\n", sizeof(SlideshowJob));
+    printf("SlideData %zu\n", sizeof(SlideData));
+    printf("Color %zu\n", sizeof(Color));
+    printf("Layout %zu\n", sizeof(Layout));
+    printf("VideoInfo %zu\n", sizeof(VideoInfo));
+    printf("VideoInfo.container %zu\n", offsetof(VideoInfo, container));
+    printf("Buffer %zu\n", sizeof(Buffer));
+    printf("Capability %zu\n", sizeof(Capability));
+    printf("QualityReport %zu\n", sizeof(QualityReport));
+    printf("Capabilities %zu\n", sizeof(Capabilities));
+    printf("EncodeOptions %zu\n", sizeof(EncodeOptions));
+    printf("EncodeOptions.cancel %zu\n", offsetof(EncodeOptions, cancel));
+    printf("EncodeOptions.warning %zu\n", offsetof(EncodeOptions, warning));
+    printf("EncodeOptions.stats %zu\n", offsetof(EncodeOptions, stats));
+    printf("EncodeOptions.max_duration_ms %zu\n", offsetof(EncodeOptions, max_duration_ms));
+    printf("EncodeOptions.memory_limit %zu\n", offsetof(EncodeOptions, memory_limit));
+    printf("EncodeOptions.encoder_pool %zu\n", offsetof(EncodeOptions, encoder_pool));
+    printf("EncodeOptions.image_cache %zu\n", offsetof(EncodeOptions, image_cache));
+    printf("EncodeOptions.deterministic %zu\n", offsetof(EncodeOptions, deterministic));
+    printf("EncodeOptions.quality_report %zu\n", offsetof(EncodeOptions, quality_report));
+    printf("EncodeOptions.spill_dir %zu\n", offsetof(EncodeOptions, spill_dir));
+    printf("EncodeOptions.priority %zu\n", offsetof(EncodeOptions, priority));
+    printf("EncodeOptions.av1_backend %zu\n", offsetof(EncodeOptions, av1_backend));
+    printf("EncodeOptions.scale_mode %zu\n", offsetof(EncodeOptions, scale_mode));
+    printf("EncodeOptions.letterbox_color %zu\n", offsetof(EncodeOptions, letterbox_color));
+    printf("EncodeOptions.watermark_margin %zu\n", offsetof(EncodeOptions, watermark_margin));
+    printf("EncodeOptions.fade_color %zu\n", offsetof(EncodeOptions, fade_color));
+    printf("EncodeOptions.bitrate_kbps %zu\n", offsetof(EncodeOptions, bitrate_kbps));
+    printf("EncodeOptions.rate_control %zu\n", offsetof(EncodeOptions, rate_control));
+    printf("EncodeOptions.keyframe_interval_ms %zu\n", offsetof(EncodeOptions, keyframe_interval_ms));
+    printf("EncodeOptions.color_space %zu\n", offsetof(EncodeOptions, color_space));
+    printf("EncodeOptions.pixel_range %zu\n", offsetof(EncodeOptions, pixel_range));
+    printf("EncodeOptions.threads %zu\n", offsetof(EncodeOptions, threads));
+    printf("EncodeStats %zu\n", sizeof(EncodeStats));
+}
+
+static void print_error_codes(void) {
+    printf("MINMPEG_ERR_CANCELLED %d\n", MINMPEG_ERR_CANCELLED);
+    printf("MINMPEG_ERR_INTERNAL %d\n", MINMPEG_ERR_INTERNAL);
+    printf("MINMPEG_ERR_MUX_ERROR %d\n", MINMPEG_ERR_MUX_ERROR);
+    printf("MINMPEG_ERR_FFMPEG_ERROR %d\n", MINMPEG_ERR_FFMPEG_ERROR);
+    printf("MINMPEG_ERR_PLATFORM_ERROR %d\n", MINMPEG_ERR_PLATFORM_ERROR);
+    printf("MINMPEG_ERR_UNSUPPORTED %d\n", MINMPEG_ERR_UNSUPPORTED);
+}
+
+int main(void) {
+    print_layout();
+    print_error_codes();
+
+    /* Library information */
+    CHECK(minmpeg_version() != NULL && strlen(minmpeg_version()) > 0);
+    expect(minmpeg_init(), MINMPEG_OK);
+    minmpeg_set_log_callback(on_log, NULL, MINMPEG_LOG_DEBUG);
+    expect(minmpeg_available(CODEC_AV1, NULL), MINMPEG_OK);
+
+    Capabilities caps;
+    expect(minmpeg_capabilities(NULL, &caps), MINMPEG_OK);
+    CHECK(caps.len > 0 && caps.items[0].backend_name != NULL);
+    minmpeg_capabilities_free(&caps);
+    CHECK(caps.items == NULL);
+
+    /* Strings */
+    const uint16_t wide[] = {'a', '/', 'b', 0};
+    char* utf8 = minmpeg_utf16_to_utf8(wide);
+    CHECK(utf8 != NULL && strcmp(utf8, "a/b") == 0);
+    minmpeg_string_free(utf8);
+    const uint16_t wide_output[] = {'o', '.', 'w', 'e', 'b', 'm', 0};
+    SlideEntryW wide_entry = {NULL, 100};
+    expect(minmpeg_slideshow_w(&wide_entry, 1, wide_output, NULL), MINMPEG_ERR_INVALID_INPUT);
+    expect(minmpeg_juxtapose_w(wide, NULL, NULL, wide_output, NULL), MINMPEG_ERR_INVALID_INPUT);
+
+    /* Encoding from raw RGBA data */
+    uint8_t pixels[16 * 16 * 4];
+    memset(pixels, 128, sizeof(pixels));
+    SlideData data = {SLIDE_DATA_RGBA, NULL, pixels, sizeof(pixels), 16, 16, 66};
+
+    uint64_t frames_done = 0;
+    CancelToken* token = minmpeg_cancel_token_new();
+    EncodeOptions options;
+    minmpeg_encode_options_init(&options);
+    CHECK(options.struct_size == sizeof(EncodeOptions));
+    options.progress = on_progress;
+    options.user_data = &frames_done;
+    options.cancel = token;
+    EncodeStats stats;
+    memset(&stats, 0, sizeof(stats));
+    options.stats = &stats;
+
+    Buffer buffer;
+    expect(minmpeg_slideshow_from_data_to_buffer(&data, 1, &options, &buffer), MINMPEG_OK);
+    CHECK(buffer.len > 4 && buffer.data[0] == 0x1A && buffer.data[1] == 0x45);
+    CHECK(frames_done > 0);
+    CHECK(stats.frames == frames_done && stats.output_bytes == buffer.len);
+    CHECK(stats.backend == BACKEND_RAV1E);
+    options.stats = NULL;
+    minmpeg_buffer_free(&buffer);
+    CHECK(buffer.data == NULL);
+
+    /* Warnings */
+    int warnings = 0;
+    EncodeOptions warn_options;
+    minmpeg_encode_options_init(&warn_options);
+    warn_options.warning = on_warning;
+    warn_options.user_data = &warnings;
+    uint8_t odd_pixels[17 * 16 * 4];
+    memset(odd_pixels, 128, sizeof(odd_pixels));
+    SlideData odd = {SLIDE_DATA_RGBA, NULL, odd_pixels, sizeof(odd_pixels), 17, 16, 66};
+    expect(minmpeg_slideshow_from_data_to_buffer(&odd, 1, &warn_options, &buffer), MINMPEG_OK);
+    minmpeg_buffer_free(&buffer);
+    CHECK(warnings == 1);
+
+    /* Encoding frames drawn by a callback */
+    uint64_t frame_limit = 3;
+    expect(minmpeg_encode_frames_to_buffer(16, 16, 30, 3, on_frame, &frame_limit, &options, &buffer),
+           MINMPEG_OK);
+    CHECK(buffer.len > 4 && buffer.data[0] == 0x1A && buffer.data[1] == 0x45);
+    minmpeg_buffer_free(&buffer);
+    frame_limit = 1;
+    expect(minmpeg_encode_frames_to_buffer(16, 16, 30, 3, on_frame, &frame_limit, &options, &buffer),
+           MINMPEG_ERR_CANCELLED);
+
+    minmpeg_cancel_token_cancel(token);
+    expect(minmpeg_slideshow_from_data_to_buffer(&data, 1, &options, &buffer), MINMPEG_ERR_CANCELLED);
+    options.cancel = NULL;
+    minmpeg_cancel_token_free(token);
+
+    /* Invalid input is reported, not crashed on */
+    options.output_path = "unused.webm";
+    expect(minmpeg_slideshow(NULL, 0, "unused.webm", CONTAINER_WEBM, CODEC_AV1, 50, NULL),
+           MINMPEG_ERR_INVALID_INPUT);
+    expect(minmpeg_slideshow_with_progress(NULL, 0, "unused.webm", CONTAINER_WEBM, CODEC_AV1, 50,
+                                           NULL, NULL, NULL, NULL),
+           MINMPEG_ERR_INVALID_INPUT);
+    expect(minmpeg_slideshow_ex(NULL, 0, &options), MINMPEG_ERR_INVALID_INPUT);
+    expect(minmpeg_slideshow_to_buffer(NULL, 0, &options, &buffer), MINMPEG_ERR_INVALID_INPUT);
+    expect(minmpeg_slideshow_from_data(NULL, 0, &options), MINMPEG_ERR_INVALID_INPUT);
+    expect(minmpeg_juxtapose(NULL, NULL, "unused.webm", CONTAINER_WEBM, CODEC_AV1, 50, NULL, NULL),
+           MINMPEG_ERR_INVALID_INPUT);
+    expect(minmpeg_juxtapose_with_progress(NULL, NULL, "unused.webm", CONTAINER_WEBM, CODEC_AV1,
+                                           50, NULL, NULL, NULL, NULL, NULL),
+           MINMPEG_ERR_INVALID_INPUT);
+    expect(minmpeg_juxtapose_ex(NULL, NULL, NULL, &options), MINMPEG_ERR_INVALID_INPUT);
+    expect(minmpeg_juxtapose_to_buffer(NULL, NULL, NULL, &options, &buffer),
+           MINMPEG_ERR_INVALID_INPUT);
+    Layout layout = {2};
+    expect(minmpeg_juxtapose_n(NULL, 0, &layout, NULL, &options), MINMPEG_ERR_INVALID_INPUT);
+    expect(minmpeg_juxtapose_n_to_buffer(NULL, 0, &layout, NULL, &options, &buffer),
+           MINMPEG_ERR_INVALID_INPUT);
+    VideoInfo info;
+    info.struct_size = sizeof(VideoInfo);
+    expect(minmpeg_probe(NULL, NULL, &info), MINMPEG_ERR_INVALID_INPUT);
+    info.struct_size = 4;
+    expect(minmpeg_probe("unused.mp4", NULL, &info), MINMPEG_ERR_INVALID_INPUT);
+    expect(minmpeg_transcode(NULL, &options), MINMPEG_ERR_INVALID_INPUT);
+    expect(minmpeg_trim(NULL, 0, 0, &options), MINMPEG_ERR_INVALID_INPUT);
+    expect(minmpeg_concat(NULL, 0, &options), MINMPEG_ERR_INVALID_INPUT);
+    expect(minmpeg_thumbnail(NULL, 0, "unused.png", NULL), MINMPEG_ERR_INVALID_INPUT);
+    expect(minmpeg_encode_frames(16, 16, 30, 1, NULL, NULL, &options), MINMPEG_ERR_INVALID_INPUT);
+    expect(minmpeg_encode_frames(15, 16, 30, 1, on_frame, &frame_limit, &options),
+           MINMPEG_ERR_INVALID_INPUT);
+
+    /* Error details name the offending file */
+    SlideEntry missing = {"missing.png", 100};
+    Result result = minmpeg_slideshow_ex(&missing, 1, &options);
+    CHECK(result.code == MINMPEG_ERR_IO_ERROR);
+    ErrorDetails details;
+    CHECK(minmpeg_result_details(&result, &details) == 1);
+    CHECK(details.path != NULL && strcmp(details.path, "missing.png") == 0);
+    CHECK(details.frame == -1 && details.chain_len >= 1);
+    CHECK(minmpeg_result_retryable(&result) == 0);
+    minmpeg_free_result(&result);
+
+    /* Asynchronous jobs */
+    Job* job = minmpeg_submit_slideshow(NULL, 0, &options);
+    CHECK(job != NULL);
+    minmpeg_job_wait(job);
+    CHECK(minmpeg_job_poll(job) == 1);
+    expect(minmpeg_job_result(job), MINMPEG_ERR_INVALID_INPUT);
+    minmpeg_job_cancel(job);
+    minmpeg_job_free(job);
+
+    job = minmpeg_submit_juxtapose(NULL, NULL, NULL, &options);
+    minmpeg_job_wait(job);
+    expect(minmpeg_job_result(job), MINMPEG_ERR_INVALID_INPUT);
+    minmpeg_job_free(job);
+
+    minmpeg_set_log_callback(NULL, NULL, MINMPEG_LOG_ERROR);
+    minmpeg_shutdown();
+
+    return failures == 0 ? 0 : 1;
+}
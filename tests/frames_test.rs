@@ -0,0 +1,130 @@
+//! Integration tests for encoding caller-rendered frames
+
+mod common;
+
+use common::*;
+use minmpeg::{
+    encode_frames, encode_frames_to_memory, Codec, Container, EncodeOptions, EncoderBackend, Error,
+    FilterChain, FilterFrame, FrameFormat, Stage,
+};
+use tempfile::TempDir;
+
+const FORMAT: FrameFormat = FrameFormat {
+    width: 64,
+    height: 48,
+    fps: 10,
+    frame_count: 5,
+};
+
+/// Fill a frame with a gray level that changes every frame
+fn render_gray(frame_idx: u64, rgba: &mut [u8]) -> minmpeg::Result<()> {
+    rgba.fill((frame_idx * 50) as u8);
+    Ok(())
+}
+
+#[test]
+fn test_encode_frames_webm_av1() {
+    let temp_dir = TempDir::new().unwrap();
+    let output_path = temp_dir.path().join("frames.webm");
+
+    let options = EncodeOptions {
+        output_path: output_path.to_string_lossy().to_string(),
+        container: Container::WebM,
+        codec: Codec::Av1,
+        quality: 50,
+        ..Default::default()
+    };
+
+    let mut rendered = Vec::new();
+    let result = encode_frames(FORMAT, &options, |frame_idx, rgba| {
+        assert_eq!(rgba.len(), FORMAT.frame_size());
+        rendered.push(frame_idx);
+        render_gray(frame_idx, rgba)
+    });
+    assert!(result.is_ok(), "Encoding frames failed: {:?}", result);
+    assert_eq!(rendered, vec![0, 1, 2, 3, 4]);
+    assert!(verify_webm_header(&output_path));
+
+    let stats = result.unwrap();
+    assert_eq!(stats.frames, 5);
+    assert_eq!(stats.duration_ms, 500);
+    assert_eq!(stats.backend, EncoderBackend::Rav1e);
+    assert!(stats.keyframes >= 1);
+    assert_eq!(
+        stats.output_bytes,
+        std::fs::metadata(&output_path).unwrap().len()
+    );
+}
+
+#[test]
+fn test_encode_frames_keyframe_interval() {
+    let temp_dir = TempDir::new().unwrap();
+    let output_path = temp_dir.path().join("frames.webm");
+
+    let options = EncodeOptions {
+        output_path: output_path.to_string_lossy().to_string(),
+        container: Container::WebM,
+        codec: Codec::Av1,
+        keyframe_interval_ms: Some(200),
+        ..Default::default()
+    };
+
+    // Two frames apart at 10 fps
+    let stats = encode_frames(FORMAT, &options, render_gray).expect("Encoding frames failed");
+    assert_eq!(stats.frames, 5);
+    assert_eq!(stats.keyframes, 3);
+}
+
+#[test]
+fn test_encode_frames_render_error() {
+    let result = encode_frames_to_memory(FORMAT, &EncodeOptions::default(), |frame_idx, _| {
+        if frame_idx == 2 {
+            Err(Error::InvalidInput("stop".to_string()))
+        } else {
+            Ok(())
+        }
+    });
+
+    let err = result.unwrap_err();
+    assert!(matches!(err.inner(), Error::InvalidInput(_)), "{:?}", err);
+    assert_eq!(err.frame(), Some(2));
+}
+
+#[test]
+fn test_encode_frames_filters() {
+    let options = EncodeOptions {
+        deterministic: true,
+        ..Default::default()
+    };
+    let expected = encode_frames_to_memory(FORMAT, &options, render_gray).unwrap();
+
+    // Filters drawing the same frames over blank ones give the same video
+    let filtered = EncodeOptions {
+        filters: FilterChain::new()
+            .with(|frame: &mut FilterFrame<'_>| {
+                assert_eq!(frame.pts_ms, frame.index * 100);
+                render_gray(frame.index, frame.data)
+            })
+            .with(|frame: &mut FilterFrame<'_>| {
+                assert_eq!((frame.width, frame.height), (64, 48));
+                Ok(())
+            }),
+        ..options.clone()
+    };
+    let output = encode_frames_to_memory(FORMAT, &filtered, |_, _| Ok(())).unwrap();
+    assert_eq!(output, expected);
+
+    let failing = EncodeOptions {
+        filters: FilterChain::new().with(|frame: &mut FilterFrame<'_>| {
+            if frame.index == 3 {
+                Err(Error::InvalidInput("bad frame".to_string()))
+            } else {
+                Ok(())
+            }
+        }),
+        ..options
+    };
+    let err = encode_frames_to_memory(FORMAT, &failing, render_gray).unwrap_err();
+    assert_eq!(err.frame(), Some(3));
+    assert_eq!(err.stage(), Some(Stage::Rendering));
+}
@@ -4,6 +4,7 @@ mod common;
 
 use common::*;
 use minmpeg::image_loader::LoadedImage;
+use minmpeg::Rotation;
 use tempfile::TempDir;
 
 /// Test loading a JPEG image
@@ -55,7 +56,7 @@ fn test_resize_image() {
     save_png(&original, &path).unwrap();
 
     let loaded = LoadedImage::from_path(&path).unwrap();
-    let resized = loaded.resize(200, 150);
+    let resized = loaded.resize(200, 150).unwrap();
 
     assert_eq!(resized.width, 200);
     assert_eq!(resized.height, 150);
@@ -72,7 +73,7 @@ fn test_resize_same_size() {
     save_png(&original, &path).unwrap();
 
     let loaded = LoadedImage::from_path(&path).unwrap();
-    let resized = loaded.resize(200, 150);
+    let resized = loaded.resize(200, 150).unwrap();
 
     assert_eq!(resized.width, 200);
     assert_eq!(resized.height, 150);
@@ -89,13 +90,53 @@ fn test_resize_fit() {
     save_png(&original, &path).unwrap();
 
     let loaded = LoadedImage::from_path(&path).unwrap();
-    let resized = loaded.resize_fit(300, 300, [255, 255, 255, 255]);
+    let resized = loaded.resize_fit(300, 300, [255, 255, 255, 255]).unwrap();
 
     // Should fit within 300x300 while preserving aspect ratio
     assert_eq!(resized.width, 300);
     assert_eq!(resized.height, 300);
 }
 
+/// Test cropping a region, and regions outside the image
+#[test]
+fn test_crop() {
+    let image =
+        LoadedImage::from_dynamic_image(generate_test_image(100, 80, [0, 0, 0, 255]).into());
+    let cropped = image.crop(10, 20, 30, 40).expect("Failed to crop");
+    assert_eq!((cropped.width, cropped.height), (30, 40));
+    assert_eq!(cropped.data.len(), 30 * 40 * 4);
+    // The gradient of the test image is the position modulo 50
+    assert_eq!(cropped.data[..4], [10, 20, 0, 255]);
+    assert_eq!(cropped.data[cropped.data.len() - 4..], [39, 9, 0, 255]);
+
+    assert_eq!(image.crop(0, 0, 100, 80).unwrap().data, image.data);
+    assert!(image.crop(90, 0, 20, 10).is_err());
+    assert!(image.crop(0, 0, 0, 10).is_err());
+    assert!(image.crop(u32::MAX, 0, 2, 2).is_err());
+}
+
+/// Test rotating and flipping a 2x1 image
+#[test]
+fn test_rotate_and_flip() {
+    let (red, blue) = ([255, 0, 0, 255], [0, 0, 255, 255]);
+    let image = LoadedImage::from_rgba(2, 1, [red, blue].concat()).unwrap();
+    let check = |image: LoadedImage, (width, height): (u32, u32), pixels: [[u8; 4]; 2]| {
+        assert_eq!((image.width, image.height), (width, height));
+        assert_eq!(image.data, pixels.concat());
+    };
+
+    check(image.rotate90().unwrap(), (1, 2), [red, blue]);
+    check(image.rotate180().unwrap(), (2, 1), [blue, red]);
+    check(image.rotate270().unwrap(), (1, 2), [blue, red]);
+    check(
+        image.rotate(Rotation::Rotate270).unwrap(),
+        (1, 2),
+        [blue, red],
+    );
+    check(image.flip_horizontal().unwrap(), (2, 1), [blue, red]);
+    check(image.flip_vertical().unwrap(), (2, 1), [red, blue]);
+}
+
 /// Test loading various JPEG quality levels
 #[test]
 fn test_load_jpeg_various_quality() {
@@ -180,7 +221,7 @@ fn test_upscale_image() {
     save_png(&original, &path).unwrap();
 
     let loaded = LoadedImage::from_path(&path).unwrap();
-    let resized = loaded.resize(400, 400);
+    let resized = loaded.resize(400, 400).unwrap();
 
     assert_eq!(resized.width, 400);
     assert_eq!(resized.height, 400);
@@ -197,8 +238,77 @@ fn test_downscale_image() {
     save_png(&original, &path).unwrap();
 
     let loaded = LoadedImage::from_path(&path).unwrap();
-    let resized = loaded.resize(200, 150);
+    let resized = loaded.resize(200, 150).unwrap();
 
     assert_eq!(resized.width, 200);
     assert_eq!(resized.height, 150);
 }
+
+/// Encode a 2x1 PNG of `pixels` with the ICC profile `icc` embedded
+fn png_with_profile(pixels: [[u8; 4]; 2], icc: Vec<u8>) -> Vec<u8> {
+    use image::codecs::png::PngEncoder;
+    use image::{ExtendedColorType, ImageEncoder};
+
+    let mut bytes = Vec::new();
+    let mut encoder = PngEncoder::new(&mut bytes);
+    encoder.set_icc_profile(icc).unwrap();
+    encoder
+        .write_image(&pixels.concat(), 2, 1, ExtendedColorType::Rgba8)
+        .unwrap();
+    bytes
+}
+
+/// Test that colors of an image with a Display P3 profile are converted to sRGB
+#[test]
+fn test_load_icc_profile() {
+    let p3 = moxcms::ColorProfile::new_display_p3().encode().unwrap();
+    let bytes = png_with_profile([[200, 50, 50, 255], [128, 128, 128, 128]], p3);
+    let loaded = LoadedImage::from_bytes(&bytes).unwrap();
+
+    // The same values are a more saturated red in P3 than in sRGB
+    let red = &loaded.data[..4];
+    assert!(red[0] > 200 && red[1] < 50 && red[2] < 50, "{:?}", red);
+    // Gray stays gray, and alpha is kept
+    let gray = &loaded.data[4..];
+    assert!(
+        gray[..3].iter().all(|&c| c.abs_diff(128) <= 1),
+        "{:?}",
+        gray
+    );
+    assert_eq!(gray[3], 128);
+
+    // An unreadable profile leaves the colors as they are
+    let bytes = png_with_profile([[200, 50, 50, 255], [128, 128, 128, 128]], vec![0; 16]);
+    let loaded = LoadedImage::from_bytes(&bytes).unwrap();
+    assert_eq!(loaded.data[..4], [200, 50, 50, 255]);
+}
+
+#[test]
+fn test_load_jpeg_at_least() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("large.jpg");
+    save_jpeg(
+        &generate_test_image(640, 480, [200, 100, 50, 255]),
+        &path,
+        90,
+    )
+    .unwrap();
+
+    // Halved, since a quarter would be narrower than 200
+    let loaded = LoadedImage::from_path_at_least(&path, 200, 100).expect("Failed to load");
+    assert_eq!((loaded.width, loaded.height), (320, 240));
+    assert_eq!(loaded.data.len(), 320 * 240 * 4);
+
+    let loaded = LoadedImage::from_path_at_least(&path, 10, 10).expect("Failed to load");
+    assert_eq!((loaded.width, loaded.height), (80, 60));
+
+    // Not twice as large as the target, so decoded at full size
+    let loaded = LoadedImage::from_path_at_least(&path, 400, 100).expect("Failed to load");
+    assert_eq!((loaded.width, loaded.height), (640, 480));
+
+    // Other formats load at full size
+    let png_path = temp_dir.path().join("large.png");
+    save_png(&generate_test_image(640, 480, [0, 0, 0, 255]), &png_path).unwrap();
+    let loaded = LoadedImage::from_path_at_least(&png_path, 10, 10).expect("Failed to load");
+    assert_eq!((loaded.width, loaded.height), (640, 480));
+}
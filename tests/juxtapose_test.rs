@@ -3,7 +3,10 @@
 mod common;
 
 use common::*;
-use minmpeg::{juxtapose, slideshow, Codec, Color, Container, EncodeOptions, SlideEntry};
+use minmpeg::{
+    juxtapose, juxtapose_n, juxtapose_n_to_memory, mosaic, slideshow, Codec, Color, Container,
+    EncodeOptions, EndPolicy, Error, Layout, SlideEntry, SlideSource, Trim,
+};
 use std::process::Command;
 use tempfile::TempDir;
 
@@ -38,14 +41,25 @@ fn create_test_video(
     let entries: Vec<SlideEntry> = image_paths
         .iter()
         .map(|path| SlideEntry {
-            path: path.to_string_lossy().to_string(),
+            source: SlideSource::Path(path.to_string_lossy().to_string()),
             duration_ms: 200,
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
         })
         .collect();
 
     let ext = match container {
         Container::WebM => "webm",
         Container::Mp4 => "mp4",
+        Container::WebP => "webp",
+        Container::Apng => "png",
+        Container::Mkv => "mkv",
+        Container::Hls => "m3u8",
+        Container::Ivf => "ivf",
+        Container::AnnexB => "h264",
+        Container::Y4m => "y4m",
     };
 
     let output_path = temp_dir.path().join(format!("{}.{}", name, ext));
@@ -56,6 +70,7 @@ fn create_test_video(
         codec,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     slideshow(&entries, &options).expect("Failed to create test video");
@@ -63,6 +78,89 @@ fn create_test_video(
     output_path.to_string_lossy().to_string()
 }
 
+/// Test shorter videos frozen, looped or ending the output
+#[test]
+fn test_juxtapose_n_end_policy() {
+    if !ffmpeg_available() {
+        println!("Skipping test: ffmpeg not available");
+        return;
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let long = create_test_video(&temp_dir, "long", 160, 120, 3, Container::WebM, Codec::Av1);
+    let short = create_test_video(&temp_dir, "short", 160, 120, 1, Container::WebM, Codec::Av1);
+    let options = EncodeOptions {
+        container: Container::Y4m,
+        codec: Codec::H264,
+        ..Default::default()
+    };
+
+    // Luma planes of the short video's half of each frame
+    let short_halves = |end_policy| {
+        let layout = Layout {
+            end_policy,
+            ..Default::default()
+        };
+        let data = juxtapose_n_to_memory(&[&long, &short], layout, &options, None).unwrap();
+        let header_len = data.iter().position(|&b| b == b'\n').unwrap() + 1;
+        let frame_size = b"FRAME\n".len() + 320 * 120 * 3 / 2;
+        data[header_len..]
+            .chunks_exact(frame_size)
+            .map(|frame| {
+                frame[b"FRAME\n".len()..][..320 * 120]
+                    .chunks_exact(320)
+                    .flat_map(|row| &row[160..])
+                    .copied()
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let frozen = short_halves(EndPolicy::FreezeLast);
+    let looped = short_halves(EndPolicy::Loop);
+    let stopped = short_halves(EndPolicy::Stop);
+    assert_eq!(frozen.len(), 18);
+    assert_eq!(looped.len(), 18);
+    assert_eq!(stopped.len(), 6);
+    assert_eq!(frozen[5], frozen[17]);
+    assert_eq!(looped[0], looped[6]);
+    assert_eq!(looped[..6], frozen[..6]);
+}
+
+/// Test inputs trimmed to a part of their video
+#[test]
+fn test_juxtapose_n_trims() {
+    if !ffmpeg_available() {
+        println!("Skipping test: ffmpeg not available");
+        return;
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let long = create_test_video(&temp_dir, "long", 160, 120, 3, Container::WebM, Codec::Av1);
+    let short = create_test_video(&temp_dir, "short", 160, 120, 1, Container::WebM, Codec::Av1);
+    let options = EncodeOptions {
+        container: Container::Y4m,
+        codec: Codec::H264,
+        ..Default::default()
+    };
+    let layout = |start_ms, end_ms| Layout {
+        trims: vec![Trim { start_ms, end_ms }],
+        ..Default::default()
+    };
+
+    // The second slide of the long video, as long as the short one
+    let data =
+        juxtapose_n_to_memory(&[&long, &short], layout(200, Some(400)), &options, None).unwrap();
+    let header_len = data.iter().position(|&b| b == b'\n').unwrap() + 1;
+    let frame_size = b"FRAME\n".len() + 320 * 120 * 3 / 2;
+    assert_eq!((data.len() - header_len) / frame_size, 6);
+
+    let err =
+        juxtapose_n_to_memory(&[&long, &short], layout(700, None), &options, None).unwrap_err();
+    assert!(matches!(err.inner(), Error::InvalidInput(_)), "{:?}", err);
+    assert!(err.path().is_some());
+}
+
 // ============================================================================
 // Same size composition tests (WebM + AV1)
 // ============================================================================
@@ -90,6 +188,7 @@ fn test_juxtapose_same_size_webm_av1() {
         codec: Codec::Av1,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = juxtapose(&left_video, &right_video, &options, None);
@@ -102,6 +201,53 @@ fn test_juxtapose_same_size_webm_av1() {
     assert!(verify_webm_header(&output_path));
 }
 
+/// Test juxtapose_n with three videos in a two-column grid (WebM + AV1)
+#[test]
+fn test_juxtapose_n_grid_webm_av1() {
+    if !ffmpeg_available() {
+        println!("Skipping test: ffmpeg not available");
+        return;
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+
+    let videos: Vec<String> = ["a", "b", "c"]
+        .iter()
+        .map(|name| create_test_video(&temp_dir, name, 160, 120, 2, Container::WebM, Codec::Av1))
+        .collect();
+
+    let output_path = temp_dir.path().join("output.webm");
+
+    let options = EncodeOptions {
+        output_path: output_path.to_string_lossy().to_string(),
+        container: Container::WebM,
+        codec: Codec::Av1,
+        quality: 50,
+        ffmpeg_path: None,
+        ..Default::default()
+    };
+
+    let result = juxtapose_n(
+        &videos,
+        Layout {
+            columns: 2,
+            ..Default::default()
+        },
+        &options,
+        None,
+    );
+    assert!(
+        result.is_ok(),
+        "Juxtapose grid WebM+AV1 failed: {:?}",
+        result
+    );
+    assert!(verify_file_exists_with_size(&output_path));
+    assert!(verify_webm_header(&output_path));
+
+    let info = minmpeg::probe(&output_path, None).unwrap();
+    assert_eq!((info.width, info.height), (320, 240));
+}
+
 // ============================================================================
 // Same size composition tests (MP4 + H.264) - Platform specific
 // ============================================================================
@@ -130,6 +276,7 @@ fn test_juxtapose_same_size_mp4_h264_macos() {
         codec: Codec::H264,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = juxtapose(&left_video, &right_video, &options, None);
@@ -166,6 +313,7 @@ fn test_juxtapose_same_size_mp4_h264_windows() {
         codec: Codec::H264,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = juxtapose(&left_video, &right_video, &options, None);
@@ -205,6 +353,7 @@ fn test_juxtapose_different_size_webm_av1() {
         codec: Codec::Av1,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     // Use a custom background color
@@ -252,6 +401,7 @@ fn test_juxtapose_different_size_mp4_h264_macos() {
         codec: Codec::H264,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     // Use a custom background color
@@ -295,6 +445,7 @@ fn test_juxtapose_different_size_mp4_h264_windows() {
         codec: Codec::H264,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     let bg = Color {
@@ -341,6 +492,7 @@ fn test_juxtapose_mixed_formats_to_webm_macos() {
         codec: Codec::Av1,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = juxtapose(&left_video, &right_video, &options, None);
@@ -377,6 +529,7 @@ fn test_juxtapose_mixed_formats_to_mp4_macos() {
         codec: Codec::H264,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = juxtapose(&left_video, &right_video, &options, None);
@@ -388,3 +541,26 @@ fn test_juxtapose_mixed_formats_to_mp4_macos() {
     assert!(verify_file_exists_with_size(&output_path));
     assert!(verify_mp4_header(&output_path));
 }
+
+/// Test that animated images are rejected before any input is opened
+#[test]
+fn test_juxtapose_rejects_animated_webp() {
+    let options = EncodeOptions {
+        output_path: "unused.webp".to_string(),
+        container: Container::WebP,
+        ..Default::default()
+    };
+    let result = juxtapose("missing_a.webm", "missing_b.webm", &options, None);
+    assert!(matches!(result, Err(minmpeg::Error::InvalidInput(_))));
+}
+
+/// Test mosaics rejecting more videos than cells before opening any
+#[test]
+fn test_mosaic_too_many_videos() {
+    let paths = ["a.mp4", "b.mp4", "c.mp4", "d.mp4", "e.mp4"];
+    let options = EncodeOptions::default();
+    for (rows, columns) in [(2, 2), (0, 3), (3, 0)] {
+        let err = mosaic(&paths, rows, columns, &options).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput(_)), "{:?}", err);
+    }
+}
@@ -0,0 +1,74 @@
+//! Integration tests for video probing
+
+mod common;
+
+use common::*;
+use minmpeg::{probe, slideshow, Codec, Container, EncodeOptions, SlideEntry, SlideSource};
+use std::process::Command;
+use tempfile::TempDir;
+
+/// Check if ffprobe is available
+fn ffprobe_available() -> bool {
+    Command::new("ffprobe")
+        .arg("-version")
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+/// Test probing a WebM file created by slideshow
+#[test]
+fn test_probe_webm_av1() {
+    if !ffprobe_available() {
+        println!("Skipping test: ffprobe not available");
+        return;
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let image_path = temp_dir.path().join("slide.png");
+    save_png(&generate_numbered_image(160, 120, 0), &image_path).unwrap();
+
+    let entries = vec![SlideEntry {
+        source: SlideSource::Path(image_path.to_string_lossy().to_string()),
+        duration_ms: 1000,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    }];
+
+    let output_path = temp_dir.path().join("output.webm");
+    let options = EncodeOptions {
+        output_path: output_path.to_string_lossy().to_string(),
+        container: Container::WebM,
+        codec: Codec::Av1,
+        ..Default::default()
+    };
+    slideshow(&entries, &options).expect("Failed to create test video");
+
+    let info = probe(&output_path, None).expect("Probe failed");
+    assert_eq!(info.width, 160);
+    assert_eq!(info.height, 120);
+    assert_eq!(info.codec, "av1");
+    assert!(info.container.contains("webm"), "{}", info.container);
+    assert!(info.frame_count > 0);
+}
+
+/// Test that probing a missing file fails
+#[test]
+fn test_probe_nonexistent_file() {
+    if !ffprobe_available() {
+        println!("Skipping test: ffprobe not available");
+        return;
+    }
+
+    let result = probe("/nonexistent/video.webm", None);
+    assert!(result.is_err());
+}
+
+/// Test that an invalid ffmpeg path is reported as an error
+#[test]
+fn test_probe_invalid_ffmpeg_path() {
+    let result = probe("video.webm", Some("/nonexistent/ffmpeg"));
+    assert!(result.is_err());
+}
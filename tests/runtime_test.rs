@@ -0,0 +1,61 @@
+//! Integration tests for the process-wide runtime configuration
+//!
+//! The configuration can only be set once per process, so everything is checked in one
+//! test.
+
+mod common;
+
+use common::*;
+use minmpeg::runtime::{self, Config};
+use minmpeg::{
+    available, slideshow_to_memory, Codec, EncodeOptions, Error, SlideEntry, SlideSource,
+};
+use tempfile::TempDir;
+
+#[test]
+fn test_runtime_configuration() {
+    let temp_dir = TempDir::new().unwrap();
+    let spill_dir = TempDir::new().unwrap();
+
+    let config = Config {
+        threads: 2,
+        temp_dir: Some(spill_dir.path().to_path_buf()),
+        ffmpeg_paths: vec!["/nonexistent/ffmpeg".to_string()],
+        hardware: false,
+    };
+    runtime::configure(config.clone()).unwrap();
+    assert_eq!(runtime::config(), &config);
+    assert!(matches!(
+        runtime::configure(Config::default()),
+        Err(Error::InvalidInput(_))
+    ));
+
+    // Neither ffmpeg nor a hardware encoder can provide H.264
+    assert!(available(Codec::H264, None).is_err());
+
+    // Slides are spilled to the temporary directory, so they do not count against the
+    // memory limit
+    let entries: Vec<SlideEntry> = [(160, 120), (320, 240)]
+        .iter()
+        .enumerate()
+        .map(|(i, &(width, height))| {
+            let path = temp_dir.path().join(format!("slide_{}.png", i));
+            save_png(&generate_numbered_image(width, height, i as u32), &path).unwrap();
+            SlideEntry {
+                source: SlideSource::Path(path.to_string_lossy().to_string()),
+                duration_ms: 200,
+                transition: None,
+                letterbox_color: None,
+                caption: None,
+                rotation: None,
+            }
+        })
+        .collect();
+    let options = EncodeOptions {
+        memory_limit: Some(160 * 120 * 4 + 1024 * 1024),
+        ..Default::default()
+    };
+    let output = slideshow_to_memory(&entries, &options).unwrap();
+    assert!(!output.is_empty());
+    assert_eq!(std::fs::read_dir(spill_dir.path()).unwrap().count(), 0);
+}
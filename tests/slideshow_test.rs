@@ -3,9 +3,25 @@
 mod common;
 
 use common::*;
-use minmpeg::{slideshow, Codec, Container, EncodeOptions, SlideEntry};
+use minmpeg::{
+    slideshow, slideshow_batch, slideshow_to_memory, Av1Backend, CancelToken, Codec, Color,
+    ColorSpace, Container, EncodeOptions, Error, FilterChain, FilterFrame, ImageCache, Phase,
+    PixelRange, Priority, ProgressCallback, RateControl, Report, Rotation, ScaleMode, SlideEntry,
+    SlideSource, SlideshowJob, StrictLimits, Transition, TransitionEffect, Violation, Warning,
+    MAX_PSNR,
+};
+use std::process::Command;
 use tempfile::TempDir;
 
+/// Check if ffmpeg is available
+fn ffmpeg_available() -> bool {
+    Command::new("ffmpeg")
+        .arg("-version")
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
 /// Test creating a slideshow with JPEG images
 #[test]
 fn test_slideshow_jpeg_images() {
@@ -25,8 +41,12 @@ fn test_slideshow_jpeg_images() {
     let entries: Vec<SlideEntry> = image_paths
         .iter()
         .map(|path| SlideEntry {
-            path: path.to_string_lossy().to_string(),
+            source: SlideSource::Path(path.to_string_lossy().to_string()),
             duration_ms: 200, // Short duration for fast testing
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
         })
         .collect();
 
@@ -38,6 +58,7 @@ fn test_slideshow_jpeg_images() {
         codec: Codec::Av1,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     // Create slideshow
@@ -78,8 +99,12 @@ fn test_slideshow_png_images() {
     let entries: Vec<SlideEntry> = image_paths
         .iter()
         .map(|path| SlideEntry {
-            path: path.to_string_lossy().to_string(),
+            source: SlideSource::Path(path.to_string_lossy().to_string()),
             duration_ms: 200, // Short duration for fast testing
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
         })
         .collect();
 
@@ -91,6 +116,7 @@ fn test_slideshow_png_images() {
         codec: Codec::Av1,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = slideshow(&entries, &options);
@@ -117,12 +143,20 @@ fn test_slideshow_mixed_formats() {
 
     let entries = vec![
         SlideEntry {
-            path: jpeg_path.to_string_lossy().to_string(),
+            source: SlideSource::Path(jpeg_path.to_string_lossy().to_string()),
             duration_ms: 200,
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
         },
         SlideEntry {
-            path: png_path.to_string_lossy().to_string(),
+            source: SlideSource::Path(png_path.to_string_lossy().to_string()),
             duration_ms: 200,
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
         },
     ];
 
@@ -134,6 +168,7 @@ fn test_slideshow_mixed_formats() {
         codec: Codec::Av1,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = slideshow(&entries, &options);
@@ -163,8 +198,12 @@ fn test_slideshow_different_resolutions() {
     let entries: Vec<SlideEntry> = image_paths
         .iter()
         .map(|path| SlideEntry {
-            path: path.to_string_lossy().to_string(),
+            source: SlideSource::Path(path.to_string_lossy().to_string()),
             duration_ms: 200,
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
         })
         .collect();
 
@@ -176,6 +215,7 @@ fn test_slideshow_different_resolutions() {
         codec: Codec::Av1,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = slideshow(&entries, &options);
@@ -204,8 +244,12 @@ fn test_slideshow_various_durations() {
         .iter()
         .zip(durations.iter())
         .map(|(path, duration)| SlideEntry {
-            path: path.to_string_lossy().to_string(),
+            source: SlideSource::Path(path.to_string_lossy().to_string()),
             duration_ms: *duration,
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
         })
         .collect();
 
@@ -217,6 +261,7 @@ fn test_slideshow_various_durations() {
         codec: Codec::Av1,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = slideshow(&entries, &options);
@@ -225,6 +270,320 @@ fn test_slideshow_various_durations() {
     assert!(verify_file_exists_with_size(&output_path));
 }
 
+/// Test that the progress callback reports every frame up to the total
+#[test]
+fn test_slideshow_progress_callback() {
+    use std::sync::{Arc, Mutex};
+
+    let temp_dir = TempDir::new().unwrap();
+
+    let path = temp_dir.path().join("slide.png");
+    let img = generate_numbered_image(160, 120, 0);
+    save_png(&img, &path).unwrap();
+
+    let entries = vec![SlideEntry {
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
+        duration_ms: 200,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    }];
+
+    let output_path = temp_dir.path().join("output.webm");
+
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let reports_clone = Arc::clone(&reports);
+
+    let options = EncodeOptions {
+        output_path: output_path.to_string_lossy().to_string(),
+        container: Container::WebM,
+        codec: Codec::Av1,
+        quality: 50,
+        ffmpeg_path: None,
+        progress: Some(ProgressCallback::new(move |p| {
+            reports_clone.lock().unwrap().push(p);
+        })),
+        ..Default::default()
+    };
+
+    let result = slideshow(&entries, &options);
+    assert!(result.is_ok(), "Slideshow creation failed: {:?}", result);
+
+    let reports = reports.lock().unwrap();
+    // 200ms at 30fps = 6 frames, then finishing the output
+    assert_eq!(reports.len(), 7);
+    for (i, p) in reports[..6].iter().enumerate() {
+        assert_eq!(p.frames_done, i as u64 + 1);
+        assert_eq!(p.frames_total, 6);
+        assert_eq!(p.phase, Phase::Encoding);
+    }
+    assert_eq!(reports[6].phase, Phase::Finishing);
+    assert_eq!(reports[6].frames_done, 6);
+}
+
+/// Test that a cancelled token stops the slideshow with `Error::Cancelled`
+#[test]
+fn test_slideshow_cancelled() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let path = temp_dir.path().join("slide.png");
+    let img = generate_numbered_image(160, 120, 0);
+    save_png(&img, &path).unwrap();
+
+    let entries = vec![SlideEntry {
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
+        duration_ms: 1000,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    }];
+
+    let output_path = temp_dir.path().join("output.webm");
+
+    // Cancel from the progress callback after the first frame
+    let cancel = CancelToken::new();
+    let cancel_clone = cancel.clone();
+
+    let options = EncodeOptions {
+        output_path: output_path.to_string_lossy().to_string(),
+        container: Container::WebM,
+        codec: Codec::Av1,
+        quality: 50,
+        ffmpeg_path: None,
+        progress: Some(ProgressCallback::new(move |_| cancel_clone.cancel())),
+        cancel: Some(cancel),
+        warning: None,
+        strict: None,
+        memory_limit: None,
+        encoder_pool: None,
+        image_cache: None,
+        deterministic: false,
+        verify: false,
+        spill_dir: None,
+        priority: Priority::Normal,
+        threads: None,
+        filters: FilterChain::new(),
+        av1_backend: Av1Backend::Rav1e,
+        scale_mode: ScaleMode::Stretch,
+        letterbox_color: Color { r: 0, g: 0, b: 0 },
+        watermark: None,
+        fade_in_ms: 0,
+        fade_out_ms: 0,
+        fade_color: Color { r: 0, g: 0, b: 0 },
+        bitrate_kbps: None,
+        rate_control: RateControl::Auto,
+        keyframe_interval_ms: None,
+        color_space: ColorSpace::Bt601,
+        pixel_range: PixelRange::Auto,
+    };
+
+    let result = slideshow(&entries, &options);
+    assert!(
+        matches!(result, Err(Error::Cancelled)),
+        "Expected cancellation, got: {:?}",
+        result
+    );
+}
+
+/// Test that a target bitrate takes over from quality
+#[test]
+fn test_slideshow_bitrate() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let entries: Vec<SlideEntry> = (0..3)
+        .map(|i| {
+            let path = temp_dir.path().join(format!("slide{}.png", i));
+            save_png(&generate_numbered_image(64, 48, i), &path).unwrap();
+            SlideEntry {
+                source: SlideSource::Path(path.to_string_lossy().to_string()),
+                duration_ms: 100,
+                transition: None,
+                letterbox_color: None,
+                caption: None,
+                rotation: None,
+            }
+        })
+        .collect();
+
+    let size = |bitrate_kbps| {
+        let options = EncodeOptions {
+            container: Container::WebM,
+            codec: Codec::Av1,
+            quality: 50,
+            bitrate_kbps,
+            ..Default::default()
+        };
+        slideshow_to_memory(&entries, &options)
+            .expect("In-memory slideshow failed")
+            .len()
+    };
+    let (low, high) = (size(Some(10)), size(Some(2000)));
+    assert!(
+        low < high,
+        "{} bytes at 10 kbps, {} at 2000 kbps",
+        low,
+        high
+    );
+}
+
+/// Test that the color space is signalled in the container
+#[test]
+fn test_slideshow_color_space() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let path = temp_dir.path().join("slide.png");
+    save_png(&generate_numbered_image(64, 48, 0), &path).unwrap();
+    let entries = [SlideEntry {
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
+        duration_ms: 100,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    }];
+
+    // Colour element with MatrixCoefficients, TransferCharacteristics, Primaries and Range
+    let colour = |color_space, pixel_range, code, range| {
+        let options = EncodeOptions {
+            container: Container::WebM,
+            codec: Codec::Av1,
+            color_space,
+            pixel_range,
+            ..Default::default()
+        };
+        let data = slideshow_to_memory(&entries, &options).expect("In-memory slideshow failed");
+        let element = [
+            0x55, 0xB0, 0x90, 0x55, 0xB1, 0x81, code, 0x55, 0xBA, 0x81, code, 0x55, 0xBB, 0x81,
+            code, 0x55, 0xB9, 0x81, range,
+        ];
+        data.windows(element.len()).any(|w| w == element)
+    };
+    assert!(colour(ColorSpace::Bt601, PixelRange::Auto, 6, 1));
+    assert!(colour(ColorSpace::Bt709, PixelRange::Full, 1, 2));
+}
+
+/// Test that a constant bitrate needs a bitrate to hold
+#[test]
+fn test_slideshow_rate_control() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let path = temp_dir.path().join("slide.png");
+    save_png(&generate_numbered_image(64, 48, 0), &path).unwrap();
+    let entries = vec![SlideEntry {
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
+        duration_ms: 100,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    }];
+
+    let options = EncodeOptions {
+        container: Container::WebM,
+        codec: Codec::Av1,
+        rate_control: RateControl::Constant,
+        ..Default::default()
+    };
+    assert!(matches!(
+        slideshow_to_memory(&entries, &options),
+        Err(Error::InvalidInput(_))
+    ));
+
+    let options = EncodeOptions {
+        bitrate_kbps: Some(200),
+        ..options
+    };
+    let data = slideshow_to_memory(&entries, &options).expect("Constant bitrate failed");
+    assert_eq!(&data[..4], &[0x1A, 0x45, 0xDF, 0xA3]);
+}
+
+/// Test creating a slideshow in memory
+#[test]
+fn test_slideshow_to_memory() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let path = temp_dir.path().join("slide.png");
+    let img = generate_numbered_image(160, 120, 0);
+    save_png(&img, &path).unwrap();
+
+    let entries = vec![SlideEntry {
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
+        duration_ms: 200,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    }];
+
+    let options = EncodeOptions {
+        container: Container::WebM,
+        codec: Codec::Av1,
+        ..Default::default()
+    };
+
+    let data = slideshow_to_memory(&entries, &options).expect("In-memory slideshow failed");
+
+    // WebM starts with EBML header: 0x1A 0x45 0xDF 0xA3
+    assert!(data.len() > 100);
+    assert_eq!(&data[..4], &[0x1A, 0x45, 0xDF, 0xA3]);
+
+    // Nothing should be written to disk
+    assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+}
+
+/// Test that odd sizes, resized slides and short durations are reported as warnings
+#[test]
+fn test_slideshow_report_warnings() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let sizes_and_durations = [(81, 60, 10), (64, 48, 100)];
+    let entries: Vec<SlideEntry> = sizes_and_durations
+        .iter()
+        .enumerate()
+        .map(|(i, &(width, height, duration_ms))| {
+            let path = temp_dir.path().join(format!("slide_{}.png", i));
+            save_png(&generate_numbered_image(width, height, i as u32), &path).unwrap();
+            SlideEntry {
+                source: SlideSource::Path(path.to_string_lossy().to_string()),
+                duration_ms,
+                transition: None,
+                letterbox_color: None,
+                caption: None,
+                rotation: None,
+            }
+        })
+        .collect();
+
+    let report = Report::new();
+    let options = EncodeOptions {
+        warning: Some(report.callback()),
+        ..Default::default()
+    };
+
+    slideshow_to_memory(&entries, &options).expect("In-memory slideshow failed");
+
+    assert_eq!(
+        report.warnings(),
+        vec![
+            Warning::DimensionsRounded {
+                width: 81,
+                height: 60
+            },
+            Warning::DurationRounded {
+                index: 0,
+                duration_ms: 10
+            },
+            Warning::ImageResized {
+                index: 1,
+                width: 64,
+                height: 48
+            },
+        ]
+    );
+}
+
 /// Test slideshow with empty entries (should fail)
 #[test]
 fn test_slideshow_empty_entries() {
@@ -237,6 +596,7 @@ fn test_slideshow_empty_entries() {
         codec: Codec::Av1,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = slideshow(&[], &options);
@@ -250,8 +610,12 @@ fn test_slideshow_nonexistent_image() {
     let output_path = temp_dir.path().join("output.webm");
 
     let entries = vec![SlideEntry {
-        path: "/nonexistent/path/image.jpg".to_string(),
+        source: SlideSource::Path("/nonexistent/path/image.jpg".to_string()),
         duration_ms: 1000,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
     }];
 
     let options = EncodeOptions {
@@ -260,6 +624,7 @@ fn test_slideshow_nonexistent_image() {
         codec: Codec::Av1,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = slideshow(&entries, &options);
@@ -277,8 +642,12 @@ fn test_slideshow_quality_settings() {
     save_png(&img, &path).unwrap();
 
     let entries = vec![SlideEntry {
-        path: path.to_string_lossy().to_string(),
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
         duration_ms: 500,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
     }];
 
     // Test different quality levels
@@ -291,6 +660,7 @@ fn test_slideshow_quality_settings() {
             codec: Codec::Av1,
             quality,
             ffmpeg_path: None,
+            ..Default::default()
         };
 
         let result = slideshow(&entries, &options);
@@ -314,8 +684,12 @@ fn test_slideshow_container_codec_mismatch() {
     save_png(&img, &path).unwrap();
 
     let entries = vec![SlideEntry {
-        path: path.to_string_lossy().to_string(),
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
         duration_ms: 500,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
     }];
 
     let output_path = temp_dir.path().join("output.webm");
@@ -327,6 +701,7 @@ fn test_slideshow_container_codec_mismatch() {
         codec: Codec::H264,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = slideshow(&entries, &options);
@@ -344,8 +719,12 @@ fn test_slideshow_large_resolution() {
     save_png(&img, &path).unwrap();
 
     let entries = vec![SlideEntry {
-        path: path.to_string_lossy().to_string(),
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
         duration_ms: 200,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
     }];
 
     let output_path = temp_dir.path().join("output.webm");
@@ -356,6 +735,7 @@ fn test_slideshow_large_resolution() {
         codec: Codec::Av1,
         quality: 30, // Lower quality for faster encoding
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = slideshow(&entries, &options);
@@ -378,8 +758,12 @@ fn test_slideshow_small_resolution() {
     save_png(&img, &path).unwrap();
 
     let entries = vec![SlideEntry {
-        path: path.to_string_lossy().to_string(),
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
         duration_ms: 500,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
     }];
 
     let output_path = temp_dir.path().join("output.webm");
@@ -390,6 +774,7 @@ fn test_slideshow_small_resolution() {
         codec: Codec::Av1,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = slideshow(&entries, &options);
@@ -419,8 +804,12 @@ fn test_slideshow_mp4_h264_macos() {
     save_png(&img, &path).unwrap();
 
     let entries = vec![SlideEntry {
-        path: path.to_string_lossy().to_string(),
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
         duration_ms: 500,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
     }];
 
     let output_path = temp_dir.path().join("output.mp4");
@@ -431,6 +820,7 @@ fn test_slideshow_mp4_h264_macos() {
         codec: Codec::H264,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = slideshow(&entries, &options);
@@ -457,8 +847,12 @@ fn test_slideshow_mp4_h264_windows() {
     save_png(&img, &path).unwrap();
 
     let entries = vec![SlideEntry {
-        path: path.to_string_lossy().to_string(),
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
         duration_ms: 500,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
     }];
 
     let output_path = temp_dir.path().join("output.mp4");
@@ -469,6 +863,7 @@ fn test_slideshow_mp4_h264_windows() {
         codec: Codec::H264,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = slideshow(&entries, &options);
@@ -503,8 +898,12 @@ fn test_slideshow_mp4_h264_linux() {
     save_png(&img, &path).unwrap();
 
     let entries = vec![SlideEntry {
-        path: path.to_string_lossy().to_string(),
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
         duration_ms: 500,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
     }];
 
     let output_path = temp_dir.path().join("output.mp4");
@@ -515,6 +914,7 @@ fn test_slideshow_mp4_h264_linux() {
         codec: Codec::H264,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = slideshow(&entries, &options);
@@ -530,119 +930,913 @@ fn test_slideshow_mp4_h264_linux() {
     );
 }
 
-/// Test WebM container with AV1 codec (multiple slides to ensure encoding works)
+/// Test WebM container with VP9 codec (requires ffmpeg with libvpx-vp9)
 #[test]
-fn test_slideshow_webm_av1_multiple() {
-    let temp_dir = TempDir::new().unwrap();
+fn test_slideshow_webm_vp9() {
+    use minmpeg::available;
 
-    // Create 5 slides with different colors
-    let image_paths: Vec<_> = (0..5)
-        .map(|i| {
-            let path = temp_dir.path().join(format!("slide_{}.png", i));
-            let img = generate_numbered_image(320, 240, i);
-            save_png(&img, &path).unwrap();
-            path
-        })
-        .collect();
+    if available(Codec::Vp9, None).is_err() {
+        println!("Skipping WebM+VP9 test: ffmpeg with libvpx-vp9 not available");
+        return;
+    }
 
-    let entries: Vec<SlideEntry> = image_paths
-        .iter()
-        .map(|path| SlideEntry {
-            path: path.to_string_lossy().to_string(),
-            duration_ms: 200,
-        })
-        .collect();
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut entries = Vec::new();
+    for i in 0..2 {
+        let path = temp_dir.path().join(format!("slide{}.png", i));
+        let img = generate_numbered_image(320, 240, i);
+        save_png(&img, &path).unwrap();
+        entries.push(SlideEntry {
+            source: SlideSource::Path(path.to_string_lossy().to_string()),
+            duration_ms: 500,
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
+        });
+    }
 
     let output_path = temp_dir.path().join("output.webm");
 
     let options = EncodeOptions {
         output_path: output_path.to_string_lossy().to_string(),
         container: Container::WebM,
-        codec: Codec::Av1,
+        codec: Codec::Vp9,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = slideshow(&entries, &options);
-    assert!(
-        result.is_ok(),
-        "WebM+AV1 multiple slides failed: {:?}",
-        result
-    );
+    assert!(result.is_ok(), "WebM+VP9 slideshow failed: {:?}", result);
     assert!(verify_file_exists_with_size(&output_path));
-    assert!(verify_webm_header(&output_path));
-
-    // Verify file has reasonable size for 5 slides
-    let size = get_file_size(&output_path).unwrap();
     assert!(
-        size > 2000,
-        "Output file too small for 5 slides: {} bytes",
-        size
+        verify_webm_header(&output_path),
+        "Output file is not a valid WebM"
     );
+    let data = std::fs::read(&output_path).unwrap();
+    assert!(data.windows(5).any(|w| w == b"V_VP9"));
 }
 
-/// Test MP4 container with H.264 codec (multiple slides, macOS)
+/// Test MP4 output with AV1, described by an av01 sample entry
 #[test]
-#[cfg(target_os = "macos")]
-fn test_slideshow_mp4_h264_multiple_macos() {
+fn test_slideshow_mp4_av1() {
     let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("slide.png");
+    save_png(&generate_numbered_image(160, 120, 0), &path).unwrap();
+    let entries = vec![SlideEntry {
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
+        duration_ms: 200,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    }];
 
-    // Create 5 slides with different colors
-    let image_paths: Vec<_> = (0..5)
-        .map(|i| {
-            let path = temp_dir.path().join(format!("slide_{}.png", i));
-            let img = generate_numbered_image(320, 240, i);
-            save_png(&img, &path).unwrap();
-            path
-        })
-        .collect();
+    let options = EncodeOptions {
+        container: Container::Mp4,
+        codec: Codec::Av1,
+        ..Default::default()
+    };
 
-    let entries: Vec<SlideEntry> = image_paths
-        .iter()
-        .map(|path| SlideEntry {
-            path: path.to_string_lossy().to_string(),
-            duration_ms: 200,
-        })
-        .collect();
+    let data = slideshow_to_memory(&entries, &options).expect("MP4+AV1 slideshow failed");
+    let size = data.len() as u64;
+    let reader = mp4::Mp4Reader::read_header(std::io::Cursor::new(data), size).unwrap();
+    let track = reader.tracks().get(&1).unwrap();
+    assert_eq!((track.width(), track.height()), (160, 120));
+    // 200 ms at 30 fps
+    assert_eq!(track.sample_count(), 6);
+    let stsd = &track.trak.mdia.minf.stbl.stsd;
+    assert!(stsd.avc1.is_none() && stsd.vp09.is_none());
+}
 
-    let output_path = temp_dir.path().join("output.mp4");
+/// Test Matroska output with AV1, written to memory
+#[test]
+fn test_slideshow_mkv_av1() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let path = temp_dir.path().join("slide.png");
+    save_png(&generate_numbered_image(160, 120, 0), &path).unwrap();
+    let entries = vec![SlideEntry {
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
+        duration_ms: 200,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    }];
+
+    let options = EncodeOptions {
+        container: Container::Mkv,
+        codec: Codec::Av1,
+        ..Default::default()
+    };
+
+    let data = slideshow_to_memory(&entries, &options).expect("MKV slideshow failed");
+    assert_eq!(&data[..4], &[0x1A, 0x45, 0xDF, 0xA3]);
+    assert!(data.windows(8).any(|w| w == b"matroska"));
+    assert!(data.windows(5).any(|w| w == b"V_AV1"));
+}
+
+/// Test Matroska output with H.264, which WebM does not allow
+#[test]
+fn test_slideshow_mkv_h264() {
+    use minmpeg::available;
+
+    if available(Codec::H264, None).is_err() {
+        println!("Skipping MKV+H.264 test: H.264 encoder not available");
+        return;
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut entries = Vec::new();
+    for i in 0..2 {
+        let path = temp_dir.path().join(format!("slide{}.png", i));
+        save_png(&generate_numbered_image(320, 240, i), &path).unwrap();
+        entries.push(SlideEntry {
+            source: SlideSource::Path(path.to_string_lossy().to_string()),
+            duration_ms: 500,
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
+        });
+    }
 
+    let output_path = temp_dir.path().join("output.mkv");
     let options = EncodeOptions {
         output_path: output_path.to_string_lossy().to_string(),
-        container: Container::Mp4,
+        container: Container::Mkv,
         codec: Codec::H264,
-        quality: 50,
-        ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = slideshow(&entries, &options);
-    assert!(
-        result.is_ok(),
-        "MP4+H.264 multiple slides failed on macOS: {:?}",
-        result
-    );
-    assert!(verify_file_exists_with_size(&output_path));
-    assert!(verify_mp4_header(&output_path));
-
-    // Verify file has reasonable size for 5 slides
-    let size = get_file_size(&output_path).unwrap();
-    assert!(
-        size > 2000,
-        "Output file too small for 5 slides: {} bytes",
-        size
-    );
+    assert!(result.is_ok(), "MKV+H.264 slideshow failed: {:?}", result);
+    let data = std::fs::read(&output_path).unwrap();
+    assert!(data.windows(15).any(|w| w == b"V_MPEG4/ISO/AVC"));
+    // CodecPrivate holding the AVC decoder configuration
+    assert!(data.windows(2).any(|w| w == [0x63, 0xA2]));
 }
 
-/// Test MP4 container with H.264 codec (multiple slides, Windows)
+/// Test IVF output, AV1 frames with no container
 #[test]
-#[cfg(target_os = "windows")]
-fn test_slideshow_mp4_h264_multiple_windows() {
+fn test_slideshow_ivf_av1() {
     let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("slide.png");
+    save_png(&generate_numbered_image(160, 120, 0), &path).unwrap();
+    let entries = vec![SlideEntry {
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
+        duration_ms: 200,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    }];
 
-    // Create 5 slides with different colors
-    let image_paths: Vec<_> = (0..5)
-        .map(|i| {
-            let path = temp_dir.path().join(format!("slide_{}.png", i));
+    let options = EncodeOptions {
+        container: Container::Ivf,
+        codec: Codec::Av1,
+        ..Default::default()
+    };
+
+    let data = slideshow_to_memory(&entries, &options).expect("IVF slideshow failed");
+    assert_eq!(&data[..4], b"DKIF");
+    assert_eq!(&data[8..12], b"AV01");
+    assert_eq!(data[12..16], [160, 0, 120, 0]);
+
+    // Walk the frames to the end of the file
+    let frame_count = u32::from_le_bytes(data[24..28].try_into().unwrap());
+    let mut offset = 32;
+    for _ in 0..frame_count {
+        let size = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+        offset += 12 + size as usize;
+    }
+    assert!(frame_count > 0);
+    assert_eq!(offset, data.len());
+}
+
+/// Test a raw H.264 elementary stream, which starts with a delimiter and the SPS
+#[test]
+fn test_slideshow_annex_b() {
+    use minmpeg::available;
+
+    if available(Codec::H264, None).is_err() {
+        println!("Skipping Annex B test: H.264 encoder not available");
+        return;
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("slide.png");
+    save_png(&generate_numbered_image(320, 240, 0), &path).unwrap();
+    let entries = vec![SlideEntry {
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
+        duration_ms: 500,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    }];
+
+    let options = EncodeOptions {
+        container: Container::AnnexB,
+        codec: Codec::H264,
+        ..Default::default()
+    };
+
+    let data = slideshow_to_memory(&entries, &options).expect("Annex B slideshow failed");
+    assert_eq!(data[..6], [0, 0, 0, 1, 0x09, 0xf0]);
+    assert_eq!(data[6..10], [0, 0, 0, 1]);
+    assert_eq!(data[10] & 0x1f, 7);
+}
+
+/// Test Y4M output, uncompressed frames that need no encoder
+#[test]
+fn test_slideshow_y4m() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("slide.png");
+    save_png(&generate_numbered_image(160, 120, 0), &path).unwrap();
+    let entries = vec![SlideEntry {
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
+        duration_ms: 100,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    }];
+
+    // The codec is ignored, even where its encoder is unavailable
+    let options = EncodeOptions {
+        container: Container::Y4m,
+        codec: Codec::H264,
+        ..Default::default()
+    };
+
+    let data = slideshow_to_memory(&entries, &options).expect("Y4M slideshow failed");
+    let header = b"YUV4MPEG2 W160 H120 F30:1 Ip A1:1 C420jpeg XCOLORRANGE=FULL\n";
+    assert!(data.starts_with(header));
+    // 100 ms at 30 fps, each frame a FRAME line and 160x120 I420 pixels
+    let frame_size = b"FRAME\n".len() + 160 * 120 * 3 / 2;
+    assert_eq!(data.len(), header.len() + 3 * frame_size);
+    assert!(data[header.len()..].starts_with(b"FRAME\n"));
+}
+
+/// Test that a slideshow converted on a pool of its own threads comes out the same
+#[test]
+fn test_slideshow_threads() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("slide.png");
+    save_png(&generate_numbered_image(64, 48, 1), &path).unwrap();
+    let entries = vec![SlideEntry {
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
+        duration_ms: 100,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    }];
+
+    let encode = |threads| {
+        let options = EncodeOptions {
+            container: Container::Y4m,
+            threads,
+            ..Default::default()
+        };
+        slideshow_to_memory(&entries, &options).expect("Y4M slideshow failed")
+    };
+    assert_eq!(encode(Some(2)), encode(None));
+}
+
+/// Test Y4M output in limited range, with black at luma 16
+#[test]
+fn test_slideshow_y4m_limited_range() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("slide.png");
+    save_png(
+        &image::RgbaImage::from_pixel(64, 48, image::Rgba([0, 0, 0, 255])),
+        &path,
+    )
+    .unwrap();
+    let entries = vec![SlideEntry {
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
+        duration_ms: 100,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    }];
+
+    let options = EncodeOptions {
+        container: Container::Y4m,
+        pixel_range: PixelRange::Limited,
+        ..Default::default()
+    };
+    let data = slideshow_to_memory(&entries, &options).expect("Y4M slideshow failed");
+    let header = b"YUV4MPEG2 W64 H48 F30:1 Ip A1:1 C420jpeg XCOLORRANGE=LIMITED\nFRAME\n";
+    assert!(data.starts_with(header));
+    let frame = &data[header.len()..header.len() + 64 * 48 * 3 / 2];
+    assert!(frame[..64 * 48].iter().all(|&y| y == 16));
+    assert!(frame[64 * 48..].iter().all(|&c| c == 128));
+}
+
+/// Test a wipe into the second slide, drawn over the first frames of the slide
+#[test]
+fn test_slideshow_wipe_transition() {
+    use minmpeg::Direction;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut entries = Vec::new();
+    for (index, (value, duration_ms)) in [(0u8, 100), (255, 200)].into_iter().enumerate() {
+        let path = temp_dir.path().join(format!("slide{}.png", index));
+        save_png(
+            &image::RgbaImage::from_pixel(160, 120, image::Rgba([value, value, value, 255])),
+            &path,
+        )
+        .unwrap();
+        entries.push(SlideEntry {
+            source: SlideSource::Path(path.to_string_lossy().to_string()),
+            duration_ms,
+            transition: Some(Transition::new(
+                TransitionEffect::Wipe,
+                Direction::Right,
+                100,
+            )),
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
+        });
+    }
+
+    let options = EncodeOptions {
+        container: Container::Y4m,
+        codec: Codec::H264,
+        ..Default::default()
+    };
+    let data = slideshow_to_memory(&entries, &options).expect("Slideshow with a wipe failed");
+
+    // The transition takes 3 of the 6 frames of the second slide, not adding any
+    let header_len = data.iter().position(|&b| b == b'\n').unwrap() + 1;
+    let frame_size = b"FRAME\n".len() + 160 * 120 * 3 / 2;
+    assert_eq!(data.len(), header_len + 9 * frame_size);
+    // Columns of the first luma row showing the white slide
+    let white_columns = |frame: usize| {
+        let start = header_len + frame * frame_size + b"FRAME\n".len();
+        data[start..start + 160]
+            .iter()
+            .filter(|&&y| y > 128)
+            .count()
+    };
+    let columns: Vec<usize> = (2..7).map(white_columns).collect();
+    assert_eq!(columns, [0, 40, 80, 120, 160]);
+    // The wipe uncovers the white slide from the left edge
+    let start = header_len + 3 * frame_size + b"FRAME\n".len();
+    assert!(data[start] > 128 && data[start + 159] < 128);
+}
+
+/// Test letterboxing slides of another aspect ratio than the first
+#[test]
+fn test_slideshow_scale_fit() {
+    let temp_dir = TempDir::new().unwrap();
+    let white = Color {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+    let mut entries = Vec::new();
+    // A white 160x120 slide sets the size, the white 160x40 slides are fitted into it,
+    // the last one with white bars of its own
+    for (index, (height, letterbox_color)) in [(120, None), (40, None), (40, Some(white))]
+        .into_iter()
+        .enumerate()
+    {
+        let path = temp_dir.path().join(format!("slide{}.png", index));
+        save_png(
+            &image::RgbaImage::from_pixel(160, height, image::Rgba([255, 255, 255, 255])),
+            &path,
+        )
+        .unwrap();
+        entries.push(SlideEntry {
+            source: SlideSource::Path(path.to_string_lossy().to_string()),
+            duration_ms: 100,
+            transition: None,
+            letterbox_color,
+            caption: None,
+            rotation: None,
+        });
+    }
+
+    let options = EncodeOptions {
+        container: Container::Y4m,
+        codec: Codec::H264,
+        scale_mode: ScaleMode::Fit,
+        letterbox_color: Color {
+            r: 128,
+            g: 128,
+            b: 128,
+        },
+        ..Default::default()
+    };
+    let data = slideshow_to_memory(&entries, &options).expect("Fitted slideshow failed");
+
+    let header_len = data.iter().position(|&b| b == b'\n').unwrap() + 1;
+    let frame_size = b"FRAME\n".len() + 160 * 120 * 3 / 2;
+    // Luma of the first column of the last frame of a slide
+    let column = |frame: usize| -> Vec<u8> {
+        let luma = header_len + frame * frame_size + b"FRAME\n".len();
+        (0..120).map(|y| data[luma + y * 160]).collect()
+    };
+    // Gray bars of the default color above and below the second slide
+    let second = column(5);
+    assert!(second[..30].iter().all(|&y| (120..=136).contains(&y)));
+    assert!(second[45..75].iter().all(|&y| y > 240));
+    assert!(second[90..].iter().all(|&y| (120..=136).contains(&y)));
+    // The last slide's own white bars
+    assert!(column(8).iter().all(|&y| y > 240));
+}
+
+/// Test a caption drawn onto a slide, or rejected by builds without the text feature
+#[test]
+fn test_slideshow_caption() {
+    use minmpeg::Caption;
+
+    let font = "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf";
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("slide.png");
+    save_png(
+        &image::RgbaImage::from_pixel(160, 120, image::Rgba([0, 0, 0, 255])),
+        &path,
+    )
+    .unwrap();
+    let entries = vec![SlideEntry {
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
+        duration_ms: 100,
+        transition: None,
+        letterbox_color: None,
+        caption: Some(Caption::new("Hello", font)),
+        rotation: None,
+    }];
+    let options = EncodeOptions {
+        container: Container::Y4m,
+        codec: Codec::H264,
+        ..Default::default()
+    };
+
+    let result = slideshow_to_memory(&entries, &options);
+    if cfg!(not(feature = "text")) {
+        let err = result.unwrap_err();
+        assert!(matches!(err.inner(), Error::Unsupported(_)), "{:?}", err);
+        assert_eq!(err.slide(), Some(0));
+        return;
+    }
+    if !std::path::Path::new(font).exists() {
+        println!("Skipping caption test: {} not found", font);
+        return;
+    }
+
+    let data = result.expect("Captioned slideshow failed");
+    let header_len = data.iter().position(|&b| b == b'\n').unwrap() + 1;
+    let luma = &data[header_len + b"FRAME\n".len()..][..160 * 120];
+    // White text in the bottom half of the black slide
+    assert!(luma[..160 * 60].iter().all(|&y| y < 16));
+    assert!(luma[160 * 60..].iter().any(|&y| y > 240));
+}
+
+/// Test a watermark drawn over every frame, and a missing watermark image
+#[test]
+fn test_slideshow_watermark() {
+    use minmpeg::{Watermark, WatermarkPosition};
+
+    let temp_dir = TempDir::new().unwrap();
+    let slide_path = temp_dir.path().join("slide.png");
+    save_png(
+        &image::RgbaImage::from_pixel(160, 120, image::Rgba([0, 0, 0, 255])),
+        &slide_path,
+    )
+    .unwrap();
+    let logo_path = temp_dir.path().join("logo.png");
+    save_png(
+        &image::RgbaImage::from_pixel(20, 10, image::Rgba([255, 255, 255, 255])),
+        &logo_path,
+    )
+    .unwrap();
+    let entries = vec![SlideEntry {
+        source: SlideSource::Path(slide_path.to_string_lossy().to_string()),
+        duration_ms: 100,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    }];
+    let options = EncodeOptions {
+        container: Container::Y4m,
+        codec: Codec::H264,
+        watermark: Some(Watermark {
+            margin: 10,
+            ..Watermark::new(logo_path.to_string_lossy())
+        }),
+        ..Default::default()
+    };
+    assert_eq!(
+        options.watermark.as_ref().unwrap().position,
+        WatermarkPosition::BottomRight
+    );
+
+    let data = slideshow_to_memory(&entries, &options).expect("Watermarked slideshow failed");
+    let header_len = data.iter().position(|&b| b == b'\n').unwrap() + 1;
+    let frame_size = b"FRAME\n".len() + 160 * 120 * 3 / 2;
+    let frames = (data.len() - header_len) / frame_size;
+    for frame in 0..frames {
+        let luma = &data[header_len + frame * frame_size + b"FRAME\n".len()..][..160 * 120];
+        // The logo covers columns 130..150 of rows 100..110
+        for y in 0..120 {
+            for x in 0..160 {
+                let inside = (130..150).contains(&x) && (100..110).contains(&y);
+                assert_eq!(luma[y * 160 + x] > 128, inside, "({}, {})", x, y);
+            }
+        }
+    }
+
+    let options = EncodeOptions {
+        watermark: Some(Watermark::new(
+            temp_dir.path().join("missing.png").to_string_lossy(),
+        )),
+        ..options
+    };
+    assert!(slideshow_to_memory(&entries, &options).is_err());
+}
+
+/// Test fades from black at the start and to black at the end of a slideshow
+#[test]
+fn test_slideshow_fade() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("slide.png");
+    save_png(
+        &image::RgbaImage::from_pixel(160, 120, image::Rgba([255, 255, 255, 255])),
+        &path,
+    )
+    .unwrap();
+    let entries = vec![SlideEntry {
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
+        duration_ms: 1000,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    }];
+    let options = EncodeOptions {
+        container: Container::Y4m,
+        codec: Codec::H264,
+        fade_in_ms: 300,
+        fade_out_ms: 300,
+        ..Default::default()
+    };
+    let data = slideshow_to_memory(&entries, &options).expect("Faded slideshow failed");
+
+    let header_len = data.iter().position(|&b| b == b'\n').unwrap() + 1;
+    let frame_size = b"FRAME\n".len() + 160 * 120 * 3 / 2;
+    let frames = (data.len() - header_len) / frame_size;
+    assert_eq!(frames, 30);
+    let luma = |frame: usize| data[header_len + frame * frame_size + b"FRAME\n".len()];
+    // Black, brightening, white, darkening and black again
+    assert!(luma(0) < 16);
+    assert!((64..192).contains(&luma(5)));
+    assert!(luma(15) > 240);
+    assert!((64..192).contains(&luma(24)));
+    assert!(luma(29) < 16);
+}
+
+/// Test slides decoded from image bytes in memory, next to a slide from a file
+#[test]
+fn test_slideshow_from_bytes() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("slide.png");
+    save_png(
+        &image::RgbaImage::from_pixel(160, 120, image::Rgba([0, 0, 0, 255])),
+        &path,
+    )
+    .unwrap();
+    let mut bytes = Vec::new();
+    image::RgbaImage::from_pixel(160, 120, image::Rgba([255, 255, 255, 255]))
+        .write_to(
+            &mut std::io::Cursor::new(&mut bytes),
+            image::ImageFormat::Png,
+        )
+        .unwrap();
+
+    let entry = |source| SlideEntry {
+        source,
+        duration_ms: 100,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    };
+    let entries = vec![
+        entry(SlideSource::Path(path.to_string_lossy().to_string())),
+        entry(SlideSource::Bytes(bytes)),
+    ];
+    let options = EncodeOptions {
+        container: Container::Y4m,
+        codec: Codec::H264,
+        ..Default::default()
+    };
+    let data = slideshow_to_memory(&entries, &options).expect("Slideshow from bytes failed");
+
+    let header_len = data.iter().position(|&b| b == b'\n').unwrap() + 1;
+    let frame_size = b"FRAME\n".len() + 160 * 120 * 3 / 2;
+    let luma = |frame: usize| data[header_len + frame * frame_size + b"FRAME\n".len()];
+    assert!(luma(0) < 16);
+    assert!(luma(3) > 240);
+
+    // Bytes that are not an image fail like an unreadable file
+    let entries = vec![entry(SlideSource::Bytes(b"not an image".to_vec()))];
+    let err = slideshow_to_memory(&entries, &options).unwrap_err();
+    assert_eq!(err.slide(), Some(0));
+}
+
+/// Test slides given as raw RGBA pixels, and pixels not matching their dimensions
+#[test]
+fn test_slideshow_from_rgba() {
+    let entry = |width: u32, height: u32, data: Vec<u8>| SlideEntry {
+        source: SlideSource::Rgba {
+            width,
+            height,
+            data,
+        },
+        duration_ms: 100,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    };
+    // Gray on the left half, white on the right half
+    let pixels: Vec<u8> = (0..120)
+        .flat_map(|_| (0..160).map(|x| if x < 80 { 128 } else { 255 }))
+        .flat_map(|v| [v, v, v, 255])
+        .collect();
+    let options = EncodeOptions {
+        container: Container::Y4m,
+        codec: Codec::H264,
+        ..Default::default()
+    };
+    let data = slideshow_to_memory(&[entry(160, 120, pixels)], &options)
+        .expect("Slideshow from RGBA failed");
+
+    let header_len = data.iter().position(|&b| b == b'\n').unwrap() + 1;
+    assert!(data[..header_len].starts_with(b"YUV4MPEG2 W160 H120"));
+    let luma = &data[header_len + b"FRAME\n".len()..][..160 * 120];
+    assert!((120..=136).contains(&luma[60 * 160 + 10]));
+    assert!(luma[60 * 160 + 150] > 240);
+
+    let err = slideshow_to_memory(&[entry(160, 120, vec![0; 16])], &options).unwrap_err();
+    assert!(matches!(err.inner(), Error::InvalidInput(_)), "{:?}", err);
+    assert_eq!(err.slide(), Some(0));
+}
+
+/// Test a portrait image rotated into a landscape slide
+#[test]
+fn test_slideshow_rotation() {
+    // 120x160, gray on the top half and white on the bottom half
+    let pixels: Vec<u8> = (0..160)
+        .flat_map(|y| (0..120).map(move |_| if y < 80 { 128 } else { 255 }))
+        .flat_map(|v| [v, v, v, 255])
+        .collect();
+    let entries = [SlideEntry {
+        source: SlideSource::Rgba {
+            width: 120,
+            height: 160,
+            data: pixels,
+        },
+        duration_ms: 100,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: Some(Rotation::Rotate90),
+    }];
+    let options = EncodeOptions {
+        container: Container::Y4m,
+        codec: Codec::H264,
+        ..Default::default()
+    };
+    let data = slideshow_to_memory(&entries, &options).expect("Rotated slideshow failed");
+
+    // Turned clockwise, the top half is on the right
+    let header_len = data.iter().position(|&b| b == b'\n').unwrap() + 1;
+    assert!(data[..header_len].starts_with(b"YUV4MPEG2 W160 H120"));
+    let luma = &data[header_len + b"FRAME\n".len()..][..160 * 120];
+    assert!(luma[60 * 160 + 10] > 240);
+    assert!((120..=136).contains(&luma[60 * 160 + 150]));
+}
+
+/// Test HLS output, a playlist with MPEG-TS segments next to it
+#[test]
+fn test_slideshow_hls() {
+    use minmpeg::available;
+
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("slide.png");
+    save_png(&generate_numbered_image(320, 240, 0), &path).unwrap();
+    let entries = vec![SlideEntry {
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
+        duration_ms: 1000,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    }];
+
+    let playlist_path = temp_dir.path().join("video.m3u8");
+    let options = EncodeOptions {
+        output_path: playlist_path.to_string_lossy().to_string(),
+        container: Container::Hls,
+        codec: Codec::H264,
+        ..Default::default()
+    };
+
+    // Several files cannot be returned as bytes
+    let result = slideshow_to_memory(&entries, &options);
+    assert!(matches!(result, Err(Error::InvalidInput(_))));
+
+    if available(Codec::H264, None).is_err() {
+        println!("Skipping HLS test: H.264 encoder not available");
+        return;
+    }
+
+    let stats = slideshow(&entries, &options).expect("HLS slideshow failed");
+    let playlist = std::fs::read_to_string(&playlist_path).unwrap();
+    assert!(playlist.starts_with(
+        "#EXTM3U
+"
+    ));
+    assert!(playlist.contains("video0.ts"));
+    assert!(playlist.ends_with(
+        "#EXT-X-ENDLIST
+"
+    ));
+
+    let segment = std::fs::read(temp_dir.path().join("video0.ts")).unwrap();
+    assert_eq!(segment.len() % 188, 0);
+    assert_eq!(stats.output_bytes, (playlist.len() + segment.len()) as u64);
+}
+
+/// Test animated WebP output, one lossless frame per slide
+#[test]
+fn test_slideshow_animated_webp() {
+    use image::codecs::webp::WebPDecoder;
+    use image::AnimationDecoder;
+
+    let temp_dir = TempDir::new().unwrap();
+
+    // Odd dimensions are kept, and transparency survives
+    let mut entries = Vec::new();
+    for (i, duration_ms) in [(0, 250), (1, 1200)] {
+        let path = temp_dir.path().join(format!("slide{}.png", i));
+        let mut img = generate_numbered_image(33, 17, i);
+        img.put_pixel(0, 0, image::Rgba([10, 20, 30, 0]));
+        save_png(&img, &path).unwrap();
+        entries.push(SlideEntry {
+            source: SlideSource::Path(path.to_string_lossy().to_string()),
+            duration_ms,
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
+        });
+    }
+
+    let options = EncodeOptions {
+        container: Container::WebP,
+        ..Default::default()
+    };
+    let data = slideshow_to_memory(&entries, &options).unwrap();
+    assert_eq!(&data[..4], b"RIFF");
+    assert_eq!(&data[8..12], b"WEBP");
+
+    let frames = WebPDecoder::new(std::io::Cursor::new(data))
+        .unwrap()
+        .into_frames()
+        .collect_frames()
+        .unwrap();
+    assert_eq!(frames.len(), 2);
+    for (frame, duration_ms) in frames.iter().zip([250, 1200]) {
+        assert_eq!(frame.delay().numer_denom_ms(), (duration_ms, 1));
+        let buffer = frame.buffer();
+        assert_eq!(buffer.dimensions(), (33, 17));
+        assert_eq!(buffer.get_pixel(0, 0).0, [10, 20, 30, 0]);
+    }
+    let expected = generate_numbered_image(33, 17, 1);
+    assert_eq!(frames[1].buffer().get_pixel(5, 5), expected.get_pixel(5, 5));
+}
+
+/// Test APNG output, keeping transparency losslessly
+#[test]
+fn test_slideshow_apng() {
+    use image::codecs::png::PngDecoder;
+    use image::AnimationDecoder;
+
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut entries = Vec::new();
+    let mut images = Vec::new();
+    for (i, duration_ms) in [(0, 100), (1, 2500), (2, 40)] {
+        let path = temp_dir.path().join(format!("slide{}.png", i));
+        let mut img = generate_numbered_image(21, 15, i);
+        for x in 0..21 {
+            img.put_pixel(x, 0, image::Rgba([255, 255, 255, (x * 12) as u8]));
+        }
+        save_png(&img, &path).unwrap();
+        entries.push(SlideEntry {
+            source: SlideSource::Path(path.to_string_lossy().to_string()),
+            duration_ms,
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
+        });
+        images.push(img);
+    }
+
+    let output_path = temp_dir.path().join("output.png");
+    let options = EncodeOptions {
+        output_path: output_path.to_string_lossy().to_string(),
+        container: Container::Apng,
+        ..Default::default()
+    };
+    let stats = slideshow(&entries, &options).unwrap();
+    assert_eq!(stats.frames, 3);
+    assert_eq!(stats.duration_ms, 2640);
+    assert_eq!(stats.backend, minmpeg::EncoderBackend::Image);
+
+    let file = std::io::BufReader::new(std::fs::File::open(&output_path).unwrap());
+    let decoder = PngDecoder::new(file).unwrap();
+    assert!(decoder.is_apng().unwrap());
+    let frames = decoder
+        .apng()
+        .unwrap()
+        .into_frames()
+        .collect_frames()
+        .unwrap();
+    assert_eq!(frames.len(), 3);
+    for ((frame, image), duration_ms) in frames.iter().zip(&images).zip([100, 2500, 40]) {
+        let (numerator, denominator) = frame.delay().numer_denom_ms();
+        assert_eq!(numerator / denominator, duration_ms);
+        assert_eq!(frame.buffer(), image);
+    }
+}
+
+/// Test AV1 encoded with SVT-AV1 (requires the svt-av1 feature and ffmpeg with libsvtav1)
+#[test]
+fn test_slideshow_webm_svt_av1() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let path = temp_dir.path().join("slide.png");
+    let img = generate_numbered_image(320, 240, 0);
+    save_png(&img, &path).unwrap();
+
+    let entries = vec![SlideEntry {
+        source: SlideSource::Path(path.to_string_lossy().to_string()),
+        duration_ms: 500,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    }];
+
+    let output_path = temp_dir.path().join("output.webm");
+
+    let options = EncodeOptions {
+        output_path: output_path.to_string_lossy().to_string(),
+        av1_backend: Av1Backend::SvtAv1,
+        ..Default::default()
+    };
+
+    let result = slideshow(&entries, &options);
+    if !cfg!(feature = "svt-av1") {
+        assert!(matches!(result, Err(Error::CodecUnavailable(_))));
+        return;
+    }
+    #[cfg(feature = "svt-av1")]
+    if minmpeg::encoder::svt_av1::check_available(None).is_err() {
+        println!("Skipping SVT-AV1 test: ffmpeg with libsvtav1 not available");
+        return;
+    }
+
+    let stats = result.unwrap();
+    assert_eq!(stats.backend, minmpeg::EncoderBackend::SvtAv1);
+    assert!(verify_webm_header(&output_path));
+}
+
+/// Test WebM container with AV1 codec (multiple slides to ensure encoding works)
+#[test]
+fn test_slideshow_webm_av1_multiple() {
+    let temp_dir = TempDir::new().unwrap();
+
+    // Create 5 slides with different colors
+    let image_paths: Vec<_> = (0..5)
+        .map(|i| {
+            let path = temp_dir.path().join(format!("slide_{}.png", i));
             let img = generate_numbered_image(320, 240, i);
             save_png(&img, &path).unwrap();
             path
@@ -652,8 +1846,69 @@ fn test_slideshow_mp4_h264_multiple_windows() {
     let entries: Vec<SlideEntry> = image_paths
         .iter()
         .map(|path| SlideEntry {
-            path: path.to_string_lossy().to_string(),
+            source: SlideSource::Path(path.to_string_lossy().to_string()),
             duration_ms: 200,
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
+        })
+        .collect();
+
+    let output_path = temp_dir.path().join("output.webm");
+
+    let options = EncodeOptions {
+        output_path: output_path.to_string_lossy().to_string(),
+        container: Container::WebM,
+        codec: Codec::Av1,
+        quality: 50,
+        ffmpeg_path: None,
+        ..Default::default()
+    };
+
+    let result = slideshow(&entries, &options);
+    assert!(
+        result.is_ok(),
+        "WebM+AV1 multiple slides failed: {:?}",
+        result
+    );
+    assert!(verify_file_exists_with_size(&output_path));
+    assert!(verify_webm_header(&output_path));
+
+    // Verify file has reasonable size for 5 slides
+    let size = get_file_size(&output_path).unwrap();
+    assert!(
+        size > 2000,
+        "Output file too small for 5 slides: {} bytes",
+        size
+    );
+}
+
+/// Test MP4 container with H.264 codec (multiple slides, macOS)
+#[test]
+#[cfg(target_os = "macos")]
+fn test_slideshow_mp4_h264_multiple_macos() {
+    let temp_dir = TempDir::new().unwrap();
+
+    // Create 5 slides with different colors
+    let image_paths: Vec<_> = (0..5)
+        .map(|i| {
+            let path = temp_dir.path().join(format!("slide_{}.png", i));
+            let img = generate_numbered_image(320, 240, i);
+            save_png(&img, &path).unwrap();
+            path
+        })
+        .collect();
+
+    let entries: Vec<SlideEntry> = image_paths
+        .iter()
+        .map(|path| SlideEntry {
+            source: SlideSource::Path(path.to_string_lossy().to_string()),
+            duration_ms: 200,
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
         })
         .collect();
 
@@ -665,6 +1920,64 @@ fn test_slideshow_mp4_h264_multiple_windows() {
         codec: Codec::H264,
         quality: 50,
         ffmpeg_path: None,
+        ..Default::default()
+    };
+
+    let result = slideshow(&entries, &options);
+    assert!(
+        result.is_ok(),
+        "MP4+H.264 multiple slides failed on macOS: {:?}",
+        result
+    );
+    assert!(verify_file_exists_with_size(&output_path));
+    assert!(verify_mp4_header(&output_path));
+
+    // Verify file has reasonable size for 5 slides
+    let size = get_file_size(&output_path).unwrap();
+    assert!(
+        size > 2000,
+        "Output file too small for 5 slides: {} bytes",
+        size
+    );
+}
+
+/// Test MP4 container with H.264 codec (multiple slides, Windows)
+#[test]
+#[cfg(target_os = "windows")]
+fn test_slideshow_mp4_h264_multiple_windows() {
+    let temp_dir = TempDir::new().unwrap();
+
+    // Create 5 slides with different colors
+    let image_paths: Vec<_> = (0..5)
+        .map(|i| {
+            let path = temp_dir.path().join(format!("slide_{}.png", i));
+            let img = generate_numbered_image(320, 240, i);
+            save_png(&img, &path).unwrap();
+            path
+        })
+        .collect();
+
+    let entries: Vec<SlideEntry> = image_paths
+        .iter()
+        .map(|path| SlideEntry {
+            source: SlideSource::Path(path.to_string_lossy().to_string()),
+            duration_ms: 200,
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
+        })
+        .collect();
+
+    let output_path = temp_dir.path().join("output.mp4");
+
+    let options = EncodeOptions {
+        output_path: output_path.to_string_lossy().to_string(),
+        container: Container::Mp4,
+        codec: Codec::H264,
+        quality: 50,
+        ffmpeg_path: None,
+        ..Default::default()
     };
 
     let result = slideshow(&entries, &options);
@@ -684,3 +1997,372 @@ fn test_slideshow_mp4_h264_multiple_windows() {
         size
     );
 }
+
+/// Test that strict mode reports every problem before encoding
+#[test]
+fn test_slideshow_strict_violations() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let entries: Vec<SlideEntry> = [(160, 120, 1000), (161, 120, 0)]
+        .iter()
+        .enumerate()
+        .map(|(i, &(width, height, duration_ms))| {
+            let path = temp_dir.path().join(format!("slide_{}.png", i));
+            save_png(&generate_numbered_image(width, height, i as u32), &path).unwrap();
+            SlideEntry {
+                source: SlideSource::Path(path.to_string_lossy().to_string()),
+                duration_ms,
+                transition: None,
+                letterbox_color: None,
+                caption: None,
+                rotation: None,
+            }
+        })
+        .collect();
+
+    let options = EncodeOptions {
+        strict: Some(StrictLimits {
+            max_duration_ms: Some(500),
+        }),
+        progress: Some(ProgressCallback::new(|_| panic!("Encoding started"))),
+        ..Default::default()
+    };
+
+    let result = slideshow_to_memory(&entries, &options);
+    let Err(Error::Validation(violations)) = result else {
+        panic!("Expected validation error, got: {:?}", result);
+    };
+    assert_eq!(
+        violations,
+        vec![
+            Violation::OddDimensions {
+                index: 1,
+                width: 161,
+                height: 120
+            },
+            Violation::ZeroDuration { index: 1 },
+            Violation::DurationTooLong {
+                duration_ms: 1000,
+                max_ms: 500
+            },
+        ]
+    );
+}
+
+/// Test that a memory limit too small for the slides fails while loading
+#[test]
+fn test_slideshow_memory_limit() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let entries: Vec<SlideEntry> = (0..3)
+        .map(|i| {
+            let path = temp_dir.path().join(format!("slide_{}.png", i));
+            save_png(&generate_numbered_image(160, 120, i), &path).unwrap();
+            SlideEntry {
+                source: SlideSource::Path(path.to_string_lossy().to_string()),
+                duration_ms: 100,
+                transition: None,
+                letterbox_color: None,
+                caption: None,
+                rotation: None,
+            }
+        })
+        .collect();
+
+    let options = EncodeOptions {
+        memory_limit: Some(100_000),
+        ..Default::default()
+    };
+
+    let result = slideshow_to_memory(&entries, &options);
+    assert!(
+        matches!(
+            result,
+            Err(Error::MemoryLimit {
+                needed: 153_600,
+                limit: 100_000,
+                ..
+            })
+        ),
+        "Expected memory limit error, got: {:?}",
+        result
+    );
+}
+
+/// Test that a batch runs every job and reports each result in order
+#[test]
+fn test_slideshow_batch() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let logo = temp_dir.path().join("logo.png");
+    save_png(&generate_numbered_image(160, 120, 0), &logo).unwrap();
+    let logo = logo.to_string_lossy().to_string();
+
+    let job = |name: &str, path: &str| SlideshowJob {
+        entries: vec![SlideEntry {
+            source: SlideSource::Path(path.to_string()),
+            duration_ms: 100,
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
+        }],
+        options: EncodeOptions {
+            output_path: temp_dir.path().join(name).to_string_lossy().to_string(),
+            ..Default::default()
+        },
+    };
+    let jobs = vec![
+        job("first.webm", &logo),
+        job("missing.webm", "/nonexistent/path/image.png"),
+        job("second.webm", &logo),
+    ];
+
+    let results = slideshow_batch(&jobs, 2);
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap().frames, 3);
+    assert!(
+        results[1].is_err(),
+        "Missing image should fail its job only"
+    );
+    assert_eq!(results[2].as_ref().unwrap().frames, 3);
+
+    assert!(temp_dir.path().join("first.webm").exists());
+    assert!(!temp_dir.path().join("missing.webm").exists());
+    assert!(temp_dir.path().join("second.webm").exists());
+}
+
+#[test]
+fn test_slideshow_image_cache() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let logo = temp_dir.path().join("logo.png");
+    save_png(&generate_numbered_image(160, 120, 0), &logo).unwrap();
+    let logo = logo.to_string_lossy().to_string();
+
+    let cache = ImageCache::new();
+    let options = EncodeOptions {
+        image_cache: Some(cache.clone()),
+        ..Default::default()
+    };
+    let entries: Vec<SlideEntry> = (0..3)
+        .map(|_| SlideEntry {
+            source: SlideSource::Path(logo.clone()),
+            duration_ms: 100,
+            transition: None,
+            letterbox_color: None,
+            caption: None,
+            rotation: None,
+        })
+        .collect();
+
+    let first = slideshow_to_memory(&entries, &options).unwrap();
+    // Only the decoded logo is cached, it already has the output size
+    assert_eq!(cache.len(), 1);
+
+    let second = slideshow_to_memory(&entries, &options).unwrap();
+    assert_eq!(cache.len(), 1);
+    assert_eq!(first, second);
+}
+
+#[test]
+fn test_slideshow_deterministic() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let entries: Vec<SlideEntry> = (0..2)
+        .map(|i| {
+            let path = temp_dir.path().join(format!("slide_{}.png", i));
+            save_png(&generate_numbered_image(160, 120, i), &path).unwrap();
+            SlideEntry {
+                source: SlideSource::Path(path.to_string_lossy().to_string()),
+                duration_ms: 200,
+                transition: None,
+                letterbox_color: None,
+                caption: None,
+                rotation: None,
+            }
+        })
+        .collect();
+    let options = EncodeOptions {
+        deterministic: true,
+        ..Default::default()
+    };
+
+    let first = slideshow_to_memory(&entries, &options).unwrap();
+    let second = slideshow_to_memory(&entries, &options).unwrap();
+    assert_eq!(
+        first, second,
+        "Identical inputs should give identical bytes"
+    );
+}
+
+#[test]
+fn test_slideshow_verify() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let entries: Vec<SlideEntry> = (0..2)
+        .map(|i| {
+            let path = temp_dir.path().join(format!("slide_{}.png", i));
+            save_png(&generate_numbered_image(160, 120, i), &path).unwrap();
+            SlideEntry {
+                source: SlideSource::Path(path.to_string_lossy().to_string()),
+                duration_ms: 200,
+                transition: None,
+                letterbox_color: None,
+                caption: None,
+                rotation: None,
+            }
+        })
+        .collect();
+    let options = EncodeOptions {
+        output_path: temp_dir
+            .path()
+            .join("verified.webm")
+            .to_string_lossy()
+            .to_string(),
+        quality: 80,
+        verify: true,
+        ..Default::default()
+    };
+
+    // In-memory output is never verified
+    let data = slideshow_to_memory(&entries, &options).unwrap();
+    assert!(!data.is_empty());
+
+    if !ffmpeg_available() {
+        println!("Skipping test: ffmpeg not available");
+        return;
+    }
+
+    let stats = slideshow(&entries, &options).unwrap();
+    let quality = stats.quality.expect("Output should be verified");
+    assert_eq!(quality.frames.len() as u64, stats.frames);
+    assert!(
+        quality.min_psnr > 25.0 && quality.mean_psnr <= MAX_PSNR,
+        "Unexpected PSNR: {:?}",
+        quality
+    );
+    assert!(quality.min_ssim > 0.8, "Unexpected SSIM: {:?}", quality);
+}
+
+#[test]
+fn test_slideshow_spill_dir() {
+    let temp_dir = TempDir::new().unwrap();
+    let spill_dir = TempDir::new().unwrap();
+
+    // The second slide is resized to the first one's size before it is spilled
+    let entries: Vec<SlideEntry> = [(160, 120), (320, 240)]
+        .iter()
+        .enumerate()
+        .map(|(i, &(width, height))| {
+            let path = temp_dir.path().join(format!("slide_{}.png", i));
+            save_png(&generate_numbered_image(width, height, i as u32), &path).unwrap();
+            SlideEntry {
+                source: SlideSource::Path(path.to_string_lossy().to_string()),
+                duration_ms: 200,
+                transition: None,
+                letterbox_color: None,
+                caption: None,
+                rotation: None,
+            }
+        })
+        .collect();
+
+    let in_memory = slideshow_to_memory(&entries, &EncodeOptions::default()).unwrap();
+    let options = EncodeOptions {
+        spill_dir: Some(spill_dir.path().to_string_lossy().to_string()),
+        // Spilled slides are not held against the limit
+        memory_limit: Some(160 * 120 * 4 + 1024 * 1024),
+        ..Default::default()
+    };
+    let spilled = slideshow_to_memory(&entries, &options).unwrap();
+    assert_eq!(spilled, in_memory);
+
+    // The temporary file is removed afterwards
+    assert_eq!(std::fs::read_dir(spill_dir.path()).unwrap().count(), 0);
+
+    let missing = EncodeOptions {
+        spill_dir: Some("/nonexistent/spill".to_string()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        slideshow_to_memory(&entries, &missing),
+        Err(Error::Io(_))
+    ));
+}
+
+#[test]
+fn test_slideshow_low_priority() {
+    let temp_dir = TempDir::new().unwrap();
+    let entries: Vec<SlideEntry> = (0..2)
+        .map(|i| {
+            let path = temp_dir.path().join(format!("slide_{}.png", i));
+            save_png(&generate_numbered_image(64, 48, i), &path).unwrap();
+            SlideEntry {
+                source: SlideSource::Path(path.to_string_lossy().to_string()),
+                duration_ms: 200,
+                transition: None,
+                letterbox_color: None,
+                caption: None,
+                rotation: None,
+            }
+        })
+        .collect();
+
+    let normal = EncodeOptions {
+        deterministic: true,
+        ..Default::default()
+    };
+    let expected = slideshow_to_memory(&entries, &normal).unwrap();
+
+    // Encoding on lowered threads changes nothing in the output
+    for priority in [Priority::Low, Priority::Idle] {
+        let options = EncodeOptions {
+            priority,
+            ..normal.clone()
+        };
+        assert_eq!(slideshow_to_memory(&entries, &options).unwrap(), expected);
+    }
+}
+
+#[test]
+fn test_slideshow_filters() {
+    use std::sync::{Arc, Mutex};
+
+    let temp_dir = TempDir::new().unwrap();
+    let entries: Vec<SlideEntry> = (0..2)
+        .map(|i| {
+            let path = temp_dir.path().join(format!("slide_{}.png", i));
+            save_png(&generate_numbered_image(64, 48, i), &path).unwrap();
+            SlideEntry {
+                source: SlideSource::Path(path.to_string_lossy().to_string()),
+                duration_ms: 200,
+                transition: None,
+                letterbox_color: None,
+                caption: None,
+                rotation: None,
+            }
+        })
+        .collect();
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&seen);
+    let options = EncodeOptions {
+        filters: FilterChain::new().with(move |frame: &mut FilterFrame<'_>| {
+            recorded.lock().unwrap().push(frame.index);
+            frame.data.fill(0);
+            Ok(())
+        }),
+        ..Default::default()
+    };
+    let filtered = slideshow_to_memory(&entries, &options).unwrap();
+    assert_ne!(
+        filtered,
+        slideshow_to_memory(&entries, &EncodeOptions::default()).unwrap()
+    );
+
+    // Every frame is filtered once, whichever segment encodes it
+    let mut seen = seen.lock().unwrap().clone();
+    seen.sort_unstable();
+    assert_eq!(seen, (0..12).collect::<Vec<u64>>());
+}
@@ -0,0 +1,153 @@
+//! Integration tests for composing timelines of clips
+
+mod common;
+
+use common::*;
+use minmpeg::{
+    compose_to_memory, slideshow, Clip, ClipContent, Codec, Color, Container, EncodeOptions, Error,
+    SlideEntry, SlideSource, Timeline, Track,
+};
+use std::process::Command;
+use tempfile::TempDir;
+
+/// Check if ffmpeg is available
+fn ffmpeg_available() -> bool {
+    Command::new("ffmpeg")
+        .arg("-version")
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+fn y4m_options() -> EncodeOptions {
+    EncodeOptions {
+        container: Container::Y4m,
+        codec: Codec::H264,
+        ..Default::default()
+    }
+}
+
+/// Luma planes of the frames of Y4M `data` at 160x120
+fn luma_planes(data: &[u8]) -> Vec<&[u8]> {
+    let header_len = data.iter().position(|&b| b == b'\n').unwrap() + 1;
+    let frame_size = b"FRAME\n".len() + 160 * 120 * 3 / 2;
+    data[header_len..]
+        .chunks_exact(frame_size)
+        .map(|frame| &frame[b"FRAME\n".len()..][..160 * 120])
+        .collect()
+}
+
+/// Test clips on two tracks appearing at their start times, the upper one blended over
+/// the lower one through its transparent pixels
+#[test]
+fn test_compose_tracks() {
+    // Gray on the left half, transparent on the right half
+    let overlay: Vec<u8> = (0..120)
+        .flat_map(|_| (0..160).map(|x| if x < 80 { 255 } else { 0 }))
+        .flat_map(|alpha| [128, 128, 128, alpha])
+        .collect();
+    let timeline = Timeline::new(160, 120)
+        .with_track(Track::new().with(Clip::new(
+            ClipContent::Color(Color {
+                r: 255,
+                g: 255,
+                b: 255,
+            }),
+            500,
+            500,
+        )))
+        .with_track(Track::new().with(Clip::new(
+            ClipContent::Image(SlideSource::Rgba {
+                width: 160,
+                height: 120,
+                data: overlay,
+            }),
+            0,
+            1000,
+        )));
+    assert_eq!(timeline.duration_ms(), 1000);
+
+    let data = compose_to_memory(&timeline, &y4m_options()).expect("Compose failed");
+    let frames = luma_planes(&data);
+    assert_eq!(frames.len(), 30);
+
+    // Before the white clip starts, the background shows on the right
+    assert!((120..=136).contains(&frames[0][60 * 160 + 10]));
+    assert!(frames[0][60 * 160 + 150] < 16);
+    // Afterwards the white clip does
+    assert!((120..=136).contains(&frames[20][60 * 160 + 10]));
+    assert!(frames[20][60 * 160 + 150] > 240);
+}
+
+#[test]
+fn test_compose_invalid_timelines() {
+    let options = y4m_options();
+    let white = ClipContent::Color(Color::default());
+
+    let err = compose_to_memory(&Timeline::new(160, 120), &options).unwrap_err();
+    assert!(matches!(err, Error::InvalidInput(_)), "{:?}", err);
+
+    let no_duration =
+        Timeline::new(160, 120).with_track(Track::new().with(Clip::new(white.clone(), 0, 0)));
+    let err = compose_to_memory(&no_duration, &options).unwrap_err();
+    assert!(matches!(err, Error::InvalidInput(_)), "{:?}", err);
+
+    let odd = Timeline::new(161, 120).with_track(Track::new().with(Clip::new(white, 0, 100)));
+    let err = compose_to_memory(&odd, &options).unwrap_err();
+    assert!(matches!(err, Error::InvalidInput(_)), "{:?}", err);
+
+    let missing = Timeline::new(160, 120).with_track(Track::new().with(Clip::new(
+        ClipContent::Image(SlideSource::Path("/nonexistent/path/image.png".to_string())),
+        0,
+        100,
+    )));
+    let err = compose_to_memory(&missing, &options).unwrap_err();
+    assert!(err.path().is_some(), "{:?}", err);
+}
+
+/// Test a video clip following an image clip, holding its last frame to the end
+#[test]
+fn test_compose_video_clip() {
+    if !ffmpeg_available() {
+        println!("Skipping video clip test: ffmpeg not available");
+        return;
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let image_path = temp_dir.path().join("slide.png");
+    save_png(&generate_numbered_image(160, 120, 1), &image_path).unwrap();
+    let video_path = temp_dir.path().join("video.webm");
+    let entries = vec![SlideEntry {
+        source: SlideSource::Path(image_path.to_string_lossy().to_string()),
+        duration_ms: 300,
+        transition: None,
+        letterbox_color: None,
+        caption: None,
+        rotation: None,
+    }];
+    slideshow(
+        &entries,
+        &EncodeOptions {
+            output_path: video_path.to_string_lossy().to_string(),
+            ..Default::default()
+        },
+    )
+    .expect("Failed to create test video");
+
+    let timeline = Timeline::new(160, 120).with_track(
+        Track::new()
+            .with(Clip::new(ClipContent::Color(Color::default()), 0, 200))
+            .with(Clip::new(
+                ClipContent::Video(video_path.to_string_lossy().to_string()),
+                200,
+                600,
+            )),
+    );
+    let data = compose_to_memory(&timeline, &y4m_options()).expect("Compose failed");
+    let frames = luma_planes(&data);
+    assert_eq!(frames.len(), 24);
+    assert!(frames[0].iter().all(|&y| y > 240));
+    // The numbered image is not all white, before or after the video ends
+    assert!(frames[8].iter().any(|&y| y < 200));
+    assert_eq!(frames[20], frames[23]);
+}
@@ -0,0 +1,157 @@
+//! Integration tests for transcode, trim, concat and thumbnail
+
+mod common;
+
+use common::*;
+use minmpeg::{
+    concat, probe, slideshow, thumbnail_to_file, transcode, trim, Codec, Container, EncodeOptions,
+    SlideEntry, SlideSource,
+};
+use std::process::Command;
+use tempfile::TempDir;
+
+/// Check if ffmpeg is available
+fn ffmpeg_available() -> bool {
+    Command::new("ffmpeg")
+        .arg("-version")
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+/// Create a 1-second WebM test video
+fn create_test_video(temp_dir: &TempDir, name: &str, width: u32, height: u32) -> String {
+    let entries: Vec<SlideEntry> = (0..2)
+        .map(|i| {
+            let path = temp_dir.path().join(format!("{}_{}.png", name, i));
+            save_png(&generate_numbered_image(width, height, i), &path).unwrap();
+            SlideEntry {
+                source: SlideSource::Path(path.to_string_lossy().to_string()),
+                duration_ms: 500,
+                transition: None,
+                letterbox_color: None,
+                caption: None,
+                rotation: None,
+            }
+        })
+        .collect();
+
+    let output_path = temp_dir.path().join(format!("{}.webm", name));
+    let options = webm_options(&output_path.to_string_lossy());
+    slideshow(&entries, &options).expect("Failed to create test video");
+
+    output_path.to_string_lossy().to_string()
+}
+
+fn webm_options(output_path: &str) -> EncodeOptions {
+    EncodeOptions {
+        output_path: output_path.to_string(),
+        container: Container::WebM,
+        codec: Codec::Av1,
+        quality: 50,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_transcode_webm_av1() {
+    if !ffmpeg_available() {
+        println!("Skipping test: ffmpeg not available");
+        return;
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let input = create_test_video(&temp_dir, "input", 160, 120);
+    let output_path = temp_dir.path().join("output.webm");
+
+    let result = transcode(&input, &webm_options(&output_path.to_string_lossy()));
+    assert!(result.is_ok(), "Transcode failed: {:?}", result);
+    assert!(verify_webm_header(&output_path));
+}
+
+#[test]
+fn test_trim_webm_av1() {
+    if !ffmpeg_available() {
+        println!("Skipping test: ffmpeg not available");
+        return;
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let input = create_test_video(&temp_dir, "input", 160, 120);
+    let output_path = temp_dir.path().join("output.webm");
+
+    let result = trim(
+        &input,
+        200,
+        Some(700),
+        &webm_options(&output_path.to_string_lossy()),
+    );
+    assert!(result.is_ok(), "Trim failed: {:?}", result);
+
+    let info = probe(&output_path, None).unwrap();
+    assert!(
+        info.duration_ms < 1000,
+        "Trimmed to {} ms",
+        info.duration_ms
+    );
+}
+
+#[test]
+fn test_trim_empty_range() {
+    if !ffmpeg_available() {
+        println!("Skipping test: ffmpeg not available");
+        return;
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let input = create_test_video(&temp_dir, "input", 160, 120);
+    let output_path = temp_dir.path().join("output.webm");
+
+    let result = trim(
+        &input,
+        5000,
+        None,
+        &webm_options(&output_path.to_string_lossy()),
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_concat_different_sizes_webm_av1() {
+    if !ffmpeg_available() {
+        println!("Skipping test: ffmpeg not available");
+        return;
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let first = create_test_video(&temp_dir, "first", 160, 120);
+    let second = create_test_video(&temp_dir, "second", 120, 120);
+    let output_path = temp_dir.path().join("output.webm");
+
+    let result = concat(
+        &[first, second],
+        &webm_options(&output_path.to_string_lossy()),
+    );
+    assert!(result.is_ok(), "Concat failed: {:?}", result);
+
+    let info = probe(&output_path, None).unwrap();
+    assert_eq!((info.width, info.height), (160, 120));
+}
+
+#[test]
+fn test_thumbnail_png() {
+    if !ffmpeg_available() {
+        println!("Skipping test: ffmpeg not available");
+        return;
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let input = create_test_video(&temp_dir, "input", 160, 120);
+    let output_path = temp_dir.path().join("thumb.png");
+
+    let result = thumbnail_to_file(&input, 600, &output_path, None);
+    assert!(result.is_ok(), "Thumbnail failed: {:?}", result);
+
+    let img = image::open(&output_path).unwrap();
+    assert_eq!((img.width(), img.height()), (160, 120));
+}